@@ -35,8 +35,70 @@ pub type CharacterOffset = u64;
 #[derive(Debug, Clone, Copy)]
 pub struct CharacterRange(u64, u64);
 
+/// The units that the characters of positions are counted in,
+/// this is negotiated with the client on initialization.
+#[derive(Debug, Eq, PartialEq, Copy, Clone, Default)]
+pub enum PositionEncoding {
+    /// UTF-8 code units, i.e. bytes.
+    Utf8,
+    /// UTF-16 code units, the default of the protocol.
+    #[default]
+    Utf16,
+    /// Unicode code points, i.e. Rust `char`s.
+    Utf32,
+}
+
+impl PositionEncoding {
+    /// The name of the encoding in the protocol, e.g. `utf-16`.
+    #[must_use]
+    pub fn as_str(self) -> &'static str {
+        match self {
+            PositionEncoding::Utf8 => "utf-8",
+            PositionEncoding::Utf16 => "utf-16",
+            PositionEncoding::Utf32 => "utf-32",
+        }
+    }
+
+    /// The encoding with the given name in the protocol.
+    #[must_use]
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "utf-8" => Some(PositionEncoding::Utf8),
+            "utf-16" => Some(PositionEncoding::Utf16),
+            "utf-32" => Some(PositionEncoding::Utf32),
+            _ => None,
+        }
+    }
+
+    /// The number of code units of the text in the encoding.
+    #[must_use]
+    pub fn text_len(self, text: &str) -> usize {
+        match self {
+            PositionEncoding::Utf8 => text.len(),
+            _ if text.is_ascii() => text.len(),
+            PositionEncoding::Utf16 => text.chars().map(char::len_utf16).sum(),
+            PositionEncoding::Utf32 => text.chars().count(),
+        }
+    }
+
+    fn char_len(self, c: char) -> usize {
+        match self {
+            PositionEncoding::Utf8 => c.len_utf8(),
+            PositionEncoding::Utf16 => c.len_utf16(),
+            PositionEncoding::Utf32 => 1,
+        }
+    }
+}
+
 /// A mapper that translates offset:length bytes to
 /// 1-based line:row characters.
+///
+/// The characters are counted in the [encoding](PositionEncoding)
+/// of the mapper, positions inside a character have no offset.
+///
+/// Only `\n` starts a new line, a `\r` that is not followed
+/// by `\n` is an ordinary character on its line, just like
+/// the parser treats it.
 #[derive(Debug, Clone)]
 pub struct Mapper {
     /// Mapping offsets to positions.
@@ -45,6 +107,8 @@ pub struct Mapper {
     /// Mapping positions to offsets.
     position_to_offset: BTreeMap<Position, TextSize>,
 
+    encoding: PositionEncoding,
+
     /// Line count.
     lines: usize,
 
@@ -56,16 +120,33 @@ impl Mapper {
     /// Creates a new Mapper that remembers where
     /// each line starts and ends.
     ///
+    /// Uses the character sizes of the given encoding for positions.
+    #[must_use]
+    pub fn new(source: &str, encoding: PositionEncoding, one_based: bool) -> Self {
+        Self::new_impl(source, encoding, if one_based { 1 } else { 0 })
+    }
+
     /// Uses UTF-16 character sizes for positions.
     #[must_use]
     pub fn new_utf16(source: &str, one_based: bool) -> Self {
-        Self::new_impl(source, true, if one_based { 1 } else { 0 })
+        Self::new(source, PositionEncoding::Utf16, one_based)
     }
 
-    /// Uses UTF-8 character sizes for positions.
+    /// Uses UTF-8 character sizes (bytes) for positions.
     #[must_use]
     pub fn new_utf8(source: &str, one_based: bool) -> Self {
-        Self::new_impl(source, false, if one_based { 1 } else { 0 })
+        Self::new(source, PositionEncoding::Utf8, one_based)
+    }
+
+    /// Uses UTF-32 character sizes (code points) for positions.
+    #[must_use]
+    pub fn new_utf32(source: &str, one_based: bool) -> Self {
+        Self::new(source, PositionEncoding::Utf32, one_based)
+    }
+
+    #[must_use]
+    pub fn encoding(&self) -> PositionEncoding {
+        self.encoding
     }
 
     #[must_use]
@@ -111,7 +192,7 @@ impl Mapper {
         }
     }
 
-    fn new_impl(source: &str, utf16: bool, base: u64) -> Self {
+    fn new_impl(source: &str, encoding: PositionEncoding, base: u64) -> Self {
         let mut offset_to_position = BTreeMap::new();
         let mut position_to_offset = BTreeMap::new();
 
@@ -122,16 +203,17 @@ impl Mapper {
         for c in source.chars() {
             let new_offset = last_offset + c.len_utf8();
 
-            let character_size = if utf16 { c.len_utf16() } else { 1 };
+            let character_size = encoding.char_len(c);
 
             offset_to_position.extend(
                 (last_offset..new_offset)
                     .map(|b| (TextSize::from(b as u32), Position { line, character })),
             );
 
-            position_to_offset.extend(
-                (last_offset..new_offset)
-                    .map(|b| (Position { line, character }, TextSize::from(b as u32))),
+            // A position is at the first byte of its character.
+            position_to_offset.insert(
+                Position { line, character },
+                TextSize::from(last_offset as u32),
             );
 
             last_offset = new_offset;
@@ -157,6 +239,7 @@ impl Mapper {
         Self {
             offset_to_position,
             position_to_offset,
+            encoding,
             lines: line as usize,
             end: Position { line, character },
         }
@@ -271,3 +354,123 @@ line-3"#;
             }
     );
 }
+
+#[cfg(test)]
+#[test]
+fn test_mapper_bom() {
+    let s = "\u{FEFF}a = 1\nb = 2";
+
+    let mapper = Mapper::new_utf16(s, false);
+
+    // The byte order mark is a single UTF-16 code unit.
+    assert_eq!(
+        mapper.position(TextSize::from(3)).unwrap(),
+        Position {
+            line: 0,
+            character: 1
+        }
+    );
+
+    assert_eq!(
+        mapper
+            .offset(Position {
+                line: 1,
+                character: 0
+            })
+            .unwrap(),
+        TextSize::from(9)
+    );
+}
+
+#[cfg(test)]
+#[test]
+fn test_mapper_bare_cr() {
+    let s = "a = 1\r\nb = 2\rc = 3\nd = 4";
+
+    let mapper = Mapper::new_utf16(s, false);
+
+    assert_eq!(mapper.line_count(), 2);
+
+    // The bare `\r` stays on the second line.
+    assert_eq!(
+        mapper.position(TextSize::from(12)).unwrap(),
+        Position::new(1, 5)
+    );
+    assert_eq!(
+        mapper.position(TextSize::from(13)).unwrap(),
+        Position::new(1, 6)
+    );
+    assert_eq!(
+        mapper.offset(Position::new(2, 0)).unwrap(),
+        TextSize::from(19)
+    );
+
+    assert_eq!(
+        mapper.range(TextRange::at(12.into(), 1.into())).unwrap(),
+        Range {
+            start: Position::new(1, 5),
+            end: Position::new(1, 6)
+        }
+    );
+}
+
+#[cfg(test)]
+#[test]
+fn test_mapper_multibyte_offsets() {
+    let s = "é😀a";
+
+    let mapper = Mapper::new_utf16(s, false);
+
+    for (character, offset) in [(0, 0), (1, 2), (3, 6), (4, 7)] {
+        assert_eq!(
+            mapper.offset(Position { line: 0, character }),
+            Some(TextSize::from(offset))
+        );
+    }
+
+    // The second UTF-16 code unit of a surrogate pair.
+    assert_eq!(
+        mapper.offset(Position {
+            line: 0,
+            character: 2
+        }),
+        None
+    );
+}
+
+#[cfg(test)]
+#[test]
+fn test_mapper_encodings() {
+    let s = "a = \"é😀\"\nb = $";
+
+    // The positions of `"` after the emoji and of `$`.
+    for (encoding, quote, invalid) in [
+        (PositionEncoding::Utf8, 11, 4),
+        (PositionEncoding::Utf16, 8, 4),
+        (PositionEncoding::Utf32, 7, 4),
+    ] {
+        let mapper = Mapper::new(s, encoding, false);
+        assert_eq!(mapper.encoding(), encoding);
+
+        assert_eq!(
+            mapper.position(TextSize::from(11)).unwrap(),
+            Position::new(0, quote)
+        );
+        assert_eq!(
+            mapper.offset(Position::new(0, quote)).unwrap(),
+            TextSize::from(11)
+        );
+        assert_eq!(
+            mapper.position(TextSize::from(17)).unwrap(),
+            Position::new(1, invalid)
+        );
+        assert_eq!(encoding.text_len("é😀") as u64, quote - 5);
+    }
+
+    // Positions inside the emoji.
+    assert_eq!(Mapper::new_utf8(s, false).offset(Position::new(0, 8)), None);
+    assert_eq!(
+        Mapper::new_utf8(s, false).position(TextSize::from(8)),
+        Some(Position::new(0, 7))
+    );
+}
@@ -89,13 +89,14 @@ impl<E: Environment> Taplo<E> {
                         Label::secondary((), std_range(required_by.text_ranges().next().unwrap()))
                             .with_message("required by this key"),
                     ])),
-                dom::Error::InvalidEscapeSequence { string } => Diagnostic::error()
+                dom::Error::InvalidEscapeSequence { string, range, .. } => Diagnostic::error()
                     .with_message(error.to_string())
-                    .with_labels(Vec::from([Label::primary(
-                        (),
-                        std_range(string.text_range()),
-                    )
-                    .with_message("the string contains invalid escape sequences")])),
+                    .with_labels(Vec::from([
+                        Label::primary((), std_range(*range))
+                            .with_message("invalid escape sequence"),
+                        Label::secondary((), std_range(string.text_range()))
+                            .with_message("in this string"),
+                    ])),
                 _ => {
                     unreachable!("this is a bug")
                 }
@@ -122,10 +123,10 @@ impl<E: Environment> Taplo<E> {
 
         let mut out_diag = Vec::<u8>::new();
         for err in errors {
-            let msg = err.error.to_string();
-            for text_range in err.node.text_ranges() {
+            let msg = err.message();
+            for text_range in err.text_ranges() {
                 let diag = Diagnostic::error()
-                    .with_message(err.error.to_string())
+                    .with_message(&msg)
                     .with_labels(Vec::from([
                         Label::primary((), std_range(text_range)).with_message(&msg)
                     ]));
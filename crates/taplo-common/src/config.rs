@@ -147,19 +147,7 @@ impl Config {
             .and_then(|s| s.enabled)
             .unwrap_or(true);
 
-        for rule in &self.rule {
-            let rule_matched = match &self.file_rule {
-                Some(r) => r.is_match(path),
-                None => {
-                    tracing::debug!("no file matches were set up");
-                    false
-                }
-            };
-
-            if !rule_matched {
-                continue;
-            }
-
+        for rule in self.rules_for(path) {
             let rule_schema_enabled = rule
                 .options
                 .schema
@@ -7,5 +7,5 @@ pub fn json_to_toml(json: &str, inline: bool) -> Result<String, anyhow::Error> {
 
 pub fn toml_to_json(toml: &str) -> Result<String, anyhow::Error> {
     let root = parse(toml).into_dom();
-    Ok(serde_json::to_string_pretty(&root)?)
+    Ok(serde_json::to_string_pretty(&root.to_json()?)?)
 }
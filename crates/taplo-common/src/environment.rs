@@ -1,15 +1,29 @@
 use async_trait::async_trait;
 use futures::Future;
-use std::path::{Path, PathBuf};
+use std::{
+    path::{Path, PathBuf},
+    time::Duration,
+};
+use thiserror::Error;
 use time::OffsetDateTime;
 use tokio::io::{AsyncRead, AsyncWrite};
 use url::Url;
 
-use crate::util::Normalize;
+use crate::util::{normalize_url, Normalize};
 
 #[cfg(not(target_family = "wasm"))]
 pub mod native;
 
+/// An error of [`Environment::fetch_file`] that is not specific to a request.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Error)]
+pub enum FetchError {
+    /// The environment has no way of fetching remote files.
+    #[error("remote schemas unsupported in this host")]
+    Unsupported,
+    #[error("timeout")]
+    Timeout,
+}
+
 /// An environment in which the operations with Taplo are executed.
 ///
 /// This is mostly needed for sandboxed environments such as WebAssembly.
@@ -54,6 +68,20 @@ pub trait Environment: Clone + Send + Sync + 'static {
 
     async fn find_config_file(&self, from: &Path) -> Option<PathBuf>;
 
+    /// Fetch the contents of a remote (`http` or `https`) URL,
+    /// giving up with [`FetchError::Timeout`] after `timeout`.
+    ///
+    /// Environments that return `None` use the built-in HTTP client instead,
+    /// the ones that cannot fetch files at all return [`FetchError::Unsupported`].
+    async fn fetch_file(
+        &self,
+        url: &Url,
+        timeout: Duration,
+    ) -> Option<Result<Vec<u8>, anyhow::Error>> {
+        let _ = (url, timeout);
+        None
+    }
+
     /// Same as [`Self::glob_files`], but the returned paths are
     /// [normalized](Normalize:normalize) in addition.
     fn glob_files_normalized(&self, glob: &str) -> Result<Vec<PathBuf>, anyhow::Error> {
@@ -70,10 +98,11 @@ pub trait Environment: Clone + Send + Sync + 'static {
         self.cwd().map(Normalize::normalize)
     }
 
-    /// Same as [`Self::to_file_path`], but the returned path is
-    /// [normalized](Normalize:normalize) in addition.
+    /// Same as [`Self::to_file_path`], but the URL is [normalized](normalize_url)
+    /// before and the returned path is [normalized](Normalize:normalize) in addition.
     fn to_file_path_normalized(&self, url: &Url) -> Option<PathBuf> {
-        self.to_file_path(url).map(Normalize::normalize)
+        self.to_file_path(&normalize_url(url))
+            .map(Normalize::normalize)
     }
 
     /// Same as [`Self::find_config_file`], but the returned path is
@@ -15,6 +15,7 @@ pub mod config;
 pub mod convert;
 pub mod environment;
 pub mod log;
+pub mod path_match;
 pub mod schema;
 pub mod util;
 
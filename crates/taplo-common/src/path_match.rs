@@ -0,0 +1,240 @@
+//! Matching documents against the glob patterns of configuration files
+//! and schema associations.
+//!
+//! Both the CLI and the language server match paths with this,
+//! so that a pattern matches the same documents everywhere.
+
+use crate::util::{fold_drive_letter, normalize_str, normalize_url, url_to_path};
+use globset::{Glob, GlobSet, GlobSetBuilder};
+use thiserror::Error;
+use url::Url;
+
+/// An invalid glob pattern.
+#[derive(Debug, Clone, Error)]
+#[error("invalid glob pattern `{pattern}`: {error}")]
+pub struct PatternError {
+    /// The index of the pattern in the patterns given to [`PathMatcher::new`],
+    /// or [`None`] if the patterns are only invalid together
+    /// (e.g. they are too large to be compiled).
+    pub index: Option<usize>,
+    pub pattern: String,
+    pub error: globset::Error,
+}
+
+/// The pattern that matched a path.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MatchInfo {
+    /// The index of the pattern in the patterns given to [`PathMatcher::new`].
+    pub index: usize,
+    /// The number of literal characters in the pattern,
+    /// see [`PathMatcher::matches`].
+    pub specificity: usize,
+}
+
+/// A set of glob patterns that paths are matched against.
+///
+/// Drive letters of Windows paths in the patterns are lowercased,
+/// the same as in [normalized](crate::util::normalize_url) paths.
+#[derive(Debug, Clone)]
+pub struct PathMatcher {
+    set: GlobSet,
+    specificities: Vec<usize>,
+}
+
+impl PathMatcher {
+    /// Compile the given glob patterns.
+    ///
+    /// All invalid patterns are returned.
+    pub fn new(
+        patterns: impl IntoIterator<Item = impl AsRef<str>>,
+    ) -> Result<Self, Vec<PatternError>> {
+        let mut builder = GlobSetBuilder::new();
+        let mut specificities = Vec::new();
+        let mut errors = Vec::new();
+
+        for (index, pattern) in patterns.into_iter().enumerate() {
+            let pattern = fold_drive_letter(pattern.as_ref().into()).into_owned();
+
+            match Glob::new(&pattern) {
+                Ok(glob) => {
+                    builder.add(glob);
+                    specificities.push(specificity(&pattern));
+                }
+                Err(error) => errors.push(PatternError {
+                    index: Some(index),
+                    pattern,
+                    error,
+                }),
+            }
+        }
+
+        if !errors.is_empty() {
+            return Err(errors);
+        }
+
+        let set = builder.build().map_err(|error| {
+            Vec::from([PatternError {
+                index: None,
+                pattern: String::new(),
+                error,
+            }])
+        })?;
+
+        Ok(Self { set, specificities })
+    }
+
+    /// Whether there are no patterns.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.set.is_empty()
+    }
+
+    /// The most specific pattern that matches the path.
+    ///
+    /// A pattern is more specific than an other one if it has more literal
+    /// (not wildcard) characters, e.g. `**/Cargo.toml` is more specific than
+    /// `**/*.toml`. If multiple patterns are equally specific, the last one wins.
+    ///
+    /// The path is expected to be normalized with `/` separators,
+    /// see [`url_match_path`] for documents with URLs.
+    #[must_use]
+    pub fn matches(&self, normalized_path: &str) -> Option<MatchInfo> {
+        self.set
+            .matches(normalized_path)
+            .into_iter()
+            .map(|index| MatchInfo {
+                index,
+                specificity: self.specificities[index],
+            })
+            .max_by_key(|m| m.specificity)
+    }
+}
+
+/// The number of characters in the glob pattern
+/// that are not wildcards or character classes.
+fn specificity(pattern: &str) -> usize {
+    let mut count = 0;
+    let mut in_class = false;
+    let mut escaped = false;
+
+    for c in pattern.chars() {
+        if escaped {
+            escaped = false;
+            count += 1;
+            continue;
+        }
+
+        match c {
+            '\\' => escaped = true,
+            '[' if !in_class => in_class = true,
+            ']' if in_class => in_class = false,
+            _ if in_class => {}
+            '*' | '?' | '{' | '}' | ',' => {}
+            _ => count += 1,
+        }
+    }
+
+    count
+}
+
+/// The text of a document URL that glob patterns are matched against.
+///
+/// Glob patterns typically come from configuration files and are
+/// absolute file paths without a scheme, so for `file` URLs this is the
+/// [normalized path](url_to_path), and the URL without the scheme otherwise.
+#[must_use]
+pub fn url_match_path(url: &Url) -> String {
+    let url = normalize_url(url);
+
+    match url_to_path(&url) {
+        Some(path) => path,
+        None => normalize_str(
+            url.as_str()
+                .strip_prefix(url.scheme())
+                .and_then(|s| s.strip_prefix("://"))
+                .unwrap_or_else(|| url.path()),
+        )
+        .into_owned(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn matched(matcher: &PathMatcher, path: &str) -> Option<usize> {
+        matcher.matches(path).map(|m| m.index)
+    }
+
+    #[test]
+    fn glob_patterns() {
+        let matcher = PathMatcher::new([
+            "**/*.toml",
+            "/home/*/project/**",
+            "*.{json,yaml}",
+            "data/[ab].toml",
+        ])
+        .unwrap();
+
+        assert_eq!(matched(&matcher, "Cargo.toml"), Some(0));
+        assert_eq!(matched(&matcher, "/home/user/Cargo.toml"), Some(0));
+        assert_eq!(matched(&matcher, "/home/user/project/README.md"), Some(1));
+        assert_eq!(matched(&matcher, "schema.yaml"), Some(2));
+        assert_eq!(matched(&matcher, "data/b.toml"), Some(3));
+        assert_eq!(matched(&matcher, "data/c.txt"), None);
+        assert_eq!(matched(&matcher, "/home/user/other/README.md"), None);
+    }
+
+    #[test]
+    fn most_specific_pattern_wins() {
+        let matcher = PathMatcher::new([
+            "**/Cargo.toml",
+            "**/*.toml",
+            "/home/user/project/Cargo.toml",
+            "**/*.toml",
+        ])
+        .unwrap();
+
+        assert_eq!(matched(&matcher, "/home/user/project/Cargo.toml"), Some(2));
+        assert_eq!(matched(&matcher, "/home/user/Cargo.toml"), Some(0));
+
+        // The last one of equally specific patterns.
+        assert_eq!(matched(&matcher, "/home/user/taplo.toml"), Some(3));
+
+        assert!(specificity("**/Cargo.toml") > specificity("**/*.toml"));
+        assert_eq!(specificity("a[bc]d/\\*"), 4);
+    }
+
+    #[test]
+    fn drive_letters() {
+        let matcher = PathMatcher::new(["C:/dir/**/*.toml"]).unwrap();
+        assert!(matcher.matches("c:/dir/sub/a.toml").is_some());
+
+        let url = "file:///C%3A/dir/sub/a.toml".parse().unwrap();
+        assert!(matcher.matches(&url_match_path(&url)).is_some());
+    }
+
+    #[test]
+    fn invalid_patterns() {
+        let errors = PathMatcher::new(["**/*.toml", "a[", "{b", "c"]).unwrap_err();
+
+        assert_eq!(
+            errors.iter().map(|e| e.index).collect::<Vec<_>>(),
+            [Some(1), Some(2)]
+        );
+        assert_eq!(errors[0].pattern, "a[");
+    }
+
+    #[test]
+    fn url_match_paths() {
+        for (url, path) in [
+            ("file:///C:/dir/a.toml", "c:/dir/a.toml"),
+            ("file://localhost/home/a%20b.toml", "/home/a b.toml"),
+            ("file://server/share/a.toml", "//server/share/a.toml"),
+            ("untitled:Untitled-1", "Untitled-1"),
+            ("https://example.com/a.toml", "example.com/a.toml"),
+        ] {
+            assert_eq!(url_match_path(&url.parse().unwrap()), path, "{url}");
+        }
+    }
+}
@@ -1,19 +1,21 @@
-use super::{builtins, cache::Cache};
+use super::{builtins, cache::Cache, fetch_remote};
 use crate::{
     config::Config,
     environment::Environment,
-    util::{normalize_str, GlobRule},
+    path_match::url_match_path,
+    util::{normalize_str, normalize_url, GlobRule},
     IndexMap,
 };
 use anyhow::anyhow;
+use arc_swap::ArcSwap;
 use parking_lot::{RwLock, RwLockReadGuard};
 use regex::Regex;
 use semver::Version;
 use serde::{de::Error, Deserialize, Serialize};
 use serde_json::{json, Value};
-use std::{borrow::Cow, path::Path, sync::Arc};
+use std::{borrow::Cow, path::Path, sync::Arc, time::Duration};
 use tap::Tap;
-use taplo::dom::Node;
+use taplo::dom::{Comment, Node};
 use tokio::sync::Semaphore;
 use url::Url;
 
@@ -44,24 +46,58 @@ pub mod source {
 pub struct SchemaAssociations<E: Environment> {
     concurrent_requests: Arc<Semaphore>,
     http: reqwest::Client,
+    fetch_timeout: Arc<ArcSwap<Duration>>,
     env: E,
     associations: Arc<RwLock<Vec<(AssociationRule, SchemaAssociation)>>>,
     cache: Cache<E>,
 }
 
 impl<E: Environment> SchemaAssociations<E> {
-    pub(crate) fn new(env: E, cache: Cache<E>, http: reqwest::Client) -> Self {
+    pub(crate) fn new(
+        env: E,
+        cache: Cache<E>,
+        http: reqwest::Client,
+        fetch_timeout: Arc<ArcSwap<Duration>>,
+    ) -> Self {
         let this = Self {
             concurrent_requests: Arc::new(Semaphore::new(10)),
             cache,
             env,
             http,
+            fetch_timeout,
             associations: Default::default(),
         };
         this.add_builtins();
         this
     }
 
+    /// An empty set of associations that shares the cache and the HTTP client.
+    ///
+    /// Associations can be collected into it without affecting
+    /// this one, then moved over with [`Self::replace_from`].
+    #[must_use]
+    pub fn detached(&self) -> Self {
+        Self {
+            concurrent_requests: self.concurrent_requests.clone(),
+            http: self.http.clone(),
+            fetch_timeout: self.fetch_timeout.clone(),
+            env: self.env.clone(),
+            associations: Default::default(),
+            cache: self.cache.clone(),
+        }
+    }
+
+    /// Replace the associations from the given sources with the ones
+    /// in `other`, readers never observe a state in between.
+    pub fn replace_from(&self, sources: &[&str], other: &Self) {
+        let new_associations = other.read().clone();
+        let mut associations = self.associations.write();
+        associations.retain(|(_, assoc)| {
+            !sources.contains(&assoc.meta["source"].as_str().unwrap_or_default())
+        });
+        associations.extend(new_associations);
+    }
+
     pub fn add(&self, rule: AssociationRule, assoc: SchemaAssociation) {
         self.associations.write().push((rule, assoc));
     }
@@ -167,8 +203,9 @@ impl<E: Environment> SchemaAssociations<E> {
         Ok(())
     }
 
-    /// Adds the schema from either a directive, or a `$schema` key in the root.
-    pub fn add_from_document(&self, doc_url: &Url, root: &Node) {
+    /// Removes the schemas that were added from the given document
+    /// with [`add_from_document`](Self::add_from_document).
+    pub fn remove_from_document(&self, doc_url: &Url) {
         self.retain(|(rule, assoc)| match rule {
             AssociationRule::Url(u) => {
                 !(u == doc_url
@@ -177,7 +214,13 @@ impl<E: Environment> SchemaAssociations<E> {
             }
             _ => true,
         });
+    }
 
+    /// The first `#:schema` directive in the header comments of the document
+    /// and the schema URL it refers to.
+    ///
+    /// Paths are relative to the document.
+    pub fn schema_directive(&self, doc_url: &Url, root: &Node) -> Option<(Comment, Url)> {
         for comment in root.header_comments() {
             if let Some("schema") = comment.directive() {
                 let value = comment.value();
@@ -212,18 +255,28 @@ impl<E: Environment> SchemaAssociations<E> {
                     }
                 };
 
-                self.associations.write().push((
-                    AssociationRule::Url(doc_url.clone()),
-                    SchemaAssociation {
-                        url: schema_url,
-                        priority: priority::DIRECTIVE,
-                        meta: json!({ "source": source::DIRECTIVE }),
-                    },
-                ));
-                break;
+                return Some((comment, schema_url));
             }
         }
 
+        None
+    }
+
+    /// Adds the schema from either a directive, or a `$schema` key in the root.
+    pub fn add_from_document(&self, doc_url: &Url, root: &Node) {
+        self.remove_from_document(doc_url);
+
+        if let Some((_, schema_url)) = self.schema_directive(doc_url, root) {
+            self.associations.write().push((
+                AssociationRule::Url(doc_url.clone()),
+                SchemaAssociation {
+                    url: schema_url,
+                    priority: priority::DIRECTIVE,
+                    meta: json!({ "source": source::DIRECTIVE }),
+                },
+            ));
+        }
+
         if let Node::Str(s) = root.get("$schema") {
             let schema_url: Url = if s.value().starts_with('.') {
                 match doc_url.join(s.value()) {
@@ -306,14 +359,9 @@ impl<E: Environment> SchemaAssociations<E> {
         self.associations
             .read()
             .iter()
-            .filter_map(|(rule, assoc)| {
-                if rule.is_match(file) {
-                    Some(assoc.clone())
-                } else {
-                    None
-                }
-            })
-            .max_by_key(|assoc| assoc.priority)
+            .filter_map(|(rule, assoc)| rule.specificity(file).map(|s| (s, assoc)))
+            .max_by_key(|(specificity, assoc)| (assoc.priority, *specificity))
+            .map(|(_, assoc)| assoc.clone())
             .tap(|s| {
                 if let Some(schema_association) = s {
                     tracing::debug!(
@@ -360,13 +408,15 @@ impl<E: Environment> SchemaAssociations<E> {
     async fn fetch_external(&self, index_url: &Url) -> Result<SchemaCatalog, anyhow::Error> {
         let _permit = self.concurrent_requests.acquire().await?;
         match index_url.scheme() {
-            "http" | "https" => Ok(self
-                .http
-                .get(index_url.clone())
-                .send()
-                .await?
-                .json()
-                .await?),
+            "http" | "https" => Ok(serde_json::from_slice(
+                &fetch_remote(
+                    &self.env,
+                    &self.http,
+                    index_url,
+                    **self.fetch_timeout.load(),
+                )
+                .await?,
+            )?),
             "file" => Ok(serde_json::from_slice(
                 &self
                     .env
@@ -415,22 +465,22 @@ impl From<GlobRule> for AssociationRule {
 impl AssociationRule {
     #[must_use]
     pub fn is_match(&self, url: &Url) -> bool {
+        self.specificity(url).is_some()
+    }
+
+    /// How specifically the rule matches the URL, or [`None`] if it does not match.
+    ///
+    /// URL rules are the most specific, glob rules are as specific
+    /// as their patterns (see [`PathMatcher::matches`](crate::path_match::PathMatcher::matches)),
+    /// and regular expressions are the least specific.
+    #[must_use]
+    pub fn specificity(&self, url: &Url) -> Option<usize> {
+        let url = normalize_url(url);
+
         match self {
-            // Glob associations typically come from config files
-            // with a glob pattern that is an absolute file path
-            // without a scheme.
-            //
-            // So in order to be a match, we need to
-            // strip the scheme from the URL.
-            AssociationRule::Glob(g) => g.is_match(&*normalize_str(
-                url.as_str()
-                    .strip_prefix(url.scheme())
-                    .unwrap()
-                    .strip_prefix("://")
-                    .unwrap(),
-            )),
-            AssociationRule::Regex(r) => r.is_match(&normalize_str(url.as_str())),
-            AssociationRule::Url(u) => u == url,
+            AssociationRule::Glob(g) => g.matches(&url_match_path(&url)).map(|m| m.specificity),
+            AssociationRule::Regex(r) => r.is_match(&normalize_str(url.as_str())).then_some(0),
+            AssociationRule::Url(u) => (normalize_url(u) == url).then_some(usize::MAX),
         }
     }
 }
@@ -560,3 +610,100 @@ pub struct SchemaAssociation {
     pub url: Url,
     pub priority: usize,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::environment::native::NativeEnvironment;
+
+    #[test]
+    fn match_equivalent_urls() {
+        let rules = [
+            AssociationRule::glob("c:/dir/**/*.toml").unwrap(),
+            AssociationRule::glob("C:/dir/**/*.toml").unwrap(),
+            AssociationRule::regex(r"^file:///c:/dir/.*\.toml$").unwrap(),
+            AssociationRule::Url("file:///C:/dir/sub/a.toml".parse().unwrap()),
+        ];
+
+        let urls = [
+            "file:///C:/dir/sub/a.toml",
+            "file:///c:/dir/sub/a.toml",
+            "file:///c%3A/dir/sub/a.toml",
+            "file:///C%3a/dir/sub/a.toml",
+            "file:///c%3A/dir%5Csub%5Ca.toml",
+            "file://localhost/C:/dir/sub/a.toml",
+        ];
+
+        for rule in &rules {
+            for url in urls {
+                assert!(rule.is_match(&url.parse().unwrap()), "{url}");
+            }
+        }
+    }
+
+    #[test]
+    fn match_unc_urls() {
+        let rule = AssociationRule::glob("//server/share/**/*.toml").unwrap();
+
+        for url in [
+            "file://server/share/dir/a.toml",
+            "file://SERVER/share/dir/a.toml",
+        ] {
+            assert!(rule.is_match(&url.parse().unwrap()), "{url}");
+        }
+
+        assert!(!rule.is_match(&"file:///server/share/dir/a.toml".parse().unwrap()));
+    }
+
+    #[test]
+    fn match_urls_without_path() {
+        let rule = AssociationRule::glob("**/*.toml").unwrap();
+        assert!(!rule.is_match(&"untitled:Untitled-1".parse().unwrap()));
+    }
+
+    #[tokio::test]
+    async fn schema_directive_is_resolved() {
+        const REGEX_SCHEMA_URL: &str = "https://example.com/regex.json";
+
+        let env = NativeEnvironment::new();
+        let associations = SchemaAssociations::new(
+            env.clone(),
+            Cache::new(env),
+            reqwest::Client::new(),
+            Arc::new(ArcSwap::from_pointee(Duration::from_secs(1))),
+        );
+        associations.add(
+            AssociationRule::regex(".*").unwrap(),
+            SchemaAssociation {
+                url: REGEX_SCHEMA_URL.parse().unwrap(),
+                priority: priority::CONFIG,
+                meta: json!({ "source": source::CONFIG }),
+            },
+        );
+
+        let doc_url: Url = "file:///home/project/a.toml".parse().unwrap();
+        let schema_url = |src: &str| {
+            associations.add_from_document(&doc_url, &taplo::parser::parse(src).into_dom());
+            associations
+                .association_for(&doc_url)
+                .map(|assoc| assoc.url.to_string())
+        };
+
+        assert_eq!(
+            schema_url("# Project\n#:schema ./schemas/a.json\n\na = 1\n").as_deref(),
+            Some("file:///home/project/schemas/a.json")
+        );
+        assert_eq!(
+            schema_url("#:schema https://example.com/b.json\n").as_deref(),
+            Some("https://example.com/b.json")
+        );
+
+        // Only directives before the first item are used,
+        // and the association goes away with the directive.
+        assert_eq!(
+            schema_url("a = 1\n#:schema ./schemas/a.json\n").as_deref(),
+            Some(REGEX_SCHEMA_URL)
+        );
+        assert_eq!(schema_url("a = 1\n").as_deref(), Some(REGEX_SCHEMA_URL));
+    }
+}
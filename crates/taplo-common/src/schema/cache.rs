@@ -20,6 +20,7 @@ pub struct Cache<E: Environment> {
     lru_expires_by: Arc<Mutex<OffsetDateTime>>,
     schemas: Arc<Mutex<LruCache<Url, Arc<Value>>>>,
     cache_path: Arc<ArcSwap<Option<PathBuf>>>,
+    invalidated_at: Arc<Mutex<Option<OffsetDateTime>>>,
 }
 
 impl<E: Environment> Cache<E> {
@@ -33,6 +34,7 @@ impl<E: Environment> Cache<E> {
             env,
             schemas: Arc::new(Mutex::new(LruCache::with_hasher(10, ahash::RandomState::new()))),
             cache_path: Default::default(),
+            invalidated_at: Default::default(),
         }
     }
 
@@ -70,7 +72,7 @@ impl<E: Environment> Cache<E> {
                 let p = cache_path.join(file_name);
                 let schema: CachedJson = serde_json::from_slice(&self.env.read_file(&p).await?)?;
 
-                if !include_expired && schema.expires_by < now {
+                if !include_expired && (schema.expires_by < now || self.is_invalidated(&schema)) {
                     return Err(anyhow!("document expired"));
                 }
 
@@ -88,13 +90,15 @@ impl<E: Environment> Cache<E> {
     }
 
     pub async fn save(&self, url: Url, value: Arc<Value>) -> Result<(), anyhow::Error> {
-        let expires_by = self.env.now() + self.expiration_times.load().1;
+        let now = self.env.now();
+        let expires_by = now + self.expiration_times.load().1;
 
         match &**self.cache_path.load() {
             Some(cache_path) => {
                 let file_name = cache_hash(&url);
                 let p = cache_path.join(file_name);
                 let bytes = serde_json::to_vec(&CachedJson {
+                    cached_at: Some(now),
                     expires_by,
                     url,
                     value: (*value).clone(),
@@ -114,6 +118,23 @@ impl<E: Environment> Cache<E> {
         self.expiration_times.store(Arc::new((mem, disk)));
     }
 
+    /// Treat everything that is currently cached as expired.
+    ///
+    /// Expired values are only used if they cannot be loaded again,
+    /// see [`load`](Self::load).
+    pub fn invalidate(&self) {
+        self.schemas.lock().clear();
+        *self.invalidated_at.lock() = Some(self.env.now());
+    }
+
+    fn is_invalidated(&self, cached: &CachedJson) -> bool {
+        match (*self.invalidated_at.lock(), cached.cached_at) {
+            (Some(invalidated_at), Some(cached_at)) => cached_at <= invalidated_at,
+            (Some(_), None) => true,
+            (None, _) => false,
+        }
+    }
+
     /// Reports whether the LRU cache is expired, and also resets
     /// the expiration timer in that case.
     pub fn lru_expired(&self) -> bool {
@@ -129,6 +150,9 @@ impl<E: Environment> Cache<E> {
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct CachedJson {
+    /// Missing in files that were cached by older versions.
+    #[serde(default)]
+    pub cached_at: Option<OffsetDateTime>,
     pub expires_by: OffsetDateTime,
     pub url: Url,
     pub value: Value,
@@ -1,6 +1,11 @@
 use self::{associations::SchemaAssociations, builtins::builtin_schema, cache::Cache};
-use crate::{environment::Environment, util::ArcHashValue, LruCache};
+use crate::{
+    environment::{Environment, FetchError},
+    util::ArcHashValue,
+    HashMap, LruCache,
+};
 use anyhow::{anyhow, Context};
+use arc_swap::ArcSwap;
 use async_recursion::async_recursion;
 use futures::{stream::FuturesUnordered, StreamExt};
 use itertools::Itertools;
@@ -9,8 +14,15 @@ use jsonschema::{error::ValidationErrorKind, JSONSchema, SchemaResolver, Validat
 use parking_lot::Mutex;
 use regex::Regex;
 use serde_json::Value;
-use std::{borrow::Cow, sync::Arc};
-use taplo::dom::{self, node::Key, KeyOrIndex, Keys};
+use std::{borrow::Cow, fmt, sync::Arc, time::Duration};
+use taplo::{
+    dom::{
+        self,
+        node::{ArrayKind, Key},
+        KeyOrIndex, Keys,
+    },
+    rowan::TextRange,
+};
 use thiserror::Error;
 use tokio::sync::Semaphore;
 use url::Url;
@@ -41,26 +53,61 @@ pub mod builtins {
     }
 }
 
+pub const DEFAULT_FETCH_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// The outcome of the last attempt to download a remote schema.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SchemaStatus {
+    Loaded,
+    /// The environment cannot fetch remote files.
+    Unsupported,
+    Failed(String),
+}
+
+impl fmt::Display for SchemaStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SchemaStatus::Loaded => f.write_str("loaded"),
+            SchemaStatus::Unsupported => FetchError::Unsupported.fmt(f),
+            SchemaStatus::Failed(error) => write!(f, "failed to download: {error}"),
+        }
+    }
+}
+
 #[derive(Clone)]
 pub struct Schemas<E: Environment> {
     env: E,
     associations: SchemaAssociations<E>,
     concurrent_requests: Arc<Semaphore>,
     http: reqwest::Client,
+    fetch_timeout: Arc<ArcSwap<Duration>>,
+    status: Arc<Mutex<HashMap<Url, SchemaStatus>>>,
     validators: Arc<Mutex<LruCache<Url, Arc<JSONSchema>>>>,
+    /// Cyclic references and whether they were returned
+    /// from [`Schemas::take_cyclic_references`].
+    cyclic_refs: Arc<Mutex<HashMap<Url, bool>>>,
     cache: Cache<E>,
 }
 
 impl<E: Environment> Schemas<E> {
     pub fn new(env: E, http: reqwest::Client) -> Self {
         let cache = Cache::new(env.clone());
+        let fetch_timeout = Arc::new(ArcSwap::from_pointee(DEFAULT_FETCH_TIMEOUT));
 
         Self {
-            associations: SchemaAssociations::new(env.clone(), cache.clone(), http.clone()),
+            associations: SchemaAssociations::new(
+                env.clone(),
+                cache.clone(),
+                http.clone(),
+                fetch_timeout.clone(),
+            ),
             cache,
             env,
             concurrent_requests: Arc::new(Semaphore::new(10)),
             http,
+            fetch_timeout,
+            status: Default::default(),
+            cyclic_refs: Default::default(),
             validators: Arc::new(Mutex::new(LruCache::with_hasher(
                 3,
                 ahash::RandomState::new(),
@@ -81,6 +128,54 @@ impl<E: Environment> Schemas<E> {
     pub fn env(&self) -> &E {
         &self.env
     }
+
+    /// Set the timeout for downloading remote schemas and catalogs.
+    pub fn set_fetch_timeout(&self, timeout: Duration) {
+        self.fetch_timeout.store(Arc::new(timeout));
+    }
+
+    /// The outcome of the last download of the given remote schema,
+    /// `None` if it was never downloaded.
+    pub fn status(&self, schema_url: &Url) -> Option<SchemaStatus> {
+        self.status.lock().get(schema_url).cloned()
+    }
+
+    /// Load all schemas again instead of using the cached ones.
+    ///
+    /// Previously cached schemas are still used
+    /// if they cannot be loaded again.
+    pub fn reload(&self) {
+        self.cache.invalidate();
+        self.validators.lock().clear();
+        self.status.lock().clear();
+        self.cyclic_refs.lock().clear();
+    }
+
+    /// The cyclic `$ref`s that were found in schemas
+    /// since the last call, every reference is only returned once.
+    ///
+    /// Schemas are not followed any further at cyclic references.
+    pub fn take_cyclic_references(&self) -> Vec<Url> {
+        self.cyclic_refs
+            .lock()
+            .iter_mut()
+            .filter(|(_, taken)| !**taken)
+            .map(|(url, taken)| {
+                *taken = true;
+                url.clone()
+            })
+            .collect()
+    }
+
+    fn cyclic_reference(&self, url: &Url) {
+        self.cyclic_refs
+            .lock()
+            .entry(url.clone())
+            .or_insert_with(|| {
+                tracing::warn!(%url, "cyclic schema reference");
+                false
+            });
+    }
 }
 
 impl<E: Environment> Schemas<E> {
@@ -240,7 +335,16 @@ impl<E: Environment> Schemas<E> {
                 let mut res_url = url.clone();
                 res_url.set_fragment(None);
                 let schema = self.resolve_schema(res_url).await?;
-                let ptr = String::from("/") + fragment;
+
+                if fragment.is_empty() {
+                    return Ok(schema);
+                }
+
+                let ptr = if fragment.starts_with('/') {
+                    fragment.to_string()
+                } else {
+                    String::from("/") + fragment
+                };
                 schema
                     .pointer(&ptr)
                     .map(|v| Arc::new(v.clone()))
@@ -269,13 +373,27 @@ impl<E: Environment> Schemas<E> {
     async fn fetch_external(&self, schema_url: &Url) -> Result<Value, anyhow::Error> {
         let _permit = self.concurrent_requests.acquire().await?;
         match schema_url.scheme() {
-            "http" | "https" => Ok(self
-                .http
-                .get(schema_url.clone())
-                .send()
-                .await?
-                .json()
-                .await?),
+            "http" | "https" => {
+                let result = fetch_remote(
+                    &self.env,
+                    &self.http,
+                    schema_url,
+                    **self.fetch_timeout.load(),
+                )
+                .await
+                .and_then(|bytes| Ok(serde_json::from_slice(&bytes)?));
+
+                let status = match &result {
+                    Ok(_) => SchemaStatus::Loaded,
+                    Err(error) if error.downcast_ref() == Some(&FetchError::Unsupported) => {
+                        SchemaStatus::Unsupported
+                    }
+                    Err(error) => SchemaStatus::Failed(error.to_string()),
+                };
+                self.status.lock().insert(schema_url.clone(), status);
+
+                result
+            }
             "file" => Ok(serde_json::from_slice(
                 &self
                     .env
@@ -292,6 +410,36 @@ impl<E: Environment> Schemas<E> {
     }
 }
 
+/// Download a remote file with the environment if it supports it,
+/// otherwise with the built-in HTTP client.
+pub(crate) async fn fetch_remote<E: Environment>(
+    env: &E,
+    http: &reqwest::Client,
+    url: &Url,
+    timeout: Duration,
+) -> Result<Vec<u8>, anyhow::Error> {
+    if let Some(result) = env.fetch_file(url, timeout).await {
+        return result;
+    }
+
+    let request = http.get(url.clone());
+
+    #[cfg(not(target_arch = "wasm32"))]
+    let request = request.timeout(timeout);
+
+    let bytes = async { request.send().await?.error_for_status()?.bytes().await }
+        .await
+        .map_err(|error| {
+            if error.is_timeout() {
+                anyhow!(FetchError::Timeout)
+            } else {
+                anyhow!(error)
+            }
+        })?;
+
+    Ok(bytes.to_vec())
+}
+
 impl<E: Environment> Schemas<E> {
     #[tracing::instrument(skip_all, fields(%schema_url, %path))]
     pub async fn schemas_at_path(
@@ -308,6 +456,7 @@ impl<E: Environment> Schemas<E> {
             value,
             Keys::empty(),
             path,
+            &[],
             &mut schemas,
         )
         .await?;
@@ -320,9 +469,12 @@ impl<E: Environment> Schemas<E> {
         Ok(schemas)
     }
 
+    /// `refs` are the references that were followed to get to `schema`
+    /// at the current path.
     #[tracing::instrument(skip_all, fields(%path))]
     #[async_recursion(?Send)]
     #[must_use]
+    #[allow(clippy::too_many_arguments)]
     async fn collect_schemas(
         &self,
         root_url: &Url,
@@ -330,6 +482,7 @@ impl<E: Environment> Schemas<E> {
         value: &Value,
         full_path: Keys,
         path: &Keys,
+        refs: &[Url],
         schemas: &mut Vec<(Keys, Arc<Value>)>,
     ) -> Result<(), anyhow::Error> {
         if !schema.is_object() {
@@ -337,43 +490,60 @@ impl<E: Environment> Schemas<E> {
         }
 
         if let Some(r) = schema.schema_ref() {
-            let url = reference_url(root_url, r)
-                .ok_or_else(|| anyhow!("could not determine schema URL"))?;
-            let schema = self.resolve_schema(url).await?;
+            let (url, schema) = self.resolve_ref(root_url, r).await?;
+
+            if refs.contains(&url) {
+                self.cyclic_reference(&url);
+                return Ok(());
+            }
+
+            let mut refs = refs.to_vec();
+            refs.push(url);
             return self
-                .collect_schemas(root_url, &schema, value, full_path.clone(), path, schemas)
+                .collect_schemas(root_url, &schema, value, full_path, path, &refs, schemas)
                 .await;
         }
 
-        if let Some(one_ofs) = schema["oneOf"].as_array() {
-            for one_of in one_ofs {
-                self.collect_schemas(root_url, one_of, value, full_path.clone(), path, schemas)
-                    .await?;
-            }
+        if let Some((schema, refs)) = self.merge_all_of(root_url, schema, refs).await {
+            return self
+                .collect_schemas(root_url, &schema, value, full_path, path, &refs, schemas)
+                .await;
         }
 
-        if let Some(any_ofs) = schema["anyOf"].as_array() {
-            for any_of in any_ofs {
-                self.collect_schemas(root_url, any_of, value, full_path.clone(), path, schemas)
-                    .await?;
+        if let Some(one_ofs) = schema["oneOf"].as_array() {
+            for one_of in self.matching_alternatives(root_url, one_ofs, value).await {
+                self.collect_schemas(
+                    root_url,
+                    one_of,
+                    value,
+                    full_path.clone(),
+                    path,
+                    refs,
+                    schemas,
+                )
+                .await?;
             }
         }
 
-        if let Some(all_ofs) = schema["allOf"].as_array() {
-            for all_of in all_ofs {
-                self.collect_schemas(root_url, all_of, value, full_path.clone(), path, schemas)
-                    .await?;
+        if let Some(any_ofs) = schema["anyOf"].as_array() {
+            for any_of in self.matching_alternatives(root_url, any_ofs, value).await {
+                self.collect_schemas(
+                    root_url,
+                    any_of,
+                    value,
+                    full_path.clone(),
+                    path,
+                    refs,
+                    schemas,
+                )
+                .await?;
             }
         }
 
-        let include_self = schema["allOf"].is_null();
-
         let key = match path.iter().next() {
             Some(k) => k,
             None => {
-                if include_self {
-                    schemas.push((full_path.clone(), Arc::new(schema.clone())));
-                }
+                schemas.push((full_path.clone(), Arc::new(schema.clone())));
                 return Ok(());
             }
         };
@@ -389,46 +559,22 @@ impl<E: Environment> Schemas<E> {
                     value,
                     full_path.join(k.clone()),
                     &child_path,
+                    &[],
                     schemas,
                 )
                 .await?;
 
-                self.collect_schemas(
-                    root_url,
-                    &schema["properties"][k.value()],
-                    &value[k.value()],
-                    full_path.join(k.clone()),
-                    &child_path,
-                    schemas,
-                )
-                .await?;
-
-                self.collect_schemas(
-                    root_url,
-                    &schema["additionalProperties"],
-                    &value[k.value()],
-                    full_path.join(k.clone()),
-                    &child_path,
-                    schemas,
-                )
-                .await?;
-
-                if let Some(pattern_props) = schema["patternProperties"].as_object() {
-                    for (pattern, pattern_schema) in pattern_props {
-                        if let Ok(re) = Regex::new(pattern) {
-                            if re.is_match(k.value()) {
-                                self.collect_schemas(
-                                    root_url,
-                                    pattern_schema,
-                                    &value[k.value()],
-                                    full_path.join(k.clone()),
-                                    &child_path,
-                                    schemas,
-                                )
-                                .await?;
-                            }
-                        }
-                    }
+                for child_schema in property_schemas(schema, k.value()) {
+                    self.collect_schemas(
+                        root_url,
+                        child_schema,
+                        &value[k.value()],
+                        full_path.join(k.clone()),
+                        &child_path,
+                        &[],
+                        schemas,
+                    )
+                    .await?;
                 }
             }
             KeyOrIndex::Index(idx) => {
@@ -439,6 +585,7 @@ impl<E: Environment> Schemas<E> {
                         &value[idx],
                         full_path.join(*idx),
                         &child_path,
+                        &[],
                         schemas,
                     )
                     .await?;
@@ -449,6 +596,7 @@ impl<E: Environment> Schemas<E> {
                         &value[idx],
                         full_path.join(*idx),
                         &child_path,
+                        &[],
                         schemas,
                     )
                     .await?;
@@ -475,9 +623,11 @@ impl<E: Environment> Schemas<E> {
             self.collect_child_schemas(
                 schema_url,
                 &*schema,
+                value_at(value, &path),
                 &path,
                 &Keys::empty(),
                 max_depth,
+                &[],
                 &mut children,
             )
             .await;
@@ -491,6 +641,9 @@ impl<E: Environment> Schemas<E> {
         Ok(children)
     }
 
+    /// `value` is the value at the path of `schema` if it exists, and
+    /// `refs` are the references that were followed to get to `schema`
+    /// at the current path.
     #[async_recursion(?Send)]
     #[must_use]
     #[allow(clippy::too_many_arguments)]
@@ -498,86 +651,63 @@ impl<E: Environment> Schemas<E> {
         &self,
         root_url: &Url,
         schema: &Value,
+        value: &Value,
         root_path: &Keys,
         path: &Keys,
         mut depth: usize,
+        refs: &[Url],
         schemas: &mut Vec<(Keys, Keys, Arc<Value>)>,
     ) {
         if !schema.is_object() || depth == 0 {
             return;
         }
 
-        if let Some(schema) = self.ref_schema_value(root_url, schema).await {
+        if let Some((url, schema)) = self.ref_schema_value(root_url, schema).await {
+            if refs.contains(&url) {
+                self.cyclic_reference(&url);
+                return;
+            }
+
+            let mut refs = refs.to_vec();
+            refs.push(url);
             return self
-                .collect_child_schemas(root_url, &schema, root_path, path, depth, schemas)
+                .collect_child_schemas(
+                    root_url, &schema, value, root_path, path, depth, &refs, schemas,
+                )
                 .await;
         }
 
-        if let Some(one_ofs) = schema["oneOf"].as_array() {
-            for one_of in one_ofs {
-                self.collect_child_schemas(root_url, one_of, root_path, path, depth, schemas)
-                    .await;
-            }
+        if let Some((schema, refs)) = self.merge_all_of(root_url, schema, refs).await {
+            return self
+                .collect_child_schemas(
+                    root_url, &schema, value, root_path, path, depth, &refs, schemas,
+                )
+                .await;
         }
 
-        if let Some(any_ofs) = schema["anyOf"].as_array() {
-            for any_of in any_ofs {
-                self.collect_child_schemas(root_url, any_of, root_path, path, depth, schemas)
-                    .await;
+        if let Some(one_ofs) = schema["oneOf"].as_array() {
+            for one_of in self.matching_alternatives(root_url, one_ofs, value).await {
+                self.collect_child_schemas(
+                    root_url, one_of, value, root_path, path, depth, refs, schemas,
+                )
+                .await;
             }
         }
 
-        // Deal with the { "description": "Foo", "allOf": [{ "$ref": "Bar" }] }
-        // pattern.
-        let composed = [
-            !schema["allOf"].is_null(),
-            !schema["oneOf"].is_null(),
-            !schema["anyOf"].is_null(),
-        ]
-        .into_iter()
-        .filter(|b| *b)
-        .count()
-            == 1
-            && schema["properties"].is_null();
-
-        if let Some(all_ofs) = schema["allOf"].as_array() {
-            if !all_ofs.is_empty() && composed {
-                let mut schema = schema.clone();
-                if let Some(obj) = schema["allOf"].as_object_mut() {
-                    obj.remove("allOf");
-                }
-
-                let mut merged_all_of = Value::Object(serde_json::Map::default());
-
-                for all_of in all_ofs {
-                    merged_all_of.merge(
-                        if let Some(schema) = self.ref_schema_value(root_url, all_of).await {
-                            Arc::try_unwrap(schema).unwrap()
-                        } else {
-                            all_of.clone()
-                        },
-                    );
-                }
-
-                merged_all_of.merge(schema);
-
+        if let Some(any_ofs) = schema["anyOf"].as_array() {
+            for any_of in self.matching_alternatives(root_url, any_ofs, value).await {
                 self.collect_child_schemas(
-                    root_url,
-                    &merged_all_of,
-                    root_path,
-                    path,
-                    depth,
-                    schemas,
+                    root_url, any_of, value, root_path, path, depth, refs, schemas,
                 )
                 .await;
             }
-            // TODO: handle allOfs in regular schemas.
-            // doing so currently will overflow the stack.
         }
 
-        let include_self = !composed;
+        // Schemas that only list alternatives are represented by the alternatives.
+        let composed = (schema["oneOf"].is_null() != schema["anyOf"].is_null())
+            && schema["properties"].is_null();
 
-        if include_self {
+        if !composed {
             schemas.push((
                 root_path.extend(path.clone()),
                 path.clone(),
@@ -592,9 +722,11 @@ impl<E: Environment> Schemas<E> {
                 self.collect_child_schemas(
                     root_url,
                     v,
+                    &value[k.as_str()],
                     root_path,
                     &path.join(Key::from(k)),
                     depth,
+                    &[],
                     schemas,
                 )
                 .await;
@@ -602,39 +734,218 @@ impl<E: Environment> Schemas<E> {
         }
     }
 
-    async fn ref_schema_value(&self, root_url: &Url, schema: &Value) -> Option<Arc<Value>> {
-        if let Some(r) = schema.schema_ref() {
-            let url = match reference_url(root_url, r)
-                .ok_or_else(|| anyhow!("could not determine schema URL"))
-            {
-                Ok(u) => u,
-                Err(error) => {
-                    tracing::error!(?error, "failed to resolve schema");
-                    return None;
-                }
-            };
-            let schema = match self.resolve_schema(url).await {
-                Ok(s) => s,
-                Err(error) => {
-                    tracing::error!(?error, "failed to resolve schema");
-                    return None;
+    /// The schema with the schemas in its `allOf` merged into it, so that
+    /// e.g. all of their properties are collected together, and `refs`
+    /// with the references that were followed to get the schemas.
+    ///
+    /// Returns [`None`] if there is no `allOf` in the schema.
+    async fn merge_all_of(
+        &self,
+        root_url: &Url,
+        schema: &Value,
+        refs: &[Url],
+    ) -> Option<(Value, Vec<Url>)> {
+        let all_ofs = schema["allOf"].as_array().filter(|a| !a.is_empty())?;
+
+        let mut merged_all_of = Value::Object(serde_json::Map::default());
+        let mut all_of_refs = refs.to_vec();
+
+        for all_of in all_ofs {
+            match self.ref_schema_value(root_url, all_of).await {
+                Some((url, _)) if refs.contains(&url) => self.cyclic_reference(&url),
+                Some((url, schema)) => {
+                    all_of_refs.push(url);
+                    merged_all_of.merge((*schema).clone());
                 }
+                None => merged_all_of.merge(all_of.clone()),
+            }
+        }
+
+        let mut schema = schema.clone();
+        if let Some(obj) = schema.as_object_mut() {
+            obj.remove("allOf");
+        }
+        merged_all_of.merge(schema);
+
+        Some((merged_all_of, all_of_refs))
+    }
+
+    /// The alternatives in `oneOf` or `anyOf` that the value can be valid for,
+    /// or all of them if there is no such alternative or no value.
+    ///
+    /// Only the types of the alternatives and the `const` values of their
+    /// properties (e.g. the "kind" of discriminated unions) are checked.
+    async fn matching_alternatives<'s>(
+        &self,
+        root_url: &Url,
+        alternatives: &'s [Value],
+        value: &Value,
+    ) -> Vec<&'s Value> {
+        if value.is_null() {
+            return alternatives.iter().collect();
+        }
+
+        let mut matching = Vec::with_capacity(alternatives.len());
+
+        for alternative in alternatives {
+            let matches = match self.ref_schema_value(root_url, alternative).await {
+                Some((_, schema)) => alternative_matches(&schema, value),
+                None => alternative_matches(alternative, value),
             };
 
-            Some(schema)
+            if matches {
+                matching.push(alternative);
+            }
+        }
+
+        if matching.is_empty() {
+            alternatives.iter().collect()
         } else {
-            None
+            matching
+        }
+    }
+
+    async fn ref_schema_value(&self, root_url: &Url, schema: &Value) -> Option<(Url, Arc<Value>)> {
+        let r = schema.schema_ref()?;
+
+        match self.resolve_ref(root_url, r).await {
+            Ok(resolved) => Some(resolved),
+            Err(error) => {
+                tracing::error!(?error, "failed to resolve schema");
+                None
+            }
         }
     }
+
+    /// Resolve a `$ref` in the document at `root_url`
+    /// and return the URL of the referenced schema along with it.
+    ///
+    /// References in schemas from other documents are made absolute,
+    /// so that they can be resolved without knowing which document
+    /// the schema came from.
+    async fn resolve_ref(
+        &self,
+        root_url: &Url,
+        reference: &str,
+    ) -> Result<(Url, Arc<Value>), anyhow::Error> {
+        let url = reference_url(root_url, reference)
+            .ok_or_else(|| anyhow!("could not determine schema URL"))?;
+        let schema = self.resolve_schema(url.clone()).await?;
+
+        let mut document_url = url.clone();
+        document_url.set_fragment(None);
+        let mut root_document_url = root_url.clone();
+        root_document_url.set_fragment(None);
+
+        if document_url == root_document_url {
+            return Ok((url, schema));
+        }
+
+        let mut schema = (*schema).clone();
+        absolute_references(&mut schema, &document_url);
+        Ok((url, Arc::new(schema)))
+    }
+}
+
+/// The schemas of the property with the given key in an object schema.
+///
+/// Only the first of the following applies, so that the documentation
+/// and extensions come from the most specific schema:
+///
+/// - the schema in `properties`,
+/// - the schemas in `patternProperties` with patterns that match the key,
+/// - the schema in `additionalProperties`.
+fn property_schemas<'s>(schema: &'s Value, key: &str) -> Vec<&'s Value> {
+    if let Some(property) = schema["properties"].get(key) {
+        return vec![property];
+    }
+
+    let pattern_schemas: Vec<&Value> = schema["patternProperties"]
+        .as_object()
+        .into_iter()
+        .flatten()
+        .filter(|(pattern, _)| Regex::new(pattern).is_ok_and(|re| re.is_match(key)))
+        .map(|(_, pattern_schema)| pattern_schema)
+        .collect();
+
+    if !pattern_schemas.is_empty() {
+        return pattern_schemas;
+    }
+
+    vec![&schema["additionalProperties"]]
+}
+
+/// Whether the value can be valid for the schema, see [`Schemas::matching_alternatives`].
+fn alternative_matches(schema: &Value, value: &Value) -> bool {
+    let type_matches = |ty: &str| match ty {
+        "object" => value.is_object(),
+        "array" => value.is_array(),
+        "string" => value.is_string(),
+        "boolean" => value.is_boolean(),
+        "integer" => value.is_i64() || value.is_u64(),
+        "number" => value.is_number(),
+        _ => true,
+    };
+
+    let type_matches = match &schema["type"] {
+        Value::String(ty) => type_matches(ty),
+        Value::Array(tys) => tys.iter().filter_map(Value::as_str).any(type_matches),
+        _ => true,
+    };
+
+    if !type_matches {
+        return false;
+    }
+
+    let (Some(properties), Some(obj)) = (schema["properties"].as_object(), value.as_object())
+    else {
+        return true;
+    };
+
+    properties.iter().all(
+        |(key, property)| match (obj.get(key), property.get("const")) {
+            (Some(value), Some(const_value)) => value == const_value,
+            _ => true,
+        },
+    )
+}
+
+/// The value at the given path, [`Value::Null`] if there is none.
+fn value_at<'v>(value: &'v Value, path: &Keys) -> &'v Value {
+    path.iter().fold(value, |value, key| match key {
+        KeyOrIndex::Key(k) => &value[k.value()],
+        KeyOrIndex::Index(idx) => &value[*idx],
+    })
 }
 
+/// The URL of a `$ref`, relative references
+/// are resolved against the document they are in.
 fn reference_url(root_url: &Url, reference: &str) -> Option<Url> {
-    if !reference.starts_with('#') {
-        return Url::parse(reference).ok();
+    root_url.join(reference).ok()
+}
+
+/// Resolve all `$ref`s in the schema against the given document URL.
+fn absolute_references(schema: &mut Value, document_url: &Url) {
+    match schema {
+        Value::Object(obj) => {
+            for (key, value) in obj.iter_mut() {
+                match value {
+                    Value::String(r) if key == "$ref" => {
+                        if let Some(url) = reference_url(document_url, r) {
+                            *r = url.into();
+                        }
+                    }
+                    _ => absolute_references(value, document_url),
+                }
+            }
+        }
+        Value::Array(arr) => {
+            for value in arr {
+                absolute_references(value, document_url);
+            }
+        }
+        _ => {}
     }
-    let mut url = root_url.clone();
-    url.set_fragment(Some(reference.trim_start_matches("#/")));
-    Some(url)
 }
 
 pub trait ValueExt {
@@ -712,6 +1023,66 @@ impl NodeValidationError {
 
         Ok(Self { keys, node, error })
     }
+
+    /// The ranges the error should be reported at.
+    ///
+    /// Errors of table entries are reported at their keys.
+    ///
+    /// Length errors of arrays of tables are reported at table headers
+    /// instead of the entire array that can span most of the document:
+    /// `maxItems` at the header of every table beyond the limit,
+    /// and `minItems` at the header of the first table.
+    #[must_use]
+    pub fn text_ranges(&self) -> Vec<TextRange> {
+        let headers = self.table_headers();
+
+        match self.error.kind {
+            ValidationErrorKind::MaxItems { limit } if !headers.is_empty() => {
+                return headers
+                    .into_iter()
+                    .skip(usize::try_from(limit).unwrap_or(usize::MAX))
+                    .collect();
+            }
+            ValidationErrorKind::MinItems { .. } if !headers.is_empty() => {
+                return headers.into_iter().take(1).collect();
+            }
+            _ => {}
+        }
+
+        match self.keys.iter().last() {
+            Some(KeyOrIndex::Key(k)) => k.text_ranges().collect(),
+            _ => self.node.text_ranges().collect(),
+        }
+    }
+
+    /// The error message, array of tables length errors
+    /// state the difference instead of the array itself.
+    #[must_use]
+    pub fn message(&self) -> String {
+        let len = self.table_headers().len() as u64;
+        if len == 0 {
+            return self.error.to_string();
+        }
+
+        match self.error.kind {
+            ValidationErrorKind::MaxItems { limit } => format!(
+                "expected at most {limit} tables, found {len} ({} too many)",
+                len.saturating_sub(limit)
+            ),
+            ValidationErrorKind::MinItems { limit } => format!(
+                "expected at least {limit} tables, found {len} ({} more required)",
+                limit.saturating_sub(len)
+            ),
+            _ => self.error.to_string(),
+        }
+    }
+
+    fn table_headers(&self) -> Vec<TextRange> {
+        match &self.node {
+            dom::Node::Array(arr) if arr.kind() == ArrayKind::Tables => arr.table_headers(),
+            _ => Vec::new(),
+        }
+    }
 }
 
 mod formats {
@@ -723,3 +1094,683 @@ mod formats {
         semver::VersionReq::parse(value).is_ok()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::environment::native::NativeEnvironment;
+    use async_trait::async_trait;
+    use std::{
+        path::{Path, PathBuf},
+        sync::atomic::{AtomicUsize, Ordering},
+    };
+    use time::OffsetDateTime;
+
+    type FetchFn = dyn Fn(Duration) -> Result<Vec<u8>, anyhow::Error> + Send + Sync;
+
+    /// A native environment with a stub [`Environment::fetch_file`] hook.
+    #[derive(Clone)]
+    struct FetchEnvironment {
+        native: NativeEnvironment,
+        fetch: Arc<FetchFn>,
+        fetched: Arc<AtomicUsize>,
+    }
+
+    impl FetchEnvironment {
+        fn new(
+            fetch: impl Fn(Duration) -> Result<Vec<u8>, anyhow::Error> + Send + Sync + 'static,
+        ) -> Self {
+            Self {
+                native: NativeEnvironment::new(),
+                fetch: Arc::new(fetch),
+                fetched: Default::default(),
+            }
+        }
+    }
+
+    #[async_trait(?Send)]
+    impl Environment for FetchEnvironment {
+        type Stdin = tokio::io::Stdin;
+        type Stdout = tokio::io::Stdout;
+        type Stderr = tokio::io::Stderr;
+
+        fn now(&self) -> OffsetDateTime {
+            self.native.now()
+        }
+
+        fn spawn<F>(&self, fut: F)
+        where
+            F: futures::Future + Send + 'static,
+            F::Output: Send,
+        {
+            self.native.spawn(fut);
+        }
+
+        fn spawn_local<F>(&self, fut: F)
+        where
+            F: futures::Future + 'static,
+        {
+            self.native.spawn_local(fut);
+        }
+
+        fn env_var(&self, name: &str) -> Option<String> {
+            self.native.env_var(name)
+        }
+
+        fn env_vars(&self) -> Vec<(String, String)> {
+            self.native.env_vars()
+        }
+
+        fn atty_stderr(&self) -> bool {
+            false
+        }
+
+        fn stdin(&self) -> Self::Stdin {
+            self.native.stdin()
+        }
+
+        fn stdout(&self) -> Self::Stdout {
+            self.native.stdout()
+        }
+
+        fn stderr(&self) -> Self::Stderr {
+            self.native.stderr()
+        }
+
+        fn glob_files(&self, glob: &str) -> Result<Vec<PathBuf>, anyhow::Error> {
+            self.native.glob_files(glob)
+        }
+
+        async fn read_file(&self, path: &Path) -> Result<Vec<u8>, anyhow::Error> {
+            self.native.read_file(path).await
+        }
+
+        async fn write_file(&self, path: &Path, bytes: &[u8]) -> Result<(), anyhow::Error> {
+            self.native.write_file(path, bytes).await
+        }
+
+        fn to_file_path(&self, url: &Url) -> Option<PathBuf> {
+            self.native.to_file_path(url)
+        }
+
+        fn is_absolute(&self, path: &Path) -> bool {
+            self.native.is_absolute(path)
+        }
+
+        fn cwd(&self) -> Option<PathBuf> {
+            self.native.cwd()
+        }
+
+        async fn find_config_file(&self, from: &Path) -> Option<PathBuf> {
+            self.native.find_config_file(from).await
+        }
+
+        async fn fetch_file(
+            &self,
+            _url: &Url,
+            timeout: Duration,
+        ) -> Option<Result<Vec<u8>, anyhow::Error>> {
+            self.fetched.fetch_add(1, Ordering::SeqCst);
+            Some((self.fetch)(timeout))
+        }
+    }
+
+    const SCHEMA_URL: &str = "https://example.com/schema.json";
+
+    #[tokio::test]
+    async fn fetch_remote_schema() {
+        let env = FetchEnvironment::new(|_| Ok(br#"{ "type": "object" }"#.to_vec()));
+        let schemas = Schemas::new(env.clone(), reqwest::Client::new());
+        let url: Url = SCHEMA_URL.parse().unwrap();
+
+        assert_eq!(schemas.status(&url), None);
+
+        let schema = schemas.load_schema(&url).await.unwrap();
+        assert_eq!(schema["type"], "object");
+        assert_eq!(schemas.status(&url), Some(SchemaStatus::Loaded));
+
+        // The schema is cached in memory.
+        schemas.load_schema(&url).await.unwrap();
+        assert_eq!(env.fetched.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn fetch_remote_schema_failure() {
+        let env = FetchEnvironment::new(|timeout| {
+            assert_eq!(timeout, Duration::from_secs(3));
+            Err(FetchError::Timeout.into())
+        });
+        let schemas = Schemas::new(env, reqwest::Client::new());
+        schemas.set_fetch_timeout(Duration::from_secs(3));
+        let url: Url = SCHEMA_URL.parse().unwrap();
+
+        assert!(schemas.load_schema(&url).await.is_err());
+
+        let status = schemas.status(&url).unwrap();
+        assert_eq!(status, SchemaStatus::Failed("timeout".into()));
+        assert_eq!(status.to_string(), "failed to download: timeout");
+
+        // Other URLs are not affected.
+        assert_eq!(
+            schemas.status(&"https://example.com/other.json".parse().unwrap()),
+            None
+        );
+    }
+
+    #[tokio::test]
+    async fn fetch_remote_schema_unsupported() {
+        let env = FetchEnvironment::new(|_| Err(FetchError::Unsupported.into()));
+        let schemas = Schemas::new(env, reqwest::Client::new());
+        let url: Url = SCHEMA_URL.parse().unwrap();
+
+        assert!(schemas.load_schema(&url).await.is_err());
+
+        let status = schemas.status(&url).unwrap();
+        assert_eq!(status, SchemaStatus::Unsupported);
+        assert_eq!(
+            status.to_string(),
+            "remote schemas unsupported in this host"
+        );
+    }
+
+    #[tokio::test]
+    async fn external_references() {
+        let dir = std::env::temp_dir().join(format!("taplo-schema-refs-{}", std::process::id()));
+        std::fs::create_dir_all(dir.join("common")).unwrap();
+        std::fs::write(
+            dir.join("a.json"),
+            r##"{
+                "properties": {
+                    "target": { "$ref": "common/b.json#/definitions/target" },
+                    "loop": { "$ref": "common/b.json#/definitions/loop" }
+                },
+                "definitions": {
+                    "name": { "type": "string", "description": "The name." },
+                    "loop": { "$ref": "#/properties/loop" }
+                }
+            }"##,
+        )
+        .unwrap();
+        std::fs::write(
+            dir.join("common/b.json"),
+            r##"{
+                "definitions": {
+                    "target": {
+                        "type": "object",
+                        "properties": { "name": { "$ref": "../a.json#/definitions/name" } }
+                    },
+                    "loop": { "$ref": "../a.json#/definitions/loop" }
+                }
+            }"##,
+        )
+        .unwrap();
+
+        let schemas = Schemas::new(NativeEnvironment::new(), reqwest::Client::new());
+        let url = Url::from_file_path(dir.join("a.json")).unwrap();
+        let value = serde_json::json!({ "target": { "name": "a" }, "loop": {} });
+
+        // References in `b.json` are relative to `b.json`.
+        let found = schemas
+            .schemas_at_path(&url, &value, &"target.name".parse().unwrap())
+            .await
+            .unwrap();
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].1["description"], "The name.");
+
+        let children = schemas
+            .possible_schemas_from(&url, &value, &"target".parse().unwrap(), 2)
+            .await
+            .unwrap();
+        assert!(children
+            .iter()
+            .any(|(_, path, schema)| path.to_string() == "name" && schema["type"] == "string"));
+
+        assert!(schemas.take_cyclic_references().is_empty());
+
+        for _ in 0..2 {
+            let found = schemas
+                .schemas_at_path(&url, &value, &"loop".parse().unwrap())
+                .await
+                .unwrap();
+            assert!(found.is_empty());
+
+            let children = schemas
+                .possible_schemas_from(&url, &value, &"loop".parse().unwrap(), 2)
+                .await
+                .unwrap();
+            assert!(children.is_empty());
+        }
+
+        // Reported only once.
+        assert_eq!(schemas.take_cyclic_references().len(), 1);
+        assert!(schemas.take_cyclic_references().is_empty());
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn reload_schemas() {
+        let cache_path =
+            std::env::temp_dir().join(format!("taplo-schema-reload-{}", std::process::id()));
+        std::fs::create_dir_all(&cache_path).unwrap();
+
+        // The version of the remote schema, 0 if it cannot be downloaded.
+        let version = Arc::new(AtomicUsize::new(1));
+        let env = FetchEnvironment::new({
+            let version = version.clone();
+            move |_| match version.load(Ordering::SeqCst) {
+                0 => Err(FetchError::Timeout.into()),
+                v => Ok(format!(r#"{{ "version": {v} }}"#).into_bytes()),
+            }
+        });
+        let schemas = Schemas::new(env, reqwest::Client::new());
+        schemas.cache().set_cache_path(Some(cache_path.clone()));
+        let url: Url = SCHEMA_URL.parse().unwrap();
+
+        assert_eq!(schemas.load_schema(&url).await.unwrap()["version"], 1);
+
+        version.store(2, Ordering::SeqCst);
+        assert_eq!(schemas.load_schema(&url).await.unwrap()["version"], 1);
+
+        schemas.reload();
+        assert_eq!(schemas.load_schema(&url).await.unwrap()["version"], 2);
+
+        // The cached schema is used if it cannot be downloaded again.
+        version.store(0, Ordering::SeqCst);
+        schemas.reload();
+        assert_eq!(schemas.load_schema(&url).await.unwrap()["version"], 2);
+        assert_eq!(
+            schemas.status(&url),
+            Some(SchemaStatus::Failed("timeout".into()))
+        );
+
+        std::fs::remove_dir_all(cache_path).unwrap();
+    }
+
+    /// Schemas with only `schema` stored in the cache, and its URL.
+    async fn test_schemas(schema: Value) -> (Schemas<NativeEnvironment>, Url) {
+        let schemas = Schemas::new(NativeEnvironment::new(), reqwest::Client::new());
+        let url: Url = "taplo://test-schema.json".parse().unwrap();
+        drop(schemas.cache().store(url.clone(), Arc::new(schema)).await);
+        (schemas, url)
+    }
+
+    /// The paths of the child schemas at `path` and the
+    /// values of the given field of the schemas.
+    async fn child_schemas(
+        schema: Value,
+        value: Value,
+        path: &str,
+        field: &str,
+    ) -> Vec<(String, Value)> {
+        let (schemas, url) = test_schemas(schema).await;
+        schemas
+            .possible_schemas_from(&url, &value, &path.parse().unwrap(), 2)
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|(_, path, schema)| (path.to_string(), schema[field].clone()))
+            .collect()
+    }
+
+    fn descriptions(schemas: &[(Keys, Arc<Value>)]) -> Vec<&str> {
+        schemas
+            .iter()
+            .filter_map(|(_, schema)| schema["description"].as_str())
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn string_or_table_alternatives() {
+        let schema = serde_json::json!({
+            "properties": {
+                "dep": {
+                    "oneOf": [
+                        { "type": "string", "description": "A version." },
+                        {
+                            "type": "object",
+                            "description": "A detailed dependency.",
+                            "properties": {
+                                "version": { "type": "string" },
+                                "path": { "type": "string" }
+                            }
+                        }
+                    ]
+                }
+            }
+        });
+        let (schemas, url) = test_schemas(schema.clone()).await;
+
+        let found = schemas
+            .schemas_at_path(
+                &url,
+                &serde_json::json!({ "dep": "1.0" }),
+                &"dep".parse().unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(descriptions(&found), ["A version."]);
+
+        let found = schemas
+            .schemas_at_path(
+                &url,
+                &serde_json::json!({ "dep": {} }),
+                &"dep".parse().unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(descriptions(&found), ["A detailed dependency."]);
+
+        // Without a value all alternatives are possible.
+        let children =
+            child_schemas(schema.clone(), serde_json::json!({}), "dep", "description").await;
+        assert_eq!(
+            children,
+            [
+                (String::new(), Value::from("A version.")),
+                (String::new(), Value::from("A detailed dependency.")),
+                (String::from("version"), Value::Null),
+                (String::from("path"), Value::Null),
+            ]
+        );
+
+        let children = child_schemas(
+            schema,
+            serde_json::json!({ "dep": "1.0" }),
+            "dep",
+            "description",
+        )
+        .await;
+        assert_eq!(children, [(String::new(), Value::from("A version."))]);
+    }
+
+    #[tokio::test]
+    async fn discriminated_alternatives() {
+        let schema = serde_json::json!({
+            "properties": {
+                "source": {
+                    "oneOf": [
+                        { "$ref": "#/definitions/git" },
+                        {
+                            "title": "Path",
+                            "properties": {
+                                "kind": { "const": "path" },
+                                "path": { "title": "Path" }
+                            }
+                        }
+                    ]
+                }
+            },
+            "definitions": {
+                "git": {
+                    "title": "Git",
+                    "type": "object",
+                    "properties": {
+                        "kind": { "const": "git" },
+                        "url": { "title": "URL" }
+                    }
+                }
+            }
+        });
+
+        let children = child_schemas(
+            schema.clone(),
+            serde_json::json!({ "source": { "kind": "git" } }),
+            "source",
+            "title",
+        )
+        .await;
+        assert_eq!(
+            children,
+            [
+                (String::new(), Value::from("Git")),
+                (String::from("kind"), Value::Null),
+                (String::from("url"), Value::from("URL")),
+            ]
+        );
+
+        let children = child_schemas(
+            schema,
+            serde_json::json!({ "source": {} }),
+            "source",
+            "title",
+        )
+        .await;
+        assert_eq!(children.len(), 6);
+    }
+
+    #[tokio::test]
+    async fn all_of_properties() {
+        let schema = serde_json::json!({
+            "properties": {
+                "a": {
+                    "description": "A",
+                    "allOf": [
+                        { "properties": { "x": { "description": "X" } } },
+                        { "$ref": "#/definitions/y" }
+                    ]
+                }
+            },
+            "definitions": {
+                "y": { "description": "Y", "properties": { "y": { "description": "Y" } } }
+            }
+        });
+
+        let children = child_schemas(schema, serde_json::json!({}), "a", "description").await;
+        assert_eq!(
+            children,
+            [
+                (String::new(), Value::from("A")),
+                (String::from("x"), Value::from("X")),
+                (String::from("y"), Value::from("Y")),
+            ]
+        );
+    }
+
+    #[test]
+    fn property_schema_precedence() {
+        let schema = serde_json::json!({
+            "properties": { "a": { "description": "A" } },
+            "patternProperties": {
+                "^a": { "description": "Pattern A" },
+                "^b": { "description": "Pattern B" },
+                "b$": { "description": "Pattern B end" },
+                "(": { "description": "Invalid" }
+            },
+            "additionalProperties": { "description": "Additional" }
+        });
+
+        let found = |key| {
+            property_schemas(&schema, key)
+                .into_iter()
+                .map(|schema| schema["description"].as_str().unwrap())
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(found("a"), ["A"]);
+        assert_eq!(found("ab"), ["Pattern A", "Pattern B end"]);
+        assert_eq!(found("bb"), ["Pattern B", "Pattern B end"]);
+        assert_eq!(found("c"), ["Additional"]);
+
+        assert_eq!(
+            property_schemas(&serde_json::json!({}), "a"),
+            [&Value::Null]
+        );
+    }
+
+    #[tokio::test]
+    async fn nested_additional_properties() {
+        let schema = serde_json::json!({
+            "properties": {
+                "profile": {
+                    "properties": { "dev": { "$ref": "#/definitions/dev" } },
+                    "patternProperties": {
+                        "^bench-": { "description": "Benchmark profile." }
+                    },
+                    "additionalProperties": { "$ref": "#/definitions/profile" }
+                }
+            },
+            "definitions": {
+                "profile": {
+                    "description": "Profile.",
+                    "type": "object",
+                    "allOf": [
+                        { "properties": { "opt-level": { "description": "Optimization level." } } },
+                        { "$ref": "#/definitions/debug" }
+                    ],
+                    "additionalProperties": {
+                        "anyOf": [
+                            { "type": "string", "description": "A package name." },
+                            { "$ref": "#/definitions/profile" }
+                        ]
+                    }
+                },
+                "debug": {
+                    "properties": { "debug": { "description": "Debug info." } }
+                },
+                "dev": { "description": "Development profile." }
+            }
+        });
+        let (schemas, url) = test_schemas(schema).await;
+
+        let found = |path: &'static str, value: Value| {
+            let schemas = &schemas;
+            let url = &url;
+            async move {
+                let found = schemas
+                    .schemas_at_path(url, &value, &path.parse().unwrap())
+                    .await
+                    .unwrap();
+                descriptions(&found)
+                    .into_iter()
+                    .map(String::from)
+                    .collect::<Vec<_>>()
+            }
+        };
+
+        // `$ref` in `additionalProperties`, with the properties from `allOf`.
+        assert_eq!(
+            found("profile.release", serde_json::json!({})).await,
+            ["Profile."]
+        );
+        assert_eq!(
+            found("profile.release.opt-level", serde_json::json!({})).await,
+            ["Optimization level."]
+        );
+        assert_eq!(
+            found("profile.release.debug", serde_json::json!({})).await,
+            ["Debug info."]
+        );
+
+        // Explicit properties and pattern properties take precedence.
+        assert_eq!(
+            found("profile.dev", serde_json::json!({})).await,
+            ["Development profile."]
+        );
+        assert!(found("profile.dev.opt-level", serde_json::json!({}))
+            .await
+            .is_empty());
+        assert_eq!(
+            found("profile.bench-fast", serde_json::json!({})).await,
+            ["Benchmark profile."]
+        );
+
+        // A second level of `additionalProperties` with `anyOf` alternatives.
+        assert_eq!(
+            found("profile.release.package", serde_json::json!({})).await,
+            ["A package name.", "Profile."]
+        );
+        assert_eq!(
+            found(
+                "profile.release.package",
+                serde_json::json!({ "profile": { "release": { "package": "a" } } })
+            )
+            .await,
+            ["A package name."]
+        );
+        assert_eq!(
+            found("profile.release.package.opt-level", serde_json::json!({})).await,
+            ["Optimization level."]
+        );
+    }
+
+    /// Validate `toml` against `schema` and return the
+    /// ranges and messages of the errors.
+    async fn validation_errors(schema: Value, toml: &str) -> Vec<(Vec<&str>, String)> {
+        let (schemas, url) = test_schemas(schema).await;
+
+        let dom = taplo::parser::parse(toml).into_dom();
+        schemas
+            .validate_root(&url, &dom)
+            .await
+            .unwrap()
+            .iter()
+            .map(|err| {
+                let ranges = err.text_ranges().into_iter().map(|r| &toml[r]).collect();
+                (ranges, err.message())
+            })
+            .collect()
+    }
+
+    const BINS: &str = r#"
+[[bin]]
+name = "a"
+
+[[bin]]
+name = "b"
+
+[[bin]] # c
+name = "c"
+
+[[bin]] # d
+name = "d"
+"#;
+
+    #[tokio::test]
+    async fn array_of_tables_max_items() {
+        let errors = validation_errors(
+            serde_json::json!({ "properties": { "bin": { "maxItems": 2 } } }),
+            BINS,
+        )
+        .await;
+
+        assert_eq!(
+            errors,
+            [(
+                vec!["[[bin]]", "[[bin]]"],
+                String::from("expected at most 2 tables, found 4 (2 too many)")
+            )]
+        );
+    }
+
+    #[tokio::test]
+    async fn array_of_tables_min_items() {
+        let errors = validation_errors(
+            serde_json::json!({ "properties": { "bin": { "minItems": 6 } } }),
+            BINS,
+        )
+        .await;
+
+        assert_eq!(
+            errors,
+            [(
+                vec!["[[bin]]"],
+                String::from("expected at least 6 tables, found 4 (2 more required)")
+            )]
+        );
+    }
+
+    #[tokio::test]
+    async fn inline_array_max_items() {
+        let errors = validation_errors(
+            serde_json::json!({ "properties": { "bin": { "maxItems": 2 } } }),
+            "bin = [1, 2, 3]",
+        )
+        .await;
+
+        assert_eq!(
+            errors,
+            [(vec!["bin"], String::from("[1,2,3] has more than 2 items"))]
+        );
+    }
+}
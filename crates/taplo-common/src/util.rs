@@ -1,4 +1,6 @@
-use globset::{Glob, GlobSetBuilder};
+use crate::path_match::{MatchInfo, PathMatcher, PatternError};
+use anyhow::anyhow;
+use itertools::Itertools;
 use percent_encoding::percent_decode_str;
 use serde_json::Value;
 use std::{
@@ -7,11 +9,13 @@ use std::{
     path::{Path, PathBuf},
     sync::Arc,
 };
+use url::Url;
 
+/// Included and excluded glob patterns, exclusions have priority.
 #[derive(Debug, Clone)]
 pub struct GlobRule {
-    include: globset::GlobSet,
-    exclude: globset::GlobSet,
+    include: PathMatcher,
+    exclude: PathMatcher,
 }
 
 impl GlobRule {
@@ -19,28 +23,27 @@ impl GlobRule {
         include: impl IntoIterator<Item = impl AsRef<str>>,
         exclude: impl IntoIterator<Item = impl AsRef<str>>,
     ) -> Result<Self, anyhow::Error> {
-        let mut inc = GlobSetBuilder::new();
-        for glob in include {
-            inc.add(Glob::new(glob.as_ref())?);
-        }
-
-        let mut exc = GlobSetBuilder::new();
-        for glob in exclude {
-            exc.add(Glob::new(glob.as_ref())?);
-        }
+        let errors = |errors: Vec<PatternError>| anyhow!(errors.iter().join(", "));
 
         Ok(Self {
-            include: inc.build()?,
-            exclude: exc.build()?,
+            include: PathMatcher::new(include).map_err(errors)?,
+            exclude: PathMatcher::new(exclude).map_err(errors)?,
         })
     }
 
     pub fn is_match(&self, text: impl AsRef<Path>) -> bool {
-        if !self.include.is_match(text.as_ref()) {
-            return false;
+        self.matches(&text.as_ref().to_string_lossy()).is_some()
+    }
+
+    /// The most specific included pattern that matches the path
+    /// if it is not excluded, see [`PathMatcher::matches`].
+    #[must_use]
+    pub fn matches(&self, normalized_path: &str) -> Option<MatchInfo> {
+        if self.exclude.matches(normalized_path).is_some() {
+            return None;
         }
 
-        !self.exclude.is_match(text.as_ref())
+        self.include.matches(normalized_path)
     }
 }
 
@@ -95,6 +98,7 @@ pub trait Normalize {
     ///
     /// - replaces `\` with `/` on windows
     /// - decodes all percent-encoded characters
+    /// - lowercases drive letters
     #[must_use]
     fn normalize(self) -> Self;
 }
@@ -114,9 +118,213 @@ pub(crate) fn normalize_str(s: &str) -> Cow<str> {
         None => return s.into(),
     };
 
-    if cfg!(windows) {
+    let separators_replaced = if cfg!(windows) {
         percent_decoded.replace('\\', "/").into()
     } else {
         percent_decoded
+    };
+
+    fold_drive_letter(separators_replaced)
+}
+
+/// Lowercase the drive letter of a Windows path (`C:/dir` or `/C:/dir`).
+pub(crate) fn fold_drive_letter(s: Cow<str>) -> Cow<str> {
+    let offset = usize::from(s.starts_with('/'));
+    let bytes = s.as_bytes();
+
+    let has_drive_letter = bytes.len() > offset + 1
+        && bytes[offset].is_ascii_uppercase()
+        && bytes[offset + 1] == b':'
+        && matches!(bytes.get(offset + 2), None | Some(b'/' | b'\\'));
+
+    if has_drive_letter {
+        let mut s = s.into_owned();
+        s[offset..=offset].make_ascii_lowercase();
+        s.into()
+    } else {
+        s
+    }
+}
+
+/// Normalize a URL so that URLs that refer to the same file are equal.
+///
+/// Only `file` URLs are changed:
+///
+/// - percent-encoded characters are decoded unless they must stay encoded,
+///   e.g. `file:///c%3A/dir` becomes `file:///c:/dir`
+/// - drive letters are lowercase
+/// - `\` is replaced with `/`
+/// - UNC paths are always represented with a host, e.g. `file://server/share`,
+///   and `localhost` is removed
+#[must_use]
+pub fn normalize_url(url: &Url) -> Url {
+    if url.scheme() != "file" {
+        return url.clone();
+    }
+
+    let mut normalized = url.clone();
+    let path = decode_path(url.path()).replace('\\', "/");
+
+    // `file:////server/share` is an alternative form of `file://server/share`,
+    // depending on the URL parser it might have an empty host.
+    let path = match path.strip_prefix("//").filter(|_| url.host().is_none()) {
+        Some(unc) => {
+            let (host, share_path) = unc.split_at(unc.find('/').unwrap_or(unc.len()));
+            if !host.is_empty() && normalized.set_host(Some(host)).is_ok() {
+                share_path.to_string()
+            } else {
+                path.clone()
+            }
+        }
+        None => path,
+    };
+
+    if normalized.host_str() == Some("localhost") {
+        // This cannot fail for `file` URLs.
+        let _ = normalized.set_host(None);
+    }
+
+    normalized.set_path(&fold_drive_letter(path.into()));
+    normalized
+}
+
+/// Decode percent-encoded characters except for the ones that
+/// would change the meaning of the path (`%`, `/`).
+fn decode_path(path: &str) -> String {
+    let mut bytes = Vec::with_capacity(path.len());
+    let mut rest = path.as_bytes();
+
+    while let Some((&b, tail)) = rest.split_first() {
+        let decoded = match tail {
+            [h, l, ..] if b == b'%' => std::str::from_utf8(&[*h, *l])
+                .ok()
+                .and_then(|hex| u8::from_str_radix(hex, 16).ok())
+                .filter(|c| !matches!(c, b'%' | b'/')),
+            _ => None,
+        };
+
+        match decoded {
+            Some(c) => {
+                bytes.push(c);
+                rest = &tail[2..];
+            }
+            None => {
+                bytes.push(b);
+                rest = tail;
+            }
+        }
+    }
+
+    match String::from_utf8(bytes) {
+        Ok(s) => s,
+        Err(_) => path.to_string(),
+    }
+}
+
+/// The path of a `file` URL with `/` separators, regardless of the platform.
+///
+/// The URL is [normalized](normalize_url) first, drive paths are returned
+/// without the leading `/` (`c:/dir/file.toml`), and UNC paths include
+/// the host (`//server/share/file.toml`).
+///
+/// Returns [`None`] for URLs of other schemes.
+#[must_use]
+pub fn url_to_path(url: &Url) -> Option<String> {
+    if url.scheme() != "file" {
+        return None;
+    }
+
+    let url = normalize_url(url);
+    let path = percent_decode_str(url.path()).decode_utf8_lossy();
+
+    let path = match url.host_str() {
+        Some(host) => format!("//{host}{path}"),
+        None => match path.as_bytes() {
+            [b'/', _, b':', ..] => path[1..].to_string(),
+            _ => path.into_owned(),
+        },
+    };
+
+    Some(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DRIVE: &str = "file:///c:/dir/a.toml";
+    const UNC: &str = "file://server/share/a.toml";
+
+    #[test]
+    fn normalize_file_urls() {
+        let cases: &[(&str, &str, Option<&str>)] = &[
+            ("file:///C:/dir/a.toml", DRIVE, Some("c:/dir/a.toml")),
+            ("file:///c%3A/dir/a.toml", DRIVE, Some("c:/dir/a.toml")),
+            ("file:///C%3A/dir/a.toml", DRIVE, Some("c:/dir/a.toml")),
+            ("file:///c%3a/dir/a.toml", DRIVE, Some("c:/dir/a.toml")),
+            ("file:///C:\\dir\\a.toml", DRIVE, Some("c:/dir/a.toml")),
+            ("file:///c%3A/dir%5Ca.toml", DRIVE, Some("c:/dir/a.toml")),
+            (
+                "file://localhost/C:/dir/a.toml",
+                DRIVE,
+                Some("c:/dir/a.toml"),
+            ),
+            (
+                "file:///c%3A/My%20Dir/a.toml",
+                "file:///c:/My%20Dir/a.toml",
+                Some("c:/My Dir/a.toml"),
+            ),
+            (UNC, UNC, Some("//server/share/a.toml")),
+            (
+                "file://SERVER/share/a.toml",
+                UNC,
+                Some("//server/share/a.toml"),
+            ),
+            (
+                "file:///home/C:/a.toml",
+                "file:///home/C:/a.toml",
+                Some("/home/C:/a.toml"),
+            ),
+            (
+                "file:///home/a.toml",
+                "file:///home/a.toml",
+                Some("/home/a.toml"),
+            ),
+            (
+                "file:///home/100%25.toml",
+                "file:///home/100%25.toml",
+                Some("/home/100%.toml"),
+            ),
+            (
+                "file:///home/a%2Fb.toml",
+                "file:///home/a%2Fb.toml",
+                Some("/home/a/b.toml"),
+            ),
+            ("untitled:Untitled-1", "untitled:Untitled-1", None),
+            (
+                "https://example.com/C%3A/schema.json",
+                "https://example.com/C%3A/schema.json",
+                None,
+            ),
+        ];
+
+        for (url, expected, expected_path) in cases {
+            let url: Url = url.parse().unwrap();
+            let normalized = normalize_url(&url);
+
+            assert_eq!(normalized.as_str(), *expected, "{url}");
+            assert_eq!(normalize_url(&normalized), normalized, "{url}");
+            assert_eq!(url_to_path(&url).as_deref(), *expected_path, "{url}");
+        }
+    }
+
+    #[test]
+    fn normalize_paths() {
+        assert_eq!(normalize_str("C:/dir/a.toml"), "c:/dir/a.toml");
+        assert_eq!(normalize_str("/C:/dir/a.toml"), "/c:/dir/a.toml");
+        assert_eq!(normalize_str("c%3A/dir/a.toml"), "c:/dir/a.toml");
+        assert_eq!(normalize_str("C:"), "c:");
+        assert_eq!(normalize_str("/home/Cx:/a.toml"), "/home/Cx:/a.toml");
+        assert_eq!(normalize_str("Cargo.toml"), "Cargo.toml");
     }
 }
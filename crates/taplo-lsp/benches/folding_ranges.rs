@@ -0,0 +1,35 @@
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use lsp_async_stub::util::Mapper;
+use taplo::parser::parse;
+use taplo_lsp::create_folding_ranges;
+
+/// A document with many nested tables that share textual prefixes.
+fn large_document() -> String {
+    let mut src = String::new();
+
+    for i in 0..200 {
+        src += &format!("[table_{i}]\nname = \"value\"\n");
+
+        for j in 0..10 {
+            src += &format!("[table_{i}.\"nested {j}\"]\nkey = {j}\n");
+            src += &format!("[ table_{i} . \"nested {j}\" . inner ]\nkey = {j}\n");
+        }
+
+        src += &format!("[table_{i}0]\nname = \"value\"\n");
+    }
+
+    src
+}
+
+pub fn folding_ranges(c: &mut Criterion) {
+    let source = large_document();
+    let syntax = parse(&source).into_syntax();
+    let mapper = Mapper::new_utf16(&source, false);
+
+    c.bench_function("folding ranges", |b| {
+        b.iter(|| create_folding_ranges(black_box(&syntax), &mapper))
+    });
+}
+
+criterion_group!(benches, folding_ranges);
+criterion_main!(benches);
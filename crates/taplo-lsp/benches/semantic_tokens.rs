@@ -0,0 +1,33 @@
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use lsp_async_stub::util::PositionEncoding;
+use taplo::parser::parse;
+use taplo_lsp::create_semantic_tokens;
+
+/// A document of about 10k lines with keys of inline tables and arrays.
+fn large_document() -> String {
+    let mut src = String::new();
+
+    for i in 0..1000 {
+        src += &format!("[table_{i}]\n");
+        src += "name = \"value\"\n";
+        src += "items = [1, 2, 3]\n";
+        src += "inline = { a = 1, b = [\"x\", \"y\"] }\n";
+        src += "# comment with unicode: é😀\n";
+        src += "nested.key = { c = [] }\n";
+        src += "text = \"\"\"\nmulti\nline\n\"\"\"\n";
+    }
+
+    src
+}
+
+pub fn semantic_tokens(c: &mut Criterion) {
+    let source = large_document();
+    let syntax = parse(&source).into_syntax();
+
+    c.bench_function("semantic tokens", |b| {
+        b.iter(|| create_semantic_tokens(black_box(&syntax), None, PositionEncoding::Utf16))
+    });
+}
+
+criterion_group!(benches, semantic_tokens);
+criterion_main!(benches);
@@ -1,20 +1,32 @@
+use anyhow::anyhow;
 use figment::{providers::Serialized, Figment};
 use lsp_types::Url;
 use serde::{Deserialize, Serialize};
-use serde_json::Value;
+use serde_json::{json, Map, Value};
 use std::path::PathBuf;
 use taplo_common::{
     config::Rule,
-    schema::{associations::DEFAULT_CATALOGS, cache::DEFAULT_LRU_CACHE_EXPIRATION_TIME},
+    schema::{
+        associations::DEFAULT_CATALOGS, cache::DEFAULT_LRU_CACHE_EXPIRATION_TIME,
+        DEFAULT_FETCH_TIMEOUT,
+    },
     HashMap,
 };
 
+/// Options sent by the client in `initializationOptions`.
+///
+/// Any other fields are treated as the initial [`LspConfig`] for
+/// every workspace, for clients that cannot serve
+/// `workspace/configuration` requests. The configuration returned by
+/// `workspace/configuration` is applied afterwards and takes precedence.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct InitConfig {
     pub cache_path: Option<PathBuf>,
     #[serde(default = "default_configuration_section")]
     pub configuration_section: String,
+    #[serde(flatten)]
+    pub configuration: Map<String, Value>,
 }
 
 impl Default for InitConfig {
@@ -22,6 +34,7 @@ impl Default for InitConfig {
         Self {
             cache_path: Default::default(),
             configuration_section: default_configuration_section(),
+            configuration: Default::default(),
         }
     }
 }
@@ -37,17 +50,65 @@ pub struct LspConfig {
     pub schema: SchemaConfig,
     pub completion: CompletionConfig,
     pub syntax: SyntaxConfig,
+    pub code_lens: CodeLensConfig,
+    pub lint: LintConfig,
     pub formatter: taplo::formatter::OptionsIncompleteCamel,
     pub rules: Vec<Rule>,
 }
 
 impl LspConfig {
+    /// Merge the given configuration into the current one.
+    ///
+    /// Invalid fields are skipped one by one instead of rejecting the entire
+    /// configuration, the valid fields are always applied and
+    /// an error is returned that lists the skipped ones.
     pub fn update_from_json(&mut self, json: &Value) -> Result<(), anyhow::Error> {
-        *self = Figment::new()
+        let mut errors = Vec::new();
+        self.update_field(&mut Vec::new(), json, &mut errors);
+
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(anyhow!(
+                "ignored invalid configuration fields: {}",
+                errors.join("; ")
+            ))
+        }
+    }
+
+    fn update_field<'v>(
+        &mut self,
+        path: &mut Vec<&'v str>,
+        value: &'v Value,
+        errors: &mut Vec<String>,
+    ) {
+        let nested = path
+            .iter()
+            .rev()
+            .fold(value.clone(), |value, key| json!({ *key: value }));
+
+        let error = match Figment::new()
             .merge(Serialized::defaults(&self))
-            .merge(Serialized::defaults(json))
-            .extract()?;
-        Ok(())
+            .merge(Serialized::defaults(nested))
+            .extract()
+        {
+            Ok(config) => {
+                *self = config;
+                return;
+            }
+            Err(error) => error,
+        };
+
+        match value {
+            Value::Object(fields) if !fields.is_empty() => {
+                for (key, value) in fields {
+                    path.push(key);
+                    self.update_field(path, value, errors);
+                    path.pop();
+                }
+            }
+            _ => errors.push(format!("{}: {}", path.join("."), error.kind)),
+        }
     }
 }
 
@@ -55,11 +116,17 @@ impl LspConfig {
 #[serde(rename_all = "camelCase")]
 pub struct CompletionConfig {
     pub max_keys: usize,
+    /// Complete keys that are already used in the document
+    /// if it has no schema.
+    pub from_document: bool,
 }
 
 impl Default for CompletionConfig {
     fn default() -> Self {
-        Self { max_keys: 5 }
+        Self {
+            max_keys: 5,
+            from_document: true,
+        }
     }
 }
 
@@ -77,6 +144,41 @@ impl Default for SyntaxConfig {
     }
 }
 
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CodeLensConfig {
+    pub enabled: bool,
+}
+
+impl Default for CodeLensConfig {
+    fn default() -> Self {
+        Self { enabled: true }
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LintConfig {
+    pub line_too_long: LineTooLongConfig,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LineTooLongConfig {
+    pub enabled: bool,
+    /// The maximum line width, the formatter column width is used if not set.
+    pub column_width: Option<usize>,
+}
+
+impl Default for LineTooLongConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            column_width: None,
+        }
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct SchemaConfig {
@@ -85,6 +187,8 @@ pub struct SchemaConfig {
     pub catalogs: Vec<Url>,
     pub links: bool,
     pub cache: SchemaCacheConfig,
+    /// Timeout in seconds for downloading remote schemas and catalogs.
+    pub fetch_timeout: u64,
 }
 
 impl Default for SchemaConfig {
@@ -98,6 +202,7 @@ impl Default for SchemaConfig {
                 .collect(),
             links: false,
             cache: Default::default(),
+            fetch_timeout: DEFAULT_FETCH_TIMEOUT.as_secs(),
         }
     }
 }
@@ -138,3 +243,92 @@ impl Default for TaploConfigFileConfig {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn init_config() -> InitConfig {
+        serde_json::from_value(json!({
+            "cachePath": "/tmp/taplo",
+            "formatter": { "alignEntries": true, "columnWidth": 100 },
+            "schema": { "links": true },
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn initialization_options_only() {
+        let init = init_config();
+        assert_eq!(init.cache_path, Some(PathBuf::from("/tmp/taplo")));
+        assert_eq!(init.configuration_section, "evenBetterToml");
+        assert!(!init.configuration.contains_key("cachePath"));
+
+        let mut config = LspConfig::default();
+        config
+            .update_from_json(&Value::Object(init.configuration))
+            .unwrap();
+
+        assert_eq!(config.formatter.align_entries, Some(true));
+        assert_eq!(config.formatter.column_width, Some(100));
+        assert!(config.schema.links);
+        assert!(config.schema.enabled);
+    }
+
+    #[test]
+    fn workspace_configuration_only() {
+        let init: InitConfig = serde_json::from_value(json!({
+            "configurationSection": "taplo",
+        }))
+        .unwrap();
+        assert!(init.configuration.is_empty());
+
+        let mut config = LspConfig::default();
+        config
+            .update_from_json(&json!({ "formatter": { "alignEntries": true } }))
+            .unwrap();
+
+        assert_eq!(config.formatter.align_entries, Some(true));
+        assert_eq!(config.formatter.column_width, None);
+    }
+
+    #[test]
+    fn workspace_configuration_overrides_initialization_options() {
+        let mut config = LspConfig::default();
+        config
+            .update_from_json(&Value::Object(init_config().configuration))
+            .unwrap();
+        config
+            .update_from_json(&json!({
+                "formatter": { "columnWidth": 80 },
+                "schema": { "enabled": false },
+            }))
+            .unwrap();
+
+        assert_eq!(config.formatter.align_entries, Some(true));
+        assert_eq!(config.formatter.column_width, Some(80));
+        assert!(config.schema.links);
+        assert!(!config.schema.enabled);
+    }
+
+    #[test]
+    fn invalid_fields_are_skipped() {
+        let mut config = LspConfig::default();
+        let error = config
+            .update_from_json(&json!({
+                "formatter": { "alignEntries": "yes", "columnWidth": 100 },
+                "completion": { "maxKeys": -1 },
+                "lint": { "lineTooLong": { "enabled": false } },
+            }))
+            .unwrap_err()
+            .to_string();
+
+        assert!(error.contains("formatter.alignEntries"), "{error}");
+        assert!(error.contains("completion.maxKeys"), "{error}");
+
+        assert_eq!(config.formatter.align_entries, None);
+        assert_eq!(config.formatter.column_width, Some(100));
+        assert_eq!(config.completion.max_keys, 5);
+        assert!(!config.lint.line_too_long.enabled);
+    }
+}
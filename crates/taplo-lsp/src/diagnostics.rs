@@ -1,12 +1,42 @@
-use crate::world::{DocumentState, WorkspaceState, World};
-use either::Either;
-use lsp_async_stub::{util::LspExt, Context, RequestWriter};
+use crate::world::{document_path, DocumentState, WorkspaceSnapshot, World};
+use lsp_async_stub::{
+    util::{LspExt, Mapper},
+    Context, RequestWriter,
+};
 use lsp_types::{
     notification, Diagnostic, DiagnosticRelatedInformation, DiagnosticSeverity, Location,
-    PublishDiagnosticsParams, Url,
+    NumberOrString, PublishDiagnosticsParams, Range, Url,
+};
+use serde_json::json;
+use std::path::Path;
+use taplo::{
+    dom::{node::DomNode, Node},
+    util::syntax::unfixable_long_lines,
 };
-use taplo::dom::{KeyOrIndex, Node};
-use taplo_common::environment::Environment;
+use taplo_common::{
+    environment::Environment,
+    schema::{associations::SchemaAssociation, SchemaStatus, Schemas},
+};
+
+const SYNTAX_ERROR: &str = "syntax-error";
+const CONFLICTING_KEYS: &str = "conflicting-keys";
+const EXPECTED_TABLE: &str = "expected-table";
+const EXPECTED_ARRAY_OF_TABLES: &str = "expected-array-of-tables";
+
+/// Diagnostic codes and the codes of the diagnostics they make redundant
+/// if their ranges overlap.
+///
+/// A syntax error usually causes more errors around it, and errors in
+/// the DOM of an invalid construct are a consequence of the syntax error.
+const SHADOWING: &[(&str, &[&str])] = &[(
+    SYNTAX_ERROR,
+    &[
+        SYNTAX_ERROR,
+        CONFLICTING_KEYS,
+        EXPECTED_TABLE,
+        EXPECTED_ARRAY_OF_TABLES,
+    ],
+)];
 
 #[tracing::instrument(skip_all)]
 pub(crate) async fn publish_diagnostics<E: Environment>(
@@ -16,6 +46,8 @@ pub(crate) async fn publish_diagnostics<E: Environment>(
 ) {
     let mut diags = Vec::new();
 
+    // Everything is taken at once, so that the diagnostics are consistent
+    // even if the document or the configuration changes in the meantime.
     let workspaces = context.workspaces.read().await;
     let ws = match workspaces.get(&ws_url) {
         Some(d) => d,
@@ -25,17 +57,28 @@ pub(crate) async fn publish_diagnostics<E: Environment>(
         }
     };
     let doc = match ws.documents.get(&document_url) {
-        Some(doc) => doc,
+        Some(doc) => doc.clone(),
         None => return,
     };
-
-    collect_syntax_errors(doc, &mut diags);
+    let snapshot = ws.snapshot();
+    let schemas = ws.schemas.clone();
+    let schema_association = schemas.associations().association_for(&document_url);
     drop(workspaces);
 
+    // Lint warnings are published regardless of other errors.
+    let mut lints = Vec::new();
+    let doc_path = document_path(&context.env, &document_url);
+    collect_lint_warnings(&snapshot, &doc, &doc_path, &mut lints);
+
+    collect_syntax_errors(&doc, &mut diags);
+
     context
         .write_notification::<notification::PublishDiagnostics, _>(Some(PublishDiagnosticsParams {
             uri: document_url.clone(),
-            diagnostics: diags.clone(),
+            diagnostics: consolidate_diagnostics(diags.clone(), &document_url)
+                .into_iter()
+                .chain(lints.iter().cloned())
+                .collect(),
             version: None,
         }))
         .await
@@ -45,28 +88,17 @@ pub(crate) async fn publish_diagnostics<E: Environment>(
         return;
     }
 
-    let workspaces = context.workspaces.read().await;
-    let ws = match workspaces.get(&ws_url) {
-        Some(d) => d,
-        None => {
-            tracing::warn!(%document_url, "workspace not found");
-            return;
-        }
-    };
-    let doc = match ws.documents.get(&document_url) {
-        Some(doc) => doc,
-        None => return,
-    };
-
     let dom = doc.dom.clone();
 
-    collect_dom_errors(doc, &dom, &document_url, &mut diags);
-    drop(workspaces);
+    collect_dom_errors(&doc, &dom, &document_url, &mut diags);
 
     context
         .write_notification::<notification::PublishDiagnostics, _>(Some(PublishDiagnosticsParams {
             uri: document_url.clone(),
-            diagnostics: diags.clone(),
+            diagnostics: consolidate_diagnostics(diags.clone(), &document_url)
+                .into_iter()
+                .chain(lints.iter().cloned())
+                .collect(),
             version: None,
         }))
         .await
@@ -76,32 +108,45 @@ pub(crate) async fn publish_diagnostics<E: Environment>(
         return;
     }
 
-    let workspaces = context.workspaces.read().await;
-    let ws = match workspaces.get(&ws_url) {
-        Some(d) => d,
-        None => {
-            tracing::warn!(%document_url, "workspace not found");
-            return;
+    if snapshot.config.schema.enabled {
+        if let Some(schema_association) = schema_association {
+            collect_schema_errors(&schemas, &schema_association, &doc, &dom, &mut diags).await;
         }
-    };
-    let doc = match ws.documents.get(&document_url) {
-        Some(doc) => doc,
-        None => return,
-    };
-
-    collect_schema_errors(ws, doc, &dom, &document_url, &mut diags).await;
-    drop(workspaces);
+    }
 
     context
         .write_notification::<notification::PublishDiagnostics, _>(Some(PublishDiagnosticsParams {
             uri: document_url.clone(),
-            diagnostics: diags.clone(),
+            diagnostics: consolidate_diagnostics(diags.clone(), &document_url)
+                .into_iter()
+                .chain(lints.iter().cloned())
+                .collect(),
             version: None,
         }))
         .await
         .unwrap_or_else(|err| tracing::error!("{err}"));
 }
 
+/// Publish diagnostics for all open documents in all workspaces.
+#[tracing::instrument(skip_all)]
+pub(crate) async fn publish_all_diagnostics<E: Environment>(context: Context<World<E>>) {
+    let documents = context
+        .workspaces
+        .read()
+        .await
+        .iter()
+        .flat_map(|(ws_url, ws)| {
+            ws.documents
+                .keys()
+                .map(|document_url| (ws_url.clone(), document_url.clone()))
+        })
+        .collect::<Vec<_>>();
+
+    for (ws_url, document_url) in documents {
+        publish_diagnostics(context.clone(), ws_url, document_url).await;
+    }
+}
+
 #[tracing::instrument(skip_all)]
 pub(crate) async fn clear_diagnostics<E: Environment>(
     mut context: Context<World<E>>,
@@ -117,22 +162,79 @@ pub(crate) async fn clear_diagnostics<E: Environment>(
         .unwrap_or_else(|err| tracing::error!("{}", err));
 }
 
+#[tracing::instrument(skip_all, fields(?doc_path))]
+fn collect_lint_warnings(
+    snapshot: &WorkspaceSnapshot,
+    doc: &DocumentState,
+    doc_path: &Path,
+    diags: &mut Vec<Diagnostic>,
+) {
+    let config = &snapshot.config.lint.line_too_long;
+
+    if !config.enabled {
+        return;
+    }
+
+    let column_width = config.column_width.unwrap_or_else(|| {
+        snapshot
+            .format_options(doc_path, Default::default())
+            .column_width
+    });
+
+    let Some(syntax) = doc.dom.syntax().and_then(|s| s.as_node()) else {
+        return;
+    };
+
+    diags.extend(
+        unfixable_long_lines(syntax, column_width)
+            .into_iter()
+            .map(|range| Diagnostic {
+                range: doc.mapper.range(range).unwrap_or_default().into_lsp(),
+                severity: Some(DiagnosticSeverity::INFORMATION),
+                code: Some(NumberOrString::String("line-too-long".into())),
+                source: Some("Even Better TOML".into()),
+                message: format!("line is longer than {column_width} characters"),
+                ..Default::default()
+            }),
+    );
+}
+
 #[tracing::instrument(skip_all)]
 fn collect_syntax_errors(doc: &DocumentState, diags: &mut Vec<Diagnostic>) {
-    diags.extend(doc.parse.errors.iter().map(|e| {
-        let range = doc.mapper.range(e.range).unwrap_or_default().into_lsp();
+    diags.extend(syntax_error_diagnostics(&doc.parse.errors, &doc.mapper));
+}
+
+fn syntax_error_diagnostics<'e>(
+    errors: &'e [taplo::parser::Error],
+    mapper: &'e Mapper,
+) -> impl Iterator<Item = Diagnostic> + 'e {
+    errors.iter().map(|e| {
+        let range = mapper.range(e.range).unwrap_or_default().into_lsp();
+
+        // Messages that are not about the expected tokens
+        // (e.g. "unexpected EOF") get them appended.
+        let message = match e.expected_message() {
+            Some(expected) if !e.message.starts_with("expected ") => {
+                format!("{}, {expected}", e.message)
+            }
+            _ => e.message.clone(),
+        };
+
         Diagnostic {
             range,
             severity: Some(DiagnosticSeverity::ERROR),
-            code: None,
+            code: Some(NumberOrString::String(SYNTAX_ERROR.into())),
             code_description: None,
             source: Some("Even Better TOML".into()),
-            message: e.message.clone(),
+            message,
             related_information: None,
             tags: None,
-            data: None,
+            data: Some(json!({
+                "expected": e.expected().iter().map(|kind| format!("{kind:?}")).collect::<Vec<_>>(),
+                "skipped": e.skipped(),
+            })),
         }
-    }));
+    })
 }
 
 #[tracing::instrument(skip_all)]
@@ -161,6 +263,7 @@ fn collect_dom_errors(
                     diags.push(Diagnostic {
                         range,
                         severity: Some(DiagnosticSeverity::ERROR),
+                        code: Some(NumberOrString::String(CONFLICTING_KEYS.into())),
                         source: Some("Even Better TOML".into()),
                         message: error.to_string(),
                         related_information: Some(Vec::from([DiagnosticRelatedInformation {
@@ -207,6 +310,7 @@ fn collect_dom_errors(
                     diags.push(Diagnostic {
                         range,
                         severity: Some(DiagnosticSeverity::ERROR),
+                        code: Some(NumberOrString::String(EXPECTED_TABLE.into())),
                         source: Some("Even Better TOML".into()),
                         message: error.to_string(),
                         related_information: Some(Vec::from([DiagnosticRelatedInformation {
@@ -253,6 +357,7 @@ fn collect_dom_errors(
                     diags.push(Diagnostic {
                         range,
                         severity: Some(DiagnosticSeverity::ERROR),
+                        code: Some(NumberOrString::String(EXPECTED_ARRAY_OF_TABLES.into())),
                         source: Some("Even Better TOML".into()),
                         message: error.to_string(),
                         related_information: Some(Vec::from([DiagnosticRelatedInformation {
@@ -280,8 +385,7 @@ fn collect_dom_errors(
                         ..Default::default()
                     });
                 }
-                taplo::dom::Error::InvalidEscapeSequence { string: _ }
-                | taplo::dom::Error::Query(_) => {}
+                taplo::dom::Error::InvalidEscapeSequence { .. } | taplo::dom::Error::Query(_) => {}
                 taplo::dom::Error::UnexpectedSyntax { syntax } => {
                     tracing::error!("unexpected syntax in dom: {syntax:#?}");
                 }
@@ -290,54 +394,264 @@ fn collect_dom_errors(
     }
 }
 
-#[tracing::instrument(skip_all, fields(%document_url))]
+#[tracing::instrument(skip_all)]
 async fn collect_schema_errors<E: Environment>(
-    ws: &WorkspaceState<E>,
+    schemas: &Schemas<E>,
+    schema_association: &SchemaAssociation,
     doc: &DocumentState,
     dom: &Node,
-    document_url: &Url,
     diags: &mut Vec<Diagnostic>,
 ) {
-    if !ws.config.schema.enabled {
-        return;
+    tracing::debug!(
+        schema.url = %schema_association.url,
+        schema.name = schema_association.meta["name"].as_str().unwrap_or(""),
+        schema.source = schema_association.meta["source"].as_str().unwrap_or(""),
+        "using schema"
+    );
+
+    match schemas.validate_root(&schema_association.url, dom).await {
+        Ok(errors) => diags.extend(errors.into_iter().flat_map(|err| {
+            let message = err.message();
+
+            err.text_ranges().into_iter().map(move |range| {
+                let range = doc.mapper.range(range).unwrap_or_default().into_lsp();
+                Diagnostic {
+                    range,
+                    severity: Some(DiagnosticSeverity::ERROR),
+                    code: None,
+                    code_description: None,
+                    source: Some("Even Better TOML".into()),
+                    message: message.clone(),
+                    related_information: None,
+                    tags: None,
+                    data: None,
+                }
+            })
+        })),
+        Err(error) => {
+            tracing::error!(?error, "schema validation failed");
+
+            // Tell the user why there is no validation for remote schemas
+            // that could not be downloaded.
+            match schemas.status(&schema_association.url) {
+                None | Some(SchemaStatus::Loaded) => {}
+                Some(status) => diags.push(Diagnostic {
+                    range: Default::default(),
+                    severity: Some(DiagnosticSeverity::WARNING),
+                    code: None,
+                    code_description: None,
+                    source: Some("Even Better TOML".into()),
+                    message: format!("schema {} is unavailable: {status}", schema_association.url),
+                    related_information: None,
+                    tags: None,
+                    data: None,
+                }),
+            }
+        }
     }
+}
+
+/// Merge the errors that are caused by the same construct,
+/// so that only one of them is shown for it.
+///
+/// Errors are merged if their ranges are identical, nested or adjacent,
+/// and one of them shadows the other (see [`SHADOWING`]).
+/// The most specific error is kept and the rest are attached to it
+/// as related information: an error that shadows the other one wins,
+/// otherwise the innermost error wins, and the first one if neither is nested.
+fn consolidate_diagnostics(diags: Vec<Diagnostic>, document_url: &Url) -> Vec<Diagnostic> {
+    // The diagnostics with the range covering all errors merged into them.
+    let mut consolidated: Vec<(Range, Diagnostic)> = Vec::with_capacity(diags.len());
+
+    for diag in diags {
+        let group = consolidated.iter_mut().find(|(extent, primary)| {
+            primary.severity == Some(DiagnosticSeverity::ERROR)
+                && diag.severity == Some(DiagnosticSeverity::ERROR)
+                && ranges_overlap(*extent, diag.range)
+                && (shadows(primary, &diag) || shadows(&diag, primary))
+        });
+
+        let Some((extent, primary)) = group else {
+            consolidated.push((diag.range, diag));
+            continue;
+        };
 
-    if let Some(schema_association) = ws.schemas.associations().association_for(document_url) {
-        tracing::debug!(
-            schema.url = %schema_association.url,
-            schema.name = schema_association.meta["name"].as_str().unwrap_or(""),
-            schema.source = schema_association.meta["source"].as_str().unwrap_or(""),
-            "using schema"
+        *extent = Range::new(
+            extent.start.min(diag.range.start),
+            extent.end.max(diag.range.end),
         );
 
-        match ws.schemas.validate_root(&schema_association.url, dom).await {
-            Ok(errors) => diags.extend(errors.into_iter().flat_map(|err| {
-                let ranges = if let Some(KeyOrIndex::Key(k)) = err.keys.into_iter().last() {
-                    Either::Left(k.text_ranges())
-                } else {
-                    Either::Right(err.node.text_ranges())
-                };
+        let diag_wins = if shadows(&diag, primary) == shadows(primary, &diag) {
+            diag.range != primary.range && contains(primary.range, diag.range)
+        } else {
+            shadows(&diag, primary)
+        };
 
-                let error = err.error;
+        let (mut kept, shadowed) = if diag_wins {
+            (diag, core::mem::take(primary))
+        } else {
+            (core::mem::take(primary), diag)
+        };
 
-                ranges.map(move |range| {
-                    let range = doc.mapper.range(range).unwrap_or_default().into_lsp();
-                    Diagnostic {
-                        range,
-                        severity: Some(DiagnosticSeverity::ERROR),
-                        code: None,
-                        code_description: None,
-                        source: Some("Even Better TOML".into()),
-                        message: error.to_string(),
-                        related_information: None,
-                        tags: None,
-                        data: None,
-                    }
-                })
-            })),
-            Err(error) => {
-                tracing::error!(?error, "schema validation failed");
-            }
+        let related = kept.related_information.get_or_insert_with(Vec::new);
+        related.push(DiagnosticRelatedInformation {
+            location: Location {
+                uri: document_url.clone(),
+                range: shadowed.range,
+            },
+            message: shadowed.message,
+        });
+        related.extend(shadowed.related_information.unwrap_or_default());
+
+        *primary = kept;
+    }
+
+    consolidated.into_iter().map(|(_, diag)| diag).collect()
+}
+
+fn shadows(diag: &Diagnostic, other: &Diagnostic) -> bool {
+    let (Some(NumberOrString::String(code)), Some(NumberOrString::String(other_code))) =
+        (&diag.code, &other.code)
+    else {
+        return false;
+    };
+
+    SHADOWING
+        .iter()
+        .any(|(c, shadowed)| c == code && shadowed.contains(&other_code.as_str()))
+}
+
+fn contains(range: Range, other: Range) -> bool {
+    range.start <= other.start && other.end <= range.end
+}
+
+/// Whether the ranges are identical, nested or adjacent.
+fn ranges_overlap(a: Range, b: Range) -> bool {
+    contains(a, b) || contains(b, a) || a.end == b.start || b.end == a.start
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use lsp_types::Position;
+    use taplo::parser::parse;
+
+    fn document_url() -> Url {
+        "file:///test.toml".parse().unwrap()
+    }
+
+    fn syntax_errors(src: &str) -> Vec<Diagnostic> {
+        let parse = parse(src);
+        let mapper = Mapper::new_utf16(src, false);
+
+        consolidate_diagnostics(
+            syntax_error_diagnostics(&parse.errors, &mapper).collect(),
+            &document_url(),
+        )
+    }
+
+    fn related_messages(diag: &Diagnostic) -> Vec<&str> {
+        diag.related_information
+            .iter()
+            .flatten()
+            .map(|r| r.message.as_str())
+            .collect()
+    }
+
+    #[test]
+    fn broken_header() {
+        let diags = syntax_errors("[a.b\nx = 1\n");
+        assert_eq!(diags.len(), 1);
+
+        let diags = syntax_errors("[[ a.b ]]\nx = 1\n");
+        assert_eq!(diags.len(), 1, "{diags:#?}");
+        assert!(!related_messages(&diags[0]).is_empty());
+    }
+
+    #[test]
+    fn broken_value() {
+        let diags = syntax_errors("a = tru\nb = 1\n");
+        assert_eq!(diags.len(), 1);
+        assert_eq!(diags[0].message, "expected value");
+    }
+
+    #[test]
+    fn multibyte_characters_before_error() {
+        let src = "\"é😀\" = tru\n";
+        let parse = parse(src);
+
+        // `é` is a single UTF-16 code unit and `😀` is two.
+        for (mapper, start, end) in [
+            (Mapper::new_utf16(src, false), 8, 11),
+            (Mapper::new_utf8(src, false), 11, 14),
+            (Mapper::new_utf32(src, false), 7, 10),
+        ] {
+            let diags: Vec<_> = syntax_error_diagnostics(&parse.errors, &mapper).collect();
+            assert_eq!(diags.len(), 1);
+            assert_eq!(
+                diags[0].range,
+                Range::new(Position::new(0, start), Position::new(0, end)),
+                "{:?}",
+                mapper.encoding()
+            );
         }
     }
+
+    #[test]
+    fn unterminated_string() {
+        let diags = syntax_errors("a = \"abc\nb = 1\n");
+        assert_eq!(diags.len(), 1);
+        assert_eq!(diags[0].message, "unexpected token");
+        assert_eq!(related_messages(&diags[0]), ["expected value"]);
+    }
+
+    #[test]
+    fn unrelated_errors_are_kept() {
+        let diags = syntax_errors("a = \nb = 1\nc = \n");
+        assert_eq!(diags.len(), 2);
+        assert!(diags.iter().all(|d| d.related_information.is_none()));
+    }
+
+    #[test]
+    fn syntax_errors_shadow_dom_errors() {
+        let range = |start, end| Range::new(Position::new(0, start), Position::new(0, end));
+        let error = |code: &str, range, message: &str| Diagnostic {
+            range,
+            severity: Some(DiagnosticSeverity::ERROR),
+            code: Some(NumberOrString::String(code.into())),
+            message: message.into(),
+            ..Default::default()
+        };
+
+        // The DOM error is more specific, but it is caused by the syntax error.
+        let diags = consolidate_diagnostics(
+            Vec::from([
+                error(CONFLICTING_KEYS, range(1, 2), "conflicting keys"),
+                error(SYNTAX_ERROR, range(0, 4), "expected \"]\""),
+            ]),
+            &document_url(),
+        );
+        assert_eq!(diags.len(), 1);
+        assert_eq!(diags[0].message, "expected \"]\"");
+        assert_eq!(related_messages(&diags[0]), ["conflicting keys"]);
+
+        // DOM errors do not shadow each other.
+        let diags = consolidate_diagnostics(
+            Vec::from([
+                error(CONFLICTING_KEYS, range(1, 2), "conflicting keys"),
+                error(EXPECTED_TABLE, range(1, 2), "expected table"),
+            ]),
+            &document_url(),
+        );
+        assert_eq!(diags.len(), 2);
+
+        // Lints are never merged.
+        let mut lint = error("line-too-long", range(0, 4), "line is too long");
+        lint.severity = Some(DiagnosticSeverity::INFORMATION);
+        let diags = consolidate_diagnostics(
+            Vec::from([lint, error(SYNTAX_ERROR, range(0, 4), "expected value")]),
+            &document_url(),
+        );
+        assert_eq!(diags.len(), 2);
+    }
 }
@@ -4,10 +4,10 @@ pub(crate) use initialize::*;
 mod documents;
 pub(crate) use documents::*;
 
-mod semantic_tokens;
+pub(crate) mod semantic_tokens;
 pub(crate) use semantic_tokens::*;
 
-mod folding_ranges;
+pub(crate) mod folding_ranges;
 pub(crate) use folding_ranges::*;
 
 mod document_symbols;
@@ -39,3 +39,18 @@ pub(crate) use rename::*;
 
 mod conversion;
 pub(crate) use conversion::*;
+
+mod available_keys;
+pub(crate) use available_keys::*;
+
+mod code_lens;
+pub(crate) use code_lens::*;
+
+mod debug;
+pub(crate) use debug::*;
+
+mod edits;
+pub(crate) use edits::*;
+
+mod navigate;
+pub(crate) use navigate::*;
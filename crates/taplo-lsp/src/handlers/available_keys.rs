@@ -0,0 +1,283 @@
+use crate::{
+    lsp_ext::request::{AvailableKey, AvailableKeysParams, AvailableKeysResponse},
+    query::{lookup_keys, Query},
+    world::{WorkspaceState, World},
+};
+use itertools::Itertools;
+use lsp_async_stub::{rpc::Error, util::LspExt, util::Position, Context, Params};
+use serde_json::Value;
+use std::collections::HashSet;
+use taplo::dom::{KeyOrIndex, Node};
+use taplo_common::{environment::Environment, schema::ext::schema_ext_of};
+
+#[tracing::instrument(skip_all)]
+pub(crate) async fn available_keys<E: Environment>(
+    context: Context<World<E>>,
+    params: Params<AvailableKeysParams>,
+) -> Result<AvailableKeysResponse, Error> {
+    let p = params.required()?;
+
+    let workspaces = context.workspaces.read().await;
+    let ws = workspaces.by_document(&p.document_uri);
+
+    Ok(workspace_available_keys(ws, &p).await)
+}
+
+/// The keys from the schema of the document that are not yet
+/// in the table at the position.
+async fn workspace_available_keys<E: Environment>(
+    ws: &WorkspaceState<E>,
+    p: &AvailableKeysParams,
+) -> AvailableKeysResponse {
+    let empty = AvailableKeysResponse { keys: Vec::new() };
+
+    let snapshot = ws.snapshot();
+
+    if !snapshot.config.schema.enabled {
+        return empty;
+    }
+
+    let doc = match ws.document(&p.document_uri) {
+        Ok(d) => d,
+        Err(error) => {
+            tracing::debug!(%error, "failed to get document from workspace");
+            return empty;
+        }
+    };
+
+    let Some(schema_association) = ws.schemas.associations().association_for(&p.document_uri)
+    else {
+        return empty;
+    };
+
+    let Some(offset) = doc.mapper.offset(Position::from_lsp(p.position)) else {
+        tracing::error!(position = ?p.position, "document position not found");
+        return empty;
+    };
+
+    let query = Query::at(&doc.dom, offset);
+    let (table_keys, table) = query.parent_table_or_array_table(&doc.dom);
+
+    let value = doc.value();
+
+    // The table itself and its direct children.
+    let schemas = match ws
+        .schemas
+        .possible_schemas_from(
+            &schema_association.url,
+            value,
+            &lookup_keys(doc.dom.clone(), &table_keys),
+            2,
+        )
+        .await
+    {
+        Ok(s) => s,
+        Err(error) => {
+            tracing::error!(?error, "failed to collect schemas");
+            return empty;
+        }
+    };
+
+    let required: HashSet<&str> = schemas
+        .iter()
+        .filter(|(_, relative_keys, _)| relative_keys.is_empty())
+        .filter_map(|(_, _, schema)| schema["required"].as_array())
+        .flatten()
+        .filter_map(Value::as_str)
+        .collect();
+
+    let table = table.as_table();
+
+    let keys = schemas
+        .iter()
+        .filter_map(
+            |(_, relative_keys, schema)| match relative_keys.iter().next() {
+                Some(KeyOrIndex::Key(k)) if relative_keys.len() == 1 => Some((k, schema)),
+                _ => None,
+            },
+        )
+        // Filter out keys that are already present.
+        .filter(|(key, _)| table.is_none_or(|t| t.get(key.value()).is_none()))
+        .unique_by(|(key, _)| key.value().to_string())
+        .take(p.limit.unwrap_or(usize::MAX))
+        .map(|(key, schema)| AvailableKey {
+            name: key.value().to_string(),
+            type_summary: type_summary(schema),
+            required: required.contains(key.value()),
+            deprecated: schema["deprecated"] == true,
+            default: schema
+                .get("default")
+                .filter(|v| !v.is_null())
+                .and_then(|v| serde_json::from_value::<Node>(v.clone()).ok())
+                .map(|node| node.to_toml(true, false)),
+            docs: schema_ext_of(schema)
+                .and_then(|ext| ext.docs)
+                .and_then(|docs| docs.main)
+                .or_else(|| schema["description"].as_str().map(ToString::to_string))
+                .and_then(|docs| docs.lines().next().map(ToString::to_string)),
+        })
+        .collect();
+
+    AvailableKeysResponse { keys }
+}
+
+pub(crate) fn type_summary(schema: &Value) -> Option<String> {
+    match &schema["type"] {
+        Value::String(ty) => Some(ty.clone()),
+        Value::Array(tys) => Some(tys.iter().filter_map(Value::as_str).join(" | ")),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::world::{DocumentState, DEFAULT_WORKSPACE_URL};
+    use lsp_async_stub::util::Mapper;
+    use serde_json::json;
+    use std::sync::Arc;
+    use taplo_common::{
+        environment::native::NativeEnvironment,
+        schema::associations::{priority, source, AssociationRule, SchemaAssociation},
+    };
+
+    const SCHEMA_URL: &str = "https://example.com/schema.json";
+
+    fn document_url() -> lsp_types::Url {
+        "file:///doc.toml".parse().unwrap()
+    }
+
+    /// A workspace with the document, and the schema
+    /// associated with it if there is one.
+    async fn workspace(src: &str, schema: Option<Value>) -> WorkspaceState<NativeEnvironment> {
+        let mut ws = WorkspaceState::new(NativeEnvironment::new(), DEFAULT_WORKSPACE_URL.clone());
+        ws.documents.insert(
+            document_url(),
+            DocumentState::new(
+                src,
+                taplo::parser::parse(src),
+                Mapper::new_utf16(src, false),
+            ),
+        );
+
+        if let Some(schema) = schema {
+            let url: lsp_types::Url = SCHEMA_URL.parse().unwrap();
+            drop(
+                ws.schemas
+                    .cache()
+                    .store(url.clone(), Arc::new(schema))
+                    .await,
+            );
+            ws.schemas.associations().add(
+                AssociationRule::Url(document_url()),
+                SchemaAssociation {
+                    url,
+                    meta: json!({ "source": source::MANUAL }),
+                    priority: priority::MAX,
+                },
+            );
+        }
+
+        ws
+    }
+
+    /// The available keys at the end of the first occurrence of `at`.
+    async fn keys_at(
+        ws: &WorkspaceState<NativeEnvironment>,
+        src: &str,
+        at: &str,
+        limit: Option<usize>,
+    ) -> Vec<AvailableKey> {
+        let offset = src.find(at).unwrap() + at.len();
+        let line = src[..offset].matches('\n').count();
+        let character = offset - src[..offset].rfind('\n').map_or(0, |i| i + 1);
+
+        workspace_available_keys(
+            ws,
+            &AvailableKeysParams {
+                document_uri: document_url(),
+                position: lsp_types::Position::new(
+                    line.try_into().unwrap(),
+                    character.try_into().unwrap(),
+                ),
+                limit,
+            },
+        )
+        .await
+        .keys
+    }
+
+    fn names(keys: &[AvailableKey]) -> Vec<&str> {
+        keys.iter().map(|key| key.name.as_str()).collect()
+    }
+
+    fn schema() -> Value {
+        json!({
+            "properties": {
+                "package": {
+                    "type": "object",
+                    "required": ["name"],
+                    "properties": {
+                        "name": { "type": "string", "description": "The name.\nMore details." },
+                        "version": { "type": "string", "default": "0.1.0" },
+                        "edition": { "type": ["string", "integer"], "deprecated": true },
+                        "authors": { "$ref": "#/definitions/authors" }
+                    }
+                },
+                "dependencies": { "type": "object" }
+            },
+            "definitions": {
+                "authors": {
+                    "type": "array",
+                    "default": ["a"],
+                    "x-taplo": { "docs": { "main": "The authors." } }
+                }
+            }
+        })
+    }
+
+    #[tokio::test]
+    async fn table_with_schema() {
+        let src = "[package]\nversion = \"1.0.0\"\n\n[dependencies]\n";
+        let ws = workspace(src, Some(schema())).await;
+
+        // Keys that are already in the table are left out.
+        let keys = keys_at(&ws, src, "[package]\n", None).await;
+        assert_eq!(names(&keys), ["name", "edition", "authors"]);
+
+        let name = &keys[0];
+        assert!(name.required);
+        assert!(!name.deprecated);
+        assert_eq!(name.type_summary.as_deref(), Some("string"));
+        assert_eq!(name.docs.as_deref(), Some("The name."));
+        assert_eq!(name.default, None);
+
+        let edition = &keys[1];
+        assert!(!edition.required);
+        assert!(edition.deprecated);
+        assert_eq!(edition.type_summary.as_deref(), Some("string | integer"));
+        assert_eq!(edition.docs, None);
+
+        // The schema is resolved from the reference.
+        let authors = &keys[2];
+        assert_eq!(authors.type_summary.as_deref(), Some("array"));
+        assert_eq!(authors.default.as_deref(), Some(r#"[ "a" ]"#));
+        assert_eq!(authors.docs.as_deref(), Some("The authors."));
+
+        assert_eq!(
+            names(&keys_at(&ws, src, "[package]\n", Some(1)).await),
+            ["name"]
+        );
+
+        // Tables without properties in the schema.
+        assert!(keys_at(&ws, src, "[dependencies]\n", None).await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn table_without_schema() {
+        let src = "[package]\nversion = \"1.0.0\"\n";
+        let ws = workspace(src, None).await;
+
+        assert!(keys_at(&ws, src, "[package]\n", None).await.is_empty());
+    }
+}
@@ -0,0 +1,413 @@
+use crate::world::World;
+use lsp_async_stub::{
+    rpc::Error,
+    util::{LspExt, Mapper},
+    Context, Params, RequestWriter,
+};
+use lsp_types::{
+    request::ApplyWorkspaceEdit, ApplyWorkspaceEditParams, CodeLens, CodeLensParams, Command,
+    ExecuteCommandParams, TextEdit, Url, WorkspaceEdit,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::HashMap;
+use taplo::{
+    dom::{
+        node::{Array, DomNode},
+        FromSyntax, Keys, Node,
+    },
+    rowan::TextRange,
+    syntax::{SyntaxElement, SyntaxKind::*, SyntaxNode},
+    util::syntax::section_ranges,
+};
+use taplo_common::environment::Environment;
+
+pub const SORT_TABLE_COMMAND: &str = "taplo.sortTable";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct SortTableArgs {
+    uri: Url,
+    range: lsp_types::Range,
+}
+
+#[tracing::instrument(skip_all)]
+pub(crate) async fn code_lens<E: Environment>(
+    context: Context<World<E>>,
+    params: Params<CodeLensParams>,
+) -> Result<Option<Vec<CodeLens>>, Error> {
+    let p = params.required()?;
+
+    let workspaces = context.workspaces.read().await;
+    let ws = workspaces.by_document(&p.text_document.uri);
+    let snapshot = ws.snapshot();
+
+    if !snapshot.config.code_lens.enabled {
+        return Ok(None);
+    }
+
+    let doc = match ws.document(&p.text_document.uri) {
+        Ok(d) => d,
+        Err(error) => {
+            tracing::debug!(%error, "failed to get document from workspace");
+            return Ok(None);
+        }
+    };
+
+    let syntax = doc.dom.syntax().unwrap().as_node().unwrap();
+
+    Ok(Some(create_code_lenses(
+        &p.text_document.uri,
+        syntax,
+        &doc.dom,
+        &doc.mapper,
+    )))
+}
+
+#[tracing::instrument(skip_all)]
+pub(crate) async fn code_lens_resolve<E: Environment>(
+    _context: Context<World<E>>,
+    params: Params<CodeLens>,
+) -> Result<CodeLens, Error> {
+    params.required()
+}
+
+#[tracing::instrument(skip_all)]
+pub(crate) async fn execute_command<E: Environment>(
+    mut context: Context<World<E>>,
+    params: Params<ExecuteCommandParams>,
+) -> Result<Option<Value>, Error> {
+    let p = params.required()?;
+
+    if p.command != SORT_TABLE_COMMAND {
+        return Err(Error::invalid_params());
+    }
+
+    let args: SortTableArgs = match p.arguments.into_iter().next().map(serde_json::from_value) {
+        Some(Ok(args)) => args,
+        _ => return Err(Error::invalid_params()),
+    };
+
+    let workspaces = context.workspaces.read().await;
+    let ws = workspaces.by_document(&args.uri);
+    let doc = match ws.document(&args.uri) {
+        Ok(d) => d,
+        Err(error) => {
+            tracing::debug!(%error, "failed to get document from workspace");
+            return Ok(None);
+        }
+    };
+
+    let Some(header_range) = doc
+        .mapper
+        .text_range(lsp_async_stub::util::Range::from_lsp(args.range))
+    else {
+        return Ok(None);
+    };
+
+    let syntax = doc.dom.syntax().unwrap().as_node().unwrap();
+
+    let edit = section_ranges(syntax)
+        .into_iter()
+        .find(|(header, _)| header.text_range() == header_range)
+        .and_then(|(header, section)| sort_entries_edit(&header, section))
+        .map(|(range, new_text)| TextEdit {
+            range: doc.mapper.range(range).unwrap().into_lsp(),
+            new_text,
+        });
+
+    drop(workspaces);
+
+    if let Some(edit) = edit {
+        if let Err(error) = context
+            .write_request::<ApplyWorkspaceEdit, _>(Some(ApplyWorkspaceEditParams {
+                label: Some("Sort keys".into()),
+                edit: WorkspaceEdit {
+                    changes: Some(HashMap::from([(args.uri, vec![edit])])),
+                    ..Default::default()
+                },
+            }))
+            .await
+        {
+            tracing::error!(%error, "failed to apply edit");
+        }
+    }
+
+    Ok(None)
+}
+
+fn create_code_lenses(
+    uri: &Url,
+    syntax: &SyntaxNode,
+    dom: &Node,
+    mapper: &Mapper,
+) -> Vec<CodeLens> {
+    let mut lenses = Vec::new();
+
+    // The amount of array of tables headers seen so far for each key.
+    let mut array_counts: Vec<(Keys, usize)> = Vec::new();
+
+    for (header, section) in section_ranges(syntax) {
+        let Some(key_syntax) = header.first_child() else {
+            continue;
+        };
+
+        let keys = Keys::from_syntax(key_syntax.into());
+
+        if header.kind() == TABLE_ARRAY_HEADER {
+            // A new element is opened, so the arrays of tables in it start over.
+            array_counts.retain(|(k, _)| !(k.len() > keys.len() && k.contains(&keys)));
+
+            match array_counts.iter_mut().find(|(k, _)| *k == keys) {
+                Some((_, count)) => *count += 1,
+                None => array_counts.push((keys.clone(), 1)),
+            }
+        }
+
+        let Some((node, array)) = header_node(dom, &keys, &array_counts) else {
+            continue;
+        };
+
+        let range = mapper.range(header.text_range()).unwrap().into_lsp();
+
+        if let Some(table) = node.as_table() {
+            lenses.push(CodeLens {
+                range,
+                command: Some(Command {
+                    title: plural(table.entries().read().len(), "entry", "entries"),
+                    command: String::new(),
+                    arguments: None,
+                }),
+                data: None,
+            });
+        }
+
+        if header.kind() == TABLE_ARRAY_HEADER {
+            if let Some(arr) = array {
+                lenses.push(CodeLens {
+                    range,
+                    command: Some(Command {
+                        title: plural(arr.items().read().len(), "element", "elements"),
+                        command: String::new(),
+                        arguments: None,
+                    }),
+                    data: None,
+                });
+            }
+        }
+
+        let keys = entry_items(&header, section)
+            .into_iter()
+            .map(|(key, _)| key)
+            .collect::<Vec<_>>();
+
+        if keys.len() >= 3 && keys.windows(2).any(|w| w[0] > w[1]) {
+            lenses.push(CodeLens {
+                range,
+                command: Some(Command {
+                    title: "Sort keys".into(),
+                    command: SORT_TABLE_COMMAND.into(),
+                    arguments: Some(vec![serde_json::to_value(SortTableArgs {
+                        uri: uri.clone(),
+                        range,
+                    })
+                    .unwrap()]),
+                }),
+                data: None,
+            });
+        }
+    }
+
+    lenses
+}
+
+/// Find the table of a header, for arrays of tables
+/// the currently open element is used.
+///
+/// The array is also returned if the last key is an array of tables.
+fn header_node(
+    dom: &Node,
+    keys: &Keys,
+    array_counts: &[(Keys, usize)],
+) -> Option<(Node, Option<Array>)> {
+    let mut node = dom.clone();
+    let mut array = None;
+
+    for (idx, key) in keys.iter().enumerate() {
+        node = node.try_get(key).ok()?;
+        array = node.as_array().cloned();
+
+        if let Some(arr) = &array {
+            let array_keys = Keys::new(keys.iter().take(idx + 1).cloned());
+            let (_, count) = array_counts.iter().find(|(k, _)| *k == array_keys)?;
+            let item = arr.items().read().get(count.checked_sub(1)?).cloned()?;
+            node = item;
+        }
+    }
+
+    Some((node, array))
+}
+
+/// The entries of a section with their leading comments,
+/// the keys are the texts of the entry keys.
+fn entry_items(header: &SyntaxNode, section: TextRange) -> Vec<(String, TextRange)> {
+    let mut items = Vec::new();
+    let mut comments: Option<TextRange> = None;
+
+    for element in header
+        .siblings_with_tokens(taplo::rowan::Direction::Next)
+        .skip(1)
+        .take_while(|e| e.text_range().end() <= section.end())
+    {
+        match element.kind() {
+            COMMENT => {
+                let range = element.text_range();
+                comments = Some(comments.map_or(range, |c| c.cover(range)));
+            }
+            // Comments separated by a blank line are not attached to the entry.
+            NEWLINE if element.to_string().matches('\n').count() > 1 => {
+                comments = None;
+            }
+            ENTRY => {
+                let key = match &element {
+                    SyntaxElement::Node(n) => n
+                        .first_child()
+                        .map(|k| k.text().to_string())
+                        .unwrap_or_default(),
+                    SyntaxElement::Token(_) => continue,
+                };
+
+                let range = element.text_range();
+                items.push((key, comments.take().map_or(range, |c| c.cover(range))));
+            }
+            _ => {}
+        }
+    }
+
+    items
+}
+
+/// A text edit that sorts the entries of a section by their keys.
+fn sort_entries_edit(header: &SyntaxNode, section: TextRange) -> Option<(TextRange, String)> {
+    let mut items = entry_items(header, section);
+
+    let range = items.iter().map(|(_, r)| *r).reduce(TextRange::cover)?;
+
+    let text = header.parent()?.text().to_string();
+
+    let newline = if text.contains("\r\n") { "\r\n" } else { "\n" };
+
+    items.sort_by(|(a, _), (b, _)| a.cmp(b));
+
+    let sorted = items
+        .iter()
+        .map(|(_, r)| &text[std::ops::Range::<usize>::from(*r)])
+        .collect::<Vec<_>>()
+        .join(newline);
+
+    Some((range, sorted))
+}
+
+fn plural(count: usize, singular: &str, plural: &str) -> String {
+    if count == 1 {
+        format!("{count} {singular}")
+    } else {
+        format!("{count} {plural}")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use taplo::parser::parse;
+
+    fn code_lenses(src: &str) -> Vec<CodeLens> {
+        let dom = parse(src).into_dom();
+        let syntax = dom.syntax().unwrap().as_node().unwrap().clone();
+        let mapper = Mapper::new_utf16(src, false);
+        let uri = "file:///doc.toml".parse().unwrap();
+
+        create_code_lenses(&uri, &syntax, &dom, &mapper)
+    }
+
+    type LensRange = ((u32, u32), (u32, u32));
+
+    /// The ranges of the lenses as `(line, character)` pairs with their titles.
+    fn lenses(src: &str) -> Vec<(LensRange, String)> {
+        code_lenses(src)
+            .into_iter()
+            .map(|lens| {
+                let range = (
+                    (lens.range.start.line, lens.range.start.character),
+                    (lens.range.end.line, lens.range.end.character),
+                );
+                (range, lens.command.unwrap().title)
+            })
+            .collect()
+    }
+
+    fn lens(line: u32, len: u32, title: &str) -> (LensRange, String) {
+        (((line, 0), (line, len)), title.into())
+    }
+
+    #[test]
+    fn table_entries() {
+        let src = "x = 1\n[a]\ny = 1\nz.w = 2\n\n[b]\n[a.c]\nv = 3\n";
+        assert_eq!(
+            lenses(src),
+            [
+                lens(1, 3, "3 entries"),
+                lens(5, 3, "0 entries"),
+                lens(6, 5, "1 entry"),
+            ]
+        );
+    }
+
+    #[test]
+    fn arrays_of_tables() {
+        let src = "[[a]]\n[[a.b]]\nx = 1\n[[a]]\ny = 2\n[[a.b]]\n[a.b.c]\n[[a.b]]\n";
+        assert_eq!(
+            lenses(src),
+            [
+                lens(0, 5, "1 entry"),
+                lens(0, 5, "2 elements"),
+                lens(1, 7, "1 entry"),
+                lens(1, 7, "1 element"),
+                lens(3, 5, "2 entries"),
+                lens(3, 5, "2 elements"),
+                // The elements of the array in the second element of `a`.
+                lens(5, 7, "1 entry"),
+                lens(5, 7, "2 elements"),
+                lens(6, 7, "0 entries"),
+                lens(7, 7, "0 entries"),
+                lens(7, 7, "2 elements"),
+            ]
+        );
+    }
+
+    #[test]
+    fn sort_keys() {
+        let src = "[a]\nc = 1\nb = 2\na = 3\n[b]\nb = 1\na = 2\n[c]\na = 1\nb = 2\nc = 3\n";
+        let lenses = code_lenses(src);
+
+        // Only unsorted tables with at least three keys can be sorted.
+        let sort = lenses
+            .iter()
+            .filter_map(|lens| lens.command.as_ref())
+            .filter(|command| command.command == SORT_TABLE_COMMAND)
+            .collect::<Vec<_>>();
+        assert_eq!(sort.len(), 1);
+        assert_eq!(sort[0].title, "Sort keys");
+
+        let args: SortTableArgs =
+            serde_json::from_value(sort[0].arguments.as_ref().unwrap()[0].clone()).unwrap();
+        assert_eq!(args.uri.as_str(), "file:///doc.toml");
+        assert_eq!(
+            args.range,
+            lsp_types::Range::new(
+                lsp_types::Position::new(0, 0),
+                lsp_types::Position::new(0, 3)
+            )
+        );
+    }
+}
@@ -1,3 +1,4 @@
+use itertools::Itertools;
 use lsp_async_stub::{
     rpc::Error,
     util::{LspExt, Position},
@@ -9,16 +10,25 @@ use lsp_types::{
 };
 use serde_json::Value;
 use std::borrow::Cow;
+use std::collections::HashSet;
 use std::fmt::Write as _;
-use taplo::dom::{node::TableKind, Keys, Node};
+use std::sync::Arc;
+use taplo::{
+    dom::{
+        node::{ArrayKind, DomNode, Key, TableKind},
+        KeyOrIndex, Keys, Node,
+    },
+    syntax::SyntaxNode,
+};
 use taplo_common::{
     environment::Environment,
     schema::{ext::schema_ext_of, ValueExt},
 };
 
+use super::{report_cyclic_references, type_summary};
 use crate::{
     query::{lookup_keys, Query},
-    world::World,
+    world::{DocumentState, World},
 };
 
 #[tracing::instrument(skip_all)]
@@ -27,16 +37,22 @@ pub async fn completion<E: Environment>(
     params: Params<CompletionParams>,
 ) -> Result<Option<CompletionResponse>, Error> {
     let p = params.required()?;
+    let document_uri = p.text_document_position.text_document.uri.clone();
+
+    let completions = document_completions(context.clone(), p).await;
+    report_cyclic_references(context, &document_uri).await;
+    completions
+}
 
+async fn document_completions<E: Environment>(
+    context: Context<World<E>>,
+    p: CompletionParams,
+) -> Result<Option<CompletionResponse>, Error> {
     let document_uri = p.text_document_position.text_document.uri;
 
     let workspaces = context.workspaces.read().await;
     let ws = workspaces.by_document(&document_uri);
-
-    // All completions are tied to schemas.
-    if !ws.config.schema.enabled {
-        return Ok(None);
-    }
+    let snapshot = ws.snapshot();
 
     let doc = match ws.document(&document_uri) {
         Ok(d) => d,
@@ -46,11 +62,6 @@ pub async fn completion<E: Environment>(
         }
     };
 
-    let schema_association = match ws.schemas.associations().association_for(&document_uri) {
-        Some(ass) => ass,
-        None => return Ok(None),
-    };
-
     let position = p.text_document_position.position;
     let offset = match doc.mapper.offset(Position::from_lsp(position)) {
         Some(ofs) => ofs,
@@ -62,14 +73,22 @@ pub async fn completion<E: Environment>(
 
     let query = Query::at(&doc.dom, offset);
 
-    let value = match serde_json::to_value(&doc.dom) {
-        Ok(v) => v,
-        Err(error) => {
-            tracing::warn!(%error, "unable to serialize DOM");
-            Value::Null
+    let schema_association = if snapshot.config.schema.enabled {
+        ws.schemas.associations().association_for(&document_uri)
+    } else {
+        None
+    };
+
+    let schema_association = match schema_association {
+        Some(ass) => ass,
+        None if snapshot.config.completion.from_document => {
+            return Ok(document_key_completions(doc, &query));
         }
+        None => return Ok(None),
     };
 
+    let value = doc.value();
+
     if query.in_table_header() {
         let key_count = query.header_keys().len();
 
@@ -77,9 +96,9 @@ pub async fn completion<E: Environment>(
             .schemas
             .possible_schemas_from(
                 &schema_association.url,
-                &value,
+                value,
                 &Keys::empty(),
-                key_count + ws.config.completion.max_keys + 1,
+                key_count + snapshot.config.completion.max_keys + 1,
             )
             .await
             .map(|s| {
@@ -111,25 +130,22 @@ pub async fn completion<E: Environment>(
             .cloned()
             .unwrap_or_else(|| (Keys::empty(), doc.dom.clone()));
 
+        let closing = if query.header_closed() { "" } else { "]" };
+
         return Ok(Some(CompletionResponse::Array(
-            object_schemas
-                // Filter out existing tables in the dom.
-                .filter(|(full_key, _, _)| match doc.dom.path(full_key) {
-                    Some(n) => {
-                        node.0 == *full_key
-                            || n.as_table()
-                                .map_or(false, |t| t.kind() == TableKind::Pseudo)
-                    }
-                    None => true,
-                })
-                .map(|(full_key, _, s)| CompletionItem {
+            with_alternatives(object_schemas)
+                .into_iter()
+                .filter(|(full_key, ..)| !key_exists(&doc.dom, full_key, &node.0))
+                .map(|(full_key, _, s, alternative)| CompletionItem {
                     label: full_key.to_string(),
                     kind: Some(CompletionItemKind::STRUCT),
+                    detail: alternative,
                     documentation: documentation(&s),
+                    insert_text: Some(format!("{full_key}{closing}")),
                     text_edit: key_range.map(|r| {
                         CompletionTextEdit::Edit(TextEdit {
                             range: doc.mapper.range(r).unwrap().into_lsp(),
-                            new_text: full_key.to_string(),
+                            new_text: format!("{full_key}{closing}"),
                         })
                     }),
                     ..Default::default()
@@ -144,9 +160,9 @@ pub async fn completion<E: Environment>(
             .schemas
             .possible_schemas_from(
                 &schema_association.url,
-                &value,
+                value,
                 &Keys::empty(),
-                key_count + ws.config.completion.max_keys + 1,
+                key_count + snapshot.config.completion.max_keys + 1,
             )
             .await
             .map(|s| {
@@ -170,22 +186,34 @@ pub async fn completion<E: Environment>(
             }
         });
 
-        return Ok(Some(CompletionResponse::Array(
-            array_of_objects_schemas
-                .map(|(full_key, _, s)| CompletionItem {
-                    label: full_key.to_string(),
-                    kind: Some(CompletionItemKind::STRUCT),
-                    documentation: documentation(&s),
-                    text_edit: key_range.map(|r| {
-                        CompletionTextEdit::Edit(TextEdit {
-                            range: doc.mapper.range(r).unwrap().into_lsp(),
-                            new_text: full_key.to_string(),
-                        })
-                    }),
-                    ..Default::default()
-                })
-                .collect(),
-        )));
+        let closing = if query.header_closed() { "" } else { "]]" };
+
+        let mut completions: Vec<_> = with_alternatives(array_of_objects_schemas)
+            .into_iter()
+            .map(|(full_key, _, s, alternative)| CompletionItem {
+                label: full_key.to_string(),
+                kind: Some(CompletionItemKind::STRUCT),
+                detail: alternative,
+                documentation: documentation(&s),
+                insert_text: Some(format!("{full_key}{closing}")),
+                text_edit: key_range.map(|r| {
+                    CompletionTextEdit::Edit(TextEdit {
+                        range: doc.mapper.range(r).unwrap().into_lsp(),
+                        new_text: format!("{full_key}{closing}"),
+                    })
+                }),
+                ..Default::default()
+            })
+            .collect();
+
+        // Arrays of tables that are not in the schema.
+        for item in document_header_completions(doc, &query) {
+            if !completions.iter().any(|c| c.label == item.label) {
+                completions.push(item);
+            }
+        }
+
+        return Ok(Some(CompletionResponse::Array(completions)));
     }
 
     if query.empty_line() {
@@ -195,9 +223,9 @@ pub async fn completion<E: Environment>(
             .schemas
             .possible_schemas_from(
                 &schema_association.url,
-                &value,
+                value,
                 &lookup_keys(doc.dom.clone(), &parent_table.0),
-                ws.config.completion.max_keys + 1,
+                snapshot.config.completion.max_keys + 1,
             )
             .await
         {
@@ -209,17 +237,12 @@ pub async fn completion<E: Environment>(
         };
 
         return Ok(Some(CompletionResponse::Array(
-            schemas
+            with_alternatives(schemas)
                 .into_iter()
-                // Filter out existing items.
-                .filter(|(full_key, _, _)| match doc.dom.path(full_key) {
-                    Some(n) => n
-                        .as_table()
-                        .map_or(false, |t| t.kind() == TableKind::Pseudo),
-                    None => true,
-                })
-                .map(|(_, relative_keys, schema)| CompletionItem {
+                .filter(|(full_key, ..)| !key_exists(&doc.dom, full_key, &Keys::empty()))
+                .map(|(_, relative_keys, schema, alternative)| CompletionItem {
                     label: relative_keys.to_string(),
+                    detail: alternative,
                     kind: Some(CompletionItemKind::VARIABLE),
                     documentation: documentation(&schema),
                     insert_text_format: Some(InsertTextFormat::SNIPPET),
@@ -241,13 +264,16 @@ pub async fn completion<E: Environment>(
 
         parent_keys = parent_keys.skip_right(entry_keys.len());
 
+        let parent_keys = lookup_keys(doc.dom.clone(), &parent_keys);
+        let typed_keys = parent_keys.extend(entry_keys.clone());
+
         let schemas = match ws
             .schemas
             .possible_schemas_from(
                 &schema_association.url,
-                &value,
-                &lookup_keys(doc.dom.clone(), &parent_keys),
-                entry_keys.len() + ws.config.completion.max_keys + 1,
+                value,
+                &parent_keys,
+                entry_keys.len() + snapshot.config.completion.max_keys + 1,
             )
             .await
         {
@@ -263,10 +289,12 @@ pub async fn completion<E: Environment>(
         let has_eq = query.entry_has_eq();
 
         return Ok(Some(CompletionResponse::Array(
-            schemas
+            with_alternatives(schemas)
                 .into_iter()
-                .map(|(_, relative_keys, schema)| CompletionItem {
+                .filter(|(full_key, ..)| !key_exists(&doc.dom, full_key, &typed_keys))
+                .map(|(_, relative_keys, schema, alternative)| CompletionItem {
                     label: relative_keys.to_string(),
+                    detail: alternative,
                     kind: Some(CompletionItemKind::VARIABLE),
                     documentation: documentation(&schema),
                     text_edit: key_range.map(|r| {
@@ -304,9 +332,9 @@ pub async fn completion<E: Environment>(
                 .schemas
                 .possible_schemas_from(
                     &schema_association.url,
-                    &value,
+                    value,
                     &lookup_keys(doc.dom.clone(), path),
-                    ws.config.completion.max_keys + 1,
+                    snapshot.config.completion.max_keys + 1,
                 )
                 .await
             {
@@ -318,17 +346,12 @@ pub async fn completion<E: Environment>(
             };
 
             return Ok(Some(CompletionResponse::Array(
-                schemas
+                with_alternatives(schemas)
                     .into_iter()
-                    // Filter out existing items.
-                    .filter(|(full_key, _, _)| match doc.dom.path(full_key) {
-                        Some(n) => n
-                            .as_table()
-                            .map_or(false, |t| t.kind() == TableKind::Pseudo),
-                        None => true,
-                    })
-                    .map(|(_, relative_keys, schema)| CompletionItem {
+                    .filter(|(full_key, ..)| !key_exists(&doc.dom, full_key, &Keys::empty()))
+                    .map(|(_, relative_keys, schema, alternative)| CompletionItem {
                         label: relative_keys.to_string(),
+                        detail: alternative,
                         kind: Some(CompletionItemKind::VARIABLE),
                         documentation: documentation(&schema),
                         insert_text_format: Some(InsertTextFormat::SNIPPET),
@@ -351,9 +374,9 @@ pub async fn completion<E: Environment>(
             .schemas
             .possible_schemas_from(
                 &schema_association.url,
-                &value,
+                value,
                 &path,
-                ws.config.completion.max_keys + 1,
+                snapshot.config.completion.max_keys + 1,
             )
             .await
         {
@@ -364,7 +387,13 @@ pub async fn completion<E: Environment>(
             }
         };
 
-        let range = if query.in_array() {
+        // A string that is being typed is replaced entirely,
+        // so that it is not quoted twice.
+        let unterminated_string = query.unterminated_string();
+
+        let range = if let Some(s) = &unterminated_string {
+            doc.mapper.range(s.text_range()).map(LspExt::into_lsp)
+        } else if query.in_array() {
             None
         } else {
             query
@@ -374,15 +403,15 @@ pub async fn completion<E: Environment>(
                 .map(lsp_async_stub::util::LspExt::into_lsp)
         };
 
+        let single_quote = unterminated_string.map_or_else(
+            || query.is_single_quote_value(),
+            |s| s.text().starts_with('\''),
+        );
+
         let mut completions = Vec::new();
 
         for (_, _, schema) in schemas {
-            add_value_completions(
-                &schema,
-                range,
-                &mut completions,
-                query.is_single_quote_value(),
-            );
+            add_value_completions(&schema, range, &mut completions, single_quote);
         }
 
         return Ok(Some(CompletionResponse::Array(completions)));
@@ -400,13 +429,16 @@ pub async fn completion<E: Environment>(
 
     parent_keys = parent_keys.skip_right(entry_keys.len());
 
+    let parent_keys = lookup_keys(doc.dom.clone(), &parent_keys);
+    let typed_keys = parent_keys.extend(entry_keys.clone());
+
     let schemas = match ws
         .schemas
         .possible_schemas_from(
             &schema_association.url,
-            &value,
-            &lookup_keys(doc.dom.clone(), &parent_keys),
-            ws.config.completion.max_keys + 1,
+            value,
+            &parent_keys,
+            snapshot.config.completion.max_keys + 1,
         )
         .await
     {
@@ -418,17 +450,12 @@ pub async fn completion<E: Environment>(
     };
 
     Ok(Some(CompletionResponse::Array(
-        schemas
+        with_alternatives(schemas)
             .into_iter()
-            // Filter out existing items.
-            .filter(|(full_key, _, _)| match doc.dom.path(full_key) {
-                Some(n) => n
-                    .as_table()
-                    .map_or(false, |t| t.kind() == TableKind::Pseudo),
-                None => true,
-            })
-            .map(|(_, relative_keys, schema)| CompletionItem {
+            .filter(|(full_key, ..)| !key_exists(&doc.dom, full_key, &typed_keys))
+            .map(|(_, relative_keys, schema, alternative)| CompletionItem {
                 label: relative_keys.to_string(),
+                detail: alternative,
                 kind: Some(CompletionItemKind::VARIABLE),
                 documentation: documentation(&schema),
                 insert_text_format: Some(InsertTextFormat::SNIPPET),
@@ -446,6 +473,193 @@ pub async fn completion<E: Environment>(
     )))
 }
 
+/// Completions of keys that are already used in the document,
+/// for documents without a schema.
+///
+/// Table headers are completed with the existing tables, and entry keys
+/// with the keys of the sibling tables (e.g. the other `[[bin]]` tables)
+/// that are not in the current table yet.
+fn document_key_completions(doc: &DocumentState, query: &Query) -> Option<CompletionResponse> {
+    if query.in_table_header() || query.in_table_array_header() {
+        return Some(CompletionResponse::Array(document_header_completions(
+            doc, query,
+        )));
+    }
+
+    if query.in_entry_value() || query.is_inline() {
+        return None;
+    }
+
+    let (table_keys, table) = query
+        .before
+        .as_ref()
+        .or(query.after.as_ref())
+        .map_or_else(|| (Keys::empty(), doc.dom.clone()), |p| p.table.clone());
+
+    let entry_keys = query.entry_keys();
+    let typed_key = entry_keys
+        .iter()
+        .next()
+        .and_then(KeyOrIndex::as_key)
+        .map(Key::value);
+
+    // The keys of the current table, except the one that is being typed.
+    let existing_keys: HashSet<String> = table
+        .as_table()
+        .map(|t| {
+            t.entries()
+                .read()
+                .iter()
+                .map(|(key, _)| key.value())
+                .filter(|key| Some(*key) != typed_key)
+                .map(String::from)
+                .collect()
+        })
+        .unwrap_or_default();
+
+    let key_range = if entry_keys.is_empty() {
+        None
+    } else {
+        doc.mapper
+            .range(entry_keys.all_text_range())
+            .map(LspExt::into_lsp)
+    };
+    let has_eq = query.entry_has_eq();
+
+    let completions = sibling_tables(&doc.dom, &table_keys)
+        .iter()
+        .filter_map(Node::as_table)
+        .flat_map(|t| {
+            t.entries()
+                .read()
+                .iter()
+                .map(|(key, _)| key.clone())
+                .collect::<Vec<_>>()
+        })
+        .filter(|key| !existing_keys.contains(key.value()))
+        .unique_by(|key| key.value().to_string())
+        .map(|key| {
+            let new_text = if has_eq {
+                format!("{key} ")
+            } else {
+                format!("{key} = $0")
+            };
+
+            CompletionItem {
+                label: key.to_string(),
+                kind: Some(CompletionItemKind::VARIABLE),
+                insert_text_format: Some(InsertTextFormat::SNIPPET),
+                text_edit: key_range.map(|range| {
+                    CompletionTextEdit::Edit(TextEdit {
+                        range,
+                        new_text: new_text.clone(),
+                    })
+                }),
+                insert_text: Some(new_text),
+                ..Default::default()
+            }
+        })
+        .collect();
+
+    Some(CompletionResponse::Array(completions))
+}
+
+/// Completions of the tables in a table header, or the arrays of tables
+/// in an array of tables header, that are already in the document.
+fn document_header_completions(doc: &DocumentState, query: &Query) -> Vec<CompletionItem> {
+    let header_array = query.in_table_array_header();
+    let header_key = query.header_key();
+    let header = header_key.as_ref().and_then(SyntaxNode::parent);
+    let key_range = header_key.map(|k| k.text_range()).filter(|r| !r.is_empty());
+
+    let closing = match (query.header_closed(), header_array) {
+        (true, _) => "",
+        (false, false) => "]",
+        (false, true) => "]]",
+    };
+
+    doc.dom
+        .flat_iter()
+        .filter(|(keys, node)| {
+            if !keys.iter().all(KeyOrIndex::is_key) {
+                return false;
+            }
+
+            // Skip the table of the header that is being typed.
+            let current =
+                header.is_some() && node.syntax().and_then(|s| s.as_node()) == header.as_ref();
+
+            match node {
+                Node::Table(t) => !header_array && !current && t.kind() != TableKind::Inline,
+                Node::Array(arr) => {
+                    header_array
+                        && arr.kind() == ArrayKind::Tables
+                        && !(current && arr.items().read().len() == 1)
+                }
+                _ => false,
+            }
+        })
+        .map(|(keys, _)| keys.to_string())
+        .unique()
+        .map(|keys| CompletionItem {
+            label: keys.clone(),
+            kind: Some(CompletionItemKind::STRUCT),
+            insert_text: Some(format!("{keys}{closing}")),
+            text_edit: key_range.and_then(|r| {
+                Some(CompletionTextEdit::Edit(TextEdit {
+                    range: doc.mapper.range(r)?.into_lsp(),
+                    new_text: format!("{keys}{closing}"),
+                }))
+            }),
+            ..Default::default()
+        })
+        .collect()
+}
+
+/// The other tables in the same array of tables as the table at the given path,
+/// or in the same parent table unless it is the root table.
+fn sibling_tables(root: &Node, table_keys: &Keys) -> Vec<Node> {
+    let last = match table_keys.iter().last() {
+        Some(last) => last,
+        None => return Vec::new(),
+    };
+
+    match (root.path(&table_keys.skip_right(1)), last) {
+        (Some(Node::Array(arr)), KeyOrIndex::Index(idx)) => arr
+            .items()
+            .read()
+            .iter()
+            .enumerate()
+            .filter(|(i, _)| i != idx)
+            .map(|(_, node)| node.clone())
+            .collect(),
+        (Some(Node::Table(parent)), KeyOrIndex::Key(key)) if table_keys.len() > 1 => parent
+            .entries()
+            .read()
+            .iter()
+            .filter(|(k, _)| k != key)
+            .map(|(_, node)| node.clone())
+            .collect(),
+        _ => Vec::new(),
+    }
+}
+
+/// Whether the key is already in the document, so that completing it
+/// would create a duplicate key.
+///
+/// Tables that were only created by dotted keys can still be extended,
+/// and the keys that are being typed at the cursor do not count.
+fn key_exists(dom: &Node, full_key: &Keys, typed_keys: &Keys) -> bool {
+    if full_key == typed_keys {
+        return false;
+    }
+
+    dom.path(full_key).map_or(false, |node| {
+        node.as_table()
+            .map_or(true, |t| t.kind() != TableKind::Pseudo)
+    })
+}
+
 fn documentation(schema: &Value) -> Option<Documentation> {
     if let Some(ext) = schema_ext_of(schema) {
         if let Some(docs) = ext.docs {
@@ -468,6 +682,55 @@ fn documentation(schema: &Value) -> Option<Documentation> {
     None
 }
 
+/// The schemas with the alternative they come from, if there are
+/// multiple schemas for the same key (e.g. in a `oneOf`), so that the
+/// completions can be told apart.
+fn with_alternatives(
+    schemas: impl IntoIterator<Item = (Keys, Keys, Arc<Value>)>,
+) -> Vec<(Keys, Keys, Arc<Value>, Option<String>)> {
+    let schemas: Vec<_> = schemas.into_iter().collect();
+    let counts = schemas.iter().map(|(full_key, ..)| full_key).counts();
+
+    schemas
+        .iter()
+        .map(|(full_key, relative_keys, schema)| {
+            let alternative = if counts[full_key] > 1 {
+                alternative_label(schema)
+            } else {
+                None
+            };
+
+            (
+                full_key.clone(),
+                relative_keys.clone(),
+                schema.clone(),
+                alternative,
+            )
+        })
+        .collect()
+}
+
+/// The title of the schema, the constant values of its properties
+/// (e.g. `kind = "git"`), or its type.
+fn alternative_label(schema: &Value) -> Option<String> {
+    if let Some(title) = schema["title"].as_str() {
+        return Some(title.into());
+    }
+
+    let consts = schema["properties"]
+        .as_object()
+        .into_iter()
+        .flatten()
+        .filter_map(|(key, property)| Some(format!("{key} = {}", property.get("const")?)))
+        .join(", ");
+
+    if !consts.is_empty() {
+        return Some(consts);
+    }
+
+    type_summary(schema)
+}
+
 fn add_value_completions(
     schema: &Value,
     range: Option<Range>,
@@ -525,63 +788,37 @@ fn add_value_completions(
     }
 
     if let Some(const_value) = schema.get("const") {
-        if !const_value.is_null() {
-            let node: Node = serde_json::from_value(const_value.clone()).unwrap();
-            let toml_value = node.to_toml(true, single_quote);
-            completions.push(CompletionItem {
-                label: toml_value.clone(),
-                kind: Some(match node {
-                    Node::Table(_) => CompletionItemKind::STRUCT,
-                    _ => CompletionItemKind::VALUE,
-                }),
-                documentation: ext_docs
-                    .const_value
-                    .or_else(|| schema_docs.clone())
-                    .map(|value| {
-                        Documentation::MarkupContent(MarkupContent {
-                            kind: lsp_types::MarkupKind::Markdown,
-                            value,
-                        })
-                    }),
-                text_edit: range.map(|range| {
-                    CompletionTextEdit::Edit(TextEdit {
-                        range,
-                        new_text: toml_value,
-                    })
-                }),
-                ..Default::default()
-            });
-        }
+        completions.extend(literal_completion(
+            const_value,
+            ext_docs.const_value.or_else(|| schema_docs.clone()),
+            range,
+            single_quote,
+        ));
 
         return;
     }
 
     if let Some(default_value) = schema.get("default") {
-        if !default_value.is_null() {
-            let node: Node = serde_json::from_value(default_value.clone()).unwrap();
-            let toml_value = node.to_toml(true, single_quote);
-            completions.push(CompletionItem {
-                label: toml_value.clone(),
-                kind: Some(match node {
-                    Node::Table(_) => CompletionItemKind::STRUCT,
-                    _ => CompletionItemKind::VALUE,
-                }),
-                documentation: ext_docs.default_value.or_else(|| schema_docs.clone()).map(
-                    |value| {
-                        Documentation::MarkupContent(MarkupContent {
-                            kind: lsp_types::MarkupKind::Markdown,
-                            value,
-                        })
-                    },
-                ),
-                text_edit: range.map(|range| {
-                    CompletionTextEdit::Edit(TextEdit {
-                        range,
-                        new_text: toml_value,
-                    })
-                }),
-                ..Default::default()
-            });
+        completions.extend(literal_completion(
+            default_value,
+            ext_docs.default_value.or_else(|| schema_docs.clone()),
+            range,
+            single_quote,
+        ));
+    }
+
+    if let Some(examples) = schema["examples"].as_array() {
+        for example in examples {
+            if Some(example) == schema.get("default") {
+                continue;
+            }
+
+            completions.extend(literal_completion(
+                example,
+                schema_docs.clone(),
+                range,
+                single_quote,
+            ));
         }
     }
 
@@ -689,6 +926,49 @@ fn add_value_completions(
     }
 }
 
+/// A completion of a single value from the schema, e.g. its default.
+fn literal_completion(
+    value: &Value,
+    docs: Option<String>,
+    range: Option<Range>,
+    single_quote: bool,
+) -> Option<CompletionItem> {
+    if value.is_null() {
+        return None;
+    }
+
+    let node: Node = match serde_json::from_value(value.clone()) {
+        Ok(v) => v,
+        Err(err) => {
+            tracing::error!(error = %err, "failed to parse JSON");
+            return None;
+        }
+    };
+
+    let toml_value = node.to_toml(true, single_quote);
+
+    Some(CompletionItem {
+        label: toml_value.clone(),
+        kind: Some(match node {
+            Node::Table(_) => CompletionItemKind::STRUCT,
+            _ => CompletionItemKind::VALUE,
+        }),
+        documentation: docs.map(|value| {
+            Documentation::MarkupContent(MarkupContent {
+                kind: lsp_types::MarkupKind::Markdown,
+                value,
+            })
+        }),
+        text_edit: range.map(|range| {
+            CompletionTextEdit::Edit(TextEdit {
+                range,
+                new_text: toml_value,
+            })
+        }),
+        ..Default::default()
+    })
+}
+
 fn new_entry_snippet(keys: &Keys, schema: &Value, single_quote: bool) -> String {
     let value = default_value_snippet(schema, 0, single_quote);
     format!("{keys} = {value}")
@@ -779,3 +1059,140 @@ fn empty_value_snippet(schema: &Value, cursor_count: usize) -> String {
         _ => format!("${cursor_count}"),
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use lsp_async_stub::util::Mapper;
+    use taplo::rowan::TextSize;
+
+    /// The labels of the completions from the document
+    /// at the end of the n-th occurrence of `at`.
+    fn document_completion_labels(src: &str, at: &str, n: usize) -> Vec<String> {
+        let doc = DocumentState::new(
+            src,
+            taplo::parser::parse(src),
+            Mapper::new_utf16(src, false),
+        );
+        let (offset, _) = src.match_indices(at).nth(n).unwrap();
+        let offset = TextSize::try_from(offset + at.len()).unwrap();
+
+        match document_key_completions(&doc, &Query::at(&doc.dom, offset)) {
+            Some(CompletionResponse::Array(items)) => items.into_iter().map(|i| i.label).collect(),
+            _ => Vec::new(),
+        }
+    }
+
+    #[test]
+    fn existing_keys() {
+        let src = r#"
+[package]
+name = "a"
+metadata.docs.all-features = true
+"dotted.key" = 1
+
+[table.sub]
+a = 1
+
+[table]
+b = 2
+"#;
+        let dom = taplo::parser::parse(src).into_dom();
+        let exists = |key: &str, typed: &str| {
+            let typed = if typed.is_empty() {
+                Keys::empty()
+            } else {
+                typed.parse().unwrap()
+            };
+            key_exists(&dom, &key.parse().unwrap(), &typed)
+        };
+
+        assert!(exists("package.name", ""));
+        assert!(!exists("package.version", ""));
+
+        // Dotted keys can be extended, but not their values.
+        assert!(!exists("package.metadata", ""));
+        assert!(!exists("package.metadata.docs", ""));
+        assert!(exists("package.metadata.docs.all-features", ""));
+        assert!(exists(r#"package."dotted.key""#, ""));
+        assert!(!exists("package.dotted", ""));
+
+        // A table of an earlier header.
+        assert!(exists("table.sub", ""));
+        assert!(exists("table.b", ""));
+
+        // The key that is being typed.
+        assert!(!exists("package.name", "package.name"));
+        assert!(exists("package.name", "package.na"));
+    }
+
+    #[test]
+    fn keys_of_sibling_array_items() {
+        let src = r#"
+[package]
+name = "a"
+
+[[bin]]
+name = "one"
+path = "src/one.rs"
+test = false
+
+[[bin]]
+name = "two"
+
+"#;
+
+        assert_eq!(
+            document_completion_labels(src, "name = \"two\"\n", 0),
+            ["path", "test"]
+        );
+        assert!(document_completion_labels(src, "name = \"a\"\n", 0).is_empty());
+    }
+
+    #[test]
+    fn existing_tables_in_headers() {
+        let src = "[package]\n[dependencies.a]\n[[bin]]\n[dependencies.]\n[[]]\n";
+
+        assert_eq!(
+            document_completion_labels(src, "[dependencies.", 1),
+            ["package", "dependencies", "dependencies.a"]
+        );
+        assert_eq!(document_completion_labels(src, "[[", 1), ["bin"]);
+    }
+
+    /// The insert texts of the header completions at the cursor, marked by `|`.
+    fn header_insert_texts(src: &str) -> Vec<String> {
+        let offset = src.find('|').unwrap();
+        let src = src.replace('|', "");
+        let doc = DocumentState::new(
+            &src,
+            taplo::parser::parse(&src),
+            Mapper::new_utf16(&src, false),
+        );
+        let offset = TextSize::try_from(offset).unwrap();
+
+        document_header_completions(&doc, &Query::at(&doc.dom, offset))
+            .into_iter()
+            .map(|item| item.insert_text.unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn unclosed_headers() {
+        // The closing brackets are added, the arrays and tables
+        // of the headers that are being typed are left out.
+        assert_eq!(
+            header_insert_texts("[package]\n[[bin]]\n[[b|\n[pa"),
+            ["bin]]"]
+        );
+        assert_eq!(header_insert_texts("[package]\n[[bin]]\n[[b|"), ["bin]]"]);
+        assert_eq!(
+            header_insert_texts("[package]\n[[bin]]\n[[b\n[pa|"),
+            ["package]"]
+        );
+
+        // Closed headers are completed without brackets.
+        assert_eq!(header_insert_texts("[package]\n[[bin]]\n[[b|]]\n"), ["bin"]);
+        assert_eq!(header_insert_texts("[[bin]]\n[[bin]]\n[[bi|n]]\n"), ["bin"]);
+    }
+}
@@ -1,12 +1,16 @@
-use crate::world::{World, DEFAULT_WORKSPACE_URL};
+use crate::{
+    diagnostics,
+    world::{WorkspaceSnapshot, World, DEFAULT_WORKSPACE_URL},
+};
 use anyhow::Context as AnyhowContext;
 use lsp_async_stub::{Context, Params, RequestWriter};
 use lsp_types::{
-    request::WorkspaceConfiguration, ConfigurationItem, ConfigurationParams,
-    DidChangeConfigurationParams,
+    request::{CodeLensRefresh, SemanticTokensRefresh, WorkspaceConfiguration},
+    ConfigurationItem, ConfigurationParams, DidChangeConfigurationParams, Url,
 };
+use serde_json::Value;
 use std::iter::once;
-use taplo_common::environment::Environment;
+use taplo_common::{environment::Environment, IndexMap};
 
 #[tracing::instrument(skip_all)]
 pub async fn configuration_change<E: Environment>(
@@ -18,43 +22,33 @@ pub async fn configuration_change<E: Environment>(
         Some(p) => p,
     };
 
-    let mut workspaces = context.workspaces.write().await;
-
-    for (_, ws) in workspaces.iter_mut() {
-        if let Err(error) = ws.config.update_from_json(&p.settings) {
-            tracing::error!(?error, "invalid configuration");
-        }
+    let updates = context
+        .workspaces
+        .read()
+        .await
+        .keys()
+        .map(|url| (url.clone(), vec![p.settings.clone()]))
+        .collect();
 
-        if let Err(error) = ws.initialize(context.clone(), &context.env).await {
-            tracing::error!(%error, "failed to update workspace");
-        }
-    }
+    apply_configuration(context.clone(), updates).await;
+    refresh_documents(context).await;
 }
 
 #[tracing::instrument(skip_all)]
 pub async fn update_configuration<E: Environment>(context: Context<World<E>>) {
     let init_config = context.init_config.load();
 
-    let mut workspaces = context.workspaces.write().await;
+    let workspace_urls: Vec<Url> = context.workspaces.read().await.keys().cloned().collect();
 
-    let config_items: Vec<_> = workspaces
+    let config_items: Vec<_> = workspace_urls
         .iter()
-        .filter_map(|(url, _)| {
-            if *url == *DEFAULT_WORKSPACE_URL {
-                None
-            } else {
-                Some(ConfigurationItem {
-                    scope_uri: Some(url.clone()),
-                    section: Some(init_config.configuration_section.clone()),
-                })
-            }
+        .filter(|url| **url != *DEFAULT_WORKSPACE_URL)
+        .map(|url| ConfigurationItem {
+            scope_uri: Some(url.clone()),
+            section: Some(init_config.configuration_section.clone()),
         })
         .collect();
 
-    for (url, _) in workspaces.iter() {
-        if *url == *DEFAULT_WORKSPACE_URL {}
-    }
-
     let res = context
         .clone()
         .write_request::<WorkspaceConfiguration, _>(Some(ConfigurationParams {
@@ -69,34 +63,264 @@ pub async fn update_configuration<E: Environment>(context: Context<World<E>>) {
         .context("failed to fetch configuration")
         .and_then(|res| res.into_result().context("invalid configuration response"));
 
-    match res {
-        Ok(configs) => {
-            for (i, config) in configs.into_iter().enumerate() {
-                if i == 0 && config.is_object() {
-                    for (_, ws) in workspaces.iter_mut() {
-                        if let Err(error) = ws.config.update_from_json(&config) {
-                            tracing::error!(?error, "invalid configuration");
-                        }
-
-                        if let Err(error) = ws.initialize(context.clone(), &context.env).await {
-                            tracing::error!(%error, "failed to update workspace");
-                        }
-                    }
-                } else if config.is_object() {
-                    let ws_url = config_items.get(i - 1).unwrap().scope_uri.as_ref().unwrap();
-                    let ws = workspaces.get_mut(ws_url).unwrap();
-                    if let Err(error) = ws.config.update_from_json(&config) {
-                        tracing::error!(?error, "invalid configuration");
-                    }
+    let configs = match res {
+        Ok(configs) => configs,
+        Err(error) => {
+            tracing::error!(?error, "failed to fetch configuration");
+            return;
+        }
+    };
+
+    let mut configs = configs.into_iter();
+    let global_config = configs.next().filter(Value::is_object);
+
+    // The global configuration is applied to every workspace,
+    // then the workspace-specific one on top of it.
+    let mut updates: IndexMap<Url, Vec<Value>> = workspace_urls
+        .into_iter()
+        .map(|url| (url, global_config.iter().cloned().collect()))
+        .collect();
+
+    for (item, config) in config_items.iter().zip(configs) {
+        if !config.is_object() {
+            continue;
+        }
 
-                    if let Err(error) = ws.initialize(context.clone(), &context.env).await {
-                        tracing::error!(%error, "failed to update workspace");
+        if let Some(ws_configs) = updates.get_mut(item.scope_uri.as_ref().unwrap()) {
+            ws_configs.push(config);
+        }
+    }
+
+    apply_configuration(context.clone(), updates.into_iter().collect()).await;
+    refresh_documents(context).await;
+}
+
+/// Merge the given configurations into the current configurations
+/// of the workspaces, then rebuild and apply their snapshots.
+///
+/// The snapshots are built without holding the lock on the workspaces,
+/// requests are not blocked while configuration files and schema catalogs
+/// are loaded, and they see either the old or the new state, never a mix of the two.
+///
+/// Invalid fields are logged and skipped, the rest of the
+/// configuration is still applied.
+pub(crate) async fn apply_configuration<E: Environment>(
+    context: Context<World<E>>,
+    updates: Vec<(Url, Vec<Value>)>,
+) {
+    let default_config = context.default_config.load_full();
+
+    let pending = {
+        let workspaces = context.workspaces.read().await;
+
+        updates
+            .into_iter()
+            .filter_map(|(url, configs)| {
+                let ws = workspaces.get(&url)?;
+                let mut config = ws.snapshot().config.clone();
+
+                for json in &configs {
+                    if let Err(error) = config.update_from_json(json) {
+                        tracing::error!(%error, "invalid configuration");
                     }
                 }
-            }
+
+                Some((url, ws.root.clone(), ws.schemas.clone(), config))
+            })
+            .collect::<Vec<_>>()
+    };
+
+    let mut snapshots = Vec::with_capacity(pending.len());
+
+    for (url, root, schemas, config) in pending {
+        let epoch = context.next_epoch();
+        let snapshot = WorkspaceSnapshot::build(
+            &context.env,
+            &root,
+            &schemas,
+            &default_config,
+            config,
+            epoch,
+        )
+        .await;
+        snapshots.push((url, snapshot));
+    }
+
+    let mut workspaces = context.workspaces.write().await;
+
+    for (url, (snapshot, associations)) in snapshots {
+        // The workspace might have been removed in the meantime.
+        let Some(ws) = workspaces.get_mut(&url) else {
+            continue;
+        };
+
+        let schema_enabled = snapshot.config.schema.enabled;
+
+        if ws.apply_snapshot(snapshot, &associations) && schema_enabled {
+            ws.emit_associations(context.clone()).await;
         }
-        Err(error) => {
-            tracing::error!(?error, "failed to fetch configuration");
+    }
+}
+
+/// Documents might have been opened before the configuration
+/// was available, so everything that depends on it is refreshed.
+async fn refresh_documents<E: Environment>(mut context: Context<World<E>>) {
+    diagnostics::publish_all_diagnostics(context.clone()).await;
+
+    let capabilities = context.client_capabilities.load();
+    let workspace = capabilities.workspace.as_ref();
+
+    if workspace
+        .and_then(|w| w.semantic_tokens.as_ref())
+        .and_then(|s| s.refresh_support)
+        .unwrap_or(false)
+    {
+        if let Err(error) = context
+            .write_request::<SemanticTokensRefresh, _>(None)
+            .await
+        {
+            tracing::error!(%error, "failed to refresh semantic tokens");
         }
     }
+
+    if workspace
+        .and_then(|w| w.code_lens.as_ref())
+        .and_then(|c| c.refresh_support)
+        .unwrap_or(false)
+    {
+        if let Err(error) = context.write_request::<CodeLensRefresh, _>(None).await {
+            tracing::error!(%error, "failed to refresh code lenses");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{create_server, create_world, world::WorkspaceState};
+    use futures::{channel::mpsc, SinkExt};
+    use lsp_async_stub::{rpc, Server};
+    use lsp_types::{
+        notification::{
+            DidChangeConfiguration, DidOpenTextDocument, Notification, PublishDiagnostics,
+        },
+        Diagnostic, DidOpenTextDocumentParams, PublishDiagnosticsParams, TextDocumentItem,
+    };
+    use serde_json::json;
+    use std::{io, sync::Arc};
+    use taplo_common::environment::native::NativeEnvironment;
+
+    const SCHEMA_URL: &str = "https://example.com/schema.json";
+    const WORKSPACE_URL: &str = "file:///proj/";
+
+    /// A world with a workspace that has the schema in its cache.
+    async fn world_with_schema() -> World<NativeEnvironment> {
+        let world = create_world(NativeEnvironment::new());
+        let url: Url = WORKSPACE_URL.parse().unwrap();
+        let ws = WorkspaceState::new(world.env.clone(), url.clone());
+
+        let schema = json!({ "properties": { "a": { "type": "integer" } } });
+        drop(
+            ws.schemas
+                .cache()
+                .store(SCHEMA_URL.parse().unwrap(), Arc::new(schema))
+                .await,
+        );
+
+        world.workspaces.write().await.insert(url, ws);
+        world
+    }
+
+    fn broken_pipe(_: mpsc::SendError) -> io::Error {
+        io::ErrorKind::BrokenPipe.into()
+    }
+
+    /// Handle the notification and return the messages the server wrote.
+    async fn notify<N: Notification>(
+        server: &Server<World<NativeEnvironment>>,
+        world: &World<NativeEnvironment>,
+        params: N::Params,
+    ) -> Vec<rpc::Message> {
+        let (sender, mut receiver) = mpsc::unbounded();
+        let message = rpc::Request::new()
+            .with_method(N::METHOD)
+            .with_params(Some(params))
+            .into_message();
+
+        server
+            .handle_message(world.clone(), message, sender.sink_map_err(broken_pipe))
+            .await
+            .unwrap();
+
+        std::iter::from_fn(|| receiver.try_recv().ok()).collect()
+    }
+
+    /// The diagnostics that were published last.
+    fn published_diagnostics(messages: &[rpc::Message]) -> Option<Vec<Diagnostic>> {
+        messages
+            .iter()
+            .rev()
+            .filter(|message| message.method.as_deref() == Some(PublishDiagnostics::METHOD))
+            .find_map(|message| {
+                serde_json::from_value::<PublishDiagnosticsParams>(message.params.clone()?).ok()
+            })
+            .map(|params| params.diagnostics)
+    }
+
+    fn open_params() -> DidOpenTextDocumentParams {
+        DidOpenTextDocumentParams {
+            text_document: TextDocumentItem {
+                uri: "file:///proj/doc.toml".parse().unwrap(),
+                language_id: "toml".into(),
+                version: 0,
+                text: "a = \"x\"\n".into(),
+            },
+        }
+    }
+
+    fn configuration_params(settings: Value) -> DidChangeConfigurationParams {
+        DidChangeConfigurationParams { settings }
+    }
+
+    /// The configuration the workspace is initialized with.
+    fn initial_configuration() -> DidChangeConfigurationParams {
+        configuration_params(json!({ "schema": { "catalogs": [] } }))
+    }
+
+    fn schema_configuration() -> DidChangeConfigurationParams {
+        configuration_params(json!({ "schema": { "associations": { ".*": SCHEMA_URL } } }))
+    }
+
+    #[tokio::test]
+    async fn configuration_change_refreshes_open_documents() {
+        let server = create_server::<NativeEnvironment>();
+
+        // The document is opened before the schema is associated with it.
+        let world = world_with_schema().await;
+        notify::<DidChangeConfiguration>(&server, &world, initial_configuration()).await;
+        let opened = notify::<DidOpenTextDocument>(&server, &world, open_params()).await;
+        assert_eq!(published_diagnostics(&opened), Some(Vec::new()));
+
+        let configured =
+            notify::<DidChangeConfiguration>(&server, &world, schema_configuration()).await;
+        let open_then_configure = published_diagnostics(&configured).unwrap();
+        assert_eq!(open_then_configure.len(), 1, "{open_then_configure:?}");
+
+        // The document is still open with the same text.
+        let document = open_params().text_document;
+        let workspaces = world.workspaces.read().await;
+        let ws = workspaces.by_document(&document.uri);
+        assert_eq!(&*ws.document(&document.uri).unwrap().text, document.text);
+        drop(workspaces);
+
+        // The same diagnostics as when the configuration comes first.
+        let world = world_with_schema().await;
+        notify::<DidChangeConfiguration>(&server, &world, initial_configuration()).await;
+        let configured =
+            notify::<DidChangeConfiguration>(&server, &world, schema_configuration()).await;
+        assert_eq!(published_diagnostics(&configured), None);
+
+        let opened = notify::<DidOpenTextDocument>(&server, &world, open_params()).await;
+        assert_eq!(published_diagnostics(&opened), Some(open_then_configure));
+    }
 }
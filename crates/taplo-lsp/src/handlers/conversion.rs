@@ -1,44 +1,101 @@
 use lsp_async_stub::{rpc::Error, Context, Params};
 use serde_json::Value;
-use taplo::{dom::Node, parser::parse};
+use taplo::{
+    dom::{json_to_toml, JsonOptions},
+    formatter,
+    parser::parse,
+};
 use taplo_common::environment::Environment;
 
 use crate::{
     lsp_ext::request::{
         ConvertToJsonParams, ConvertToJsonResponse, ConvertToTomlParams, ConvertToTomlResponse,
     },
-    world::World,
+    world::{document_path, DocumentState, World},
 };
 
 #[tracing::instrument(skip_all)]
 pub(crate) async fn convert_to_json<E: Environment>(
-    _context: Context<World<E>>,
+    context: Context<World<E>>,
     params: Params<ConvertToJsonParams>,
 ) -> Result<ConvertToJsonResponse, Error> {
     let p = params.required()?;
 
-    if serde_json::from_str::<Value>(&p.text).is_ok() {
-        return Ok(ConvertToJsonResponse {
-            text: Some(p.text),
-            error: None,
-        });
-    }
+    let doc = match (&p.document_uri, p.text) {
+        (Some(uri), _) => {
+            let workspaces = context.workspaces.read().await;
+            workspaces.by_document(uri).document(uri)?.clone()
+        }
+        (None, Some(text)) => {
+            if serde_json::from_str::<Value>(&text).is_ok() {
+                return Ok(ConvertToJsonResponse {
+                    text: Some(text),
+                    error: None,
+                });
+            }
+
+            DocumentState::new(&text, parse(&text), context.mapper(&text))
+        }
+        (None, None) => return Err(Error::invalid_params()),
+    };
+
+    Ok(convert_document_to_json(
+        &doc,
+        JsonOptions {
+            tagged: p.tagged,
+            ..Default::default()
+        },
+        p.compact,
+    ))
+}
+
+/// The errors are returned in the response with their positions,
+/// so that the client can show them.
+fn convert_document_to_json(
+    doc: &DocumentState,
+    options: JsonOptions,
+    compact: bool,
+) -> ConvertToJsonResponse {
+    let json = match doc.dom.to_json_with_options(options) {
+        Ok(json) => json,
+        Err(err) => {
+            let position = err
+                .range
+                .and_then(|range| doc.mapper.position(range.start()));
+
+            return ConvertToJsonResponse {
+                text: None,
+                error: Some(match position {
+                    Some(position) => {
+                        format!("{err} ({}:{})", position.line + 1, position.character + 1)
+                    }
+                    None => err.to_string(),
+                }),
+            };
+        }
+    };
+
+    let text = if compact {
+        serde_json::to_string(&json)
+    } else {
+        serde_json::to_string_pretty(&json)
+    };
 
-    match serde_json::to_string_pretty(&parse(&p.text).into_dom()) {
-        Ok(text) => Ok(ConvertToJsonResponse {
+    match text {
+        Ok(text) => ConvertToJsonResponse {
             text: Some(text),
             error: None,
-        }),
-        Err(err) => Ok(ConvertToJsonResponse {
+        },
+        Err(err) => ConvertToJsonResponse {
             text: None,
             error: Some(err.to_string()),
-        }),
+        },
     }
 }
 
 #[tracing::instrument(skip_all)]
 pub(crate) async fn convert_to_toml<E: Environment>(
-    _context: Context<World<E>>,
+    context: Context<World<E>>,
     params: Params<ConvertToTomlParams>,
 ) -> Result<ConvertToTomlResponse, Error> {
     let p = params.required()?;
@@ -51,18 +108,123 @@ pub(crate) async fn convert_to_toml<E: Environment>(
         });
     }
 
-    let dom = match serde_json::from_str::<Node>(&p.text) {
-        Ok(dom) => dom,
-        Err(err) => {
-            return Ok(ConvertToTomlResponse {
-                text: None,
-                error: Some(err.to_string()),
-            })
+    let options = match &p.document_uri {
+        Some(uri) => {
+            let workspaces = context.workspaces.read().await;
+            workspaces
+                .by_document(uri)
+                .snapshot()
+                .format_options(&document_path(&context.env, uri), Default::default())
         }
+        None => Default::default(),
     };
 
-    Ok(ConvertToTomlResponse {
-        text: Some(dom.to_toml(false, false)),
-        error: None,
-    })
+    Ok(convert_json_to_toml(&p.text, options))
+}
+
+/// The errors are returned in the response (e.g. for invalid JSON),
+/// so that the client can show them.
+fn convert_json_to_toml(json: &str, options: formatter::Options) -> ConvertToTomlResponse {
+    match json_to_toml(json, JsonOptions::default(), options) {
+        Ok(text) => ConvertToTomlResponse {
+            text: Some(text),
+            error: None,
+        },
+        Err(err) => ConvertToTomlResponse {
+            text: None,
+            error: Some(err.to_string()),
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use lsp_async_stub::util::Mapper;
+
+    fn document(src: &str) -> DocumentState {
+        DocumentState::new(src, parse(src), Mapper::new_utf16(src, false))
+    }
+
+    #[test]
+    fn document_to_json() {
+        let doc = document("a = 1\n[b]\nc = [0xFF]\n");
+
+        let res = convert_document_to_json(&doc, Default::default(), true);
+        assert_eq!(res.error, None);
+        assert_eq!(res.text.unwrap(), r#"{"a":1,"b":{"c":[255]}}"#);
+
+        let res = convert_document_to_json(&doc, Default::default(), false);
+        assert_eq!(
+            res.text.unwrap(),
+            serde_json::to_string_pretty(&serde_json::json!({ "a": 1, "b": { "c": [255] } }))
+                .unwrap()
+        );
+
+        let res = convert_document_to_json(
+            &doc,
+            JsonOptions {
+                tagged: true,
+                ..Default::default()
+            },
+            true,
+        );
+        assert_eq!(
+            res.text.unwrap(),
+            r#"{"a":1,"b":{"c":[{"$type":"integer-hex","value":255}]}}"#
+        );
+    }
+
+    #[test]
+    fn document_to_json_error_position() {
+        let doc = document("a = 1\nb = [1, nan]\n");
+
+        let res = convert_document_to_json(&doc, Default::default(), false);
+        assert_eq!(res.text, None);
+        assert_eq!(
+            res.error.unwrap(),
+            "infinite and NaN floats cannot be represented in JSON (2:9)"
+        );
+    }
+
+    #[test]
+    fn json_to_formatted_toml() {
+        let json = r#"{
+            "package": { "name": "taplo", "keywords": ["toml", "lsp"] },
+            "bin": [{ "name": "taplo", "path": "src/main.rs" }, { "name": "other" }]
+        }"#;
+
+        let res = convert_json_to_toml(
+            json,
+            formatter::Options {
+                indent_entries: true,
+                ..Default::default()
+            },
+        );
+
+        assert_eq!(res.error, None);
+        assert_eq!(
+            res.text.unwrap(),
+            r#"[package]
+  name = "taplo"
+  keywords = ["toml", "lsp"]
+[[bin]]
+  name = "taplo"
+  path = "src/main.rs"
+[[bin]]
+  name = "other"
+"#
+        );
+    }
+
+    #[test]
+    fn json_to_toml_errors() {
+        let res = convert_json_to_toml(r#"{ "a": [1, null] }"#, Default::default());
+        assert_eq!(res.text, None);
+        assert!(res.error.unwrap().starts_with("`a[1]` is null"));
+
+        let res = convert_json_to_toml(r#"{ "a": "#, Default::default());
+        assert_eq!(res.text, None);
+        assert!(res.error.is_some());
+    }
 }
@@ -0,0 +1,53 @@
+use crate::{
+    lsp_ext::request::{DebugTreeParams, DebugTreeResponse},
+    world::World,
+};
+use lsp_async_stub::{rpc::Error, Context, Params};
+use taplo::util::debug::{syntax_debug_string, DebugOptions, DebugString};
+use taplo_common::environment::Environment;
+
+#[tracing::instrument(skip_all)]
+pub(crate) async fn syntax_tree<E: Environment>(
+    context: Context<World<E>>,
+    params: Params<DebugTreeParams>,
+) -> Result<DebugTreeResponse, Error> {
+    let p = params.required()?;
+
+    let workspaces = context.workspaces.read().await;
+    let ws = workspaces.by_document(&p.document_uri);
+    let doc = ws.document(&p.document_uri)?;
+
+    let syntax = doc.parse.clone().into_syntax();
+
+    Ok(response(syntax_debug_string(&syntax, &debug_options(&p))))
+}
+
+#[tracing::instrument(skip_all)]
+pub(crate) async fn dom_tree<E: Environment>(
+    context: Context<World<E>>,
+    params: Params<DebugTreeParams>,
+) -> Result<DebugTreeResponse, Error> {
+    let p = params.required()?;
+
+    let workspaces = context.workspaces.read().await;
+    let ws = workspaces.by_document(&p.document_uri);
+    let doc = ws.document(&p.document_uri)?;
+
+    Ok(response(doc.dom.debug_string(&debug_options(&p))))
+}
+
+fn debug_options(p: &DebugTreeParams) -> DebugOptions {
+    let defaults = DebugOptions::default();
+    DebugOptions {
+        max_depth: p.max_depth.unwrap_or(defaults.max_depth),
+        max_length: p.max_length.unwrap_or(defaults.max_length),
+        ..defaults
+    }
+}
+
+fn response(debug: DebugString) -> DebugTreeResponse {
+    DebugTreeResponse {
+        text: debug.text,
+        truncated: debug.truncated,
+    }
+}
@@ -5,7 +5,11 @@ use lsp_async_stub::{
     Context, Params,
 };
 use lsp_types::{DocumentSymbol, DocumentSymbolParams, DocumentSymbolResponse, SymbolKind};
-use taplo::{dom::Node, rowan::TextRange, util::join_ranges};
+use taplo::{
+    dom::{node::DomKind, Node},
+    rowan::TextRange,
+    util::join_ranges,
+};
 use taplo_common::environment::Environment;
 
 #[tracing::instrument(skip_all)]
@@ -71,46 +75,18 @@ fn symbols_for_value(
     let selection_range = key_range.map_or(own_range, |r| mapper.range(r).unwrap());
 
     match node {
-        Node::Bool(_) => symbols.push(DocumentSymbol {
-            name,
-            kind: SymbolKind::BOOLEAN,
-            range: range.into_lsp(),
-            selection_range: selection_range.into_lsp(),
-            detail: None,
-            deprecated: None,
-            tags: Default::default(),
-            children: None,
-        }),
-        Node::Str(_) => symbols.push(DocumentSymbol {
-            name,
-            kind: SymbolKind::STRING,
-            range: range.into_lsp(),
-            selection_range: selection_range.into_lsp(),
-            detail: None,
-            deprecated: None,
-            tags: Default::default(),
-            children: None,
-        }),
-        Node::Integer(_) | Node::Float(_) => symbols.push(DocumentSymbol {
-            name,
-            kind: SymbolKind::NUMBER,
-            range: range.into_lsp(),
-            selection_range: selection_range.into_lsp(),
-            detail: None,
-            deprecated: None,
-            tags: Default::default(),
-            children: None,
-        }),
-        Node::Date(_) => symbols.push(DocumentSymbol {
-            name,
-            kind: SymbolKind::FIELD,
-            range: range.into_lsp(),
-            selection_range: selection_range.into_lsp(),
-            detail: None,
-            deprecated: None,
-            tags: Default::default(),
-            children: None,
-        }),
+        Node::Bool(_) | Node::Str(_) | Node::Integer(_) | Node::Float(_) | Node::Date(_) => {
+            symbols.push(DocumentSymbol {
+                name,
+                kind: value_symbol_kind(node.dom_kind()),
+                range: range.into_lsp(),
+                selection_range: selection_range.into_lsp(),
+                detail: None,
+                deprecated: None,
+                tags: Default::default(),
+                children: None,
+            });
+        }
         Node::Array(arr) => symbols.push(DocumentSymbol {
             name,
             kind: SymbolKind::ARRAY,
@@ -160,6 +136,15 @@ fn symbols_for_value(
     }
 }
 
+fn value_symbol_kind(kind: DomKind) -> SymbolKind {
+    match kind {
+        DomKind::Bool => SymbolKind::BOOLEAN,
+        DomKind::String(_) => SymbolKind::STRING,
+        DomKind::Integer(_) | DomKind::Float => SymbolKind::NUMBER,
+        _ => SymbolKind::FIELD,
+    }
+}
+
 fn ensure_non_empty_key(s: String) -> String {
     if s.is_empty() {
         r#"''"#.into()
@@ -1,8 +1,16 @@
-use lsp_async_stub::{util::Mapper, Context, Params, RequestWriter};
+use lsp_async_stub::{
+    util::{LspExt, Mapper, Position, PositionEncoding},
+    Context, Params, RequestWriter,
+};
 use lsp_types::{
     notification, Diagnostic, DiagnosticSeverity, DidChangeTextDocumentParams,
-    DidCloseTextDocumentParams, DidOpenTextDocumentParams, DidSaveTextDocumentParams,
-    PublishDiagnosticsParams,
+    DidChangeWatchedFilesParams, DidCloseTextDocumentParams, DidOpenTextDocumentParams,
+    DidSaveTextDocumentParams, FileChangeType, PublishDiagnosticsParams,
+    TextDocumentContentChangeEvent,
+};
+use taplo::{
+    parser::Parse,
+    rowan::{TextRange, TextSize},
 };
 use taplo_common::{
     environment::Environment,
@@ -26,9 +34,10 @@ pub(crate) async fn document_open<E: Environment>(
 
     let mut workspaces = context.workspaces.write().await;
     let ws = workspaces.by_document_mut(&p.text_document.uri);
+    let snapshot = ws.snapshot();
 
     if let Some(pth) = context.env.to_file_path_normalized(&p.text_document.uri) {
-        if !ws.taplo_config.is_included(&pth) {
+        if !snapshot.taplo_config.is_included(&pth) {
             drop(workspaces);
             context
                 .write_notification::<notification::PublishDiagnostics, _>(Some(
@@ -54,12 +63,13 @@ pub(crate) async fn document_open<E: Environment>(
         }
     }
 
-    let parse = taplo::parser::parse(&p.text_document.text);
-    let mapper = Mapper::new_utf16(&p.text_document.text, false);
-
-    let dom = parse.clone().into_dom();
+    let doc = DocumentState::new(
+        &p.text_document.text,
+        taplo::parser::parse(&p.text_document.text),
+        context.mapper(&p.text_document.text),
+    );
 
-    if ws.config.schema.enabled {
+    if snapshot.config.schema.enabled {
         ws.schemas
             .associations()
             .retain(|(rule, assoc)| match rule {
@@ -72,14 +82,11 @@ pub(crate) async fn document_open<E: Environment>(
             });
         ws.schemas
             .associations()
-            .add_from_document(&p.text_document.uri, &dom);
+            .add_from_document(&p.text_document.uri, &doc.dom);
         ws.emit_associations(context.clone()).await;
     }
 
-    ws.documents.insert(
-        p.text_document.uri.clone(),
-        DocumentState { parse, dom, mapper },
-    );
+    ws.documents.insert(p.text_document.uri.clone(), doc);
 
     let ws_root = ws.root.clone();
     drop(workspaces);
@@ -91,22 +98,17 @@ pub(crate) async fn document_change<E: Environment>(
     mut context: Context<World<E>>,
     params: Params<DidChangeTextDocumentParams>,
 ) {
-    let mut p = match params.optional() {
+    let p = match params.optional() {
         None => return,
         Some(p) => p,
     };
 
-    // We expect one full change
-    let change = match p.content_changes.pop() {
-        None => return,
-        Some(c) => c,
-    };
-
     let mut workspaces = context.workspaces.write().await;
     let ws = workspaces.by_document_mut(&p.text_document.uri);
+    let snapshot = ws.snapshot();
 
     if let Some(pth) = context.env.to_file_path_normalized(&p.text_document.uri) {
-        if !ws.taplo_config.is_included(&pth) {
+        if !snapshot.taplo_config.is_included(&pth) {
             drop(workspaces);
             context
                 .write_notification::<notification::PublishDiagnostics, _>(Some(
@@ -132,28 +134,145 @@ pub(crate) async fn document_change<E: Environment>(
         }
     }
 
-    let parse = taplo::parser::parse(&change.text);
-    let mapper = Mapper::new_utf16(&change.text, false);
+    let encoding = **context.position_encoding.load();
+    let changed = match ws.documents.get(&p.text_document.uri) {
+        Some(doc) => apply_changes(&doc.text, &doc.parse, p.content_changes, encoding),
+        None => apply_changes("", &taplo::parser::parse(""), p.content_changes, encoding),
+    };
 
-    let dom = parse.clone().into_dom();
+    let (text, parse) = match changed {
+        Some(changed) => changed,
+        None => {
+            tracing::error!(uri = %p.text_document.uri, "invalid document change range");
+            return;
+        }
+    };
+
+    let doc = DocumentState::new(&text, parse, context.mapper(&text));
 
-    if ws.config.schema.enabled {
+    if snapshot.config.schema.enabled {
         ws.schemas
             .associations()
-            .add_from_document(&p.text_document.uri, &dom);
+            .add_from_document(&p.text_document.uri, &doc.dom);
         ws.emit_associations(context.clone()).await;
     }
 
-    ws.documents.insert(
-        p.text_document.uri.clone(),
-        DocumentState { parse, dom, mapper },
-    );
+    ws.documents.insert(p.text_document.uri.clone(), doc);
 
     let ws_root = ws.root.clone();
     drop(workspaces);
     diagnostics::publish_diagnostics(context.clone(), ws_root, p.text_document.uri).await;
 }
 
+/// Apply the changes of a notification to the text of a document
+/// and parse the new text.
+///
+/// The changes are applied in order, the range of each change
+/// is in the text after the previous changes. A change without
+/// a range replaces the entire text. The positions
+/// are in the given encoding.
+///
+/// Only the parts of the text that changed are parsed again if possible.
+///
+/// Positions past the end of a line are at the end of the line,
+/// and positions past the last line are at the end of the text.
+///
+/// Returns [`None`] if the end of a range is before its start.
+pub(crate) fn apply_changes(
+    text: &str,
+    parse: &Parse,
+    changes: impl IntoIterator<Item = TextDocumentContentChangeEvent>,
+    encoding: PositionEncoding,
+) -> Option<(String, Parse)> {
+    let mut text = text.to_string();
+    let mut parse = parse.clone();
+
+    for change in changes {
+        match change.range {
+            Some(range) => {
+                let mapper = Mapper::new(&text, encoding, false);
+                let start = clamped_offset(&text, &mapper, Position::from_lsp(range.start));
+                let end = clamped_offset(&text, &mapper, Position::from_lsp(range.end));
+                if start > end {
+                    return None;
+                }
+                parse = parse.update(TextRange::new(start, end), &change.text);
+                text.replace_range(usize::from(start)..usize::from(end), &change.text);
+            }
+            None => {
+                parse = taplo::parser::parse(&change.text);
+                text = change.text;
+            }
+        }
+    }
+
+    Some((text, parse))
+}
+
+/// The offset of a position in the text.
+///
+/// Positions past the end of a line are clamped to the end of the line,
+/// and positions past the last line to the end of the text. A position
+/// inside a character is at the start of the character.
+fn clamped_offset(text: &str, mapper: &Mapper, position: Position) -> TextSize {
+    if let Some(offset) = mapper.offset(position) {
+        return offset;
+    }
+
+    let (_, positions) = mapper.mappings();
+
+    let Some(&line_start) = positions.get(&Position::new(position.line, 0)) else {
+        return TextSize::of(text);
+    };
+
+    let line = text[usize::from(line_start)..]
+        .split('\n')
+        .next()
+        .unwrap_or_default();
+    let line_end = line_start + TextSize::of(line.strip_suffix('\r').unwrap_or(line));
+
+    positions
+        .range(..=position)
+        .next_back()
+        .map_or(line_end, |(_, &offset)| offset.min(line_end))
+}
+
+/// The range in the old text that was replaced and its replacement.
+pub(crate) fn changed_range<'t>(old: &str, new: &'t str) -> Option<(TextRange, &'t str)> {
+    if old == new {
+        return None;
+    }
+
+    let mut prefix = old
+        .bytes()
+        .zip(new.bytes())
+        .take_while(|(a, b)| a == b)
+        .count();
+    while !old.is_char_boundary(prefix) {
+        prefix -= 1;
+    }
+
+    let max_suffix = old.len().min(new.len()) - prefix;
+    let mut suffix = old
+        .bytes()
+        .rev()
+        .zip(new.bytes().rev())
+        .take(max_suffix)
+        .take_while(|(a, b)| a == b)
+        .count();
+    while !old.is_char_boundary(old.len() - suffix) || !new.is_char_boundary(new.len() - suffix) {
+        suffix -= 1;
+    }
+
+    Some((
+        TextRange::new(
+            TextSize::try_from(prefix).ok()?,
+            TextSize::try_from(old.len() - suffix).ok()?,
+        ),
+        &new[prefix..new.len() - suffix],
+    ))
+}
+
 #[tracing::instrument(skip_all)]
 pub(crate) async fn document_save<E: Environment>(
     _context: Context<World<E>>,
@@ -175,7 +294,7 @@ pub(crate) async fn document_close<E: Environment>(
     let mut workspaces = context.workspaces.write().await;
     let ws = workspaces.by_document_mut(&p.text_document.uri);
 
-    ws.documents.remove(&p.text_document.uri);
+    ws.close_document(&p.text_document.uri);
     drop(workspaces);
 
     context.env.spawn_local(diagnostics::clear_diagnostics(
@@ -183,3 +302,181 @@ pub(crate) async fn document_close<E: Environment>(
         p.text_document.uri,
     ));
 }
+
+#[tracing::instrument(skip_all)]
+pub(crate) async fn watched_files_change<E: Environment>(
+    context: Context<World<E>>,
+    params: Params<DidChangeWatchedFilesParams>,
+) {
+    let p = match params.optional() {
+        None => return,
+        Some(p) => p,
+    };
+
+    let workspaces = context.workspaces.read().await;
+
+    for change in p.changes {
+        if change.typ != FileChangeType::DELETED {
+            continue;
+        }
+
+        // The client still owns the contents of open documents
+        // even if their files are deleted, and the following changes
+        // are applied to the text we have, so they are only
+        // forgotten when the client closes them.
+        if workspaces
+            .by_document(&change.uri)
+            .documents
+            .contains_key(&change.uri)
+        {
+            tracing::debug!(document_url = %change.uri, "file of open document was deleted");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn change(
+        range: Option<((u32, u32), (u32, u32))>,
+        text: &str,
+    ) -> TextDocumentContentChangeEvent {
+        TextDocumentContentChangeEvent {
+            range: range.map(|(start, end)| lsp_types::Range {
+                start: lsp_types::Position::new(start.0, start.1),
+                end: lsp_types::Position::new(end.0, end.1),
+            }),
+            range_length: None,
+            text: text.into(),
+        }
+    }
+
+    /// Apply the changes to the text, the updated parse
+    /// must be the same as parsing the new text.
+    fn apply(
+        text: &str,
+        changes: impl IntoIterator<Item = TextDocumentContentChangeEvent>,
+        encoding: PositionEncoding,
+    ) -> Option<String> {
+        let (text, parse) = apply_changes(text, &taplo::parser::parse(text), changes, encoding)?;
+
+        assert_eq!(
+            format!("{:#?}", parse.into_syntax()),
+            format!("{:#?}", taplo::parser::parse(&text).into_syntax())
+        );
+
+        Some(text)
+    }
+
+    fn apply_utf16(
+        text: &str,
+        changes: impl IntoIterator<Item = TextDocumentContentChangeEvent>,
+    ) -> Option<String> {
+        apply(text, changes, PositionEncoding::Utf16)
+    }
+
+    #[test]
+    fn apply_single_changes() {
+        let text = "a = 1\nb = \"é😀\"\r\nc = 3\n";
+
+        assert_eq!(
+            apply_utf16(text, [change(Some(((0, 4), (0, 5))), "2")]).unwrap(),
+            "a = 2\nb = \"é😀\"\r\nc = 3\n"
+        );
+        assert_eq!(
+            apply_utf16(text, [change(Some(((1, 6), (1, 8))), "x")]).unwrap(),
+            "a = 1\nb = \"éx\"\r\nc = 3\n"
+        );
+        assert_eq!(
+            apply_utf16(text, [change(Some(((1, 9), (2, 0))), "")]).unwrap(),
+            "a = 1\nb = \"é😀\"c = 3\n"
+        );
+        assert_eq!(
+            apply_utf16(text, [change(Some(((3, 0), (3, 0))), "d = 4")]).unwrap(),
+            "a = 1\nb = \"é😀\"\r\nc = 3\nd = 4"
+        );
+        assert_eq!(apply_utf16(text, [change(None, "new")]).unwrap(), "new");
+
+        // Positions past the end of a line or the text.
+        assert_eq!(
+            apply_utf16(text, [change(Some(((0, 9), (0, 9))), " # one")]).unwrap(),
+            "a = 1 # one\nb = \"é😀\"\r\nc = 3\n"
+        );
+        assert_eq!(
+            apply_utf16(text, [change(Some(((1, 8), (1, 20))), "\"")]).unwrap(),
+            "a = 1\nb = \"é😀\"\r\nc = 3\n"
+        );
+        assert_eq!(
+            apply_utf16(text, [change(Some(((2, 5), (9, 0))), "")]).unwrap(),
+            "a = 1\nb = \"é😀\"\r\nc = 3"
+        );
+
+        // Inside a surrogate pair.
+        assert_eq!(
+            apply_utf16(text, [change(Some(((1, 7), (1, 7))), "x")]).unwrap(),
+            "a = 1\nb = \"éx😀\"\r\nc = 3\n"
+        );
+
+        // Backwards, also after the positions are clamped.
+        assert!(apply_utf16(text, [change(Some(((0, 5), (0, 4))), "")]).is_none());
+        assert!(apply_utf16(text, [change(Some(((0, 9), (0, 2))), "")]).is_none());
+    }
+
+    #[test]
+    fn apply_changes_in_encodings() {
+        let text = "a = \"é😀\"\nb = 2\n";
+
+        // Replacing the emoji.
+        for (encoding, start, end) in [
+            (PositionEncoding::Utf8, 7, 11),
+            (PositionEncoding::Utf16, 6, 8),
+            (PositionEncoding::Utf32, 6, 7),
+        ] {
+            assert_eq!(
+                apply(text, [change(Some(((0, start), (0, end))), "x")], encoding).unwrap(),
+                "a = \"éx\"\nb = 2\n"
+            );
+        }
+
+        // Inside of the emoji.
+        assert_eq!(
+            apply(
+                text,
+                [change(Some(((0, 8), (0, 8))), "x")],
+                PositionEncoding::Utf8
+            )
+            .unwrap(),
+            "a = \"éx😀\"\nb = 2\n"
+        );
+    }
+
+    #[test]
+    fn apply_multiple_changes_in_order() {
+        let changes = [
+            change(Some(((0, 0), (0, 1))), "key"),
+            change(Some(((0, 1), (0, 3))), "EY\n["),
+            change(None, "a = 1\n"),
+            change(Some(((1, 0), (1, 0))), "b = 2"),
+            change(Some(((0, 4), (1, 4))), "3\nc = "),
+        ];
+
+        assert_eq!(apply_utf16("k = 1", changes).unwrap(), "a = 3\nc = 2");
+    }
+
+    #[test]
+    fn apply_overlapping_changes() {
+        let changes = [
+            change(Some(((1, 1), (1, 4))), "x\n"),
+            change(Some(((1, 0), (2, 1))), "[a]\nb = 1"),
+            change(Some(((0, 5), (1, 2))), "😀"),
+            change(Some(((0, 7), (0, 8))), "\""),
+            change(Some(((2, 3), (3, 0))), "3\n"),
+        ];
+
+        assert_eq!(
+            apply_utf16("a = \"é\"\n[t😀]\r\nb = 2\n", changes).unwrap(),
+            "a = \"😀\"\nb = 1\r\nb =3\n"
+        );
+    }
+}
@@ -0,0 +1,210 @@
+use crate::{
+    handlers::changed_range,
+    lsp_ext::request::{
+        ComputeEditsParams, ComputeEditsResponse, EditOperation, EditOperationError,
+    },
+    world::World,
+};
+use lsp_async_stub::{rpc::Error, util::LspExt, Context, Params};
+use lsp_types::{TextEdit, WorkspaceEdit};
+use std::collections::HashMap;
+use taplo::{
+    dom::rewrite::{self, Rewrite},
+    parser::parse,
+};
+use taplo_common::environment::Environment;
+
+#[tracing::instrument(skip_all)]
+pub(crate) async fn compute_edits<E: Environment>(
+    context: Context<World<E>>,
+    params: Params<ComputeEditsParams>,
+) -> Result<ComputeEditsResponse, Error> {
+    let p = params.required()?;
+
+    let workspaces = context.workspaces.read().await;
+    let ws = workspaces.by_document(&p.document_uri);
+    let doc = ws.document(&p.document_uri)?;
+
+    let original = &*doc.text;
+    let (text, errors) = apply_operations(original, &p.operations);
+
+    let Some((range, new_text)) = changed_range(original, &text) else {
+        return Ok(ComputeEditsResponse { edit: None, errors });
+    };
+
+    let Some(range) = doc.mapper.range(range) else {
+        return Err(Error::internal_error());
+    };
+
+    Ok(ComputeEditsResponse {
+        edit: Some(WorkspaceEdit {
+            changes: Some(HashMap::from([(
+                p.document_uri,
+                vec![TextEdit {
+                    range: range.into_lsp(),
+                    new_text: new_text.into(),
+                }],
+            )])),
+            ..Default::default()
+        }),
+        errors,
+    })
+}
+
+/// Apply the operations one after the other, the failed ones are skipped
+/// and reported with their indices.
+fn apply_operations(text: &str, operations: &[EditOperation]) -> (String, Vec<EditOperationError>) {
+    let mut text = text.to_string();
+    let mut errors = Vec::new();
+
+    for (index, operation) in operations.iter().enumerate() {
+        match apply_operation(&text, operation) {
+            Ok(new_text) => text = new_text,
+            Err(message) => errors.push(EditOperationError { index, message }),
+        }
+    }
+
+    (text, errors)
+}
+
+/// Apply the operation to the text and validate the result.
+fn apply_operation(text: &str, operation: &EditOperation) -> Result<String, String> {
+    let before = parse(text);
+    let error_count = before.errors.len() + dom_error_count(&before);
+
+    let mut rewrite = Rewrite::new(before.into_dom()).map_err(|err| err.to_string())?;
+
+    match operation {
+        EditOperation::Set { path, value } => rewrite.set_value(path, value).map(drop),
+        EditOperation::Remove { path } => rewrite.remove(path).map(drop),
+        EditOperation::AppendArrayItem { path, value } => {
+            check_path(path).and_then(|()| rewrite.append_array_item(path, value).map(drop))
+        }
+        EditOperation::RenameKey { path, to } => {
+            check_path(path).and_then(|()| rewrite.rename_keys(path, to).map(drop))
+        }
+    }
+    .map_err(|err| err.to_string())?;
+
+    let new_text = rewrite.to_string();
+    let after = parse(&new_text);
+
+    if after.errors.len() + dom_error_count(&after) > error_count {
+        return Err("the operation would result in an invalid document".into());
+    }
+
+    Ok(new_text)
+}
+
+/// The array and rename operations use dotted keys,
+/// only paths without array indices can be converted to them.
+fn check_path(path: &str) -> Result<(), rewrite::Error> {
+    let path: taplo::dom::Path = path.parse()?;
+
+    if path.iter().any(|s| s.as_index().is_some()) {
+        return Err(rewrite::Error::UnexpectedIndex);
+    }
+
+    Ok(())
+}
+
+fn dom_error_count(p: &taplo::parser::Parse) -> usize {
+    match p.clone().into_dom().validate() {
+        Ok(()) => 0,
+        Err(errors) => errors.count(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn set(path: &str, value: &str) -> EditOperation {
+        EditOperation::Set {
+            path: path.into(),
+            value: value.into(),
+        }
+    }
+
+    fn append(path: &str, value: &str) -> EditOperation {
+        EditOperation::AppendArrayItem {
+            path: path.into(),
+            value: value.into(),
+        }
+    }
+
+    fn rename(path: &str, to: &str) -> EditOperation {
+        EditOperation::RenameKey {
+            path: path.into(),
+            to: to.into(),
+        }
+    }
+
+    fn remove(path: &str) -> EditOperation {
+        EditOperation::Remove { path: path.into() }
+    }
+
+    /// The resulting text and the indices of the failed operations with their messages.
+    fn edit(src: &str, operations: &[EditOperation]) -> (String, Vec<(usize, String)>) {
+        let (text, errors) = apply_operations(src, operations);
+        let errors = errors.into_iter().map(|e| (e.index, e.message)).collect();
+        (text, errors)
+    }
+
+    const SRC: &str = "a = 1\nb = [1, 2]\n\n[[c]]\nd = [3]\n";
+
+    #[test]
+    fn failed_operations_are_skipped() {
+        let (text, errors) = edit(
+            SRC,
+            &[
+                set("a", "2"),
+                remove("x"),
+                append("b", "3"),
+                rename("a", "b"),
+                set("c[0].d", "[3, 4]"),
+                rename("c", "e"),
+            ],
+        );
+
+        assert_eq!(text, "a = 2\nb = [1, 2, 3]\n\n[[e]]\nd = [3, 4]\n");
+        assert_eq!(
+            errors,
+            [
+                (1, "nothing was found at the given path".into()),
+                (
+                    3,
+                    "the operation would result in an invalid document".into()
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn invalid_operations() {
+        for (operation, message) in [
+            (set("a", "[1"), "invalid value: unexpected EOF"),
+            (append("b", "x y"), "invalid value: expected value"),
+            (append("a", "1"), "expected array"),
+            (set("x[", "1"), "the given path is invalid at offset 1"),
+        ] {
+            let (text, errors) = edit(SRC, &[operation]);
+            assert_eq!(text, SRC);
+            assert_eq!(errors, [(0, message.into())]);
+        }
+    }
+
+    #[test]
+    fn array_indices_in_paths() {
+        // Array and rename operations use dotted keys.
+        for operation in [append("c[0].d", "4"), rename("c[0].d", "e")] {
+            let (text, errors) = edit(SRC, &[operation]);
+            assert_eq!(text, SRC);
+            assert_eq!(errors, [(0, rewrite::Error::UnexpectedIndex.to_string())]);
+        }
+
+        let (text, errors) = edit(SRC, &[set("b[1]", "5"), set("c[0].d", "5")]);
+        assert_eq!(text, "a = 1\nb = [1, 5]\n\n[[c]]\nd = 5\n");
+        assert!(errors.is_empty());
+    }
+}
@@ -3,16 +3,20 @@
 use lsp_async_stub::{rpc::Error, util::Mapper, Context, Params};
 use lsp_types::{FoldingRange, FoldingRangeKind, FoldingRangeParams};
 use taplo::{
-    dom::node::DomNode,
+    dom::{
+        node::{DomNode, Key},
+        FromSyntax, Path,
+    },
     rowan::TextRange,
     syntax::{
         SyntaxElement,
         SyntaxKind::{
-            ARRAY, COMMENT, MULTI_LINE_STRING, MULTI_LINE_STRING_LITERAL, NEWLINE,
-            TABLE_ARRAY_HEADER, TABLE_HEADER, WHITESPACE,
+            ARRAY, COMMENT, IDENT, MULTI_LINE_STRING, MULTI_LINE_STRING_LITERAL, NEWLINE, STRING,
+            STRING_LITERAL, TABLE_ARRAY_HEADER, TABLE_HEADER, WHITESPACE,
         },
         SyntaxNode,
     },
+    util::syntax::section_ranges,
 };
 use taplo_common::environment::Environment;
 
@@ -49,55 +53,17 @@ pub fn create_folding_ranges(syntax: &SyntaxNode, mapper: &Mapper) -> Vec<Foldin
     let mut last_comment: Option<TextRange> = None;
     let mut was_comment: bool = false;
 
-    let mut header_starts: Vec<(String, TextRange)> = Vec::new();
-
-    let mut last_non_header: Option<TextRange> = None;
-
     for element in syntax.children_with_tokens() {
         let mut is_comment = false;
 
         match element.kind() {
-            TABLE_ARRAY_HEADER | TABLE_HEADER => {
-                let key = element
-                    .as_node()
-                    .unwrap()
-                    .first_child()
-                    .unwrap()
-                    .text()
-                    .to_string();
-
-                if let Some(e) = &last_non_header {
-                    header_starts.retain(|(k, h)| {
-                        if k == &key || !key.starts_with(k) {
-                            folding_ranges.push(FoldingRange {
-                                start_line: mapper.position(h.start()).unwrap().line as u32,
-                                start_character: None,
-                                end_line: mapper
-                                    .position(e.end().checked_sub(1.into()).unwrap_or_default())
-                                    .unwrap()
-                                    .line as u32,
-                                end_character: None,
-                                kind: Some(FoldingRangeKind::Region),
-                            });
-
-                            false
-                        } else {
-                            true
-                        }
-                    });
-                }
-
-                header_starts.push((key, element.text_range()));
-                last_non_header = None;
-            }
+            TABLE_ARRAY_HEADER | TABLE_HEADER => {}
             WHITESPACE => {
                 if was_comment {
                     is_comment = true;
                 }
             }
             _ => {
-                last_non_header = Some(element.text_range());
-
                 match element {
                     SyntaxElement::Node(n) => {
                         for d in n.descendants_with_tokens() {
@@ -192,15 +158,49 @@ pub fn create_folding_ranges(syntax: &SyntaxNode, mapper: &Mapper) -> Vec<Foldin
         }
     }
 
-    if let Some(e) = &last_non_header {
-        for (_, h) in header_starts {
+    let sections = section_ranges(syntax)
+        .into_iter()
+        .map(|(header, range)| (header_path(&header), range))
+        .collect::<Vec<_>>();
+
+    // Nested tables are folded together with their parent,
+    // a section ends before the first header that is not nested in it.
+    let mut ends = sections.iter().map(|(_, r)| r.end()).collect::<Vec<_>>();
+    let mut parents: Vec<usize> = Vec::new();
+
+    for (idx, (path, _)) in sections.iter().enumerate() {
+        while let Some(&parent) = parents.last() {
+            let parent_path = &sections[parent].0;
+            if parent_path.len() < path.len() && parent_path.is_part_of(path) {
+                break;
+            }
+
+            // All sections since the parent are nested in it.
+            ends[parent] = sections[idx - 1].1.end();
+            parents.pop();
+        }
+
+        parents.push(idx);
+    }
+
+    if let Some((_, last)) = sections.last() {
+        for parent in parents {
+            ends[parent] = last.end();
+        }
+    }
+
+    for ((_, range), end) in sections.iter().zip(ends) {
+        let start_line = mapper.position(range.start()).unwrap().line;
+        let end_line = mapper
+            .position(end.checked_sub(1.into()).unwrap_or_default())
+            .unwrap()
+            .line;
+
+        if end_line > start_line {
             folding_ranges.push(FoldingRange {
-                start_line: mapper.position(h.start()).unwrap().line as u32,
+                start_line: start_line as u32,
                 start_character: None,
-                end_line: mapper
-                    .position(e.end().checked_sub(1.into()).unwrap_or_default())
-                    .unwrap()
-                    .line as u32,
+                end_line: end_line as u32,
                 end_character: None,
                 kind: Some(FoldingRangeKind::Region),
             });
@@ -221,3 +221,58 @@ pub fn create_folding_ranges(syntax: &SyntaxNode, mapper: &Mapper) -> Vec<Foldin
 
     folding_ranges
 }
+
+/// The path of the unescaped header keys, so that
+/// `[foo]` is not mistaken for the parent of `[foobar]`,
+/// and `[ "a b" ]` is the same table as `['a b']`.
+fn header_path(header: &SyntaxNode) -> Path {
+    header
+        .first_child()
+        .into_iter()
+        .flat_map(|key| key.children_with_tokens())
+        .filter(|t| matches!(t.kind(), IDENT | STRING | STRING_LITERAL))
+        .map(|t| Key::from_syntax(t).value().to_string())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use taplo::parser::parse;
+
+    /// The lines of the folded table sections.
+    fn table_folds(src: &str) -> Vec<(u32, u32)> {
+        let syntax = parse(src).into_syntax();
+        let mapper = Mapper::new_utf16(src, false);
+
+        create_folding_ranges(&syntax, &mapper)
+            .into_iter()
+            .filter(|r| r.kind == Some(FoldingRangeKind::Region) && r.start_character.is_none())
+            .map(|r| (r.start_line, r.end_line))
+            .collect()
+    }
+
+    #[test]
+    fn nested_tables() {
+        let src = "[a]\nx = 1\n[a.b]\ny = 2\n[a.b.c]\nz = 3\n[a.d]\nw = 4\n[e]\nv = 5\n";
+        assert_eq!(table_folds(src), [(0, 7), (2, 5), (4, 5), (6, 7), (8, 9)]);
+    }
+
+    #[test]
+    fn tables_with_shared_prefixes() {
+        let src = "[foo]\nx = 1\n[foobar]\ny = 2\n[foo.bar]\nz = 3\n";
+        assert_eq!(table_folds(src), [(0, 1), (2, 3), (4, 5)]);
+    }
+
+    #[test]
+    fn quoted_header_keys() {
+        let src = "[ \"a b\" ]\nx = 1\n['a b'.c]\ny = 2\n[\"a b c\"]\nz = 3\n";
+        assert_eq!(table_folds(src), [(0, 3), (2, 3), (4, 5)]);
+    }
+
+    #[test]
+    fn whitespace_in_headers() {
+        let src = "[a]\nx = 1\n[ a . b ]\ny = 2\n[[a. b .c]]\nz = 3\n[a .d]\nw = 4\n";
+        assert_eq!(table_folds(src), [(0, 7), (2, 5), (4, 5), (6, 7)]);
+    }
+}
@@ -1,10 +1,15 @@
-use lsp_async_stub::{rpc::Error, util::LspExt, Context, Params};
-use lsp_types::{DocumentFormattingParams, TextEdit};
-use std::path::PathBuf;
+use lsp_async_stub::{
+    rpc::Error,
+    util::{LspExt, Range},
+    Context, Params,
+};
+use lsp_types::{
+    DocumentFormattingParams, DocumentRangeFormattingParams, FormattingOptions, TextEdit,
+};
 use taplo::formatter;
-use taplo_common::{environment::Environment, util::Normalize};
+use taplo_common::environment::Environment;
 
-use crate::World;
+use crate::{world::document_path, World};
 
 #[tracing::instrument(skip_all)]
 pub(crate) async fn format<E: Environment>(
@@ -15,6 +20,7 @@ pub(crate) async fn format<E: Environment>(
 
     let workspaces = context.workspaces.read().await;
     let ws = workspaces.by_document(&p.text_document.uri);
+    let snapshot = ws.snapshot();
     let doc = match ws.document(&p.text_document.uri) {
         Ok(d) => d,
         Err(error) => {
@@ -23,41 +29,105 @@ pub(crate) async fn format<E: Environment>(
         }
     };
 
-    let doc_path = PathBuf::from(p.text_document.uri.as_str()).normalize();
+    let doc_path = document_path(&context.env, &p.text_document.uri);
 
-    let mut format_opts = formatter::Options {
-        indent_string: if p.options.insert_spaces {
-            " ".repeat(p.options.tab_size as usize)
+    let format_opts = snapshot.format_options(&doc_path, base_options(&p.options));
+
+    let edits = formatter::format_edits_with_path_scopes(
+        doc.dom.clone(),
+        format_opts,
+        &doc.parse
+            .errors
+            .iter()
+            .map(|err| err.range)
+            .collect::<Vec<_>>(),
+        snapshot.taplo_config.format_scopes(&doc_path),
+    )
+    .map_err(|err| {
+        tracing::error!(error = %err, "invalid key pattern");
+        Error::internal_error().with_data("invalid Taplo configuration")
+    })?;
+
+    Ok(edits
+        .into_iter()
+        .map(|(range, new_text)| {
+            doc.mapper.range(range).map(|range| TextEdit {
+                range: range.into_lsp(),
+                new_text,
+            })
+        })
+        .collect())
+}
+
+#[tracing::instrument(skip_all)]
+pub(crate) async fn format_range<E: Environment>(
+    context: Context<World<E>>,
+    params: Params<DocumentRangeFormattingParams>,
+) -> Result<Option<Vec<TextEdit>>, Error> {
+    let p = params.required()?;
+
+    let workspaces = context.workspaces.read().await;
+    let ws = workspaces.by_document(&p.text_document.uri);
+    let snapshot = ws.snapshot();
+    let doc = match ws.document(&p.text_document.uri) {
+        Ok(d) => d,
+        Err(error) => {
+            tracing::debug!(%error, "failed to get document from workspace");
+            return Ok(None);
+        }
+    };
+
+    let Some(range) = doc.mapper.text_range(Range::from_lsp(p.range)) else {
+        return Ok(None);
+    };
+
+    let doc_path = document_path(&context.env, &p.text_document.uri);
+    let format_opts = snapshot.format_options(&doc_path, base_options(&p.options));
+
+    let formatted = formatter::format_range_with_path_scopes(
+        doc.dom.clone(),
+        range,
+        format_opts,
+        &doc.parse
+            .errors
+            .iter()
+            .map(|err| err.range)
+            .collect::<Vec<_>>(),
+        snapshot.taplo_config.format_scopes(&doc_path),
+    )
+    .map_err(|err| {
+        tracing::error!(error = %err, "invalid key pattern");
+        Error::internal_error().with_data("invalid Taplo configuration")
+    })?;
+
+    Ok(Some(
+        formatted
+            .and_then(|(range, new_text)| {
+                doc.mapper.range(range).map(|range| TextEdit {
+                    range: range.into_lsp(),
+                    new_text,
+                })
+            })
+            .into_iter()
+            .collect(),
+    ))
+}
+
+/// The formatter options requested by the client,
+/// the configuration is applied on top of these.
+fn base_options(options: &FormattingOptions) -> formatter::Options {
+    let mut base_opts = formatter::Options {
+        indent_string: if options.insert_spaces {
+            " ".repeat(options.tab_size as usize)
         } else {
             "\t".into()
         },
         ..Default::default()
     };
 
-    if let Some(v) = p.options.insert_final_newline {
-        format_opts.trailing_newline = v;
+    if let Some(v) = options.insert_final_newline {
+        base_opts.trailing_newline = v;
     }
 
-    format_opts.update_camel(ws.config.formatter.clone());
-
-    ws.taplo_config
-        .update_format_options(&doc_path, &mut format_opts);
-
-    Ok(Some(vec![TextEdit {
-        range: doc.mapper.all_range().into_lsp(),
-        new_text: taplo::formatter::format_with_path_scopes(
-            doc.dom.clone(),
-            format_opts,
-            &doc.parse
-                .errors
-                .iter()
-                .map(|err| err.range)
-                .collect::<Vec<_>>(),
-            ws.taplo_config.format_scopes(&doc_path),
-        )
-        .map_err(|err| {
-            tracing::error!(error = %err, "invalid key pattern");
-            Error::internal_error().with_data("invalid Taplo configuration")
-        })?,
-    }]))
+    base_opts
 }
@@ -1,3 +1,4 @@
+use super::report_cyclic_references;
 use crate::{
     query::{lookup_keys, Query},
     world::World,
@@ -5,13 +6,14 @@ use crate::{
 use itertools::Itertools;
 use lsp_async_stub::{
     rpc::Error,
-    util::{LspExt, Position},
+    util::{LspExt, Mapper, Position},
     Context, Params,
 };
 use lsp_types::{Hover, HoverContents, HoverParams, MarkupContent, MarkupKind};
 use serde_json::Value;
 use taplo::{
-    dom::{KeyOrIndex, Keys},
+    dom::{KeyOrIndex, Keys, Node},
+    rowan::TextSize,
     syntax::SyntaxKind::{
         self, BOOL, DATE, DATE_TIME_LOCAL, DATE_TIME_OFFSET, IDENT, INTEGER, INTEGER_BIN,
         INTEGER_HEX, INTEGER_OCT, MULTI_LINE_STRING, MULTI_LINE_STRING_LITERAL, STRING,
@@ -26,11 +28,22 @@ pub(crate) async fn hover<E: Environment>(
     params: Params<HoverParams>,
 ) -> Result<Option<Hover>, Error> {
     let p = params.required()?;
+    let document_uri = p.text_document_position_params.text_document.uri.clone();
 
+    let hover = document_hover(context.clone(), p).await;
+    report_cyclic_references(context, &document_uri).await;
+    hover
+}
+
+async fn document_hover<E: Environment>(
+    context: Context<World<E>>,
+    p: HoverParams,
+) -> Result<Option<Hover>, Error> {
     let document_uri = p.text_document_position_params.text_document.uri;
 
     let workspaces = context.workspaces.read().await;
     let ws = workspaces.by_document(&document_uri);
+    let snapshot = ws.snapshot();
     let doc = match ws.document(&document_uri) {
         Ok(d) => d,
         Err(error) => {
@@ -48,6 +61,12 @@ pub(crate) async fn hover<E: Environment>(
         }
     };
 
+    let schema_association = ws.schemas.associations().association_for(&document_uri);
+
+    if schema_association.is_none() {
+        return Ok(array_index_hover(&doc.dom, &doc.mapper, offset));
+    }
+
     let query = Query::at(&doc.dom, offset);
 
     let position_info = match query.before.clone().and_then(|p| {
@@ -70,7 +89,7 @@ pub(crate) async fn hover<E: Environment>(
         },
     };
 
-    if let Some(schema_association) = ws.schemas.associations().association_for(&document_uri) {
+    if let Some(schema_association) = schema_association {
         tracing::debug!(
             schema.url = %schema_association.url,
             schema.name = schema_association.meta["name"].as_str().unwrap_or(""),
@@ -78,20 +97,14 @@ pub(crate) async fn hover<E: Environment>(
             "using schema"
         );
 
-        let value = match serde_json::to_value(&doc.dom) {
-            Ok(v) => v,
-            Err(error) => {
-                tracing::warn!(%error, "cannot turn DOM into JSON");
-                return Ok(None);
-            }
-        };
+        let value = doc.value();
 
         let (keys, _) = match &position_info.dom_node {
             Some(n) => n,
             None => return Ok(None),
         };
 
-        let links_in_hover = !ws.config.schema.links;
+        let links_in_hover = !snapshot.config.schema.links;
 
         let mut keys = keys.clone();
 
@@ -123,7 +136,7 @@ pub(crate) async fn hover<E: Environment>(
 
             let schemas = match ws
                 .schemas
-                .schemas_at_path(&schema_association.url, &value, &keys)
+                .schemas_at_path(&schema_association.url, value, &keys)
                 .await
             {
                 Ok(s) => s,
@@ -161,6 +174,9 @@ pub(crate) async fn hover<E: Environment>(
 
                     s
                 })
+                // Alternatives (e.g. in a `oneOf`) often share their documentation.
+                .filter(|s| !s.is_empty())
+                .unique()
                 .join("\n\n");
 
             if content.is_empty() {
@@ -182,7 +198,7 @@ pub(crate) async fn hover<E: Environment>(
         } else if is_primitive(position_info.syntax.kind()) {
             let schemas = match ws
                 .schemas
-                .schemas_at_path(&schema_association.url, &value, &keys)
+                .schemas_at_path(&schema_association.url, value, &keys)
                 .await
             {
                 Ok(s) => s,
@@ -263,6 +279,8 @@ pub(crate) async fn hover<E: Environment>(
                         "".to_string()
                     }
                 })
+                .filter(|s| !s.is_empty())
+                .unique()
                 .join("\n");
 
             if content.is_empty() {
@@ -287,6 +305,28 @@ pub(crate) async fn hover<E: Environment>(
     Ok(None)
 }
 
+/// Show the position of the array item at the offset
+/// so that the place in long arrays is not lost.
+fn array_index_hover(dom: &Node, mapper: &Mapper, offset: TextSize) -> Option<Hover> {
+    let keys = dom.array_item_at(offset)?;
+    let Some(KeyOrIndex::Index(idx)) = keys.iter().last().cloned() else {
+        return None;
+    };
+
+    let array_keys = keys.skip_right(1);
+    let arr = dom.path(&array_keys)?.try_into_array().ok()?;
+    let range = *arr.item_ranges().get(idx)?;
+    let len = arr.items().read().len();
+
+    Some(Hover {
+        contents: HoverContents::Markup(MarkupContent {
+            kind: MarkupKind::Markdown,
+            value: format!("index {idx} of {len} in `{}`", array_keys.dotted()),
+        }),
+        range: Some(mapper.range(range)?.into_lsp()),
+    })
+}
+
 fn is_primitive(kind: SyntaxKind) -> bool {
     matches!(
         kind,
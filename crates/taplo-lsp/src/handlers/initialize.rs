@@ -1,18 +1,20 @@
 use std::sync::Arc;
 
-use super::{semantic_tokens, update_configuration};
+use super::{apply_configuration, semantic_tokens, update_configuration, SORT_TABLE_COMMAND};
 use crate::config::InitConfig;
-use crate::world::WorkspaceState;
+use crate::world::{WorkspaceState, DEFAULT_WORKSPACE_URL};
 use crate::World;
-use lsp_async_stub::{rpc::Error, Context, Params};
+use lsp_async_stub::{rpc::Error, util::PositionEncoding, Context, Params};
 use lsp_types::{
-    CompletionOptions, DocumentLinkOptions, FoldingRangeProviderCapability,
-    HoverProviderCapability, InitializedParams, OneOf, RenameOptions, SemanticTokensFullOptions,
+    ClientCapabilities, CodeLensOptions, CompletionOptions, DocumentLinkOptions,
+    ExecuteCommandOptions, FoldingRangeProviderCapability, HoverProviderCapability,
+    InitializedParams, OneOf, PositionEncodingKind, RenameOptions, SemanticTokensFullOptions,
     SemanticTokensLegend, SemanticTokensOptions, SemanticTokensServerCapabilities,
     ServerCapabilities, ServerInfo, TextDocumentSyncCapability, TextDocumentSyncKind,
     WorkDoneProgressOptions, WorkspaceFoldersServerCapabilities, WorkspaceServerCapabilities,
 };
 use lsp_types::{InitializeParams, InitializeResult};
+use serde_json::Value;
 use taplo_common::environment::Environment;
 
 #[tracing::instrument(skip_all)]
@@ -22,6 +24,13 @@ pub async fn initialize<E: Environment>(
 ) -> Result<InitializeResult, Error> {
     let p = params.required()?;
 
+    context
+        .client_capabilities
+        .store(Arc::new(p.capabilities.clone()));
+
+    let position_encoding = negotiate_position_encoding(&p.capabilities);
+    context.position_encoding.store(Arc::new(position_encoding));
+
     if let Some(init_opts) = p.initialization_options {
         match serde_json::from_value::<InitConfig>(init_opts) {
             Ok(c) => context.init_config.store(Arc::new(c)),
@@ -31,27 +40,41 @@ pub async fn initialize<E: Environment>(
         }
     }
 
-    if let Some(workspaces) = p.workspace_folders {
-        let mut wss = context.workspaces.write().await;
-        let init_config = context.init_config.load();
+    let init_config = context.init_config.load();
+    let configuration = Value::Object(init_config.configuration.clone());
 
-        for workspace in workspaces {
-            let ws = wss
-                .entry(workspace.uri.clone())
-                .or_insert(WorkspaceState::new(context.env.clone(), workspace.uri));
+    let mut updates = Vec::new();
 
-            ws.schemas
-                .cache()
-                .set_cache_path(init_config.cache_path.clone());
+    // The default workspace is otherwise only initialized
+    // once the configuration is fetched from the client.
+    if !init_config.configuration.is_empty() {
+        updates.push((DEFAULT_WORKSPACE_URL.clone(), vec![configuration.clone()]));
+    }
 
-            if let Err(error) = ws.initialize(context.clone(), &context.env).await {
-                tracing::error!(?error, "failed to initialize workspace");
-            }
-        }
+    let mut wss = context.workspaces.write().await;
+
+    for workspace in p.workspace_folders.into_iter().flatten() {
+        let ws = wss
+            .entry(workspace.uri.clone())
+            .or_insert(WorkspaceState::new(
+                context.env.clone(),
+                workspace.uri.clone(),
+            ));
+
+        ws.schemas
+            .cache()
+            .set_cache_path(init_config.cache_path.clone());
+
+        updates.push((workspace.uri, vec![configuration.clone()]));
     }
 
+    drop(wss);
+
+    apply_configuration(context.clone(), updates).await;
+
     Ok(InitializeResult {
         capabilities: ServerCapabilities {
+            position_encoding: Some(PositionEncodingKind::new(position_encoding.as_str())),
             workspace: Some(WorkspaceServerCapabilities {
                 workspace_folders: Some(WorkspaceFoldersServerCapabilities {
                     supported: Some(true),
@@ -59,7 +82,9 @@ pub async fn initialize<E: Environment>(
                 }),
                 ..Default::default()
             }),
-            text_document_sync: Some(TextDocumentSyncCapability::Kind(TextDocumentSyncKind::FULL)),
+            text_document_sync: Some(TextDocumentSyncCapability::Kind(
+                TextDocumentSyncKind::INCREMENTAL,
+            )),
             semantic_tokens_provider: Some(
                 SemanticTokensServerCapabilities::SemanticTokensOptions(SemanticTokensOptions {
                     work_done_progress_options: WorkDoneProgressOptions {
@@ -70,7 +95,7 @@ pub async fn initialize<E: Environment>(
                         token_modifiers: semantic_tokens::TokenModifier::MODIFIERS.into(),
                     },
                     full: Some(SemanticTokensFullOptions::Bool(true)),
-                    range: Some(false),
+                    range: Some(true),
                 }),
             ),
             rename_provider: Some(OneOf::Right(RenameOptions {
@@ -80,6 +105,7 @@ pub async fn initialize<E: Environment>(
             folding_range_provider: Some(FoldingRangeProviderCapability::Simple(true)),
             document_symbol_provider: Some(OneOf::Left(true)),
             document_formatting_provider: Some(OneOf::Left(true)),
+            document_range_formatting_provider: Some(OneOf::Left(true)),
             hover_provider: Some(HoverProviderCapability::Simple(true)),
             completion_provider: Some(CompletionOptions {
                 resolve_provider: Some(false),
@@ -97,16 +123,53 @@ pub async fn initialize<E: Environment>(
                 resolve_provider: None,
                 work_done_progress_options: Default::default(),
             }),
+            code_lens_provider: Some(CodeLensOptions {
+                resolve_provider: Some(true),
+            }),
+            execute_command_provider: Some(ExecuteCommandOptions {
+                commands: vec![SORT_TABLE_COMMAND.into()],
+                work_done_progress_options: Default::default(),
+            }),
             ..Default::default()
         },
         server_info: Some(ServerInfo {
             name: "Taplo".into(),
             version: Some(env!("CARGO_PKG_VERSION").into()),
         }),
-        offset_encoding: None,
+        offset_encoding: p
+            .capabilities
+            .offset_encoding
+            .is_some()
+            .then(|| position_encoding.as_str().into()),
     })
 }
 
+/// The first supported encoding in the client's order of preference.
+///
+/// The `offsetEncoding` extension is only used if the client does not
+/// list the encodings of the protocol, and clients that list neither
+/// only support UTF-16.
+fn negotiate_position_encoding(capabilities: &ClientCapabilities) -> PositionEncoding {
+    let names: Vec<&str> = match capabilities
+        .general
+        .as_ref()
+        .and_then(|general| general.position_encodings.as_ref())
+    {
+        Some(encodings) => encodings.iter().map(PositionEncodingKind::as_str).collect(),
+        None => capabilities
+            .offset_encoding
+            .iter()
+            .flatten()
+            .map(String::as_str)
+            .collect(),
+    };
+
+    names
+        .into_iter()
+        .find_map(PositionEncoding::from_name)
+        .unwrap_or_default()
+}
+
 #[tracing::instrument(skip_all)]
 pub async fn initialized<E: Environment>(
     context: Context<World<E>>,
@@ -116,3 +179,43 @@ pub async fn initialized<E: Environment>(
         .env
         .spawn_local(update_configuration(context.clone()));
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn negotiate(capabilities: Value) -> PositionEncoding {
+        negotiate_position_encoding(&serde_json::from_value(capabilities).unwrap())
+    }
+
+    #[test]
+    fn position_encoding_negotiation() {
+        assert_eq!(negotiate(json!({})), PositionEncoding::Utf16);
+        assert_eq!(
+            negotiate(json!({ "general": { "positionEncodings": ["utf-32", "utf-8"] } })),
+            PositionEncoding::Utf32
+        );
+        assert_eq!(
+            negotiate(json!({ "general": { "positionEncodings": ["utf-7", "utf-8"] } })),
+            PositionEncoding::Utf8
+        );
+        assert_eq!(
+            negotiate(json!({ "general": { "positionEncodings": ["utf-7"] } })),
+            PositionEncoding::Utf16
+        );
+
+        // The extension of clangd.
+        assert_eq!(
+            negotiate(json!({ "offsetEncoding": ["utf-8", "utf-16"] })),
+            PositionEncoding::Utf8
+        );
+        assert_eq!(
+            negotiate(json!({
+                "general": { "positionEncodings": ["utf-16"] },
+                "offsetEncoding": ["utf-8"]
+            })),
+            PositionEncoding::Utf16
+        );
+    }
+}
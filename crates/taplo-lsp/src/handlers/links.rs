@@ -16,8 +16,9 @@ pub async fn links<E: Environment>(
 
     let workspaces = context.workspaces.write().await;
     let ws = workspaces.by_document(&p.text_document.uri);
+    let snapshot = ws.snapshot();
 
-    if !ws.config.schema.enabled || !ws.config.schema.links {
+    if !snapshot.config.schema.enabled {
         return Ok(None);
     }
 
@@ -31,6 +32,25 @@ pub async fn links<E: Environment>(
 
     let mut links = Vec::new();
 
+    if let Some((directive, schema_url)) = ws
+        .schemas
+        .associations()
+        .schema_directive(&p.text_document.uri, &doc.dom)
+    {
+        if let Some(range) = directive.value_text_range() {
+            links.push(DocumentLink {
+                range: doc.mapper.range(range).unwrap().into_lsp(),
+                target: Some(schema_url),
+                tooltip: Some("Open schema".into()),
+                data: None,
+            });
+        }
+    }
+
+    if !snapshot.config.schema.links {
+        return Ok(Some(links));
+    }
+
     if let Some(schema_association) = ws
         .schemas
         .associations()
@@ -43,13 +63,11 @@ pub async fn links<E: Environment>(
             "using schema"
         );
 
-        for (keys, last_key, node) in doc.dom.flat_iter().filter_map(|(k, n)| {
-            if let Some(KeyOrIndex::Key(last_key)) = k.iter().last().cloned() {
-                Some((k, last_key, n))
-            } else {
-                None
-            }
-        }) {
+        for (keys, node) in doc.dom.flat_entries(false) {
+            let Some(KeyOrIndex::Key(last_key)) = keys.iter().last().cloned() else {
+                continue;
+            };
+
             let value = match serde_json::to_value(&node) {
                 Ok(v) => v,
                 Err(error) => {
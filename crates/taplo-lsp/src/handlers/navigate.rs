@@ -0,0 +1,256 @@
+use crate::{
+    lsp_ext::request::{NavigateDirection, NavigateParams, NavigateResponse},
+    query::Query,
+    world::World,
+};
+use lsp_async_stub::{rpc::Error, util::LspExt, util::Position, Context, Params};
+use taplo::{
+    dom::{
+        node::{ArrayKind, Table},
+        KeyOrIndex, Keys, Node,
+    },
+    rowan::{TextRange, TextSize},
+};
+use taplo_common::environment::Environment;
+
+#[tracing::instrument(skip_all)]
+pub(crate) async fn navigate<E: Environment>(
+    context: Context<World<E>>,
+    params: Params<NavigateParams>,
+) -> Result<NavigateResponse, Error> {
+    let p = params.required()?;
+
+    let workspaces = context.workspaces.read().await;
+    let ws = workspaces.by_document(&p.document_uri);
+    let doc = ws.document(&p.document_uri)?;
+
+    let Some(offset) = doc.mapper.offset(Position::from_lsp(p.position)) else {
+        tracing::error!(position = ?p.position, "document position not found");
+        return Ok(NavigateResponse { range: None });
+    };
+
+    Ok(NavigateResponse {
+        range: navigate_target(&doc.dom, offset, p.direction)
+            .and_then(|range| doc.mapper.range(range))
+            .map(LspExt::into_lsp),
+    })
+}
+
+/// The range to jump to from the node at the given offset.
+///
+/// Entries of tables are visited in document order, entries
+/// of the same table that are defined elsewhere with dotted keys
+/// are siblings as well, their target is where they are defined.
+pub(crate) fn navigate_target(
+    root: &Node,
+    offset: TextSize,
+    direction: NavigateDirection,
+) -> Option<TextRange> {
+    let query = Query::at(root, offset);
+
+    // The cursor is usually in front of the node it is on,
+    // or right after it at the end of a line.
+    let (keys, node) = [query.after, query.before]
+        .into_iter()
+        .flatten()
+        .find_map(|p| node_at(root, p.syntax.text_range()))
+        .unwrap_or_else(|| (Keys::empty(), root.clone()));
+
+    match direction {
+        NavigateDirection::NextSibling | NavigateDirection::PrevSibling => {
+            let last = keys.iter().last()?.clone();
+            let parent = root.path(&keys.skip_right(1))?;
+            let siblings = children(&parent);
+            let idx = siblings.iter().position(|(k, _)| *k == last)?;
+
+            let sibling = if direction == NavigateDirection::NextSibling {
+                siblings.get(idx + 1)?
+            } else {
+                siblings.get(idx.checked_sub(1)?)?
+            };
+
+            target_range(&sibling.0, &sibling.1)
+        }
+        NavigateDirection::Parent => (1..keys.len()).find_map(|n| {
+            let parent_keys = keys.skip_right(n);
+            let parent = root.path(&parent_keys)?;
+
+            match &parent {
+                // The tables of arrays of tables are reached through their headers.
+                Node::Array(arr) if arr.kind() == ArrayKind::Tables => return None,
+                Node::Table(t) => {
+                    if let Some(header) = t.header_range() {
+                        return Some(header);
+                    }
+                }
+                _ => {}
+            }
+
+            match parent_keys.iter().last()? {
+                KeyOrIndex::Key(k) => k.text_ranges().next(),
+                KeyOrIndex::Index(_) => None,
+            }
+        }),
+        NavigateDirection::FirstChild => children(&node)
+            .first()
+            .and_then(|(k, n)| target_range(k, n)),
+    }
+}
+
+/// The narrowest node that covers the token at the cursor.
+///
+/// Unlike [`Query::dom_node`], array items only cover
+/// their own ranges and not the key of the array.
+fn node_at(root: &Node, token: TextRange) -> Option<(Keys, Node)> {
+    root.flat_iter()
+        .filter(|(keys, node)| {
+            let key_range = match keys.iter().last() {
+                Some(KeyOrIndex::Key(k)) => k.text_ranges().next(),
+                _ => None,
+            };
+
+            key_range
+                .into_iter()
+                .chain(node.text_ranges())
+                .any(|range| range.contains_range(token))
+        })
+        .max_by_key(|(keys, _)| keys.len())
+}
+
+/// The entries of a table or the items of an array.
+fn children(node: &Node) -> Vec<(KeyOrIndex, Node)> {
+    match node {
+        Node::Table(t) => t
+            .entries()
+            .read()
+            .iter()
+            .map(|(k, n)| (KeyOrIndex::Key(k.clone()), n.clone()))
+            .collect(),
+        Node::Array(arr) => arr
+            .items()
+            .read()
+            .iter()
+            .enumerate()
+            .map(|(idx, n)| (KeyOrIndex::Index(idx), n.clone()))
+            .collect(),
+        _ => Vec::new(),
+    }
+}
+
+/// Entries are targeted at their keys, tables
+/// of arrays of tables at their headers.
+fn target_range(key: &KeyOrIndex, node: &Node) -> Option<TextRange> {
+    match key {
+        KeyOrIndex::Key(k) => k.text_ranges().next(),
+        KeyOrIndex::Index(_) => node
+            .as_table()
+            .and_then(Table::header_range)
+            .or_else(|| node.text_ranges().next()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use NavigateDirection::*;
+
+    const SRC: &str = r#"a.x = 1
+top = [1, { inner = 2 }]
+a.y = 2
+
+[table]
+first = 1
+dotted.key = "value"
+last = 3
+
+[[bin]]
+name = "one"
+
+[[bin]]
+name = "two"
+"#;
+
+    /// Navigate from the n-th occurrence of `at`
+    /// and return the target as `line:text`.
+    fn navigate_nth(at: &str, n: usize, direction: NavigateDirection) -> Option<String> {
+        let root = taplo::parser::parse(SRC).into_dom();
+        let (offset, _) = SRC.match_indices(at).nth(n).unwrap();
+        let range = navigate_target(&root, TextSize::try_from(offset).unwrap(), direction)?;
+
+        let line = SRC[..usize::from(range.start())].matches('\n').count();
+        Some(format!("{line}:{}", &SRC[range]))
+    }
+
+    fn navigate(at: &str, direction: NavigateDirection) -> Option<String> {
+        navigate_nth(at, 0, direction)
+    }
+
+    #[test]
+    fn siblings() {
+        assert_eq!(navigate("first", NextSibling).unwrap(), "6:dotted");
+        assert_eq!(navigate("dotted", NextSibling).unwrap(), "7:last");
+        assert_eq!(navigate("last", PrevSibling).unwrap(), "6:dotted");
+        assert_eq!(navigate("last", NextSibling), None);
+        assert_eq!(navigate("first", PrevSibling), None);
+
+        // Dotted keys of the same table defined elsewhere.
+        assert_eq!(navigate("x", NextSibling).unwrap(), "2:y");
+        assert_eq!(navigate("y", PrevSibling).unwrap(), "0:x");
+        assert_eq!(navigate("y", NextSibling), None);
+
+        // Top level entries in document order.
+        assert_eq!(navigate("a.x", NextSibling).unwrap(), "1:top");
+        assert_eq!(navigate("top", PrevSibling).unwrap(), "0:a");
+        assert_eq!(navigate("top", NextSibling).unwrap(), "4:table");
+        assert_eq!(navigate("[table]", NextSibling).unwrap(), "9:bin");
+        assert_eq!(navigate("[table]", PrevSibling).unwrap(), "1:top");
+
+        // Array items.
+        assert_eq!(navigate("1, {", NextSibling).unwrap(), "1:{ inner = 2 }");
+        assert_eq!(navigate("{ inner", PrevSibling).unwrap(), "1:1");
+        assert_eq!(navigate("{ inner", NextSibling), None);
+
+        // Tables of arrays of tables.
+        assert_eq!(
+            navigate_nth("[[bin]]", 0, NextSibling).unwrap(),
+            "12:[[bin]]"
+        );
+        assert_eq!(
+            navigate_nth("[[bin]]", 1, PrevSibling).unwrap(),
+            "9:[[bin]]"
+        );
+        assert_eq!(navigate_nth("[[bin]]", 1, NextSibling), None);
+        assert_eq!(navigate("name", NextSibling), None);
+    }
+
+    #[test]
+    fn parent() {
+        assert_eq!(navigate("first", Parent).unwrap(), "4:[table]");
+        assert_eq!(navigate("key", Parent).unwrap(), "6:dotted");
+        assert_eq!(navigate("inner", Parent).unwrap(), "1:top");
+        assert_eq!(navigate("1, {", Parent).unwrap(), "1:top");
+        assert_eq!(navigate("y", Parent).unwrap(), "0:a");
+        assert_eq!(navigate_nth("name", 1, Parent).unwrap(), "12:[[bin]]");
+
+        // There is nothing above the top level.
+        assert_eq!(navigate("top", Parent), None);
+        assert_eq!(navigate("[table]", Parent), None);
+        assert_eq!(navigate("[[bin]]", Parent), None);
+    }
+
+    #[test]
+    fn first_child() {
+        assert_eq!(navigate("[table]", FirstChild).unwrap(), "5:first");
+        assert_eq!(navigate("top", FirstChild).unwrap(), "1:1");
+        assert_eq!(navigate("{ inner", FirstChild).unwrap(), "1:inner");
+        assert_eq!(navigate("dotted", FirstChild).unwrap(), "6:key");
+        assert_eq!(navigate("[[bin]]", FirstChild).unwrap(), "10:name");
+        assert_eq!(navigate("first", FirstChild), None);
+
+        // Empty lines at the top level are in the root table.
+        let root = taplo::parser::parse(SRC).into_dom();
+        let empty_line = SRC.find("\n\n[table]").unwrap() + 1;
+        let range = navigate_target(&root, TextSize::try_from(empty_line).unwrap(), FirstChild);
+        assert_eq!(range.map(|r| &SRC[r]), Some("a"));
+    }
+}
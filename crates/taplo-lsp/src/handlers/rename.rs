@@ -145,7 +145,9 @@ pub async fn rename<E: Environment>(
         keys = keys.skip_right(1);
     }
 
-    rewrite.rename_keys(keys.dotted(), &p.new_name).unwrap();
+    if let Err(error) = rewrite.rename_keys(keys.dotted(), &p.new_name) {
+        return Err(Error::new(&error.to_string()).with_code(Error::invalid_params().code));
+    }
 
     Ok(Some(WorkspaceEdit {
         changes: Some(HashMap::from([(
@@ -1,16 +1,19 @@
 use crate::{
-    diagnostics::publish_diagnostics,
+    diagnostics::{publish_all_diagnostics, publish_diagnostics},
     lsp_ext::{
-        notification::{self, AssociateSchemaParams},
+        notification::{self, AssociateSchemaParams, MessageKind, MessageWithOutputParams},
         request::{
-            AssociatedSchemaParams, AssociatedSchemaResponse, ListSchemasParams,
-            ListSchemasResponse, SchemaInfo,
+            AssociatedSchemaParams, AssociatedSchemaResponse, GenerateSchemaParams,
+            GenerateSchemaResponse, ListSchemasParams, ListSchemasResponse, SchemaInfo,
         },
     },
     world::World,
 };
-use lsp_async_stub::{rpc::Error, Context, Params};
+use itertools::Itertools;
+use lsp_async_stub::{rpc::Error, Context, Params, RequestWriter};
+use lsp_types::Url;
 use serde_json::json;
+use taplo::schema_gen;
 use taplo_common::{
     environment::Environment,
     schema::associations::{priority, source, AssociationRule, SchemaAssociation},
@@ -119,6 +122,83 @@ pub async fn associate_schema<E: Environment>(
     }
 }
 
+/// Download the schemas of open documents again, bypassing the cache,
+/// and report the ones that could not be loaded.
+#[tracing::instrument(skip_all)]
+pub async fn reload_schemas<E: Environment>(mut context: Context<World<E>>, _params: Params<()>) {
+    // Schemas are loaded without holding the lock on the workspaces.
+    let pending = context
+        .workspaces
+        .read()
+        .await
+        .iter()
+        .map(|(_, ws)| {
+            ws.schemas.reload();
+
+            let schema_urls = ws
+                .documents
+                .keys()
+                .filter_map(|document_url| ws.schemas.associations().association_for(document_url))
+                .map(|assoc| assoc.url)
+                .unique()
+                .collect::<Vec<_>>();
+
+            (ws.schemas.clone(), schema_urls)
+        })
+        .collect::<Vec<_>>();
+
+    for (schemas, schema_urls) in pending {
+        for schema_url in schema_urls {
+            if let Err(error) = schemas.load_schema(&schema_url).await {
+                if let Err(error) = context
+                    .write_notification::<notification::MessageWithOutput, _>(Some(
+                        MessageWithOutputParams {
+                            kind: MessageKind::Error,
+                            message: format!("Failed to load schema {schema_url}: {error}"),
+                        },
+                    ))
+                    .await
+                {
+                    tracing::error!(%error, "failed to write notification");
+                }
+            }
+        }
+    }
+
+    publish_all_diagnostics(context).await;
+}
+
+/// Tell the user about the cyclic references that were found
+/// in the schemas of the workspace of the document.
+///
+/// Every reference is only reported once.
+pub(crate) async fn report_cyclic_references<E: Environment>(
+    mut context: Context<World<E>>,
+    document_uri: &Url,
+) {
+    let cyclic_refs = context
+        .workspaces
+        .read()
+        .await
+        .by_document(document_uri)
+        .schemas
+        .take_cyclic_references();
+
+    for schema_url in cyclic_refs {
+        if let Err(error) = context
+            .write_notification::<notification::MessageWithOutput, _>(Some(
+                MessageWithOutputParams {
+                    kind: MessageKind::Warn,
+                    message: format!("Cyclic reference in schema: {schema_url}"),
+                },
+            ))
+            .await
+        {
+            tracing::error!(%error, "failed to write notification");
+        }
+    }
+}
+
 #[tracing::instrument(skip_all)]
 pub async fn associated_schema<E: Environment>(
     context: Context<World<E>>,
@@ -140,3 +220,36 @@ pub async fn associated_schema<E: Environment>(
             }),
     })
 }
+
+#[tracing::instrument(skip_all)]
+pub async fn generate_schema<E: Environment>(
+    context: Context<World<E>>,
+    params: Params<GenerateSchemaParams>,
+) -> Result<GenerateSchemaResponse, Error> {
+    let p = params.required()?;
+
+    let workspaces = context.workspaces.read().await;
+    let ws = workspaces.by_document(&p.document_uri);
+    let doc = match ws.document(&p.document_uri) {
+        Ok(d) => d,
+        Err(error) => {
+            return Ok(GenerateSchemaResponse {
+                text: None,
+                error: Some(error.to_string()),
+            })
+        }
+    };
+
+    let schema = schema_gen::from_dom(&doc.dom, &Default::default());
+
+    match serde_json::to_string_pretty(&schema) {
+        Ok(text) => Ok(GenerateSchemaResponse {
+            text: Some(text),
+            error: None,
+        }),
+        Err(err) => Ok(GenerateSchemaResponse {
+            text: None,
+            error: Some(err.to_string()),
+        }),
+    }
+}
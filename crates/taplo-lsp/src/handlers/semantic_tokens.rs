@@ -1,20 +1,22 @@
 use crate::World;
 use lsp_async_stub::{
     rpc::Error,
-    util::{relative_range, LspExt, Mapper},
+    util::{LspExt, PositionEncoding},
     Context, Params,
 };
 use lsp_types::{
-    Range, SemanticToken, SemanticTokenModifier, SemanticTokenType, SemanticTokens,
-    SemanticTokensParams, SemanticTokensResult,
+    SemanticToken, SemanticTokenModifier, SemanticTokenType, SemanticTokens, SemanticTokensParams,
+    SemanticTokensRangeParams, SemanticTokensRangeResult, SemanticTokensResult,
 };
 use taplo::{
     dom::node::DomNode,
+    rowan::TextRange,
     syntax::{
         SyntaxElement,
-        SyntaxKind::{ARRAY, IDENT, INLINE_TABLE},
+        SyntaxKind::{ARRAY, IDENT, INLINE_TABLE, WHITESPACE},
         SyntaxNode, SyntaxToken,
     },
+    util::overlaps,
 };
 use taplo_common::environment::Environment;
 
@@ -27,8 +29,9 @@ pub(crate) async fn semantic_tokens<E: Environment>(
 
     let workspaces = context.workspaces.read().await;
     let ws = workspaces.by_document(&p.text_document.uri);
+    let snapshot = ws.snapshot();
 
-    if !ws.config.syntax.semantic_tokens {
+    if !snapshot.config.syntax.semantic_tokens {
         return Ok(None);
     }
 
@@ -42,7 +45,51 @@ pub(crate) async fn semantic_tokens<E: Environment>(
 
     Ok(Some(SemanticTokensResult::Tokens(SemanticTokens {
         result_id: None,
-        data: create_tokens(doc.dom.syntax().unwrap().as_node().unwrap(), &doc.mapper),
+        data: create_tokens(
+            doc.dom.syntax().unwrap().as_node().unwrap(),
+            None,
+            doc.mapper.encoding(),
+        ),
+    })))
+}
+
+#[tracing::instrument(skip_all)]
+pub(crate) async fn semantic_tokens_range<E: Environment>(
+    context: Context<World<E>>,
+    params: Params<SemanticTokensRangeParams>,
+) -> Result<Option<SemanticTokensRangeResult>, Error> {
+    let p = params.required()?;
+
+    let workspaces = context.workspaces.read().await;
+    let ws = workspaces.by_document(&p.text_document.uri);
+    let snapshot = ws.snapshot();
+
+    if !snapshot.config.syntax.semantic_tokens {
+        return Ok(None);
+    }
+
+    let doc = match ws.document(&p.text_document.uri) {
+        Ok(d) => d,
+        Err(error) => {
+            tracing::debug!(%error, "failed to get document from workspace");
+            return Ok(None);
+        }
+    };
+
+    let Some(range) = doc
+        .mapper
+        .text_range(lsp_async_stub::util::Range::from_lsp(p.range))
+    else {
+        return Ok(None);
+    };
+
+    Ok(Some(SemanticTokensRangeResult::Tokens(SemanticTokens {
+        result_id: None,
+        data: create_tokens(
+            doc.dom.syntax().unwrap().as_node().unwrap(),
+            Some(range),
+            doc.mapper.encoding(),
+        ),
     })))
 }
 
@@ -72,89 +119,224 @@ impl TokenModifier {
     pub const MODIFIERS: &'static [SemanticTokenModifier] = &[SemanticTokenModifier::READONLY];
 }
 
+/// Create the semantic tokens of the keys in the syntax tree,
+/// or only the ones that overlap the given range.
+///
+/// The tokens are delta encoded as the protocol requires, their positions
+/// are zero-based with characters in the given encoding like the positions
+/// of the documents' mappers. They are computed in a single pass over the
+/// tokens of the tree instead of looking up every key in the mapper.
+#[must_use]
 #[tracing::instrument(skip_all)]
-pub fn create_tokens(syntax: &SyntaxNode, mapper: &Mapper) -> Vec<SemanticToken> {
-    let mut builder = SemanticTokensBuilder::new(mapper);
-
-    for element in syntax.descendants_with_tokens() {
-        match element {
-            SyntaxElement::Node(_node) => {}
-            SyntaxElement::Token(token) => {
-                if let IDENT = token.kind() {
-                    // look for an inline table value
-                    let is_table_key = token
-                        .parent()
-                        .and_then(|p| p.next_sibling())
-                        .and_then(|t| t.first_child())
-                        .map_or(false, |t| t.kind() == INLINE_TABLE);
-
-                    if is_table_key {
-                        builder.add_token(&token, TokenType::TomlTableKey, &[]);
-                        continue;
-                    }
-
-                    // look for an array
-                    let is_array_key = token
-                        .parent()
-                        .and_then(|p| p.next_sibling())
-                        .and_then(|t| t.first_child())
-                        .map_or(false, |t| t.kind() == ARRAY);
-
-                    if is_array_key {
-                        builder.add_token(&token, TokenType::TomlArrayKey, &[]);
-                    }
-                }
+pub fn create_tokens(
+    syntax: &SyntaxNode,
+    range: Option<TextRange>,
+    encoding: PositionEncoding,
+) -> Vec<SemanticToken> {
+    let mut tokens = Vec::new();
+
+    // The position of the current token.
+    let mut line = 0;
+    let mut character = 0;
+
+    // The position of the last semantic token.
+    let mut last_line = 0;
+    let mut last_character = 0;
+
+    // `next_token` would stop at empty nodes that error recovery can produce.
+    let all_tokens = syntax
+        .descendants_with_tokens()
+        .filter_map(SyntaxElement::into_token);
+
+    for token in all_tokens {
+        if range.is_some_and(|range| token.text_range().start() > range.end()) {
+            break;
+        }
+
+        let text = token.text();
+
+        // Whitespace is the most common token and never contains line breaks.
+        if token.kind() == WHITESPACE {
+            character += text_len(encoding, text);
+            continue;
+        }
+
+        let in_range = range.map_or(true, |range| overlaps(range, token.text_range()));
+
+        if let (IDENT, true) = (token.kind(), in_range) {
+            if let Some(ty) = key_token_type(&token) {
+                let delta_line = line - last_line;
+
+                tokens.push(SemanticToken {
+                    delta_line,
+                    delta_start: if delta_line == 0 {
+                        character - last_character
+                    } else {
+                        character
+                    },
+                    length: text_len(encoding, text),
+                    token_type: ty as u32,
+                    token_modifiers_bitset: 0,
+                });
+
+                last_line = line;
+                last_character = character;
             }
         }
+
+        match text.rfind('\n') {
+            Some(idx) => {
+                line += count_lines(text);
+                character = text_len(encoding, &text[idx + 1..]);
+            }
+            None => character += text_len(encoding, text),
+        }
+    }
+
+    tokens
+}
+
+/// Keys of inline tables and arrays have semantic tokens.
+fn key_token_type(token: &SyntaxToken) -> Option<TokenType> {
+    let value = token.parent()?.next_sibling()?.first_child()?;
+
+    match value.kind() {
+        INLINE_TABLE => Some(TokenType::TomlTableKey),
+        ARRAY => Some(TokenType::TomlArrayKey),
+        _ => None,
     }
+}
 
-    builder.build()
+#[allow(clippy::cast_possible_truncation)]
+fn text_len(encoding: PositionEncoding, text: &str) -> u32 {
+    encoding.text_len(text) as u32
 }
 
-struct SemanticTokensBuilder<'b> {
-    tokens: Vec<SemanticToken>,
-    mapper: &'b Mapper,
-    last_range: Option<Range>,
+#[allow(clippy::cast_possible_truncation)]
+fn count_lines(text: &str) -> u32 {
+    text.bytes().filter(|b| *b == b'\n').count() as u32
 }
 
-impl<'b> SemanticTokensBuilder<'b> {
-    fn new(mapper: &'b Mapper) -> Self {
-        Self {
-            tokens: Vec::new(),
-            mapper,
-            last_range: None,
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use lsp_async_stub::util::{relative_range, Mapper};
+    use lsp_types::Range;
+    use std::path::Path;
+
+    /// The previous implementation that looked up every token in the mapper.
+    #[allow(clippy::cast_possible_truncation)]
+    fn create_tokens_with_mapper(syntax: &SyntaxNode, mapper: &Mapper) -> Vec<SemanticToken> {
+        let mut tokens = Vec::new();
+        let mut last_range: Option<Range> = None;
+
+        for element in syntax.descendants_with_tokens() {
+            let SyntaxElement::Token(token) = element else {
+                continue;
+            };
+
+            if token.kind() != IDENT {
+                continue;
+            }
+
+            let Some(ty) = key_token_type(&token) else {
+                continue;
+            };
+
+            let range = mapper.range(token.text_range()).unwrap();
+            let relative = relative_range(
+                range,
+                lsp_async_stub::util::Range::from_lsp(last_range.unwrap_or_default()),
+            );
+
+            tokens.push(SemanticToken {
+                delta_line: relative.start.line as u32,
+                delta_start: relative.start.character as u32,
+                length: (relative.end.character - relative.start.character) as u32,
+                token_type: ty as u32,
+                token_modifiers_bitset: 0,
+            });
+
+            last_range = Some(range.into_lsp());
+        }
+
+        tokens
+    }
+
+    fn corpus(dir: &Path, sources: &mut Vec<String>) {
+        for entry in std::fs::read_dir(dir).unwrap() {
+            let path = entry.unwrap().path();
+
+            if path.is_dir() {
+                corpus(&path, sources);
+            } else if path.extension().is_some_and(|ext| ext == "toml") {
+                if let Ok(src) = std::fs::read_to_string(&path) {
+                    sources.push(src);
+                }
+            }
         }
     }
 
-    fn add_token(
-        &mut self,
-        token: &SyntaxToken,
-        ty: TokenType,
-        modifiers: &[SemanticTokenModifier],
-    ) {
-        let range = self.mapper.range(token.text_range()).unwrap();
-
-        let relative = relative_range(
-            range,
-            lsp_async_stub::util::Range::from_lsp(self.last_range.unwrap_or_default()),
+    #[test]
+    fn same_tokens_as_mapper() {
+        let mut sources = vec![
+            "a = { b = [1] }\n\"é😀\" = [2]\r\nc.d = { e = 3 }\n".to_string(),
+            "s = \"\"\"\nline\n\"\"\" ; x = []\n\u{FEFF}y = {}\r\rz = []".to_string(),
+        ];
+        corpus(
+            &Path::new(env!("CARGO_MANIFEST_DIR")).join("../../test-data"),
+            &mut sources,
         );
 
-        #[allow(clippy::cast_possible_truncation)]
-        self.tokens.push(SemanticToken {
-            delta_line: relative.start.line as u32,
-            delta_start: relative.start.character as u32,
-            length: (relative.end.character - relative.start.character) as u32,
-            token_type: ty as u32,
-            token_modifiers_bitset: modifiers.iter().enumerate().fold(0, |mut total, (i, _)| {
-                total += 1 << i;
-                total
-            }),
-        });
-
-        self.last_range = Some(range.into_lsp());
+        for src in &sources {
+            let syntax = taplo::parser::parse(src).into_syntax();
+
+            for encoding in [
+                PositionEncoding::Utf8,
+                PositionEncoding::Utf16,
+                PositionEncoding::Utf32,
+            ] {
+                let mapper = Mapper::new(src, encoding, false);
+
+                assert_eq!(
+                    create_tokens(&syntax, None, encoding),
+                    create_tokens_with_mapper(&syntax, &mapper),
+                    "{encoding:?}: {src}"
+                );
+            }
+        }
     }
 
-    fn build(self) -> Vec<SemanticToken> {
-        self.tokens
+    #[test]
+    fn tokens_in_range() {
+        let src = "a = [1]\nb = { c = [2] }\nd = [3]\n";
+        let syntax = taplo::parser::parse(src).into_syntax();
+
+        let start = src.find('b').unwrap();
+        let range = TextRange::new(
+            u32::try_from(start).unwrap().into(),
+            u32::try_from(src.find('d').unwrap() - 1).unwrap().into(),
+        );
+
+        let tokens = create_tokens(&syntax, Some(range), PositionEncoding::Utf16);
+        assert_eq!(
+            tokens,
+            [
+                SemanticToken {
+                    delta_line: 1,
+                    delta_start: 0,
+                    length: 1,
+                    token_type: TokenType::TomlTableKey as u32,
+                    token_modifiers_bitset: 0,
+                },
+                SemanticToken {
+                    delta_line: 0,
+                    delta_start: 6,
+                    length: 1,
+                    token_type: TokenType::TomlArrayKey as u32,
+                    token_modifiers_bitset: 0,
+                },
+            ]
+        );
     }
 }
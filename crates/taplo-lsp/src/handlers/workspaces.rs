@@ -1,7 +1,8 @@
-use super::update_configuration;
+use super::{apply_configuration, update_configuration};
 use crate::world::{WorkspaceState, World};
 use lsp_async_stub::{Context, Params};
 use lsp_types::DidChangeWorkspaceFoldersParams;
+use serde_json::Value;
 use taplo_common::environment::Environment;
 
 pub async fn workspace_change<E: Environment>(
@@ -20,20 +21,24 @@ pub async fn workspace_change<E: Environment>(
         workspaces.remove(&removed.uri);
     }
 
+    let mut updates = Vec::new();
+
     for added in p.event.added {
         let ws = workspaces
             .entry(added.uri.clone())
-            .or_insert(WorkspaceState::new(context.env.clone(), added.uri));
+            .or_insert(WorkspaceState::new(context.env.clone(), added.uri.clone()));
 
         ws.schemas
             .cache()
             .set_cache_path(init_config.cache_path.clone());
 
-        if let Err(error) = ws.initialize(context.clone(), &context.env).await {
-            tracing::error!(?error, "failed to initialize workspace");
-        }
+        updates.push((
+            added.uri,
+            vec![Value::Object(init_config.configuration.clone())],
+        ));
     }
 
     drop(workspaces);
+    apply_configuration(context.clone(), updates).await;
     update_configuration(context).await;
 }
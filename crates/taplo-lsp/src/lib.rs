@@ -26,6 +26,11 @@ pub mod lsp_ext;
 pub mod query;
 pub mod world;
 
+#[doc(hidden)]
+pub use handlers::folding_ranges::create_folding_ranges;
+#[doc(hidden)]
+pub use handlers::semantic_tokens::create_tokens as create_semantic_tokens;
+
 #[must_use]
 pub fn create_server<E: Environment>() -> Server<World<E>> {
     Server::new()
@@ -33,24 +38,37 @@ pub fn create_server<E: Environment>() -> Server<World<E>> {
         .on_request::<request::FoldingRangeRequest, _>(handlers::folding_ranges)
         .on_request::<request::DocumentSymbolRequest, _>(handlers::document_symbols)
         .on_request::<request::Formatting, _>(handlers::format)
+        .on_request::<request::RangeFormatting, _>(handlers::format_range)
         .on_request::<request::Completion, _>(handlers::completion)
         .on_request::<request::HoverRequest, _>(handlers::hover)
         .on_request::<request::DocumentLinkRequest, _>(handlers::links)
         .on_request::<request::SemanticTokensFullRequest, _>(handlers::semantic_tokens)
+        .on_request::<request::SemanticTokensRangeRequest, _>(handlers::semantic_tokens_range)
         .on_request::<request::PrepareRenameRequest, _>(handlers::prepare_rename)
         .on_request::<request::Rename, _>(handlers::rename)
+        .on_request::<request::CodeLensRequest, _>(handlers::code_lens)
+        .on_request::<request::CodeLensResolve, _>(handlers::code_lens_resolve)
+        .on_request::<request::ExecuteCommand, _>(handlers::execute_command)
         .on_notification::<notification::Initialized, _>(handlers::initialized)
         .on_notification::<notification::DidOpenTextDocument, _>(handlers::document_open)
         .on_notification::<notification::DidChangeTextDocument, _>(handlers::document_change)
         .on_notification::<notification::DidSaveTextDocument, _>(handlers::document_save)
         .on_notification::<notification::DidCloseTextDocument, _>(handlers::document_close)
+        .on_notification::<notification::DidChangeWatchedFiles, _>(handlers::watched_files_change)
         .on_notification::<notification::DidChangeConfiguration, _>(handlers::configuration_change)
         .on_notification::<notification::DidChangeWorkspaceFolders, _>(handlers::workspace_change)
         .on_request::<lsp_ext::request::ConvertToJsonRequest, _>(handlers::convert_to_json)
         .on_request::<lsp_ext::request::ConvertToTomlRequest, _>(handlers::convert_to_toml)
         .on_request::<lsp_ext::request::ListSchemasRequest, _>(handlers::list_schemas)
         .on_request::<lsp_ext::request::AssociatedSchemaRequest, _>(handlers::associated_schema)
+        .on_request::<lsp_ext::request::AvailableKeysRequest, _>(handlers::available_keys)
+        .on_request::<lsp_ext::request::GenerateSchemaRequest, _>(handlers::generate_schema)
+        .on_request::<lsp_ext::request::SyntaxTreeRequest, _>(handlers::syntax_tree)
+        .on_request::<lsp_ext::request::DomTreeRequest, _>(handlers::dom_tree)
+        .on_request::<lsp_ext::request::ComputeEditsRequest, _>(handlers::compute_edits)
+        .on_request::<lsp_ext::request::NavigateRequest, _>(handlers::navigate)
         .on_notification::<lsp_ext::notification::AssociateSchema, _>(handlers::associate_schema)
+        .on_notification::<lsp_ext::notification::ReloadSchemas, _>(handlers::reload_schemas)
         .build()
 }
 
@@ -63,3 +63,11 @@ impl Notification for DidChangeSchemaAssociation {
     type Params = DidChangeSchemaAssociationParams;
     const METHOD: &'static str = "taplo/didChangeSchemaAssociation";
 }
+
+/// Load the associated schemas again without using cached ones.
+pub enum ReloadSchemas {}
+
+impl Notification for ReloadSchemas {
+    type Params = ();
+    const METHOD: &'static str = "taplo/reloadSchemas";
+}
@@ -1,4 +1,4 @@
-use lsp_types::{request::Request, Url};
+use lsp_types::{request::Request, Position, Range, Url, WorkspaceEdit};
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
 
@@ -8,8 +8,19 @@ pub enum ConvertToJsonRequest {}
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct ConvertToJsonParams {
-    /// TOML or JSON text.
-    pub text: String,
+    /// TOML or JSON text, required if there is no document URI.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub text: Option<String>,
+    /// An open document that is converted instead of the text.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub document_uri: Option<Url>,
+    /// Output JSON without whitespace instead of pretty-printing it.
+    #[serde(default)]
+    pub compact: bool,
+    /// Keep the TOML types that JSON has no type for
+    /// in `{"$type": ..., "value": ...}` objects.
+    #[serde(default)]
+    pub tagged: bool,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -37,6 +48,9 @@ pub enum ConvertToTomlRequest {}
 pub struct ConvertToTomlParams {
     /// TOML or JSON text.
     pub text: String,
+    /// The document the TOML is for, its formatter options are used if given.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub document_uri: Option<Url>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -102,3 +116,207 @@ impl Request for AssociatedSchemaRequest {
     type Result = AssociatedSchemaResponse;
     const METHOD: &'static str = "taplo/associatedSchema";
 }
+
+/// List the keys that are known by the schema
+/// but are not yet present in the table at the given position.
+pub enum AvailableKeysRequest {}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AvailableKeysParams {
+    pub document_uri: Url,
+    pub position: Position,
+    /// The maximum amount of keys to return.
+    #[serde(default)]
+    pub limit: Option<usize>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AvailableKeysResponse {
+    pub keys: Vec<AvailableKey>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AvailableKey {
+    pub name: String,
+    /// The type(s) of the value, e.g. `string | array`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub type_summary: Option<String>,
+    pub required: bool,
+    pub deprecated: bool,
+    /// The default value as TOML text.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub default: Option<String>,
+    /// The first line of the documentation.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub docs: Option<String>,
+}
+
+impl Request for AvailableKeysRequest {
+    type Params = AvailableKeysParams;
+    type Result = AvailableKeysResponse;
+    const METHOD: &'static str = "taplo/availableKeys";
+}
+
+/// Generate a starter JSON schema from the contents of a document.
+pub enum GenerateSchemaRequest {}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GenerateSchemaParams {
+    pub document_uri: Url,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GenerateSchemaResponse {
+    /// Pretty-printed JSON schema.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub text: Option<String>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+impl Request for GenerateSchemaRequest {
+    type Params = GenerateSchemaParams;
+    type Result = GenerateSchemaResponse;
+    const METHOD: &'static str = "taplo/generateSchema";
+}
+
+/// A bounded debug representation of the syntax tree of a document.
+pub enum SyntaxTreeRequest {}
+
+/// A bounded representation of the DOM of a document,
+/// in the stable format of `Node::tree_string`.
+pub enum DomTreeRequest {}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DebugTreeParams {
+    pub document_uri: Url,
+    /// Nodes deeper than this are omitted.
+    #[serde(default)]
+    pub max_depth: Option<usize>,
+    /// The maximum length of the text in bytes.
+    #[serde(default)]
+    pub max_length: Option<usize>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DebugTreeResponse {
+    pub text: String,
+    /// Whether parts of the tree were left out.
+    pub truncated: bool,
+}
+
+impl Request for SyntaxTreeRequest {
+    type Params = DebugTreeParams;
+    type Result = DebugTreeResponse;
+    const METHOD: &'static str = "taplo/syntaxTree";
+}
+
+impl Request for DomTreeRequest {
+    type Params = DebugTreeParams;
+    type Result = DebugTreeResponse;
+    const METHOD: &'static str = "taplo/domTree";
+}
+
+/// Compute the edits of structured operations on a document
+/// without applying them.
+pub enum ComputeEditsRequest {}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ComputeEditsParams {
+    pub document_uri: Url,
+    /// The operations are applied in order,
+    /// every operation sees the results of the previous ones.
+    pub operations: Vec<EditOperation>,
+}
+
+/// Paths use the `dom::Path` syntax, e.g. `bin[0].name`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "camelCase")]
+pub enum EditOperation {
+    /// Set the value at the path to a TOML value, e.g. `"text"` or `[1, 2]`.
+    #[serde(rename_all = "camelCase")]
+    Set { path: String, value: String },
+    /// Remove everything matching the path, `*` and `**` wildcards are allowed.
+    #[serde(rename_all = "camelCase")]
+    Remove { path: String },
+    /// Append a TOML value to the array at the path.
+    #[serde(rename_all = "camelCase")]
+    AppendArrayItem { path: String, value: String },
+    /// Rename the last key of the path.
+    #[serde(rename_all = "camelCase")]
+    RenameKey { path: String, to: String },
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ComputeEditsResponse {
+    /// The combined edit of the successful operations,
+    /// if there were any.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub edit: Option<WorkspaceEdit>,
+    pub errors: Vec<EditOperationError>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EditOperationError {
+    /// The index of the failed operation.
+    pub index: usize,
+    pub message: String,
+}
+
+impl Request for ComputeEditsRequest {
+    type Params = ComputeEditsParams;
+    type Result = ComputeEditsResponse;
+    const METHOD: &'static str = "taplo/computeEdits";
+}
+
+/// Find the target of a structural motion,
+/// e.g. to jump to the next entry of the same table.
+pub enum NavigateRequest {}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct NavigateParams {
+    pub document_uri: Url,
+    pub position: Position,
+    pub direction: NavigateDirection,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum NavigateDirection {
+    /// The next entry of the same table or the next item of the same array.
+    NextSibling,
+    /// The previous entry of the same table or the previous item of the same array.
+    PrevSibling,
+    /// The header of the containing table, or the containing entry
+    /// for values in inline tables, arrays and dotted keys.
+    Parent,
+    /// The first entry of a table or the first item of an array.
+    FirstChild,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct NavigateResponse {
+    /// The range of the target, there is no target
+    /// at the top level or past the first or last sibling.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub range: Option<Range>,
+}
+
+impl Request for NavigateRequest {
+    type Params = NavigateParams;
+    type Result = NavigateResponse;
+    const METHOD: &'static str = "taplo/navigate";
+}
@@ -6,7 +6,10 @@ use taplo::{
         FromSyntax, KeyOrIndex, Keys, Node,
     },
     rowan::{Direction, TextRange, TextSize},
-    syntax::{SyntaxKind::*, SyntaxNode, SyntaxToken},
+    syntax::{
+        SyntaxKind::{self, *},
+        SyntaxNode, SyntaxToken,
+    },
     util::join_ranges,
 };
 
@@ -59,6 +62,7 @@ impl Query {
         };
 
         Some(PositionInfo {
+            table: containing_table(root, offset),
             syntax,
             dom_node: root
                 .flat_iter()
@@ -71,102 +75,68 @@ impl Query {
 impl Query {
     #[must_use]
     pub fn in_table_header(&self) -> bool {
-        match (&self.before, &self.after) {
-            (Some(before), Some(after)) => {
-                let header_syntax = match before
-                    .syntax
-                    .parent_ancestors()
-                    .find(|s| s.kind() == TABLE_HEADER)
-                {
-                    Some(h) => h,
-                    None => return false,
-                };
-
-                if !after.syntax.parent_ancestors().any(|a| a == header_syntax) {
-                    return false;
-                }
-
-                let bracket_start = match header_syntax.children_with_tokens().find_map(|t| {
-                    if t.kind() == BRACKET_START {
-                        t.into_token()
-                    } else {
-                        None
-                    }
-                }) {
-                    Some(t) => t,
-                    None => return false,
-                };
-
-                let bracket_end = match header_syntax.children_with_tokens().find_map(|t| {
-                    if t.kind() == BRACKET_END {
-                        t.into_token()
-                    } else {
-                        None
-                    }
-                }) {
-                    Some(t) => t,
-                    None => return false,
-                };
-
-                (before.syntax == bracket_start
-                    || before.syntax.text_range().start() >= bracket_start.text_range().end())
-                    && (after.syntax == bracket_end
-                        || after.syntax.text_range().end() <= bracket_end.text_range().start())
-            }
-            _ => false,
-        }
+        self.in_header(TABLE_HEADER)
     }
 
     #[must_use]
     pub fn in_table_array_header(&self) -> bool {
-        match (&self.before, &self.after) {
-            (Some(before), Some(after)) => {
-                let header_syntax = match before
-                    .syntax
-                    .parent_ancestors()
-                    .find(|s| s.kind() == TABLE_ARRAY_HEADER)
-                {
-                    Some(h) => h,
-                    None => return false,
-                };
+        self.in_header(TABLE_ARRAY_HEADER)
+    }
 
-                if !after.syntax.parent_ancestors().any(|a| a == header_syntax) {
-                    return false;
-                }
+    /// Whether the cursor is between the brackets of a header of the given kind.
+    ///
+    /// Headers that are being typed might not be closed yet,
+    /// then the cursor has to be in or right after the key.
+    fn in_header(&self, kind: SyntaxKind) -> bool {
+        let header_syntax = match self
+            .before
+            .as_ref()
+            .and_then(|before| before.syntax.parent_ancestors().find(|s| s.kind() == kind))
+        {
+            Some(h) => h,
+            None => return false,
+        };
 
-                let bracket_start = match header_syntax
-                    .children_with_tokens()
-                    .filter_map(|t| {
-                        if t.kind() == BRACKET_START {
-                            t.into_token()
-                        } else {
-                            None
-                        }
-                    })
-                    .nth(1)
-                {
-                    Some(t) => t,
-                    None => return false,
-                };
+        let bracket_count = if kind == TABLE_ARRAY_HEADER { 2 } else { 1 };
 
-                let bracket_end = match header_syntax.children_with_tokens().find_map(|t| {
-                    if t.kind() == BRACKET_END {
-                        t.into_token()
-                    } else {
-                        None
-                    }
-                }) {
-                    Some(t) => t,
-                    None => return false,
-                };
+        let bracket_start = match header_syntax
+            .children_with_tokens()
+            .filter(|t| t.kind() == BRACKET_START)
+            .nth(bracket_count - 1)
+        {
+            Some(t) => t,
+            None => return false,
+        };
 
-                (before.syntax == bracket_start
-                    || before.syntax.text_range().start() >= bracket_start.text_range().end())
-                    && (after.syntax == bracket_end
-                        || after.syntax.text_range().end() <= bracket_end.text_range().start())
-            }
-            _ => false,
+        if self.offset < bracket_start.text_range().end() {
+            return false;
         }
+
+        let end = header_syntax
+            .children_with_tokens()
+            .find(|t| t.kind() == BRACKET_END)
+            .map(|bracket_end| bracket_end.text_range().start())
+            .or_else(|| {
+                header_syntax
+                    .children()
+                    .find(|n| n.kind() == KEY)
+                    .map(|key| key.text_range().end())
+            })
+            .unwrap_or_else(|| bracket_start.text_range().end());
+
+        self.offset <= end
+    }
+
+    /// Whether the header at the cursor has its closing brackets.
+    #[must_use]
+    pub fn header_closed(&self) -> bool {
+        self.header_key()
+            .and_then(|key| key.parent())
+            .map_or(false, |header| {
+                header
+                    .children_with_tokens()
+                    .any(|t| t.kind() == BRACKET_END)
+            })
     }
 
     #[must_use]
@@ -358,6 +328,19 @@ impl Query {
         })
     }
 
+    /// The string that is being typed at the cursor,
+    /// it has an opening quote but no closing one yet.
+    #[must_use]
+    pub fn unterminated_string(&self) -> Option<SyntaxToken> {
+        let syntax = &self.before.as_ref()?.syntax;
+
+        if syntax.kind() == ERROR && syntax.text().starts_with(['"', '\'']) {
+            Some(syntax.clone())
+        } else {
+            None
+        }
+    }
+
     #[must_use]
     pub fn is_inline(&self) -> bool {
         let syntax = match self.before.as_ref().or(self.after.as_ref()) {
@@ -471,6 +454,27 @@ pub struct PositionInfo {
     pub syntax: SyntaxToken,
     /// The narrowest node that covers the position.
     pub dom_node: Option<(Keys, Node)>,
+    /// The table of the last table header before the position,
+    /// or the root table if there is none.
+    pub table: (Keys, Node),
+}
+
+fn containing_table(root: &Node, offset: TextSize) -> (Keys, Node) {
+    let header_start = |node: &Node| {
+        node.as_table()?
+            .syntax()
+            .filter(|s| matches!(s.kind(), TABLE_HEADER | TABLE_ARRAY_HEADER))
+            .map(|s| s.text_range().start())
+            .filter(|start| *start <= offset)
+    };
+
+    root.flat_iter()
+        .filter_map(|(keys, node)| Some((header_start(&node)?, keys, node)))
+        .max_by_key(|(start, ..)| *start)
+        .map_or_else(
+            || (Keys::empty(), root.clone()),
+            |(_, keys, node)| (keys, node),
+        )
 }
 
 fn full_range(keys: &Keys, node: &Node) -> TextRange {
@@ -488,3 +492,64 @@ fn full_range(keys: &Keys, node: &Node) -> TextRange {
 
     join_ranges(last_key.chain(node.text_ranges()))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn unterminated_string(src: &str) -> Option<String> {
+        let root = taplo::parser::parse(src).into_dom();
+        let query = Query::at(&root, TextSize::of(src));
+        query.unterminated_string().map(|t| t.text().to_string())
+    }
+
+    #[test]
+    fn containing_tables() {
+        let src = "a = 1\n[table]\nb = 2\n[[bin]]\nname = 'a'\n[[bin]]\nname = 'b'\n";
+        let root = taplo::parser::parse(src).into_dom();
+
+        for (at, keys) in [
+            ("a =", ""),
+            ("b =", "table"),
+            ("name = 'a'", "bin.0"),
+            ("name = 'b'", "bin.1"),
+        ] {
+            let offset = TextSize::try_from(src.find(at).unwrap()).unwrap();
+            let query = Query::at(&root, offset);
+            assert_eq!(query.after.unwrap().table.0.to_string(), keys, "{at}");
+        }
+    }
+
+    fn in_headers(src: &str) -> (bool, bool, bool) {
+        let root = taplo::parser::parse(&src.replace('|', "")).into_dom();
+        let query = Query::at(&root, TextSize::try_from(src.find('|').unwrap()).unwrap());
+        (
+            query.in_table_header(),
+            query.in_table_array_header(),
+            query.header_closed(),
+        )
+    }
+
+    #[test]
+    fn unclosed_headers() {
+        assert_eq!(in_headers("a = 1\n[|\n"), (true, false, false));
+        assert_eq!(in_headers("[pro|\nb = 2\n"), (true, false, false));
+        assert_eq!(in_headers("[profile.|"), (true, false, false));
+        assert_eq!(in_headers("[[bi|\nx = 1\n"), (false, true, false));
+        assert_eq!(in_headers("[pro|]\n"), (true, false, true));
+        assert_eq!(in_headers("[[|]]\n"), (false, true, true));
+
+        assert_eq!(in_headers("|[pro]\n").0, false);
+        assert_eq!(in_headers("[pro]|\n").0, false);
+        assert_eq!(in_headers("[\n|b = 2\n").0, false);
+    }
+
+    #[test]
+    fn unterminated_strings() {
+        assert_eq!(unterminated_string(r#"edition = ""#).unwrap(), r#"""#);
+        assert_eq!(unterminated_string("edition = '20").unwrap(), "'20");
+        assert_eq!(unterminated_string(r#"a = ["x", "y"#).unwrap(), r#""y"#);
+        assert_eq!(unterminated_string(r#"edition = "2021""#), None);
+        assert_eq!(unterminated_string("edition = "), None);
+    }
+}
@@ -4,20 +4,32 @@ use crate::{
 };
 use anyhow::anyhow;
 use arc_swap::ArcSwap;
-use lsp_async_stub::{rpc, util::Mapper, Context, RequestWriter};
-use lsp_types::Url;
-use once_cell::sync::Lazy;
+use lsp_async_stub::{
+    rpc,
+    util::{Mapper, PositionEncoding},
+    Context, RequestWriter,
+};
+use lsp_types::{ClientCapabilities, Url};
+use once_cell::sync::{Lazy, OnceCell};
 use regex::Regex;
-use serde_json::json;
-use std::{sync::Arc, time::Duration};
-use taplo::{dom::Node, parser::Parse};
+use serde_json::{json, Value};
+use std::{
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+use taplo::{dom::Node, formatter, parser::Parse};
 use taplo_common::{
     config::Config,
     environment::Environment,
     schema::{
-        associations::{priority, source, AssociationRule, SchemaAssociation},
+        associations::{priority, source, AssociationRule, SchemaAssociation, SchemaAssociations},
         Schemas,
     },
+    util::{normalize_url, Normalize},
     AsyncRwLock, HashMap, IndexMap,
 };
 
@@ -45,7 +57,7 @@ impl<E: Environment> Workspaces<E> {
     pub fn by_document(&self, url: &Url) -> &WorkspaceState<E> {
         self.0
             .iter()
-            .filter(|(key, _)| url.as_str().starts_with(key.as_str()))
+            .filter(|(key, _)| is_in_workspace(key, url))
             .max_by(|(a, _), (b, _)| a.as_str().len().cmp(&b.as_str().len()))
             .map_or_else(
                 || {
@@ -59,9 +71,7 @@ impl<E: Environment> Workspaces<E> {
     pub fn by_document_mut(&mut self, url: &Url) -> &mut WorkspaceState<E> {
         self.0
             .iter_mut()
-            .filter(|(key, _)| {
-                url.as_str().starts_with(key.as_str()) || *key == &*DEFAULT_WORKSPACE_URL
-            })
+            .filter(|(key, _)| is_in_workspace(key, url) || *key == &*DEFAULT_WORKSPACE_URL)
             .max_by(|(a, _), (b, _)| a.as_str().len().cmp(&b.as_str().len()))
             .map(|(k, ws)| {
                 if k == &*DEFAULT_WORKSPACE_URL {
@@ -74,11 +84,38 @@ impl<E: Environment> Workspaces<E> {
     }
 }
 
+/// The path of the document that is matched against
+/// the include and exclude patterns of the Taplo configuration.
+///
+/// Documents that are not files (e.g. unsaved documents)
+/// are represented by their URL.
+pub(crate) fn document_path(env: &impl Environment, url: &Url) -> PathBuf {
+    env.to_file_path_normalized(url)
+        .unwrap_or_else(|| PathBuf::from(url.as_str()).normalize())
+}
+
+/// Whether the document is inside the workspace root,
+/// the URLs are compared after [normalization](normalize_url).
+fn is_in_workspace(root: &Url, document_url: &Url) -> bool {
+    let root = normalize_url(root);
+    let document_url = normalize_url(document_url);
+
+    match document_url.as_str().strip_prefix(root.as_str()) {
+        Some(rest) => root.as_str().ends_with('/') || rest.is_empty() || rest.starts_with('/'),
+        None => false,
+    }
+}
+
 pub struct WorldState<E: Environment> {
     pub(crate) init_config: ArcSwap<InitConfig>,
     pub(crate) env: E,
     pub(crate) workspaces: AsyncRwLock<Workspaces<E>>,
     pub(crate) default_config: ArcSwap<Config>,
+    pub(crate) client_capabilities: ArcSwap<ClientCapabilities>,
+    /// The position encoding negotiated with the client on initialization.
+    pub(crate) position_encoding: ArcSwap<PositionEncoding>,
+    /// The epoch of the latest configuration update, see [`WorkspaceSnapshot`].
+    pub(crate) epoch: AtomicU64,
 }
 
 pub static DEFAULT_WORKSPACE_URL: Lazy<Url> = Lazy::new(|| Url::parse("root:///").unwrap());
@@ -96,10 +133,25 @@ impl<E: Environment> WorldState<E> {
                 AsyncRwLock::new(Workspaces(m))
             },
             default_config: Default::default(),
+            client_capabilities: Default::default(),
+            position_encoding: Default::default(),
+            epoch: AtomicU64::new(0),
             env,
         }
     }
 
+    /// The epoch for a new configuration update,
+    /// updates that are started later have higher epochs.
+    pub(crate) fn next_epoch(&self) -> u64 {
+        self.epoch.fetch_add(1, Ordering::SeqCst) + 1
+    }
+
+    /// A mapper for the text of a document with
+    /// the negotiated position encoding.
+    pub(crate) fn mapper(&self, text: &str) -> Mapper {
+        Mapper::new(text, **self.position_encoding.load(), false)
+    }
+
     /// Set the world state's default config.
     pub fn set_default_config(&self, default_config: Arc<Config>) {
         self.default_config.store(default_config);
@@ -109,9 +161,8 @@ impl<E: Environment> WorldState<E> {
 pub struct WorkspaceState<E: Environment> {
     pub(crate) root: Url,
     pub(crate) documents: HashMap<lsp_types::Url, DocumentState>,
-    pub(crate) taplo_config: Config,
     pub(crate) schemas: Schemas<E>,
-    pub(crate) config: LspConfig,
+    snapshot: Arc<WorkspaceSnapshot>,
 }
 
 impl<E: Environment> WorkspaceState<E> {
@@ -133,9 +184,8 @@ impl<E: Environment> WorkspaceState<E> {
         Self {
             root,
             documents: Default::default(),
-            taplo_config: Default::default(),
             schemas: Schemas::new(env, client),
-            config: LspConfig::default(),
+            snapshot: Default::default(),
         }
     }
 }
@@ -147,86 +197,157 @@ impl<E: Environment> WorkspaceState<E> {
             .ok_or_else(rpc::Error::invalid_params)
     }
 
-    #[tracing::instrument(skip_all, fields(%self.root))]
-    pub(crate) async fn initialize(
-        &mut self,
-        context: Context<World<E>>,
-        env: &impl Environment,
-    ) -> Result<(), anyhow::Error> {
-        if let Err(error) = self
-            .load_config(env, &*context.world().default_config.load())
-            .await
-        {
-            tracing::warn!(%error, "failed to load workspace configuration");
-        }
+    /// The current configuration of the workspace.
+    ///
+    /// Handlers should take it once and use it for their entire duration,
+    /// it is not affected by configuration updates that happen in the meantime.
+    pub(crate) fn snapshot(&self) -> Arc<WorkspaceSnapshot> {
+        self.snapshot.clone()
+    }
 
-        if !self.config.schema.enabled {
-            return Ok(());
+    /// Swap in a snapshot built by [`WorkspaceSnapshot::build`]
+    /// together with its schema associations.
+    ///
+    /// Snapshots from updates that were started before the one
+    /// that is already applied are discarded, in that case `false` is returned.
+    pub(crate) fn apply_snapshot(
+        &mut self,
+        snapshot: WorkspaceSnapshot,
+        associations: &SchemaAssociations<E>,
+    ) -> bool {
+        if snapshot.epoch < self.snapshot.epoch {
+            tracing::debug!(
+                epoch = snapshot.epoch,
+                current_epoch = self.snapshot.epoch,
+                "discarding outdated configuration"
+            );
+            return false;
         }
 
         self.schemas.cache().set_expiration_times(
-            Duration::from_secs(self.config.schema.cache.memory_expiration),
-            Duration::from_secs(self.config.schema.cache.disk_expiration),
+            Duration::from_secs(snapshot.config.schema.cache.memory_expiration),
+            Duration::from_secs(snapshot.config.schema.cache.disk_expiration),
+        );
+        self.schemas
+            .set_fetch_timeout(Duration::from_secs(snapshot.config.schema.fetch_timeout));
+
+        // Associations from a previous configuration might not be valid anymore.
+        self.schemas.associations().replace_from(
+            &[source::CONFIG, source::LSP_CONFIG, source::CATALOG],
+            associations,
         );
 
+        self.snapshot = Arc::new(snapshot);
+        true
+    }
+
+    /// Forget a closed document along with the schemas
+    /// that were associated to it by the document itself.
+    ///
+    /// Returns whether the document was open.
+    pub(crate) fn close_document(&mut self, document_url: &Url) -> bool {
         self.schemas
             .associations()
-            .add_from_config(&self.taplo_config);
+            .remove_from_document(document_url);
+        self.documents.remove(document_url).is_some()
+    }
 
-        for (pattern, schema_url) in &self.config.schema.associations {
-            let pattern = match Regex::new(pattern) {
-                Ok(p) => p,
-                Err(error) => {
-                    tracing::error!(%error, "invalid association pattern");
-                    continue;
+    pub(crate) async fn emit_associations(&self, mut context: Context<World<E>>) {
+        for document_url in self.documents.keys() {
+            if let Some(assoc) = self.schemas.associations().association_for(document_url) {
+                if let Err(error) = context
+                    .write_notification::<DidChangeSchemaAssociation, _>(Some(
+                        DidChangeSchemaAssociationParams {
+                            document_uri: document_url.clone(),
+                            schema_uri: Some(assoc.url.clone()),
+                            meta: Some(assoc.meta.clone()),
+                        },
+                    ))
+                    .await
+                {
+                    tracing::error!(%error, "failed to write notification");
                 }
-            };
+            } else if let Err(error) = context
+                .write_notification::<DidChangeSchemaAssociation, _>(Some(
+                    DidChangeSchemaAssociationParams {
+                        document_uri: document_url.clone(),
+                        schema_uri: None,
+                        meta: None,
+                    },
+                ))
+                .await
+            {
+                tracing::error!(%error, "failed to write notification");
+            }
+        }
+    }
+}
 
-            let url = if schema_url.starts_with("./") {
-                self.root.join(schema_url)
-            } else {
-                schema_url.parse()
-            };
+/// The configuration-dependent state of a workspace.
+///
+/// A snapshot is never modified, configuration updates build a new one
+/// without holding any locks and swap it in with [`WorkspaceState::apply_snapshot`].
+#[derive(Default)]
+pub struct WorkspaceSnapshot {
+    /// The epoch of the update that created the snapshot, see [`WorldState::next_epoch`].
+    pub(crate) epoch: u64,
+    pub(crate) taplo_config: Config,
+    pub(crate) config: LspConfig,
+}
 
-            let url = match url {
-                Ok(u) => u,
-                Err(error) => {
-                    tracing::error!(%error, url = %schema_url, "invalid schema url");
-                    continue;
-                }
-            };
+impl WorkspaceSnapshot {
+    /// Formatter options for the document at the given path,
+    /// the LSP and Taplo configurations are applied on top of `options`.
+    pub(crate) fn format_options(
+        &self,
+        doc_path: &Path,
+        mut options: formatter::Options,
+    ) -> formatter::Options {
+        options.update_camel(self.config.formatter.clone());
+        self.taplo_config
+            .update_format_options(doc_path, &mut options);
+        options
+    }
 
-            self.schemas.associations().add(
-                AssociationRule::Regex(pattern),
-                SchemaAssociation {
-                    url,
-                    meta: json!({
-                        "source": source::LSP_CONFIG,
-                    }),
-                    priority: priority::LSP_CONFIG,
-                },
-            );
+    /// Load the Taplo configuration and collect the schema associations
+    /// for the given LSP configuration.
+    ///
+    /// The associations are collected into a detached copy of `schemas`.
+    #[tracing::instrument(skip_all, fields(%root))]
+    pub(crate) async fn build<E: Environment>(
+        env: &E,
+        root: &Url,
+        schemas: &Schemas<E>,
+        default_config: &Config,
+        config: LspConfig,
+        epoch: u64,
+    ) -> (Self, SchemaAssociations<E>) {
+        let mut snapshot = Self {
+            epoch,
+            taplo_config: default_config.clone(),
+            config,
+        };
+
+        if let Err(error) = snapshot.load_config(env, root).await {
+            tracing::warn!(%error, "failed to load workspace configuration");
         }
 
-        for catalog in &self.config.schema.catalogs {
-            if let Err(error) = self.schemas.associations().add_from_catalog(catalog).await {
-                tracing::error!(%error, "failed to add schemas from catalog");
-            }
+        let associations = schemas.associations().detached();
+
+        if snapshot.config.schema.enabled {
+            snapshot.collect_associations(root, &associations).await;
         }
 
-        self.emit_associations(context).await;
-        Ok(())
+        (snapshot, associations)
     }
 
-    pub(crate) async fn load_config(
+    async fn load_config(
         &mut self,
         env: &impl Environment,
-        default_config: &Config,
+        root: &Url,
     ) -> Result<(), anyhow::Error> {
-        self.taplo_config = default_config.clone();
-
         let root_path = env
-            .to_file_path_normalized(&self.root)
+            .to_file_path_normalized(root)
             .ok_or_else(|| anyhow!("invalid root URL"))?;
 
         if self.config.taplo.config_file.enabled {
@@ -235,13 +356,13 @@ impl<E: Environment> WorkspaceState<E> {
 
                 if env.is_absolute(p) {
                     Some(p.clone())
-                } else if self.root != *DEFAULT_WORKSPACE_URL {
+                } else if *root != *DEFAULT_WORKSPACE_URL {
                     Some(root_path.join(p))
                 } else {
                     tracing::debug!("relative config path is not valid for detached workspace");
                     None
                 }
-            } else if self.root != *DEFAULT_WORKSPACE_URL {
+            } else if *root != *DEFAULT_WORKSPACE_URL {
                 tracing::debug!("discovering config file in workspace");
                 env.find_config_file_normalized(&root_path).await
             } else {
@@ -262,40 +383,284 @@ impl<E: Environment> WorkspaceState<E> {
         Ok(())
     }
 
-    pub(crate) async fn emit_associations(&self, mut context: Context<World<E>>) {
-        for document_url in self.documents.keys() {
-            if let Some(assoc) = self.schemas.associations().association_for(document_url) {
-                if let Err(error) = context
-                    .write_notification::<DidChangeSchemaAssociation, _>(Some(
-                        DidChangeSchemaAssociationParams {
-                            document_uri: document_url.clone(),
-                            schema_uri: Some(assoc.url.clone()),
-                            meta: Some(assoc.meta.clone()),
-                        },
-                    ))
-                    .await
-                {
-                    tracing::error!(%error, "failed to write notification");
+    async fn collect_associations<E: Environment>(
+        &self,
+        root: &Url,
+        associations: &SchemaAssociations<E>,
+    ) {
+        associations.add_from_config(&self.taplo_config);
+
+        for (pattern, schema_url) in &self.config.schema.associations {
+            let pattern = match Regex::new(pattern) {
+                Ok(p) => p,
+                Err(error) => {
+                    tracing::error!(%error, "invalid association pattern");
+                    continue;
                 }
-            } else if let Err(error) = context
-                .write_notification::<DidChangeSchemaAssociation, _>(Some(
-                    DidChangeSchemaAssociationParams {
-                        document_uri: document_url.clone(),
-                        schema_uri: None,
-                        meta: None,
-                    },
-                ))
-                .await
-            {
-                tracing::error!(%error, "failed to write notification");
+            };
+
+            let url = if schema_url.starts_with("./") {
+                root.join(schema_url)
+            } else {
+                schema_url.parse()
+            };
+
+            let url = match url {
+                Ok(u) => u,
+                Err(error) => {
+                    tracing::error!(%error, url = %schema_url, "invalid schema url");
+                    continue;
+                }
+            };
+
+            associations.add(
+                AssociationRule::Regex(pattern),
+                SchemaAssociation {
+                    url,
+                    meta: json!({
+                        "source": source::LSP_CONFIG,
+                    }),
+                    priority: priority::LSP_CONFIG,
+                },
+            );
+        }
+
+        for catalog in &self.config.schema.catalogs {
+            if let Err(error) = associations.add_from_catalog(catalog).await {
+                tracing::error!(%error, "failed to add schemas from catalog");
             }
         }
     }
 }
 
+/// An open document, cloning it is cheap.
 #[derive(Debug, Clone)]
 pub struct DocumentState {
+    /// The text of the document that the changes are applied to.
+    pub(crate) text: Arc<str>,
     pub(crate) parse: Parse,
     pub(crate) dom: Node,
-    pub(crate) mapper: Mapper,
+    pub(crate) mapper: Arc<Mapper>,
+    /// The DOM converted to JSON for schemas,
+    /// it is only converted once and shared by the clones.
+    value: Arc<OnceCell<Value>>,
+}
+
+impl DocumentState {
+    pub(crate) fn new(text: &str, parse: Parse, mapper: Mapper) -> Self {
+        let dom = parse.clone().into_dom();
+
+        Self {
+            text: text.into(),
+            parse,
+            dom,
+            mapper: Arc::new(mapper),
+            value: Default::default(),
+        }
+    }
+
+    /// The DOM as JSON, or `null` if it cannot be converted.
+    pub(crate) fn value(&self) -> &Value {
+        self.value
+            .get_or_init(|| match serde_json::to_value(&self.dom) {
+                Ok(v) => v,
+                Err(error) => {
+                    tracing::warn!(%error, "unable to serialize DOM");
+                    Value::Null
+                }
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use taplo_common::environment::native::NativeEnvironment;
+
+    const SCHEMA_URL: &str = "https://example.com/schema.json";
+
+    fn workspace() -> WorkspaceState<NativeEnvironment> {
+        WorkspaceState::new(NativeEnvironment::new(), DEFAULT_WORKSPACE_URL.clone())
+    }
+
+    fn document_url() -> Url {
+        "file:///doc.toml".parse().unwrap()
+    }
+
+    fn column_width(snapshot: &WorkspaceSnapshot) -> usize {
+        snapshot
+            .format_options(Path::new("/doc.toml"), Default::default())
+            .column_width
+    }
+
+    fn associated_schema(ws: &WorkspaceState<NativeEnvironment>) -> Option<String> {
+        ws.schemas
+            .associations()
+            .association_for(&document_url())
+            .map(|assoc| assoc.url.to_string())
+    }
+
+    async fn build(
+        ws: &WorkspaceState<NativeEnvironment>,
+        config: serde_json::Value,
+        epoch: u64,
+    ) -> (WorkspaceSnapshot, SchemaAssociations<NativeEnvironment>) {
+        let mut lsp_config = LspConfig::default();
+        lsp_config.schema.catalogs = Vec::new();
+        lsp_config.update_from_json(&config).unwrap();
+
+        WorkspaceSnapshot::build(
+            &NativeEnvironment::new(),
+            &ws.root,
+            &ws.schemas,
+            &Config::default(),
+            lsp_config,
+            epoch,
+        )
+        .await
+    }
+
+    #[test]
+    fn workspace_of_document() {
+        let cases = [
+            ("file:///C:/proj", "file:///c%3A/proj/a.toml", true),
+            ("file:///c%3A/proj/", "file:///C:/proj/dir/a.toml", true),
+            ("file:///c:/proj", "file:///c:/project/a.toml", false),
+            ("file://server/share", "file://SERVER/share/a.toml", true),
+            ("file://server/share", "file:///share/a.toml", false),
+            ("file:///home/proj", "file:///home/proj/a.toml", true),
+            ("file:///home/proj", "untitled:Untitled-1", false),
+        ];
+
+        for (root, document_url, expected) in cases {
+            assert_eq!(
+                is_in_workspace(&root.parse().unwrap(), &document_url.parse().unwrap()),
+                expected,
+                "{root} {document_url}"
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn format_during_configuration_update() {
+        let mut ws = workspace();
+
+        // A format request that started before the update.
+        let before = ws.snapshot();
+
+        let (snapshot, associations) = build(
+            &ws,
+            json!({
+                "formatter": { "columnWidth": 50 },
+                "schema": { "associations": { ".*": SCHEMA_URL } },
+            }),
+            1,
+        )
+        .await;
+
+        // A format request while the update is built but not yet applied.
+        let during = ws.snapshot();
+        assert_eq!(column_width(&during), 80);
+        assert_eq!(associated_schema(&ws), None);
+
+        assert!(ws.apply_snapshot(snapshot, &associations));
+
+        // A format request after the update.
+        let after = ws.snapshot();
+        assert_eq!(column_width(&after), 50);
+        assert_eq!(associated_schema(&ws).as_deref(), Some(SCHEMA_URL));
+
+        // Requests that are still running keep the configuration they started with.
+        assert_eq!(column_width(&before), 80);
+        assert_eq!(column_width(&during), 80);
+    }
+
+    #[tokio::test]
+    async fn outdated_configuration_is_discarded() {
+        let mut ws = workspace();
+
+        let (older, older_associations) =
+            build(&ws, json!({ "formatter": { "columnWidth": 50 } }), 1).await;
+        let (newer, newer_associations) =
+            build(&ws, json!({ "formatter": { "columnWidth": 60 } }), 2).await;
+
+        assert!(ws.apply_snapshot(newer, &newer_associations));
+        assert!(!ws.apply_snapshot(older, &older_associations));
+
+        assert_eq!(ws.snapshot().epoch, 2);
+        assert_eq!(column_width(&ws.snapshot()), 60);
+    }
+
+    #[tokio::test]
+    async fn configuration_update_keeps_runtime_associations() {
+        let mut ws = workspace();
+
+        ws.schemas.associations().add(
+            AssociationRule::Url(document_url()),
+            SchemaAssociation {
+                url: SCHEMA_URL.parse().unwrap(),
+                meta: json!({ "source": source::MANUAL }),
+                priority: priority::MAX,
+            },
+        );
+
+        let (snapshot, associations) = build(
+            &ws,
+            json!({ "schema": { "associations": { ".*": "https://example.com/other.json" } } }),
+            1,
+        )
+        .await;
+        assert!(ws.apply_snapshot(snapshot, &associations));
+
+        let (snapshot, associations) = build(&ws, json!({}), 2).await;
+        assert!(ws.apply_snapshot(snapshot, &associations));
+
+        assert_eq!(associated_schema(&ws).as_deref(), Some(SCHEMA_URL));
+        assert_eq!(
+            ws.schemas
+                .associations()
+                .read()
+                .iter()
+                .filter(|(_, assoc)| assoc.meta["source"] == source::LSP_CONFIG)
+                .count(),
+            0
+        );
+    }
+
+    #[test]
+    fn document_value_is_shared() {
+        let src = "a = 1";
+        let doc = DocumentState::new(
+            src,
+            taplo::parser::parse(src),
+            Mapper::new_utf16(src, false),
+        );
+        let clone = doc.clone();
+
+        assert_eq!(clone.value(), &json!({ "a": 1 }));
+        assert!(std::ptr::eq(doc.value(), clone.value()));
+    }
+
+    #[tokio::test]
+    async fn close_document_forgets_document() {
+        let mut ws = workspace();
+
+        let src = format!("#:schema {SCHEMA_URL}\na = 1");
+        let doc = DocumentState::new(
+            &src,
+            taplo::parser::parse(&src),
+            Mapper::new_utf16(&src, false),
+        );
+        ws.schemas
+            .associations()
+            .add_from_document(&document_url(), &doc.dom);
+        ws.documents.insert(document_url(), doc);
+        assert_eq!(associated_schema(&ws).as_deref(), Some(SCHEMA_URL));
+
+        assert!(ws.close_document(&document_url()));
+        assert!(ws.documents.is_empty());
+        assert_eq!(associated_schema(&ws), None);
+
+        assert!(!ws.close_document(&document_url()));
+    }
 }
@@ -6,8 +6,9 @@ use std::{
     path::Path,
     pin::Pin,
     task::{self, Poll},
+    time::Duration,
 };
-use taplo_common::environment::Environment;
+use taplo_common::environment::{Environment, FetchError};
 use time::OffsetDateTime;
 use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
 use url::Url;
@@ -179,6 +180,7 @@ pub(crate) struct WasmEnvironment {
     js_is_absolute: Function,
     js_cwd: Function,
     js_find_config_file: Function,
+    js_fetch_file: Option<Function>,
 }
 
 impl From<JsValue> for WasmEnvironment {
@@ -229,6 +231,10 @@ impl From<JsValue> for WasmEnvironment {
             )
             .unwrap()
             .into(),
+            js_fetch_file: js_sys::Reflect::get(&val, &JsValue::from_str("js_fetch_file"))
+                .ok()
+                .filter(JsValue::is_function)
+                .map(Into::into),
         }
     }
 }
@@ -374,4 +380,51 @@ impl Environment for WasmEnvironment {
 
         res.as_string().map(Into::into)
     }
+
+    async fn fetch_file(
+        &self,
+        url: &Url,
+        timeout: Duration,
+    ) -> Option<Result<Vec<u8>, anyhow::Error>> {
+        let js_fetch_file = match &self.js_fetch_file {
+            Some(f) => f,
+            None => return Some(Err(FetchError::Unsupported.into())),
+        };
+
+        let url_str = JsValue::from_str(url.as_str());
+        let timeout_ms = JsValue::from_f64(timeout.as_millis() as f64);
+        let this = JsValue::null();
+        let res: JsValue = match js_fetch_file.call2(&this, &url_str, &timeout_ms) {
+            Ok(res) => res,
+            Err(err) => return Some(Err(anyhow!("{:?}", err))),
+        };
+
+        // The host might not honor the timeout.
+        let fetch = JsFuture::from(Promise::from(res));
+        let res = match futures::future::select(fetch, sleep(timeout)).await {
+            futures::future::Either::Left((res, _)) => res.map_err(|err| anyhow!("{:?}", err)),
+            futures::future::Either::Right(_) => Err(FetchError::Timeout.into()),
+        };
+
+        Some(res.map(|ret| Uint8Array::from(ret).to_vec()))
+    }
+}
+
+/// A future that resolves after the given duration using the host's `setTimeout`.
+fn sleep(duration: Duration) -> JsFuture {
+    let promise = Promise::new(&mut |resolve, _| {
+        let set_timeout: Function =
+            js_sys::Reflect::get(&js_sys::global(), &JsValue::from_str("setTimeout"))
+                .unwrap()
+                .into();
+        set_timeout
+            .call2(
+                &JsValue::null(),
+                &resolve,
+                &JsValue::from_f64(duration.as_millis() as f64),
+            )
+            .unwrap();
+    });
+
+    JsFuture::from(promise)
 }
@@ -1,6 +1,6 @@
 use criterion::{black_box, criterion_group, criterion_main, Criterion};
 use taplo::{
-    dom::Node,
+    dom::{FromSyntax, Node},
     formatter::{format, format_syntax, Options},
     parser::parse,
 };
@@ -21,6 +21,26 @@ pub fn parsing(c: &mut Criterion) {
     });
 }
 
+/// A document with many tables and arrays of tables.
+fn large_document(tables: usize) -> String {
+    let mut source = String::new();
+    for i in 0..tables {
+        source += &format!(
+            "[section_{i}]\nname = \"s{i}\"\nnested.key = true\n\n\
+             [[section_{i}.items]]\nid = 1\n\n[[section_{i}.items]]\nid = 2\n\n"
+        );
+    }
+    source
+}
+
+pub fn large_dom(c: &mut Criterion) {
+    let source = large_document(1500);
+    let syntax = parse(&source).into_syntax();
+    c.bench_function("dom from large syntax", |b| {
+        b.iter(|| Node::from_syntax(black_box(syntax.clone()).into()))
+    });
+}
+
 pub fn formatting(c: &mut Criterion) {
     let source = include_str!("../../../test-data/example.toml");
 
@@ -46,5 +66,5 @@ pub fn conversion(c: &mut Criterion) {
     });
 }
 
-criterion_group!(benches, parsing, formatting, conversion);
+criterion_group!(benches, parsing, large_dom, formatting, conversion);
 criterion_main!(benches);
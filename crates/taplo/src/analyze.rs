@@ -0,0 +1,382 @@
+//! Analysis of TOML documents that span multiple files.
+
+use crate::{
+    dom::{
+        node::{DomNode, Key, Table, TableInner, TableKind},
+        Keys, Node,
+    },
+    parser, HashMap,
+};
+use rowan::TextRange;
+use thiserror::Error;
+
+/// Options for [`resolve_includes`].
+#[derive(Debug, Clone)]
+pub struct IncludeOptions {
+    /// The key of the array of included paths.
+    ///
+    /// It is only recognized in the root table of each file,
+    /// and it is not part of the merged document.
+    pub key: String,
+
+    /// The path of the document passed to [`resolve_includes`].
+    ///
+    /// Included paths are relative to the file that includes them.
+    pub root_path: String,
+
+    /// The maximum depth of nested includes.
+    pub max_depth: usize,
+}
+
+impl Default for IncludeOptions {
+    fn default() -> Self {
+        Self {
+            key: String::from("include"),
+            root_path: String::new(),
+            max_depth: 32,
+        }
+    }
+}
+
+/// A file that is part of a [`MergedDom`].
+#[derive(Debug, Clone)]
+pub struct IncludedFile {
+    /// The path of the file, as it was passed to the loader.
+    ///
+    /// Except for the root document, paths are normalized
+    /// by removing `.` and `..` segments where possible.
+    pub path: String,
+
+    /// The DOM of the file, the text ranges of merged nodes
+    /// from this file point into its source.
+    pub dom: Node,
+}
+
+/// The result of [`resolve_includes`].
+#[derive(Debug, Clone)]
+pub struct MergedDom {
+    root: Node,
+    files: Vec<IncludedFile>,
+    sources: HashMap<Keys, usize>,
+}
+
+impl MergedDom {
+    /// The root table of the merged document.
+    pub fn root(&self) -> &Node {
+        &self.root
+    }
+
+    /// All the files of the merged document,
+    /// the first one is always the root document.
+    pub fn files(&self) -> &[IncludedFile] {
+        &self.files
+    }
+
+    /// The file that defines the node at the given path.
+    ///
+    /// Tables that are defined in multiple files belong to the
+    /// file with the highest precedence.
+    pub fn source_of(&self, keys: &Keys) -> Option<&IncludedFile> {
+        (0..=keys.len())
+            .find_map(|n| self.sources.get(&keys.skip_right(n)))
+            .map(|&idx| &self.files[idx])
+    }
+}
+
+/// An error that prevented [`resolve_includes`] from merging the files.
+///
+/// `E` is the error type of the loader.
+#[derive(Debug, Error)]
+pub enum IncludeError<E> {
+    #[error("\"{key}\" must be an array of strings in {path}")]
+    InvalidInclude {
+        path: String,
+        key: String,
+        range: Option<TextRange>,
+    },
+    #[error("failed to load {path}: {error}")]
+    Load { path: String, error: E },
+    #[error("{path} is not a valid TOML document")]
+    Syntax {
+        path: String,
+        errors: Vec<parser::Error>,
+    },
+    #[error("include cycle: {}", .cycle.join(" -> "))]
+    Cycle { cycle: Vec<String> },
+    #[error("includes are nested too deep at {path}")]
+    TooDeep { path: String },
+}
+
+/// Merge all the files included by the given document into one.
+///
+/// Included files are loaded with `loader`, the paths are resolved
+/// relative to the including file (see [`IncludeOptions::root_path`]).
+/// Each file is loaded only once, even if it is included multiple times.
+///
+/// Tables are merged recursively, other values (including arrays and inline tables)
+/// are replaced. A file takes precedence over the files it includes,
+/// and later includes take precedence over earlier ones.
+///
+/// ```
+/// use taplo::{analyze::{resolve_includes, IncludeOptions}, dom::Keys};
+///
+/// let dom = taplo::parser::parse(
+///     r#"
+/// include = ["base.toml"]
+///
+/// [server]
+/// port = 8080
+/// "#,
+/// )
+/// .into_dom();
+///
+/// let loader = |path: &str| match path {
+///     "base.toml" => Ok(String::from("[server]\nhost = 'localhost'\nport = 80")),
+///     _ => Err("not found"),
+/// };
+///
+/// let merged = resolve_includes(dom, &loader, &IncludeOptions::default()).unwrap();
+///
+/// let entries = merged.root().as_table().unwrap().entries().read();
+/// assert!(entries.get_path(&["server", "host"]).is_some());
+///
+/// let port: Keys = "server.port".parse().unwrap();
+/// assert_eq!(merged.source_of(&port).unwrap().path, "");
+///
+/// let host: Keys = "server.host".parse().unwrap();
+/// assert_eq!(merged.source_of(&host).unwrap().path, "base.toml");
+/// ```
+pub fn resolve_includes<E>(
+    dom: Node,
+    loader: &dyn Fn(&str) -> Result<String, E>,
+    options: &IncludeOptions,
+) -> Result<MergedDom, IncludeError<E>> {
+    let root = TableInner {
+        errors: Default::default(),
+        syntax: dom.syntax().cloned(),
+        header: false,
+        kind: TableKind::Regular,
+        entries: Default::default(),
+        indexed: Default::default(),
+    }
+    .wrap();
+
+    let mut resolver = Resolver {
+        loader,
+        options,
+        files: vec![IncludedFile {
+            path: options.root_path.clone(),
+            dom,
+        }],
+        loaded: HashMap::default(),
+        sources: HashMap::default(),
+        root,
+    };
+
+    resolver
+        .loaded
+        .insert(normalize_path(&options.root_path), 0);
+    resolver.resolve(0, &mut Vec::new())?;
+
+    Ok(MergedDom {
+        root: resolver.root.into(),
+        files: resolver.files,
+        sources: resolver.sources,
+    })
+}
+
+struct Resolver<'l, E> {
+    loader: &'l dyn Fn(&str) -> Result<String, E>,
+    options: &'l IncludeOptions,
+    files: Vec<IncludedFile>,
+    loaded: HashMap<String, usize>,
+    sources: HashMap<Keys, usize>,
+    root: Table,
+}
+
+impl<'l, E> Resolver<'l, E> {
+    fn resolve(&mut self, file: usize, stack: &mut Vec<String>) -> Result<(), IncludeError<E>> {
+        let path = self.files[file].path.clone();
+        let dom = self.files[file].dom.clone();
+
+        stack.push(normalize_path(&path));
+
+        for include in self.includes(&path, &dom)? {
+            let include = normalize_path(&join_path(&path, &include));
+
+            if let Some(pos) = stack.iter().position(|p| *p == include) {
+                let mut cycle = stack[pos..].to_vec();
+                cycle.push(include);
+                return Err(IncludeError::Cycle { cycle });
+            }
+
+            if stack.len() > self.options.max_depth {
+                return Err(IncludeError::TooDeep { path: include });
+            }
+
+            let included = match self.loaded.get(&include) {
+                Some(&idx) => idx,
+                None => self.load(include)?,
+            };
+
+            self.resolve(included, stack)?;
+        }
+
+        stack.pop();
+
+        if let Node::Table(table) = &dom {
+            let root = self.root.clone();
+            self.merge(&root, table, &Keys::empty(), file);
+        }
+
+        Ok(())
+    }
+
+    fn includes(&self, path: &str, dom: &Node) -> Result<Vec<String>, IncludeError<E>> {
+        let node = match dom
+            .as_table()
+            .and_then(|t| t.get(self.options.key.as_str()))
+        {
+            Some(node) => node,
+            None => return Ok(Vec::new()),
+        };
+
+        let invalid = |node: &Node| IncludeError::InvalidInclude {
+            path: path.to_string(),
+            key: self.options.key.clone(),
+            range: node.text_ranges().next(),
+        };
+
+        let array = match &node {
+            Node::Array(array) if !array.kind().is_tables() => array,
+            _ => return Err(invalid(&node)),
+        };
+
+        let items = array.items().read();
+        items
+            .iter()
+            .map(|item| match item {
+                Node::Str(s) => Ok(s.value().to_string()),
+                _ => Err(invalid(item)),
+            })
+            .collect()
+    }
+
+    fn load(&mut self, path: String) -> Result<usize, IncludeError<E>> {
+        let source = (self.loader)(&path).map_err(|error| IncludeError::Load {
+            path: path.clone(),
+            error,
+        })?;
+
+        let parse = parser::parse(&source);
+        if !parse.errors.is_empty() {
+            return Err(IncludeError::Syntax {
+                path,
+                errors: parse.errors,
+            });
+        }
+
+        let idx = self.files.len();
+        self.loaded.insert(path.clone(), idx);
+        self.files.push(IncludedFile {
+            path,
+            dom: parse.into_dom(),
+        });
+
+        Ok(idx)
+    }
+
+    fn merge(&mut self, target: &Table, source: &Table, keys: &Keys, file: usize) {
+        let entries = source.entries().read();
+
+        for (key, node) in entries.iter() {
+            if keys.is_empty() && key.value() == self.options.key {
+                continue;
+            }
+
+            let entry_keys = keys.join(key.clone());
+            let existing = target.get(key.clone());
+
+            let table = match node {
+                Node::Table(table) if is_mergeable(table) => table,
+                _ => {
+                    self.replace(target, key, node.clone(), &entry_keys, existing.is_some());
+                    self.sources.insert(entry_keys, file);
+                    continue;
+                }
+            };
+
+            let merged = match existing {
+                Some(Node::Table(existing)) if is_mergeable(&existing) => existing,
+                existing => {
+                    let merged = TableInner {
+                        errors: Default::default(),
+                        syntax: table.syntax().cloned(),
+                        header: table.inner.header,
+                        kind: table.kind(),
+                        entries: Default::default(),
+                        indexed: Default::default(),
+                    }
+                    .wrap();
+
+                    let node = merged.clone().into();
+                    self.replace(target, key, node, &entry_keys, existing.is_some());
+                    merged
+                }
+            };
+
+            self.sources.insert(entry_keys.clone(), file);
+            self.merge(&merged, table, &entry_keys, file);
+        }
+    }
+
+    fn replace(&mut self, target: &Table, key: &Key, node: Node, keys: &Keys, existed: bool) {
+        if existed {
+            // Nothing below the replaced entry is defined by its file anymore.
+            self.sources.retain(|k, _| !k.contains(keys));
+        }
+
+        target
+            .entries()
+            .update(|entries| entries.set(key.clone(), node));
+    }
+}
+
+fn is_mergeable(table: &Table) -> bool {
+    table.kind() != TableKind::Inline
+}
+
+/// Resolve `include` relative to the directory of `path`.
+fn join_path(path: &str, include: &str) -> String {
+    let is_absolute = include.starts_with('/')
+        || include.starts_with('\\')
+        || include.contains("://")
+        || include.as_bytes().get(1) == Some(&b':');
+
+    if is_absolute {
+        return include.to_string();
+    }
+
+    match path.rfind(['/', '\\']) {
+        Some(idx) => format!("{}/{}", &path[..idx], include),
+        None => include.to_string(),
+    }
+}
+
+/// Remove `.` and `..` segments so that the same file
+/// is recognized regardless of how it was included.
+fn normalize_path(path: &str) -> String {
+    let mut segments: Vec<&str> = Vec::new();
+
+    for segment in path.split(['/', '\\']) {
+        match segment {
+            "." => {}
+            ".." if matches!(segments.last(), Some(s) if !s.is_empty() && *s != "..") => {
+                segments.pop();
+            }
+            _ => segments.push(segment),
+        }
+    }
+
+    segments.join("/")
+}
@@ -1,13 +1,19 @@
 use super::node::Key;
-use crate::syntax::SyntaxElement;
+use crate::{syntax::SyntaxElement, util::UnescapeError};
+use rowan::TextRange;
 use thiserror::Error;
 
 #[derive(Debug, Clone, Error)]
 pub enum Error {
     #[error("the syntax was not expected here: {syntax:#?}")]
     UnexpectedSyntax { syntax: SyntaxElement },
-    #[error("the string contains invalid escape sequence(s)")]
-    InvalidEscapeSequence { string: SyntaxElement },
+    #[error("{error}")]
+    InvalidEscapeSequence {
+        string: SyntaxElement,
+        /// The range of the first invalid escape sequence.
+        range: TextRange,
+        error: UnescapeError,
+    },
     #[error("conflicting keys")]
     ConflictingKeys { key: Key, other: Key },
     #[error("expected table")]
@@ -21,6 +27,30 @@ pub enum Error {
     Query(#[from] QueryError),
 }
 
+/// An error of converting a node to JSON or to a TOML document,
+/// see [`Node::to_json`](super::Node::to_json) and
+/// [`Node::to_toml_document`](super::Node::to_toml_document).
+#[derive(Debug, Clone, Error)]
+#[error("{reason}")]
+pub struct ConversionError {
+    /// The range of the value the error is about,
+    /// this is not known for nodes that were not parsed.
+    pub range: Option<TextRange>,
+    pub reason: ConversionErrorReason,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Error)]
+pub enum ConversionErrorReason {
+    #[error("the value is invalid")]
+    InvalidNode,
+    #[error("the string contains an invalid escape sequence")]
+    InvalidEscapeSequence,
+    #[error("infinite and NaN floats cannot be represented in JSON")]
+    NonFiniteFloat,
+    #[error("only tables can be TOML documents")]
+    ExpectedTable,
+}
+
 #[derive(Debug, Clone, Error)]
 pub enum QueryError {
     #[error("the key or index was not found")]
@@ -29,4 +59,6 @@ pub enum QueryError {
     InvalidGlob(#[from] globset::Error),
     #[error("the given key is invalid: {0}")]
     InvalidKey(crate::parser::Error),
+    #[error("the given path is invalid at offset {offset}")]
+    InvalidPath { offset: usize },
 }
@@ -22,7 +22,10 @@ impl FromSyntax for Node {
     fn from_syntax(syntax: SyntaxElement) -> Self {
         match syntax.kind() {
             VALUE => {
-                if let Some(child) = syntax.as_node().and_then(|n| n.first_child_or_token()) {
+                if let Some(child) = syntax.as_node().and_then(|n| {
+                    n.children_with_tokens()
+                        .find(|c| !matches!(c.kind(), WHITESPACE | NEWLINE | COMMENT))
+                }) {
                     Node::from_syntax(child)
                 } else {
                     Invalid::from_syntax(syntax).into()
@@ -57,6 +60,7 @@ impl FromSyntax for Table {
                 header: true,
                 kind: TableKind::Regular,
                 entries: Default::default(),
+                indexed: Default::default(),
             }
             .wrap(),
             INLINE_TABLE => {
@@ -66,6 +70,7 @@ impl FromSyntax for Table {
                     syntax: Some(syntax.clone()),
                     kind: TableKind::Inline,
                     entries: Default::default(),
+                    indexed: Default::default(),
                 }
                 .wrap();
 
@@ -91,6 +96,7 @@ impl FromSyntax for Table {
                     syntax: Some(syntax),
                     kind: TableKind::Regular,
                     entries: Default::default(),
+                    indexed: Default::default(),
                 }
                 .into()
             }
@@ -106,6 +112,7 @@ impl Table {
             header,
             kind: TableKind::Pseudo,
             entries: Default::default(),
+            indexed: Default::default(),
         }
         .wrap()
     }
@@ -409,52 +416,29 @@ fn entry_from_syntax(syntax: SyntaxElement) -> (Key, Node) {
             .and_then(|n| n.first_child())
             .map(Into::into)
             .unwrap_or_else(|| syntax.clone()),
-    );
-    let first_key = keys
-        .next()
-        .unwrap_or_else(|| Key::from_syntax_invalid(syntax.clone()));
+    )
+    .collect::<Vec<_>>();
 
     let mut value = syntax
         .as_node()
         .and_then(|n| n.first_child())
         .and_then(|k| k.next_sibling())
         .map(|n| Node::from_syntax(n.into()))
-        .unwrap_or_else(|| Invalid::from_syntax(syntax).into());
-
-    let mut top_pseudo_table: Option<Table> = None;
-    let mut last_pseudo_table: Option<Table> = None;
-    while let Some(pseudo_key) = keys.next() {
-        let new_pseudo_table = Table::pseudo(&pseudo_key, false);
-
-        match (top_pseudo_table.take(), last_pseudo_table.take()) {
-            (None, None) => {
-                let top_pt = Table::pseudo(&first_key, false);
-                top_pseudo_table = Some(top_pt.clone());
+        .unwrap_or_else(|| Invalid::from_syntax(syntax.clone()).into());
 
-                if keys.len() == 0 {
-                    top_pt.add_entry(pseudo_key, value);
-                    value = top_pt.into();
-                } else {
-                    top_pt.add_entry(pseudo_key, new_pseudo_table.clone().into());
-                    last_pseudo_table = Some(new_pseudo_table.clone());
-                }
-            }
-            (Some(top_pt), Some(last_pt)) => {
-                if keys.len() == 0 {
-                    last_pt.add_entry(pseudo_key, value);
-                    value = top_pt.into();
-                    break;
-                } else {
-                    top_pseudo_table = Some(top_pt);
-                    last_pt.add_entry(pseudo_key, new_pseudo_table.clone().into());
-                    last_pseudo_table = Some(new_pseudo_table);
-                }
-            }
-            _ => unreachable!(),
-        }
+    // Build the pseudo-tables of dotted keys bottom-up,
+    // each one contains the entry of the following key.
+    let mut key = keys
+        .pop()
+        .unwrap_or_else(|| Key::from_syntax_invalid(syntax.clone()));
+    for parent_key in keys.into_iter().rev() {
+        let pseudo_table = Table::pseudo(&parent_key, false);
+        pseudo_table.add_entry(key, value);
+        value = pseudo_table.into();
+        key = parent_key;
     }
 
-    (first_key, value)
+    (key, value)
 }
 
 fn root_from_syntax(syntax: SyntaxElement) -> Table {
@@ -470,6 +454,7 @@ fn root_from_syntax(syntax: SyntaxElement) -> Table {
                 header: false,
                 kind: TableKind::Regular,
                 entries: Default::default(),
+                indexed: Default::default(),
             }
             .into()
         }
@@ -481,6 +466,7 @@ fn root_from_syntax(syntax: SyntaxElement) -> Table {
         header: false,
         kind: TableKind::Regular,
         entries: Default::default(),
+        indexed: Default::default(),
     }
     .wrap();
 
@@ -502,8 +488,14 @@ fn root_from_syntax(syntax: SyntaxElement) -> Table {
                         while let Some(key) = keys.next() {
                             if keys.len() == 0 {
                                 let new_table = Table::from_syntax(child.into());
-                                let current_entries = current_table.inner.entries.read();
-                                match current_entries.lookup.get_key_value(&key) {
+                                let existing = current_table
+                                    .inner
+                                    .entries
+                                    .read()
+                                    .lookup
+                                    .get_key_value(&key)
+                                    .map(|(k, n)| (k.clone(), n.clone()));
+                                match existing {
                                     Some((k, Node::Table(t))) => {
                                         if let Some(syntax) = key.syntax() {
                                             k.inner
@@ -519,7 +511,7 @@ fn root_from_syntax(syntax: SyntaxElement) -> Table {
                                                 })
                                             });
                                         }
-                                        current_table = t.clone();
+                                        current_table = t;
                                     }
                                     Some((k, _)) => {
                                         current_table.inner.errors.update(|errors| {
@@ -610,9 +602,42 @@ fn root_from_syntax(syntax: SyntaxElement) -> Table {
         }
     }
 
+    #[cfg(debug_assertions)]
+    assert_array_of_tables_order(&root_table);
+
     root_table
 }
 
+/// Items of arrays of tables must be in the order
+/// of their headers in the source.
+#[cfg(debug_assertions)]
+fn assert_array_of_tables_order(table: &Table) {
+    for (key, node) in table.entries().read().iter() {
+        match node {
+            Node::Table(t) if t.kind() != TableKind::Inline => assert_array_of_tables_order(t),
+            Node::Array(arr) if arr.kind() == ArrayKind::Tables => {
+                let items = arr.items().read();
+                let offsets = items
+                    .iter()
+                    .filter_map(|item| item.syntax().map(|s| s.text_range().start()))
+                    .collect::<Vec<_>>();
+
+                debug_assert!(
+                    offsets.is_sorted(),
+                    "items of the array of tables {key} are out of order"
+                );
+
+                for item in items.iter() {
+                    if let Node::Table(t) = item {
+                        assert_array_of_tables_order(t);
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
 /// Merge or create an intermediate dotted key in a top-level table or array.
 /// Returns a pseudo-table.
 #[must_use]
@@ -678,7 +703,7 @@ fn merge_intermediate(key: Key, table: Table) -> Table {
                             required_by: key.clone(),
                         })
                     });
-                    entries.add(key, new_table.clone().into());
+                    entries.add_conflicting(key, new_table.clone().into());
                     current_table = new_table.clone();
                 }
             }
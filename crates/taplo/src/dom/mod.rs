@@ -1,5 +1,10 @@
 use self::{error::QueryError, from_syntax::keys_from_syntax, node::Key};
-use crate::{parser::Parser, syntax::SyntaxElement, util::join_ranges, HashMap};
+use crate::{
+    parser::Parser,
+    syntax::SyntaxElement,
+    util::{self, iter::ExactIterExt, join_ranges},
+    HashMap,
+};
 use core::iter::once;
 use std::{
     iter::{empty, FromIterator},
@@ -9,23 +14,31 @@ use std::{
 
 #[cfg(feature = "serde")]
 mod serde;
+#[cfg(feature = "serde")]
+pub use self::serde::json_to_toml;
 
 pub(crate) mod from_syntax;
 
 pub mod error;
 pub mod index;
 pub mod node;
+pub mod parents;
+pub mod path;
 pub mod rewrite;
+mod to_json;
 mod to_toml;
+pub mod visit;
 
 pub use error::Error;
 pub use from_syntax::FromSyntax;
 use itertools::Itertools;
 pub use node::Node;
 use once_cell::unsync::OnceCell;
-use rowan::TextRange;
+pub use path::{Path, PathPattern, Segment};
+use rowan::{TextRange, TextSize};
+pub use to_json::JsonOptions;
 
-#[derive(Debug, Clone, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub enum KeyOrIndex {
     Key(Key),
     Index(usize),
@@ -96,10 +109,20 @@ impl KeyOrIndex {
     }
 }
 
+/// The maximum number of segments [`Keys`] are stored in,
+/// before they are copied into a single segment.
+const MAX_KEY_SEGMENTS: usize = 4;
+
 #[derive(Debug, Clone)]
 pub struct Keys {
-    dotted: Arc<str>,
-    keys: Arc<[KeyOrIndex]>,
+    /// The keys are stored in segments that are shared with the keys
+    /// they were joined to or skipped from, so that deriving keys
+    /// does not copy all of them.
+    segments: Arc<[Arc<[KeyOrIndex]>]>,
+    /// The range of these keys in the concatenated segments.
+    start: usize,
+    end: usize,
+    dotted: OnceCell<Arc<str>>,
 }
 
 impl Keys {
@@ -113,9 +136,16 @@ impl Keys {
     }
 
     pub fn new(keys: impl Iterator<Item = KeyOrIndex>) -> Self {
-        let keys: Arc<[KeyOrIndex]> = keys.collect();
-        let dotted: Arc<str> = Arc::from(keys.iter().join(".").as_str());
-        Self { keys, dotted }
+        Self::from_segment(keys.collect())
+    }
+
+    fn from_segment(keys: Arc<[KeyOrIndex]>) -> Self {
+        Self {
+            start: 0,
+            end: keys.len(),
+            segments: Arc::from([keys]),
+            dotted: OnceCell::new(),
+        }
     }
 
     pub fn join(&self, key: impl Into<KeyOrIndex>) -> Self {
@@ -127,28 +157,58 @@ impl Keys {
         I: IntoIterator<Item = K>,
         K: Into<KeyOrIndex>,
     {
-        Self::new(
-            self.keys
-                .iter()
-                .cloned()
-                .chain(keys.into_iter().map(Into::into)),
-        )
+        let keys: Arc<[KeyOrIndex]> = keys.into_iter().map(Into::into).collect();
+
+        if keys.is_empty() {
+            return self.clone();
+        }
+
+        if self.is_empty() {
+            return Self::from_segment(keys);
+        }
+
+        let stored_len: usize = self.segments.iter().map(|s| s.len()).sum();
+
+        // Keys that were skipped from the right can not share
+        // the segments, as the new keys must follow them directly.
+        if self.end != stored_len || self.segments.len() == MAX_KEY_SEGMENTS {
+            return Self::new(self.iter().chain(keys.iter()).cloned());
+        }
+
+        Self {
+            start: self.start,
+            end: self.end + keys.len(),
+            segments: self.segments.iter().cloned().chain(once(keys)).collect(),
+            dotted: OnceCell::new(),
+        }
     }
 
     pub fn iter(&self) -> impl ExactSizeIterator<Item = &KeyOrIndex> + DoubleEndedIterator {
-        self.keys.iter()
+        let mut slices: [&[KeyOrIndex]; MAX_KEY_SEGMENTS] = Default::default();
+
+        let mut offset = 0;
+        for (slice, segment) in slices.iter_mut().zip(self.segments.iter()) {
+            let range = offset..offset + segment.len();
+            let start = self.start.clamp(range.start, range.end) - offset;
+            let end = self.end.clamp(range.start, range.end) - offset;
+            *slice = &segment[start..end];
+            offset = range.end;
+        }
+
+        slices.into_iter().flatten().exactly(self.len())
     }
 
     pub fn dotted(&self) -> &str {
-        &*self.dotted
+        self.dotted
+            .get_or_init(|| Arc::from(self.iter().join(".").as_str()))
     }
 
     pub fn len(&self) -> usize {
-        self.keys.len()
+        self.end - self.start
     }
 
     pub fn is_empty(&self) -> bool {
-        self.keys.len() == 0
+        self.len() == 0
     }
 
     pub fn common_prefix_count(&self, other: &Self) -> usize {
@@ -167,17 +227,26 @@ impl Keys {
     }
 
     pub fn skip_left(&self, n: usize) -> Self {
-        Self::new(self.keys.iter().skip(n).cloned())
+        Self {
+            segments: self.segments.clone(),
+            start: self.start + n.min(self.len()),
+            end: self.end,
+            dotted: OnceCell::new(),
+        }
     }
 
     pub fn skip_right(&self, n: usize) -> Self {
-        Self::new(self.keys.iter().rev().skip(n).cloned().rev())
+        Self {
+            segments: self.segments.clone(),
+            start: self.start,
+            end: self.end - n.min(self.len()),
+            dotted: OnceCell::new(),
+        }
     }
 
     pub fn all_text_range(&self) -> TextRange {
         join_ranges(
-            self.keys
-                .iter()
+            self.iter()
                 .filter_map(KeyOrIndex::as_key)
                 .flat_map(|k| k.text_ranges()),
         )
@@ -190,7 +259,7 @@ impl IntoIterator for Keys {
     type IntoIter = std::vec::IntoIter<KeyOrIndex>;
 
     fn into_iter(self) -> Self::IntoIter {
-        Vec::from(&*self.keys).into_iter()
+        self.iter().cloned().collect::<Vec<_>>().into_iter()
     }
 }
 
@@ -214,17 +283,23 @@ impl FromStr for Keys {
     }
 }
 
+/// Keys are compared by their unescaped values,
+/// so `"a\u0062c"` and `abc` are the same key.
 impl PartialEq for Keys {
     fn eq(&self, other: &Self) -> bool {
-        self.dotted == other.dotted
+        self.len() == other.len() && self.iter().eq(other.iter())
     }
 }
 
 impl Eq for Keys {}
 
+/// The same as the hash of a slice of the keys.
 impl std::hash::Hash for Keys {
     fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
-        self.dotted.hash(state);
+        self.len().hash(state);
+        for key in self.iter() {
+            key.hash(state);
+        }
     }
 }
 
@@ -262,26 +337,158 @@ impl Entries {
         self.all.iter()
     }
 
+    /// All the entries in document order.
+    pub fn as_slice(&self) -> &[(Key, Node)] {
+        &self.all
+    }
+
+    /// The first entry in document order.
+    pub fn first(&self) -> Option<&(Key, Node)> {
+        self.all.first()
+    }
+
+    /// The last entry in document order.
+    pub fn last(&self) -> Option<&(Key, Node)> {
+        self.all.last()
+    }
+
+    /// Returns the node of the entry with the given key.
+    ///
+    /// The key is compared against the unescaped key values,
+    /// so `"a b"` matches both `'a b'` and `"a\u0020b"`
+    /// in the document.
+    pub fn get(&self, key: &str) -> Option<&Node> {
+        self.lookup.get(&Key::new(key))
+    }
+
+    /// Returns the node at the given path relative to these entries.
+    ///
+    /// Each path segment is either a key for tables
+    /// or an index for arrays (including arrays of tables).
+    ///
+    /// ```
+    /// let dom = taplo::parser::parse(
+    ///     r#"
+    /// [package]
+    /// name = "taplo"
+    ///
+    /// [[bin]]
+    /// path = "src/main.rs"
+    /// "#,
+    /// )
+    /// .into_dom();
+    ///
+    /// let entries = dom.as_table().unwrap().entries().read();
+    ///
+    /// assert!(entries.get_path(&["package", "name"]).is_some());
+    /// assert!(entries.get_path(&["bin", "0", "path"]).is_some());
+    /// ```
+    pub fn get_path(&self, keys: &[&str]) -> Option<Node> {
+        let (first, rest) = keys.split_first()?;
+        let mut node = self.get(first)?.clone();
+
+        for key in rest {
+            node = match &node {
+                Node::Array(_) => node.try_get(key.parse::<usize>().ok()?).ok()?,
+                _ => node.try_get(*key).ok()?,
+            };
+        }
+
+        Some(node)
+    }
+
+    /// The range covering the keys and values of all the entries,
+    /// or [`None`] if there are no entries.
+    pub fn text_range(&self) -> Option<TextRange> {
+        self.all
+            .iter()
+            .flat_map(|(key, node)| key.text_ranges().chain(node.text_ranges()))
+            .reduce(|a, b| a.cover(b))
+    }
+
+    /// Add a new entry, the key must not be in the entries already.
     pub(crate) fn add(&mut self, key: Key, node: Node) {
+        debug_assert!(
+            !self.lookup.contains_key(&key),
+            "entry {key} would silently replace an existing entry"
+        );
+        self.add_conflicting(key, node);
+    }
+
+    /// Add an entry even if the key already exists.
+    ///
+    /// The lookup will return the new node, but the existing entry
+    /// is kept in the list, the caller is responsible for reporting
+    /// the conflict as an error.
+    pub(crate) fn add_conflicting(&mut self, key: Key, node: Node) {
         self.lookup.insert(key.clone(), node.clone());
         self.all.push((key, node));
     }
+
+    /// Replace the node of an existing entry in place,
+    /// or add a new entry if the key does not exist yet.
+    pub(crate) fn set(&mut self, key: Key, node: Node) {
+        match self.all.iter_mut().rev().find(|(k, _)| *k == key) {
+            Some(entry) => *entry = (key.clone(), node.clone()),
+            None => self.all.push((key.clone(), node.clone())),
+        }
+        self.lookup.insert(key, node);
+    }
 }
 
-impl FromIterator<(Key, Node)> for Entries {
-    fn from_iter<T: IntoIterator<Item = (Key, Node)>>(iter: T) -> Self {
+impl core::ops::Index<&str> for Entries {
+    type Output = Node;
+
+    /// # Panics
+    ///
+    /// Panics if there is no entry with the given key.
+    fn index(&self, key: &str) -> &Self::Output {
+        self.get(key)
+            .unwrap_or_else(|| panic!("no entry found for key {}", util::quote_key(key)))
+    }
+}
+
+impl<'e> IntoIterator for &'e Entries {
+    type Item = &'e (Key, Node);
+    type IntoIter = core::slice::Iter<'e, (Key, Node)>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.all.iter()
+    }
+}
+
+impl IntoIterator for Entries {
+    type Item = (Key, Node);
+    type IntoIter = std::vec::IntoIter<(Key, Node)>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.all.into_iter()
+    }
+}
+
+/// Duplicate keys are kept as separate entries,
+/// lookups return the last node with the key.
+impl Extend<(Key, Node)> for Entries {
+    fn extend<T: IntoIterator<Item = (Key, Node)>>(&mut self, iter: T) {
         let iter = iter.into_iter();
         let size = iter.size_hint().0;
 
-        let mut lookup = HashMap::with_capacity(size);
-        let mut all = Vec::with_capacity(size);
+        self.lookup.reserve(size);
+        self.all.reserve(size);
 
         for (k, n) in iter {
-            lookup.insert(k.clone(), n.clone());
-            all.push((k, n));
+            self.add_conflicting(k, n);
         }
+    }
+}
 
-        Self { lookup, all }
+/// Duplicate keys are kept as separate entries,
+/// lookups return the last node with the key.
+impl FromIterator<(Key, Node)> for Entries {
+    fn from_iter<T: IntoIterator<Item = (Key, Node)>>(iter: T) -> Self {
+        let mut entries = Self::default();
+        entries.extend(iter);
+        entries
     }
 }
 
@@ -328,6 +535,33 @@ impl Comment {
         }
     }
 
+    /// The range of the value in the source text.
+    ///
+    /// For directives this is only the directive value,
+    /// and there is no range if the value is missing.
+    pub fn value_text_range(&self) -> Option<TextRange> {
+        let token = self.syntax.as_ref()?.as_token()?;
+        let text = token.text();
+
+        let (start, len) = match self.value_internal() {
+            CommentValue::Comment(_) => (1, text.len() - 1),
+            CommentValue::Directive { value, .. } => {
+                if value.is_empty() {
+                    return None;
+                }
+
+                let rest = text.strip_prefix("#:")?.trim_start();
+                let rest = rest
+                    .trim_start_matches(|c: char| !c.is_whitespace())
+                    .trim_start();
+                (text.len() - rest.len(), value.len())
+            }
+        };
+
+        let start = token.text_range().start() + TextSize::try_from(start).ok()?;
+        Some(TextRange::at(start, TextSize::try_from(len).ok()?))
+    }
+
     fn value_internal(&self) -> &CommentValue {
         self.value
             .get_or_init(|| match self.syntax.as_ref().and_then(|s| s.as_token()) {
@@ -9,12 +9,13 @@ use crate::{
 mod nodes;
 use either::Either;
 pub use nodes::*;
-use rowan::TextRange;
+use rowan::{TextRange, TextSize};
 
 use super::{
     error::{Error, QueryError},
     index::Index,
-    Comment, FromSyntax, KeyOrIndex, Keys,
+    visit::{walk, Visitor},
+    Comment, FromSyntax, KeyOrIndex, Keys, Path, PathPattern,
 };
 
 pub trait DomNode: Sized + Sealed {
@@ -81,6 +82,45 @@ impl DomNode for Node {
 }
 
 impl Node {
+    /// The semantic kind of the node.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use taplo::dom::node::{DomKind, IntegerRepr};
+    ///
+    /// let dom = taplo::parser::parse("a = 0xFF").into_dom();
+    ///
+    /// assert_eq!(dom.dom_kind(), DomKind::Root);
+    /// assert_eq!(dom.get("a").dom_kind(), DomKind::Integer(IntegerRepr::Hex));
+    /// ```
+    pub fn dom_kind(&self) -> DomKind {
+        match self {
+            Node::Table(t) => {
+                let syntax_kind = t.syntax().map(|s| s.kind());
+
+                if syntax_kind == Some(SyntaxKind::ROOT) {
+                    DomKind::Root
+                } else {
+                    DomKind::Table {
+                        inline: t.kind() == TableKind::Inline,
+                        pseudo: t.kind() == TableKind::Pseudo,
+                        array_member: syntax_kind == Some(SyntaxKind::TABLE_ARRAY_HEADER),
+                    }
+                }
+            }
+            Node::Array(arr) => DomKind::Array {
+                of_tables: arr.kind().is_tables(),
+            },
+            Node::Bool(_) => DomKind::Bool,
+            Node::Str(s) => DomKind::String(s.inner.repr),
+            Node::Integer(i) => DomKind::Integer(i.inner.repr),
+            Node::Float(_) => DomKind::Float,
+            Node::Date(d) => DomKind::Date(d.kind()),
+            Node::Invalid(_) => DomKind::Invalid,
+        }
+    }
+
     pub fn path(&self, keys: &Keys) -> Option<Node> {
         let mut node = self.clone();
         for key in keys.iter() {
@@ -154,26 +194,117 @@ impl Node {
         }
     }
 
+    /// All the descendant nodes with their paths in document order.
     pub fn flat_iter(&self) -> impl DoubleEndedIterator<Item = (Keys, Node)> {
-        let mut all = Vec::new();
+        self.flat_iter_impl().into_iter()
+    }
 
-        match self {
-            Node::Table(t) => {
-                let entries = t.inner.entries.read();
-                for (key, entry) in &entries.all {
-                    entry.collect_flat(Keys::from(key.clone()), &mut all);
+    /// All the descendant table entries with their paths in document order.
+    ///
+    /// This includes entries of pseudo-tables created by dotted keys,
+    /// inline tables and tables in arrays, the paths of the latter
+    /// contain the index of the table in the array.
+    ///
+    /// Array items are also included if `array_items` is `true`,
+    /// which makes this equivalent to [`Self::flat_iter`].
+    pub fn flat_entries(&self, array_items: bool) -> impl DoubleEndedIterator<Item = (Keys, Node)> {
+        struct Collect {
+            array_items: bool,
+            all: Vec<(Keys, Node)>,
+        }
+
+        impl Visitor for Collect {
+            fn visit_entry(&mut self, path: &Keys, _key: &Key, node: &Node) {
+                self.all.push((path.clone(), node.clone()));
+            }
+
+            fn visit_array_item(&mut self, path: &Keys, _index: usize, node: &Node) {
+                if self.array_items {
+                    self.all.push((path.clone(), node.clone()));
                 }
             }
-            Node::Array(arr) => {
-                let items = arr.inner.items.read();
-                for (idx, item) in items.iter().enumerate() {
-                    item.collect_flat(Keys::from(idx), &mut all);
+        }
+
+        let mut collect = Collect {
+            array_items,
+            all: Vec::new(),
+        };
+        walk(self, &mut collect);
+        collect.all.into_iter()
+    }
+
+    /// The path of the innermost inline array item at the given offset,
+    /// the last segment of the path is the index of the item.
+    ///
+    /// See [`Array::item_index_at`] for how offsets are matched to items.
+    pub fn array_item_at(&self, offset: TextSize) -> Option<Keys> {
+        self.flat_iter()
+            .filter_map(|(keys, node)| {
+                let arr = node.as_array().filter(|arr| arr.kind().is_inline())?;
+                Some(keys.join(arr.item_index_at(offset)?))
+            })
+            .max_by_key(Keys::len)
+    }
+
+    /// All the nodes with paths that match the given [`PathPattern`],
+    /// including the node itself, in document order.
+    ///
+    /// Arrays (including arrays of tables) are traversed as well,
+    /// so `bin.*.path` matches the `path` of every `[[bin]]` table.
+    ///
+    /// ```
+    /// let dom = taplo::parser::parse(
+    ///     r#"
+    /// [dependencies]
+    /// serde = { version = "1" }
+    /// toml = { version = "0.5", optional = true }
+    /// "#,
+    /// )
+    /// .into_dom();
+    ///
+    /// let versions = dom.find_all("dependencies.*.version").unwrap();
+    ///
+    /// assert_eq!(versions.len(), 2);
+    /// assert_eq!(versions[1].0.to_string(), "dependencies.toml.version");
+    /// ```
+    pub fn find_all(&self, pattern: &str) -> Result<Vec<(Path, Node)>, Error> {
+        fn find(
+            node: &Node,
+            path: &mut Path,
+            pattern: &PathPattern,
+            found: &mut Vec<(Path, Node)>,
+        ) {
+            if !pattern.matches_prefix(path) {
+                return;
+            }
+
+            if pattern.matches(path) {
+                found.push((path.clone(), node.clone()));
+            }
+
+            match node {
+                Node::Table(t) => {
+                    for (key, entry) in t.entries().read().iter() {
+                        path.push(key.value());
+                        find(entry, path, pattern, found);
+                        path.pop();
+                    }
+                }
+                Node::Array(arr) => {
+                    for (idx, item) in arr.items().read().iter().enumerate() {
+                        path.push(idx);
+                        find(item, path, pattern, found);
+                        path.pop();
+                    }
                 }
+                _ => {}
             }
-            _ => {}
         }
 
-        all.into_iter()
+        let pattern: PathPattern = pattern.parse()?;
+        let mut found = Vec::new();
+        find(self, &mut Path::new(), &pattern, &mut found);
+        Ok(found)
     }
 
     pub fn find_all_matches(
@@ -322,47 +453,7 @@ impl Node {
     }
 
     fn flat_iter_impl(&self) -> Vec<(Keys, Node)> {
-        let mut all = Vec::new();
-
-        match self {
-            Node::Table(t) => {
-                let entries = t.inner.entries.read();
-                for (key, entry) in &entries.all {
-                    entry.collect_flat(Keys::from(key.clone()), &mut all);
-                }
-            }
-            Node::Array(arr) => {
-                let items = arr.inner.items.read();
-                for (idx, item) in items.iter().enumerate() {
-                    item.collect_flat(Keys::from(idx), &mut all);
-                }
-            }
-            _ => {}
-        }
-
-        all
-    }
-
-    fn collect_flat(&self, parent: Keys, all: &mut Vec<(Keys, Node)>) {
-        match self {
-            Node::Table(t) => {
-                all.push((parent.clone(), self.clone()));
-                let entries = t.inner.entries.read();
-                for (key, entry) in &entries.all {
-                    entry.collect_flat(parent.join(key.clone()), all);
-                }
-            }
-            Node::Array(arr) => {
-                all.push((parent.clone(), self.clone()));
-                let items = arr.inner.items.read();
-                for (idx, item) in items.iter().enumerate() {
-                    item.collect_flat(parent.join(idx), all);
-                }
-            }
-            _ => {
-                all.push((parent, self.clone()));
-            }
-        }
+        self.flat_entries(true).collect()
     }
 
     fn validate_all_impl(&self, errors: &mut Vec<Error>) {
@@ -483,6 +574,21 @@ impl Node {
         matches!(self, Self::Invalid(..))
     }
 
+    /// Returns the inner value if the node is [`Table`].
+    ///
+    /// ```
+    /// let dom = taplo::parser::parse("name = 'taplo'").into_dom();
+    ///
+    /// let name = dom
+    ///     .as_table()
+    ///     .and_then(|table| table.get("name"))
+    ///     .and_then(|node| node.as_str().map(|s| s.value().to_string()));
+    ///
+    /// assert_eq!(name.as_deref(), Some("taplo"));
+    /// assert!(dom.as_array().is_none());
+    /// ```
+    ///
+    /// [`Table`]: Node::Table
     pub fn as_table(&self) -> Option<&Table> {
         if let Self::Table(v) = self {
             Some(v)
@@ -491,6 +597,9 @@ impl Node {
         }
     }
 
+    /// Returns the inner value if the node is [`Array`].
+    ///
+    /// [`Array`]: Node::Array
     pub fn as_array(&self) -> Option<&Array> {
         if let Self::Array(v) = self {
             Some(v)
@@ -499,6 +608,9 @@ impl Node {
         }
     }
 
+    /// Returns the inner value if the node is [`Bool`].
+    ///
+    /// [`Bool`]: Node::Bool
     pub fn as_bool(&self) -> Option<&Bool> {
         if let Self::Bool(v) = self {
             Some(v)
@@ -507,6 +619,9 @@ impl Node {
         }
     }
 
+    /// Returns the inner value if the node is [`Str`].
+    ///
+    /// [`Str`]: Node::Str
     pub fn as_str(&self) -> Option<&Str> {
         if let Self::Str(v) = self {
             Some(v)
@@ -515,6 +630,9 @@ impl Node {
         }
     }
 
+    /// Returns the inner value if the node is [`Integer`].
+    ///
+    /// [`Integer`]: Node::Integer
     pub fn as_integer(&self) -> Option<&Integer> {
         if let Self::Integer(v) = self {
             Some(v)
@@ -523,6 +641,9 @@ impl Node {
         }
     }
 
+    /// Returns the inner value if the node is [`Float`].
+    ///
+    /// [`Float`]: Node::Float
     pub fn as_float(&self) -> Option<&Float> {
         if let Self::Float(v) = self {
             Some(v)
@@ -531,6 +652,9 @@ impl Node {
         }
     }
 
+    /// Returns the inner value if the node is [`Date`].
+    ///
+    /// [`Date`]: Node::Date
     pub fn as_date(&self) -> Option<&DateTime> {
         if let Self::Date(v) = self {
             Some(v)
@@ -539,6 +663,9 @@ impl Node {
         }
     }
 
+    /// Returns the inner value if the node is [`Invalid`].
+    ///
+    /// [`Invalid`]: Node::Invalid
     pub fn as_invalid(&self) -> Option<&Invalid> {
         if let Self::Invalid(v) = self {
             Some(v)
@@ -547,6 +674,17 @@ impl Node {
         }
     }
 
+    /// Converts the node into the inner value if it is [`Table`],
+    /// otherwise the node is returned unchanged.
+    ///
+    /// ```
+    /// let dom = taplo::parser::parse("").into_dom();
+    ///
+    /// let dom = dom.try_into_array().unwrap_err();
+    /// assert!(dom.try_into_table().is_ok());
+    /// ```
+    ///
+    /// [`Table`]: Node::Table
     pub fn try_into_table(self) -> Result<Table, Self> {
         if let Self::Table(v) = self {
             Ok(v)
@@ -555,6 +693,10 @@ impl Node {
         }
     }
 
+    /// Converts the node into the inner value if it is [`Array`],
+    /// otherwise the node is returned unchanged.
+    ///
+    /// [`Array`]: Node::Array
     pub fn try_into_array(self) -> Result<Array, Self> {
         if let Self::Array(v) = self {
             Ok(v)
@@ -563,6 +705,10 @@ impl Node {
         }
     }
 
+    /// Converts the node into the inner value if it is [`Bool`],
+    /// otherwise the node is returned unchanged.
+    ///
+    /// [`Bool`]: Node::Bool
     pub fn try_into_bool(self) -> Result<Bool, Self> {
         if let Self::Bool(v) = self {
             Ok(v)
@@ -571,6 +717,10 @@ impl Node {
         }
     }
 
+    /// Converts the node into the inner value if it is [`Str`],
+    /// otherwise the node is returned unchanged.
+    ///
+    /// [`Str`]: Node::Str
     pub fn try_into_str(self) -> Result<Str, Self> {
         if let Self::Str(v) = self {
             Ok(v)
@@ -579,6 +729,10 @@ impl Node {
         }
     }
 
+    /// Converts the node into the inner value if it is [`Integer`],
+    /// otherwise the node is returned unchanged.
+    ///
+    /// [`Integer`]: Node::Integer
     pub fn try_into_integer(self) -> Result<Integer, Self> {
         if let Self::Integer(v) = self {
             Ok(v)
@@ -587,6 +741,10 @@ impl Node {
         }
     }
 
+    /// Converts the node into the inner value if it is [`Float`],
+    /// otherwise the node is returned unchanged.
+    ///
+    /// [`Float`]: Node::Float
     pub fn try_into_float(self) -> Result<Float, Self> {
         if let Self::Float(v) = self {
             Ok(v)
@@ -595,6 +753,10 @@ impl Node {
         }
     }
 
+    /// Converts the node into the inner value if it is [`Date`],
+    /// otherwise the node is returned unchanged.
+    ///
+    /// [`Date`]: Node::Date
     pub fn try_into_date(self) -> Result<DateTime, Self> {
         if let Self::Date(v) = self {
             Ok(v)
@@ -603,6 +765,10 @@ impl Node {
         }
     }
 
+    /// Converts the node into the inner value if it is [`Invalid`],
+    /// otherwise the node is returned unchanged.
+    ///
+    /// [`Invalid`]: Node::Invalid
     pub fn try_into_invalid(self) -> Result<Invalid, Self> {
         if let Self::Invalid(v) = self {
             Ok(v)
@@ -612,6 +778,24 @@ impl Node {
     }
 }
 
+impl core::ops::Index<&str> for Node {
+    type Output = Node;
+
+    /// # Panics
+    ///
+    /// Panics if the node is not a table or there is no entry with the given key.
+    fn index(&self, key: &str) -> &Self::Output {
+        match self {
+            Node::Table(table) => &table[key],
+            _ => panic!(
+                "cannot index {:?} with key {}",
+                self.dom_kind(),
+                crate::util::quote_key(key)
+            ),
+        }
+    }
+}
+
 impl From<DateTime> for Node {
     fn from(v: DateTime) -> Self {
         Self::Date(v)
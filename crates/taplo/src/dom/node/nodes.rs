@@ -1,13 +1,12 @@
 use super::{DomNode, Node};
 use crate::{
     dom::{error::Error, Entries, KeyOrIndex, Keys},
-    syntax::{SyntaxElement, SyntaxKind},
-    util::{shared::Shared, unescape},
+    syntax::{SyntaxElement, SyntaxKind, SyntaxToken},
+    util::{quote_key, shared::Shared, unescape, UnescapeError},
 };
-use logos::Lexer;
 use once_cell::unsync::OnceCell;
-use rowan::{NodeOrToken, TextRange};
-use std::{fmt::Write, iter::once, sync::Arc};
+use rowan::{NodeOrToken, TextRange, TextSize};
+use std::{iter::once, sync::Arc};
 use time::macros::format_description;
 
 macro_rules! wrap_node {
@@ -54,6 +53,85 @@ macro_rules! wrap_node {
     };
 }
 
+/// Implements validation and [`Display`](core::fmt::Display)
+/// for nodes that wrap a single primitive value token.
+///
+/// The values are parsed lazily, the parsing itself records
+/// an error if the token is not valid for the node.
+macro_rules! primitive_node {
+    ($($name:ident),* $(,)?) => {
+        $(
+            impl $name {
+                fn validate_impl(&self) -> Result<(), &Shared<Vec<Error>>> {
+                    let _ = self.value();
+                    if self.errors().read().as_ref().is_empty() {
+                        Ok(())
+                    } else {
+                        Err(self.errors())
+                    }
+                }
+            }
+
+            /// The normalized TOML representation of the value.
+            impl core::fmt::Display for $name {
+                fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+                    PrimitiveValue::fmt_toml(&self.value(), f)
+                }
+            }
+        )*
+    };
+}
+
+trait PrimitiveValue {
+    fn fmt_toml(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result;
+}
+
+impl PrimitiveValue for bool {
+    fn fmt_toml(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        core::fmt::Display::fmt(self, f)
+    }
+}
+
+impl PrimitiveValue for IntegerValue {
+    fn fmt_toml(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        core::fmt::Display::fmt(self, f)
+    }
+}
+
+impl PrimitiveValue for f64 {
+    fn fmt_toml(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.is_nan() {
+            f.write_str("nan")
+        } else if self.is_infinite() {
+            f.write_str(if self.is_sign_negative() {
+                "-inf"
+            } else {
+                "inf"
+            })
+        } else {
+            // Unlike `Display`, this always keeps a fractional part or an exponent.
+            write!(f, "{self:?}")
+        }
+    }
+}
+
+impl PrimitiveValue for DateTimeValue {
+    fn fmt_toml(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        core::fmt::Display::fmt(self, f)
+    }
+}
+
+primitive_node!(Bool, Integer, Float, DateTime);
+
+/// Returns the token of a primitive node if it has the expected kind,
+/// otherwise the node was already created with an error.
+fn primitive_token(syntax: Option<&SyntaxElement>, kinds: &[SyntaxKind]) -> Option<SyntaxToken> {
+    syntax
+        .and_then(|s| s.as_token())
+        .filter(|t| kinds.contains(&t.kind()))
+        .cloned()
+}
+
 #[derive(Debug)]
 pub(crate) struct TableInner {
     pub(crate) errors: Shared<Vec<Error>>,
@@ -61,6 +139,9 @@ pub(crate) struct TableInner {
     pub(crate) header: bool,
     pub(crate) kind: TableKind,
     pub(crate) entries: Shared<Entries>,
+    /// The entries that references are returned into by indexing,
+    /// they can only change while the DOM is constructed.
+    pub(crate) indexed: OnceCell<Arc<Entries>>,
 }
 
 wrap_node! {
@@ -83,39 +164,124 @@ impl Table {
         self.inner.kind
     }
 
-    /// Add an entry and also collect errors on conflicts.
+    /// The range of the header (e.g. `[table]` or `[[array]]`)
+    /// if the table has one.
+    ///
+    /// Comments after the header are not included.
+    pub fn header_range(&self) -> Option<TextRange> {
+        let header = self.syntax().filter(|s| {
+            matches!(
+                s.kind(),
+                SyntaxKind::TABLE_HEADER | SyntaxKind::TABLE_ARRAY_HEADER
+            )
+        })?;
+        let range = header.text_range();
+
+        let end = header
+            .as_node()
+            .and_then(|n| {
+                n.children_with_tokens()
+                    .filter(|t| t.kind() == SyntaxKind::BRACKET_END)
+                    .last()
+            })
+            .map_or(range.end(), |t| t.text_range().end());
+
+        Some(TextRange::new(range.start(), end))
+    }
+
+    /// The range covering the keys and values of all the entries,
+    /// or [`None`] if the table has no entries.
+    ///
+    /// See [`Entries::text_range`].
+    pub fn entries_range(&self) -> Option<TextRange> {
+        self.inner.entries.read().text_range()
+    }
+
+    /// The range covering the table and all of its entries.
+    ///
+    /// For tables with a header, this spans from the header up to the next
+    /// header (or the end of the document), including any comments, whitespace
+    /// or invalid syntax in between.
+    pub fn full_range(&self) -> Option<TextRange> {
+        if let Some(header) = self.syntax().filter(|s| {
+            matches!(
+                s.kind(),
+                SyntaxKind::TABLE_HEADER | SyntaxKind::TABLE_ARRAY_HEADER
+            )
+        }) {
+            let range = header.text_range();
+
+            let end = std::iter::successors(header.next_sibling_or_token(), |s| {
+                s.next_sibling_or_token()
+            })
+            .take_while(|s| {
+                !matches!(
+                    s.kind(),
+                    SyntaxKind::TABLE_HEADER | SyntaxKind::TABLE_ARRAY_HEADER
+                )
+            })
+            .last()
+            .map_or(range.end(), |s| s.text_range().end());
+
+            return Some(TextRange::new(range.start(), end));
+        }
+
+        let own = self.syntax().map(|s| s.text_range());
+
+        match (own, self.entries_range()) {
+            (Some(own), Some(entries)) => Some(own.cover(entries)),
+            (own, entries) => own.or(entries),
+        }
+    }
+
+    /// Add an entry to the table, pseudo-tables of dotted keys
+    /// are merged with the existing pseudo-tables with the same keys.
+    ///
+    /// The entries are looked up by key, so that only the entries with
+    /// the same key are merged, and nested pseudo-tables are merged
+    /// with an explicit stack, as dotted keys can be arbitrarily deep.
     pub(crate) fn add_entry(&self, key: Key, node: Node) {
-        self.inner.entries.update(|entries| {
-            if let Some((existing_key, value)) = entries.lookup.get_key_value(&key) {
-                // Merge the two pseudo-tables together.
-                if let (Node::Table(existing_table), Node::Table(new_table)) = (value, &node) {
-                    if existing_table.inner.kind == TableKind::Pseudo
-                        && new_table.inner.kind == TableKind::Pseudo
-                    {
-                        let new_entries = new_table.entries().read();
-                        for (k, n) in new_entries.iter() {
-                            if let Some(additional_syntax) = k.syntax() {
-                                existing_key.inner.additional_syntaxes.update(|syntaxes| {
-                                    syntaxes.push(additional_syntax.clone());
-                                });
+        let mut stack = Vec::from([(self.clone(), key, node)]);
+
+        while let Some((table, key, node)) = stack.pop() {
+            table.inner.entries.update(|entries| {
+                if let Some((existing_key, value)) = entries.lookup.get_key_value(&key) {
+                    // Merge the two pseudo-tables together.
+                    if let (Node::Table(existing_table), Node::Table(new_table)) = (value, &node) {
+                        if existing_table.inner.kind == TableKind::Pseudo
+                            && new_table.inner.kind == TableKind::Pseudo
+                        {
+                            let new_entries = new_table.entries().read();
+                            for (k, _) in new_entries.iter() {
+                                if let Some(additional_syntax) = k.syntax() {
+                                    existing_key.inner.additional_syntaxes.update(|syntaxes| {
+                                        syntaxes.push(additional_syntax.clone());
+                                    });
+                                }
                             }
 
-                            existing_table.add_entry(k.clone(), n.clone());
+                            // Reversed, so that the entries are merged in order.
+                            for (k, n) in new_entries.as_slice().iter().rev() {
+                                stack.push((existing_table.clone(), k.clone(), n.clone()));
+                            }
+                            return;
                         }
-                        return;
                     }
-                }
 
-                self.inner.errors.update(|errors| {
-                    errors.push(Error::ConflictingKeys {
-                        key: key.clone(),
-                        other: existing_key.clone(),
-                    })
-                });
-            }
+                    table.inner.errors.update(|errors| {
+                        errors.push(Error::ConflictingKeys {
+                            key: key.clone(),
+                            other: existing_key.clone(),
+                        })
+                    });
 
-            entries.add(key, node);
-        });
+                    entries.add_conflicting(key, node);
+                    return;
+                }
+
+                entries.add(key, node);
+            });
+        }
     }
 
     fn validate_impl(&self) -> Result<(), &Shared<Vec<Error>>> {
@@ -127,13 +293,53 @@ impl Table {
     }
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+impl core::ops::Index<&str> for Table {
+    type Output = Node;
+
+    /// # Panics
+    ///
+    /// Panics if there is no entry with the given key.
+    fn index(&self, key: &str) -> &Self::Output {
+        &self.inner.indexed.get_or_init(|| self.inner.entries.get())[key]
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum TableKind {
     Regular,
     Inline,
     Pseudo,
 }
 
+/// The semantic kind of a DOM node.
+///
+/// Unlike [`SyntaxKind`], this does not depend on the
+/// tokens or syntax nodes that back the DOM node.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum DomKind {
+    /// The root table of a document.
+    Root,
+    Table {
+        /// The table is an inline table.
+        inline: bool,
+        /// The table was implicitly created by a dotted key
+        /// or a table header.
+        pseudo: bool,
+        /// The table is an item of an array of tables.
+        array_member: bool,
+    },
+    String(StrRepr),
+    Integer(IntegerRepr),
+    Float,
+    Bool,
+    Date(DateKind),
+    Array {
+        /// The array is an array of tables.
+        of_tables: bool,
+    },
+    Invalid,
+}
+
 #[derive(Debug)]
 pub(crate) struct KeyInner {
     pub(crate) errors: Shared<Vec<Error>>,
@@ -206,11 +412,9 @@ impl Key {
                         let string = string.strip_suffix('"').unwrap_or(string);
                         match unescape(string) {
                             Ok(s) => s,
-                            Err(_) => {
+                            Err(error) => {
                                 self.inner.errors.update(|errors| {
-                                    errors.push(Error::InvalidEscapeSequence {
-                                        string: s.clone().into(),
-                                    })
+                                    errors.push(invalid_escape(&s.clone().into(), 1, error))
                                 });
                                 String::new()
                             }
@@ -266,17 +470,7 @@ impl core::fmt::Display for Key {
             return s.fmt(f);
         }
 
-        if !matches!(
-            Lexer::<SyntaxKind>::new(self.value()).next(),
-            Some(SyntaxKind::IDENT) | None
-        ) {
-            f.write_char('\'')?;
-            self.value().fmt(f)?;
-            f.write_char('\'')?;
-            return Ok(());
-        }
-
-        self.value().fmt(f)
+        quote_key(self.value()).fmt(f)
     }
 }
 
@@ -324,6 +518,40 @@ impl Array {
         self.inner.kind
     }
 
+    /// The text ranges of the items in the array.
+    pub fn item_ranges(&self) -> Vec<TextRange> {
+        self.items()
+            .read()
+            .iter()
+            .map(|item| item.text_ranges().next().unwrap_or_default())
+            .collect()
+    }
+
+    /// The header ranges of the tables in an array of tables,
+    /// empty for inline arrays.
+    pub fn table_headers(&self) -> Vec<TextRange> {
+        self.items()
+            .read()
+            .iter()
+            .filter_map(|item| item.as_table()?.header_range())
+            .collect()
+    }
+
+    /// The index of the item at the given offset.
+    ///
+    /// Separators and whitespace after an item belong to it,
+    /// [`None`] is returned for offsets outside of the array
+    /// or before the first item.
+    pub fn item_index_at(&self, offset: TextSize) -> Option<usize> {
+        if !self.syntax()?.text_range().contains(offset) {
+            return None;
+        }
+
+        self.item_ranges()
+            .iter()
+            .rposition(|range| range.start() <= offset)
+    }
+
     fn validate_impl(&self) -> Result<(), &Shared<Vec<Error>>> {
         if self.errors().read().as_ref().is_empty() {
             Ok(())
@@ -333,7 +561,7 @@ impl Array {
     }
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum ArrayKind {
     Tables,
     Inline,
@@ -371,20 +599,24 @@ impl Bool {
     /// A boolean value.
     pub fn value(&self) -> bool {
         *self.inner.value.get_or_init(|| {
-            self.syntax()
-                .and_then(|s| s.as_token())
-                .and_then(|s| s.text().parse().ok())
-                .unwrap_or_default()
+            let Some(token) = primitive_token(self.syntax(), &[SyntaxKind::BOOL]) else {
+                return false;
+            };
+
+            match token.text() {
+                "true" => true,
+                "false" => false,
+                _ => {
+                    self.inner.errors.update(|errors| {
+                        errors.push(Error::UnexpectedSyntax {
+                            syntax: token.clone().into(),
+                        })
+                    });
+                    false
+                }
+            }
         })
     }
-
-    fn validate_impl(&self) -> Result<(), &Shared<Vec<Error>>> {
-        if self.errors().read().as_ref().is_empty() {
-            Ok(())
-        } else {
-            Err(self.errors())
-        }
-    }
 }
 
 #[derive(Debug)]
@@ -407,54 +639,61 @@ impl Str {
             self.inner
                 .syntax
                 .as_ref()
-                .map(|s| match self.inner.repr {
-                    StrRepr::Basic => {
-                        let string = s.as_token().unwrap().text();
-                        let string = string.strip_prefix('"').unwrap_or(string);
-                        let string = string.strip_suffix('"').unwrap_or(string);
-                        match unescape(string) {
-                            Ok(s) => s,
-                            Err(_) => {
-                                self.inner.errors.update(|errors| {
-                                    errors.push(Error::InvalidEscapeSequence { string: s.clone() })
-                                });
-                                String::new()
+                .map(|s| {
+                    let Some(string) = s.as_token().map(|t| t.text()) else {
+                        self.inner.errors.update(|errors| {
+                            errors.push(Error::UnexpectedSyntax { syntax: s.clone() })
+                        });
+                        return String::new();
+                    };
+
+                    match self.inner.repr {
+                        StrRepr::Basic => {
+                            let string = string.strip_prefix('"').unwrap_or(string);
+                            let string = string.strip_suffix('"').unwrap_or(string);
+                            match unescape(string) {
+                                Ok(s) => s,
+                                Err(error) => {
+                                    self.inner
+                                        .errors
+                                        .update(|errors| errors.push(invalid_escape(s, 1, error)));
+                                    String::new()
+                                }
                             }
                         }
-                    }
-                    StrRepr::Literal => {
-                        let string = s.as_token().unwrap().text();
-                        let string = string.strip_prefix('\'').unwrap_or(string);
-                        let string = string.strip_suffix('\'').unwrap_or(string);
-                        string.to_string()
-                    }
-                    StrRepr::MultiLine => {
-                        let string = s.as_token().unwrap().text();
-                        let string = string.strip_prefix(r#"""""#).unwrap_or(string);
-                        let string = match string.strip_prefix("\r\n") {
-                            Some(s) => s,
-                            None => string.strip_prefix('\n').unwrap_or(string),
-                        };
-                        let string = string.strip_suffix(r#"""""#).unwrap_or(string);
-                        match unescape(string) {
-                            Ok(s) => s,
-                            Err(_) => {
-                                self.inner.errors.update(|errors| {
-                                    errors.push(Error::InvalidEscapeSequence { string: s.clone() })
-                                });
-                                String::new()
+                        StrRepr::Literal => {
+                            let string = string.strip_prefix('\'').unwrap_or(string);
+                            let string = string.strip_suffix('\'').unwrap_or(string);
+                            string.to_string()
+                        }
+                        StrRepr::MultiLine => {
+                            let text_len = string.len();
+                            let string = string.strip_prefix(r#"""""#).unwrap_or(string);
+                            let string = match string.strip_prefix("\r\n") {
+                                Some(s) => s,
+                                None => string.strip_prefix('\n').unwrap_or(string),
+                            };
+                            let offset = text_len - string.len();
+                            let string = string.strip_suffix(r#"""""#).unwrap_or(string);
+                            match unescape(string) {
+                                Ok(s) => s,
+                                Err(error) => {
+                                    self.inner.errors.update(|errors| {
+                                        errors.push(invalid_escape(s, offset, error))
+                                    });
+                                    String::new()
+                                }
                             }
                         }
-                    }
-                    StrRepr::MultiLineLiteral => {
-                        let string = s.as_token().unwrap().text();
-                        let string = string.strip_prefix(r#"'''"#).unwrap_or(string);
-                        let string = match string.strip_prefix("\r\n") {
-                            Some(s) => s,
-                            None => string.strip_prefix('\n').unwrap_or(string),
-                        };
-                        let string = string.strip_suffix(r#"'''"#).unwrap_or(string);
-                        string.to_string()
+                        StrRepr::MultiLineLiteral => {
+                            let string = string.strip_prefix(r#"'''"#).unwrap_or(string);
+                            let string = match string.strip_prefix("\r\n") {
+                                Some(s) => s,
+                                None => string.strip_prefix('\n').unwrap_or(string),
+                            };
+                            let string = string.strip_suffix(r#"'''"#).unwrap_or(string);
+                            string.to_string()
+                        }
                     }
                 })
                 .unwrap_or_default()
@@ -471,7 +710,19 @@ impl Str {
     }
 }
 
-#[derive(Debug, Copy, Clone)]
+/// An error for an invalid escape sequence in `string`,
+/// the escaped content starts at `offset` in the string token.
+fn invalid_escape(string: &SyntaxElement, offset: usize, error: UnescapeError) -> Error {
+    let start = string.text_range().start() + TextSize::from((offset + error.offset) as u32);
+
+    Error::InvalidEscapeSequence {
+        string: string.clone(),
+        range: TextRange::at(start, TextSize::from(error.len as u32)),
+        error,
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
 pub enum StrRepr {
     Basic,
     MultiLine,
@@ -493,49 +744,64 @@ wrap_node! {
 }
 
 impl Integer {
+    /// The representation of the integer, e.g. hexadecimal.
+    pub fn repr(&self) -> IntegerRepr {
+        self.inner.repr
+    }
+
     /// An integer value.
     pub fn value(&self) -> IntegerValue {
         *self.inner.value.get_or_init(|| {
-            if let Some(s) = self.syntax().and_then(|s| s.as_token()) {
-                let int_text = s.text().replace('_', "");
-
-                match self.inner.repr {
-                    IntegerRepr::Dec => {
-                        if s.text().starts_with('-') {
-                            IntegerValue::Negative(int_text.parse().unwrap_or_default())
-                        } else {
-                            IntegerValue::Positive(int_text.parse().unwrap_or_default())
-                        }
+            let Some(token) = primitive_token(
+                self.syntax(),
+                &[
+                    SyntaxKind::INTEGER,
+                    SyntaxKind::INTEGER_BIN,
+                    SyntaxKind::INTEGER_OCT,
+                    SyntaxKind::INTEGER_HEX,
+                ],
+            ) else {
+                return IntegerValue::Positive(0);
+            };
+
+            let int_text = token.text().replace('_', "");
+
+            let parsed = match self.inner.repr {
+                IntegerRepr::Dec => {
+                    if int_text.starts_with('-') {
+                        int_text.parse().ok().map(IntegerValue::Negative)
+                    } else {
+                        int_text.parse().ok().map(IntegerValue::Positive)
                     }
-                    IntegerRepr::Bin => IntegerValue::Positive(
-                        u64::from_str_radix(int_text.trim_start_matches("0b"), 2)
-                            .unwrap_or_default(),
-                    ),
-                    IntegerRepr::Oct => IntegerValue::Positive(
-                        u64::from_str_radix(int_text.trim_start_matches("0o"), 8)
-                            .unwrap_or_default(),
-                    ),
-                    IntegerRepr::Hex => IntegerValue::Positive(
-                        u64::from_str_radix(int_text.trim_start_matches("0x"), 16)
-                            .unwrap_or_default(),
-                    ),
                 }
-            } else {
-                IntegerValue::Positive(0)
+                IntegerRepr::Bin => u64::from_str_radix(int_text.trim_start_matches("0b"), 2)
+                    .ok()
+                    .map(IntegerValue::Positive),
+                IntegerRepr::Oct => u64::from_str_radix(int_text.trim_start_matches("0o"), 8)
+                    .ok()
+                    .map(IntegerValue::Positive),
+                IntegerRepr::Hex => u64::from_str_radix(int_text.trim_start_matches("0x"), 16)
+                    .ok()
+                    .map(IntegerValue::Positive),
+            };
+
+            match parsed {
+                Some(v) => v,
+                None => {
+                    // The integer is out of range or the token is malformed.
+                    self.inner.errors.update(|errors| {
+                        errors.push(Error::UnexpectedSyntax {
+                            syntax: token.clone().into(),
+                        })
+                    });
+                    IntegerValue::Positive(0)
+                }
             }
         })
     }
-
-    fn validate_impl(&self) -> Result<(), &Shared<Vec<Error>>> {
-        if self.errors().read().as_ref().is_empty() {
-            Ok(())
-        } else {
-            Err(self.errors())
-        }
-    }
 }
 
-#[derive(Debug, Copy, Clone)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
 pub enum IntegerRepr {
     Dec,
     Bin,
@@ -606,22 +872,23 @@ impl Float {
     /// A float value.
     pub fn value(&self) -> f64 {
         *self.inner.value.get_or_init(|| {
-            if let Some(text) = self.syntax().and_then(|s| s.as_token()).map(|s| s.text()) {
-                text.replace('_', "").replace("nan", "NaN").parse().unwrap()
-            } else {
-                0_f64
+            let Some(token) = primitive_token(self.syntax(), &[SyntaxKind::FLOAT]) else {
+                return 0_f64;
+            };
+
+            match token.text().replace('_', "").replace("nan", "NaN").parse() {
+                Ok(v) => v,
+                Err(_) => {
+                    self.inner.errors.update(|errors| {
+                        errors.push(Error::UnexpectedSyntax {
+                            syntax: token.clone().into(),
+                        })
+                    });
+                    0_f64
+                }
             }
         })
     }
-
-    fn validate_impl(&self) -> Result<(), &Shared<Vec<Error>>> {
-        let _ = self.value();
-        if self.errors().read().as_ref().is_empty() {
-            Ok(())
-        } else {
-            Err(self.errors())
-        }
-    }
 }
 
 #[derive(Debug)]
@@ -637,80 +904,101 @@ wrap_node! {
 }
 
 impl DateTime {
+    /// The kind of the date-time, this is based on the syntax
+    /// and is available even if the value itself is invalid.
+    pub fn kind(&self) -> DateKind {
+        match self.syntax().map(|s| s.kind()) {
+            Some(SyntaxKind::DATE_TIME_OFFSET) => DateKind::OffsetDateTime,
+            Some(SyntaxKind::DATE_TIME_LOCAL) => DateKind::LocalDateTime,
+            Some(SyntaxKind::DATE) => DateKind::LocalDate,
+            Some(SyntaxKind::TIME) => DateKind::LocalTime,
+            _ => self.value().kind(),
+        }
+    }
+
+    /// A date-time value.
+    ///
+    /// Invalid date-times are recorded as errors
+    /// and are returned as the UNIX epoch.
     pub fn value(&self) -> DateTimeValue {
         *self.inner.value.get_or_init(|| {
-            if let Some(token) = self.syntax().and_then(|s| s.as_token()) {
-                let mut text = token.text().to_string();
-
-                // SAFETY: we're replacing single-byte characters.
-                unsafe {
-                    for b in text.as_bytes_mut() {
-                        if *b == b' ' || *b == b't' {
-                            *b = b'T';
-                        } else if *b == b'z' {
-                            *b = b'Z';
-                        } else if *b == b',' {
-                            *b = b'.';
-                        }
+            let Some(token) = primitive_token(
+                self.syntax(),
+                &[
+                    SyntaxKind::DATE_TIME_OFFSET,
+                    SyntaxKind::DATE_TIME_LOCAL,
+                    SyntaxKind::DATE,
+                    SyntaxKind::TIME,
+                ],
+            ) else {
+                return DateTimeValue::OffsetDateTime(time::OffsetDateTime::UNIX_EPOCH);
+            };
+
+            let mut text = token.text().to_string();
+
+            // SAFETY: we're replacing single-byte characters.
+            unsafe {
+                for b in text.as_bytes_mut() {
+                    if *b == b' ' || *b == b't' {
+                        *b = b'T';
+                    } else if *b == b'z' {
+                        *b = b'Z';
+                    } else if *b == b',' {
+                        *b = b'.';
                     }
                 }
+            }
 
-                match token.kind() {
-                    SyntaxKind::DATE_TIME_OFFSET => {
-                        if let Ok(d) = time::OffsetDateTime::parse(
-                            &text,
-                            &time::format_description::well_known::Rfc3339,
-                        ) {
-                            return DateTimeValue::OffsetDateTime(d);
-                        }
-                    }
-                    SyntaxKind::DATE_TIME_LOCAL => {
-                        let desc = if text.contains('.') {
-                            format_description!(
-                                "[year]-[month]-[day]T[hour]:[minute]:[second].[subsecond]"
-                            )
-                        } else {
-                            format_description!("[year]-[month]-[day]T[hour]:[minute]:[second]")
-                        };
-
-                        if let Ok(d) = time::PrimitiveDateTime::parse(&text, &desc) {
-                            return DateTimeValue::LocalDateTime(d);
-                        }
+            match token.kind() {
+                SyntaxKind::DATE_TIME_OFFSET => {
+                    if let Ok(d) = time::OffsetDateTime::parse(
+                        &text,
+                        &time::format_description::well_known::Rfc3339,
+                    ) {
+                        return DateTimeValue::OffsetDateTime(d);
                     }
-                    SyntaxKind::DATE => {
-                        if let Ok(d) =
-                            time::Date::parse(&text, &format_description!("[year]-[month]-[day]"))
-                        {
-                            return DateTimeValue::Date(d);
-                        }
+                }
+                SyntaxKind::DATE_TIME_LOCAL => {
+                    let desc = if text.contains('.') {
+                        format_description!(
+                            "[year]-[month]-[day]T[hour]:[minute]:[second].[subsecond]"
+                        )
+                    } else {
+                        format_description!("[year]-[month]-[day]T[hour]:[minute]:[second]")
+                    };
+
+                    if let Ok(d) = time::PrimitiveDateTime::parse(&text, &desc) {
+                        return DateTimeValue::LocalDateTime(d);
                     }
-                    SyntaxKind::TIME => {
-                        let desc = if text.contains('.') {
-                            format_description!("[hour]:[minute]:[second].[subsecond]")
-                        } else {
-                            format_description!("[hour]:[minute]:[second]")
-                        };
-
-                        if let Ok(d) = time::Time::parse(&text, &desc) {
-                            return DateTimeValue::Time(d);
-                        }
+                }
+                SyntaxKind::DATE => {
+                    if let Ok(d) =
+                        time::Date::parse(&text, &format_description!("[year]-[month]-[day]"))
+                    {
+                        return DateTimeValue::Date(d);
                     }
-                    _ => {}
                 }
+                SyntaxKind::TIME => {
+                    let desc = if text.contains('.') {
+                        format_description!("[hour]:[minute]:[second].[subsecond]")
+                    } else {
+                        format_description!("[hour]:[minute]:[second]")
+                    };
 
-                DateTimeValue::OffsetDateTime(time::OffsetDateTime::UNIX_EPOCH)
-            } else {
-                DateTimeValue::OffsetDateTime(time::OffsetDateTime::UNIX_EPOCH)
+                    if let Ok(d) = time::Time::parse(&text, &desc) {
+                        return DateTimeValue::Time(d);
+                    }
+                }
+                _ => {}
             }
-        })
-    }
 
-    fn validate_impl(&self) -> Result<(), &Shared<Vec<Error>>> {
-        if self.errors().read().as_ref().is_empty() {
-            Ok(())
-        } else {
-            Err(self.errors())
-        }
+            self.inner.errors.update(|errors| {
+                errors.push(Error::UnexpectedSyntax {
+                    syntax: token.clone().into(),
+                })
+            });
+            DateTimeValue::OffsetDateTime(time::OffsetDateTime::UNIX_EPOCH)
+        })
     }
 }
 
@@ -722,12 +1010,33 @@ pub enum DateTimeValue {
     Time(time::Time),
 }
 
+impl DateTimeValue {
+    /// The kind of the date-time value.
+    pub fn kind(&self) -> DateKind {
+        match self {
+            DateTimeValue::OffsetDateTime(_) => DateKind::OffsetDateTime,
+            DateTimeValue::LocalDateTime(_) => DateKind::LocalDateTime,
+            DateTimeValue::Date(_) => DateKind::LocalDate,
+            DateTimeValue::Time(_) => DateKind::LocalTime,
+        }
+    }
+}
+
+/// The kinds of date-time values defined by TOML.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum DateKind {
+    OffsetDateTime,
+    LocalDateTime,
+    LocalDate,
+    LocalTime,
+}
+
 impl core::fmt::Display for DateTimeValue {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
             DateTimeValue::OffsetDateTime(dt) => dt
                 .format(&time::format_description::well_known::Rfc3339)
-                .unwrap()
+                .map_err(|_| core::fmt::Error)?
                 .fmt(f),
             DateTimeValue::LocalDateTime(dt) => dt
                 .format(if dt.time().nanosecond() > 0 {
@@ -737,11 +1046,11 @@ impl core::fmt::Display for DateTimeValue {
                 } else {
                     &format_description!("[year]-[month]-[day]T[hour]:[minute]:[second]")
                 })
-                .unwrap()
+                .map_err(|_| core::fmt::Error)?
                 .fmt(f),
             DateTimeValue::Date(date) => date
                 .format(&format_description!("[year]-[month]-[day]"))
-                .unwrap()
+                .map_err(|_| core::fmt::Error)?
                 .fmt(f),
             DateTimeValue::Time(time) => time
                 .format(if time.nanosecond() > 0 {
@@ -749,7 +1058,7 @@ impl core::fmt::Display for DateTimeValue {
                 } else {
                     &format_description!("[hour]:[minute]:[second]")
                 })
-                .unwrap()
+                .map_err(|_| core::fmt::Error)?
                 .fmt(f),
         }
     }
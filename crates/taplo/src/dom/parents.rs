@@ -0,0 +1,116 @@
+//! Parent lookups for DOM nodes.
+//!
+//! DOM nodes only reference their children, [`ParentIndex`]
+//! can be built from a root node to navigate the tree upwards.
+
+use super::{node::DomNode, KeyOrIndex, Keys, Node};
+use crate::HashMap;
+use std::iter::successors;
+
+/// An index of the parents of all the nodes in a tree.
+///
+/// Nodes are identified by their address, so only
+/// nodes of the indexed tree (or clones of them) can be looked up.
+///
+/// # Example
+///
+/// ```
+/// use taplo::dom::{node::Key, parents::ParentIndex, Keys};
+///
+/// let root = taplo::parser::parse("a.b = [1, 2]").into_dom();
+/// let index = ParentIndex::new(&root);
+///
+/// let keys = Keys::single(Key::new("a")).join(Key::new("b")).join(1usize);
+/// let item = root.path(&keys).unwrap();
+///
+/// assert_eq!(index.path(&item).unwrap(), keys);
+/// assert_eq!(index.ancestors(&item).count(), 3);
+/// ```
+#[derive(Debug, Clone)]
+pub struct ParentIndex {
+    root: Node,
+    parents: HashMap<usize, (Node, KeyOrIndex)>,
+}
+
+impl ParentIndex {
+    /// Index all the descendants of the given root node.
+    pub fn new(root: &Node) -> Self {
+        let mut index = Self {
+            root: root.clone(),
+            parents: HashMap::default(),
+        };
+        index.add_children(root);
+        index
+    }
+
+    /// The node the index was built from.
+    pub fn root(&self) -> &Node {
+        &self.root
+    }
+
+    /// The direct parent of the node, or [`None`]
+    /// for the root and nodes that are not in the tree.
+    pub fn parent(&self, node: &Node) -> Option<&Node> {
+        self.parents.get(&node.addr()).map(|(parent, _)| parent)
+    }
+
+    /// The ancestors of the node starting with its parent
+    /// and ending with the root.
+    pub fn ancestors<'i>(&'i self, node: &Node) -> impl Iterator<Item = &'i Node> {
+        successors(self.parent(node), move |n| self.parent(n))
+    }
+
+    /// The path of the node relative to the root.
+    ///
+    /// The path is empty for the root and [`None`]
+    /// is returned for nodes that are not in the tree.
+    pub fn path(&self, node: &Node) -> Option<Keys> {
+        if node.addr() == self.root.addr() {
+            return Some(Keys::empty());
+        }
+
+        let mut segments = Vec::new();
+        let mut current = node;
+
+        while let Some((parent, segment)) = self.parents.get(&current.addr()) {
+            segments.push(segment.clone());
+            current = parent;
+        }
+
+        if current.addr() != self.root.addr() {
+            return None;
+        }
+
+        Some(Keys::new(segments.into_iter().rev()))
+    }
+
+    fn add_children(&mut self, node: &Node) {
+        match node {
+            Node::Table(table) => {
+                let entries = table.entries().read();
+                for (key, entry) in entries.iter() {
+                    self.parents
+                        .insert(entry.addr(), (node.clone(), key.clone().into()));
+                    self.add_children(entry);
+                }
+            }
+            Node::Array(arr) => {
+                let items = arr.items().read();
+                for (idx, item) in items.iter().enumerate() {
+                    self.parents.insert(item.addr(), (node.clone(), idx.into()));
+                    self.add_children(item);
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+impl Node {
+    /// The address of the shared node data, this is
+    /// the same for all clones of the node.
+    fn addr(&self) -> usize {
+        // Every node type has error storage that is unique to the node.
+        self.errors() as *const _ as usize
+    }
+}
@@ -0,0 +1,434 @@
+//! Paths that address nodes in a DOM.
+//!
+//! The textual form of a path is a list of dotted keys with
+//! array indices in brackets, e.g. `package.authors[0]` or
+//! `bin[1]."quoted key"`. Keys are quoted when required,
+//! using the same rules as TOML keys, so a key like `3`
+//! is never confused with the index `[3]`.
+
+use super::{error::QueryError, node::Key, Error, KeyOrIndex, Keys};
+use crate::util::{quote_key, unescape};
+use std::{fmt::Write, str::FromStr};
+
+/// A single segment of a [`Path`].
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum Segment {
+    /// An unescaped table key.
+    Key(String),
+    /// An array index.
+    Index(usize),
+}
+
+impl Segment {
+    pub fn as_key(&self) -> Option<&str> {
+        match self {
+            Segment::Key(k) => Some(k),
+            Segment::Index(_) => None,
+        }
+    }
+
+    pub fn as_index(&self) -> Option<usize> {
+        match self {
+            Segment::Key(_) => None,
+            Segment::Index(idx) => Some(*idx),
+        }
+    }
+}
+
+impl From<&str> for Segment {
+    fn from(key: &str) -> Self {
+        Segment::Key(key.into())
+    }
+}
+
+impl From<String> for Segment {
+    fn from(key: String) -> Self {
+        Segment::Key(key)
+    }
+}
+
+impl From<usize> for Segment {
+    fn from(idx: usize) -> Self {
+        Segment::Index(idx)
+    }
+}
+
+impl From<&KeyOrIndex> for Segment {
+    fn from(k: &KeyOrIndex) -> Self {
+        match k {
+            KeyOrIndex::Key(k) => Segment::Key(k.value().into()),
+            KeyOrIndex::Index(idx) => Segment::Index(*idx),
+        }
+    }
+}
+
+impl core::fmt::Display for Segment {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Segment::Key(k) => quote_key(k).fmt(f),
+            Segment::Index(idx) => write!(f, "[{idx}]"),
+        }
+    }
+}
+
+/// A path of keys and indices from a node to one of its descendants.
+///
+/// Unlike [`Keys`], a path owns only the unescaped values of the keys,
+/// so it is cheap to compare, hash and send across threads.
+///
+/// ```
+/// use taplo::dom::{Path, Segment};
+///
+/// let path: Path = r#"bin[0]."a b""#.parse().unwrap();
+///
+/// assert_eq!(
+///     path.segments(),
+///     [Segment::from("bin"), Segment::from(0), Segment::from("a b")]
+/// );
+/// assert_eq!(path.to_string(), r#"bin[0].'a b'"#);
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Path {
+    segments: Vec<Segment>,
+}
+
+impl Path {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn segments(&self) -> &[Segment] {
+        &self.segments
+    }
+
+    pub fn iter(&self) -> impl ExactSizeIterator<Item = &Segment> + DoubleEndedIterator {
+        self.segments.iter()
+    }
+
+    pub fn len(&self) -> usize {
+        self.segments.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.segments.is_empty()
+    }
+
+    pub fn last(&self) -> Option<&Segment> {
+        self.segments.last()
+    }
+
+    pub fn push(&mut self, segment: impl Into<Segment>) {
+        self.segments.push(segment.into());
+    }
+
+    pub fn pop(&mut self) -> Option<Segment> {
+        self.segments.pop()
+    }
+
+    /// Return a new path with the given segment appended.
+    pub fn join(&self, segment: impl Into<Segment>) -> Self {
+        let mut path = self.clone();
+        path.push(segment);
+        path
+    }
+
+    /// The path without its last segment, or `None` for an empty path.
+    pub fn parent(&self) -> Option<Self> {
+        let (_, parent) = self.segments.split_last()?;
+        Some(Self {
+            segments: parent.to_vec(),
+        })
+    }
+
+    pub fn common_prefix_count(&self, other: &Self) -> usize {
+        self.iter()
+            .zip(other.iter())
+            .take_while(|(a, b)| a == b)
+            .count()
+    }
+
+    /// Whether the given path is a prefix of this path.
+    ///
+    /// Every path starts with itself and the empty path.
+    pub fn starts_with(&self, prefix: &Self) -> bool {
+        self.segments.starts_with(&prefix.segments)
+    }
+
+    /// Whether this path is a prefix of the given path,
+    /// i.e. whether it addresses the node itself or one of its parents.
+    pub fn is_part_of(&self, other: &Self) -> bool {
+        other.starts_with(self)
+    }
+
+    /// The rest of the path after the given prefix.
+    pub fn strip_prefix(&self, prefix: &Self) -> Option<Self> {
+        self.segments
+            .strip_prefix(&*prefix.segments)
+            .map(|rest| Self {
+                segments: rest.to_vec(),
+            })
+    }
+}
+
+impl core::fmt::Display for Path {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for (i, segment) in self.segments.iter().enumerate() {
+            if i != 0 && matches!(segment, Segment::Key(_)) {
+                f.write_char('.')?;
+            }
+            segment.fmt(f)?;
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for Path {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(parse_segments(s, false)?
+            .into_iter()
+            .map(|segment| match segment {
+                PatternSegment::Segment(segment) => segment,
+                PatternSegment::Any | PatternSegment::AnyMany => {
+                    unreachable!("wildcards are only parsed for patterns")
+                }
+            })
+            .collect())
+    }
+}
+
+/// A pattern that matches [`Path`]s.
+///
+/// Patterns use the same syntax as paths, with two additional
+/// segments: an unquoted `*` matches exactly one key or index,
+/// and an unquoted `**` matches any number of segments, including none.
+/// Quoted keys are always literals, so `'*'` only matches a key
+/// that is a single asterisk.
+///
+/// ```
+/// use taplo::dom::{Path, PathPattern};
+///
+/// let pattern: PathPattern = "dependencies.*.version".parse().unwrap();
+///
+/// assert!(pattern.matches(&"dependencies.serde.version".parse::<Path>().unwrap()));
+/// assert!(!pattern.matches(&"dependencies.version".parse::<Path>().unwrap()));
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct PathPattern {
+    segments: Vec<PatternSegment>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+enum PatternSegment {
+    Segment(Segment),
+    /// `*`
+    Any,
+    /// `**`
+    AnyMany,
+}
+
+impl PathPattern {
+    /// Whether the pattern matches the entire path.
+    pub fn matches(&self, path: &Path) -> bool {
+        matches_segments(&self.segments, &path.segments)
+    }
+
+    /// Whether the pattern could match the given path
+    /// or any of the paths that start with it.
+    pub fn matches_prefix(&self, path: &Path) -> bool {
+        matches_prefix(&self.segments, &path.segments)
+    }
+}
+
+fn matches_segments(pattern: &[PatternSegment], path: &[Segment]) -> bool {
+    match pattern.split_first() {
+        None => path.is_empty(),
+        Some((PatternSegment::AnyMany, rest)) => {
+            (0..=path.len()).any(|skip| matches_segments(rest, &path[skip..]))
+        }
+        Some((segment, rest)) => match path.split_first() {
+            Some((first, path)) => matches_segment(segment, first) && matches_segments(rest, path),
+            None => false,
+        },
+    }
+}
+
+fn matches_prefix(pattern: &[PatternSegment], path: &[Segment]) -> bool {
+    match pattern.split_first() {
+        None => path.is_empty(),
+        Some((PatternSegment::AnyMany, _)) => true,
+        Some((segment, rest)) => match path.split_first() {
+            Some((first, path)) => matches_segment(segment, first) && matches_prefix(rest, path),
+            None => true,
+        },
+    }
+}
+
+fn matches_segment(pattern: &PatternSegment, segment: &Segment) -> bool {
+    match pattern {
+        PatternSegment::Segment(s) => s == segment,
+        PatternSegment::Any | PatternSegment::AnyMany => true,
+    }
+}
+
+impl FromStr for PathPattern {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(Self {
+            segments: parse_segments(s, true)?,
+        })
+    }
+}
+
+impl core::fmt::Display for PathPattern {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for (i, segment) in self.segments.iter().enumerate() {
+            if i != 0 && !matches!(segment, PatternSegment::Segment(Segment::Index(_))) {
+                f.write_char('.')?;
+            }
+            match segment {
+                PatternSegment::Segment(segment) => segment.fmt(f)?,
+                PatternSegment::Any => f.write_char('*')?,
+                PatternSegment::AnyMany => f.write_str("**")?,
+            }
+        }
+        Ok(())
+    }
+}
+
+impl From<Path> for PathPattern {
+    fn from(path: Path) -> Self {
+        Self {
+            segments: path
+                .segments
+                .into_iter()
+                .map(PatternSegment::Segment)
+                .collect(),
+        }
+    }
+}
+
+fn parse_segments(s: &str, wildcards: bool) -> Result<Vec<PatternSegment>, Error> {
+    let invalid = |offset: usize| Error::from(QueryError::InvalidPath { offset });
+
+    let mut segments = Vec::new();
+    let mut rest = s;
+
+    while !rest.is_empty() {
+        let offset = s.len() - rest.len();
+
+        if let Some(r) = rest.strip_prefix('[') {
+            let end = r.find(']').ok_or_else(|| invalid(offset))?;
+            let idx = r[..end].parse().map_err(|_| invalid(offset + 1))?;
+            segments.push(PatternSegment::Segment(Segment::Index(idx)));
+            rest = &r[end + 1..];
+            continue;
+        }
+
+        if offset != 0 {
+            rest = rest.strip_prefix('.').ok_or_else(|| invalid(offset))?;
+        }
+        let offset = s.len() - rest.len();
+
+        let (segment, r) = if let Some(r) = rest.strip_prefix('"') {
+            let end = closing_quote(r).ok_or_else(|| invalid(offset))?;
+            let key = unescape(&r[..end]).map_err(|err| invalid(offset + 1 + err.offset))?;
+            (PatternSegment::Segment(Segment::Key(key)), &r[end + 1..])
+        } else if let Some(r) = rest.strip_prefix('\'') {
+            let end = r.find('\'').ok_or_else(|| invalid(offset))?;
+            (
+                PatternSegment::Segment(Segment::Key(r[..end].into())),
+                &r[end + 1..],
+            )
+        } else if let Some(r) = rest.strip_prefix("**").filter(|_| wildcards) {
+            (PatternSegment::AnyMany, r)
+        } else if let Some(r) = rest.strip_prefix('*').filter(|_| wildcards) {
+            (PatternSegment::Any, r)
+        } else {
+            let end = rest
+                .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_' || c == '-'))
+                .unwrap_or(rest.len());
+            if end == 0 {
+                return Err(invalid(offset));
+            }
+            (
+                PatternSegment::Segment(Segment::Key(rest[..end].into())),
+                &rest[end..],
+            )
+        };
+
+        segments.push(segment);
+        rest = r;
+    }
+
+    Ok(segments)
+}
+
+/// The index of the first unescaped `"`.
+fn closing_quote(s: &str) -> Option<usize> {
+    let mut escaped = false;
+    for (idx, c) in s.char_indices() {
+        match c {
+            '"' if !escaped => return Some(idx),
+            '\\' => escaped = !escaped,
+            _ => escaped = false,
+        }
+    }
+    None
+}
+
+impl<S: Into<Segment>> FromIterator<S> for Path {
+    fn from_iter<T: IntoIterator<Item = S>>(iter: T) -> Self {
+        Self {
+            segments: iter.into_iter().map(Into::into).collect(),
+        }
+    }
+}
+
+impl<S: Into<Segment>> Extend<S> for Path {
+    fn extend<T: IntoIterator<Item = S>>(&mut self, iter: T) {
+        self.segments.extend(iter.into_iter().map(Into::into));
+    }
+}
+
+impl IntoIterator for Path {
+    type Item = Segment;
+    type IntoIter = std::vec::IntoIter<Segment>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.segments.into_iter()
+    }
+}
+
+impl<'p> IntoIterator for &'p Path {
+    type Item = &'p Segment;
+    type IntoIter = core::slice::Iter<'p, Segment>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.segments.iter()
+    }
+}
+
+impl From<&Keys> for Path {
+    fn from(keys: &Keys) -> Self {
+        keys.iter().map(Segment::from).collect()
+    }
+}
+
+impl Keys {
+    /// The path of the unescaped keys and indices.
+    pub fn to_path(&self) -> Path {
+        self.into()
+    }
+}
+
+impl Key {
+    /// A path with the unescaped key as its only segment.
+    pub fn to_path(&self) -> Path {
+        Path {
+            segments: vec![Segment::Key(self.value().into())],
+        }
+    }
+}
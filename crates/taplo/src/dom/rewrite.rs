@@ -1,9 +1,13 @@
 use super::{
-    node::{DomNode, Node},
-    Keys,
+    node::{ArrayKind, DomNode, Node, TableKind},
+    Keys, Path, Segment,
 };
-use crate::{dom, syntax::SyntaxKind};
-use rowan::TextRange;
+use crate::{
+    dom::{self, FromSyntax},
+    parser::{parse_key, parse_value},
+    syntax::{SyntaxElement, SyntaxKind, SyntaxNode},
+};
+use rowan::{TextRange, TextSize};
 use std::{ops::Range, sync::Arc};
 use thiserror::Error;
 
@@ -29,6 +33,7 @@ impl Rewrite {
         let patch = patch.into();
         match patch {
             Patch::RenameKeys { key, to } => {
+                check_key(&to)?;
                 let keys = key.parse::<Keys>()?;
                 let nodes = self.root.find_all_matches(keys, false)?;
 
@@ -48,6 +53,52 @@ impl Rewrite {
                     }
                 }
             }
+            Patch::AppendArrayItem { key, item } => {
+                check_value(&item)?;
+                let keys = key.parse::<Keys>()?;
+                let nodes = self.root.find_all_matches(keys, false)?;
+
+                for (_, node) in nodes {
+                    let edits = append_item_edits(&array_syntax(&node)?, &item);
+                    self.add_edits(edits)?;
+                }
+            }
+            Patch::RemoveArrayItem { key, index } => {
+                let keys = key.parse::<Keys>()?;
+                let nodes = self.root.find_all_matches(keys, false)?;
+
+                for (_, node) in nodes {
+                    let edits = remove_item_edits(&array_syntax(&node)?, SyntaxKind::VALUE, index)?;
+                    self.add_edits(edits)?;
+                }
+            }
+            Patch::SetValue { path, value } => {
+                check_value(&value)?;
+                let path = path.parse::<Path>()?;
+                let edits = set_value_edits(&self.root, &path, &value)?;
+                self.add_edits(edits)?;
+            }
+            Patch::Remove { path } => {
+                let nodes = self.root.find_all(&path)?;
+
+                if nodes.is_empty() {
+                    return Err(Error::NotFound);
+                }
+
+                let mut edits = Vec::new();
+                for (path, node) in nodes {
+                    if path.is_empty() {
+                        return Err(Error::ExpectedValue);
+                    }
+                    remove_edits(&node, &mut edits)?;
+                }
+
+                // Nested nodes are removed together with their parents.
+                edits.sort_by_key(|(range, _)| (range.start(), std::cmp::Reverse(range.end())));
+                edits.dedup_by(|inner, outer| outer.0.contains_range(inner.0));
+
+                self.add_edits(edits)?;
+            }
         }
 
         self.patches
@@ -60,8 +111,28 @@ impl Rewrite {
         &self.patches
     }
 
+    fn add_edits(&mut self, edits: Vec<(TextRange, Arc<str>)>) -> Result<(), Error> {
+        for (range, _) in &edits {
+            self.check_overlap(*range)?;
+        }
+
+        self.patches
+            .extend(edits.into_iter().map(|(range, to)| PendingPatch {
+                range,
+                kind: PendingPatchKind::Replace(to),
+            }));
+
+        Ok(())
+    }
+
     fn check_overlap(&self, range: TextRange) -> Result<(), Error> {
         for patch in self.patches() {
+            // The result of the insertions would depend on each other,
+            // e.g. a separator would be missing between two appended array items.
+            if patch.range.is_empty() && patch.range == range {
+                return Err(Error::SameInsertionPoint);
+            }
+
             if patch.range.contains_range(range)
                 || range.contains_range(patch.range)
                 || patch.range.contains(range.start())
@@ -82,6 +153,46 @@ impl Rewrite {
             to: to.into(),
         })
     }
+
+    /// Append an item to the arrays matching the given key.
+    ///
+    /// The item text is inserted as-is, while the separating commas,
+    /// newlines and indentation follow the existing layout of the array.
+    ///
+    /// Only one item can be appended to an array in the same rewrite.
+    pub fn append_array_item(&mut self, key: &str, item: &str) -> Result<&mut Self, Error> {
+        self.add(Patch::AppendArrayItem {
+            key: key.into(),
+            item: item.into(),
+        })
+    }
+
+    /// Remove the item at the given index from the arrays matching the given key.
+    ///
+    /// The neighbouring comma and the line of the item (if it had its own line)
+    /// are removed as well.
+    pub fn remove_array_item(&mut self, key: &str, index: usize) -> Result<&mut Self, Error> {
+        self.add(Patch::RemoveArrayItem {
+            key: key.into(),
+            index,
+        })
+    }
+
+    /// Set the value at the given [`Path`] to the given TOML value.
+    ///
+    /// An existing value is replaced, otherwise a new entry is added
+    /// to the closest existing table, using dotted keys if needed.
+    pub fn set_value(&mut self, path: &str, value: &str) -> Result<&mut Self, Error> {
+        self.add(Patch::SetValue {
+            path: path.into(),
+            value: value.into(),
+        })
+    }
+
+    /// Remove all the entries, tables and array items matching the given [`PathPattern`].
+    pub fn remove(&mut self, path: &str) -> Result<&mut Self, Error> {
+        self.add(Patch::Remove { path: path.into() })
+    }
 }
 
 impl core::fmt::Display for Rewrite {
@@ -103,6 +214,10 @@ impl core::fmt::Display for Rewrite {
 #[derive(Debug)]
 pub enum Patch {
     RenameKeys { key: Arc<str>, to: Arc<str> },
+    AppendArrayItem { key: Arc<str>, item: Arc<str> },
+    RemoveArrayItem { key: Arc<str>, index: usize },
+    SetValue { path: Arc<str>, value: Arc<str> },
+    Remove { path: Arc<str> },
 }
 
 #[derive(Debug)]
@@ -123,16 +238,566 @@ pub enum Error {
     RootNodeExpected,
     #[error("expected table")]
     ExpectedTable,
+    #[error("expected array")]
+    ExpectedArray,
+    #[error("array index {0} is out of bounds")]
+    IndexOutOfBounds(usize),
+    #[error("array indices are not allowed in the path")]
+    UnexpectedIndex,
+    #[error("expected a value")]
+    ExpectedValue,
+    #[error("invalid value: {0}")]
+    InvalidValue(String),
+    #[error("invalid key: {0}")]
+    InvalidKey(String),
+    #[error("nothing was found at the given path")]
+    NotFound,
     #[error("new patches would overlap with existing ones")]
     Overlap,
+    #[error("new patches would insert text at the same position as existing ones")]
+    SameInsertionPoint,
     #[error("{0}")]
     Dom(#[from] dom::error::Error),
 }
 
+fn array_syntax(node: &Node) -> Result<SyntaxNode, Error> {
+    match node {
+        Node::Array(arr) if arr.kind() == ArrayKind::Inline => arr
+            .syntax()
+            .and_then(SyntaxElement::as_node)
+            .cloned()
+            .ok_or(Error::ExpectedArray),
+        _ => Err(Error::ExpectedArray),
+    }
+}
+
+fn append_item_edits(array: &SyntaxNode, item: &str) -> Vec<(TextRange, Arc<str>)> {
+    let children: Vec<SyntaxElement> = array.children_with_tokens().collect();
+
+    let last_value = match children.iter().rposition(|c| c.kind() == SyntaxKind::VALUE) {
+        Some(idx) => idx,
+        None => {
+            // The array is empty, the item goes right after the opening bracket.
+            let start = children
+                .iter()
+                .find(|c| c.kind() == SyntaxKind::BRACKET_START)
+                .map_or(array.text_range().start(), |c| c.text_range().end());
+
+            return vec![(TextRange::empty(start), item.into())];
+        }
+    };
+
+    let value_end = value_range(&children[last_value]).end();
+
+    let trailing_comma = children[last_value + 1..]
+        .iter()
+        .find(|c| c.kind() == SyntaxKind::COMMA)
+        .map(|c| c.text_range().end());
+
+    if !children.iter().any(|c| c.kind() == SyntaxKind::NEWLINE) {
+        return match trailing_comma {
+            Some(comma_end) => vec![(TextRange::empty(comma_end), format!(" {item},").into())],
+            None => vec![(TextRange::empty(value_end), format!(", {item}").into())],
+        };
+    }
+
+    let newline = if array.text().contains_char('\r') {
+        "\r\n"
+    } else {
+        "\n"
+    };
+
+    let indent = line_indent(&children, last_value)
+        .or_else(|| {
+            children
+                .iter()
+                .enumerate()
+                .filter(|(_, c)| c.kind() == SyntaxKind::VALUE)
+                .find_map(|(idx, _)| line_indent(&children, idx))
+        })
+        .unwrap_or_else(|| "  ".into());
+
+    // Insert the item on a new line after any comments
+    // that follow the last item.
+    let line_end = children[last_value + 1..]
+        .iter()
+        .take_while(|c| c.kind() != SyntaxKind::BRACKET_END)
+        .find(|c| c.kind() == SyntaxKind::NEWLINE)
+        .map_or_else(
+            || trailing_comma.unwrap_or(value_end),
+            |c| c.text_range().start(),
+        );
+
+    match trailing_comma {
+        Some(_) => vec![(
+            TextRange::empty(line_end),
+            format!("{newline}{indent}{item},").into(),
+        )],
+        None if line_end == value_end => vec![(
+            TextRange::empty(value_end),
+            format!(",{newline}{indent}{item}").into(),
+        )],
+        None => vec![
+            (TextRange::empty(value_end), ",".into()),
+            (
+                TextRange::empty(line_end),
+                format!("{newline}{indent}{item}").into(),
+            ),
+        ],
+    }
+}
+
+/// Edits that remove the item at the given index from an array,
+/// or the entry at the given index from an inline table
+/// if the item kind is [`SyntaxKind::ENTRY`].
+fn remove_item_edits(
+    array: &SyntaxNode,
+    item_kind: SyntaxKind,
+    index: usize,
+) -> Result<Vec<(TextRange, Arc<str>)>, Error> {
+    let children: Vec<SyntaxElement> = array.children_with_tokens().collect();
+
+    let values: Vec<usize> = children
+        .iter()
+        .enumerate()
+        .filter(|(_, c)| c.kind() == item_kind)
+        .map(|(idx, _)| idx)
+        .collect();
+
+    let value_idx = *values.get(index).ok_or(Error::IndexOutOfBounds(index))?;
+    let value_range = value_range(&children[value_idx]);
+    let is_last = index + 1 == values.len();
+
+    let comma_before = index.checked_sub(1).and_then(|prev| {
+        children[values[prev]..value_idx]
+            .iter()
+            .find(|c| c.kind() == SyntaxKind::COMMA)
+            .map(SyntaxElement::text_range)
+    });
+
+    let comma_after = children
+        [value_idx + 1..values.get(index + 1).copied().unwrap_or(children.len())]
+        .iter()
+        .find(|c| c.kind() == SyntaxKind::COMMA)
+        .map(SyntaxElement::text_range);
+
+    // The item has a line on its own, remove the entire line.
+    if let Some(line) = item_line_range(&children, value_idx) {
+        let mut edits = vec![(line, Arc::from(""))];
+
+        // Do not leave a trailing comma behind if there was none.
+        if let (true, None, Some(comma_before)) = (is_last, comma_after, comma_before) {
+            if comma_before.end() <= line.start() {
+                edits.push((comma_before, Arc::from("")));
+            }
+        }
+
+        return Ok(edits);
+    }
+
+    let range = match (comma_before, comma_after) {
+        (_, Some(comma_after)) if !is_last => {
+            let end = children[value_idx + 1..]
+                .iter()
+                .skip_while(|c| c.text_range().end() <= comma_after.start())
+                .skip(1)
+                .take_while(|c| c.kind() == SyntaxKind::WHITESPACE)
+                .last()
+                .map_or(comma_after.end(), |c| c.text_range().end());
+
+            TextRange::new(value_range.start(), end)
+        }
+        (Some(comma_before), Some(comma_after)) => {
+            TextRange::new(comma_before.end(), comma_after.end())
+        }
+        (None, Some(comma_after)) => TextRange::new(value_range.start(), comma_after.end()),
+        (Some(comma_before), None) => TextRange::new(comma_before.start(), value_range.end()),
+        (None, None) => value_range,
+    };
+
+    Ok(vec![(range, Arc::from(""))])
+}
+
+/// The range of the value without the trailing whitespace and comments
+/// that the parser attaches to value nodes.
+fn value_range(value: &SyntaxElement) -> TextRange {
+    match value {
+        SyntaxElement::Node(n) => n
+            .descendants_with_tokens()
+            .filter_map(SyntaxElement::into_token)
+            .filter(|t| {
+                !matches!(
+                    t.kind(),
+                    SyntaxKind::WHITESPACE | SyntaxKind::NEWLINE | SyntaxKind::COMMENT
+                )
+            })
+            .map(|t| t.text_range())
+            .reduce(|a, b| a.cover(b))
+            .unwrap_or_else(|| n.text_range()),
+        SyntaxElement::Token(t) => t.text_range(),
+    }
+}
+
+/// The indentation of the item at the given index,
+/// if the item is the first one on its line.
+fn line_indent(children: &[SyntaxElement], idx: usize) -> Option<String> {
+    match idx.checked_sub(1).map(|i| &children[i]) {
+        Some(c) if c.kind() == SyntaxKind::NEWLINE => Some(String::new()),
+        Some(c) if c.kind() == SyntaxKind::WHITESPACE => idx
+            .checked_sub(2)
+            .filter(|i| children[*i].kind() == SyntaxKind::NEWLINE)
+            .map(|_| c.to_string()),
+        _ => None,
+    }
+}
+
+/// The range of the line of the item at the given index (including the newline),
+/// if nothing else but the item, its comma and a comment is on the line.
+fn item_line_range(children: &[SyntaxElement], idx: usize) -> Option<TextRange> {
+    let start = children[..idx]
+        .iter()
+        .rev()
+        .find(|c| c.kind() != SyntaxKind::WHITESPACE)
+        .filter(|c| c.kind() == SyntaxKind::NEWLINE)?
+        .text_range()
+        .end();
+
+    let newline = children[idx + 1..]
+        .iter()
+        .find(|c| {
+            !matches!(
+                c.kind(),
+                SyntaxKind::WHITESPACE | SyntaxKind::COMMA | SyntaxKind::COMMENT
+            )
+        })
+        .filter(|c| c.kind() == SyntaxKind::NEWLINE)?;
+
+    // Only remove a single newline, keep the rest of the blank lines.
+    let newline_len = if newline.to_string().starts_with("\r\n") {
+        2
+    } else {
+        1
+    };
+
+    Some(TextRange::new(
+        start,
+        newline.text_range().start() + TextSize::from(newline_len),
+    ))
+}
+
+/// Check that the text is a single valid TOML value.
+fn check_value(value: &str) -> Result<(), Error> {
+    let p = parse_value(value);
+
+    if let Some(err) = p.errors.first() {
+        return Err(Error::InvalidValue(err.message.clone()));
+    }
+
+    if let Err(mut errors) = p.into_dom().validate() {
+        return Err(Error::InvalidValue(
+            errors.next().map(|e| e.to_string()).unwrap_or_default(),
+        ));
+    }
+
+    Ok(())
+}
+
+/// Check that the text is a single valid TOML key.
+fn check_key(key: &str) -> Result<(), Error> {
+    let p = parse_key(key);
+
+    if let Some(err) = p.errors.first() {
+        return Err(Error::InvalidKey(err.message.clone()));
+    }
+
+    if Keys::from_syntax(p.into_syntax().into()).len() != 1 {
+        return Err(Error::InvalidKey("expected a single key".into()));
+    }
+
+    Ok(())
+}
+
+/// The node at the given path, array indices are only
+/// allowed for arrays and keys only for tables.
+fn node_at(root: &Node, path: &[Segment]) -> Option<Node> {
+    let mut node = root.clone();
+
+    for segment in path {
+        node = match (&node, segment) {
+            (Node::Table(t), Segment::Key(k)) => t.entries().read().get(k)?.clone(),
+            (Node::Array(arr), Segment::Index(idx)) => arr.items().read().get(*idx)?.clone(),
+            _ => return None,
+        };
+    }
+
+    Some(node)
+}
+
+fn set_value_edits(
+    root: &Node,
+    path: &Path,
+    value: &str,
+) -> Result<Vec<(TextRange, Arc<str>)>, Error> {
+    let segments = path.segments();
+
+    if let Some(node) = node_at(root, segments) {
+        return match &node {
+            Node::Table(t) if t.kind() != TableKind::Inline => Err(Error::ExpectedValue),
+            Node::Array(arr) if arr.kind() == ArrayKind::Tables => Err(Error::ExpectedValue),
+            _ => {
+                let syntax = node.syntax().ok_or(Error::ExpectedValue)?;
+                Ok(vec![(value_range(syntax), value.into())])
+            }
+        };
+    }
+
+    // The closest existing ancestor, pseudo-tables are skipped
+    // so that the new entry is added with dotted keys next to them.
+    let mut parent_len = (0..segments.len())
+        .rev()
+        .find(|len| node_at(root, &segments[..*len]).is_some())
+        .unwrap_or_default();
+
+    let mut parent = node_at(root, &segments[..parent_len]).ok_or(Error::NotFound)?;
+
+    while parent_len > 0
+        && parent
+            .as_table()
+            .is_some_and(|t| t.kind() == TableKind::Pseudo)
+    {
+        parent_len -= 1;
+        parent = node_at(root, &segments[..parent_len]).ok_or(Error::NotFound)?;
+    }
+
+    let rest = &segments[parent_len..];
+
+    if let Some(idx) = rest.iter().find_map(Segment::as_index) {
+        return Err(Error::IndexOutOfBounds(idx));
+    }
+
+    let Node::Table(table) = &parent else {
+        return Err(Error::ExpectedTable);
+    };
+
+    let root_syntax = root
+        .syntax()
+        .and_then(SyntaxElement::as_node)
+        .ok_or(Error::RootNodeExpected)?;
+    let newline = if root_syntax.text().contains_char('\r') {
+        "\r\n"
+    } else {
+        "\n"
+    };
+
+    let entry = format!("{} = {value}", rest.iter().cloned().collect::<Path>());
+
+    let syntax = table.syntax().ok_or(Error::ExpectedTable)?;
+
+    match syntax.kind() {
+        SyntaxKind::INLINE_TABLE => {
+            let inline_table = syntax.as_node().ok_or(Error::ExpectedTable)?;
+            let children: Vec<SyntaxElement> = inline_table.children_with_tokens().collect();
+
+            if let Some(last_entry) = children
+                .iter()
+                .rev()
+                .find(|c| c.kind() == SyntaxKind::ENTRY)
+            {
+                return Ok(vec![(
+                    TextRange::empty(value_range(last_entry).end()),
+                    format!(", {entry}").into(),
+                )]);
+            }
+
+            let start = children
+                .iter()
+                .find(|c| c.kind() == SyntaxKind::BRACE_START)
+                .ok_or(Error::ExpectedTable)?
+                .text_range()
+                .end();
+            let end = children
+                .iter()
+                .find(|c| c.kind() == SyntaxKind::BRACE_END)
+                .map_or(start, |c| c.text_range().start());
+
+            Ok(vec![(
+                TextRange::new(start, end),
+                format!(" {entry} ").into(),
+            )])
+        }
+        SyntaxKind::ROOT => {
+            let first_header = root_syntax.children().find(|c| {
+                matches!(
+                    c.kind(),
+                    SyntaxKind::TABLE_HEADER | SyntaxKind::TABLE_ARRAY_HEADER
+                )
+            });
+
+            let last_entry = root_syntax
+                .children()
+                .take_while(|c| Some(c) != first_header.as_ref())
+                .filter(|c| c.kind() == SyntaxKind::ENTRY)
+                .last();
+
+            Ok(vec![match (last_entry, first_header) {
+                (Some(last_entry), _) => (
+                    TextRange::empty(last_entry.text_range().end()),
+                    format!("{newline}{entry}").into(),
+                ),
+                (None, Some(first_header)) => (
+                    TextRange::empty(first_header.text_range().start()),
+                    format!("{entry}{newline}{newline}").into(),
+                ),
+                (None, None) => {
+                    let end = root_syntax.text_range().end();
+                    let text = root_syntax.to_string();
+                    let separator = if text.is_empty() || text.ends_with('\n') {
+                        ""
+                    } else {
+                        newline
+                    };
+                    (
+                        TextRange::empty(end),
+                        format!("{separator}{entry}{newline}").into(),
+                    )
+                }
+            }])
+        }
+        SyntaxKind::TABLE_HEADER | SyntaxKind::TABLE_ARRAY_HEADER => {
+            let header = syntax.as_node().ok_or(Error::ExpectedTable)?;
+            let end = section_entries(header)
+                .last()
+                .map_or(header.text_range().end(), |e| e.text_range().end());
+
+            Ok(vec![(
+                TextRange::empty(end),
+                format!("{newline}{entry}").into(),
+            )])
+        }
+        _ => Err(Error::ExpectedTable),
+    }
+}
+
+/// The top-level entries after a table header up to the next header.
+fn section_entries(header: &SyntaxNode) -> impl Iterator<Item = SyntaxNode> {
+    header
+        .siblings(rowan::Direction::Next)
+        .skip(1)
+        .take_while(|c| {
+            !matches!(
+                c.kind(),
+                SyntaxKind::TABLE_HEADER | SyntaxKind::TABLE_ARRAY_HEADER
+            )
+        })
+        .filter(|c| c.kind() == SyntaxKind::ENTRY)
+}
+
+fn remove_edits(node: &Node, edits: &mut Vec<(TextRange, Arc<str>)>) -> Result<(), Error> {
+    match node {
+        Node::Table(t) if t.kind() == TableKind::Pseudo => {
+            for (_, entry) in t.entries().read().iter() {
+                remove_edits(entry, edits)?;
+            }
+            Ok(())
+        }
+        Node::Table(t) if t.kind() == TableKind::Regular => {
+            let header = t
+                .syntax()
+                .and_then(SyntaxElement::as_node)
+                .filter(|s| {
+                    matches!(
+                        s.kind(),
+                        SyntaxKind::TABLE_HEADER | SyntaxKind::TABLE_ARRAY_HEADER
+                    )
+                })
+                .ok_or(Error::ExpectedTable)?;
+
+            let last = section_entries(header)
+                .last()
+                .unwrap_or_else(|| header.clone());
+            edits.push((
+                root_lines_range(&header.clone().into(), &last.into()),
+                "".into(),
+            ));
+
+            // Sub-tables with their own headers.
+            for (_, entry) in t.entries().read().iter() {
+                remove_edits(entry, edits)?;
+            }
+            Ok(())
+        }
+        Node::Array(arr) if arr.kind() == ArrayKind::Tables => {
+            for item in arr.items().read().iter() {
+                remove_edits(item, edits)?;
+            }
+            Ok(())
+        }
+        _ => {
+            let syntax = node.syntax().ok_or(Error::ExpectedValue)?;
+            let value = syntax.parent().ok_or(Error::ExpectedValue)?;
+            let parent = value.parent().ok_or(Error::ExpectedValue)?;
+
+            match parent.kind() {
+                // An array item.
+                SyntaxKind::ARRAY => {
+                    let index = parent
+                        .children()
+                        .filter(|c| c.kind() == SyntaxKind::VALUE)
+                        .position(|c| c == value)
+                        .ok_or(Error::ExpectedValue)?;
+                    edits.extend(remove_item_edits(&parent, SyntaxKind::VALUE, index)?);
+                }
+                SyntaxKind::ENTRY => {
+                    let container = parent.parent().ok_or(Error::ExpectedValue)?;
+
+                    if container.kind() == SyntaxKind::INLINE_TABLE {
+                        let index = container
+                            .children()
+                            .filter(|c| c.kind() == SyntaxKind::ENTRY)
+                            .position(|c| c == parent)
+                            .ok_or(Error::ExpectedValue)?;
+                        edits.extend(remove_item_edits(&container, SyntaxKind::ENTRY, index)?);
+                    } else {
+                        let entry = SyntaxElement::from(parent);
+                        edits.push((root_lines_range(&entry, &entry), "".into()));
+                    }
+                }
+                _ => return Err(Error::ExpectedValue),
+            }
+
+            Ok(())
+        }
+    }
+}
+
+/// The range of the lines of the top-level elements from `first` to `last`,
+/// including the indentation before and a single newline after them.
+fn root_lines_range(first: &SyntaxElement, last: &SyntaxElement) -> TextRange {
+    let start = first
+        .prev_sibling_or_token()
+        .filter(|p| p.kind() == SyntaxKind::WHITESPACE)
+        .map_or(first.text_range().start(), |ws| ws.text_range().start());
+
+    let end = last
+        .next_sibling_or_token()
+        .filter(|n| n.kind() == SyntaxKind::NEWLINE)
+        .map_or(last.text_range().end(), |newline| {
+            let newline_len = if newline.to_string().starts_with("\r\n") {
+                2
+            } else {
+                1
+            };
+            newline.text_range().start() + TextSize::from(newline_len)
+        });
+
+    TextRange::new(start, end)
+}
+
 #[cfg(test)]
 mod tests {
-    use super::Rewrite;
+    use super::{Error, Rewrite};
     use crate::parser::parse;
+    use serde_json::json;
 
     #[test]
     fn rename_keys() {
@@ -191,6 +856,290 @@ mod tests {
 
         assert_eq!(expected_toml, patches.to_string());
     }
+
+    #[test]
+    fn append_array_item() {
+        let cases = [
+            ("a = []\n", "1", "a = [1]\n", json!([1])),
+            ("a = [1]\n", "2", "a = [1, 2]\n", json!([1, 2])),
+            ("a = [1, 2,]\n", "3", "a = [1, 2, 3,]\n", json!([1, 2, 3])),
+            (
+                "a = [\n  1,\n  2,\n]\n",
+                "3",
+                "a = [\n  1,\n  2,\n  3,\n]\n",
+                json!([1, 2, 3]),
+            ),
+            (
+                "a = [\n    1,\n    2 # two\n]\n",
+                "3",
+                "a = [\n    1,\n    2, # two\n    3\n]\n",
+                json!([1, 2, 3]),
+            ),
+            (
+                "a = [\n  \"x\"\n]\n",
+                "\"y\"",
+                "a = [\n  \"x\",\n  \"y\"\n]\n",
+                json!(["x", "y"]),
+            ),
+        ];
+
+        for (toml, item, expected_toml, expected_value) in cases {
+            let mut patches = Rewrite::new(parse(toml).into_dom()).unwrap();
+            patches.append_array_item("a", item).unwrap();
+
+            let new_toml = patches.to_string();
+            assert_eq!(expected_toml, new_toml);
+
+            let parse = parse(&new_toml);
+            assert!(parse.errors.is_empty());
+            assert_eq!(
+                serde_json::to_value(parse.into_dom()).unwrap(),
+                json!({ "a": expected_value })
+            );
+        }
+    }
+
+    #[test]
+    fn remove_array_item() {
+        let cases = [
+            ("a = [1]\n", 0, "a = []\n", json!([])),
+            ("a = [1,]\n", 0, "a = []\n", json!([])),
+            ("a = [1, 2, 3]\n", 0, "a = [2, 3]\n", json!([2, 3])),
+            ("a = [1, 2, 3]\n", 1, "a = [1, 3]\n", json!([1, 3])),
+            ("a = [1, 2, 3]\n", 2, "a = [1, 2]\n", json!([1, 2])),
+            ("a = [1, 2, 3,]\n", 2, "a = [1, 2,]\n", json!([1, 2])),
+            (
+                "a = [\n  1,\n  2, # two\n  3,\n]\n",
+                1,
+                "a = [\n  1,\n  3,\n]\n",
+                json!([1, 3]),
+            ),
+            (
+                "a = [\n  1,\n  2,\n  3\n]\n",
+                2,
+                "a = [\n  1,\n  2\n]\n",
+                json!([1, 2]),
+            ),
+            ("a = [\n  1\n]\n", 0, "a = [\n]\n", json!([])),
+        ];
+
+        for (toml, index, expected_toml, expected_value) in cases {
+            let mut patches = Rewrite::new(parse(toml).into_dom()).unwrap();
+            patches.remove_array_item("a", index).unwrap();
+
+            let new_toml = patches.to_string();
+            assert_eq!(expected_toml, new_toml);
+
+            let parse = parse(&new_toml);
+            assert!(parse.errors.is_empty());
+            assert_eq!(
+                serde_json::to_value(parse.into_dom()).unwrap(),
+                json!({ "a": expected_value })
+            );
+        }
+    }
+
+    #[test]
+    fn set_value() {
+        let cases = [
+            ("a = 1\n", "a", "2", "a = 2\n"),
+            ("a = [1, 2] # c\n", "a[1]", "'x'", "a = [1, 'x'] # c\n"),
+            ("a = { b = 1 }\n", "a.b", "true", "a = { b = true }\n"),
+            ("a = { b = 1 }\n", "a.c", "2", "a = { b = 1, c = 2 }\n"),
+            ("a = {}\n", "a.c", "2", "a = { c = 2 }\n"),
+            ("a = 1 # c\n", "b", "2", "a = 1 # c\nb = 2\n"),
+            ("a.b = 1\n", "a.c", "2", "a.b = 1\na.c = 2\n"),
+            ("a = 1", "b.'c d'", "2", "a = 1\nb.'c d' = 2"),
+            ("", "a", "1", "a = 1\n"),
+            ("# comment\n", "a", "1", "# comment\na = 1\n"),
+            ("[t]\n", "b", "2", "b = 2\n\n[t]\n"),
+            (
+                "a = 1\n\n[t]\nx = 1\n\n[u]\n",
+                "t.y",
+                "2",
+                "a = 1\n\n[t]\nx = 1\ny = 2\n\n[u]\n",
+            ),
+            ("[t]\n[u]\n", "t.y.z", "2", "[t]\ny.z = 2\n[u]\n"),
+            (
+                "[[t]]\n[[t]]\nx = 1\n",
+                "t[1].y",
+                "2",
+                "[[t]]\n[[t]]\nx = 1\ny = 2\n",
+            ),
+        ];
+
+        for (toml, path, value, expected_toml) in cases {
+            let mut patches = Rewrite::new(parse(toml).into_dom()).unwrap();
+            patches.set_value(path, value).unwrap();
+
+            let new_toml = patches.to_string();
+            assert_eq!(expected_toml, new_toml, "{path} = {value}");
+
+            let dom = parse(&new_toml).into_dom();
+            assert!(dom.validate().is_ok(), "{new_toml}");
+        }
+    }
+
+    #[test]
+    fn set_value_errors() {
+        let root = parse("a = [1]\n[t]\n[[arr]]\n").into_dom();
+        let mut patches = Rewrite::new(root).unwrap();
+
+        assert!(matches!(
+            patches.set_value("b", "1 2"),
+            Err(Error::InvalidValue(_))
+        ));
+        assert!(matches!(
+            patches.set_value("b", "1\nc = 2"),
+            Err(Error::InvalidValue(_))
+        ));
+        assert!(matches!(
+            patches.set_value("t", "1"),
+            Err(Error::ExpectedValue)
+        ));
+        assert!(matches!(
+            patches.set_value("arr", "1"),
+            Err(Error::ExpectedValue)
+        ));
+        assert!(matches!(
+            patches.set_value("a[3]", "1"),
+            Err(Error::IndexOutOfBounds(3))
+        ));
+        assert!(matches!(
+            patches.set_value("a.b", "1"),
+            Err(Error::ExpectedTable)
+        ));
+        assert!(patches.patches().is_empty());
+    }
+
+    #[test]
+    fn remove() {
+        let cases = [
+            ("a = 1\nb = 2\n", "a", "b = 2\n"),
+            ("a = 1\n  b = 2 # c\nc = 3\n", "b", "a = 1\nc = 3\n"),
+            ("a = [1, 2, 3]\n", "a[1]", "a = [1, 3]\n"),
+            ("a = { b = 1, c = 2 }\n", "a.b", "a = { c = 2 }\n"),
+            ("a = { b = 1, c = 2 }\n", "a.c", "a = { b = 1 }\n"),
+            ("a.b = 1\nx = 0\na.c = 2\n", "a", "x = 0\n"),
+            (
+                "x = 0\n[t]\na = 1\n\n[t.sub]\nb = 2\n[u]\nc = 3\n",
+                "t",
+                "x = 0\n\n[u]\nc = 3\n",
+            ),
+            ("[[t]]\na = 1\n[[t]]\na = 2\n[u]\n", "t", "[u]\n"),
+            ("[[t]]\na = 1\n[[t]]\na = 2\n", "t.*.a", "[[t]]\n[[t]]\n"),
+            (
+                "[dependencies]\na = { version = '1' }\nb = '2'\n",
+                "dependencies.*",
+                "[dependencies]\n",
+            ),
+        ];
+
+        for (toml, path, expected_toml) in cases {
+            let mut patches = Rewrite::new(parse(toml).into_dom()).unwrap();
+            patches.remove(path).unwrap();
+
+            let new_toml = patches.to_string();
+            assert_eq!(expected_toml, new_toml, "{path}");
+            assert!(parse(&new_toml).errors.is_empty());
+        }
+
+        let mut patches = Rewrite::new(parse("a = 1\n").into_dom()).unwrap();
+        assert!(matches!(patches.remove("b"), Err(Error::NotFound)));
+    }
+
+    #[test]
+    fn chained_patches() {
+        let mut toml = String::from(
+            r#"[package]
+name = "taplo"
+version = "0.1.0"
+
+[dependencies]
+serde = "1"
+toml = "0.5"
+"#,
+        );
+
+        fn apply(
+            toml: &str,
+            patch: impl FnOnce(&mut Rewrite) -> Result<&mut Rewrite, Error>,
+        ) -> String {
+            let mut patches = Rewrite::new(parse(toml).into_dom()).unwrap();
+            patch(&mut patches).unwrap();
+            patches.to_string()
+        }
+
+        toml = apply(&toml, |r| r.set_value("package.edition", "\"2021\""));
+        toml = apply(&toml, |r| r.rename_keys("dependencies", "dev-dependencies"));
+        toml = apply(&toml, |r| r.remove("dev-dependencies.toml"));
+        toml = apply(&toml, |r| {
+            r.set_value("dev-dependencies.serde", "{ version = \"1\" }")
+        });
+
+        assert_eq!(
+            toml,
+            r#"[package]
+name = "taplo"
+version = "0.1.0"
+edition = "2021"
+
+[dev-dependencies]
+serde = { version = "1" }
+"#
+        );
+    }
+
+    #[test]
+    fn same_insertion_point() {
+        let root = parse("a = [1]\nb = []\n").into_dom();
+
+        let mut patches = Rewrite::new(root).unwrap();
+        patches.append_array_item("a", "2").unwrap();
+        patches.append_array_item("b", "1").unwrap();
+        assert!(matches!(
+            patches.append_array_item("a", "3"),
+            Err(Error::SameInsertionPoint)
+        ));
+        assert!(matches!(
+            patches.append_array_item("b", "2"),
+            Err(Error::SameInsertionPoint)
+        ));
+
+        // The failed patches are not applied.
+        assert_eq!(patches.to_string(), "a = [1, 2]\nb = [1]\n");
+    }
+
+    #[test]
+    fn array_item_errors() {
+        let root = parse("a = [1]\nb = 2\n[[c]]\n").into_dom();
+
+        let mut patches = Rewrite::new(root).unwrap();
+        assert!(matches!(
+            patches.remove_array_item("a", 1),
+            Err(Error::IndexOutOfBounds(1))
+        ));
+        assert!(matches!(
+            patches.append_array_item("b", "1"),
+            Err(Error::ExpectedArray)
+        ));
+        assert!(matches!(
+            patches.append_array_item("c", "1"),
+            Err(Error::ExpectedArray)
+        ));
+        assert!(matches!(
+            patches.append_array_item("a", "1,"),
+            Err(Error::InvalidValue(_))
+        ));
+        assert!(matches!(
+            patches.rename_keys("b", "c d"),
+            Err(Error::InvalidKey(_))
+        ));
+        assert!(matches!(
+            patches.rename_keys("b", "c.d"),
+            Err(Error::InvalidKey(_))
+        ));
+    }
 }
 
 fn std_range(range: TextRange) -> Range<usize> {
@@ -1,10 +1,13 @@
-use super::node::{
-    ArrayInner, ArrayKind, BoolInner, FloatInner, IntegerInner, IntegerValue, Node, StrInner,
-    TableInner,
+use super::{
+    node::{
+        ArrayInner, ArrayKind, BoolInner, FloatInner, IntegerInner, IntegerValue, Node, StrInner,
+        TableInner,
+    },
+    JsonOptions, Path, Segment,
 };
-use crate::dom::node::Key;
+use crate::{dom::node::Key, formatter};
 use serde::{
-    de::Visitor,
+    de::{DeserializeSeed, Visitor},
     ser::{Error, SerializeMap, SerializeSeq},
     Deserialize, Serialize, Serializer,
 };
@@ -231,12 +234,26 @@ impl<'de> Visitor<'de> for TomlVisitor {
             header: Default::default(),
             kind: super::node::TableKind::Regular,
             entries: Default::default(),
+            indexed: Default::default(),
         };
 
         table.entries.update(|entries| loop {
             match map.next_entry::<String, Node>() {
                 Ok(Some((key, node))) => {
-                    entries.add(Key::new(key), node);
+                    let key = Key::new(key);
+
+                    match entries.lookup.get_key_value(&key) {
+                        Some((existing_key, _)) => {
+                            table.errors.update(|errors| {
+                                errors.push(crate::dom::Error::ConflictingKeys {
+                                    key: key.clone(),
+                                    other: existing_key.clone(),
+                                })
+                            });
+                            entries.add_conflicting(key, node);
+                        }
+                        None => entries.add(key, node),
+                    }
                 }
                 Ok(None) => break,
                 Err(error) => {
@@ -268,3 +285,200 @@ impl<'de> Deserialize<'de> for Node {
         de.deserialize_any(TomlVisitor::default())
     }
 }
+
+/// Converts JSON text to a TOML document that is formatted with the given options,
+/// see [`Node::from_json_str`].
+///
+/// The JSON value must be an object.
+pub fn json_to_toml(
+    json: &str,
+    json_options: JsonOptions,
+    options: formatter::Options,
+) -> Result<String, serde_json::Error> {
+    Node::from_json_str(json, json_options)?
+        .to_toml_document(options)
+        .map_err(serde::de::Error::custom)
+}
+
+impl Node {
+    /// Parses a node from JSON text.
+    ///
+    /// Unlike deserializing a node, this fails on the first value that can't be
+    /// converted instead of skipping it, and the error contains the path of the value.
+    /// `null` values are errors unless [`JsonOptions::skip_nulls`] is set, and so are
+    /// integers that are too large for TOML. The order of the keys of objects is kept.
+    pub fn from_json_str(json: &str, options: JsonOptions) -> Result<Node, serde_json::Error> {
+        let mut de = serde_json::Deserializer::from_str(json);
+
+        let node = JsonSeed {
+            options,
+            path: Path::new(),
+        }
+        .deserialize(&mut de)?;
+
+        de.end()?;
+
+        node.ok_or_else(|| serde::de::Error::custom("the JSON value is null"))
+    }
+}
+
+/// Deserializes JSON values into nodes, `None` is a skipped `null`.
+struct JsonSeed {
+    options: JsonOptions,
+    path: Path,
+}
+
+impl JsonSeed {
+    fn child(&self, segment: impl Into<Segment>) -> Self {
+        Self {
+            options: self.options,
+            path: self.path.join(segment),
+        }
+    }
+
+    fn describe_path(&self) -> String {
+        if self.path.is_empty() {
+            "the root".into()
+        } else {
+            format!("`{}`", self.path)
+        }
+    }
+}
+
+impl<'de> DeserializeSeed<'de> for JsonSeed {
+    type Value = Option<Node>;
+
+    fn deserialize<D>(self, de: D) -> Result<Self::Value, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        de.deserialize_any(self)
+    }
+}
+
+impl<'de> Visitor<'de> for JsonSeed {
+    type Value = Option<Node>;
+
+    fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(formatter, "a JSON value")
+    }
+
+    fn visit_bool<E>(self, v: bool) -> Result<Self::Value, E>
+    where
+        E: serde::de::Error,
+    {
+        TomlVisitor.visit_bool(v).map(Some)
+    }
+
+    fn visit_i64<E>(self, v: i64) -> Result<Self::Value, E>
+    where
+        E: serde::de::Error,
+    {
+        TomlVisitor.visit_i64(v).map(Some)
+    }
+
+    fn visit_u64<E>(self, v: u64) -> Result<Self::Value, E>
+    where
+        E: serde::de::Error,
+    {
+        if i64::try_from(v).is_err() {
+            return Err(E::custom(format!(
+                "the integer {v} at {} is too large for TOML",
+                self.describe_path()
+            )));
+        }
+
+        TomlVisitor.visit_u64(v).map(Some)
+    }
+
+    fn visit_f64<E>(self, v: f64) -> Result<Self::Value, E>
+    where
+        E: serde::de::Error,
+    {
+        TomlVisitor.visit_f64(v).map(Some)
+    }
+
+    fn visit_str<E>(self, v: &str) -> Result<Self::Value, E>
+    where
+        E: serde::de::Error,
+    {
+        TomlVisitor.visit_str(v).map(Some)
+    }
+
+    fn visit_unit<E>(self) -> Result<Self::Value, E>
+    where
+        E: serde::de::Error,
+    {
+        if self.options.skip_nulls && !self.path.is_empty() {
+            return Ok(None);
+        }
+
+        Err(E::custom(format!(
+            "{} is null, which can't be represented in TOML",
+            self.describe_path()
+        )))
+    }
+
+    fn visit_seq<A>(self, mut seq: A) -> Result<Self::Value, A::Error>
+    where
+        A: serde::de::SeqAccess<'de>,
+    {
+        let mut items = Vec::new();
+        let mut idx = 0;
+
+        while let Some(item) = seq.next_element_seed(self.child(idx))? {
+            items.extend(item);
+            idx += 1;
+        }
+
+        let kind = if !items.is_empty() && items.iter().all(Node::is_table) {
+            ArrayKind::Tables
+        } else {
+            ArrayKind::Inline
+        };
+
+        Ok(Some(
+            ArrayInner {
+                errors: Default::default(),
+                syntax: Default::default(),
+                kind,
+                items: items.into(),
+            }
+            .wrap()
+            .into(),
+        ))
+    }
+
+    fn visit_map<A>(self, mut map: A) -> Result<Self::Value, A::Error>
+    where
+        A: serde::de::MapAccess<'de>,
+    {
+        let table = TableInner {
+            errors: Default::default(),
+            syntax: Default::default(),
+            header: Default::default(),
+            kind: super::node::TableKind::Regular,
+            entries: Default::default(),
+            indexed: Default::default(),
+        };
+
+        while let Some(key) = map.next_key::<String>()? {
+            let seed = self.child(key.as_str());
+
+            if table.entries.read().lookup.contains_key(&Key::new(&key)) {
+                return Err(serde::de::Error::custom(format!(
+                    "{} is defined more than once",
+                    seed.describe_path()
+                )));
+            }
+
+            if let Some(node) = map.next_value_seed(seed)? {
+                table
+                    .entries
+                    .update(|entries| entries.add(Key::new(key), node));
+            }
+        }
+
+        Ok(Some(table.wrap().into()))
+    }
+}
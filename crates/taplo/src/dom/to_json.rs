@@ -0,0 +1,127 @@
+use super::{
+    error::{ConversionError, ConversionErrorReason},
+    node::{DateKind, DomNode, IntegerRepr, IntegerValue},
+    Error, Node,
+};
+use serde_json::{Map, Number, Value};
+
+/// Options for converting nodes to and from JSON, see
+/// [`Node::to_json_with_options`] and `Node::from_json_str`.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct JsonOptions {
+    /// Convert infinite and NaN floats to `null` instead of returning an error.
+    pub non_finite_floats_as_null: bool,
+    /// Skip `null` values in objects and arrays instead of returning an error.
+    pub skip_nulls: bool,
+    /// Convert the values that JSON has no type for to `{"$type": ..., "value": ...}`
+    /// objects, so that no information is lost.
+    ///
+    /// The types are the ones of [toml-test](https://github.com/toml-lang/toml-test):
+    /// `datetime`, `datetime-local`, `date-local` and `time-local` for date-times with
+    /// string values, `integer-hex`, `integer-oct` and `integer-bin` for integers that
+    /// are not decimal with number values, and `float` for infinite and NaN floats
+    /// with `inf`, `-inf` or `nan` values.
+    pub tagged: bool,
+}
+
+impl Node {
+    /// Converts the node to a JSON value with the default options.
+    ///
+    /// Unlike serializing the node, invalid nodes are not skipped
+    /// and strings with invalid escape sequences are not left empty,
+    /// these are errors instead along with floats that JSON can't represent.
+    ///
+    /// Integers are numbers regardless of their representation (e.g. hexadecimal),
+    /// and date-times are strings in their normalized TOML form, which is RFC 3339
+    /// for offset date-times.
+    pub fn to_json(&self) -> Result<Value, ConversionError> {
+        self.to_json_with_options(JsonOptions::default())
+    }
+
+    /// Converts the node to a JSON value, see [`Node::to_json`].
+    pub fn to_json_with_options(&self, options: JsonOptions) -> Result<Value, ConversionError> {
+        let error = |reason| ConversionError {
+            range: self.syntax().map(|s| s.text_range()),
+            reason,
+        };
+
+        match self {
+            Node::Table(t) => {
+                let mut map = Map::new();
+
+                for (key, node) in t.entries().read().iter() {
+                    map.insert(key.value().to_string(), node.to_json_with_options(options)?);
+                }
+
+                Ok(Value::Object(map))
+            }
+            Node::Array(arr) => Ok(Value::Array(
+                arr.items()
+                    .read()
+                    .iter()
+                    .map(|node| node.to_json_with_options(options))
+                    .collect::<Result<_, _>>()?,
+            )),
+            Node::Bool(v) => Ok(Value::Bool(v.value())),
+            Node::Str(v) => {
+                let value = v.value();
+
+                match v.errors().read().first() {
+                    Some(Error::InvalidEscapeSequence { range, .. }) => Err(ConversionError {
+                        range: Some(*range),
+                        reason: ConversionErrorReason::InvalidEscapeSequence,
+                    }),
+                    Some(_) => Err(error(ConversionErrorReason::InvalidNode)),
+                    None => Ok(Value::String(value.to_string())),
+                }
+            }
+            Node::Integer(v) => {
+                let value = match v.value() {
+                    IntegerValue::Negative(v) => v.into(),
+                    IntegerValue::Positive(v) => v.into(),
+                };
+
+                Ok(match v.repr() {
+                    _ if !options.tagged => value,
+                    IntegerRepr::Dec => value,
+                    IntegerRepr::Hex => tagged("integer-hex", value),
+                    IntegerRepr::Oct => tagged("integer-oct", value),
+                    IntegerRepr::Bin => tagged("integer-bin", value),
+                })
+            }
+            Node::Float(v) => match Number::from_f64(v.value()) {
+                Some(number) => Ok(Value::Number(number)),
+                None if options.tagged => Ok(tagged("float", v.to_string().into())),
+                None if options.non_finite_floats_as_null => Ok(Value::Null),
+                None => Err(error(ConversionErrorReason::NonFiniteFloat)),
+            },
+            Node::Date(v) => {
+                let value = Value::String(v.value().to_string());
+
+                Ok(match v.value().kind() {
+                    _ if !options.tagged => value,
+                    DateKind::OffsetDateTime => tagged("datetime", value),
+                    DateKind::LocalDateTime => tagged("datetime-local", value),
+                    DateKind::LocalDate => tagged("date-local", value),
+                    DateKind::LocalTime => tagged("time-local", value),
+                })
+            }
+            Node::Invalid(_) => Err(error(ConversionErrorReason::InvalidNode)),
+        }
+    }
+}
+
+fn tagged(ty: &str, value: Value) -> Value {
+    let mut map = Map::new();
+    map.insert("$type".into(), ty.into());
+    map.insert("value".into(), value);
+    Value::Object(map)
+}
+
+impl TryFrom<&Node> for Value {
+    type Error = ConversionError;
+
+    fn try_from(node: &Node) -> Result<Self, Self::Error> {
+        node.to_json()
+    }
+}
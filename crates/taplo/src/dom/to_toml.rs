@@ -1,12 +1,67 @@
-use crate::util::escape;
+use crate::{
+    formatter,
+    util::{escape, preferred_string_kind, StringKind},
+};
 
 use super::{
+    error::{ConversionError, ConversionErrorReason},
     node::{ArrayKind, DomNode, IntegerRepr, IntegerValue, TableKind},
-    Keys, Node,
+    Error, Keys, Node,
 };
 use std::fmt::{Formatter, Write};
 
 impl Node {
+    /// Converts a table to a TOML document that is formatted with the given options.
+    ///
+    /// Other nodes can't be documents, and invalid nodes or strings
+    /// with invalid escape sequences would result in invalid TOML,
+    /// so these are errors.
+    pub fn to_toml_document(&self, options: formatter::Options) -> Result<String, ConversionError> {
+        if !self.is_table() {
+            return Err(ConversionError {
+                range: self.syntax().map(|s| s.text_range()),
+                reason: ConversionErrorReason::ExpectedTable,
+            });
+        }
+
+        self.check_toml()?;
+
+        Ok(formatter::format(&self.to_toml(false, false), options))
+    }
+
+    fn check_toml(&self) -> Result<(), ConversionError> {
+        match self {
+            Node::Table(t) => t
+                .entries()
+                .read()
+                .iter()
+                .try_for_each(|(_, node)| node.check_toml()),
+            Node::Array(arr) => arr.items().read().iter().try_for_each(Node::check_toml),
+            Node::Invalid(_) => Err(ConversionError {
+                range: self.syntax().map(|s| s.text_range()),
+                reason: ConversionErrorReason::InvalidNode,
+            }),
+            _ => {
+                // The escape sequences are only checked when the value is read.
+                if let Node::Str(s) = self {
+                    s.value();
+                }
+
+                match self.errors().read().first() {
+                    Some(Error::InvalidEscapeSequence { range, .. }) => Err(ConversionError {
+                        range: Some(*range),
+                        reason: ConversionErrorReason::InvalidEscapeSequence,
+                    }),
+                    Some(_) => Err(ConversionError {
+                        range: self.syntax().map(|s| s.text_range()),
+                        reason: ConversionErrorReason::InvalidNode,
+                    }),
+                    None => Ok(()),
+                }
+            }
+        }
+    }
+
     pub fn to_toml(&self, inline: bool, prefer_single_quote: bool) -> String {
         let mut s = String::new();
         self.to_toml_fmt(&mut s, inline, prefer_single_quote)
@@ -78,13 +133,7 @@ impl Node {
 
                     // We make two runs to put tables and array of tables last.
                     // No tables:
-                    for (key, node) in entries.iter().filter(|(_, n)| {
-                        !n.is_table()
-                            && !n
-                                .as_array()
-                                .map(|n| n.inner.kind == ArrayKind::Tables)
-                                .unwrap_or(false)
-                    }) {
+                    for (key, node) in entries.iter().filter(|(_, n)| !is_section(n)) {
                         node.to_toml_impl(
                             f,
                             key.clone().into(),
@@ -96,12 +145,7 @@ impl Node {
                     }
 
                     // Tables only:
-                    for (key, node) in entries.iter().filter(|(_, n)| {
-                        n.is_table()
-                            || n.as_array()
-                                .map(|n| n.inner.kind == ArrayKind::Tables)
-                                .unwrap_or(false)
-                    }) {
+                    for (key, node) in entries.iter().filter(|(_, n)| is_section(n)) {
                         node.to_toml_impl(
                             f,
                             parent_keys.join(key.clone()),
@@ -113,7 +157,7 @@ impl Node {
                 }
             }
             Node::Array(array) => {
-                if array.inner.kind == ArrayKind::Inline || inline {
+                if !is_section(self) || inline {
                     if !parent_keys.is_empty() {
                         f.write_str(parent_keys.dotted())?;
                         f.write_str(" = ")?;
@@ -150,14 +194,16 @@ impl Node {
                     }
                 }
             }
-            Node::Bool(b) => write!(f, "{}", b.value())?,
+            Node::Bool(b) => write!(f, "{b}")?,
             Node::Str(s) => {
                 if let Some(syntax) = s.syntax() {
                     write!(f, "{}", syntax)?;
                 } else {
                     let escaped = escape(s.value());
 
-                    if prefer_single_quote && escaped == s.value() {
+                    if (prefer_single_quote && escaped == s.value())
+                        || preferred_string_kind(s.value()) == StringKind::Literal
+                    {
                         write!(f, "'{}'", s.value())?;
                     } else {
                         write!(f, r#""{escaped}""#)?;
@@ -169,14 +215,21 @@ impl Node {
                     IntegerValue::Negative(i) => write!(f, "{i}")?,
                     IntegerValue::Positive(i) => write!(f, "{i}")?,
                 },
-                IntegerRepr::Bin => write!(f, "{:#b}", i.value().as_positive().unwrap())?,
-                IntegerRepr::Oct => write!(f, "{:#o}", i.value().as_positive().unwrap())?,
-                IntegerRepr::Hex => write!(f, "{:#X}", i.value().as_positive().unwrap())?,
+                IntegerRepr::Bin => match i.value() {
+                    IntegerValue::Positive(v) => write!(f, "{v:#b}")?,
+                    v => write!(f, "{v}")?,
+                },
+                IntegerRepr::Oct => match i.value() {
+                    IntegerValue::Positive(v) => write!(f, "{v:#o}")?,
+                    v => write!(f, "{v}")?,
+                },
+                IntegerRepr::Hex => match i.value() {
+                    IntegerValue::Positive(v) => write!(f, "{v:#X}")?,
+                    v => write!(f, "{v}")?,
+                },
             },
-            Node::Float(float) => {
-                write!(f, "{}", float.value())?;
-            }
-            Node::Date(d) => write!(f, "{}", d.value())?,
+            Node::Float(float) => write!(f, "{float}")?,
+            Node::Date(d) => write!(f, "{d}")?,
             Node::Invalid(_) => {}
         }
 
@@ -184,6 +237,24 @@ impl Node {
     }
 }
 
+/// Whether the node is written as a table or as an array of tables with headers.
+///
+/// Arrays that were not parsed (e.g. the ones from JSON) are arrays
+/// of tables if all of their items are tables.
+fn is_section(node: &Node) -> bool {
+    match node {
+        Node::Table(table) => table.inner.kind != TableKind::Inline,
+        Node::Array(array) => {
+            array.inner.kind == ArrayKind::Tables
+                || (array.syntax().is_none() && {
+                    let items = array.items().read();
+                    !items.is_empty() && items.iter().all(is_section)
+                })
+        }
+        _ => false,
+    }
+}
+
 impl core::fmt::Display for Node {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         self.to_toml_impl(f, Keys::empty(), false, false, false)
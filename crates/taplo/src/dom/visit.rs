@@ -0,0 +1,89 @@
+//! Depth-first traversal of the DOM.
+//!
+//! [`walk`] visits every node in document order, including
+//! the contents of arrays of tables, inline tables and pseudo-tables
+//! created by dotted keys.
+//!
+//! # Example
+//!
+//! ```
+//! use taplo::dom::{node::Key, visit::{walk, Visitor}, Keys, Node};
+//!
+//! #[derive(Default)]
+//! struct Paths(Vec<String>);
+//!
+//! impl Visitor for Paths {
+//!     fn visit_entry(&mut self, path: &Keys, _key: &Key, _node: &Node) {
+//!         self.0.push(path.dotted().to_string());
+//!     }
+//! }
+//!
+//! let dom = taplo::parser::parse("a.b = 1\nc = { d = [2] }").into_dom();
+//!
+//! let mut paths = Paths::default();
+//! walk(&dom, &mut paths);
+//!
+//! assert_eq!(paths.0, ["a", "a.b", "c", "c.d"]);
+//! ```
+
+use super::{
+    node::{Key, Table},
+    Keys, Node,
+};
+
+/// Callbacks for [`walk`].
+///
+/// Every path is relative to the node passed to [`walk`],
+/// all methods do nothing by default.
+pub trait Visitor {
+    /// Called for every table, before its entries are visited.
+    fn visit_table(&mut self, path: &Keys, table: &Table) {
+        let _ = (path, table);
+    }
+
+    /// Called for every entry of a table, the path
+    /// already includes the key of the entry.
+    fn visit_entry(&mut self, path: &Keys, key: &Key, node: &Node) {
+        let _ = (path, key, node);
+    }
+
+    /// Called for every item of an array, the path
+    /// already includes the index of the item.
+    fn visit_array_item(&mut self, path: &Keys, index: usize, node: &Node) {
+        let _ = (path, index, node);
+    }
+
+    /// Called for every node that is neither a table nor an array.
+    fn visit_value(&mut self, path: &Keys, node: &Node) {
+        let _ = (path, node);
+    }
+}
+
+/// Visit the node and all of its descendants depth-first in document order.
+pub fn walk(node: &Node, visitor: &mut impl Visitor) {
+    walk_impl(&Keys::empty(), node, visitor);
+}
+
+fn walk_impl(path: &Keys, node: &Node, visitor: &mut impl Visitor) {
+    match node {
+        Node::Table(table) => {
+            visitor.visit_table(path, table);
+
+            let entries = table.entries().read();
+            for (key, entry) in entries.iter() {
+                let path = path.join(key.clone());
+                visitor.visit_entry(&path, key, entry);
+                walk_impl(&path, entry, visitor);
+            }
+        }
+        Node::Array(arr) => {
+            let items = arr.items().read();
+            for (idx, item) in items.iter().enumerate() {
+                let path = path.join(idx);
+                visitor.visit_array_item(&path, idx, item);
+                walk_impl(&path, item, visitor);
+            }
+        }
+        _ => visitor.visit_value(path, node),
+    }
+}
@@ -0,0 +1,145 @@
+//! Line based diffing of the formatted text against the original one.
+
+use rowan::{TextRange, TextSize};
+
+/// The maximum number of inserted and removed lines
+/// that are diffed, above this the differing lines are replaced at once.
+const MAX_COST: usize = 2000;
+
+/// The edits that turn the original text into the formatted one.
+///
+/// Only the differing lines are replaced, the edits are ordered
+/// and do not overlap.
+pub(super) fn line_edits(original: &str, formatted: &str) -> Vec<(TextRange, String)> {
+    let old: Vec<&str> = original.split_inclusive('\n').collect();
+    let new: Vec<&str> = formatted.split_inclusive('\n').collect();
+
+    let prefix = old.iter().zip(&new).take_while(|(a, b)| a == b).count();
+    let suffix = old[prefix..]
+        .iter()
+        .rev()
+        .zip(new[prefix..].iter().rev())
+        .take_while(|(a, b)| a == b)
+        .count();
+
+    let old = &old[prefix..old.len() - suffix];
+    let new = &new[prefix..new.len() - suffix];
+
+    if old.is_empty() && new.is_empty() {
+        return Vec::new();
+    }
+
+    let start: TextSize = original
+        .split_inclusive('\n')
+        .take(prefix)
+        .map(TextSize::of)
+        .sum();
+
+    let mut offsets = Vec::with_capacity(old.len() + 1);
+    offsets.push(start);
+    for line in old {
+        offsets.push(offsets[offsets.len() - 1] + TextSize::of(*line));
+    }
+
+    let matches = matching_lines(old, new).unwrap_or_default();
+
+    let mut edits = Vec::new();
+    let (mut i, mut j) = (0, 0);
+
+    for (mi, mj) in matches.into_iter().chain([(old.len(), new.len())]) {
+        if mi > i || mj > j {
+            edits.push((TextRange::new(offsets[i], offsets[mi]), new[j..mj].concat()));
+        }
+
+        i = mi + 1;
+        j = mj + 1;
+    }
+
+    edits
+}
+
+/// The indices of the lines that are kept, based on Myers' diff algorithm.
+///
+/// Returns [`None`] if there are too many differences.
+fn matching_lines(a: &[&str], b: &[&str]) -> Option<Vec<(usize, usize)>> {
+    let (n, m) = (a.len() as isize, b.len() as isize);
+    let max = (a.len() + b.len()).min(MAX_COST) as isize;
+    let offset = max + 1;
+
+    // The furthest reaching x on each diagonal k = x - y.
+    let mut v = vec![0isize; (2 * max + 3) as usize];
+    let mut trace: Vec<Vec<isize>> = Vec::new();
+
+    let mut end = None;
+
+    'outer: for d in 0..=max {
+        for k in (-d..=d).step_by(2) {
+            let get = |k: isize| v[(offset + k) as usize];
+
+            let mut x = if go_down(get, d, k) {
+                get(k + 1)
+            } else {
+                get(k - 1) + 1
+            };
+            let mut y = x - k;
+
+            while x < n && y < m && a[x as usize] == b[y as usize] {
+                x += 1;
+                y += 1;
+            }
+
+            v[(offset + k) as usize] = x;
+
+            if x >= n && y >= m {
+                trace.push(v[(offset - d) as usize..=(offset + d) as usize].to_vec());
+                end = Some(d);
+                break 'outer;
+            }
+        }
+
+        trace.push(v[(offset - d) as usize..=(offset + d) as usize].to_vec());
+    }
+
+    let mut matches = Vec::new();
+    let (mut x, mut y) = (n, m);
+
+    for d in (1..=end?).rev() {
+        let prev = &trace[(d - 1) as usize];
+        let get = |k: isize| prev[(k + d - 1) as usize];
+
+        let k = x - y;
+        let prev_k = if go_down(get, d, k) { k + 1 } else { k - 1 };
+        let prev_x = get(prev_k);
+        let prev_y = prev_x - prev_k;
+
+        let (start_x, start_y) = if prev_k == k + 1 {
+            (prev_x, prev_y + 1)
+        } else {
+            (prev_x + 1, prev_y)
+        };
+
+        while x > start_x && y > start_y {
+            x -= 1;
+            y -= 1;
+            matches.push((x as usize, y as usize));
+        }
+
+        x = prev_x;
+        y = prev_y;
+    }
+
+    while x > 0 && y > 0 {
+        x -= 1;
+        y -= 1;
+        matches.push((x as usize, y as usize));
+    }
+
+    matches.reverse();
+    Some(matches)
+}
+
+/// Whether the path to the diagonal `k` of the step `d` comes from the diagonal `k + 1`,
+/// i.e. a line is inserted rather than removed.
+fn go_down(v: impl Fn(isize) -> isize, d: isize, k: isize) -> bool {
+    k == -d || (k != d && v(k - 1) < v(k + 1))
+}
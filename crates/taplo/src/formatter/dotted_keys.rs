@@ -0,0 +1,245 @@
+//! Collapsing tables with single entries into dotted keys,
+//! and expanding long dotted keys into tables.
+
+use super::{Context, Options};
+use crate::{
+    dom::{FromSyntax, Keys, Node},
+    syntax::{SyntaxKind::*, SyntaxNode},
+    util::syntax::section_ranges,
+};
+use rowan::{Direction, TextRange};
+use std::collections::HashMap;
+
+/// A table with a single entry that is collapsed
+/// into a dotted key under its parent table.
+pub(super) struct CollapsedTable {
+    pub(super) entry: SyntaxNode,
+    /// The keys of the header that are not part of the parent table.
+    pub(super) prefix: String,
+    /// The comments before the header followed by
+    /// the trailing comment of the header.
+    pub(super) comments: Vec<String>,
+}
+
+/// The table headers of a document and the tables that are collapsed
+/// into dotted keys, see [`Options::prefer_dotted_keys_for_single_entries`]
+/// and [`Options::expand_dotted_keys`].
+#[derive(Default)]
+pub(super) struct DottedKeys {
+    /// Tables are only restructured in valid documents.
+    valid: bool,
+    /// The keys of the headers, and whether they are arrays of tables.
+    headers: Vec<(Keys, bool)>,
+    /// The collapsed tables by the range of the header of their parent table,
+    /// or [`None`] for the root table.
+    collapsed: HashMap<Option<TextRange>, Vec<CollapsedTable>>,
+    collapsed_headers: Vec<TextRange>,
+}
+
+impl DottedKeys {
+    pub(super) fn new(root: &SyntaxNode, options: &Options, context: &Context) -> Self {
+        let enabled = options.prefer_dotted_keys_for_single_entries
+            || options.expand_dotted_keys
+            || context.scopes.0.iter().any(|(_, scoped)| {
+                scoped.prefer_dotted_keys_for_single_entries == Some(true)
+                    || scoped.expand_dotted_keys == Some(true)
+            });
+
+        // Conflicting keys could end up anywhere after the tables are restructured.
+        if !enabled
+            || !context.errors.is_empty()
+            || Node::from_syntax(root.clone().into()).validate().is_err()
+        {
+            return Self::default();
+        }
+
+        let sections = section_ranges(root);
+
+        let mut dotted_keys = Self {
+            valid: true,
+            headers: sections
+                .iter()
+                .map(|(header, _)| (header_keys(header), header.kind() == TABLE_ARRAY_HEADER))
+                .collect(),
+            ..Default::default()
+        };
+
+        for (idx, (header, range)) in sections.iter().enumerate() {
+            let mut options = options.clone();
+            context.update_options(&mut options, header.text_range());
+
+            if let Some((parent, table)) =
+                dotted_keys.collapsed_table(&sections, idx, *range, &options)
+            {
+                dotted_keys.collapsed_headers.push(header.text_range());
+                dotted_keys.collapsed.entry(parent).or_default().push(table);
+            }
+        }
+
+        dotted_keys
+    }
+
+    /// Whether the table of the header is collapsed into a dotted key.
+    pub(super) fn is_collapsed(&self, header: &SyntaxNode) -> bool {
+        self.collapsed_headers.contains(&header.text_range())
+    }
+
+    /// The tables that are collapsed into the table of the given header.
+    pub(super) fn collapsed_into(&self, header: Option<&SyntaxNode>) -> &[CollapsedTable] {
+        self.collapsed
+            .get(&header.map(|h| h.text_range()))
+            .map(Vec::as_slice)
+            .unwrap_or_default()
+    }
+
+    /// The keys of the table that an entry of the given table
+    /// is expanded into, if its key is too long.
+    pub(super) fn expanded_table(
+        &self,
+        table: &Keys,
+        entry: &SyntaxNode,
+        options: &Options,
+    ) -> Option<Keys> {
+        if !self.valid || !options.expand_dotted_keys {
+            return None;
+        }
+
+        let keys = Keys::from_syntax(entry.first_child().filter(|n| n.kind() == KEY)?.into());
+
+        if keys.len() <= options.max_dotted_key_depth.max(1) {
+            return None;
+        }
+
+        let expanded = table.extend(keys.skip_right(1).iter().cloned());
+
+        // The tables of arrays of tables are left as-is,
+        // and the expanded table must not be defined already.
+        if self
+            .headers
+            .iter()
+            .any(|(keys, array)| (*array && table.contains(keys)) || *keys == expanded)
+        {
+            return None;
+        }
+
+        Some(expanded)
+    }
+
+    fn collapsed_table(
+        &self,
+        sections: &[(SyntaxNode, TextRange)],
+        idx: usize,
+        range: TextRange,
+        options: &Options,
+    ) -> Option<(Option<TextRange>, CollapsedTable)> {
+        if !options.prefer_dotted_keys_for_single_entries {
+            return None;
+        }
+
+        let (header, _) = &sections[idx];
+        let (keys, array) = &self.headers[idx];
+
+        if *array || keys.is_empty() {
+            return None;
+        }
+
+        // Sub-tables would be orphaned, and tables of arrays of tables are left as-is.
+        if self.headers.iter().enumerate().any(|(i, (other, array))| {
+            (i != idx && other.contains(keys)) || (*array && keys.contains(other))
+        }) {
+            return None;
+        }
+
+        let mut entry = None;
+
+        for element in header.siblings_with_tokens(Direction::Next).skip(1) {
+            if !range.contains_range(element.text_range()) {
+                break;
+            }
+
+            match element.kind() {
+                WHITESPACE | NEWLINE => {}
+                ENTRY if entry.is_none() => entry = element.into_node(),
+                _ => return None,
+            }
+        }
+
+        let entry = entry?;
+
+        let (parent, parent_len) = self
+            .headers
+            .iter()
+            .enumerate()
+            .filter(|(_, (other, _))| other.len() < keys.len() && keys.contains(other))
+            .max_by_key(|(_, (other, _))| other.len())
+            .map_or((None, 0), |(i, (other, _))| {
+                (Some(sections[i].0.text_range()), other.len())
+            });
+
+        let parts = key_parts(&header.first_child()?);
+        let entry_parts = key_parts(&entry.first_child()?);
+
+        if parts.len() - parent_len + entry_parts.len() > options.max_dotted_key_depth {
+            return None;
+        }
+
+        let mut comments = leading_comments(header);
+        comments.extend(
+            header
+                .children_with_tokens()
+                .filter(|t| t.kind() == COMMENT)
+                .map(|t| t.to_string()),
+        );
+
+        Some((
+            parent,
+            CollapsedTable {
+                entry,
+                prefix: parts[parent_len..].join("."),
+                comments,
+            },
+        ))
+    }
+}
+
+/// The text of the individual keys of a key node.
+pub(super) fn key_parts(key: &SyntaxNode) -> Vec<String> {
+    key.children_with_tokens()
+        .filter(|t| t.kind() == IDENT)
+        .map(|t| t.to_string())
+        .collect()
+}
+
+fn header_keys(header: &SyntaxNode) -> Keys {
+    header
+        .first_child()
+        .map(|key| Keys::from_syntax(key.into()))
+        .unwrap_or_else(Keys::empty)
+}
+
+/// The comments that are directly before a header,
+/// they are formatted together with the header.
+fn leading_comments(header: &SyntaxNode) -> Vec<String> {
+    let mut comments = Vec::new();
+    let mut newline_count = 0;
+
+    for element in header.siblings_with_tokens(Direction::Prev).skip(1) {
+        match element.kind() {
+            WHITESPACE => {}
+            NEWLINE => {
+                newline_count += element.to_string().matches('\n').count();
+                if newline_count > 1 {
+                    break;
+                }
+            }
+            COMMENT => {
+                comments.push(element.to_string());
+                newline_count = 0;
+            }
+            _ => break,
+        }
+    }
+
+    comments.reverse();
+    comments
+}
@@ -24,6 +24,8 @@ macro_rules! create_options {
                         self.$name = v;
                     }
                 )+
+
+                self.update_groups(incomplete.array, incomplete.inline_table, incomplete.keys);
             }
 
             pub fn update_camel(&mut self, incomplete: OptionsIncompleteCamel) {
@@ -32,9 +34,15 @@ macro_rules! create_options {
                         self.$name = v;
                     }
                 )+
+
+                self.update_groups(incomplete.array, incomplete.inline_table, incomplete.keys);
             }
 
-            pub fn update_from_str<S: AsRef<str>, I: Iterator<Item = (S, S)>>(
+            /// Updates the options from string key-value pairs,
+            /// e.g. `("column_width", "100")` or `("array.reorder", "true")`.
+            ///
+            /// The options before the first invalid one are still updated.
+            pub fn update_from_str<S: AsRef<str>, I: IntoIterator<Item = (S, S)>>(
                 &mut self,
                 values: I,
             ) -> Result<(), OptionParseError> {
@@ -43,17 +51,31 @@ macro_rules! create_options {
                     $(
                         if key.as_ref() == stringify!($name) {
                             self.$name =
-                                val.as_ref()
-                                    .parse()
+                                <$ty as OptionValue>::parse_option(val.as_ref())
                                     .map_err(|error| OptionParseError::InvalidValue {
                                         key: key.as_ref().into(),
-                                        error: Box::new(error),
+                                        error,
                                     })?;
 
                             continue;
                         }
                     )+
 
+                    if let Some((name, value)) = grouped_option_from_str(key.as_ref(), val.as_ref())? {
+                        self.update_from_str([(name, value.as_str())]).map_err(|error| {
+                            match error {
+                                OptionParseError::InvalidValue { error, .. } => {
+                                    OptionParseError::InvalidValue {
+                                        key: key.as_ref().into(),
+                                        error,
+                                    }
+                                }
+                                error => error,
+                            }
+                        })?;
+                        continue;
+                    }
+
                     return Err(OptionParseError::InvalidOption(key.as_ref().into()));
                 }
 
@@ -69,6 +91,18 @@ macro_rules! create_options {
                 $(#[$field_attr])*
                 pub $name: Option<$ty>,
             )+
+
+            /// Array options, these take precedence over
+            /// the legacy flat array options.
+            pub array: Option<ArrayOptions>,
+
+            /// Inline table options, these take precedence over
+            /// the legacy flat inline table options.
+            pub inline_table: Option<InlineTableOptions>,
+
+            /// Key options, these take precedence over
+            /// the legacy flat key options.
+            pub keys: Option<KeyOptions>,
         }
 
         impl OptionsIncomplete {
@@ -92,6 +126,18 @@ macro_rules! create_options {
                 $(#[$field_attr])*
                 pub $name: Option<$ty>,
             )+
+
+            /// Array options, these take precedence over
+            /// the legacy flat array options.
+            pub array: Option<ArrayOptions>,
+
+            /// Inline table options, these take precedence over
+            /// the legacy flat inline table options.
+            pub inline_table: Option<InlineTableOptions>,
+
+            /// Key options, these take precedence over
+            /// the legacy flat key options.
+            pub keys: Option<KeyOptions>,
         }
 
         impl OptionsIncompleteCamel {
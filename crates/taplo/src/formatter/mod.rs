@@ -4,14 +4,20 @@
 //! contain invalid syntax. In that case the invalid part is skipped.
 
 use crate::{
-    dom::{self, node::DomNode, FromSyntax, Keys, Node},
-    syntax::{SyntaxElement, SyntaxKind::*, SyntaxNode, SyntaxToken},
-    util::overlaps,
+    dom::{
+        self,
+        node::{Array, DomNode, Key},
+        FromSyntax, Keys, Node,
+    },
+    syntax::{SyntaxElement, SyntaxKind, SyntaxKind::*, SyntaxNode, SyntaxToken},
+    util::{overlaps, quote_key},
 };
-use rowan::{GreenNode, NodeOrToken, TextRange};
+use logos::Logos;
+use rowan::{GreenNode, NodeOrToken, TextRange, TextSize};
 use std::{
     cmp,
-    iter::{repeat, FromIterator},
+    iter::{repeat, repeat_n, FromIterator},
+    mem,
     ops::Range,
     rc::Rc,
 };
@@ -24,9 +30,18 @@ use schemars::JsonSchema;
 
 #[macro_use]
 mod macros;
+mod diff;
+mod dotted_keys;
+mod tables;
+
+use dotted_keys::{key_parts, CollapsedTable, DottedKeys};
+use tables::reordered_elements;
 
 #[derive(Debug, Clone, Default)]
 /// Scoped formatter options based on text ranges.
+///
+/// The options of all scopes that contain a part of the document are applied
+/// in order, so later scopes take precedence over earlier ones.
 pub struct ScopedOptions(Vec<(TextRange, OptionsIncomplete)>);
 
 impl FromIterator<(TextRange, OptionsIncomplete)> for ScopedOptions {
@@ -49,18 +64,26 @@ create_options!(
         /// Align consecutive comments after entries and items vertically.
         ///
         /// This applies to comments that are after entries or array items.
+        /// Comments that would go beyond `column_width` are not aligned,
+        /// widths are counted in characters.
         pub align_comments: bool,
 
         /// Put trailing commas for multiline
         /// arrays.
+        ///
+        /// Deprecated in favour of `array.trailing_comma`.
         pub array_trailing_comma: bool,
 
         /// Automatically expand arrays to multiple lines
         /// if they're too long.
+        ///
+        /// Deprecated in favour of `array.auto_expand`.
         pub array_auto_expand: bool,
 
         /// Expand values (e.g.) inside inline tables
         /// where possible.
+        ///
+        /// Deprecated in favour of `inline_table.expand`.
         pub inline_table_expand: bool,
 
         /// Automatically collapse arrays if they
@@ -68,15 +91,24 @@ create_options!(
         ///
         /// The array won't be collapsed if it
         /// contains a comment.
+        ///
+        /// Deprecated in favour of `array.auto_collapse`.
         pub array_auto_collapse: bool,
 
         /// Omit whitespace padding inside single-line arrays.
+        ///
+        /// Deprecated in favour of `array.bracket_spacing` (inverted).
         pub compact_arrays: bool,
 
         /// Omit whitespace padding inside inline tables.
+        ///
+        /// Deprecated in favour of `inline_table.brace_spacing` (inverted).
         pub compact_inline_tables: bool,
 
         /// Omit whitespace around `=`.
+        ///
+        /// This also applies to the entries of inline tables,
+        /// aligned entries are padded before the `=`.
         pub compact_entries: bool,
 
         /// Target maximum column width after which
@@ -98,20 +130,311 @@ create_options!(
         /// Add trailing newline to the source.
         pub trailing_newline: bool,
 
-        /// Alphabetically reorder keys that are not separated by blank lines.
+        /// Alphabetically reorder keys that are not separated by blank lines,
+        /// comments directly before an entry are moved together with it.
+        ///
+        /// Only the entries within tables are reordered, table headers are never
+        /// moved by this option, see `reorder_tables` for that.
+        ///
+        /// Deprecated in favour of `keys.reorder`.
         pub reorder_keys: bool,
 
         /// Alphabetically reorder array values that are not separated by blank lines.
+        ///
+        /// Deprecated in favour of `array.reorder`.
         pub reorder_arrays: bool,
 
         /// The maximum amount of consecutive blank lines allowed.
+        ///
+        /// Blank lines in multi-line strings are kept, and blank lines
+        /// at the end of the document are always removed.
         pub allowed_blank_lines: usize,
 
         /// Use CRLF line endings
         pub crlf: bool,
+
+        /// Re-indent the lines of multi-line basic strings
+        /// when the indentation of their entries changes.
+        ///
+        /// Line ending backslashes are added where needed
+        /// so that the value of the string is unchanged,
+        /// strings that cannot be re-indented this way are left as-is.
+        pub reflow_multiline_string_indent: bool,
+
+        /// Keep the UTF-8 byte order mark at the start
+        /// of the document if there is one.
+        pub preserve_bom: bool,
+
+        /// The number of blank lines before table headers, this takes
+        /// precedence over `allowed_blank_lines`.
+        ///
+        /// Comments directly before a header are kept together with it,
+        /// and a header at the start of the document has no blank lines before it.
+        /// If not set, the blank lines before headers are kept.
+        pub table_spacing: Option<usize>,
+
+        /// Convert inline tables that exceed the column width to standard tables.
+        ///
+        /// This only applies to inline tables that are the values of entries
+        /// at the top level or under table headers, the tables are placed
+        /// after the rest of the entries with the comments of the entries.
+        /// Inline tables in arrays are left as-is.
+        pub convert_long_inline_tables: bool,
+
+        /// Collapse tables with a single entry into dotted keys under their parent tables.
+        ///
+        /// Tables with sub-tables or comments in them and tables of arrays of tables are left as-is.
+        /// The comments before the headers are kept before the entries.
+        pub prefer_dotted_keys_for_single_entries: bool,
+
+        /// Expand the dotted keys of entries that are longer than
+        /// `max_dotted_key_depth` into tables.
+        ///
+        /// The tables are placed after the rest of the entries,
+        /// entries in arrays of tables and inline tables are left as-is.
+        pub expand_dotted_keys: bool,
+
+        /// The maximum number of keys in dotted keys that are created
+        /// by `prefer_dotted_keys_for_single_entries`, or kept by `expand_dotted_keys`.
+        pub max_dotted_key_depth: usize,
+
+        /// The case of the digits of hexadecimal integers,
+        /// the `0x` prefix is always lowercase.
+        pub hex_case: HexCase,
+
+        /// Separate the digits of decimal integers with underscores.
+        ///
+        /// Floats and the integers of other bases are left as-is.
+        pub integer_grouping: IntegerGrouping,
+
+        /// The separator between the date and the time of date-times,
+        /// and the case of the `Z` zone designator.
+        ///
+        /// Local dates and local times are left as-is.
+        pub datetime_style: DateTimeStyle,
+
+        /// Alphabetically reorder the tables by their keys, comments directly
+        /// before a header are moved together with it.
+        ///
+        /// Arrays of tables are kept in their relative order together with
+        /// the tables under them, and the entries before the first header stay in place.
+        pub reorder_tables: bool,
+
+        /// The layout of arrays.
+        ///
+        /// With `preserve`, single-line arrays stay on a single line and the values of
+        /// multi-line arrays stay on the lines they are on, `array_auto_collapse` and
+        /// `array_auto_expand` are not applied to arrays.
+        pub array_layout: ArrayLayout,
+
+        /// Glob patterns of the keys of arrays that are always expanded
+        /// to one value per line, e.g. `workspace.members`.
+        ///
+        /// The patterns are separated by commas when given as a string.
+        pub expand_arrays_paths: Vec<String>,
+
+        /// Glob patterns of the keys of arrays whose values are sorted if they are all strings.
+        ///
+        /// The strings are compared bytewise by their unescaped values, so the
+        /// quotes and escapes don't matter and non-ASCII characters are ordered by
+        /// their code points. Comments directly before a value and after it on the
+        /// same line are moved together with it, values separated by blank lines
+        /// are sorted separately. Arrays that contain other values are left as-is,
+        /// these are reported by [`check`].
+        ///
+        /// The patterns are separated by commas when given as a string.
+        pub sort_string_arrays: Vec<String>,
+
+        /// Expand arrays with more values than this to one value per line.
+        ///
+        /// Also available as `array.expand_threshold`.
+        pub array_expand_threshold: Option<usize>,
+
+        /// The quotes of keys.
+        ///
+        /// Also available as `keys.quotes`.
+        pub key_quotes: KeyQuotes,
+
+        /// Keys that are put first in the given order when the keys are reordered,
+        /// e.g. `name, version`, the rest of the keys are ordered alphabetically after them.
+        ///
+        /// The keys are separated by commas when given as a string.
+        /// Also available as `keys.order_rules`.
+        pub key_order_rules: Vec<String>,
     }
 );
 
+/// The case of the digits of hexadecimal integers, see [`Options::hex_case`].
+#[derive(Debug, Clone, Copy, Default, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+#[cfg_attr(feature = "schema", derive(JsonSchema))]
+pub enum HexCase {
+    /// Keep the digits as they are.
+    #[default]
+    Keep,
+    Upper,
+    Lower,
+}
+
+/// The grouping of the digits of decimal integers, see [`Options::integer_grouping`].
+#[derive(Debug, Clone, Copy, Default, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+#[cfg_attr(feature = "schema", derive(JsonSchema))]
+pub enum IntegerGrouping {
+    /// Keep the underscores as they are.
+    #[default]
+    Keep,
+    /// Separate every three digits from the right, e.g. `1_000_000`.
+    Thousands,
+}
+
+/// The layout of arrays, see [`Options::array_layout`].
+#[derive(Debug, Clone, Copy, Default, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+#[cfg_attr(feature = "schema", derive(JsonSchema))]
+pub enum ArrayLayout {
+    /// Collapse and expand arrays based on the other array options,
+    /// multi-line arrays have one value per line.
+    #[default]
+    Auto,
+    /// Keep the line breaks between the values of arrays.
+    Preserve,
+}
+
+/// The style of date-times, see [`Options::datetime_style`].
+#[derive(Debug, Clone, Copy, Default, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+#[cfg_attr(feature = "schema", derive(JsonSchema))]
+pub enum DateTimeStyle {
+    /// Keep the separator and the zone designator as they are.
+    #[default]
+    Keep,
+    /// Separate the date and the time with `T` and use `Z` for UTC,
+    /// e.g. `2021-01-01T10:00:00Z`.
+    TUppercase,
+    /// Separate the date and the time with a space and use `Z` for UTC,
+    /// e.g. `2021-01-01 10:00:00Z`.
+    Space,
+}
+
+/// The quotes of keys, see [`Options::key_quotes`].
+#[derive(Debug, Clone, Copy, Default, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+#[cfg_attr(feature = "schema", derive(JsonSchema))]
+pub enum KeyQuotes {
+    /// Keep the quotes as they are.
+    #[default]
+    Keep,
+    /// Remove the quotes of keys that can be bare keys.
+    ///
+    /// Keys that would be read as other values, e.g. `"123"`, stay quoted.
+    Minimal,
+}
+
+/// The value of an option that can be parsed in [`Options::update_from_str`].
+trait OptionValue: Sized {
+    fn parse_option(value: &str) -> Result<Self, Box<dyn std::error::Error + Send + Sync>>;
+}
+
+macro_rules! from_str_option_values {
+    ($($ty:ty),*) => {
+        $(
+            impl OptionValue for $ty {
+                fn parse_option(value: &str) -> Result<Self, Box<dyn std::error::Error + Send + Sync>> {
+                    Ok(value.parse()?)
+                }
+            }
+        )*
+    };
+}
+
+from_str_option_values!(bool, usize, String);
+
+impl OptionValue for HexCase {
+    fn parse_option(value: &str) -> Result<Self, Box<dyn std::error::Error + Send + Sync>> {
+        match value {
+            "keep" => Ok(Self::Keep),
+            "upper" => Ok(Self::Upper),
+            "lower" => Ok(Self::Lower),
+            _ => Err(r#"expected one of "keep", "upper" or "lower""#.into()),
+        }
+    }
+}
+
+impl OptionValue for IntegerGrouping {
+    fn parse_option(value: &str) -> Result<Self, Box<dyn std::error::Error + Send + Sync>> {
+        match value {
+            "keep" => Ok(Self::Keep),
+            "thousands" => Ok(Self::Thousands),
+            _ => Err(r#"expected "keep" or "thousands""#.into()),
+        }
+    }
+}
+
+impl OptionValue for ArrayLayout {
+    fn parse_option(value: &str) -> Result<Self, Box<dyn std::error::Error + Send + Sync>> {
+        match value {
+            "auto" => Ok(Self::Auto),
+            "preserve" => Ok(Self::Preserve),
+            _ => Err(r#"expected "auto" or "preserve""#.into()),
+        }
+    }
+}
+
+impl OptionValue for DateTimeStyle {
+    fn parse_option(value: &str) -> Result<Self, Box<dyn std::error::Error + Send + Sync>> {
+        match value {
+            "keep" => Ok(Self::Keep),
+            "t_uppercase" => Ok(Self::TUppercase),
+            "space" => Ok(Self::Space),
+            _ => Err(r#"expected one of "keep", "t_uppercase" or "space""#.into()),
+        }
+    }
+}
+
+impl OptionValue for KeyQuotes {
+    fn parse_option(value: &str) -> Result<Self, Box<dyn std::error::Error + Send + Sync>> {
+        match value {
+            "keep" => Ok(Self::Keep),
+            "minimal" => Ok(Self::Minimal),
+            _ => Err(r#"expected "keep" or "minimal""#.into()),
+        }
+    }
+}
+
+/// Comma separated key patterns.
+impl OptionValue for Vec<String> {
+    fn parse_option(value: &str) -> Result<Self, Box<dyn std::error::Error + Send + Sync>> {
+        value
+            .split(',')
+            .map(str::trim)
+            .filter(|pattern| !pattern.is_empty())
+            .map(|pattern| {
+                pattern
+                    .parse::<Keys>()
+                    .map_err(|error| format!("invalid pattern {pattern:?}: {error}"))?;
+                Ok(pattern.to_string())
+            })
+            .collect()
+    }
+}
+
+/// An empty value unsets the option.
+impl OptionValue for Option<usize> {
+    fn parse_option(value: &str) -> Result<Self, Box<dyn std::error::Error + Send + Sync>> {
+        if value.is_empty() {
+            Ok(None)
+        } else {
+            Ok(Some(value.parse()?))
+        }
+    }
+}
+
 #[derive(Debug)]
 pub enum OptionParseError {
     InvalidOption(String),
@@ -140,6 +463,126 @@ impl core::fmt::Display for OptionParseError {
 
 impl std::error::Error for OptionParseError {}
 
+/// Grouped array formatting options.
+///
+/// Unset options fall back to the legacy flat options.
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "schema", derive(JsonSchema))]
+pub struct ArrayOptions {
+    /// Automatically collapse arrays if they
+    /// fit in one line.
+    #[cfg_attr(feature = "serde", serde(alias = "autoCollapse"))]
+    pub auto_collapse: Option<bool>,
+
+    /// Automatically expand arrays to multiple lines
+    /// if they're too long.
+    #[cfg_attr(feature = "serde", serde(alias = "autoExpand"))]
+    pub auto_expand: Option<bool>,
+
+    /// Put trailing commas for multiline arrays.
+    #[cfg_attr(feature = "serde", serde(alias = "trailingComma"))]
+    pub trailing_comma: Option<bool>,
+
+    /// Add whitespace padding inside single-line arrays.
+    #[cfg_attr(feature = "serde", serde(alias = "bracketSpacing"))]
+    pub bracket_spacing: Option<bool>,
+
+    /// Alphabetically reorder array values that are not separated by blank lines.
+    pub reorder: Option<bool>,
+
+    /// Expand arrays with more values than this to one value per line.
+    #[cfg_attr(feature = "serde", serde(alias = "expandThreshold"))]
+    pub expand_threshold: Option<usize>,
+}
+
+/// Grouped inline table formatting options.
+///
+/// Unset options fall back to the legacy flat options.
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "schema", derive(JsonSchema))]
+pub struct InlineTableOptions {
+    /// Expand values (e.g.) inside inline tables
+    /// where possible.
+    pub expand: Option<bool>,
+
+    /// Add whitespace padding inside inline tables.
+    #[cfg_attr(feature = "serde", serde(alias = "braceSpacing"))]
+    pub brace_spacing: Option<bool>,
+}
+
+/// Grouped key formatting options.
+///
+/// Unset options fall back to the legacy flat options.
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "schema", derive(JsonSchema))]
+pub struct KeyOptions {
+    /// Alphabetically reorder keys that are not separated by blank lines,
+    /// comments directly before an entry are moved together with it.
+    pub reorder: Option<bool>,
+
+    /// The quotes of keys.
+    pub quotes: Option<KeyQuotes>,
+
+    /// Keys that are put first in the given order when the keys are reordered.
+    #[cfg_attr(feature = "serde", serde(alias = "orderRules"))]
+    pub order_rules: Option<Vec<String>>,
+}
+
+/// The grouped options as `(group, option, flat option, inverted)`,
+/// only boolean options can be inverted.
+const GROUPED_OPTIONS: &[(&str, &str, &str, bool)] = &[
+    ("array", "auto_collapse", "array_auto_collapse", false),
+    ("array", "auto_expand", "array_auto_expand", false),
+    ("array", "trailing_comma", "array_trailing_comma", false),
+    ("array", "bracket_spacing", "compact_arrays", true),
+    ("array", "reorder", "reorder_arrays", false),
+    ("array", "expand_threshold", "array_expand_threshold", false),
+    ("inline_table", "expand", "inline_table_expand", false),
+    (
+        "inline_table",
+        "brace_spacing",
+        "compact_inline_tables",
+        true,
+    ),
+    ("keys", "reorder", "reorder_keys", false),
+    ("keys", "quotes", "key_quotes", false),
+    ("keys", "order_rules", "key_order_rules", false),
+];
+
+/// Translate a grouped option (e.g. `array.auto_collapse`)
+/// into the flat option and its value.
+fn grouped_option_from_str(
+    key: &str,
+    value: &str,
+) -> Result<Option<(&'static str, String)>, OptionParseError> {
+    let Some((group, name)) = key.split_once('.') else {
+        return Ok(None);
+    };
+
+    let Some((_, _, legacy, inverted)) = GROUPED_OPTIONS
+        .iter()
+        .find(|(g, n, ..)| *g == group && *n == name)
+    else {
+        return Ok(None);
+    };
+
+    if !inverted {
+        return Ok(Some((legacy, value.to_string())));
+    }
+
+    let value: bool = value
+        .parse()
+        .map_err(|error| OptionParseError::InvalidValue {
+            key: key.into(),
+            error: Box::new(error),
+        })?;
+
+    Ok(Some((legacy, (!value).to_string())))
+}
+
 impl Default for Options {
     fn default() -> Self {
         Options {
@@ -161,11 +604,77 @@ impl Default for Options {
             reorder_keys: false,
             reorder_arrays: false,
             crlf: false,
+            reflow_multiline_string_indent: false,
+            preserve_bom: true,
+            table_spacing: None,
+            convert_long_inline_tables: false,
+            prefer_dotted_keys_for_single_entries: false,
+            expand_dotted_keys: false,
+            max_dotted_key_depth: 3,
+            hex_case: HexCase::Keep,
+            integer_grouping: IntegerGrouping::Keep,
+            datetime_style: DateTimeStyle::Keep,
+            reorder_tables: false,
+            array_layout: ArrayLayout::Auto,
+            expand_arrays_paths: Vec::new(),
+            sort_string_arrays: Vec::new(),
+            array_expand_threshold: None,
+            key_quotes: KeyQuotes::Keep,
+            key_order_rules: Vec::new(),
         }
     }
 }
 
 impl Options {
+    fn update_groups(
+        &mut self,
+        array: Option<ArrayOptions>,
+        inline_table: Option<InlineTableOptions>,
+        keys: Option<KeyOptions>,
+    ) {
+        if let Some(array) = array {
+            if let Some(v) = array.auto_collapse {
+                self.array_auto_collapse = v;
+            }
+            if let Some(v) = array.auto_expand {
+                self.array_auto_expand = v;
+            }
+            if let Some(v) = array.trailing_comma {
+                self.array_trailing_comma = v;
+            }
+            if let Some(v) = array.bracket_spacing {
+                self.compact_arrays = !v;
+            }
+            if let Some(v) = array.reorder {
+                self.reorder_arrays = v;
+            }
+            if let Some(v) = array.expand_threshold {
+                self.array_expand_threshold = Some(v);
+            }
+        }
+
+        if let Some(inline_table) = inline_table {
+            if let Some(v) = inline_table.expand {
+                self.inline_table_expand = v;
+            }
+            if let Some(v) = inline_table.brace_spacing {
+                self.compact_inline_tables = !v;
+            }
+        }
+
+        if let Some(keys) = keys {
+            if let Some(v) = keys.reorder {
+                self.reorder_keys = v;
+            }
+            if let Some(v) = keys.quotes {
+                self.key_quotes = v;
+            }
+            if let Some(v) = keys.order_rules {
+                self.key_order_rules = v;
+            }
+        }
+    }
+
     fn newline(&self) -> &'static str {
         if self.crlf {
             "\r\n"
@@ -185,6 +694,10 @@ struct Context {
     force_multiline: bool,
     errors: Rc<[TextRange]>,
     scopes: Rc<ScopedOptions>,
+    /// The arrays that are expanded, see [`Options::expand_arrays_paths`].
+    expanded_arrays: Rc<[TextRange]>,
+    /// The arrays whose values are sorted, see [`Options::sort_string_arrays`].
+    sorted_arrays: Rc<[TextRange]>,
 }
 
 impl Default for Context {
@@ -194,6 +707,8 @@ impl Default for Context {
             force_multiline: Default::default(),
             errors: Rc::from([]),
             scopes: Default::default(),
+            expanded_arrays: Rc::from([]),
+            sorted_arrays: Rc::from([]),
         }
     }
 }
@@ -240,6 +755,59 @@ pub fn format(src: &str, options: Options) -> String {
     format_impl(p.into_syntax(), options, ctx)
 }
 
+/// The result of [`check`].
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct CheckResult {
+    /// The formatted text.
+    pub formatted: String,
+    /// The line based edits that turn the input into the formatted text,
+    /// see [`format_edits`].
+    ///
+    /// There are no edits if the input was already formatted.
+    pub edits: Vec<(TextRange, String)>,
+    /// The parts of the input that the options could not be applied to,
+    /// these don't affect whether the input is formatted.
+    pub issues: Vec<CheckIssue>,
+}
+
+/// A part of the input that the options could not be applied to, see [`check`].
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct CheckIssue {
+    pub range: TextRange,
+    pub message: String,
+}
+
+impl CheckResult {
+    /// Whether the input was already formatted.
+    pub fn is_formatted(&self) -> bool {
+        self.edits.is_empty()
+    }
+}
+
+/// Checks whether a TOML document is formatted without rewriting it,
+/// the differing lines are returned as edits.
+pub fn check(src: &str, options: Options) -> CheckResult {
+    let issues = matching_arrays(
+        &crate::parser::parse(src).into_syntax(),
+        &options.sort_string_arrays,
+    )
+    .into_iter()
+    .filter(|(_, array)| !is_string_array(array))
+    .map(|(range, _)| CheckIssue {
+        range,
+        message: "the array is not sorted because not all of its values are strings".into(),
+    })
+    .collect();
+
+    let formatted = format(src, options);
+
+    CheckResult {
+        edits: diff::line_edits(src, &formatted),
+        formatted,
+        issues,
+    }
+}
+
 /// Formats a parsed TOML syntax tree.
 pub fn format_syntax(node: SyntaxNode, options: Options) -> String {
     let mut s = format_impl(node, options.clone(), Context::default());
@@ -253,6 +821,191 @@ pub fn format_syntax(node: SyntaxNode, options: Options) -> String {
     s
 }
 
+/// Formats a parsed TOML syntax tree, and returns the edits
+/// that turn the original text into the formatted one.
+///
+/// Only the lines that are changed by the formatter are replaced,
+/// the result of applying the edits is the same as [`format_syntax`].
+pub fn format_edits(node: SyntaxNode, options: Options) -> Vec<(TextRange, String)> {
+    let original = node.to_string();
+    diff::line_edits(&original, &format_syntax(node, options))
+}
+
+/// Same as [`format_edits`], but the result of applying
+/// the edits is the same as [`format_with_path_scopes`].
+///
+/// **This doesn't check errors of the DOM.**
+pub fn format_edits_with_path_scopes<I, S>(
+    dom: Node,
+    options: Options,
+    errors: &[TextRange],
+    scopes: I,
+) -> Result<Vec<(TextRange, String)>, dom::Error>
+where
+    I: IntoIterator<Item = (S, OptionsIncomplete)>,
+    S: AsRef<str>,
+{
+    let original = dom.syntax().unwrap().to_string();
+    let formatted = format_with_path_scopes(dom, options, errors, scopes)?;
+    Ok(diff::line_edits(&original, &formatted))
+}
+
+/// Formats the top-level entries, table headers and comments of a parsed
+/// TOML syntax tree that overlap the given range.
+///
+/// The range is expanded to complete entries and headers, and also to
+/// complete groups of entries if keys are reordered, or to the entire
+/// document if tables are reordered. The entire tree is
+/// formatted so that the indentation and alignment are the same as if it was
+/// formatted completely, but only the formatted text for the range is returned
+/// along with the range of the original text that it replaces.
+///
+/// Returns [`None`] if the range has no entries or table headers.
+pub fn format_range(
+    node: SyntaxNode,
+    range: TextRange,
+    options: Options,
+) -> Option<(TextRange, String)> {
+    let context = Context {
+        errors: node
+            .descendants_with_tokens()
+            .filter(|e| e.kind() == ERROR)
+            .map(|e| e.text_range())
+            .collect(),
+        ..Context::default()
+    };
+
+    format_range_impl(node, range, options, context)
+}
+
+/// Same as [`format_range`], but with scopes like [`format_with_path_scopes`].
+///
+/// **This doesn't check errors of the DOM.**
+pub fn format_range_with_path_scopes<I, S>(
+    dom: Node,
+    range: TextRange,
+    options: Options,
+    errors: &[TextRange],
+    scopes: I,
+) -> Result<Option<(TextRange, String)>, dom::Error>
+where
+    I: IntoIterator<Item = (S, OptionsIncomplete)>,
+    S: AsRef<str>,
+{
+    let c = Context {
+        errors: errors.into(),
+        scopes: Rc::new(path_scopes(&dom, scopes)?),
+        ..Context::default()
+    };
+
+    Ok(format_range_impl(
+        dom.syntax().unwrap().clone().into_node().unwrap(),
+        range,
+        options,
+        c,
+    ))
+}
+
+fn format_range_impl(
+    node: SyntaxNode,
+    range: TextRange,
+    options: Options,
+    context: Context,
+) -> Option<(TextRange, String)> {
+    let elements = top_level_elements(&node);
+
+    let in_range = |element: &SyntaxElement| {
+        let r = element.text_range();
+        if range.is_empty() {
+            r.contains_inclusive(range.start())
+        } else {
+            r.start() < range.end() && range.start() < r.end()
+        }
+    };
+
+    let mut first = elements.iter().position(in_range)?;
+    let mut last = elements.iter().rposition(in_range)?;
+
+    if elements[first..=last].iter().all(|e| e.kind() == COMMENT) {
+        return None;
+    }
+
+    // The moved tables could end up anywhere.
+    if options.reorder_tables {
+        first = 0;
+        last = elements.len() - 1;
+    }
+
+    if options.reorder_keys {
+        while first > 0 && same_entry_group(&elements[first - 1], &elements[first]) {
+            first -= 1;
+        }
+
+        while last + 1 < elements.len() && same_entry_group(&elements[last], &elements[last + 1]) {
+            last += 1;
+        }
+    }
+
+    let formatted = format_impl(node, options, context);
+    let formatted_elements = top_level_elements(&crate::parser::parse(&formatted).into_syntax());
+
+    // The formatter does not add or remove any of the elements.
+    if formatted_elements.len() != elements.len() {
+        return None;
+    }
+
+    let original_range = TextRange::new(
+        line_start(&elements[first]),
+        elements[last].text_range().end(),
+    );
+    let formatted_range = TextRange::new(
+        line_start(&formatted_elements[first]),
+        formatted_elements[last].text_range().end(),
+    );
+
+    Some((original_range, formatted[formatted_range].to_string()))
+}
+
+/// The entries, table headers and comments of the root.
+fn top_level_elements(root: &SyntaxNode) -> Vec<SyntaxElement> {
+    root.children_with_tokens()
+        .filter(|c| {
+            matches!(
+                c.kind(),
+                ENTRY | TABLE_HEADER | TABLE_ARRAY_HEADER | COMMENT
+            )
+        })
+        .collect()
+}
+
+/// Whether the consecutive top-level elements are entries or comments that are
+/// formatted (and reordered) together, i.e. they have no blank lines between them.
+fn same_entry_group(element: &SyntaxElement, next: &SyntaxElement) -> bool {
+    if !matches!(element.kind(), ENTRY | COMMENT) || !matches!(next.kind(), ENTRY | COMMENT) {
+        return false;
+    }
+
+    let mut sibling = element.next_sibling_or_token();
+    let mut newline_count = 0;
+
+    while let Some(s) = sibling.filter(|s| s != next) {
+        if s.kind() == NEWLINE {
+            newline_count += s.as_token().map_or(0, |t| t.text().newline_count());
+        }
+        sibling = s.next_sibling_or_token();
+    }
+
+    newline_count <= 1
+}
+
+/// The start of the element including its indentation.
+fn line_start(element: &SyntaxElement) -> TextSize {
+    match element.prev_sibling_or_token() {
+        Some(ws) if ws.kind() == WHITESPACE => ws.text_range().start(),
+        _ => element.text_range().start(),
+    }
+}
+
 /// Formats a DOM root node with given scopes.
 ///
 /// **This doesn't check errors of the DOM.**
@@ -296,49 +1049,119 @@ where
     I: IntoIterator<Item = (S, OptionsIncomplete)>,
     S: AsRef<str>,
 {
-    let mut c = Context {
+    let c = Context {
         errors: errors.into(),
+        scopes: Rc::new(path_scopes(&dom, scopes)?),
         ..Context::default()
     };
 
+    let mut s = format_impl(
+        dom.syntax().unwrap().clone().into_node().unwrap(),
+        options.clone(),
+        c,
+    );
+
+    s = s.trim_end().into();
+
+    if options.trailing_newline {
+        s += options.newline();
+    }
+
+    Ok(s)
+}
+
+/// The text ranges of the nodes matched by the key patterns of the scopes.
+///
+/// Scopes of more deeply nested nodes come after the ones of their parents,
+/// so that the options of the most specific scope are applied last.
+/// Otherwise the order of the given scopes is kept.
+fn path_scopes<I, S>(dom: &Node, scopes: I) -> Result<ScopedOptions, dom::Error>
+where
+    I: IntoIterator<Item = (S, OptionsIncomplete)>,
+    S: AsRef<str>,
+{
     let mut s = Vec::new();
 
     for (scope, opts) in scopes {
         let keys: Keys = scope.as_ref().parse()?;
         let matched = dom.find_all_matches(keys, false)?;
 
-        for (_, node) in matched {
-            s.extend(node.text_ranges().into_iter().map(|r| (r, opts.clone())));
+        for (keys, node) in matched {
+            s.extend(node.text_ranges().map(|r| (keys.len(), r, opts.clone())));
         }
     }
 
-    c.scopes = Rc::new(ScopedOptions::from_iter(s));
+    s.sort_by_key(|(depth, _, _)| *depth);
 
-    let mut s = format_impl(
-        dom.syntax().unwrap().clone().into_node().unwrap(),
-        options.clone(),
-        c,
-    );
+    Ok(s.into_iter().map(|(_, r, opts)| (r, opts)).collect())
+}
 
-    s = s.trim_end().into();
+/// The arrays at the keys that match the patterns of [`Options::expand_arrays_paths`]
+/// or [`Options::sort_string_arrays`], arrays of tables are not included.
+///
+/// Invalid patterns are ignored, these are rejected by [`Options::update_from_str`].
+fn matching_arrays(root: &SyntaxNode, patterns: &[String]) -> Vec<(TextRange, Array)> {
+    if patterns.is_empty() {
+        return Vec::new();
+    }
 
-    if options.trailing_newline {
-        s += options.newline();
+    let dom = Node::from_syntax(root.clone().into());
+    let mut arrays = Vec::new();
+
+    for pattern in patterns {
+        let Ok(keys) = pattern.parse::<Keys>() else {
+            continue;
+        };
+
+        let Ok(matches) = dom.find_all_matches(keys, false) else {
+            continue;
+        };
+
+        arrays.extend(matches.filter_map(|(_, node)| {
+            let array = node.as_array()?;
+            let range = array
+                .syntax()
+                .filter(|syntax| syntax.kind() == ARRAY)?
+                .text_range();
+
+            Some((range, array.clone()))
+        }));
     }
 
-    Ok(s)
+    arrays
 }
 
-fn format_impl(node: SyntaxNode, options: Options, context: Context) -> String {
+fn is_string_array(array: &Array) -> bool {
+    array
+        .items()
+        .read()
+        .iter()
+        .all(|item| matches!(item, Node::Str(_)))
+}
+
+fn format_impl(node: SyntaxNode, options: Options, mut context: Context) -> String {
     assert!(node.kind() == ROOT);
+    let bom = node.first_token().filter(|t| t.kind() == BOM);
+
+    context.expanded_arrays = matching_arrays(&node, &options.expand_arrays_paths)
+        .into_iter()
+        .map(|(range, _)| range)
+        .collect();
+    context.sorted_arrays = matching_arrays(&node, &options.sort_string_arrays)
+        .into_iter()
+        .filter(|(_, array)| is_string_array(array))
+        .map(|(range, _)| range)
+        .collect();
+
     let mut formatted = format_root(node, &options, &context);
 
-    if formatted.ends_with("\r\n") {
-        formatted.truncate(formatted.len() - 2);
-    } else if formatted.ends_with('\n') {
-        formatted.truncate(formatted.len() - 1);
+    if let Some(bom) = bom.filter(|_| options.preserve_bom) {
+        formatted.insert_str(0, bom.text());
     }
 
+    // Blank lines at the end are removed regardless of `allowed_blank_lines`.
+    formatted.truncate(formatted.trim_end_matches(['\r', '\n']).len());
+
     if options.trailing_newline {
         formatted += options.newline();
     }
@@ -351,6 +1174,9 @@ struct FormattedEntry {
     key: String,
     value: String,
     comment: Option<String>,
+    /// The comments directly before the entry,
+    /// they are moved together with it when the keys are reordered.
+    comments: Vec<String>,
 }
 
 impl PartialEq for FormattedEntry {
@@ -423,6 +1249,21 @@ fn format_root(node: SyntaxNode, options: &Options, context: &Context) -> String
     let mut last_table_key = None;
     let mut table_key_indent_history: Vec<(Keys, usize)> = Vec::new();
 
+    // Tables that are created from entries, these are added
+    // after the entries of the current table.
+    let mut converted_tables: Vec<ConvertedTable> = Vec::new();
+
+    // The header of the current table, and where its entries start.
+    let mut section: Option<(SyntaxNode, String)> = None;
+    let mut section_start = 0;
+
+    let dotted_keys = DottedKeys::new(&node, options, &context);
+    let mut skip_collapsed_section = false;
+
+    // The line of a converted entry is removed, so is its line ending
+    // unless it is skipped already.
+    let mut skip_converted_newline = false;
+
     fn add_comments(
         comments: &mut Vec<String>,
         formatted: &mut String,
@@ -442,11 +1283,42 @@ fn format_root(node: SyntaxNode, options: &Options, context: &Context) -> String
         were_comments
     }
 
+    /// Adds the entries followed by the comments that are not
+    /// directly before an entry.
+    fn add_entries_and_comments(
+        entry_group: &mut Vec<FormattedEntry>,
+        comments: &mut Vec<String>,
+        formatted: &mut String,
+        context: &Context,
+        options: &Options,
+    ) -> bool {
+        let were_entries = add_entries(entry_group, formatted, options, context);
+
+        if were_entries && !comments.is_empty() {
+            *formatted += options.newline();
+        }
+
+        add_comments(comments, formatted, context, options) || were_entries
+    }
+
     let mut dangling_newline_count = 0;
     let mut scoped_options = options.clone();
 
-    for c in node.children_with_tokens() {
+    for c in reordered_elements(&node, options, &context) {
         if context.error_at(c.text_range()) {
+            // The deferred entries and comments are before the invalid syntax.
+            if add_entries_and_comments(
+                &mut entry_group,
+                &mut comment_group,
+                &mut formatted,
+                &context,
+                &scoped_options,
+            ) && c.kind() != NEWLINE
+            {
+                formatted += scoped_options.newline();
+            }
+            skip_newlines = 0;
+
             formatted += &c.to_string();
             continue;
         }
@@ -461,9 +1333,49 @@ fn format_root(node: SyntaxNode, options: &Options, context: &Context) -> String
                         skip_newlines = 0;
                     }
 
+                    if dotted_keys.is_collapsed(&node) {
+                        // The comments are moved together with the entry.
+                        comment_group.clear();
+                        skip_newlines = 0;
+                        skip_collapsed_section = true;
+                        continue;
+                    }
+                    skip_collapsed_section = false;
+
+                    add_collapsed_entries(
+                        dotted_keys.collapsed_into(section.as_ref().map(|(header, _)| header)),
+                        &mut formatted,
+                        section_start,
+                        &scoped_options,
+                        &context,
+                    );
+
+                    // The created tables are indented as if they were there originally.
+                    if let Some(table) = converted_tables.last() {
+                        context.indent_level = table.context.indent_level;
+                        last_table_key = Some(table.keys.clone());
+                        table_key_indent_history.extend(
+                            converted_tables
+                                .iter()
+                                .map(|t| (t.keys.clone(), t.context.indent_level)),
+                        );
+                    }
+
+                    add_tables(&mut converted_tables, &mut formatted, &scoped_options);
+
                     scoped_options = options.clone();
                     context.update_options(&mut scoped_options, c_range);
 
+                    if let Some(spacing) = scoped_options.table_spacing {
+                        // The blank lines go before the comments of the header.
+                        let content_len = formatted.trim_end_matches(['\r', '\n']).len();
+
+                        if content_len > 0 {
+                            formatted.truncate(content_len);
+                            formatted.extend(repeat_n(scoped_options.newline(), spacing + 1));
+                        }
+                    }
+
                     // We treat everything as indented other than table headers from now on.
                     if scoped_options.indent_entries && context.indent_level == 0 {
                         context.indent_level = 1;
@@ -487,6 +1399,12 @@ fn format_root(node: SyntaxNode, options: &Options, context: &Context) -> String
                         last_table_key = Some(key);
                     }
 
+                    let mut section_key = String::new();
+                    if let Some(key) = node.first_child() {
+                        format_key(key, &mut section_key, &scoped_options, &context);
+                    }
+                    section = Some((node.clone(), section_key));
+
                     let mut header_context = context.clone();
 
                     if scoped_options.indent_entries {
@@ -515,27 +1433,118 @@ fn format_root(node: SyntaxNode, options: &Options, context: &Context) -> String
                         formatted += " ";
                         formatted += &c;
                     }
+
+                    section_start = formatted.len();
                 }
                 ENTRY => {
+                    if skip_collapsed_section {
+                        continue;
+                    }
+
                     scoped_options = options.clone();
                     context.update_options(&mut scoped_options, c_range);
 
-                    if add_comments(
-                        &mut comment_group,
-                        &mut formatted,
-                        &context,
-                        &scoped_options,
-                    ) {
-                        formatted += scoped_options.newline();
-                        skip_newlines = 0;
+                    let mut entry = format_entry(node.clone(), &scoped_options, &context);
+
+                    if scoped_options.reflow_multiline_string_indent {
+                        reflow_entry_string(&node, &mut entry, &scoped_options, &context);
+                    }
+
+                    let mut table_context = context.clone();
+                    table_context.indent_level = match section {
+                        Some(_) if scoped_options.indent_tables => context.indent_level + 1,
+                        Some(_) => context.indent_level,
+                        None => usize::from(scoped_options.indent_entries),
+                    };
+
+                    let section_keys = match &section {
+                        Some(_) => last_table_key.clone().unwrap_or_else(Keys::empty),
+                        None => Keys::empty(),
+                    };
+
+                    if let Some(table) = long_inline_table(&entry, &scoped_options, &context) {
+                        // The comments are left in place.
+                        if add_entries_and_comments(
+                            &mut entry_group,
+                            &mut comment_group,
+                            &mut formatted,
+                            &context,
+                            &scoped_options,
+                        ) {
+                            formatted += scoped_options.newline();
+                            skip_newlines = 0;
+                        }
+
+                        format_inline_table_as_tables(
+                            section
+                                .as_ref()
+                                .map(|(_, key)| (key.as_str(), &section_keys)),
+                            entry,
+                            table,
+                            &mut converted_tables,
+                            &scoped_options,
+                            &table_context,
+                        );
+
+                        skip_converted_newline = entry_group.is_empty();
+                        continue;
                     }
 
-                    entry_group.push(format_entry(node, &scoped_options, &context));
+                    if let Some(keys) =
+                        dotted_keys.expanded_table(&section_keys, &node, &scoped_options)
+                    {
+                        if add_entries_and_comments(
+                            &mut entry_group,
+                            &mut comment_group,
+                            &mut formatted,
+                            &context,
+                            &scoped_options,
+                        ) {
+                            formatted += scoped_options.newline();
+                            skip_newlines = 0;
+                        }
+
+                        let mut parts = node
+                            .first_child()
+                            .map(|key| key_parts(&key))
+                            .unwrap_or_default();
+                        entry.key = parts.pop().unwrap_or_default();
+
+                        match converted_tables.iter_mut().find(|t| t.keys == keys) {
+                            Some(table) => table.entries.push(entry),
+                            None => {
+                                let mut key = parts.join(".");
+                                if let Some((_, section_key)) = &section {
+                                    key = format!("{section_key}.{key}");
+                                }
+
+                                converted_tables.push(ConvertedTable {
+                                    keys,
+                                    header: table_header(
+                                        &key,
+                                        None,
+                                        &scoped_options,
+                                        &table_context,
+                                    ),
+                                    entries: vec![entry],
+                                    options: scoped_options.clone(),
+                                    context: table_context,
+                                });
+                            }
+                        }
+
+                        skip_converted_newline = entry_group.is_empty();
+                        continue;
+                    }
+
+                    entry.comments = mem::take(&mut comment_group);
+                    entry_group.push(entry);
                     skip_newlines += 1;
                 }
                 _ => unreachable!(),
             },
             NodeOrToken::Token(token) => match token.kind() {
+                NEWLINE | WHITESPACE if skip_collapsed_section => {}
                 NEWLINE => {
                     let mut newline_count = token.text().newline_count();
 
@@ -550,14 +1559,28 @@ fn format_root(node: SyntaxNode, options: &Options, context: &Context) -> String
                         }
                     }
 
+                    if skip_converted_newline {
+                        newline_count = newline_count.saturating_sub(1);
+                        skip_converted_newline = false;
+                    }
+
+                    if formatted.is_empty() && entry_group.is_empty() && comment_group.is_empty() {
+                        // There is no line before, all of the newlines are blank lines.
+                        formatted.extend(repeat_n(
+                            scoped_options.newline(),
+                            newline_count.min(scoped_options.allowed_blank_lines),
+                        ));
+                        continue;
+                    }
+
                     if newline_count > 1 {
-                        add_comments(
+                        add_entries_and_comments(
+                            &mut entry_group,
                             &mut comment_group,
                             &mut formatted,
                             &context,
                             &scoped_options,
                         );
-                        add_entries(&mut entry_group, &mut formatted, &scoped_options, &context);
                         skip_newlines = 0;
                     }
 
@@ -566,30 +1589,258 @@ fn format_root(node: SyntaxNode, options: &Options, context: &Context) -> String
                     );
                 }
                 COMMENT => {
-                    if add_entries(&mut entry_group, &mut formatted, &scoped_options, &context) {
-                        formatted += scoped_options.newline();
-                        skip_newlines = 0;
-                    }
                     comment_group.push(token.text().to_string());
                     skip_newlines += 1;
                 }
-                WHITESPACE => {}
+                WHITESPACE | BOM => {}
                 _ => formatted += token.text(),
             },
         }
     }
 
-    add_comments(
+    add_entries_and_comments(
+        &mut entry_group,
         &mut comment_group,
         &mut formatted,
         &context,
         &scoped_options,
     );
-    add_entries(&mut entry_group, &mut formatted, &scoped_options, &context);
+    add_collapsed_entries(
+        dotted_keys.collapsed_into(section.as_ref().map(|(header, _)| header)),
+        &mut formatted,
+        section_start,
+        &scoped_options,
+        &context,
+    );
+    add_tables(&mut converted_tables, &mut formatted, &scoped_options);
+
+    formatted
+}
+
+/// The inline table value of the entry if it should be converted
+/// to a standard table, see [`Options::convert_long_inline_tables`].
+fn long_inline_table(
+    entry: &FormattedEntry,
+    options: &Options,
+    context: &Context,
+) -> Option<SyntaxNode> {
+    // The placement of the tables would be unreliable around invalid syntax.
+    if !options.convert_long_inline_tables || !context.errors.is_empty() {
+        return None;
+    }
+
+    let table = entry
+        .syntax
+        .as_node()?
+        .children()
+        .find(|n| n.kind() == VALUE)?
+        .first_child()
+        .filter(|n| n.kind() == INLINE_TABLE)?;
+
+    let mut lines = entry.value.split('\n');
+
+    let mut chars_count = context.indent_level * options.indent_string.chars().count()
+        + entry.key.chars().count()
+        + if options.compact_entries { 1 } else { 3 }
+        + lines.next().unwrap_or_default().chars().count();
+
+    if lines.next().is_none() {
+        chars_count += entry.comment.as_ref().map_or(0, |c| c.chars().count() + 1);
+    }
+
+    (chars_count > options.column_width).then_some(table)
+}
+
+/// A standard table that is created from entries of an other table, see
+/// [`Options::convert_long_inline_tables`] and [`Options::expand_dotted_keys`].
+struct ConvertedTable {
+    keys: Keys,
+    /// The formatted header with its trailing comment.
+    header: String,
+    entries: Vec<FormattedEntry>,
+    options: Options,
+    /// The context of the entries.
+    context: Context,
+}
+
+/// Formats the header of a created table.
+fn table_header(
+    key: &str,
+    comment: Option<String>,
+    options: &Options,
+    context: &Context,
+) -> String {
+    let mut formatted = String::new();
+
+    if options.indent_tables {
+        let mut header_context = context.clone();
+        if options.indent_entries {
+            header_context.indent_level = header_context.indent_level.saturating_sub(1);
+        }
+        formatted.extend(header_context.indent(options));
+    }
+
+    formatted += "[";
+    formatted += key;
+    formatted += "]";
+
+    if let Some(c) = comment {
+        formatted += " ";
+        formatted += &c;
+    }
 
     formatted
 }
 
+/// Formats the inline table of an entry as a standard table
+/// under the parent table given by its formatted and its actual keys,
+/// the trailing comment of the entry is moved to the header.
+///
+/// The table is added to `tables` followed by the tables
+/// converted from its own long inline tables.
+fn format_inline_table_as_tables(
+    parent: Option<(&str, &Keys)>,
+    entry: FormattedEntry,
+    table: SyntaxNode,
+    tables: &mut Vec<ConvertedTable>,
+    options: &Options,
+    context: &Context,
+) {
+    let Some(entry_keys) = entry
+        .syntax
+        .as_node()
+        .and_then(|n| n.first_child())
+        .filter(|n| n.kind() == KEY)
+        .map(|n| Keys::from_syntax(n.into()))
+    else {
+        return;
+    };
+
+    let (key, keys) = match parent {
+        Some((key, keys)) => (
+            format!("{key}.{}", entry.key),
+            keys.extend(entry_keys.iter().cloned()),
+        ),
+        None => (entry.key, entry_keys),
+    };
+
+    let index = tables.len();
+    tables.push(ConvertedTable {
+        keys: keys.clone(),
+        header: table_header(&key, entry.comment, options, context),
+        entries: Vec::new(),
+        options: options.clone(),
+        context: context.clone(),
+    });
+
+    let mut nested_context = context.clone();
+    if options.indent_tables {
+        nested_context.indent_level += 1;
+    }
+
+    for node in table.children().filter(|n| n.kind() == ENTRY) {
+        let entry = format_entry(node, options, context);
+
+        match long_inline_table(&entry, options, context) {
+            Some(nested) => format_inline_table_as_tables(
+                Some((&key, &keys)),
+                entry,
+                nested,
+                tables,
+                options,
+                &nested_context,
+            ),
+            None => tables[index].entries.push(entry),
+        }
+    }
+}
+
+/// Adds the created tables after the formatted text,
+/// but before its trailing newlines.
+fn add_tables(tables: &mut Vec<ConvertedTable>, formatted: &mut String, options: &Options) {
+    if tables.is_empty() {
+        return;
+    }
+
+    let content_len = formatted.trim_end_matches(['\r', '\n']).len();
+    let mut trailing = formatted.split_off(content_len);
+
+    // The line ending of the last table.
+    if trailing.is_empty() {
+        trailing += options.newline();
+    }
+
+    let spacing = options
+        .table_spacing
+        .unwrap_or_else(|| options.allowed_blank_lines.min(1));
+
+    for mut table in tables.drain(..) {
+        if !formatted.is_empty() {
+            formatted.extend(repeat_n(options.newline(), spacing + 1));
+        }
+
+        *formatted += &table.header;
+
+        if !table.entries.is_empty() {
+            *formatted += table.options.newline();
+            add_entries(
+                &mut table.entries,
+                formatted,
+                &table.options,
+                &table.context,
+            );
+        }
+    }
+
+    *formatted += &trailing;
+}
+
+/// Adds the entries of the tables that are collapsed into the current table
+/// after the formatted text, but before its trailing newlines.
+///
+/// The entries are separated from the rest of the entries by a blank line.
+fn add_collapsed_entries(
+    tables: &[CollapsedTable],
+    formatted: &mut String,
+    section_start: usize,
+    options: &Options,
+    context: &Context,
+) {
+    if tables.is_empty() {
+        return;
+    }
+
+    let content_len = formatted.trim_end_matches(['\r', '\n']).len();
+    let mut trailing = formatted.split_off(content_len);
+
+    // The line ending of the last entry.
+    if trailing.is_empty() {
+        trailing += options.newline();
+    }
+
+    if content_len > section_start && options.allowed_blank_lines > 0 {
+        formatted.extend(options.newlines(2));
+    } else if content_len > 0 {
+        *formatted += options.newline();
+    }
+
+    let mut entry_group = Vec::new();
+
+    for table in tables {
+        let mut entry_options = options.clone();
+        context.update_options(&mut entry_options, table.entry.text_range());
+
+        let mut entry = format_entry(table.entry.clone(), &entry_options, context);
+        entry.key = format!("{}.{}", table.prefix, entry.key);
+        entry.comments = table.comments.clone();
+        entry_group.push(entry);
+    }
+
+    add_entries(&mut entry_group, formatted, options, context);
+
+    *formatted += &trailing;
+}
+
 /// Determine the indentation level based on 2 consecutive table keys.
 fn table_indent_level(
     history: &[(Keys, usize)],
@@ -633,12 +1884,30 @@ fn add_entries(
 
     if options.reorder_keys {
         entry_group.sort();
+
+        if !options.key_order_rules.is_empty() {
+            let rules = options
+                .key_order_rules
+                .iter()
+                .filter_map(|rule| rule.parse::<Keys>().ok())
+                .collect::<Vec<_>>();
+
+            // The sort is stable, so the rest of the keys stay in alphabetical order.
+            entry_group.sort_by_cached_key(|entry| {
+                let keys = entry.key.parse::<Keys>().ok();
+                rules
+                    .iter()
+                    .position(|rule| Some(rule) == keys.as_ref())
+                    .unwrap_or(rules.len())
+            });
+        }
     }
 
     let indent_chars_count = context.indent_level * options.indent_string.chars().count();
 
     // We check for too long lines, and try to expand them if possible.
-    // We don't take vertical alignment into account for simplicity.
+    // The padding of aligned entries is left out if it makes a line too long,
+    // see [`format_rows`].
     if options.array_auto_expand {
         for entry in entry_group.iter_mut() {
             let comment_chars_count = entry
@@ -685,8 +1954,8 @@ fn add_entries(
 
                     entry.value.clear();
 
+                    // The trailing comment of the value is the one the entry has already.
                     if let Some(c) = value.trailing_comment() {
-                        debug_assert!(entry.comment.is_none());
                         entry.comment = Some(c);
                     }
 
@@ -697,43 +1966,59 @@ fn add_entries(
         }
     }
 
-    // Transform the entries into generic rows that can be aligned.
-    let rows = entry_group
-        .drain(0..)
-        .map(|e| {
-            let mut row = Vec::with_capacity(5);
+    // Transform the entries into generic rows that can be aligned,
+    // the comments before the entries separate the aligned blocks.
+    let mut blocks: Vec<(Vec<String>, Vec<Vec<String>>)> = Vec::new();
 
-            row.push(context.indent(options).collect::<String>());
-            row.push(e.key);
-            row.push("=".to_string());
-            row.push(e.value);
-            if let Some(c) = e.comment {
-                row.push(c);
-            }
+    for e in entry_group.drain(0..) {
+        let mut row = Vec::with_capacity(5);
 
-            row
-        })
-        .collect::<Vec<_>>();
-
-    *formatted += &format_rows(
-        if !options.align_entries && !options.align_comments {
-            0..0
-        } else if !options.align_entries && options.align_comments {
-            3..usize::MAX
-        } else if options.align_entries && !options.align_comments {
-            0..3
-        } else {
-            0..usize::MAX
-        },
-        if options.compact_entries {
-            3..usize::MAX
-        } else {
-            1..usize::MAX
-        },
-        &rows,
-        options.newline(),
-        " ",
-    );
+        row.push(context.indent(options).collect::<String>());
+        row.push(e.key);
+        row.push("=".to_string());
+        row.push(e.value);
+        if let Some(c) = e.comment {
+            row.push(c);
+        }
+
+        match blocks.last_mut() {
+            Some((_, rows)) if e.comments.is_empty() => rows.push(row),
+            _ => blocks.push((e.comments, vec![row])),
+        }
+    }
+
+    for (idx, (comments, rows)) in blocks.iter().enumerate() {
+        if idx != 0 {
+            *formatted += options.newline();
+        }
+
+        for comment in comments {
+            formatted.extend(context.indent(options));
+            *formatted += comment;
+            *formatted += options.newline();
+        }
+
+        *formatted += &format_rows(
+            if !options.align_entries && !options.align_comments {
+                0..0
+            } else if !options.align_entries && options.align_comments {
+                3..usize::MAX
+            } else if options.align_entries && !options.align_comments {
+                0..3
+            } else {
+                0..usize::MAX
+            },
+            if options.compact_entries {
+                3..usize::MAX
+            } else {
+                1..usize::MAX
+            },
+            rows,
+            options.newline(),
+            " ",
+            options.column_width,
+        );
+    }
 
     were_entries
 }
@@ -776,16 +2061,151 @@ fn format_entry(node: SyntaxNode, options: &Options, context: &Context) -> Forma
         key,
         value,
         comment,
+        comments: Vec::new(),
+    }
+}
+
+/// Re-indent the multi-line basic string value of a top-level entry
+/// based on the original and the new indentation of the entry.
+fn reflow_entry_string(
+    node: &SyntaxNode,
+    entry: &mut FormattedEntry,
+    options: &Options,
+    context: &Context,
+) {
+    let is_multiline_string = node
+        .children()
+        .find(|n| n.kind() == VALUE)
+        .and_then(|v| v.first_token())
+        .is_some_and(|t| t.kind() == MULTI_LINE_STRING);
+
+    if !is_multiline_string {
+        return;
+    }
+
+    // The entry must be at the start of its line.
+    let old_indent = match node.prev_sibling_or_token() {
+        None => String::new(),
+        Some(NodeOrToken::Token(t)) if t.kind() == NEWLINE => String::new(),
+        Some(NodeOrToken::Token(t)) if t.kind() == WHITESPACE => match t.prev_sibling_or_token() {
+            None => t.text().to_string(),
+            Some(NodeOrToken::Token(nl)) if nl.kind() == NEWLINE => t.text().to_string(),
+            _ => return,
+        },
+        _ => return,
+    };
+
+    let new_indent = context.indent(options).collect::<String>();
+
+    entry.value = reflow_multiline_string(&entry.value, &old_indent, &new_indent);
+}
+
+/// Re-indent the lines of a multi-line basic string (including the delimiters)
+/// by replacing the `old_indent` prefix with `new_indent`.
+///
+/// Lines with significant leading whitespace are moved behind a line ending
+/// backslash in the previous line, with the line break escaped instead.
+/// The string is returned unchanged if its value would not be preserved.
+pub(crate) fn reflow_multiline_string(string: &str, old_indent: &str, new_indent: &str) -> String {
+    if old_indent == new_indent {
+        return string.into();
+    }
+
+    let Some(value) = multiline_string_value(string) else {
+        return string.into();
+    };
+
+    reflow_lines(string, old_indent, new_indent)
+        .filter(|reflowed| multiline_string_value(reflowed).as_ref() == Some(&value))
+        .unwrap_or_else(|| string.into())
+}
+
+fn reflow_lines(string: &str, old_indent: &str, new_indent: &str) -> Option<String> {
+    let mut lines: Vec<String> = string.split('\n').map(Into::into).collect();
+
+    // A line break directly after the opening delimiter is not part of the value.
+    let opening_line_break = lines[0].trim_end_matches('\r') == r#"""""#;
+
+    // Whether the leading whitespace of the current line
+    // is trimmed by a preceding line ending backslash.
+    let mut trimmed = ends_with_line_continuation(&lines[0]);
+
+    for idx in 1..lines.len() {
+        let line = lines[idx].clone();
+        let content = line.trim_start_matches([' ', '\t']);
+        let whitespace = &line[..line.len() - content.len()];
+
+        let Some(rest) = whitespace.strip_prefix(old_indent) else {
+            trimmed = ends_with_line_continuation(&line)
+                || (trimmed && content.trim_end_matches('\r').is_empty());
+            continue;
+        };
+
+        if trimmed {
+            lines[idx] = format!("{new_indent}{rest}{content}");
+            trimmed =
+                ends_with_line_continuation(&line) || content.trim_end_matches('\r').is_empty();
+            continue;
+        }
+
+        // The whitespace is part of the value and would be trimmed.
+        if !whitespace.is_empty() {
+            return None;
+        }
+
+        let previous = &mut lines[idx - 1];
+        let crlf = previous.ends_with('\r');
+        if crlf {
+            previous.pop();
+        }
+
+        if !(idx == 1 && opening_line_break) {
+            *previous += if crlf { r"\r\n" } else { r"\n" };
+        }
+
+        previous.push('\\');
+
+        if crlf {
+            previous.push('\r');
+        }
+
+        lines[idx] = format!("{new_indent}{content}");
+        trimmed = ends_with_line_continuation(&line);
+    }
+
+    Some(lines.join("\n"))
+}
+
+/// Whether the line ends with a backslash that escapes the line break.
+fn ends_with_line_continuation(line: &str) -> bool {
+    let line = line.trim_end_matches([' ', '\t', '\r']);
+    let backslashes = line.len() - line.trim_end_matches('\\').len();
+    backslashes % 2 == 1
+}
+
+/// The value of a multi-line basic string, or [`None`] if it is not valid.
+fn multiline_string_value(string: &str) -> Option<String> {
+    let parse = crate::parser::parse(&format!("s = {string}"));
+
+    if !parse.errors.is_empty() {
+        return None;
     }
+
+    let node = parse.into_dom().get("s");
+    node.validate().ok()?;
+    node.as_str().map(|s| s.value().to_string())
 }
 
-fn format_key(node: SyntaxNode, formatted: &mut String, _options: &Options, _context: &Context) {
+fn format_key(node: SyntaxNode, formatted: &mut String, options: &Options, _context: &Context) {
     // Idents and periods without whitespace
     for c in node.children_with_tokens() {
         match c {
             NodeOrToken::Node(_) => {}
             NodeOrToken::Token(t) => match t.kind() {
                 WHITESPACE | NEWLINE => {}
+                IDENT if options.key_quotes == KeyQuotes::Minimal => {
+                    *formatted += &unquoted_key(t);
+                }
                 _ => {
                     *formatted += t.text();
                 }
@@ -794,9 +2214,39 @@ fn format_key(node: SyntaxNode, formatted: &mut String, _options: &Options, _con
     }
 }
 
+/// The key without quotes if it can be a bare key, see [`KeyQuotes::Minimal`].
+fn unquoted_key(token: SyntaxToken) -> String {
+    if !token.text().starts_with(['"', '\'']) {
+        return token.text().into();
+    }
+
+    let key = Key::from_syntax(token.clone().into());
+    if !key.errors().read().is_empty() {
+        return token.text().into();
+    }
+
+    // Keys like `123` or `true` are valid bare keys in entries,
+    // but not in every position, so only identifiers are unquoted.
+    let value = key.value();
+    let mut tokens = SyntaxKind::lexer(value);
+    if quote_key(value) == value && tokens.next() == Some(IDENT) && tokens.next().is_none() {
+        value.into()
+    } else {
+        token.text().into()
+    }
+}
+
 fn format_value(node: SyntaxNode, options: &Options, context: &Context) -> impl FormattedItem {
     let mut value = String::new();
     let mut comment = None;
+
+    // Scopes can also match arrays and inline tables that are nested in entries.
+    let mut scoped_options = options.clone();
+    if let Some(n) = node.first_child() {
+        context.update_options(&mut scoped_options, n.text_range());
+    }
+    let options = &scoped_options;
+
     for c in node.children_with_tokens() {
         match c {
             NodeOrToken::Node(n) => match n.kind() {
@@ -835,6 +2285,12 @@ fn format_value(node: SyntaxNode, options: &Options, context: &Context) -> impl
                     debug_assert!(comment.is_none());
                     comment = Some(t.text().into());
                 }
+                INTEGER | INTEGER_HEX => {
+                    value = format_integer(&t, options);
+                }
+                DATE_TIME_OFFSET | DATE_TIME_LOCAL => {
+                    value = format_date_time(&t, options);
+                }
                 _ => {
                     value = t.text().into();
                 }
@@ -845,6 +2301,60 @@ fn format_value(node: SyntaxNode, options: &Options, context: &Context) -> impl
     (node.into(), value, comment)
 }
 
+/// Formats a date-time based on [`Options::datetime_style`].
+fn format_date_time(token: &SyntaxToken, options: &Options) -> String {
+    let text = token.text();
+
+    let separator = match options.datetime_style {
+        DateTimeStyle::Keep => return text.into(),
+        DateTimeStyle::TUppercase => 'T',
+        DateTimeStyle::Space => ' ',
+    };
+
+    // The date is always `YYYY-MM-DD`, followed by the separator.
+    let (date, time) = text.split_at(10);
+    let time = &time[1..];
+
+    match time.strip_suffix('z') {
+        Some(time) => format!("{date}{separator}{time}Z"),
+        None => format!("{date}{separator}{time}"),
+    }
+}
+
+/// Formats an integer based on [`Options::hex_case`] and [`Options::integer_grouping`].
+fn format_integer(token: &SyntaxToken, options: &Options) -> String {
+    let text = token.text();
+
+    match token.kind() {
+        INTEGER_HEX => {
+            let (prefix, digits) = text.split_at(2);
+
+            match options.hex_case {
+                HexCase::Keep => text.into(),
+                HexCase::Upper => format!("{prefix}{}", digits.to_ascii_uppercase()),
+                HexCase::Lower => format!("{prefix}{}", digits.to_ascii_lowercase()),
+            }
+        }
+        INTEGER if options.integer_grouping == IntegerGrouping::Thousands => {
+            let digits_start = text.len() - text.trim_start_matches(['+', '-']).len();
+            let (sign, digits) = text.split_at(digits_start);
+
+            let digits: Vec<char> = digits.chars().filter(|c| *c != '_').collect();
+
+            let mut formatted = String::from(sign);
+            for (idx, c) in digits.iter().enumerate() {
+                if idx != 0 && idx % 3 == digits.len() % 3 {
+                    formatted.push('_');
+                }
+                formatted.push(*c);
+            }
+
+            formatted
+        }
+        _ => text.into(),
+    }
+}
+
 fn format_inline_table(
     node: SyntaxNode,
     options: &Options,
@@ -902,7 +2412,8 @@ fn format_inline_table(
                     }
                     formatted += "}";
                 }
-                WHITESPACE | COMMA => {}
+                // Newlines are not allowed between the entries.
+                WHITESPACE | COMMA | NEWLINE => {}
                 COMMENT => {
                     debug_assert!(comment.is_none());
                     comment = Some(t.text().into());
@@ -920,84 +2431,154 @@ fn is_array_multiline(node: &SyntaxNode) -> bool {
     node.descendants_with_tokens().any(|n| n.kind() == NEWLINE)
 }
 
+/// Arrays with comments anywhere in them are never collapsed,
+/// the comments would end up after the closing bracket.
 fn can_collapse_array(node: &SyntaxNode) -> bool {
     !node.descendants_with_tokens().any(|n| n.kind() == COMMENT)
 }
 
 fn format_array(node: SyntaxNode, options: &Options, context: &Context) -> impl FormattedItem {
-    let mut multiline = is_array_multiline(&node) || context.force_multiline;
+    let expand = context.expanded_arrays.contains(&node.text_range())
+        // Arrays that contain expanded arrays are expanded as well.
+        || options.array_expand_threshold.is_some_and(|threshold| {
+            node.descendants()
+                .filter(|n| n.kind() == ARRAY)
+                .any(|n| n.children().filter(|n| n.kind() == VALUE).count() > threshold)
+        });
+    let sort = context.sorted_arrays.contains(&node.text_range());
+    // The line breaks can't be kept if the values are moved.
+    let preserve = options.array_layout == ArrayLayout::Preserve && !expand && !sort;
+
+    let mut multiline =
+        is_array_multiline(&node) || (context.force_multiline && !preserve) || expand;
 
     let mut formatted = String::new();
 
     // We always try to collapse it if possible.
-    if can_collapse_array(&node) && options.array_auto_collapse && !context.force_multiline {
+    if can_collapse_array(&node)
+        && options.array_auto_collapse
+        && !context.force_multiline
+        && !preserve
+        && !expand
+    {
         multiline = false;
     }
 
     // We use the same strategy as for entries, refer to [`format_root`].
     let mut skip_newlines = 0;
 
-    // Formatted value, optional trailing comment
-    // The value must not include the comma at the end.
-    let mut value_group: Vec<(String, Option<String>)> = Vec::new();
+    let mut value_group: Vec<ArrayValue> = Vec::new();
     let mut commas_group: Vec<bool> = Vec::new();
+    // Comments on their own lines that are moved together with the next value.
+    let mut comment_group: Vec<String> = Vec::new();
 
-    let add_values = |value_group: &mut Vec<(String, Option<String>)>,
+    let add_values = |value_group: &mut Vec<ArrayValue>,
                       commas_group: &mut Vec<bool>,
+                      comment_group: &mut Vec<String>,
                       formatted: &mut String,
                       context: &Context|
      -> bool {
-        let were_values = !value_group.is_empty();
-
-        if options.reorder_arrays {
-            value_group.sort_unstable_by(|x, y| x.0.cmp(&y.0));
+        let were_values = !value_group.is_empty() || !comment_group.is_empty();
+
+        if sort {
+            // The sort is stable, so duplicates keep their comments in order.
+            value_group.sort_by(|x, y| x.string.cmp(&y.string));
+        } else if options.reorder_arrays {
+            // The values are not moved to other lines if the layout is preserved.
+            if preserve && multiline {
+                value_group.sort_by(|x, y| (x.line, &x.value).cmp(&(y.line, &y.value)));
+            } else {
+                value_group.sort_unstable_by(|x, y| x.value.cmp(&y.value));
+            }
         }
 
         for (has_comma, p) in commas_group.drain(0..).zip(value_group.iter_mut()) {
             if has_comma {
-                p.0 += ","
+                p.value += ","
             };
         }
 
         if !multiline {
-            for (idx, (val, comment)) in value_group.drain(0..).enumerate() {
-                debug_assert!(comment.is_none());
+            for (idx, val) in value_group.drain(0..).enumerate() {
+                debug_assert!(val.comment.is_none());
                 if idx != 0 {
                     *formatted += " "
                 }
 
-                *formatted += &val;
+                *formatted += &val.value;
             }
 
             return were_values;
         }
 
-        let rows = value_group
-            .drain(0..)
-            .map(|(value, comment)| {
-                let mut row = Vec::with_capacity(5);
+        // The values are aligned in blocks that are separated by comments.
+        let mut blocks: Vec<(Vec<String>, Vec<Vec<String>>)> = Vec::new();
+        let mut last_line = None;
+
+        for val in value_group.drain(0..) {
+            if blocks.is_empty() || !val.comments.is_empty() {
+                blocks.push((val.comments, Vec::new()));
+            }
+
+            let rows = &mut blocks.last_mut().unwrap().1;
 
-                row.push(context.indent(options).collect::<String>());
-                row.push(value);
-                if let Some(c) = comment {
-                    row.push(c);
+            match rows.last_mut() {
+                // A comment can only be at the end of a line.
+                Some(row) if preserve && last_line == Some(val.line) => {
+                    row[1] += " ";
+                    row[1] += &val.value;
+                    row.extend(val.comment);
                 }
+                _ => {
+                    let mut row = Vec::with_capacity(5);
 
-                row
-            })
-            .collect::<Vec<_>>();
+                    row.push(context.indent(options).collect::<String>());
+                    row.push(val.value);
+                    if let Some(c) = val.comment {
+                        row.push(c);
+                    }
 
-        *formatted += &format_rows(
-            if options.align_comments {
-                0..usize::MAX
-            } else {
-                0..0
-            },
-            1..usize::MAX,
-            &rows,
-            options.newline(),
-            " ",
-        );
+                    rows.push(row);
+                }
+            }
+
+            last_line = Some(val.line);
+        }
+
+        for (idx, (comments, rows)) in blocks.into_iter().enumerate() {
+            if idx != 0 {
+                *formatted += options.newline();
+            }
+
+            for comment in comments {
+                formatted.extend(context.indent(options));
+                *formatted += &comment;
+                *formatted += options.newline();
+            }
+
+            *formatted += &format_rows(
+                if options.align_comments {
+                    0..usize::MAX
+                } else {
+                    0..0
+                },
+                1..usize::MAX,
+                &rows,
+                options.newline(),
+                " ",
+                options.column_width,
+            );
+        }
+
+        // Comments that are not directly before a value stay where they are.
+        for comment in comment_group.drain(0..) {
+            if !formatted.ends_with('\n') {
+                *formatted += options.newline();
+            }
+
+            formatted.extend(context.indent(options));
+            *formatted += &comment;
+        }
 
         were_values
     };
@@ -1012,6 +2593,9 @@ fn format_array(node: SyntaxNode, options: &Options, context: &Context) -> impl
 
     let mut dangling_newline_count = 0;
 
+    // The line of the current value in the original array.
+    let mut line = 0;
+
     let mut node_index = 0;
     for c in node.children_with_tokens() {
         match c {
@@ -1021,6 +2605,7 @@ fn format_array(node: SyntaxNode, options: &Options, context: &Context) -> impl
                         formatted += options.newline();
                     }
 
+                    let string = sort.then(|| string_value(&n));
                     let val = format_value(n, options, &inner_context);
                     let mut val_string = String::new();
 
@@ -1030,7 +2615,13 @@ fn format_array(node: SyntaxNode, options: &Options, context: &Context) -> impl
                         node_index < node_count - 1 || (multiline && options.array_trailing_comma);
                     commas_group.push(has_comma);
 
-                    value_group.push((val_string, val.trailing_comment()));
+                    value_group.push(ArrayValue {
+                        string,
+                        value: val_string,
+                        comment: val.trailing_comment(),
+                        comments: mem::take(&mut comment_group),
+                        line,
+                    });
                     skip_newlines += 1;
 
                     node_index += 1;
@@ -1052,6 +2643,7 @@ fn format_array(node: SyntaxNode, options: &Options, context: &Context) -> impl
                     add_values(
                         &mut value_group,
                         &mut commas_group,
+                        &mut comment_group,
                         &mut formatted,
                         &inner_context,
                     );
@@ -1068,6 +2660,8 @@ fn format_array(node: SyntaxNode, options: &Options, context: &Context) -> impl
                     formatted += "]";
                 }
                 NEWLINE => {
+                    line += 1;
+
                     if !multiline {
                         continue;
                     }
@@ -1089,6 +2683,7 @@ fn format_array(node: SyntaxNode, options: &Options, context: &Context) -> impl
                         add_values(
                             &mut value_group,
                             &mut commas_group,
+                            &mut comment_group,
                             &mut formatted,
                             &inner_context,
                         );
@@ -1110,13 +2705,20 @@ fn format_array(node: SyntaxNode, options: &Options, context: &Context) -> impl
 
                     if !newline_before && !value_group.is_empty() {
                         // It's actually trailing comment, so we add it to the last value.
-                        value_group.last_mut().unwrap().1 = Some(t.text().to_string());
+                        value_group.last_mut().unwrap().comment = Some(t.text().to_string());
+                        continue;
+                    }
+
+                    if sort && newline_before {
+                        comment_group.push(t.text().to_string());
+                        skip_newlines += 1;
                         continue;
                     }
 
                     if add_values(
                         &mut value_group,
                         &mut commas_group,
+                        &mut comment_group,
                         &mut formatted,
                         &inner_context,
                     ) {
@@ -1144,6 +2746,28 @@ fn format_array(node: SyntaxNode, options: &Options, context: &Context) -> impl
     (node.into(), formatted, None)
 }
 
+/// A formatted value of an array, see [`format_array`].
+struct ArrayValue {
+    /// The formatted value without the comma at the end.
+    value: String,
+    /// The comment after the value.
+    comment: Option<String>,
+    /// The comments directly before the value, only kept for sorted arrays.
+    comments: Vec<String>,
+    /// The line of the value in the original array.
+    line: usize,
+    /// The unescaped string value if the array is sorted.
+    string: Option<String>,
+}
+
+/// The unescaped value of a string value.
+fn string_value(value: &SyntaxNode) -> String {
+    Node::from_syntax(value.clone().into())
+        .as_str()
+        .map(|s| s.value().to_string())
+        .unwrap_or_default()
+}
+
 fn format_table_header(
     node: SyntaxNode,
     options: &Options,
@@ -1204,13 +2828,20 @@ impl NewlineCount for &str {
     }
 }
 
-// FIXME(docs)
+/// Joins the items of the rows with the separator, and aligns the items
+/// in the align range vertically by padding them with spaces.
+///
+/// Widths are counted in characters, so a tab or a wide character
+/// counts as a single column. The last item of a row (e.g. a trailing comment)
+/// is not aligned if the row would be wider than `max_width` otherwise,
+/// the other items are not aligned if only the padding would make it wider.
 fn format_rows<R, S>(
     align_range: Range<usize>,
     separator_range: Range<usize>,
     rows: &[R],
     newline: &str,
     separator: &str,
+    max_width: usize,
 ) -> String
 where
     R: AsRef<[S]>,
@@ -1256,6 +2887,7 @@ where
         }
 
         let mut last_align_idx = 0_usize;
+        let row_start = out.len();
 
         for (item_idx, item) in row.as_ref().iter().enumerate() {
             if item_idx > separator_range.start
@@ -1273,8 +2905,35 @@ where
                 && item_idx < row.as_ref().len() - 1
             {
                 let diff = diff_widths(last_align_idx..item_idx + 1, row);
-                out.extend(repeat(" ").take(diff));
                 last_align_idx = item_idx + 1;
+
+                // The width of the whole row without the padding.
+                let width = out[row_start..].chars().count()
+                    + row.as_ref()[item_idx + 1..]
+                        .iter()
+                        .enumerate()
+                        .map(|(idx, item)| {
+                            let has_separator = separator_range.start < item_idx + 1 + idx
+                                && item_idx + 1 + idx <= separator_range.end.saturating_add(1);
+
+                            item.as_ref().chars().count()
+                                + if has_separator {
+                                    separator.chars().count()
+                                } else {
+                                    0
+                                }
+                        })
+                        .sum::<usize>();
+
+                // The last item is never aligned beyond the maximum width,
+                // the others only if the row fits without the padding.
+                if width + diff > max_width
+                    && (item_idx + 2 == row.as_ref().len() || width <= max_width)
+                {
+                    continue;
+                }
+
+                out.extend(repeat_n(" ", diff));
             }
         }
     }
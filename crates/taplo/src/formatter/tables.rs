@@ -0,0 +1,166 @@
+//! Reordering of the tables of a document, see [`Options::reorder_tables`].
+
+use super::{Context, Options};
+use crate::{
+    dom::{FromSyntax, Keys, Node},
+    syntax::{SyntaxElement, SyntaxKind::*, SyntaxNode},
+};
+
+/// A table header with the sub-tables that belong to it,
+/// together with the comments directly before them.
+struct Section {
+    keys: Keys,
+    elements: Vec<SyntaxElement>,
+}
+
+/// The top-level elements of the root in the order they are formatted.
+///
+/// The tables are sorted by their keys, arrays of tables are kept in their
+/// relative order along with the tables under them. The entries before
+/// the first header and the blank lines between the tables stay in place.
+pub(super) fn reordered_elements(
+    root: &SyntaxNode,
+    options: &Options,
+    context: &Context,
+) -> Vec<SyntaxElement> {
+    let elements: Vec<SyntaxElement> = root.children_with_tokens().collect();
+
+    if !options.reorder_tables
+        || !context.errors.is_empty()
+        || Node::from_syntax(root.clone().into()).validate().is_err()
+    {
+        return elements;
+    }
+
+    let headers: Vec<usize> = elements
+        .iter()
+        .enumerate()
+        .filter(|(_, e)| matches!(e.kind(), TABLE_HEADER | TABLE_ARRAY_HEADER))
+        .map(|(idx, _)| idx)
+        .collect();
+
+    if headers.len() < 2 {
+        return elements;
+    }
+
+    let starts: Vec<usize> = headers
+        .iter()
+        .map(|&idx| section_start(&elements, idx))
+        .collect();
+
+    let array_keys: Vec<Keys> = headers
+        .iter()
+        .filter(|&&idx| elements[idx].kind() == TABLE_ARRAY_HEADER)
+        .map(|&idx| header_keys(&elements[idx]))
+        .collect();
+
+    let mut sections: Vec<Section> = Vec::new();
+    // The whitespace after each section, this stays in place.
+    let mut gaps: Vec<&[SyntaxElement]> = Vec::new();
+
+    for (i, (&header, &start)) in headers.iter().zip(&starts).enumerate() {
+        let next = starts.get(i + 1).copied().unwrap_or(elements.len());
+
+        let end = start
+            + elements[start..next]
+                .iter()
+                .rposition(|e| !matches!(e.kind(), NEWLINE | WHITESPACE))
+                .map_or(0, |idx| idx + 1);
+
+        let keys = header_keys(&elements[header]);
+        let section_elements = elements[start..end].to_vec();
+
+        let parent = sections
+            .last_mut()
+            .filter(|s| s.is_array() && keys.len() > s.keys.len() && keys.contains(&s.keys));
+
+        match parent {
+            Some(parent) => {
+                // The whitespace between the sub-tables moves together with them.
+                parent
+                    .elements
+                    .extend(gaps.pop().unwrap_or_default().iter().cloned());
+                parent.elements.extend(section_elements);
+            }
+            None => {
+                // Tables under arrays of tables belong to the last array element
+                // before them, these could end up under a different element.
+                if array_keys
+                    .iter()
+                    .any(|array| keys.len() > array.len() && keys.contains(array))
+                {
+                    return elements;
+                }
+
+                sections.push(Section {
+                    keys,
+                    elements: section_elements,
+                });
+            }
+        }
+
+        gaps.push(&elements[end..next]);
+    }
+
+    // The sort is stable, so arrays of tables with the same keys keep their order.
+    sections.sort_by_cached_key(|section| {
+        section
+            .keys
+            .iter()
+            .filter_map(|k| k.as_key())
+            .map(|k| k.value().to_string())
+            .collect::<Vec<_>>()
+    });
+
+    let mut reordered = elements[..starts[0]].to_vec();
+
+    for (section, gap) in sections.into_iter().zip(gaps) {
+        reordered.extend(section.elements);
+        reordered.extend(gap.iter().cloned());
+    }
+
+    reordered
+}
+
+impl Section {
+    fn is_array(&self) -> bool {
+        self.elements
+            .iter()
+            .find(|e| matches!(e.kind(), TABLE_HEADER | TABLE_ARRAY_HEADER))
+            .is_some_and(|e| e.kind() == TABLE_ARRAY_HEADER)
+    }
+}
+
+/// The start of the section of a header, including
+/// the comments directly before it.
+fn section_start(elements: &[SyntaxElement], header: usize) -> usize {
+    let mut start = header;
+    let mut newline_count = 0;
+
+    for idx in (0..header).rev() {
+        match elements[idx].kind() {
+            WHITESPACE => {}
+            NEWLINE => {
+                newline_count += elements[idx].to_string().matches('\n').count();
+                if newline_count > 1 {
+                    break;
+                }
+            }
+            COMMENT => {
+                start = idx;
+                newline_count = 0;
+            }
+            _ => break,
+        }
+    }
+
+    start
+}
+
+fn header_keys(header: &SyntaxElement) -> Keys {
+    header
+        .as_node()
+        .and_then(|header| header.first_child())
+        .map(|key| Keys::from_syntax(key.into()))
+        .unwrap_or_else(Keys::empty)
+}
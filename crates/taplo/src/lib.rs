@@ -16,7 +16,7 @@
 //! - **time**: Use [time](https://github.com/time-rs/time) for TOML dates and times
 //!
 //! - **serde**: Support for [serde](https://serde.rs) serialization of the DOM nodes.
-//! - **schema**: Enable JSON-schema generation for formatter configuration.
+//! - **schema**: Enable JSON-schema generation for formatter configuration and [example documents](schema_gen).
 //!
 //! # Usage
 //!
@@ -50,9 +50,12 @@
 //! assert!(root_node.validate().is_err());
 //! ```
 
+pub mod analyze;
 pub mod dom;
 pub mod formatter;
 pub mod parser;
+#[cfg(feature = "schema")]
+pub mod schema_gen;
 pub mod syntax;
 pub mod util;
 
@@ -6,20 +6,57 @@ use crate::{
     util::{allowed_chars, check_escape},
 };
 use logos::{Lexer, Logos};
-use rowan::{GreenNode, GreenNodeBuilder, TextRange, TextSize};
+use rowan::{GreenNode, GreenNodeBuilder, NodeOrToken, TextRange, TextSize};
 use std::convert::TryInto;
+use thiserror::Error as ThisError;
 
 #[macro_use]
 mod macros;
 
 /// A syntax error that can occur during parsing.
-#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+#[derive(Debug, Default, Clone, Eq, PartialEq, Hash)]
+#[non_exhaustive]
 pub struct Error {
     /// The span of the error.
     pub range: TextRange,
 
     /// Human-friendly error message.
     pub message: String,
+
+    pub(crate) expected: Vec<SyntaxKind>,
+    pub(crate) skipped: bool,
+}
+
+impl Error {
+    /// An error that is not about a missing or unexpected token.
+    pub fn new(range: TextRange, message: impl Into<String>) -> Self {
+        Self {
+            range,
+            message: message.into(),
+            ..Default::default()
+        }
+    }
+
+    /// The tokens that would have been accepted where the error occurred,
+    /// empty if the error is not about a missing or unexpected token.
+    pub fn expected(&self) -> &[SyntaxKind] {
+        &self.expected
+    }
+
+    /// Whether tokens were skipped (added as `ERROR` tokens)
+    /// in order to recover from the error.
+    pub fn skipped(&self) -> bool {
+        self.skipped
+    }
+
+    /// A description of the expected tokens, e.g. `expected "." or "="`.
+    pub fn expected_message(&self) -> Option<String> {
+        if self.expected.is_empty() {
+            None
+        } else {
+            Some(expected_message(&self.expected))
+        }
+    }
 }
 
 impl core::fmt::Display for Error {
@@ -39,11 +76,241 @@ impl std::error::Error for Error {}
 /// invalid escape sequences and invalid characters.
 /// These will also be reported as syntax errors.
 ///
+/// A leading UTF-8 byte order mark is added to the tree
+/// as a `BOM` token, see [`Parse::has_bom`].
+///
 /// This does not check for semantic errors such as duplicate keys.
 pub fn parse(source: &str) -> Parse {
     Parser::new(source).parse()
 }
 
+/// An error returned by [`parse_bytes`] for documents
+/// that are not valid UTF-8.
+#[derive(Debug, Clone, Eq, PartialEq, ThisError)]
+pub enum EncodingError {
+    #[error("the document is encoded as {0}, only UTF-8 is supported")]
+    UnsupportedEncoding(&'static str),
+    #[error("invalid UTF-8 at byte offset {offset}")]
+    InvalidUtf8 { offset: usize },
+}
+
+/// Parse a TOML document from bytes.
+///
+/// The document must be UTF-8, optionally starting with a byte order mark,
+/// UTF-16 and UTF-32 byte order marks and invalid UTF-8 sequences are rejected.
+///
+/// # Example
+///
+/// ```
+/// use taplo::parser::{parse_bytes, EncodingError};
+///
+/// let p = parse_bytes(b"\xEF\xBB\xBFa = 1").unwrap();
+/// assert!(p.errors.is_empty());
+/// assert!(p.has_bom());
+///
+/// assert_eq!(
+///     parse_bytes(b"a = \"\xFF\"").unwrap_err(),
+///     EncodingError::InvalidUtf8 { offset: 5 }
+/// );
+/// ```
+pub fn parse_bytes(source: &[u8]) -> Result<Parse, EncodingError> {
+    const BOMS: &[(&[u8], &str)] = &[
+        (&[0x00, 0x00, 0xFE, 0xFF], "UTF-32 (big-endian)"),
+        (&[0xFF, 0xFE, 0x00, 0x00], "UTF-32 (little-endian)"),
+        (&[0xFE, 0xFF], "UTF-16 (big-endian)"),
+        (&[0xFF, 0xFE], "UTF-16 (little-endian)"),
+    ];
+
+    if let Some((_, encoding)) = BOMS.iter().find(|(bom, _)| source.starts_with(bom)) {
+        return Err(EncodingError::UnsupportedEncoding(encoding));
+    }
+
+    let source = std::str::from_utf8(source).map_err(|err| EncodingError::InvalidUtf8 {
+        offset: err.valid_up_to(),
+    })?;
+
+    Ok(parse(source))
+}
+
+/// Parse a single TOML value, such as `[1, 2]` or `{ version = "1.0" }`.
+///
+/// The root of the syntax tree is a `VALUE` node,
+/// and [`Parse::into_dom`] returns the value itself.
+///
+/// Leading and trailing whitespace and comments are allowed,
+/// anything else after the value is reported as an error.
+///
+/// # Example
+///
+/// ```
+/// let p = taplo::parser::parse_value(r#"{ version = "1.0", features = ["derive"] }"#);
+/// assert!(p.errors.is_empty());
+///
+/// let dom = p.into_dom();
+/// assert_eq!(dom.get("version").as_str().unwrap().value(), "1.0");
+///
+/// assert!(!taplo::parser::parse_value("1 2").errors.is_empty());
+/// ```
+pub fn parse_value(source: &str) -> Parse {
+    Parser::new(source).parse_value_fragment()
+}
+
+/// Parse a dotted key, such as `target."cfg(windows)".dependencies`.
+///
+/// The root of the syntax tree is a `KEY` node, it can be turned into
+/// [`Keys`](crate::dom::Keys) with [`FromSyntax`].
+///
+/// Anything after the key is reported as an error.
+///
+/// # Example
+///
+/// ```
+/// use taplo::dom::{FromSyntax, Keys};
+///
+/// let p = taplo::parser::parse_key(r#"target."cfg(windows)".dependencies"#);
+/// assert!(p.errors.is_empty());
+///
+/// let keys = Keys::from_syntax(p.into_syntax().into());
+/// assert_eq!(keys.len(), 3);
+///
+/// assert!(!taplo::parser::parse_key("a = 1").errors.is_empty());
+/// ```
+pub fn parse_key(source: &str) -> Parse {
+    Parser::new(source).parse_key_fragment()
+}
+
+/// Split a TOML document into tokens without building a syntax tree.
+///
+/// This is the same lexer that [`parse`] uses, the tokens are returned
+/// with their kinds and ranges, and concatenating them always
+/// results in the original source.
+///
+/// Only the kinds for which [`SyntaxKind::is_token`] is true are returned,
+/// invalid input is returned as `ERROR` tokens and a leading byte order mark
+/// as a `BOM` token. No errors are reported, that requires parsing.
+///
+/// The parser can re-kind tokens depending on their context
+/// (e.g. a `BOOL` used as a key is an `IDENT` in the syntax tree),
+/// and it splits dotted keys lexed as a single `FLOAT` (e.g. `1.2 = true`)
+/// into `IDENT` and `PERIOD` tokens, otherwise the tokens in the syntax tree
+/// have the same ranges.
+///
+/// # Example
+///
+/// ```
+/// use taplo::{parser::lex, syntax::SyntaxKind::*};
+///
+/// let kinds: Vec<_> = lex("a = 1 # one").map(|(kind, _)| kind).collect();
+/// assert_eq!(kinds, [IDENT, WHITESPACE, EQ, WHITESPACE, INTEGER, WHITESPACE, COMMENT]);
+/// ```
+pub fn lex(source: &str) -> Lex<'_> {
+    Lex {
+        lexer: SyntaxKind::lexer(source),
+        bom: starts_with_bom(source),
+    }
+}
+
+/// An iterator over the tokens of a TOML document, see [`lex`].
+#[derive(Clone)]
+pub struct Lex<'s> {
+    lexer: Lexer<'s, SyntaxKind>,
+    bom: bool,
+}
+
+impl Iterator for Lex<'_> {
+    type Item = (SyntaxKind, TextRange);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if std::mem::take(&mut self.bom) {
+            self.lexer.bump(BOM_LEN);
+            return Some((BOM, TextRange::up_to(TextSize::from(BOM_LEN as u32))));
+        }
+
+        let kind = self.lexer.next()?;
+        let span = self.lexer.span();
+
+        Some((
+            kind,
+            TextRange::new(
+                TextSize::from(span.start as u32),
+                TextSize::from(span.end as u32),
+            ),
+        ))
+    }
+}
+
+const BOM_LEN: usize = '\u{FEFF}'.len_utf8();
+
+fn starts_with_bom(source: &str) -> bool {
+    source.starts_with('\u{FEFF}')
+}
+
+/// Parse a source that contains multiple TOML documents
+/// separated by lines that only contain the given separator (e.g. `+++`).
+///
+/// Separator lines are only recognized outside of tokens, so
+/// the separator can safely appear inside multi-line strings.
+///
+/// Every document is parsed with [`parse`] and returned with its range
+/// in the source. The syntax trees start at offset 0, but the ranges
+/// of the errors are offset to point into the original source.
+///
+/// # Example
+///
+/// ```
+/// let docs = taplo::parser::parse_multi("a = 1\n+++\nb = 2\n", "+++");
+///
+/// assert_eq!(docs.len(), 2);
+/// assert_eq!(docs[1].1.clone().into_dom().to_toml(false, false), "b = 2\n");
+/// ```
+pub fn parse_multi(source: &str, separator: &str) -> Vec<(TextRange, Parse)> {
+    let mut docs = Vec::new();
+    let mut doc_start = 0;
+
+    let mut lexer = SyntaxKind::lexer(source);
+    while lexer.next().is_some() {
+        let start = lexer.span().start;
+
+        if start != 0 && source.as_bytes()[start - 1] != b'\n' {
+            continue;
+        }
+
+        let line_end = source[start..]
+            .find('\n')
+            .map_or(source.len(), |idx| start + idx);
+        let line = &source[start..line_end];
+
+        if line.trim_end_matches([' ', '\t', '\r']) != separator {
+            continue;
+        }
+
+        docs.push(parse_doc(source, doc_start, start));
+        doc_start = (line_end + 1).min(source.len());
+
+        // Skip the tokens of the separator line.
+        while lexer.span().end < doc_start {
+            if lexer.next().is_none() {
+                break;
+            }
+        }
+    }
+
+    docs.push(parse_doc(source, doc_start, source.len()));
+
+    docs
+}
+
+fn parse_doc(source: &str, start: usize, end: usize) -> (TextRange, Parse) {
+    let offset = TextSize::from(start as u32);
+    let mut p = parse(&source[start..end]);
+
+    for err in &mut p.errors {
+        err.range += offset;
+    }
+
+    (TextRange::new(offset, TextSize::from(end as u32)), p)
+}
+
 /// A hand-written parser that uses the Logos lexer
 /// to tokenize the source, then constructs
 /// a Rowan green tree from them.
@@ -88,6 +355,51 @@ impl<'p> Parser<'p> {
             errors: self.errors,
         }
     }
+
+    fn parse_value_fragment(mut self) -> Parse {
+        self.builder.start_node(VALUE.into());
+        let _ = self.parse_value();
+        self.parse_trailing_input();
+        self.builder.finish_node();
+
+        Parse {
+            green_node: self.builder.finish(),
+            errors: self.errors,
+        }
+    }
+
+    fn parse_key_fragment(mut self) -> Parse {
+        self.builder.start_node(KEY.into());
+        let _ = self.parse_key();
+        self.parse_trailing_input();
+        self.builder.finish_node();
+
+        Parse {
+            green_node: self.builder.finish(),
+            errors: self.errors,
+        }
+    }
+
+    /// Report anything after a fragment as an error
+    /// and add it as error tokens.
+    fn parse_trailing_input(&mut self) {
+        if self.get_token().is_err() {
+            return;
+        }
+
+        let start = self.lexer.span().start;
+        let end = start + self.lexer.remainder().len() + self.lexer.slice().len();
+        self.add_error(&Error {
+            range: TextRange::new(TextSize::from(start as u32), TextSize::from(end as u32)),
+            message: "unexpected trailing input".into(),
+            skipped: true,
+            ..Default::default()
+        });
+
+        while self.get_token().is_ok() {
+            let _ = self.token_as(ERROR);
+        }
+    }
 }
 
 /// This is just a convenience type during parsing.
@@ -112,7 +424,10 @@ impl<'p> Parser<'p> {
     }
 
     fn parse(mut self) -> Parse {
-        let _ = with_node!(self.builder, ROOT, self.parse_root());
+        let _ = with_node!(self.builder, ROOT, {
+            self.parse_bom();
+            self.parse_root()
+        });
 
         Parse {
             green_node: self.builder.finish(),
@@ -120,37 +435,52 @@ impl<'p> Parser<'p> {
         }
     }
 
+    fn parse_bom(&mut self) {
+        if starts_with_bom(self.lexer.remainder()) {
+            self.lexer.bump(BOM_LEN);
+            self.insert_token(BOM, "\u{FEFF}");
+        }
+    }
+
     fn error(&mut self, message: &str) -> ParserResult<()> {
-        let span = self.lexer.span();
+        self.error_with(message, &[])
+    }
 
-        let err = Error {
-            range: TextRange::new(
-                TextSize::from(span.start as u32),
-                TextSize::from(span.end as u32),
-            ),
-            message: message.into(),
-        };
+    /// Report an error for a token that is not in the expected set.
+    fn error_expected(&mut self, expected: &[SyntaxKind]) -> ParserResult<()> {
+        self.error_with(&expected_message(expected), expected)
+    }
+
+    fn error_with(&mut self, message: &str, expected: &[SyntaxKind]) -> ParserResult<()> {
+        let span = self.lexer.span();
+        let range = TextRange::new(
+            TextSize::from(span.start as u32),
+            TextSize::from(span.end as u32),
+        );
 
         let same_error = self
             .errors
             .last()
-            .map(|e| e.range == err.range)
+            .map(|e| e.range == range)
             .unwrap_or(false);
 
         if !same_error {
+            let skipped = self.current_token.is_some_and(|t| !self.whitelisted(t));
+
             self.add_error(&Error {
-                range: TextRange::new(
-                    TextSize::from(span.start as u32),
-                    TextSize::from(span.end as u32),
-                ),
+                range,
                 message: message.into(),
+                expected: expected.to_vec(),
+                skipped,
             });
-            if let Some(t) = self.current_token {
-                if !self.whitelisted(t) {
-                    self.token_as(ERROR).ok();
-                }
+
+            if skipped {
+                self.token_as(ERROR).ok();
             }
         } else {
+            if let Some(last_error) = self.errors.last_mut() {
+                last_error.skipped = true;
+            }
             self.token_as(ERROR).ok();
         }
 
@@ -158,7 +488,11 @@ impl<'p> Parser<'p> {
     }
 
     // report error without consuming the current the token
-    fn report_error(&mut self, message: &str) -> ParserResult<()> {
+    fn report_expected(&mut self, expected: &[SyntaxKind]) -> ParserResult<()> {
+        self.report_error_with(&expected_message(expected), expected)
+    }
+
+    fn report_error_with(&mut self, message: &str, expected: &[SyntaxKind]) -> ParserResult<()> {
         let span = self.lexer.span();
         self.add_error(&Error {
             range: TextRange::new(
@@ -166,10 +500,45 @@ impl<'p> Parser<'p> {
                 TextSize::from(span.end as u32),
             ),
             message: message.into(),
+            expected: expected.to_vec(),
+            skipped: false,
         });
         Err(())
     }
 
+    /// Report each invalid escape sequence in the current token
+    /// with its exact range.
+    ///
+    /// Parsing proceeds normally even if the string contains invalid escapes,
+    /// it shouldn't affect the rest of the parsing.
+    fn check_escapes(&mut self) {
+        if let Err(errors) = check_escape(self.lexer.slice()) {
+            for e in errors {
+                let start = TextSize::from((self.lexer.span().start + e.offset) as u32);
+                self.add_error(&Error {
+                    range: TextRange::at(start, TextSize::from(e.len as u32)),
+                    message: e.to_string(),
+                    ..Default::default()
+                });
+            }
+        }
+    }
+
+    /// Report each forbidden character in the current token
+    /// with its exact range.
+    fn check_allowed_chars(&mut self, check: fn(&str) -> Result<(), Vec<usize>>, message: &str) {
+        if let Err(err_indices) = check(self.lexer.slice()) {
+            for e in err_indices {
+                let start = TextSize::from((self.lexer.span().start + e) as u32);
+                self.add_error(&Error {
+                    range: TextRange::at(start, TextSize::from(1)),
+                    message: message.into(),
+                    ..Default::default()
+                });
+            }
+        }
+    }
+
     fn add_error(&mut self, e: &Error) {
         if let Some(last_err) = self.errors.last_mut() {
             if last_err == e {
@@ -199,13 +568,13 @@ impl<'p> Parser<'p> {
         self.builder.token(kind.into(), s)
     }
 
-    fn must_token_or(&mut self, kind: SyntaxKind, message: &str) -> ParserResult<()> {
+    fn must_token_or(&mut self, kind: SyntaxKind, expected: &[SyntaxKind]) -> ParserResult<()> {
         match self.get_token() {
             Ok(t) => {
                 if kind == t {
                     self.token()
                 } else {
-                    self.error(message)
+                    self.error_expected(expected)
                 }
             }
             Err(_) => {
@@ -215,6 +584,8 @@ impl<'p> Parser<'p> {
                         self.lexer.span().end.try_into().unwrap(),
                     ),
                     message: "unexpected EOF".into(),
+                    expected: expected.to_vec(),
+                    skipped: false,
                 });
                 Err(())
             }
@@ -266,20 +637,10 @@ impl<'p> Parser<'p> {
         while let Some(token) = self.lexer.next() {
             match token {
                 COMMENT => {
-                    match allowed_chars::comment(self.lexer.slice()) {
-                        Ok(_) => {}
-                        Err(err_indices) => {
-                            for e in err_indices {
-                                self.add_error(&Error {
-                                    range: TextRange::new(
-                                        (self.lexer.span().start + e).try_into().unwrap(),
-                                        (self.lexer.span().start + e).try_into().unwrap(),
-                                    ),
-                                    message: "invalid character in comment".into(),
-                                });
-                            }
-                        }
-                    };
+                    self.check_allowed_chars(
+                        allowed_chars::comment,
+                        "invalid control character in comment",
+                    );
 
                     self.insert_token(token, self.lexer.slice());
                 }
@@ -300,8 +661,19 @@ impl<'p> Parser<'p> {
                             span.end.try_into().unwrap(),
                         ),
                         message: "unexpected token".into(),
+                        skipped: true,
+                        ..Default::default()
                     })
                 }
+                NEWLINE => {
+                    self.check_allowed_chars(
+                        allowed_chars::newline,
+                        r"bare carriage return, expected \n or \r\n",
+                    );
+
+                    self.current_token = Some(token);
+                    break;
+                }
                 _ => {
                     self.current_token = Some(token);
                     break;
@@ -334,15 +706,15 @@ impl<'p> Parser<'p> {
                         entry_started = false;
                     }
 
-                    if not_newline {
-                        let _ = self.error("expected new line");
+                    if not_newline && !self.at_line_start() {
+                        let _ = self.error_expected(&[NEWLINE]);
                         continue;
                     }
 
                     not_newline = true;
 
-                    if self.lexer.remainder().starts_with('[') {
-                        let _ = whitelisted!(
+                    let res = if self.lexer.remainder().starts_with('[') {
+                        whitelisted!(
                             self,
                             NEWLINE,
                             with_node!(
@@ -350,13 +722,17 @@ impl<'p> Parser<'p> {
                                 TABLE_ARRAY_HEADER,
                                 self.parse_table_array_header()
                             )
-                        );
+                        )
                     } else {
-                        let _ = whitelisted!(
+                        whitelisted!(
                             self,
                             NEWLINE,
                             with_node!(self.builder, TABLE_HEADER, self.parse_table_header())
-                        );
+                        )
+                    };
+
+                    if res.is_err() {
+                        self.skip_header();
                     }
                 }
                 NEWLINE => {
@@ -368,8 +744,11 @@ impl<'p> Parser<'p> {
                     let _ = self.token();
                 }
                 _ => {
-                    if not_newline {
-                        let _ = self.error("expected new line");
+                    // Values such as unterminated arrays can stop
+                    // at the start of a line without a newline token.
+                    if not_newline && !self.at_line_start() {
+                        let _ = self.error_expected(&[NEWLINE]);
+                        self.skip_line();
                         continue;
                     }
                     if entry_started {
@@ -378,7 +757,13 @@ impl<'p> Parser<'p> {
                     not_newline = true;
                     self.builder.start_node(ENTRY.into());
                     entry_started = true;
-                    let _ = whitelisted!(self, NEWLINE, self.parse_entry());
+                    let start = self.lexer.span().start;
+                    if whitelisted!(self, NEWLINE, self.parse_entry()).is_err() {
+                        if self.lexer.span().start == start {
+                            let _ = self.token_as(ERROR);
+                        }
+                        self.skip_line();
+                    }
                 }
             }
         }
@@ -389,22 +774,109 @@ impl<'p> Parser<'p> {
         Ok(())
     }
 
+    /// Whether the current token is the first one on its line.
+    fn at_line_start(&self) -> bool {
+        let before = &self.lexer.source()[..self.lexer.span().start];
+        let before = before.trim_end_matches([' ', '\t']);
+        before.is_empty() || before.ends_with('\n')
+    }
+
+    /// Whether the current line starts a new entry or table header,
+    /// neither of which can be a part of an array value.
+    ///
+    /// Only bare keys are recognized in headers, as `["a"]` or `[1]`
+    /// are valid array items.
+    fn at_entry_line(&self) -> bool {
+        let source = &self.lexer.source()[self.lexer.span().start..];
+        let line = source.lines().next().unwrap_or_default();
+
+        let mut tokens = SyntaxKind::lexer(line).filter(|t| *t != WHITESPACE);
+
+        match tokens.next() {
+            Some(BRACKET_START) => {
+                let mut tokens = tokens.skip_while(|t| *t == BRACKET_START);
+                tokens.next() == Some(IDENT)
+            }
+            Some(IDENT | STRING | STRING_LITERAL | INTEGER | FLOAT | BOOL) => loop {
+                match tokens.next() {
+                    Some(PERIOD) => {}
+                    Some(EQ) => break true,
+                    _ => break false,
+                }
+                if !matches!(
+                    tokens.next(),
+                    Some(IDENT | STRING | STRING_LITERAL | INTEGER | FLOAT | BOOL)
+                ) {
+                    break false;
+                }
+            },
+            _ => false,
+        }
+    }
+
+    /// Skips the rest of a line after an invalid entry,
+    /// so that a single error does not affect the following ones.
+    fn skip_line(&mut self) {
+        while let Ok(token) = self.get_token() {
+            if token == NEWLINE || self.at_line_start() {
+                break;
+            }
+            self.skip_token();
+        }
+    }
+
+    /// Skips the rest of an invalid table header
+    /// up to and including the closing bracket(s).
+    fn skip_header(&mut self) {
+        while let Ok(token) = self.get_token() {
+            match token {
+                NEWLINE => break,
+                BRACKET_END => {
+                    self.skip_token();
+                    if self.get_token() == Ok(BRACKET_END) {
+                        self.skip_token();
+                    }
+                    break;
+                }
+                _ if self.at_line_start() => break,
+                _ => self.skip_token(),
+            }
+        }
+    }
+
+    /// Adds the current token as an error token
+    /// that belongs to the last reported error.
+    fn skip_token(&mut self) {
+        if let Some(last_error) = self.errors.last_mut() {
+            last_error.skipped = true;
+        }
+        let _ = self.token_as(ERROR);
+    }
+
     fn parse_table_header(&mut self) -> ParserResult<()> {
-        self.must_token_or(BRACKET_START, r#"expected "[""#)?;
+        self.must_token_or(BRACKET_START, &[BRACKET_START])?;
         let _ = with_node!(self.builder, KEY, self.parse_key());
-        self.must_token_or(BRACKET_END, r#"expected "]""#)?;
+        self.must_token_or(BRACKET_END, &[PERIOD, BRACKET_END])?;
 
         Ok(())
     }
 
     fn parse_table_array_header(&mut self) -> ParserResult<()> {
         self.skip_whitespace = false;
-        self.must_token_or(BRACKET_START, r#"expected "[[""#)?;
-        self.must_token_or(BRACKET_START, r#"expected "[[""#)?;
+        let res = self.parse_table_array_header_inner();
+        // Whitespace would not be skipped in the rest of
+        // the document if the header is invalid otherwise.
+        self.skip_whitespace = true;
+        res
+    }
+
+    fn parse_table_array_header_inner(&mut self) -> ParserResult<()> {
+        self.must_token_or(BRACKET_START, &[BRACKET_START])?;
+        self.must_token_or(BRACKET_START, &[BRACKET_START])?;
         self.skip_whitespace = true;
         let _ = with_node!(self.builder, KEY, self.parse_key());
         self.skip_whitespace = false;
-        let _ = self.must_token_or(BRACKET_END, r#"expected "]]""#);
+        let _ = self.must_token_or(BRACKET_END, &[PERIOD, BRACKET_END]);
 
         // Hack in order to avoid calling `step` after
         // the second closing bracket.
@@ -414,7 +886,7 @@ impl<'p> Parser<'p> {
                 self.token_as_no_step(token)?;
             }
             _ => {
-                self.error(r#"expected "]]"#)?;
+                self.error_with(r#"expected "]]""#, &[BRACKET_END])?;
             }
         }
         self.skip_whitespace = true;
@@ -426,7 +898,7 @@ impl<'p> Parser<'p> {
 
     fn parse_entry(&mut self) -> ParserResult<()> {
         with_node!(self.builder, KEY, self.parse_key())?;
-        self.must_token_or(EQ, r#"expected "=""#)?;
+        self.must_token_or(EQ, &[PERIOD, EQ])?;
         with_node!(self.builder, VALUE, self.parse_value())?;
 
         Ok(())
@@ -434,7 +906,7 @@ impl<'p> Parser<'p> {
 
     fn parse_key(&mut self) -> ParserResult<()> {
         if self.parse_ident().is_err() {
-            return self.report_error("expected identifier");
+            return self.report_expected(KEY_START);
         }
 
         let mut after_period = false;
@@ -445,14 +917,14 @@ impl<'p> Parser<'p> {
                     if !after_period {
                         return Ok(());
                     }
-                    return self.error("unexpected end of input");
+                    return self.error_with("unexpected end of input", KEY_START);
                 }
             };
 
             match t {
                 PERIOD => {
                     if after_period {
-                        return self.error(r#"unexpected ".""#);
+                        return self.error_with(r#"unexpected ".""#, KEY_START);
                     } else {
                         self.token()?;
                         after_period = true;
@@ -463,13 +935,13 @@ impl<'p> Parser<'p> {
 
                     match self.parse_ident() {
                         Ok(_) => {}
-                        Err(_) => return self.error("expected identifier"),
+                        Err(_) => return self.error_expected(KEY_START),
                     }
 
                     let token = self.get_token()?;
 
                     if !matches!(token, BRACKET_END) {
-                        self.error(r#"expected "]""#)?;
+                        self.error_expected(&[BRACKET_END])?;
                     }
                     self.step();
                     after_period = false;
@@ -478,7 +950,7 @@ impl<'p> Parser<'p> {
                     if after_period {
                         match self.parse_ident() {
                             Ok(_) => {}
-                            Err(_) => return self.report_error("expected identifier"),
+                            Err(_) => return self.report_expected(KEY_START),
                         }
                         after_period = false;
                     } else if self.key_pattern_syntax {
@@ -501,7 +973,7 @@ impl<'p> Parser<'p> {
                 if self.key_pattern_syntax {
                     self.token_as(IDENT)
                 } else {
-                    self.error("expected identifier")
+                    self.error_expected(KEY_START)
                 }
             }
             INTEGER_HEX | INTEGER_BIN | INTEGER_OCT => self.token_as(IDENT),
@@ -513,58 +985,21 @@ impl<'p> Parser<'p> {
                 }
             }
             STRING_LITERAL => {
-                match allowed_chars::string_literal(self.lexer.slice()) {
-                    Ok(_) => {}
-                    Err(err_indices) => {
-                        for e in err_indices {
-                            self.add_error(&Error {
-                                range: TextRange::new(
-                                    (self.lexer.span().start + e).try_into().unwrap(),
-                                    (self.lexer.span().start + e).try_into().unwrap(),
-                                ),
-                                message: "invalid control character in string literal".into(),
-                            });
-                        }
-                    }
-                };
+                self.check_allowed_chars(
+                    allowed_chars::string_literal,
+                    "invalid control character in string",
+                );
 
                 self.token_as(IDENT)
             }
             STRING => {
-                match allowed_chars::string(self.lexer.slice()) {
-                    Ok(_) => {}
-                    Err(err_indices) => {
-                        for e in err_indices {
-                            self.add_error(&Error {
-                                range: TextRange::new(
-                                    (self.lexer.span().start + e).try_into().unwrap(),
-                                    (self.lexer.span().start + e).try_into().unwrap(),
-                                ),
-                                message: "invalid character in string".into(),
-                            });
-                        }
-                    }
-                };
-
-                match check_escape(self.lexer.slice()) {
-                    Ok(_) => self.token_as(IDENT),
-                    Err(err_indices) => {
-                        for e in err_indices {
-                            self.add_error(&Error {
-                                range: TextRange::new(
-                                    (self.lexer.span().start + e).try_into().unwrap(),
-                                    (self.lexer.span().start + e).try_into().unwrap(),
-                                ),
-                                message: "invalid escape sequence".into(),
-                            });
-                        }
+                self.check_allowed_chars(
+                    allowed_chars::string,
+                    "invalid control character in string",
+                );
 
-                        // We proceed normally even if
-                        // the string contains invalid escapes.
-                        // It shouldn't affect the rest of the parsing.
-                        self.token_as(IDENT)
-                    }
-                }
+                self.check_escapes();
+                self.token_as(IDENT)
             }
             FLOAT => {
                 if self.lexer.slice().starts_with('0') {
@@ -584,14 +1019,14 @@ impl<'p> Parser<'p> {
                 }
             }
             BOOL => self.token_as(IDENT),
-            _ => self.error("expected identifier"),
+            _ => self.error_expected(KEY_START),
         }
     }
 
     fn parse_value(&mut self) -> ParserResult<()> {
         let t = match self.get_token() {
             Ok(t) => t,
-            Err(_) => return self.error("expected value"),
+            Err(_) => return self.error_expected(VALUE_START),
         };
 
         match t {
@@ -665,110 +1100,36 @@ impl<'p> Parser<'p> {
                 }
             }
             STRING_LITERAL => {
-                match allowed_chars::string_literal(self.lexer.slice()) {
-                    Ok(_) => {}
-                    Err(err_indices) => {
-                        for e in err_indices {
-                            self.add_error(&Error {
-                                range: TextRange::new(
-                                    (self.lexer.span().start + e).try_into().unwrap(),
-                                    (self.lexer.span().start + e).try_into().unwrap(),
-                                ),
-                                message: "invalid control character in string literal".into(),
-                            });
-                        }
-                    }
-                };
+                self.check_allowed_chars(
+                    allowed_chars::string_literal,
+                    "invalid control character in string",
+                );
                 self.token()
             }
             MULTI_LINE_STRING_LITERAL => {
-                match allowed_chars::multi_line_string_literal(self.lexer.slice()) {
-                    Ok(_) => {}
-                    Err(err_indices) => {
-                        for e in err_indices {
-                            self.add_error(&Error {
-                                range: TextRange::new(
-                                    (self.lexer.span().start + e).try_into().unwrap(),
-                                    (self.lexer.span().start + e).try_into().unwrap(),
-                                ),
-                                message: "invalid character in string".into(),
-                            });
-                        }
-                    }
-                };
+                self.check_allowed_chars(
+                    allowed_chars::multi_line_string_literal,
+                    "invalid control character in string",
+                );
                 self.token()
             }
             STRING => {
-                match allowed_chars::string(self.lexer.slice()) {
-                    Ok(_) => {}
-                    Err(err_indices) => {
-                        for e in err_indices {
-                            self.add_error(&Error {
-                                range: TextRange::new(
-                                    (self.lexer.span().start + e).try_into().unwrap(),
-                                    (self.lexer.span().start + e).try_into().unwrap(),
-                                ),
-                                message: "invalid character in string".into(),
-                            });
-                        }
-                    }
-                };
-
-                match check_escape(self.lexer.slice()) {
-                    Ok(_) => self.token(),
-                    Err(err_indices) => {
-                        for e in err_indices {
-                            self.add_error(&Error {
-                                range: TextRange::new(
-                                    (self.lexer.span().start + e).try_into().unwrap(),
-                                    (self.lexer.span().start + e).try_into().unwrap(),
-                                ),
-                                message: "invalid escape sequence".into(),
-                            });
-                        }
+                self.check_allowed_chars(
+                    allowed_chars::string,
+                    "invalid control character in string",
+                );
 
-                        // We proceed normally even if
-                        // the string contains invalid escapes.
-                        // It shouldn't affect the rest of the parsing.
-                        self.token()
-                    }
-                }
+                self.check_escapes();
+                self.token()
             }
             MULTI_LINE_STRING => {
-                match allowed_chars::multi_line_string(self.lexer.slice()) {
-                    Ok(_) => {}
-                    Err(err_indices) => {
-                        for e in err_indices {
-                            self.add_error(&Error {
-                                range: TextRange::new(
-                                    (self.lexer.span().start + e).try_into().unwrap(),
-                                    (self.lexer.span().start + e).try_into().unwrap(),
-                                ),
-                                message: "invalid character in string".into(),
-                            });
-                        }
-                    }
-                };
+                self.check_allowed_chars(
+                    allowed_chars::multi_line_string,
+                    "invalid control character in string",
+                );
 
-                match check_escape(self.lexer.slice()) {
-                    Ok(_) => self.token(),
-                    Err(err_indices) => {
-                        for e in err_indices {
-                            self.add_error(&Error {
-                                range: TextRange::new(
-                                    (self.lexer.span().start + e).try_into().unwrap(),
-                                    (self.lexer.span().start + e).try_into().unwrap(),
-                                ),
-                                message: "invalid escape sequence".into(),
-                            });
-                        }
-
-                        // We proceed normally even if
-                        // the string contains invalid escapes.
-                        // It shouldn't affect the rest of the parsing.
-                        self.token()
-                    }
-                }
+                self.check_escapes();
+                self.token()
             }
             BRACKET_START => {
                 with_node!(self.builder, ARRAY, self.parse_array())
@@ -778,53 +1139,90 @@ impl<'p> Parser<'p> {
             }
             IDENT | BRACE_END => {
                 // FIXME(bit_flags): This branch is just a workaround.
-                self.report_error("expected value").ok();
+                self.report_expected(VALUE_START).ok();
                 Ok(())
             }
-            _ => self.error("expected value"),
+            _ => self.error_expected(VALUE_START),
         }
     }
 
     fn parse_inline_table(&mut self) -> ParserResult<()> {
-        self.must_token_or(BRACE_START, r#"expected "{""#)?;
+        self.must_token_or(BRACE_START, &[BRACE_START])?;
 
         let mut first = true;
         let mut comma_last = false;
         let mut was_newline = false;
 
+        // Whether the table is closed on a later line, decided at the first newline.
+        let mut closed_ahead = None;
+
         loop {
             let t = match self.get_token() {
                 Ok(t) => t,
-                Err(_) => return self.report_error(r#"expected "}""#),
+                Err(_) => {
+                    return if comma_last {
+                        self.report_expected(KEY_START)
+                    } else {
+                        self.report_expected(&[COMMA, BRACE_END])
+                    }
+                }
             };
 
+            if t != NEWLINE
+                && closed_ahead != Some(true)
+                && self.at_line_start()
+                && self.at_entry_line()
+            {
+                return self.report_expected(&[COMMA, BRACE_END]);
+            }
+
             match t {
                 BRACE_END => {
                     if comma_last {
                         // it is still reported as a syntax error,
                         // but we can still analyze it as if it was a valid
                         // table.
-                        let _ = self.report_error("expected value, trailing comma is not allowed");
+                        let _ = self.report_error_with(
+                            "expected value, trailing comma is not allowed",
+                            KEY_START,
+                        );
                     }
                     break self.add_token()?;
                 }
                 NEWLINE => {
+                    // The newline is reported, but the entries on the
+                    // following lines still belong to the table if it is closed,
+                    // otherwise the table ends here so that the following
+                    // entries are not lost.
+                    if *closed_ahead.get_or_insert_with(|| self.inline_table_closed_ahead()) {
+                        let _ = self.report_error_with(
+                            "newline is not allowed in an inline table",
+                            &[COMMA, BRACE_END],
+                        );
+                        self.token()?;
+                        continue;
+                    }
+
                     // To avoid infinite loop in case
                     // new lines are whitelisted.
                     if was_newline {
                         break;
                     }
 
-                    let _ = self.error("newline is not allowed in an inline table");
+                    let _ = self.error_with(
+                        "newline is not allowed in an inline table",
+                        &[COMMA, BRACE_END],
+                    );
                     was_newline = true;
                 }
                 COMMA => {
                     if comma_last {
-                        let _ = self.report_error(r#"unexpected ",""#);
+                        let _ = self.report_error_with(r#"unexpected ",""#, KEY_START);
                     }
 
                     if first {
-                        let _ = self.error(r#"unexpected ",""#);
+                        let _ = self
+                            .error_with(r#"unexpected ",""#, &[KEY_START, &[BRACE_END]].concat());
                     } else {
                         self.token()?;
                     }
@@ -834,7 +1232,7 @@ impl<'p> Parser<'p> {
                 _ => {
                     was_newline = false;
                     if !comma_last && !first {
-                        let _ = self.error(r#"expected ",""#);
+                        let _ = self.error_expected(&[COMMA, BRACE_END]);
                     }
                     let _ = whitelisted!(
                         self,
@@ -850,8 +1248,47 @@ impl<'p> Parser<'p> {
         Ok(())
     }
 
+    /// Whether the inline table is closed after the current token,
+    /// before a line that starts with a bracket (e.g. a table header).
+    fn inline_table_closed_ahead(&self) -> bool {
+        let lexer = self.lexer.clone();
+
+        let mut braces = 0_usize;
+        let mut brackets = 0_usize;
+        let mut line_start = false;
+
+        for token in lexer {
+            match token {
+                NEWLINE => {
+                    line_start = true;
+                    continue;
+                }
+                WHITESPACE | COMMENT => continue,
+                BRACE_START => braces += 1,
+                BRACE_END => {
+                    if braces == 0 {
+                        return true;
+                    }
+                    braces -= 1;
+                }
+                BRACKET_START => {
+                    if line_start && brackets == 0 {
+                        return false;
+                    }
+                    brackets += 1;
+                }
+                BRACKET_END => brackets = brackets.saturating_sub(1),
+                _ => {}
+            }
+
+            line_start = false;
+        }
+
+        false
+    }
+
     fn parse_array(&mut self) -> ParserResult<()> {
-        self.must_token_or(BRACKET_START, r#"expected "[""#)?;
+        self.must_token_or(BRACKET_START, &[BRACKET_START])?;
 
         let mut first = true;
         let mut comma_last = false;
@@ -859,11 +1296,28 @@ impl<'p> Parser<'p> {
             let t = match self.get_token() {
                 Ok(t) => t,
                 Err(_) => {
-                    let _ = self.report_error("unexpected EOF");
+                    let _ = if first || comma_last {
+                        self.report_error_with(
+                            "unexpected EOF",
+                            &[VALUE_START, &[BRACKET_END]].concat(),
+                        )
+                    } else {
+                        self.report_error_with("unexpected EOF", &[COMMA, BRACKET_END])
+                    };
                     return Err(());
                 }
             };
 
+            // The closing bracket is most likely missing,
+            // leave the rest of the document to the root.
+            if t != NEWLINE && self.at_line_start() && self.at_entry_line() {
+                return if first || comma_last {
+                    self.report_expected(&[VALUE_START, &[BRACKET_END]].concat())
+                } else {
+                    self.report_expected(&[COMMA, BRACKET_END])
+                };
+            }
+
             match t {
                 BRACKET_END => break self.add_token()?,
                 NEWLINE => {
@@ -872,14 +1326,17 @@ impl<'p> Parser<'p> {
                 }
                 COMMA => {
                     if first || comma_last {
-                        let _ = self.error(r#"unexpected ",""#);
+                        let _ = self.error_with(
+                            r#"unexpected ",""#,
+                            &[VALUE_START, &[BRACKET_END]].concat(),
+                        );
                     }
                     self.token()?;
                     comma_last = true;
                 }
                 _ => {
                     if !comma_last && !first {
-                        let _ = self.error(r#"expected ",""#);
+                        let _ = self.error_expected(&[COMMA, BRACKET_END]);
                     }
                     let _ = whitelisted!(
                         self,
@@ -916,6 +1373,62 @@ fn check_underscores(s: &str, radix: u32) -> bool {
     true
 }
 
+/// Tokens that can start a value.
+const VALUE_START: &[SyntaxKind] = &[
+    STRING,
+    MULTI_LINE_STRING,
+    STRING_LITERAL,
+    MULTI_LINE_STRING_LITERAL,
+    INTEGER,
+    INTEGER_HEX,
+    INTEGER_OCT,
+    INTEGER_BIN,
+    FLOAT,
+    BOOL,
+    DATE_TIME_OFFSET,
+    DATE_TIME_LOCAL,
+    DATE,
+    TIME,
+    BRACKET_START,
+    BRACE_START,
+];
+
+/// Tokens that can be a key or a part of a dotted key.
+const KEY_START: &[SyntaxKind] = &[IDENT, STRING, STRING_LITERAL];
+
+/// Describe a set of expected tokens, tokens that can start a value or a key
+/// are described as "value" and "identifier" respectively.
+fn expected_message(expected: &[SyntaxKind]) -> String {
+    let mut rest = expected.to_vec();
+    let mut descriptions = Vec::new();
+
+    for (group, description) in [(VALUE_START, "value"), (KEY_START, "identifier")] {
+        if group.iter().all(|kind| rest.contains(kind)) {
+            rest.retain(|kind| !group.contains(kind));
+            descriptions.push(description.to_string());
+        }
+    }
+
+    descriptions.extend(rest.into_iter().map(|kind| match kind {
+        EQ => r#""=""#.into(),
+        PERIOD => r#"".""#.into(),
+        COMMA => r#"",""#.into(),
+        BRACKET_START => r#""[""#.into(),
+        BRACKET_END => r#""]""#.into(),
+        BRACE_START => r#""{""#.into(),
+        BRACE_END => r#""}""#.into(),
+        NEWLINE => "new line".into(),
+        IDENT => "identifier".into(),
+        kind => format!("{kind:?}").to_lowercase().replace('_', " "),
+    }));
+
+    match descriptions.split_last() {
+        Some((last, [])) => format!("expected {last}"),
+        Some((last, init)) => format!("expected {} or {last}", init.join(", ")),
+        None => "expected nothing".into(),
+    }
+}
+
 /// The final results of a parsing.
 /// It contains the green tree, and
 /// the errors that ocurred during parsing.
@@ -926,6 +1439,14 @@ pub struct Parse {
 }
 
 impl Parse {
+    /// Whether the document starts with a UTF-8 byte order mark.
+    pub fn has_bom(&self) -> bool {
+        matches!(
+            self.green_node.children().next(),
+            Some(NodeOrToken::Token(t)) if t.kind() == BOM.into()
+        )
+    }
+
     /// Turn the parse into a syntax node.
     pub fn into_syntax(self) -> SyntaxNode {
         SyntaxNode::new_root(self.green_node)
@@ -938,4 +1459,170 @@ impl Parse {
     pub fn into_dom(self) -> dom::node::Node {
         dom::Node::from_syntax(self.into_syntax().into())
     }
+
+    /// Apply an edit to the parsed source and return the new parse.
+    ///
+    /// The text in `edit_range` is replaced with `replacement`.
+    ///
+    /// If the edit is contained in a single top-level entry, only that entry
+    /// is parsed again and the rest of the green tree is reused. If the
+    /// entry cannot be parsed in isolation (e.g. because of an unclosed
+    /// multi-line string, or when the edit spans table headers or newlines
+    /// between entries), the entire new source is parsed instead.
+    ///
+    /// In both cases the result is the same as parsing the new source
+    /// from scratch.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the edit range is out of bounds or not on character boundaries.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use taplo::{parser::parse, rowan::TextRange};
+    ///
+    /// let p = parse("a = 1\nb = 2\n");
+    /// let p = p.update(TextRange::new(4.into(), 5.into()), "\"one\"");
+    ///
+    /// assert_eq!(p.clone().into_syntax().to_string(), "a = \"one\"\nb = 2\n");
+    /// assert!(p.errors.is_empty());
+    /// ```
+    #[must_use]
+    pub fn update(&self, edit_range: TextRange, replacement: &str) -> Parse {
+        self.reparse_entry(edit_range, replacement)
+            .unwrap_or_else(|| {
+                let mut source = SyntaxNode::new_root(self.green_node.clone()).to_string();
+                source.replace_range(
+                    usize::from(edit_range.start())..usize::from(edit_range.end()),
+                    replacement,
+                );
+                parse(&source)
+            })
+    }
+
+    /// Parse the top-level entry that contains the edit again,
+    /// only the text of the entry is edited and parsed.
+    fn reparse_entry(&self, edit_range: TextRange, replacement: &str) -> Option<Parse> {
+        let root = SyntaxNode::new_root(self.green_node.clone());
+
+        let (index, entry) = root
+            .children_with_tokens()
+            .enumerate()
+            .find(|(_, c)| c.kind() == ENTRY && c.text_range().contains_range(edit_range))?;
+
+        // Entries always start after a newline (and possibly
+        // some indentation), and end with a newline or the end of the document.
+        let mut prev = entry.prev_sibling_or_token();
+        if prev.as_ref().map(|p| p.kind()) == Some(WHITESPACE) {
+            prev = prev.and_then(|p| p.prev_sibling_or_token());
+        }
+        if prev.is_some_and(|p| p.kind() != NEWLINE) {
+            return None;
+        }
+
+        // Strings that are not closed are lexed up to the end of the document,
+        // these could be closed by the edit.
+        let unclosed_string = root
+            .descendants_with_tokens()
+            .filter_map(NodeOrToken::into_token)
+            .take_while(|t| t.text_range().start() < entry.text_range().start())
+            .any(|t| t.kind() == ERROR && t.text().starts_with(['"', '\'']));
+        if unclosed_string {
+            return None;
+        }
+
+        let newline = match entry.next_sibling_or_token() {
+            Some(NodeOrToken::Token(t)) if t.kind() == NEWLINE => t.text().to_string(),
+            Some(_) => return None,
+            None => String::new(),
+        };
+
+        let old_range = entry.text_range();
+        let delta = TextSize::of(replacement);
+        let new_end = old_range.end() - edit_range.len() + delta;
+        let new_range = TextRange::new(old_range.start(), new_end);
+
+        let mut entry_source = entry.to_string();
+        let relative_range = edit_range - old_range.start();
+        entry_source.replace_range(
+            usize::from(relative_range.start())..usize::from(relative_range.end()),
+            replacement,
+        );
+        entry_source.push_str(&newline);
+
+        // The tokens of the entry must not extend past it in the new source,
+        // otherwise they would be different when lexed in isolation.
+        // The newline after the entry is enough to tell, such tokens
+        // would also extend into it when lexed in isolation.
+        let mut lexer = SyntaxKind::lexer(&entry_source);
+        loop {
+            lexer.next()?;
+            let end = TextSize::try_from(lexer.span().end).ok()?;
+            match end.cmp(&new_range.len()) {
+                std::cmp::Ordering::Less => continue,
+                std::cmp::Ordering::Equal => break,
+                std::cmp::Ordering::Greater => return None,
+            }
+        }
+
+        let reparsed = parse(&entry_source);
+        let reparsed_root = SyntaxNode::new_root(reparsed.green_node.clone());
+
+        let mut children = reparsed_root.children_with_tokens();
+        let new_entry = match children.next()? {
+            NodeOrToken::Node(n)
+                if n.kind() == ENTRY && n.text_range().len() == new_range.len() =>
+            {
+                n.green().into_owned()
+            }
+            _ => return None,
+        };
+        if !newline.is_empty() && children.next()?.kind() != NEWLINE {
+            return None;
+        }
+        if children.next().is_some() {
+            return None;
+        }
+
+        // Errors of the old entry, including the ones
+        // reported at the newline after it.
+        let old_end = old_range.end() + TextSize::of(newline.as_str());
+        let is_entry_error = |e: &Error| {
+            e.range.start() >= old_range.start()
+                && (e.range.start() < old_end || newline.is_empty())
+        };
+
+        let mut errors: Vec<Error> = self
+            .errors
+            .iter()
+            .filter(|e| e.range.start() < old_range.start())
+            .cloned()
+            .collect();
+
+        errors.extend(reparsed.errors.into_iter().map(|mut e| {
+            e.range += old_range.start();
+            e
+        }));
+
+        errors.extend(
+            self.errors
+                .iter()
+                .filter(|e| e.range.start() >= old_range.start() && !is_entry_error(e))
+                .map(|e| Error {
+                    range: TextRange::new(
+                        e.range.start() - edit_range.len() + delta,
+                        e.range.end() - edit_range.len() + delta,
+                    ),
+                    ..e.clone()
+                }),
+        );
+
+        Some(Parse {
+            green_node: self
+                .green_node
+                .replace_child(index, NodeOrToken::Node(new_entry)),
+            errors,
+        })
+    }
 }
@@ -0,0 +1,223 @@
+//! Generate a starter JSON schema from an example document.
+//!
+//! The types of the schema are inferred from the values in the document,
+//! tables become objects with properties, and the items of arrays are
+//! inferred from the union of the types of their elements.
+//!
+//! None of the properties are required, the schema is
+//! meant to be a starting point for further customization.
+//!
+//! # Example
+//!
+//! ```
+//! let dom = taplo::parser::parse("name = 'taplo'\nversion = 1").into_dom();
+//! let schema = taplo::schema_gen::from_dom(&dom, &Default::default());
+//!
+//! let properties = &schema.schema.object.as_ref().unwrap().properties;
+//! assert!(properties.contains_key("name"));
+//! assert!(properties.contains_key("version"));
+//! ```
+
+use crate::dom::{node::DateKind, Node};
+use schemars::schema::{
+    ArrayValidation, InstanceType, ObjectValidation, RootSchema, Schema, SchemaObject,
+    SubschemaValidation,
+};
+
+/// Schema generation options.
+#[derive(Debug, Clone, Default)]
+pub struct Options {
+    /// The title of the generated schema.
+    pub title: Option<String>,
+}
+
+/// Generate a JSON schema that describes the given document.
+pub fn from_dom(dom: &Node, options: &Options) -> RootSchema {
+    let mut schema = Inferred::from_node(dom).into_schema_object();
+
+    if let Some(title) = &options.title {
+        schema.metadata().title = Some(title.clone());
+    }
+
+    RootSchema {
+        meta_schema: Some("http://json-schema.org/draft-07/schema#".into()),
+        schema,
+        ..Default::default()
+    }
+}
+
+/// Local date-times and local times have no JSON Schema format,
+/// they are matched by patterns instead.
+const LOCAL_DATE_TIME_PATTERN: &str = r"^\d{4}-\d{2}-\d{2}[Tt ]\d{2}:\d{2}(:\d{2}(\.\d+)?)?$";
+const LOCAL_TIME_PATTERN: &str = r"^\d{2}:\d{2}(:\d{2}(\.\d+)?)?$";
+
+/// The type inferred from one or more values.
+#[derive(Debug, Clone, PartialEq)]
+enum Inferred {
+    String { date: Option<DateKind> },
+    Integer,
+    Number,
+    Boolean,
+    Object(Vec<(String, Inferred)>),
+    Array(Option<Box<Inferred>>),
+    AnyOf(Vec<Inferred>),
+}
+
+impl Inferred {
+    fn from_node(node: &Node) -> Self {
+        match node {
+            Node::Table(table) => Inferred::Object(table.entries().read().iter().fold(
+                Vec::new(),
+                |mut properties, (key, node)| {
+                    add_property(&mut properties, key.value(), Inferred::from_node(node));
+                    properties
+                },
+            )),
+            Node::Array(arr) => Inferred::Array(
+                arr.items()
+                    .read()
+                    .iter()
+                    .map(Inferred::from_node)
+                    .reduce(Inferred::merge)
+                    .map(Box::new),
+            ),
+            Node::Bool(_) => Inferred::Boolean,
+            Node::Str(_) => Inferred::String { date: None },
+            Node::Integer(_) => Inferred::Integer,
+            Node::Float(_) => Inferred::Number,
+            Node::Date(date) => Inferred::String {
+                date: Some(date.kind()),
+            },
+            Node::Invalid(_) => Inferred::AnyOf(Vec::new()),
+        }
+    }
+
+    /// The union of two inferred types.
+    fn merge(self, other: Inferred) -> Inferred {
+        match (self, other) {
+            (Inferred::AnyOf(a), b) if a.is_empty() => b,
+            (a, Inferred::AnyOf(b)) if b.is_empty() => a,
+            (Inferred::AnyOf(mut variants), other) => {
+                match other {
+                    Inferred::AnyOf(others) => {
+                        for other in others {
+                            add_variant(&mut variants, other);
+                        }
+                    }
+                    other => add_variant(&mut variants, other),
+                }
+                Inferred::AnyOf(variants)
+            }
+            (this, Inferred::AnyOf(variants)) => {
+                Inferred::AnyOf(vec![this]).merge(Inferred::AnyOf(variants))
+            }
+            (Inferred::String { date: a }, Inferred::String { date: b }) => Inferred::String {
+                date: if a == b { a } else { None },
+            },
+            (Inferred::Integer | Inferred::Number, Inferred::Number)
+            | (Inferred::Number, Inferred::Integer) => Inferred::Number,
+            (Inferred::Object(mut properties), Inferred::Object(others)) => {
+                for (key, other) in others {
+                    add_property(&mut properties, &key, other);
+                }
+                Inferred::Object(properties)
+            }
+            (Inferred::Array(a), Inferred::Array(b)) => Inferred::Array(match (a, b) {
+                (Some(a), Some(b)) => Some(Box::new(a.merge(*b))),
+                (a, b) => a.or(b),
+            }),
+            (a, b) if a == b => a,
+            (a, b) => Inferred::AnyOf(vec![a, b]),
+        }
+    }
+
+    fn kind(&self) -> u8 {
+        match self {
+            Inferred::String { .. } => 0,
+            Inferred::Integer | Inferred::Number => 1,
+            Inferred::Boolean => 2,
+            Inferred::Object(_) => 3,
+            Inferred::Array(_) => 4,
+            Inferred::AnyOf(_) => 5,
+        }
+    }
+
+    fn into_schema_object(self) -> SchemaObject {
+        let instance_type = |ty: InstanceType| SchemaObject {
+            instance_type: Some(ty.into()),
+            ..Default::default()
+        };
+
+        match self {
+            Inferred::String { date } => {
+                let mut schema = instance_type(InstanceType::String);
+                match date {
+                    Some(DateKind::OffsetDateTime) => schema.format = Some("date-time".into()),
+                    Some(DateKind::LocalDate) => schema.format = Some("date".into()),
+                    Some(DateKind::LocalDateTime) => {
+                        schema.string().pattern = Some(LOCAL_DATE_TIME_PATTERN.into());
+                    }
+                    Some(DateKind::LocalTime) => {
+                        schema.string().pattern = Some(LOCAL_TIME_PATTERN.into());
+                    }
+                    None => {}
+                }
+                schema
+            }
+            Inferred::Integer => instance_type(InstanceType::Integer),
+            Inferred::Number => instance_type(InstanceType::Number),
+            Inferred::Boolean => instance_type(InstanceType::Boolean),
+            Inferred::Object(properties) => SchemaObject {
+                object: Some(Box::new(ObjectValidation {
+                    properties: properties
+                        .into_iter()
+                        .map(|(key, ty)| (key, ty.into_schema()))
+                        .collect(),
+                    ..Default::default()
+                })),
+                ..instance_type(InstanceType::Object)
+            },
+            Inferred::Array(items) => SchemaObject {
+                array: items.map(|items| {
+                    Box::new(ArrayValidation {
+                        items: Some(items.into_schema().into()),
+                        ..Default::default()
+                    })
+                }),
+                ..instance_type(InstanceType::Array)
+            },
+            Inferred::AnyOf(variants) if variants.is_empty() => SchemaObject::default(),
+            Inferred::AnyOf(variants) => SchemaObject {
+                subschemas: Some(Box::new(SubschemaValidation {
+                    any_of: Some(variants.into_iter().map(Inferred::into_schema).collect()),
+                    ..Default::default()
+                })),
+                ..Default::default()
+            },
+        }
+    }
+
+    fn into_schema(self) -> Schema {
+        Schema::Object(self.into_schema_object())
+    }
+}
+
+/// Add a property, merging it with an existing one with the same key.
+fn add_property(properties: &mut Vec<(String, Inferred)>, key: &str, ty: Inferred) {
+    match properties.iter_mut().find(|(k, _)| k == key) {
+        Some((_, existing)) => {
+            *existing = existing.clone().merge(ty);
+        }
+        None => properties.push((key.into(), ty)),
+    }
+}
+
+/// Add a variant to an `anyOf`, merging it with an existing variant of the same kind.
+fn add_variant(variants: &mut Vec<Inferred>, ty: Inferred) {
+    match variants.iter_mut().find(|v| v.kind() == ty.kind()) {
+        Some(existing) => {
+            *existing = existing.clone().merge(ty);
+        }
+        None => variants.push(ty),
+    }
+}
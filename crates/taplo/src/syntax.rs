@@ -4,14 +4,17 @@
 
 use logos::{Lexer, Logos};
 
-/// Enum containing all the tokens in a syntax tree.
+/// Enum containing all the tokens and nodes in a syntax tree,
+/// see [`SyntaxKind::is_token`].
 #[derive(Logos, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
 #[repr(u16)]
 pub enum SyntaxKind {
     #[regex(r"([ \t])+")]
     WHITESPACE = 0,
 
-    #[regex(r"(\n|\r\n)+")]
+    /// A bare `\r` is not a valid line ending, it is lexed
+    /// as one anyway and reported by the parser.
+    #[regex(r"(\n|\r\n|\r)+")]
     NEWLINE,
 
     #[regex(r"#[^\n\r]*")]
@@ -88,6 +91,10 @@ pub enum SyntaxKind {
     #[token("}")]
     BRACE_END,
 
+    /// A UTF-8 byte order mark at the start of the document,
+    /// it is not produced by the lexer.
+    BOM,
+
     #[error]
     ERROR,
 
@@ -103,6 +110,17 @@ pub enum SyntaxKind {
     ROOT, // root node
 }
 
+impl SyntaxKind {
+    /// Whether the kind is a token kind, every kind declared
+    /// before `KEY` is a token, the rest are nodes of the syntax tree.
+    ///
+    /// Tokens are the leaves of the syntax tree
+    /// and the items returned by [`lex`](crate::parser::lex).
+    pub fn is_token(self) -> bool {
+        self < SyntaxKind::KEY
+    }
+}
+
 impl From<SyntaxKind> for rowan::SyntaxKind {
     fn from(kind: SyntaxKind) -> Self {
         Self(kind as u16)
@@ -126,6 +144,10 @@ pub type SyntaxNode = rowan::SyntaxNode<Lang>;
 pub type SyntaxToken = rowan::SyntaxToken<Lang>;
 pub type SyntaxElement = rowan::NodeOrToken<SyntaxNode, SyntaxToken>;
 
+/// Basic and literal strings cannot span lines, so an unterminated
+/// string ends at the line break and the rest of the line is
+/// reported as a single error token instead of swallowing the
+/// following lines.
 fn lex_string(lex: &mut Lexer<SyntaxKind>) -> bool {
     let remainder: &str = lex.remainder();
     let mut escaped = false;
@@ -133,6 +155,10 @@ fn lex_string(lex: &mut Lexer<SyntaxKind>) -> bool {
     let mut total_len = 0;
 
     for c in remainder.chars() {
+        if c == '\n' || c == '\r' {
+            break;
+        }
+
         total_len += c.len_utf8();
 
         if c == '\\' {
@@ -141,12 +167,14 @@ fn lex_string(lex: &mut Lexer<SyntaxKind>) -> bool {
         }
 
         if c == '"' && !escaped {
-            lex.bump(remainder[0..total_len].as_bytes().len());
+            lex.bump(total_len);
             return true;
         }
 
         escaped = false;
     }
+
+    lex.bump(total_len);
     false
 }
 
@@ -171,8 +199,7 @@ fn lex_multi_line_string(lex: &mut Lexer<SyntaxKind>) -> bool {
                     return false;
                 }
 
-                lex.bump(remainder[0..total_len].as_bytes().len());
-                return true;
+                return end_multi_line(lex, total_len, '"');
             } else {
                 quote_count += 1;
                 total_len += c.len_utf8();
@@ -205,10 +232,9 @@ fn lex_multi_line_string(lex: &mut Lexer<SyntaxKind>) -> bool {
             return false;
         }
 
-        lex.bump(remainder[0..total_len].as_bytes().len());
-        true
+        end_multi_line(lex, total_len, '"')
     } else {
-        false
+        unterminated_multi_line(lex)
     }
 }
 
@@ -217,13 +243,19 @@ fn lex_string_literal(lex: &mut Lexer<SyntaxKind>) -> bool {
     let mut total_len = 0;
 
     for c in remainder.chars() {
+        if c == '\n' || c == '\r' {
+            break;
+        }
+
         total_len += c.len_utf8();
 
         if c == '\'' {
-            lex.bump(remainder[0..total_len].as_bytes().len());
+            lex.bump(total_len);
             return true;
         }
     }
+
+    lex.bump(total_len);
     false
 }
 
@@ -242,8 +274,7 @@ fn lex_multi_line_string_literal(lex: &mut Lexer<SyntaxKind>) -> bool {
     for c in remainder.chars() {
         if quotes_found {
             if c != '\'' {
-                lex.bump(remainder[0..total_len].as_bytes().len());
-                return true;
+                return end_multi_line(lex, total_len, '\'');
             } else {
                 if quote_count > 4 {
                     return false;
@@ -269,9 +300,81 @@ fn lex_multi_line_string_literal(lex: &mut Lexer<SyntaxKind>) -> bool {
 
     // End of input
     if quotes_found {
-        lex.bump(remainder[0..total_len].as_bytes().len());
-        true
+        end_multi_line(lex, total_len, '\'')
     } else {
-        false
+        unterminated_multi_line(lex)
+    }
+}
+
+/// Accepts a multi-line string of `len` bytes unless its closing
+/// delimiter looks like the opening delimiter of a later `key = """`
+/// entry and the string spans a table header on the way there,
+/// which usually means that the actual closing delimiter is missing.
+///
+/// In that case the string is cut off before the header line
+/// so that the tables that follow are still parsed.
+fn end_multi_line(lex: &mut Lexer<SyntaxKind>, len: usize, quote: char) -> bool {
+    let body = &lex.remainder()[..len];
+    let content = body.trim_end_matches(quote);
+
+    if let Some(cut) = header_line_break(content) {
+        let last_line = &content[content.rfind('\n').map_or(0, |i| i + 1)..];
+
+        if looks_like_assignment(last_line) {
+            lex.bump(cut);
+            return false;
+        }
     }
+
+    lex.bump(len);
+    true
+}
+
+/// Unterminated multi-line strings consume the input at most
+/// until the next line that looks like a table header.
+fn unterminated_multi_line(lex: &mut Lexer<SyntaxKind>) -> bool {
+    let remainder = lex.remainder();
+    lex.bump(header_line_break(remainder).unwrap_or(remainder.len()));
+    false
+}
+
+/// Returns the position of the line break before the first
+/// line in `s` that looks like a table header.
+fn header_line_break(s: &str) -> Option<usize> {
+    s.match_indices('\n')
+        .find(|(i, _)| {
+            let line = s[i + 1..].lines().next().unwrap_or_default();
+            looks_like_table_header(line)
+        })
+        .map(|(i, _)| if s[..i].ends_with('\r') { i - 1 } else { i })
+}
+
+fn is_key_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || matches!(c, '_' | '-' | '.' | ' ' | '\t' | '"' | '\'')
+}
+
+/// Whether the line is `[key]` or `[[key]]` with an optional comment.
+fn looks_like_table_header(line: &str) -> bool {
+    let Some(rest) = line.strip_prefix('[') else {
+        return false;
+    };
+    let rest = rest.strip_prefix('[').unwrap_or(rest);
+
+    let Some(end) = rest.find(']') else {
+        return false;
+    };
+
+    let key = &rest[..end];
+    let after = rest[end..].trim_start_matches(']').trim();
+
+    !key.trim().is_empty()
+        && key.chars().all(is_key_char)
+        && (after.is_empty() || after.starts_with('#'))
+}
+
+/// Whether the line is `key =` with nothing after the equals sign.
+fn looks_like_assignment(line: &str) -> bool {
+    line.trim()
+        .strip_suffix('=')
+        .is_some_and(|key| !key.trim().is_empty() && key.chars().all(is_key_char))
 }
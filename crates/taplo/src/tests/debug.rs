@@ -0,0 +1,159 @@
+use super::dom::dom_errors;
+use crate::{
+    parser::parse,
+    util::debug::{syntax_debug_string, DebugOptions, TreeStringOptions},
+};
+use std::path::Path;
+
+fn large_document() -> String {
+    let mut src = String::new();
+
+    for i in 0..2000 {
+        src += &format!("key_{i} = \"{}\"\n", "x".repeat(100));
+    }
+
+    src += "deep = ";
+    src += &"[".repeat(200);
+    src += &"]".repeat(200);
+    src += "\n";
+
+    src
+}
+
+#[test]
+fn debug_string_max_length() {
+    let dom = parse(&large_document()).into_dom();
+
+    let options = DebugOptions {
+        max_length: 4096,
+        ..Default::default()
+    };
+
+    let debug = dom.debug_string(&options);
+    assert!(debug.truncated);
+    assert!(debug.text.len() <= options.max_length);
+    assert!(debug.text.ends_with('\n'));
+
+    let syntax = parse(&large_document()).into_syntax();
+    let debug = syntax_debug_string(&syntax, &options);
+    assert!(debug.truncated);
+    assert!(debug.text.len() <= options.max_length);
+    assert!(debug.text.ends_with('\n'));
+}
+
+#[test]
+fn debug_string_max_depth() {
+    let dom = parse("a = [[[[[1]]]]]").into_dom();
+
+    let options = DebugOptions {
+        max_depth: 2,
+        ..Default::default()
+    };
+
+    let debug = dom.debug_string(&options);
+    assert!(debug.truncated);
+    assert!(debug
+        .text
+        .lines()
+        .all(|line| !line.starts_with("      ") || line.trim() == "..."));
+    assert!(!debug.text.contains("Integer"));
+
+    let debug = dom.debug_string(&DebugOptions::default());
+    assert!(!debug.truncated);
+    assert!(debug.text.contains("Integer"));
+}
+
+#[test]
+fn debug_string_max_text_length() {
+    let src = format!("a = \"{}\"", "x".repeat(100));
+    let dom = parse(&src).into_dom();
+
+    let options = DebugOptions {
+        max_text_length: 10,
+        ..Default::default()
+    };
+
+    let debug = dom.debug_string(&options);
+    assert!(debug.truncated);
+    assert!(debug.text.contains(&format!("{:?}...", "x".repeat(10))));
+    assert!(!debug.text.contains(&"x".repeat(11)));
+}
+
+/// Compares the tree strings and errors of the fixtures
+/// in `test-data/dom-tree` with the `.tree` files next to them.
+///
+/// Run with `UPDATE_GOLDEN=1` to write the current output instead.
+#[test]
+fn dom_tree_golden_files() {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("../../test-data/dom-tree");
+    let update = std::env::var_os("UPDATE_GOLDEN").is_some();
+
+    let mut fixtures: Vec<_> = std::fs::read_dir(&dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "toml"))
+        .collect();
+    fixtures.sort();
+    assert!(!fixtures.is_empty());
+
+    for fixture in fixtures {
+        let src = std::fs::read_to_string(&fixture).unwrap();
+        let mut tree = parse(&src)
+            .into_dom()
+            .tree_string(&TreeStringOptions::default());
+
+        for error in dom_errors(&src) {
+            tree += &format!("error: {error}\n");
+        }
+
+        let golden = fixture.with_extension("tree");
+        if update {
+            std::fs::write(&golden, &tree).unwrap();
+            continue;
+        }
+
+        let expected = std::fs::read_to_string(&golden)
+            .unwrap_or_else(|err| panic!("{}: {err}", golden.display()));
+        assert!(
+            tree == expected,
+            "{} does not match, run with UPDATE_GOLDEN=1 to update it:\n{}",
+            golden.display(),
+            difference::Changeset::new(&expected, &tree, "\n")
+        );
+    }
+}
+
+#[test]
+fn tree_string_without_offsets() {
+    let options = TreeStringOptions {
+        offsets: false,
+        ..Default::default()
+    };
+
+    let a = parse("a = { b = [1] }").into_dom().tree_string(&options);
+    let b = parse("\n\na={b=[\n  1,\n]}\n").into_dom().tree_string(&options);
+    assert_eq!(a, b);
+    assert_eq!(a, "Table\n  a: InlineTable\n    a.b: Array\n      a.b[0]: Integer 1\n");
+}
+
+#[test]
+fn tree_string_keys() {
+    let tree = parse("\"a.b\" = 1\n\"\" = 2\n[[c]]\n'd e'.long = 3")
+        .into_dom()
+        .tree_string(&TreeStringOptions {
+            offsets: false,
+            max_text_length: 3,
+        });
+
+    assert_eq!(
+        tree,
+        r#"Table
+  'a.b': Integer 1
+  "": Integer 2
+  c: ArrayOfTables
+    c[0]: Table
+      c[0].'d e': PseudoTable
+        c[0].'d e'.lon...: Integer 3
+"#
+    );
+}
@@ -0,0 +1,1872 @@
+use crate::{
+    dom::{
+        error::ConversionErrorReason,
+        node::{DateKind, IntegerRepr, Key},
+        JsonOptions, Keys, Node,
+    },
+    parser::parse,
+    util::quote_key,
+};
+
+#[test]
+fn quote_keys() {
+    assert_eq!(quote_key("bare_key-1"), "bare_key-1");
+    assert_eq!(quote_key(""), r#""""#);
+    assert_eq!(quote_key("a.b"), "'a.b'");
+    assert_eq!(quote_key("with space"), "'with space'");
+    assert_eq!(quote_key("ü"), "'ü'");
+    assert_eq!(quote_key("it's"), r#""it's""#);
+    assert_eq!(quote_key(r#"a'b"c"#), r#""a'b\"c""#);
+    assert_eq!(quote_key("tab\tkey"), "'tab\tkey'");
+    assert_eq!(quote_key("new\nline"), r#""new\nline""#);
+}
+
+#[test]
+fn quoted_keys_round_trip() {
+    for key in [
+        "",
+        "a.b",
+        "with space",
+        "it's",
+        r#"a'b"c"#,
+        "tab\tkey",
+        "new\nline",
+    ] {
+        let src = format!("{} = 1", Key::new(key));
+        let parse = parse(&src);
+        assert!(parse.errors.is_empty(), "{:#?}", parse.errors);
+
+        let dom = parse.into_dom();
+        assert!(dom.validate().is_ok());
+        assert!(dom.get(key).is_integer(), "{src}");
+    }
+}
+
+#[test]
+fn dotted_keys_are_quoted() {
+    let keys = Keys::new(
+        [Key::new("a.b"), Key::new("c"), Key::new("")]
+            .into_iter()
+            .map(Into::into),
+    );
+    assert_eq!(keys.dotted(), r#"'a.b'.c."""#);
+
+    let src = format!("{keys} = 1");
+    let dom = parse(&src).into_dom();
+    assert!(dom.get("a.b").get("c").get("").is_integer());
+    assert!(dom.get("a").is_invalid());
+}
+
+#[test]
+fn unescaped_keys() {
+    let dom = parse(
+        r#"
+"tab\tkey" = 1
+"abc" = 2
+'lit\eral' = 3
+"#,
+    )
+    .into_dom();
+
+    assert!(dom.validate().is_ok());
+    assert!(dom.get("tab\tkey").is_integer());
+    assert!(dom.get("abc").is_integer());
+    assert!(dom.get(r#"lit\eral"#).is_integer());
+
+    let quoted = "\"a\\u0062c\".d".parse::<Keys>().unwrap();
+    let bare = "abc.d".parse::<Keys>().unwrap();
+    assert_eq!(quoted, bare);
+
+    let mut set = crate::HashSet::default();
+    set.insert(quoted);
+    assert!(set.contains(&bare));
+}
+
+#[test]
+fn escaped_keys_conflict() {
+    let dom = parse(
+        r#"
+abc = 1
+"abc" = 2
+"#,
+    )
+    .into_dom();
+
+    let errors = dom.validate().unwrap_err().collect::<Vec<_>>();
+    assert!(matches!(
+        errors.as_slice(),
+        [crate::dom::Error::ConflictingKeys { .. }]
+    ));
+}
+
+#[test]
+fn invalid_key_escape() {
+    let src = r#""a\qb" = 1"#;
+    let errors = parse(src)
+        .into_dom()
+        .validate()
+        .unwrap_err()
+        .collect::<Vec<_>>();
+
+    match errors.as_slice() {
+        [crate::dom::Error::InvalidEscapeSequence {
+            string,
+            range,
+            error,
+        }] => {
+            assert_eq!(
+                string.text_range(),
+                rowan::TextRange::new(0.into(), 6.into())
+            );
+            assert_eq!(*range, rowan::TextRange::new(2.into(), 4.into()));
+            assert_eq!(error.kind, crate::util::UnescapeErrorKind::Unknown);
+        }
+        errors => panic!("unexpected errors: {errors:#?}"),
+    }
+}
+
+#[test]
+fn typed_casts() {
+    let dom = parse(
+        r#"
+str = "value"
+int = 1
+float = 1.5
+bool = true
+date = 1979-05-27
+arr = [1, 2]
+table = { a = 1 }
+"#,
+    )
+    .into_dom();
+
+    let table = dom.as_table().unwrap();
+
+    let str_node = table.get("str").unwrap();
+    assert_eq!(str_node.as_str().unwrap().value(), "value");
+    assert!(str_node.as_integer().is_none());
+    assert!(str_node.as_table().is_none());
+
+    assert!(table.get("int").unwrap().as_integer().is_some());
+    assert_eq!(table.get("float").unwrap().as_float().unwrap().value(), 1.5);
+    assert!(table.get("bool").unwrap().as_bool().unwrap().value());
+    assert!(table.get("date").unwrap().as_date().is_some());
+    assert_eq!(
+        table
+            .get("arr")
+            .unwrap()
+            .as_array()
+            .unwrap()
+            .items()
+            .read()
+            .len(),
+        2
+    );
+    assert!(table.get("table").unwrap().as_table().is_some());
+
+    let arr = table.get("arr").unwrap();
+    let arr = arr.try_into_table().unwrap_err();
+    assert!(arr.try_into_array().is_ok());
+    assert!(table.get("bool").unwrap().try_into_bool().unwrap().value());
+}
+
+#[test]
+fn entries_lookup() {
+    let dom = parse(
+        r#"
+[package]
+name = "taplo"
+"quoted key" = 1
+
+[[bin]]
+name = "first"
+path = "src/first.rs"
+
+[[bin]]
+name = "second"
+
+[dependencies]
+serde.version = "1"
+"#,
+    )
+    .into_dom();
+
+    let table = dom.as_table().unwrap();
+    let entries = table.entries().read();
+
+    assert!(entries.get("package").unwrap().is_table());
+    assert!(entries.get("missing").is_none());
+    assert!(entries["bin"].is_array());
+
+    let name = entries.get_path(&["package", "name"]).unwrap();
+    assert_eq!(name.as_str().unwrap().value(), "taplo");
+
+    assert!(entries.get_path(&["package", "quoted key"]).is_some());
+
+    let path = entries.get_path(&["bin", "0", "path"]).unwrap();
+    assert_eq!(path.as_str().unwrap().value(), "src/first.rs");
+
+    assert!(entries.get_path(&["bin", "1", "path"]).is_none());
+    assert!(entries.get_path(&["bin", "name"]).is_none());
+    assert!(entries
+        .get_path(&["dependencies", "serde", "version"])
+        .is_some());
+    assert!(entries.get_path(&[]).is_none());
+}
+
+#[test]
+#[should_panic(expected = "no entry found for key 'a b'")]
+fn entries_index_missing() {
+    let dom = parse("a = 1").into_dom();
+    let table = dom.as_table().unwrap();
+    let _ = &table.entries().read()["a b"];
+}
+
+#[test]
+fn node_index() {
+    let dom = parse(
+        r#"
+[package]
+name = "taplo"
+"quoted key" = true
+
+[dependencies]
+serde.version = "1"
+"#,
+    )
+    .into_dom();
+
+    assert_eq!(dom["package"]["name"].as_str().unwrap().value(), "taplo");
+    assert!(dom["package"]["quoted key"].as_bool().unwrap().value());
+    assert_eq!(
+        dom["dependencies"]["serde"]["version"]
+            .as_str()
+            .unwrap()
+            .value(),
+        "1"
+    );
+
+    let package = dom["package"].as_table().unwrap();
+    assert!(package["name"].is_str());
+}
+
+#[test]
+#[should_panic(expected = "no entry found for key 'a b'")]
+fn node_index_missing() {
+    let dom = parse("a = 1").into_dom();
+    let _ = &dom["a b"];
+}
+
+#[test]
+#[should_panic(expected = "with key b")]
+fn node_index_not_table() {
+    let dom = parse("a = 1").into_dom();
+    let _ = &dom["a"]["b"];
+}
+
+#[test]
+fn visit_document_order() {
+    use crate::dom::{
+        node::{Key, Table},
+        visit::{walk, Visitor},
+        Node,
+    };
+
+    #[derive(Default)]
+    struct Events(Vec<String>);
+
+    impl Visitor for Events {
+        fn visit_table(&mut self, path: &Keys, _table: &Table) {
+            self.0.push(format!("table {}", path.dotted()));
+        }
+
+        fn visit_entry(&mut self, path: &Keys, key: &Key, _node: &Node) {
+            self.0
+                .push(format!("entry {} {}", path.dotted(), key.value()));
+        }
+
+        fn visit_array_item(&mut self, path: &Keys, index: usize, _node: &Node) {
+            self.0.push(format!("item {} {index}", path.dotted()));
+        }
+
+        fn visit_value(&mut self, path: &Keys, _node: &Node) {
+            self.0.push(format!("value {}", path.dotted()));
+        }
+    }
+
+    let dom = parse(
+        r#"
+b.c = 1
+inline = { x = [true] }
+
+[[arr]]
+y = "s"
+"#,
+    )
+    .into_dom();
+
+    let mut events = Events::default();
+    walk(&dom, &mut events);
+
+    assert_eq!(
+        events.0,
+        [
+            "table ",
+            "entry b b",
+            "table b",
+            "entry b.c c",
+            "value b.c",
+            "entry inline inline",
+            "table inline",
+            "entry inline.x x",
+            "item inline.x.0 0",
+            "value inline.x.0",
+            "entry arr arr",
+            "item arr.0 0",
+            "table arr.0",
+            "entry arr.0.y y",
+            "value arr.0.y",
+        ]
+    );
+}
+
+#[test]
+fn flat_entries() {
+    let dom = parse(
+        r#"
+a.b = [1, 2]
+inline = { c = 3 }
+
+[[arr]]
+d = 4
+"#,
+    )
+    .into_dom();
+
+    let paths = |array_items| {
+        dom.flat_entries(array_items)
+            .map(|(keys, _)| keys.to_string())
+            .collect::<Vec<_>>()
+    };
+
+    assert_eq!(
+        paths(false),
+        ["a", "a.b", "inline", "inline.c", "arr", "arr.0.d"]
+    );
+    assert_eq!(
+        paths(true),
+        ["a", "a.b", "a.b.0", "a.b.1", "inline", "inline.c", "arr", "arr.0", "arr.0.d"]
+    );
+    assert_eq!(
+        paths(true),
+        dom.flat_iter()
+            .map(|(keys, _)| keys.to_string())
+            .collect::<Vec<_>>()
+    );
+}
+
+#[test]
+fn array_item_at() {
+    let src = r#"arr = [
+  "a",
+  "b" , "c",
+  ["d", ["e"]],
+]
+other = 1
+"#;
+
+    let dom = parse(src).into_dom();
+
+    let item_at = |pattern: &str| {
+        let offset = src.find(pattern).unwrap() as u32;
+        dom.array_item_at(offset.into())
+            .map(|keys| keys.to_string())
+    };
+
+    assert_eq!(item_at(r#""a""#).as_deref(), Some("arr.0"));
+    assert_eq!(item_at(r#""c""#).as_deref(), Some("arr.2"));
+
+    // Separators belong to the previous item.
+    assert_eq!(item_at(r#" , "#).as_deref(), Some("arr.1"));
+    assert_eq!(item_at(r#", "c""#).as_deref(), Some("arr.1"));
+
+    // The innermost array is used.
+    assert_eq!(item_at(r#""d""#).as_deref(), Some("arr.3.0"));
+    assert_eq!(item_at(r#""e""#).as_deref(), Some("arr.3.1.0"));
+    assert_eq!(item_at(r#"]],"#).as_deref(), Some("arr.3.1.0"));
+
+    assert_eq!(item_at("[\n"), None);
+    assert_eq!(item_at("other"), None);
+}
+
+#[test]
+fn parent_index() {
+    use crate::dom::parents::ParentIndex;
+
+    let root = parse(
+        r#"
+[[bin]]
+name = "first"
+
+[[bin]]
+name = "second"
+inline = { list = [1, [2]] }
+"#,
+    )
+    .into_dom();
+
+    let index = ParentIndex::new(&root);
+
+    let keys = Keys::single(Key::new("bin"))
+        .join(1usize)
+        .join(Key::new("inline"))
+        .join(Key::new("list"))
+        .join(1usize)
+        .join(0usize);
+
+    let node = root.path(&keys).unwrap();
+
+    assert_eq!(index.path(&node).unwrap(), keys);
+    assert_eq!(index.path(&root).unwrap(), Keys::empty());
+    assert!(index.parent(&root).is_none());
+
+    let parent = index.parent(&node).unwrap();
+    assert_eq!(parent.as_array().unwrap().items().read().len(), 1);
+
+    let ancestors = index
+        .ancestors(&node)
+        .map(|n| index.path(n).unwrap().to_string())
+        .collect::<Vec<_>>();
+    assert_eq!(
+        ancestors,
+        [
+            "bin.1.inline.list.1",
+            "bin.1.inline.list",
+            "bin.1.inline",
+            "bin.1",
+            "bin",
+            ""
+        ]
+    );
+
+    // Nodes of other trees are not found.
+    let other = parse("a = 1").into_dom();
+    let other_node = other.path(&Keys::single(Key::new("a"))).unwrap();
+    assert!(index.parent(&other_node).is_none());
+    assert!(index.path(&other_node).is_none());
+}
+
+#[test]
+fn dotted_keys_conflict_with_headers() {
+    let src = r#"
+a.b = 1
+
+[a]
+b = 2
+"#;
+
+    let dom = parse(src).into_dom();
+
+    let errors = dom.validate().unwrap_err().collect::<Vec<_>>();
+
+    // The leaf `b` is defined twice, both definitions are reported.
+    let leaf_conflict = errors.iter().find_map(|err| match err {
+        crate::dom::Error::ConflictingKeys { key, other } if key.value() == "b" => {
+            Some((key.text_ranges().next()?, other.text_ranges().next()?))
+        }
+        _ => None,
+    });
+
+    let (key_range, other_range) = leaf_conflict.unwrap();
+    assert_eq!(usize::from(key_range.start()), src.rfind('b').unwrap());
+    assert_eq!(usize::from(other_range.start()), src.find('b').unwrap());
+
+    // Tables defined by dotted keys cannot be reopened by headers either.
+    assert!(parse("a.b = 1\n[a]\nc = 2").into_dom().validate().is_err());
+    assert!(parse("[a]\nb.c = 1\n[a.b]\nd = 2")
+        .into_dom()
+        .validate()
+        .is_err());
+}
+
+#[test]
+fn dotted_keys_under_headers() {
+    for src in [
+        "[a]\nb.c = 1\nb.d = 2",
+        "[a]\nb.c = 1\n\n[a.e]\nf = 1",
+        "[a.b]\nc = 1\n\n[a]\nd.e = 2",
+    ] {
+        assert!(parse(src).into_dom().validate().is_ok(), "{src}");
+    }
+}
+
+/// Inputs that must not cause panics anywhere in the DOM.
+const PATHOLOGICAL_INPUTS: &[&str] = &[
+    "a.b = 1\n[a.b]",
+    "[[a]]\n[a]\n[[a]]",
+    "a = [1]\n[[a]]",
+    "[a]\n[[a.b]]\n[a.b]",
+    "a = {b = 1}\n[a.b]",
+    "a.b.c = 1\na.b = 2",
+    "[[a.b]]\n[a]\nb = 1",
+    "= 1",
+    "[]",
+    "[[]]",
+    "a = ",
+    "a = [,]",
+    "a = {,}",
+    "a = { b = }",
+    "a.= 1",
+    ".a = 1",
+    "a = 1e",
+    "a = 1.",
+    "a = +nan",
+    "a = -inf",
+    "a = 9999999999999999999999",
+    "a = -0x1",
+    "a = 0b",
+    r#"a = "\u""#,
+    r#"a = "\uD800""#,
+    "a = '''",
+    r#"a = """"#,
+    "a = 1979-02-30",
+    "a = 1979-05-27T25:00:00Z",
+    "a = 07:32:60.999999999",
+    r#""\x" = 1"#,
+    "[a\n]\nb = 1",
+    "a = [[[[[[[[[[",
+    "a = {{{{{{{{{{",
+    "\r\r\r",
+    "\u{feff}a = 1",
+];
+
+fn exercise_dom(src: &str) {
+    let dom = parse(src).into_dom();
+    let _ = dom.validate();
+    let _ = dom.flat_iter().count();
+    let _ = dom.to_toml(false, false);
+    let _ = dom.to_toml(true, true);
+    let _ = dom.to_string();
+    let _ = serde_json::to_value(&dom);
+}
+
+#[test]
+fn no_panics_on_pathological_input() {
+    for src in PATHOLOGICAL_INPUTS {
+        if std::panic::catch_unwind(|| exercise_dom(src)).is_err() {
+            panic!("panicked on input {src:?}");
+        }
+    }
+}
+
+#[test]
+fn no_panics_on_generated_input() {
+    let fragments = [
+        "a",
+        "b",
+        ".",
+        "=",
+        "[",
+        "]",
+        "[[",
+        "]]",
+        "{",
+        "}",
+        ",",
+        "\n",
+        " ",
+        "1",
+        "1.5",
+        "1e",
+        "inf",
+        "nan",
+        "-",
+        "+",
+        r#""s""#,
+        "'l'",
+        r#"""""#,
+        "'''",
+        "#c",
+        "true",
+        "1979-05-27",
+        "07:32:00",
+        "1979-05-27T07:32:00Z",
+        "0x1F",
+        "0o7",
+        "0b1",
+        "_",
+        r#""\u""#,
+        "\r",
+        "\t",
+        "a.b",
+        r#""a""#,
+        "1_000",
+        "-0x1",
+        "+nan",
+        "1.",
+        ".5",
+    ];
+
+    let mut rng = super::Rng(0x2545_F491_4F6C_DD1D);
+
+    for _ in 0..5000 {
+        let src: String = (0..rng.next(12))
+            .map(|_| fragments[rng.next(fragments.len())])
+            .collect();
+
+        if std::panic::catch_unwind(|| exercise_dom(&src)).is_err() {
+            panic!("panicked on input {src:?}");
+        }
+    }
+}
+
+#[test]
+fn dom_kinds() {
+    use crate::dom::node::{DateKind, DomKind, IntegerRepr, StrRepr};
+
+    let src = r#"
+a.b = 'lit'
+c = { d = """ml""" }
+e = [1.5, true, 1979-05-27, 07:32:00]
+f = 0o17
+
+[g.h]
+
+[[i]]
+"#;
+
+    let dom = parse(src).into_dom();
+    let entries = dom.as_table().unwrap().entries().read();
+    let kind = |path: &str| {
+        let keys: Vec<&str> = path.split('.').collect();
+        entries.get_path(&keys).unwrap().dom_kind()
+    };
+    let table = |inline, pseudo, array_member| DomKind::Table {
+        inline,
+        pseudo,
+        array_member,
+    };
+
+    assert_eq!(dom.dom_kind(), DomKind::Root);
+    assert_eq!(kind("a"), table(false, true, false));
+    assert_eq!(kind("a.b"), DomKind::String(StrRepr::Literal));
+    assert_eq!(kind("c"), table(true, false, false));
+    assert_eq!(kind("c.d"), DomKind::String(StrRepr::MultiLine));
+    assert_eq!(kind("e"), DomKind::Array { of_tables: false });
+    assert_eq!(kind("e.0"), DomKind::Float);
+    assert_eq!(kind("e.1"), DomKind::Bool);
+    assert_eq!(kind("e.2"), DomKind::Date(DateKind::LocalDate));
+    assert_eq!(kind("e.3"), DomKind::Date(DateKind::LocalTime));
+    assert_eq!(kind("f"), DomKind::Integer(IntegerRepr::Oct));
+    assert_eq!(kind("g"), table(false, true, false));
+    assert_eq!(kind("g.h"), table(false, false, false));
+    assert_eq!(kind("i"), DomKind::Array { of_tables: true });
+    assert_eq!(kind("i.0"), table(false, false, true));
+    assert_eq!(dom.get("missing").dom_kind(), DomKind::Invalid);
+}
+
+fn conflict_count(node: &crate::dom::Node) -> usize {
+    match node.validate() {
+        Ok(_) => 0,
+        Err(errors) => errors
+            .filter(|err| matches!(err, crate::dom::Error::ConflictingKeys { .. }))
+            .count(),
+    }
+}
+
+#[test]
+fn duplicate_keys_after_unescaping() {
+    let dom = parse("a = 1\n\"a\" = 2\n'a' = 3\n\"\\u0061\" = 4").into_dom();
+
+    let entries = dom.as_table().unwrap().entries().read();
+    assert_eq!(entries.len(), 4);
+    assert_eq!(conflict_count(&dom), 3);
+}
+
+#[test]
+fn duplicate_keys_in_array_of_tables() {
+    let dom = parse("[[a]]\nb = 1\n[[a]]\nb = 2\n[[a]]\nb = 3\nb = 4").into_dom();
+
+    let items = dom.get("a").as_array().unwrap().items().read().clone();
+    let lens: Vec<usize> = items
+        .iter()
+        .map(|item| item.as_table().unwrap().entries().read().len())
+        .collect();
+
+    assert_eq!(lens, [1, 1, 2]);
+    assert_eq!(conflict_count(&dom), 1);
+}
+
+#[test]
+fn duplicate_keys_in_dotted_keys() {
+    let dom = parse("a.b.c = 1\na.b.d = 2\na.e = 3").into_dom();
+
+    assert_eq!(dom.get("a").as_table().unwrap().entries().read().len(), 2);
+    assert_eq!(
+        dom.get("a")
+            .get("b")
+            .as_table()
+            .unwrap()
+            .entries()
+            .read()
+            .len(),
+        2
+    );
+    assert_eq!(conflict_count(&dom), 0);
+
+    let dom = parse("a.b.c = 1\na.b.c = 2\na.b = 3").into_dom();
+
+    // Both entries are kept, lookups return the last one.
+    let a = dom.get("a").as_table().unwrap().entries().read().clone();
+    assert_eq!(a.len(), 2);
+    assert_eq!(
+        a.iter()
+            .next()
+            .unwrap()
+            .1
+            .as_table()
+            .unwrap()
+            .entries()
+            .read()
+            .len(),
+        2
+    );
+    assert!(a["b"].as_integer().is_some());
+    assert_eq!(conflict_count(&dom), 2);
+}
+
+#[test]
+fn entries_iteration() {
+    use crate::dom::Entries;
+
+    let dom = parse("a = 1\nb = 2\nc = 3").into_dom();
+    let entries = Entries::clone(&dom.as_table().unwrap().entries().read());
+
+    let keys: Vec<&str> = (&entries).into_iter().map(|(k, _)| k.value()).collect();
+    assert_eq!(keys, ["a", "b", "c"]);
+    assert_eq!(entries.as_slice().len(), 3);
+    assert_eq!(entries.first().unwrap().0.value(), "a");
+    assert_eq!(entries.last().unwrap().0.value(), "c");
+
+    let mut combined: Entries = entries.clone().into_iter().take(1).collect();
+    combined.extend(entries.into_iter().skip(2));
+    let other = parse("a = true").into_dom();
+    combined.extend(Entries::clone(&other.as_table().unwrap().entries().read()));
+
+    let keys: Vec<&str> = combined.iter().map(|(k, _)| k.value()).collect();
+    assert_eq!(keys, ["a", "c", "a"]);
+    assert!(combined["a"].as_bool().is_some());
+}
+
+#[test]
+fn table_ranges() {
+    let src = "[foo]\na = 1\nb = 2\n\n# comment\n[foobar]\n\n[[arr]] # comment\nc = { d = 3 }\n";
+    let dom = parse(src).into_dom();
+
+    let text =
+        |range: Option<rowan::TextRange>| range.map(|r| &src[std::ops::Range::<usize>::from(r)]);
+
+    let foo = dom.get("foo");
+    let foo = foo.as_table().unwrap();
+    assert_eq!(text(foo.header_range()), Some("[foo]"));
+    assert_eq!(text(foo.entries_range()), Some("a = 1\nb = 2"));
+    assert_eq!(
+        text(foo.full_range()),
+        Some("[foo]\na = 1\nb = 2\n\n# comment\n")
+    );
+
+    let foobar = dom.get("foobar");
+    let foobar = foobar.as_table().unwrap();
+    assert_eq!(text(foobar.header_range()), Some("[foobar]"));
+    assert_eq!(foobar.entries_range(), None);
+    assert_eq!(text(foobar.full_range()), Some("[foobar]\n\n"));
+
+    let arr = dom.get("arr").get(0);
+    let arr = arr.as_table().unwrap();
+    assert_eq!(text(arr.header_range()), Some("[[arr]]"));
+    assert_eq!(
+        text(arr.full_range()),
+        Some("[[arr]] # comment\nc = { d = 3 }\n")
+    );
+
+    let headers = dom.get("arr").as_array().unwrap().table_headers();
+    assert_eq!(
+        headers
+            .into_iter()
+            .map(|r| text(Some(r)))
+            .collect::<Vec<_>>(),
+        [Some("[[arr]]")]
+    );
+
+    let inline = dom.get("arr").get(0).get("c");
+    let inline = inline.as_table().unwrap();
+    assert_eq!(inline.header_range(), None);
+    assert_eq!(text(inline.entries_range()), Some("d = 3"));
+    assert_eq!(text(inline.full_range()), Some("{ d = 3 }"));
+}
+
+#[test]
+fn table_array_header_ranges() {
+    let src = "[[arr]]\na = 1\n# comment\ninvalid\n\n[[arr]] # comment\n[[arr]\nb = 2\n";
+    let dom = parse(src).into_dom();
+
+    let text =
+        |range: Option<rowan::TextRange>| range.map(|r| &src[std::ops::Range::<usize>::from(r)]);
+
+    let arr = dom.get("arr");
+    let tables = arr.as_array().unwrap().items().read();
+    let ranges = tables
+        .iter()
+        .map(|t| {
+            let t = t.as_table().unwrap();
+            (
+                text(t.header_range()),
+                text(t.entries_range()),
+                text(t.full_range()),
+            )
+        })
+        .collect::<Vec<_>>();
+
+    assert_eq!(
+        ranges,
+        [
+            (
+                Some("[[arr]]"),
+                Some("a = 1\n# comment\ninvalid"),
+                Some("[[arr]]\na = 1\n# comment\ninvalid\n\n")
+            ),
+            (Some("[[arr]]"), None, Some("[[arr]] # comment\n")),
+            (Some("[[arr]"), Some("b = 2"), Some("[[arr]\nb = 2\n")),
+        ]
+    );
+}
+
+#[test]
+fn path_display_and_parse() {
+    use crate::dom::{Path, Segment};
+
+    let path: Path = [
+        Segment::from("package"),
+        Segment::from("a b"),
+        Segment::from(2),
+        Segment::from("3"),
+        Segment::from("it's"),
+        Segment::from(""),
+    ]
+    .into_iter()
+    .collect();
+
+    let text = path.to_string();
+    assert_eq!(text, r#"package.'a b'[2].3."it's"."""#);
+    assert_eq!(text.parse::<Path>().unwrap(), path);
+
+    let path: Path = r#"a."b\"c"[0][1].'d.e'"#.parse().unwrap();
+    assert_eq!(
+        path.segments(),
+        [
+            Segment::from("a"),
+            Segment::from("b\"c"),
+            Segment::from(0),
+            Segment::from(1),
+            Segment::from("d.e"),
+        ]
+    );
+
+    assert!("".parse::<Path>().unwrap().is_empty());
+
+    for invalid in ["a.", ".a", "a..b", "a[x]", "a[1", "a b", r#""a"#, "a[0]b"] {
+        assert!(invalid.parse::<Path>().is_err(), "{invalid}");
+    }
+}
+
+#[test]
+fn path_prefixes() {
+    use crate::dom::Path;
+
+    let parent: Path = "a.b".parse().unwrap();
+    let child: Path = "a.b[0].c".parse().unwrap();
+    let other: Path = "a.bc".parse().unwrap();
+
+    assert!(parent.is_part_of(&child));
+    assert!(parent.is_part_of(&parent));
+    assert!(!child.is_part_of(&parent));
+    assert!(!parent.is_part_of(&other));
+    assert!(Path::new().is_part_of(&child));
+
+    assert_eq!(child.common_prefix_count(&other), 1);
+    assert_eq!(
+        child.strip_prefix(&parent).unwrap().to_string(),
+        "[0].c".to_string()
+    );
+    assert_eq!(child.parent().unwrap().to_string(), "a.b[0]");
+    assert_eq!(parent.join(1).to_string(), "a.b[1]");
+}
+
+#[test]
+fn keys_to_path() {
+    use crate::dom::Path;
+
+    let dom = parse(
+        r#"
+"quoted a" = 1
+"#,
+    )
+    .into_dom();
+
+    let (key, _) = dom
+        .as_table()
+        .unwrap()
+        .entries()
+        .read()
+        .first()
+        .cloned()
+        .unwrap();
+    assert_eq!(key.to_path().to_string(), "'quoted a'");
+
+    let keys = key.join(0usize).join(Key::new("b"));
+    assert_eq!(keys.to_path(), "'quoted a'[0].b".parse::<Path>().unwrap());
+    assert_eq!(Keys::empty().to_path(), Path::new());
+}
+
+/// Compares the operations on keys against the same operations
+/// on a plain vector of the keys.
+#[test]
+fn keys_operations() {
+    use crate::dom::KeyOrIndex;
+    use itertools::Itertools;
+    use std::{
+        collections::hash_map::DefaultHasher,
+        hash::{Hash, Hasher},
+    };
+
+    fn hash(value: &(impl Hash + ?Sized)) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        value.hash(&mut hasher);
+        hasher.finish()
+    }
+
+    let mut rng = super::Rng(0x2545_F491_4F6C_DD1D);
+
+    let key = |n: usize| -> KeyOrIndex {
+        match n % 4 {
+            0 => (n % 3).into(),
+            _ => Key::new(["a", "b", "c d"][n % 3]).into(),
+        }
+    };
+
+    for _ in 0..500 {
+        let mut keys = Keys::empty();
+        let mut expected: Vec<KeyOrIndex> = Vec::new();
+        let mut all = Vec::new();
+
+        for _ in 0..rng.next(16) {
+            match rng.next(5) {
+                0 | 1 => {
+                    let k = key(rng.next(12));
+                    keys = keys.join(k.clone());
+                    expected.push(k);
+                }
+                2 => {
+                    let ks: Vec<_> = (0..rng.next(3)).map(|_| key(rng.next(12))).collect();
+                    keys = keys.extend(ks.clone());
+                    expected.extend(ks);
+                }
+                3 => {
+                    let n = rng.next(3);
+                    keys = keys.skip_left(n);
+                    expected.drain(..n.min(expected.len()));
+                }
+                _ => {
+                    let n = rng.next(3);
+                    keys = keys.skip_right(n);
+                    expected.truncate(expected.len().saturating_sub(n));
+                }
+            }
+
+            all.push((keys.clone(), expected.clone()));
+        }
+
+        for (keys, expected) in &all {
+            assert_eq!(keys.len(), expected.len());
+            assert!(keys.iter().eq(expected.iter()));
+            assert!(keys.iter().rev().eq(expected.iter().rev()));
+            assert_eq!(keys.dotted(), expected.iter().join("."));
+            assert_eq!(hash(keys), hash(expected.as_slice()));
+            assert_eq!(*keys, Keys::new(expected.iter().cloned()));
+        }
+
+        for (a, a_expected) in &all {
+            for (b, b_expected) in &all {
+                assert_eq!(a == b, a_expected == b_expected);
+                assert_eq!(
+                    a.common_prefix_count(b),
+                    a_expected
+                        .iter()
+                        .zip(b_expected)
+                        .take_while(|(a, b)| a == b)
+                        .count()
+                );
+                assert_eq!(a.contains(b), a_expected.starts_with(b_expected));
+            }
+        }
+    }
+}
+
+#[test]
+fn find_all_patterns() {
+    let dom = parse(
+        r#"
+name = "root"
+
+[dependencies]
+serde = { version = "1", features = ["derive"] }
+serde_json = "1"
+"*" = { version = "2" }
+
+[dependencies.toml]
+version = "0.5"
+
+[[bin]]
+name = "a"
+path = "src/a.rs"
+
+[[bin]]
+name = "b"
+"#,
+    )
+    .into_dom();
+
+    let paths = |pattern: &str| {
+        dom.find_all(pattern)
+            .unwrap()
+            .into_iter()
+            .map(|(path, _)| path.to_string())
+            .collect::<Vec<_>>()
+    };
+
+    // Anchored at the root.
+    assert_eq!(paths("name"), ["name"]);
+    assert_eq!(paths(""), [""]);
+    assert_eq!(paths("*"), ["name", "dependencies", "bin"]);
+
+    // Wildcards in the middle of the pattern.
+    assert_eq!(
+        paths("dependencies.*.version"),
+        [
+            "dependencies.serde.version",
+            "dependencies.'*'.version",
+            "dependencies.toml.version"
+        ]
+    );
+    assert_eq!(
+        paths("dependencies.'serde_json'"),
+        ["dependencies.serde_json"]
+    );
+    assert_eq!(
+        paths("dependencies.'*'.version"),
+        ["dependencies.'*'.version"]
+    );
+
+    // Array of tables items.
+    assert_eq!(paths("bin.*.name"), ["bin[0].name", "bin[1].name"]);
+    assert_eq!(paths("bin[1].name"), ["bin[1].name"]);
+
+    // Any number of segments.
+    assert_eq!(paths("**.name"), ["name", "bin[0].name", "bin[1].name"]);
+    assert_eq!(
+        paths("dependencies.**[0]"),
+        ["dependencies.serde.features[0]"]
+    );
+
+    // Nothing matches.
+    assert!(paths("package.*").is_empty());
+    assert!(paths("bin.*.*.name").is_empty());
+    assert!(paths("name.*").is_empty());
+
+    assert!(dom.find_all("dependencies.*a").is_err());
+    assert!("a.*".parse::<crate::dom::Path>().is_err());
+}
+
+/// The `id` values of the items in the array of tables at the given path.
+fn array_of_tables_ids(root: &crate::dom::Node, path: &str) -> Vec<String> {
+    let keys = path.split('.').collect::<Vec<_>>();
+    let array = root
+        .as_table()
+        .unwrap()
+        .entries()
+        .read()
+        .get_path(&keys)
+        .unwrap();
+
+    array
+        .as_array()
+        .unwrap()
+        .items()
+        .read()
+        .iter()
+        .map(|item| item.get("id").as_integer().unwrap().value().to_string())
+        .collect()
+}
+
+#[test]
+fn array_of_tables_order_interleaved() {
+    let src = r#"
+[[a]]
+id = 1
+
+[b]
+x = 1
+
+[[c]]
+id = 1
+
+[[a]]
+id = 2
+
+[b.d]
+y = 2
+
+[[c]]
+id = 2
+
+[[a]]
+id = 3
+
+[e]
+[[c]]
+id = 3
+"#;
+
+    let root = parse(src).into_dom();
+    assert!(root.validate().is_ok());
+
+    assert_eq!(array_of_tables_ids(&root, "a"), ["1", "2", "3"]);
+    assert_eq!(array_of_tables_ids(&root, "c"), ["1", "2", "3"]);
+
+    let json = serde_json::to_value(&root).unwrap();
+    assert_eq!(json["a"][0]["id"], 1);
+    assert_eq!(json["a"][2]["id"], 3);
+}
+
+#[test]
+fn array_of_tables_order_nested() {
+    let src = r#"
+[[a]]
+id = 1
+
+[[a.b]]
+id = 1
+
+[[a.b.c]]
+id = 1
+
+[other]
+
+[[a.b.c]]
+id = 2
+
+[[a.b]]
+id = 2
+
+[[x.y]]
+id = 1
+
+[[a]]
+id = 2
+
+[[a.b]]
+id = 3
+
+[[x.y]]
+id = 2
+
+[[a.b.c]]
+id = 3
+
+[[a.b]]
+id = 4
+"#;
+
+    let root = parse(src).into_dom();
+    assert!(root.validate().is_ok());
+
+    assert_eq!(array_of_tables_ids(&root, "a"), ["1", "2"]);
+    assert_eq!(array_of_tables_ids(&root, "a.0.b"), ["1", "2"]);
+    assert_eq!(array_of_tables_ids(&root, "a.0.b.0.c"), ["1", "2"]);
+    assert_eq!(array_of_tables_ids(&root, "a.1.b"), ["3", "4"]);
+    assert_eq!(array_of_tables_ids(&root, "a.1.b.0.c"), ["3"]);
+    assert_eq!(array_of_tables_ids(&root, "x.y"), ["1", "2"]);
+}
+
+fn primitive<T: crate::dom::FromSyntax>(kind: crate::syntax::SyntaxKind, text: &str) -> T {
+    use crate::syntax::{SyntaxKind::ROOT, SyntaxNode};
+
+    let root = SyntaxNode::new_root(rowan::GreenNode::new(
+        ROOT.into(),
+        [rowan::GreenToken::new(kind.into(), text).into()],
+    ));
+    T::from_syntax(root.first_token().unwrap().into())
+}
+
+fn primitive_values(src: &str) -> Vec<String> {
+    use crate::dom::Node;
+
+    let dom = parse(src).into_dom();
+    assert!(dom.validate().is_ok(), "{src}");
+    dom.as_table()
+        .unwrap()
+        .entries()
+        .read()
+        .iter()
+        .map(|(_, node)| match node {
+            Node::Bool(v) => v.to_string(),
+            Node::Integer(v) => v.to_string(),
+            Node::Float(v) => v.to_string(),
+            Node::Date(v) => v.to_string(),
+            _ => panic!("expected a primitive value"),
+        })
+        .collect()
+}
+
+#[test]
+fn bool_nodes() {
+    use crate::{
+        dom::node::{Bool, DomNode},
+        syntax::SyntaxKind::*,
+    };
+
+    assert_eq!(primitive_values("a = true\nb = false"), ["true", "false"]);
+
+    let node: Bool = primitive(BOOL, "true");
+    assert!(node.value());
+    assert_eq!(node.to_string(), "true");
+    assert!(node.validate_node().is_ok());
+
+    let node: Bool = primitive(BOOL, "yes");
+    assert!(!node.value());
+    assert!(node.validate_node().is_err());
+
+    let node: Bool = primitive(INTEGER, "1");
+    assert!(!node.value());
+    assert_eq!(node.errors().read().len(), 1);
+}
+
+#[test]
+fn integer_nodes() {
+    use crate::{
+        dom::node::{DomNode, Integer, IntegerValue},
+        syntax::SyntaxKind::*,
+    };
+
+    assert_eq!(
+        primitive_values("a = 1_000\nb = -42\nc = 0xff\nd = 0o17\ne = 0b101\nf = +3"),
+        ["1000", "-42", "255", "15", "5", "3"]
+    );
+
+    let node: Integer = primitive(INTEGER_HEX, "0xdead_beef");
+    assert_eq!(node.value(), IntegerValue::Positive(0xdead_beef));
+    assert_eq!(node.to_string(), "3735928559");
+    assert!(node.validate_node().is_ok());
+
+    let node: Integer = primitive(INTEGER, "-9223372036854775808");
+    assert_eq!(node.value(), IntegerValue::Negative(i64::MIN));
+
+    for src in ["a = 18446744073709551616", "a = -9223372036854775809"] {
+        let dom = parse(src).into_dom();
+        assert!(dom.validate().is_err(), "{src}");
+    }
+
+    let node: Integer = primitive(INTEGER_BIN, "0b102");
+    assert_eq!(node.value(), IntegerValue::Positive(0));
+    assert!(node.validate_node().is_err());
+
+    let node: Integer = primitive(FLOAT, "1.5");
+    assert_eq!(node.value(), IntegerValue::Positive(0));
+    assert_eq!(node.errors().read().len(), 1);
+}
+
+#[test]
+fn float_nodes() {
+    use crate::{
+        dom::node::{DomNode, Float},
+        syntax::SyntaxKind::*,
+    };
+
+    assert_eq!(
+        primitive_values(
+            "a = 1.0\nb = -0.5\nc = 1e3\nd = 6.626e-34\ne = inf\nf = -inf\ng = nan\nh = 1_000.5"
+        ),
+        [
+            "1.0",
+            "-0.5",
+            "1000.0",
+            "6.626e-34",
+            "inf",
+            "-inf",
+            "nan",
+            "1000.5"
+        ]
+    );
+
+    let node: Float = primitive(FLOAT, "-nan");
+    assert!(node.value().is_nan());
+    assert_eq!(node.to_string(), "nan");
+    assert!(node.validate_node().is_ok());
+
+    let node: Float = primitive(FLOAT, "1.2.3");
+    assert_eq!(node.value(), 0.0);
+    assert!(node.validate_node().is_err());
+
+    let node: Float = primitive(BOOL, "true");
+    assert_eq!(node.value(), 0.0);
+    assert_eq!(node.errors().read().len(), 1);
+}
+
+#[test]
+fn date_time_nodes() {
+    use crate::{
+        dom::node::{DateKind, DateTime, DomNode},
+        syntax::SyntaxKind::*,
+    };
+
+    assert_eq!(
+        primitive_values(
+            "a = 1979-05-27T07:32:00Z\nb = 1979-05-27 07:32:00.5\nc = 1979-05-27\nd = 07:32:00"
+        ),
+        [
+            "1979-05-27T07:32:00Z",
+            "1979-05-27T07:32:00.5",
+            "1979-05-27",
+            "07:32:00"
+        ]
+    );
+
+    let node: DateTime = primitive(DATE_TIME_OFFSET, "1979-05-27t07:32:00+01:00");
+    assert_eq!(node.kind(), DateKind::OffsetDateTime);
+    assert_eq!(node.to_string(), "1979-05-27T07:32:00+01:00");
+    assert!(node.validate_node().is_ok());
+
+    let node: DateTime = primitive(DATE, "1979-02-30");
+    assert_eq!(node.kind(), DateKind::LocalDate);
+    assert!(node.validate_node().is_err());
+
+    let node: DateTime = primitive(TIME, "25:00:00");
+    assert!(node.validate_node().is_err());
+
+    let node: DateTime = primitive(STRING, r#""1979-05-27""#);
+    assert_eq!(node.errors().read().len(), 1);
+}
+
+/// The errors of the document with the first ranges of the keys involved.
+pub(super) fn dom_errors(src: &str) -> Vec<String> {
+    use crate::dom::{node::Key, Error};
+
+    let range = |key: &Key| {
+        key.text_ranges()
+            .next()
+            .map(|r| format!("{r:?}"))
+            .unwrap_or_default()
+    };
+
+    match parse(src).into_dom().validate() {
+        Ok(_) => Vec::new(),
+        Err(errors) => errors
+            .map(|error| match &error {
+                Error::ConflictingKeys { key, other } => {
+                    format!("{error}: {} {}", range(key), range(other))
+                }
+                Error::ExpectedTable {
+                    not_table,
+                    required_by,
+                } => format!("{error}: {} {}", range(not_table), range(required_by)),
+                Error::ExpectedArrayOfTables {
+                    not_array_of_tables,
+                    required_by,
+                } => format!(
+                    "{error}: {} {}",
+                    range(not_array_of_tables),
+                    range(required_by)
+                ),
+                Error::UnexpectedSyntax { syntax } => {
+                    format!("unexpected syntax: {:?}", syntax.text_range())
+                }
+                _ => error.to_string(),
+            })
+            .collect(),
+    }
+}
+
+#[test]
+fn header_errors_order() {
+    let src = r#"
+a = 1
+b.c = 2
+
+[a]
+[b]
+[b.c]
+[[b]]
+
+[d]
+x = 1
+[d]
+x = 2
+
+[[e]]
+[e.f]
+[e]
+[[e.f]]
+
+g = [1]
+[[g]]
+[g.h]
+"#;
+
+    // The errors are reported in the order of the tables they are
+    // collected in, and not in the order of their sources.
+    assert_eq!(
+        dom_errors(src),
+        [
+            "conflicting keys: 17..18 1..2",
+            "conflicting keys: 71..72 60..61",
+            "conflicting keys: 21..22 7..8",
+            "conflicting keys: 27..28 9..10",
+            "expected array of tables: 7..8 32..33",
+            "conflicting keys: 48..49 38..39",
+            "conflicting keys: 51..52 41..42",
+        ]
+    );
+}
+
+#[test]
+fn array_of_tables_indices_with_subtables() {
+    let src = r#"
+[[a]]
+id = 1
+[a.b]
+x = 1
+[[a.c]]
+id = 1
+
+[[a]]
+id = 2
+[a.b]
+x = 2
+[[a.c]]
+id = 2
+[[a.c]]
+id = 3
+
+[[a]]
+id = 3
+[[a.c]]
+id = 4
+"#;
+
+    let root = parse(src).into_dom();
+    assert!(root.validate().is_ok());
+
+    assert_eq!(array_of_tables_ids(&root, "a"), ["1", "2", "3"]);
+    assert_eq!(array_of_tables_ids(&root, "a.0.c"), ["1"]);
+    assert_eq!(array_of_tables_ids(&root, "a.1.c"), ["2", "3"]);
+    assert_eq!(array_of_tables_ids(&root, "a.2.c"), ["4"]);
+    assert_eq!(root.get("a").get(1).get("b").get("x").to_string(), "2");
+}
+
+#[test]
+fn many_deep_dotted_keys() {
+    let keys = (0..19).map(|i| format!("k{i}")).collect::<Vec<_>>();
+    let prefix = keys.join(".");
+
+    let mut src = String::new();
+    for i in 0..5000 {
+        src += &format!("{prefix}.e{i} = {i}\n");
+    }
+    src += &format!("{prefix}.e42 = 0\n");
+
+    let root = parse(&src).into_dom();
+    let errors = root.validate().unwrap_err().collect::<Vec<_>>();
+    assert_eq!(errors.len(), 1);
+    assert!(matches!(
+        &errors[0],
+        crate::dom::Error::ConflictingKeys { key, .. } if key.value() == "e42"
+    ));
+
+    let path = keys.iter().map(String::as_str).collect::<Vec<_>>();
+    let table = root
+        .as_table()
+        .unwrap()
+        .entries()
+        .read()
+        .get_path(&path)
+        .unwrap();
+    let entries = table.as_table().unwrap().entries().read();
+    assert_eq!(entries.len(), 5001);
+    assert_eq!(entries.first().unwrap().0.value(), "e0");
+    assert_eq!(
+        entries
+            .get("e4999")
+            .unwrap()
+            .as_integer()
+            .unwrap()
+            .value()
+            .as_positive(),
+        Some(4999)
+    );
+}
+
+#[test]
+fn very_deep_dotted_keys() {
+    let keys = (0..2000).map(|i| format!("k{i}")).collect::<Vec<_>>();
+    let src = format!("{0}.a = 1\n{0}.b = 2\n", keys.join("."));
+
+    let root = parse(&src).into_dom();
+    let entries = root.as_table().unwrap().entries().read();
+
+    let mut path = keys.iter().map(String::as_str).collect::<Vec<_>>();
+    path.push("b");
+    assert!(entries.get_path(&path).unwrap().as_integer().is_some());
+}
+
+#[test]
+fn comment_value_ranges() {
+    let src = "# Title\n#:schema   ./schema.json  trailing\n#:schema\n\na = 1 #:schema after\n";
+    let root = parse(src).into_dom();
+
+    let header = root.header_comments().collect::<Vec<_>>();
+    assert_eq!(header.len(), 3);
+
+    let ranges = header
+        .iter()
+        .map(|c| c.value_text_range().map(|r| &src[r]))
+        .collect::<Vec<_>>();
+    assert_eq!(ranges, [Some(" Title"), Some("./schema.json"), None]);
+    assert_eq!(header[1].directive(), Some("schema"));
+    assert_eq!(header[1].value(), "./schema.json");
+
+    assert_eq!(root.comments().count(), 4);
+}
+
+#[test]
+fn to_json() {
+    let src = r#"
+a = "a\tb"
+b = [1, -2, 3.5, true]
+c = 1979-05-27T07:32:00Z
+
+[d]
+e = { f = 'g' }
+"#;
+
+    let root = parse(src).into_dom();
+
+    assert_eq!(
+        root.to_json().unwrap(),
+        serde_json::json!({
+            "a": "a\tb",
+            "b": [1, -2, 3.5, true],
+            "c": "1979-05-27T07:32:00Z",
+            "d": { "e": { "f": "g" } },
+        })
+    );
+}
+
+#[test]
+fn to_json_invalid_escape() {
+    // The string would be serialized as an empty string.
+    let src = "a = 1\nb = [\"x\", \"bad \\q escape\"]\n";
+    let root = parse(src).into_dom();
+
+    let error = serde_json::Value::try_from(&root).unwrap_err();
+    assert_eq!(error.reason, ConversionErrorReason::InvalidEscapeSequence);
+    assert_eq!(&src[error.range.unwrap()], "\\q");
+}
+
+#[test]
+fn to_json_errors() {
+    let src = "a = nan\n";
+    let error = parse(src).into_dom().to_json().unwrap_err();
+    assert_eq!(error.reason, ConversionErrorReason::NonFiniteFloat);
+    assert_eq!(&src[error.range.unwrap()], "nan");
+
+    let src = "a = \n";
+    let error = parse(src).into_dom().to_json().unwrap_err();
+    assert_eq!(error.reason, ConversionErrorReason::InvalidNode);
+}
+
+#[test]
+fn to_toml_document_round_trip() {
+    for (path, src) in super::corpus() {
+        let parse = parse(&src);
+        if !parse.errors.is_empty() {
+            continue;
+        }
+
+        let root = parse.into_dom();
+        if root.validate().is_err() {
+            continue;
+        }
+
+        // Documents with floats that JSON can't represent are not compared.
+        let Ok(json) = root.to_json() else {
+            continue;
+        };
+
+        let mut roots = vec![root];
+        #[cfg(feature = "serde")]
+        roots.push(serde_json::from_value(json.clone()).unwrap());
+
+        for root in roots {
+            let toml = root.to_toml_document(Default::default()).unwrap();
+            let parse = crate::parser::parse(&toml);
+            assert!(
+                parse.errors.is_empty(),
+                "{path}: {:?}\n{toml}",
+                parse.errors
+            );
+            assert_eq!(parse.into_dom().to_json().unwrap(), json, "{path}\n{toml}");
+        }
+    }
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn to_toml_document_from_json() {
+    // The keys are sorted, so the order is the same
+    // with or without the `preserve_order` feature of serde_json.
+    let json = serde_json::json!({
+        "name": "taplo",
+        "products": [{ "name": "Hammer" }, { "name": "Nail", "tags": [] }],
+        "tables": { "a": [{ "b": 1 }] },
+    });
+
+    let root: Node = serde_json::from_value(json).unwrap();
+
+    // Arrays of JSON objects are arrays of tables.
+    let expected = r#"name = "taplo"
+[[products]]
+name = "Hammer"
+[[products]]
+name = "Nail"
+tags = []
+[tables]
+[[tables.a]]
+b = 1
+"#;
+
+    assert_eq!(root.to_toml_document(Default::default()).unwrap(), expected);
+}
+
+#[test]
+fn to_toml_document_errors() {
+    let root = parse("a = [1]\n").into_dom();
+    let array = root.get("a");
+
+    let error = array.to_toml_document(Default::default()).unwrap_err();
+    assert_eq!(error.reason, ConversionErrorReason::ExpectedTable);
+
+    let src = "a = \"\\q\"\n";
+    let error = parse(src)
+        .into_dom()
+        .to_toml_document(Default::default())
+        .unwrap_err();
+    assert_eq!(error.reason, ConversionErrorReason::InvalidEscapeSequence);
+    assert_eq!(&src[error.range.unwrap()], "\\q");
+}
+
+#[test]
+fn to_json_typed_values() {
+    let src = r#"
+hex = 0xFF
+oct = 0o17
+offset = 1979-05-27T00:32:00.5-07:00
+local = 1979-05-27T07:32:00
+date = 1979-05-27
+time = 07:32:00
+"#;
+
+    let root = parse(src).into_dom();
+
+    assert_eq!(
+        root.get("hex").as_integer().unwrap().repr(),
+        IntegerRepr::Hex
+    );
+    assert_eq!(
+        root.get("local").as_date().unwrap().kind(),
+        DateKind::LocalDateTime
+    );
+    assert_eq!(
+        root.get("time").as_date().unwrap().kind(),
+        DateKind::LocalTime
+    );
+
+    // Integers are numbers and dates are strings in all cases.
+    assert_eq!(
+        root.to_json().unwrap(),
+        serde_json::json!({
+            "hex": 255,
+            "oct": 15,
+            "offset": "1979-05-27T00:32:00.5-07:00",
+            "local": "1979-05-27T07:32:00",
+            "date": "1979-05-27",
+            "time": "07:32:00",
+        })
+    );
+}
+
+#[test]
+fn to_json_non_finite_floats() {
+    let src = "a = [inf, -inf, nan, 1.5]\n";
+    let root = parse(src).into_dom();
+
+    let error = root.to_json().unwrap_err();
+    assert_eq!(error.reason, ConversionErrorReason::NonFiniteFloat);
+    assert_eq!(&src[error.range.unwrap()], "inf");
+
+    let json = root
+        .to_json_with_options(JsonOptions {
+            non_finite_floats_as_null: true,
+            ..Default::default()
+        })
+        .unwrap();
+    assert_eq!(json, serde_json::json!({ "a": [null, null, null, 1.5] }));
+
+    // The specials are kept when written back as TOML.
+    assert_eq!(
+        root.to_toml_document(Default::default()).unwrap(),
+        "a = [inf, -inf, nan, 1.5]\n"
+    );
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn json_to_toml() {
+    let json = r#"{
+        "name": "taplo",
+        "version": 1,
+        "authors": ["a", "b"],
+        "package": { "z": true, "a": 1.5 },
+        "bin": [{ "name": "taplo" }]
+    }"#;
+
+    // The keys keep the order of the JSON object.
+    let expected = r#"name = "taplo"
+version = 1
+authors = ["a", "b"]
+[package]
+z = true
+a = 1.5
+[[bin]]
+name = "taplo"
+"#;
+
+    let toml = crate::dom::json_to_toml(json, JsonOptions::default(), Default::default()).unwrap();
+    assert_eq!(toml, expected);
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn json_to_toml_nulls() {
+    let json = r#"{ "a": { "b": [1, null, 2] }, "c": null }"#;
+
+    let error = Node::from_json_str(json, JsonOptions::default()).unwrap_err();
+    assert!(error.to_string().starts_with("`a.b[1]` is null"), "{error}");
+
+    let toml = crate::dom::json_to_toml(
+        json,
+        JsonOptions {
+            skip_nulls: true,
+            ..Default::default()
+        },
+        Default::default(),
+    )
+    .unwrap();
+    assert_eq!(toml, "[a]\nb = [1, 2]\n");
+
+    // There is nothing to skip at the root.
+    assert!(Node::from_json_str(
+        "null",
+        JsonOptions {
+            skip_nulls: true,
+            ..Default::default()
+        }
+    )
+    .is_err());
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn json_to_toml_errors() {
+    let error = Node::from_json_str(
+        r#"{ "a": [{ "big": 9223372036854775808 }] }"#,
+        JsonOptions::default(),
+    )
+    .unwrap_err();
+    assert!(
+        error
+            .to_string()
+            .starts_with("the integer 9223372036854775808 at `a[0].big` is too large"),
+        "{error}"
+    );
+
+    let error = Node::from_json_str(r#"{ "a": 1, "a": 2 }"#, JsonOptions::default()).unwrap_err();
+    assert!(
+        error
+            .to_string()
+            .starts_with("`a` is defined more than once"),
+        "{error}"
+    );
+
+    let error =
+        crate::dom::json_to_toml("[1, 2]", JsonOptions::default(), Default::default()).unwrap_err();
+    assert!(
+        error
+            .to_string()
+            .starts_with("only tables can be TOML documents"),
+        "{error}"
+    );
+}
+
+#[test]
+fn to_json_tagged() {
+    let src = r#"
+dec = 10
+hex = 0xFF
+oct = 0o17
+bin = 0b11
+float = 1.5
+specials = [inf, -inf, nan]
+offset = 1979-05-27T07:32:00Z
+local = 1979-05-27T07:32:00
+date = 1979-05-27
+time = 07:32:00
+"#;
+
+    let json = parse(src)
+        .into_dom()
+        .to_json_with_options(JsonOptions {
+            tagged: true,
+            ..Default::default()
+        })
+        .unwrap();
+
+    assert_eq!(
+        json,
+        serde_json::json!({
+            "dec": 10,
+            "hex": { "$type": "integer-hex", "value": 255 },
+            "oct": { "$type": "integer-oct", "value": 15 },
+            "bin": { "$type": "integer-bin", "value": 3 },
+            "float": 1.5,
+            "specials": [
+                { "$type": "float", "value": "inf" },
+                { "$type": "float", "value": "-inf" },
+                { "$type": "float", "value": "nan" },
+            ],
+            "offset": { "$type": "datetime", "value": "1979-05-27T07:32:00Z" },
+            "local": { "$type": "datetime-local", "value": "1979-05-27T07:32:00" },
+            "date": { "$type": "date-local", "value": "1979-05-27" },
+            "time": { "$type": "time-local", "value": "07:32:00" },
+        })
+    );
+}
@@ -0,0 +1,176 @@
+use crate::{
+    dom::{node::Key, Keys, Node, Path},
+    formatter,
+    parser::parse,
+    util::quote_key,
+};
+use serde_json::json;
+use std::hash::{Hash, Hasher};
+
+const SRC: &str = r#"
+"" = 1
+" " = 2
+a."".b = 3
+a."\t".b = 4
+
+["\t"]
+x = 5
+
+[t." "]
+y = 6
+
+[[t.""]]
+z = 7
+"#;
+
+fn root() -> Node {
+    let p = parse(SRC);
+    assert!(p.errors.is_empty(), "{:?}", p.errors);
+
+    let root = p.into_dom();
+    assert!(root.validate().is_ok());
+    root
+}
+
+fn paths(root: &Node, pattern: &str) -> Vec<String> {
+    root.find_all(pattern)
+        .unwrap()
+        .iter()
+        .map(|(path, _)| path.to_string())
+        .collect()
+}
+
+#[test]
+fn empty_keys_in_dom() {
+    let root = root();
+    let entries = root.as_table().unwrap().entries().read();
+
+    let keys = entries.iter().map(|(k, _)| k.value()).collect::<Vec<_>>();
+    assert_eq!(keys, ["", " ", "a", "\t", "t"]);
+
+    assert!(entries.get("").unwrap().as_integer().is_some());
+    assert!(entries.get(" ").unwrap().as_integer().is_some());
+    assert!(entries.get("\t").unwrap().as_table().is_some());
+    assert!(entries.get("  ").is_none());
+
+    assert!(entries.get_path(&["a", "", "b"]).is_some());
+    assert!(entries.get_path(&["a", "\t", "b"]).is_some());
+    assert!(entries.get_path(&["a", " ", "b"]).is_none());
+    assert!(entries.get_path(&["\t", "x"]).is_some());
+    assert!(entries.get_path(&["t", " ", "y"]).is_some());
+    assert!(entries.get_path(&["t", "", "0", "z"]).is_some());
+}
+
+#[test]
+fn empty_keys_are_distinct() {
+    let keys = [Key::new(""), Key::new(" "), Key::new("\t"), Key::new("  ")];
+
+    for (i, a) in keys.iter().enumerate() {
+        for (j, b) in keys.iter().enumerate() {
+            assert_eq!(a == b, i == j, "{a:?} {b:?}");
+        }
+    }
+
+    let hash = |key: &Key| {
+        let mut hasher = std::collections::hash_map::DefaultHasher::new();
+        key.hash(&mut hasher);
+        hasher.finish()
+    };
+    let mut hashes = keys.iter().map(hash).collect::<Vec<_>>();
+    hashes.sort_unstable();
+    hashes.dedup();
+    assert_eq!(hashes.len(), keys.len());
+
+    // Quoting and escapes don't matter.
+    let dom = parse("\"\" = 1\n'' = 2\n\"\\u0020\" = 3\n' ' = 4").into_dom();
+    let errors = dom.validate().unwrap_err().count();
+    assert_eq!(errors, 2);
+}
+
+#[test]
+fn empty_keys_are_quoted() {
+    assert_eq!(quote_key(""), r#""""#);
+    assert_eq!(quote_key(" "), r#"" ""#);
+    assert_eq!(quote_key("\t"), r#""\t""#);
+    assert_eq!(quote_key(" \t "), r#"" \t ""#);
+
+    let keys = Keys::new(
+        [Key::new(""), Key::new(" "), Key::new("\t")]
+            .into_iter()
+            .map(Into::into),
+    );
+    assert_eq!(keys.dotted(), r#"""." "."\t""#);
+}
+
+#[test]
+fn empty_keys_in_paths() {
+    let root = root();
+
+    let path: Path = r#"a."".b"#.parse().unwrap();
+    assert_eq!(path.len(), 3);
+    assert_eq!(path.segments()[1].as_key(), Some(""));
+    assert_eq!(path.to_string(), r#"a."".b"#);
+
+    assert_eq!(paths(&root, r#""""#), [r#""""#]);
+    assert_eq!(paths(&root, r#"a.*.b"#), [r#"a."".b"#, r#"a."\t".b"#]);
+    assert_eq!(paths(&root, r#"t."".*.z"#), [r#"t.""[0].z"#]);
+    assert_eq!(paths(&root, r#""\t".x"#), [r#""\t".x"#]);
+    assert!(paths(&root, r#"" ".x"#).is_empty());
+}
+
+#[test]
+fn empty_keys_in_formatter() {
+    let formatted = formatter::format(SRC, Default::default());
+    assert!(parse(&formatted).errors.is_empty());
+
+    for line in [
+        r#""" = 1"#,
+        r#"" " = 2"#,
+        r#"a."".b = 3"#,
+        r#"a."\t".b = 4"#,
+        r#"["\t"]"#,
+        r#"[t." "]"#,
+        r#"[[t.""]]"#,
+    ] {
+        assert!(formatted.contains(line), "{line} in {formatted}");
+    }
+
+    let options = formatter::Options {
+        align_entries: true,
+        reorder_keys: true,
+        ..Default::default()
+    };
+    let formatted = formatter::format(SRC, options);
+    let dom = parse(&formatted).into_dom();
+    assert!(dom.validate().is_ok(), "{formatted}");
+    assert_eq!(
+        serde_json::to_value(&dom).unwrap(),
+        serde_json::to_value(root()).unwrap()
+    );
+}
+
+#[test]
+fn empty_keys_in_json() {
+    let json = serde_json::to_value(root()).unwrap();
+
+    assert_eq!(
+        json,
+        json!({
+            "": 1,
+            " ": 2,
+            "a": { "": { "b": 3 }, "\t": { "b": 4 } },
+            "\t": { "x": 5 },
+            "t": { " ": { "y": 6 }, "": [{ "z": 7 }] },
+        })
+    );
+
+    let node: Node = serde_json::from_value(json.clone()).unwrap();
+    let toml = node.to_toml(false, false);
+    assert!(toml.contains(r#""" = 1"#), "{toml}");
+    assert!(toml.contains(r#"[[t.""]]"#), "{toml}");
+    assert!(!toml.contains("''"), "{toml}");
+
+    let p = parse(&toml);
+    assert!(p.errors.is_empty(), "{toml}: {:?}", p.errors);
+    assert_eq!(serde_json::to_value(p.into_dom()).unwrap(), json);
+}
@@ -55,7 +55,7 @@ asd = ""
 
   incremental = true
   lol = 2            #yo
-  debug = 0          # Set this to 1 or 2 to get more useful backtraces in debugger.
+  debug = 0 # Set this to 1 or 2 to get more useful backtraces in debugger.
 
   # asd
 "#;
@@ -214,6 +214,71 @@ my_array = [
     assert_format!(expected, &formatted);
 }
 
+#[test]
+fn comments_in_short_arrays() {
+    let src = r#"
+leading = [
+  # The first feature.
+  "a", "b"
+]
+after_last_comma = [
+  "a",
+  "b", # The last feature.
+]
+before_bracket = [
+  "a",
+  "b"
+  # The end.
+]
+nested = [["a", # Inner.
+  "b"], "c"]
+same_line = ["a", # Why "a".
+  "b", "c"]
+"#;
+
+    let expected = r#"
+leading = [
+  # The first feature.
+  "a",
+  "b",
+]
+after_last_comma = [
+  "a",
+  "b", # The last feature.
+]
+before_bracket = [
+  "a",
+  "b",
+  # The end.
+]
+nested = [
+  [
+    "a", # Inner.
+    "b",
+  ],
+  "c",
+]
+same_line = [
+  "a", # Why "a".
+  "b",
+  "c",
+]
+"#;
+
+    // None of the arrays are collapsed although they would fit in a single line.
+    let formatted = crate::formatter::format(src, formatter::Options::default());
+    assert_format!(expected, &formatted);
+
+    let formatted = crate::formatter::format(
+        expected,
+        formatter::Options {
+            column_width: 200,
+            ..Default::default()
+        },
+    );
+    assert_format!(expected, &formatted);
+}
+
 #[test]
 fn test_align_comments() {
     let src = r#"
@@ -281,6 +346,143 @@ a = "longer_string_hm"      # trailing comment
     assert_format!(expected, &formatted);
 }
 
+#[test]
+fn test_align_comments_column_width() {
+    let src = r#"
+a = 1 # short
+long_value = "aaaaaaaaaaaaaaaaaaaaa" # c
+b = 2 # a comment that is too long to be aligned
+"#;
+
+    let expected = r#"
+a = 1                                # short
+long_value = "aaaaaaaaaaaaaaaaaaaaa" # c
+b = 2 # a comment that is too long to be aligned
+"#;
+
+    let formatted = crate::formatter::format(
+        src,
+        formatter::Options {
+            align_comments: true,
+            column_width: 50,
+            ..Default::default()
+        },
+    );
+
+    assert_format!(expected, &formatted);
+}
+
+#[test]
+fn column_width_boundary() {
+    // The widths are counted in characters, the lines of the first entry
+    // are exactly 80 characters wide and the second one would be 81.
+    let src = r#"
+[a]
+[a.b]
+"ключ" = ["éééééééééééééééééééééééééééééééééééééééééééééééééééééééééé", "ü"]
+"клюк" = ["ééééééééééééééééééééééééééééééééééééééééééééééééééééééééééé", "ü"]
+x = 1
+"#;
+
+    let expected = r#"
+[a]
+  [a.b]
+    "ключ" = ["éééééééééééééééééééééééééééééééééééééééééééééééééééééééééé", "ü"]
+    "клюк" = [
+      "ééééééééééééééééééééééééééééééééééééééééééééééééééééééééééé",
+      "ü",
+    ]
+    x = 1
+"#;
+
+    let options = formatter::Options {
+        indent_tables: true,
+        indent_entries: true,
+        column_width: 80,
+        ..Default::default()
+    };
+
+    let formatted = crate::formatter::format(src, options.clone());
+    assert_format!(expected, &formatted);
+    assert_eq!(formatted.lines().nth(3).unwrap().chars().count(), 80);
+
+    // The padding is left out where it would make the line too long.
+    let src = r#"
+[a]
+[a.b]
+"ключ" = ["éééééééééééééééééééééééééééééééééééééééééééééééééééééééééé", "ü"]
+"ключ_2" = 1
+x = 2
+"#;
+
+    let expected = r#"
+[a]
+  [a.b]
+    "ключ" = ["éééééééééééééééééééééééééééééééééééééééééééééééééééééééééé", "ü"]
+    "ключ_2" = 1
+    x        = 2
+"#;
+
+    let formatted = crate::formatter::format(
+        src,
+        formatter::Options {
+            align_entries: true,
+            ..options
+        },
+    );
+    assert_format!(expected, &formatted);
+}
+
+#[test]
+fn test_align_comments_indentation() {
+    let src = r#"[t]
+a = 1 # c
+bbb = 2 # d
+"#;
+
+    for indent in ["\t", "    "] {
+        let expected = format!("[t]\n{indent}a = 1   # c\n{indent}bbb = 2 # d\n");
+
+        let formatted = crate::formatter::format(
+            src,
+            formatter::Options {
+                align_comments: true,
+                indent_entries: true,
+                indent_string: indent.into(),
+                ..Default::default()
+            },
+        );
+
+        assert_format!(&expected, &formatted);
+    }
+}
+
+#[test]
+fn test_align_comments_unicode() {
+    // Widths are counted in characters.
+    let src = r#"
+a = "ü" # c
+b = "日本語" # d
+c = "xy" # e
+"#;
+
+    let expected = r#"
+a = "ü"   # c
+b = "日本語" # d
+c = "xy"  # e
+"#;
+
+    let formatted = crate::formatter::format(
+        src,
+        formatter::Options {
+            align_comments: true,
+            ..Default::default()
+        },
+    );
+
+    assert_format!(expected, &formatted);
+}
+
 #[test]
 fn test_align_entries_no_comments() {
     let src = r#"
@@ -307,6 +509,55 @@ a            = "longer_string_hm" # trailing comment
     assert_format!(expected, &formatted);
 }
 
+#[test]
+fn test_align_entries_per_block() {
+    let src = r#"
+[package]
+name = "taplo"
+a_very_long_key_name = true
+
+version = "0.1.0"
+edition = "2021"
+# comment
+description = "A TOML toolkit"
+license = "MIT"
+  
+readme = "README.md"
+rust-version = "1.60"
+[dependencies]
+toml = "0.5"
+serde_json = "1"
+"#;
+
+    let expected = r#"
+[package]
+name                 = "taplo"
+a_very_long_key_name = true
+
+version = "0.1.0"
+edition = "2021"
+# comment
+description = "A TOML toolkit"
+license     = "MIT"
+
+readme       = "README.md"
+rust-version = "1.60"
+[dependencies]
+toml       = "0.5"
+serde_json = "1"
+"#;
+
+    let formatted = crate::formatter::format(
+        src,
+        formatter::Options {
+            align_entries: true,
+            ..Default::default()
+        },
+    );
+
+    assert_format!(expected, &formatted);
+}
+
 #[test]
 fn test_nested_arrays() {
     let src = r#"
@@ -597,6 +848,78 @@ inline_table={ key="value" }
     assert_format!(expected, &formatted);
 }
 
+#[test]
+fn compact_entries_round_trip() {
+    let src = r#"a = 1
+long_key = { x = 1, yy = [1, 2] } # comment
+b="s" # comment
+"#;
+
+    let cases = [
+        (
+            false,
+            false,
+            r#"a = 1
+long_key = { x = 1, yy = [1, 2] } # comment
+b = "s"                           # comment
+"#,
+        ),
+        (
+            false,
+            true,
+            r#"a        = 1
+long_key = { x = 1, yy = [1, 2] } # comment
+b        = "s"                    # comment
+"#,
+        ),
+        (
+            true,
+            false,
+            r#"a=1
+long_key={x=1, yy=[1, 2]} # comment
+b="s"                     # comment
+"#,
+        ),
+        (
+            true,
+            true,
+            r#"a       =1
+long_key={x=1, yy=[1, 2]} # comment
+b       ="s"              # comment
+"#,
+        ),
+    ];
+
+    for (compact, align, expected) in cases {
+        let options = formatter::Options {
+            compact_entries: compact,
+            compact_inline_tables: compact,
+            align_entries: align,
+            ..Default::default()
+        };
+
+        let formatted = formatter::format(src, options.clone());
+        assert_format!(expected, &formatted);
+
+        // Formatting again with the same options changes nothing.
+        assert_format!(expected, &formatter::format(&formatted, options.clone()));
+
+        // Formatting with the other options gives the same result
+        // as formatting the original source.
+        for (other_compact, other_align, other_expected) in cases {
+            let other_options = formatter::Options {
+                compact_entries: other_compact,
+                compact_inline_tables: other_compact,
+                align_entries: other_align,
+                ..Default::default()
+            };
+
+            let reformatted = formatter::format(&formatted, other_options);
+            assert_format!(other_expected, &reformatted);
+        }
+    }
+}
+
 #[test]
 fn array_no_trailing_comma() {
     let src = r#"
@@ -1024,3 +1347,2202 @@ foo = [
 
     assert_format!(expected, &formatted);
 }
+
+#[test]
+fn grouped_options_match_legacy_options() {
+    use formatter::{ArrayOptions, InlineTableOptions, KeyOptions, OptionsIncomplete};
+
+    let src = r#"
+b = 2
+a = { x = [3, 1], y = 2 }
+arr = [
+  "c", "b", "a" ]
+long = ["aaaaaaaaaaaaaaaaaaaa", "bbbbbbbbbbbbbbbbbbbbbbbbb", "cccccccccccccccccccccccccc"]
+t = { x = ["aaaaaaaaaaaaaaaaaaaa", "bbbbbbbbbbbbbbbbbbbbbbbbb", "cccccccccccccccccccccccccc"] }
+"quoted" = 3
+"#;
+
+    let format_with = |opts: OptionsIncomplete| {
+        let mut options = formatter::Options::default();
+        options.update(opts);
+        formatter::format(src, options)
+    };
+
+    // Every option is set to the opposite of its default.
+    let cases = [
+        (
+            OptionsIncomplete {
+                array_auto_collapse: Some(false),
+                ..Default::default()
+            },
+            OptionsIncomplete {
+                array: Some(ArrayOptions {
+                    auto_collapse: Some(false),
+                    ..Default::default()
+                }),
+                ..Default::default()
+            },
+        ),
+        (
+            OptionsIncomplete {
+                array_auto_expand: Some(false),
+                ..Default::default()
+            },
+            OptionsIncomplete {
+                array: Some(ArrayOptions {
+                    auto_expand: Some(false),
+                    ..Default::default()
+                }),
+                ..Default::default()
+            },
+        ),
+        (
+            OptionsIncomplete {
+                array_trailing_comma: Some(false),
+                ..Default::default()
+            },
+            OptionsIncomplete {
+                array: Some(ArrayOptions {
+                    trailing_comma: Some(false),
+                    ..Default::default()
+                }),
+                ..Default::default()
+            },
+        ),
+        (
+            OptionsIncomplete {
+                compact_arrays: Some(false),
+                ..Default::default()
+            },
+            OptionsIncomplete {
+                array: Some(ArrayOptions {
+                    bracket_spacing: Some(true),
+                    ..Default::default()
+                }),
+                ..Default::default()
+            },
+        ),
+        (
+            OptionsIncomplete {
+                reorder_arrays: Some(true),
+                ..Default::default()
+            },
+            OptionsIncomplete {
+                array: Some(ArrayOptions {
+                    reorder: Some(true),
+                    ..Default::default()
+                }),
+                ..Default::default()
+            },
+        ),
+        (
+            OptionsIncomplete {
+                inline_table_expand: Some(false),
+                ..Default::default()
+            },
+            OptionsIncomplete {
+                inline_table: Some(InlineTableOptions {
+                    expand: Some(false),
+                    ..Default::default()
+                }),
+                ..Default::default()
+            },
+        ),
+        (
+            OptionsIncomplete {
+                compact_inline_tables: Some(true),
+                ..Default::default()
+            },
+            OptionsIncomplete {
+                inline_table: Some(InlineTableOptions {
+                    brace_spacing: Some(false),
+                    ..Default::default()
+                }),
+                ..Default::default()
+            },
+        ),
+        (
+            OptionsIncomplete {
+                reorder_keys: Some(true),
+                ..Default::default()
+            },
+            OptionsIncomplete {
+                keys: Some(KeyOptions {
+                    reorder: Some(true),
+                    ..Default::default()
+                }),
+                ..Default::default()
+            },
+        ),
+        (
+            OptionsIncomplete {
+                array_expand_threshold: Some(Some(2)),
+                ..Default::default()
+            },
+            OptionsIncomplete {
+                array: Some(ArrayOptions {
+                    expand_threshold: Some(2),
+                    ..Default::default()
+                }),
+                ..Default::default()
+            },
+        ),
+        (
+            OptionsIncomplete {
+                key_quotes: Some(formatter::KeyQuotes::Minimal),
+                ..Default::default()
+            },
+            OptionsIncomplete {
+                keys: Some(KeyOptions {
+                    quotes: Some(formatter::KeyQuotes::Minimal),
+                    ..Default::default()
+                }),
+                ..Default::default()
+            },
+        ),
+        (
+            OptionsIncomplete {
+                reorder_keys: Some(true),
+                key_order_rules: Some(vec!["long".into()]),
+                ..Default::default()
+            },
+            OptionsIncomplete {
+                keys: Some(KeyOptions {
+                    reorder: Some(true),
+                    order_rules: Some(vec!["long".into()]),
+                    ..Default::default()
+                }),
+                ..Default::default()
+            },
+        ),
+    ];
+
+    let default = format_with(OptionsIncomplete::default());
+
+    for (idx, (legacy, grouped)) in cases.into_iter().enumerate() {
+        let mut legacy_options = formatter::Options::default();
+        legacy_options.update(legacy.clone());
+
+        let mut grouped_options = formatter::Options::default();
+        grouped_options.update(grouped.clone());
+
+        assert_eq!(legacy_options, grouped_options);
+        assert_ne!(legacy_options, formatter::Options::default());
+
+        let formatted = format_with(legacy);
+        assert_eq!(formatted, format_with(grouped));
+        assert_ne!(formatted, default, "option {idx} has no effect");
+    }
+}
+
+#[test]
+fn grouped_options_take_precedence() {
+    let mut options = formatter::Options::default();
+    options.update(formatter::OptionsIncomplete {
+        compact_arrays: Some(true),
+        array: Some(formatter::ArrayOptions {
+            bracket_spacing: Some(true),
+            ..Default::default()
+        }),
+        ..Default::default()
+    });
+
+    assert!(!options.compact_arrays);
+}
+
+#[test]
+fn grouped_options_from_str() {
+    let mut options = formatter::Options::default();
+    options
+        .update_from_str(
+            [
+                ("array.bracket_spacing", "true"),
+                ("inline_table.expand", "false"),
+                ("keys.reorder", "true"),
+                ("array.expand_threshold", "3"),
+                ("keys.quotes", "minimal"),
+                ("keys.order_rules", "name, version"),
+            ]
+            .into_iter(),
+        )
+        .unwrap();
+
+    assert!(!options.compact_arrays);
+    assert!(!options.inline_table_expand);
+    assert!(options.reorder_keys);
+    assert_eq!(options.array_expand_threshold, Some(3));
+    assert_eq!(options.key_quotes, formatter::KeyQuotes::Minimal);
+    assert_eq!(options.key_order_rules, ["name", "version"]);
+
+    assert!(options
+        .update_from_str([("array.bracket_spacing", "yes")].into_iter())
+        .is_err());
+
+    // Errors name the grouped option.
+    let error = options
+        .update_from_str([("keys.quotes", "always")])
+        .unwrap_err();
+    assert!(
+        matches!(&error, formatter::OptionParseError::InvalidValue { key, .. } if key == "keys.quotes"),
+        "{error}"
+    );
+    assert!(options
+        .update_from_str([("array.unknown", "true")].into_iter())
+        .is_err());
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn grouped_options_deserialize() {
+    let camel: formatter::OptionsIncompleteCamel = serde_json::from_value(serde_json::json!({
+        "compactArrays": true,
+        "array": { "bracketSpacing": true, "expandThreshold": 4 },
+        "inlineTable": { "braceSpacing": true },
+        "keys": { "quotes": "minimal", "orderRules": ["name"] },
+    }))
+    .unwrap();
+
+    let snake: formatter::OptionsIncomplete = serde_json::from_value(serde_json::json!({
+        "compact_arrays": true,
+        "array": { "bracket_spacing": true, "expand_threshold": 4 },
+        "inline_table": { "brace_spacing": true },
+        "keys": { "quotes": "minimal", "order_rules": ["name"] },
+    }))
+    .unwrap();
+
+    let mut camel_options = formatter::Options::default();
+    camel_options.update_camel(camel);
+
+    let mut snake_options = formatter::Options::default();
+    snake_options.update(snake);
+
+    assert_eq!(camel_options, snake_options);
+    assert!(!camel_options.compact_arrays);
+    assert!(!camel_options.compact_inline_tables);
+    assert_eq!(camel_options.array_expand_threshold, Some(4));
+    assert_eq!(camel_options.key_quotes, formatter::KeyQuotes::Minimal);
+    assert_eq!(camel_options.key_order_rules, ["name"]);
+}
+
+#[test]
+fn array_expand_threshold() {
+    let src = r#"a = [1, 2, 3]
+b = [1, 2]
+c = [[1, 2, 3], []]
+"#;
+
+    let expected = r#"a = [
+  1,
+  2,
+  3,
+]
+b = [1, 2]
+c = [
+  [
+    1,
+    2,
+    3,
+  ],
+  [],
+]
+"#;
+
+    let options = formatter::Options {
+        array_expand_threshold: Some(2),
+        ..Default::default()
+    };
+
+    let formatted = formatter::format(src, options);
+    assert_format!(expected, &formatted);
+}
+
+#[test]
+fn key_quotes_minimal() {
+    let src = r#""a" = 1
+'b-c' = 2
+"d e" = 3
+"123" = 4
+"true" = 5
+"" = 6
+"f\u0067" = 7
+"1979-05-27".x = 8
+
+["t"."u"]
+v.'w' = 9
+"#;
+
+    let expected = r#"a = 1
+b-c = 2
+"d e" = 3
+"123" = 4
+"true" = 5
+"" = 6
+fg = 7
+"1979-05-27".x = 8
+
+[t.u]
+v.w = 9
+"#;
+
+    let options = formatter::Options {
+        key_quotes: formatter::KeyQuotes::Minimal,
+        ..Default::default()
+    };
+
+    let formatted = formatter::format(src, options);
+    assert_format!(expected, &formatted);
+    let formatted = formatter::format(src, Default::default());
+    assert_format!(src, &formatted);
+}
+
+#[test]
+fn key_order_rules() {
+    let src = r#"c = 1
+# comment
+version = "1.0"
+a = 2
+name = "taplo"
+"#;
+
+    let expected = r#"name = "taplo"
+# comment
+version = "1.0"
+a = 2
+c = 1
+"#;
+
+    let options = formatter::Options {
+        reorder_keys: true,
+        key_order_rules: vec!["name".into(), "version".into(), "missing".into()],
+        ..Default::default()
+    };
+
+    let formatted = formatter::format(src, options);
+    assert_format!(expected, &formatted);
+
+    // The rules don't move the keys without reordering.
+    let options = formatter::Options {
+        key_order_rules: vec!["name".into()],
+        ..Default::default()
+    };
+
+    let formatted = formatter::format(src, options);
+    assert_format!(src, &formatted);
+}
+
+#[test]
+fn reflow_multiline_string_indent() {
+    let src = r#"[a]
+[a.b]
+s = """
+first
+
+second"""
+t = """\
+    continued \
+    line"""
+u = '''
+literal'''
+v = """
+  significant"""
+"#;
+
+    let expected = r#"[a]
+  [a.b]
+    s = """\
+    first\n\
+    \n\
+    second"""
+    t = """\
+        continued \
+        line"""
+    u = '''
+literal'''
+    v = """
+  significant"""
+"#;
+
+    let options = formatter::Options {
+        indent_tables: true,
+        indent_entries: true,
+        reflow_multiline_string_indent: true,
+        ..Default::default()
+    };
+
+    let formatted = crate::formatter::format(src, options.clone());
+    assert_format!(expected, &formatted);
+
+    let value = |src: &str, key: &str| {
+        crate::parser::parse(src)
+            .into_dom()
+            .get("a")
+            .get("b")
+            .get(key)
+            .as_str()
+            .unwrap()
+            .value()
+            .to_string()
+    };
+
+    for key in ["s", "t", "u", "v"] {
+        assert_eq!(value(src, key), value(&formatted, key));
+    }
+
+    // The reflowed strings are stable.
+    let reformatted = crate::formatter::format(&formatted, options);
+    assert_format!(&formatted, &reformatted);
+}
+
+#[test]
+fn reflow_multiline_string_preserves_values() {
+    use crate::formatter::reflow_multiline_string;
+
+    let fragments = [
+        "a", "b c", " ", "  ", "\t", "\n", "\r\n", "\\\n", "\\  \n", "\\n", "\\\\", "\\t", "\"",
+        "\\u00e9",
+    ];
+    let indents = ["", " ", "  ", "    ", "\t"];
+
+    let mut rng = super::Rng(0x9E37_79B9_7F4A_7C15);
+
+    let mut changed = 0;
+
+    for _ in 0..5000 {
+        let old_indent = indents[rng.next(indents.len())];
+        let new_indent = indents[rng.next(indents.len())];
+
+        let mut string = String::from(r#"""""#);
+        if rng.next(2) == 0 {
+            string += "\n";
+        }
+        for _ in 0..rng.next(10) {
+            string += fragments[rng.next(fragments.len())];
+            if rng.next(3) == 0 {
+                string += "\n";
+                string += old_indent;
+            }
+        }
+        string += r#"""""#;
+
+        let Some(value) = crate::parser::parse(&format!("s = {string}"))
+            .into_dom()
+            .get("s")
+            .as_str()
+            .map(|s| s.value().to_string())
+        else {
+            continue;
+        };
+
+        let reflowed = reflow_multiline_string(&string, old_indent, new_indent);
+
+        let reflowed_value = crate::parser::parse(&format!("s = {reflowed}"))
+            .into_dom()
+            .get("s")
+            .as_str()
+            .map(|s| s.value().to_string());
+
+        assert_eq!(Some(value), reflowed_value, "{string:?} -> {reflowed:?}");
+
+        if reflowed != string {
+            changed += 1;
+        }
+    }
+
+    assert!(changed > 0);
+}
+
+#[test]
+fn byte_order_mark() {
+    let src = "\u{FEFF}a=1\n\n\n\n[b]\nc=2\n";
+
+    assert_eq!(
+        crate::formatter::format(src, Default::default()),
+        "\u{FEFF}a = 1\n\n\n[b]\nc = 2\n"
+    );
+
+    assert_eq!(
+        crate::formatter::format(
+            src,
+            formatter::Options {
+                preserve_bom: false,
+                ..Default::default()
+            }
+        ),
+        "a = 1\n\n\n[b]\nc = 2\n"
+    );
+}
+
+#[test]
+fn long_inline_tables_stay_on_one_line() {
+    let src = r#"
+t = { name = "aaaaaaaaaa", version = "bbbbbbbbbb", nested = { a = "cccccccccc", b = "dddddddddd" } }
+arr = [{ a = "aaaaaaaaaa", b = "bbbbbbbbbb" }, { c = "cccccccccc", d = "dddddddddd", e = [1, 2, 3] }]
+"#;
+
+    for inline_table_expand in [true, false] {
+        let formatted = crate::formatter::format(
+            src,
+            formatter::Options {
+                column_width: 20,
+                inline_table_expand,
+                ..Default::default()
+            },
+        );
+
+        // Arrays in inline tables can be expanded,
+        // but there are no newlines between the entries.
+        let p = crate::parser::parse(&formatted);
+        assert!(p.errors.is_empty(), "{formatted}\n{:?}", p.errors);
+        assert!(p
+            .into_syntax()
+            .descendants()
+            .filter(|n| n.kind() == crate::syntax::SyntaxKind::INLINE_TABLE)
+            .all(|n| n
+                .children_with_tokens()
+                .all(|c| c.kind() != crate::syntax::SyntaxKind::NEWLINE)));
+    }
+}
+
+/// Format the part of the source between the `|` markers (or at a single one)
+/// and return the entire source with the formatted part.
+fn format_range(src: &str, options: formatter::Options) -> Option<String> {
+    let markers: Vec<usize> = src.match_indices('|').map(|(i, _)| i).collect();
+    let src = src.replace('|', "");
+    let range = rowan::TextRange::new(
+        (markers[0] as u32).into(),
+        ((markers[markers.len() - 1] - markers.len() + 1) as u32).into(),
+    );
+
+    let (range, formatted) =
+        formatter::format_range(crate::parser::parse(&src).into_syntax(), range, options)?;
+
+    let mut src = src;
+    src.replace_range(std::ops::Range::<usize>::from(range), &formatted);
+    Some(src)
+}
+
+#[test]
+fn format_range_expands_to_entries() {
+    let src = r#"a=1
+b   =  |  2
+c  =  [ 3,4 ]
+d=4
+"#;
+
+    let expected = r#"a=1
+b = 2
+c = [3, 4]
+d=4
+"#;
+
+    let src = src.replacen("c  =  [ 3,4 ]", "c  =  [ 3,|4 ]", 1);
+    assert_format!(
+        expected,
+        &format_range(&src, formatter::Options::default()).unwrap()
+    );
+
+    // The cursor in an entry.
+    assert_format!(
+        "a=1\nb = 2\n",
+        &format_range("a=1\nb  =|  2\n", formatter::Options::default()).unwrap()
+    );
+}
+
+#[test]
+fn format_range_indentation() {
+    let src = r#"[a]
+x=1
+
+[a.b]
+|y   =  2
+z=3|
+"#;
+
+    let expected = r#"[a]
+x=1
+
+[a.b]
+    y = 2
+    z = 3
+"#;
+
+    assert_format!(
+        expected,
+        &format_range(
+            src,
+            formatter::Options {
+                indent_tables: true,
+                indent_entries: true,
+                ..Default::default()
+            }
+        )
+        .unwrap()
+    );
+}
+
+#[test]
+fn format_range_reordered_entries() {
+    let src = "c=3\nb=|2\na=1\n\nz=0\n";
+
+    assert_format!(
+        "a = 1\nb = 2\nc = 3\n\nz=0\n",
+        &format_range(
+            src,
+            formatter::Options {
+                reorder_keys: true,
+                ..Default::default()
+            }
+        )
+        .unwrap()
+    );
+}
+
+#[test]
+fn format_range_only_comments() {
+    assert!(format_range("a=1\n|# comment|\nb=2\n", formatter::Options::default()).is_none());
+}
+
+#[test]
+fn format_range_with_path_scopes() {
+    let src = "a=[1,2]\n[t]\nb=[1,2]\nc=[1,2]\n";
+    let dom = crate::parser::parse(src).into_dom();
+
+    let (range, formatted) = formatter::format_range_with_path_scopes(
+        dom,
+        rowan::TextRange::new(0.into(), 15.into()),
+        formatter::Options::default(),
+        &[],
+        [(
+            "t",
+            formatter::OptionsIncomplete {
+                compact_arrays: Some(false),
+                ..Default::default()
+            },
+        )],
+    )
+    .unwrap()
+    .unwrap();
+
+    let mut src = src.to_string();
+    src.replace_range(std::ops::Range::<usize>::from(range), &formatted);
+    assert_format!("a = [1, 2]\n[t]\nb = [ 1, 2 ]\nc=[1,2]\n", &src);
+}
+
+fn apply_edits(src: &str, edits: &[(rowan::TextRange, String)]) -> String {
+    let mut src = src.to_string();
+    for (range, text) in edits.iter().rev() {
+        src.replace_range(std::ops::Range::<usize>::from(*range), text);
+    }
+    src
+}
+
+#[test]
+fn format_edits_only_changed_lines() {
+    let src = "a = 1\nb=2\nc = 3\n\n\n\nd = [ 4 ]\ne = 5";
+
+    let edits = formatter::format_edits(
+        crate::parser::parse(src).into_syntax(),
+        formatter::Options::default(),
+    );
+
+    assert_eq!(
+        edits
+            .iter()
+            .map(|(range, text)| (&src[*range], text.as_str()))
+            .collect::<Vec<_>>(),
+        [
+            ("b=2\n", "b = 2\n"),
+            ("\nd = [ 4 ]\ne = 5", "d = [4]\ne = 5\n")
+        ]
+    );
+}
+
+#[test]
+fn format_edits_agree_with_format() {
+    for (name, src) in &super::corpus() {
+        let options = formatter::Options::default();
+        let p = crate::parser::parse(src);
+
+        let formatted = formatter::format_syntax(p.clone().into_syntax(), options.clone());
+        let edits = formatter::format_edits(p.clone().into_syntax(), options.clone());
+        assert_eq!(apply_edits(src, &edits), formatted, "{name}");
+
+        let errors: Vec<_> = p.errors.iter().map(|err| err.range).collect();
+        let scopes = [(
+            "*",
+            formatter::OptionsIncomplete {
+                compact_arrays: Some(false),
+                ..Default::default()
+            },
+        )];
+
+        let formatted = formatter::format_with_path_scopes(
+            p.clone().into_dom(),
+            options.clone(),
+            &errors,
+            scopes.clone(),
+        );
+        let edits = formatter::format_edits_with_path_scopes(
+            p.into_dom(),
+            options.clone(),
+            &errors,
+            scopes,
+        );
+
+        match (formatted, edits) {
+            (Ok(formatted), Ok(edits)) => assert_eq!(apply_edits(src, &edits), formatted, "{name}"),
+            (formatted, edits) => assert_eq!(formatted.is_err(), edits.is_err(), "{name}"),
+        }
+    }
+}
+
+#[test]
+fn path_scopes() {
+    let src = r#"[package]
+name = "taplo"
+version = "0.1.0"
+edition = "2021"
+
+[dependencies]
+toml = "0.5"
+serde = "1"
+anyhow = "1"
+
+[workspace]
+members = [
+    "crates/a",
+    "crates/b",
+]
+exclude = [
+    "crates/c",
+]
+"#;
+
+    let expected = r#"[package]
+name = "taplo"
+version = "0.1.0"
+edition = "2021"
+
+[dependencies]
+anyhow = "1"
+serde = "1"
+toml = "0.5"
+
+[workspace]
+members = [
+  "crates/a",
+  "crates/b",
+]
+exclude = ["crates/c"]
+"#;
+
+    let formatted = formatter::format_with_path_scopes(
+        crate::parser::parse(src).into_dom(),
+        formatter::Options::default(),
+        &[],
+        vec![
+            (
+                String::from("dependencies"),
+                formatter::OptionsIncomplete {
+                    reorder_keys: Some(true),
+                    ..Default::default()
+                },
+            ),
+            (
+                String::from("workspace.members"),
+                formatter::OptionsIncomplete {
+                    array_auto_collapse: Some(false),
+                    ..Default::default()
+                },
+            ),
+        ],
+    )
+    .unwrap();
+
+    assert_format!(expected, &formatted);
+}
+
+#[test]
+fn nested_path_scopes() {
+    let src = r#"
+[t]
+a = [1, 2]
+b = [1, 2]
+c = { d = [1, 2] }
+
+[t.inner]
+e = [1, 2]
+
+[u]
+f = [1, 2]
+"#;
+
+    let expected = r#"
+[t]
+a = [ 1, 2 ]
+b = [1, 2]
+c = { d = [1, 2] }
+
+[t.inner]
+e = [1, 2]
+
+[u]
+f = [1, 2]
+"#;
+
+    let scope = |compact_arrays| formatter::OptionsIncomplete {
+        compact_arrays: Some(compact_arrays),
+        ..Default::default()
+    };
+
+    // The most specific scope wins regardless of the order of the scopes.
+    let formatted = formatter::format_with_path_scopes(
+        crate::parser::parse(src).into_dom(),
+        formatter::Options::default(),
+        &[],
+        [
+            ("t.b", scope(true)),
+            ("t.c.d", scope(true)),
+            ("t.inner", scope(true)),
+            ("t", scope(false)),
+        ],
+    )
+    .unwrap();
+
+    assert_format!(expected, &formatted);
+
+    // The later one of equally specific scopes wins.
+    let formatted = formatter::format_with_path_scopes(
+        crate::parser::parse(src).into_dom(),
+        formatter::Options::default(),
+        &[],
+        [("u", scope(false)), ("u", scope(true))],
+    )
+    .unwrap();
+
+    assert_format!(src, &formatted);
+}
+
+#[test]
+fn allowed_blank_lines() {
+    let src = "\n\n\n\na = 1\n\n\n\n\nb = 2\n\n\n\n[table]\n\n\n\nc = \"\"\"\nx\n\n\n\ny\"\"\"\nd = '''\n\n\n\n'''\n\n\n\n\n";
+
+    let options = |allowed_blank_lines| formatter::Options {
+        allowed_blank_lines,
+        ..Default::default()
+    };
+
+    let expected =
+        "\na = 1\n\nb = 2\n\n[table]\n\nc = \"\"\"\nx\n\n\n\ny\"\"\"\nd = '''\n\n\n\n'''\n";
+    assert_format!(expected, &formatter::format(src, options(1)));
+
+    let expected = "a = 1\nb = 2\n[table]\nc = \"\"\"\nx\n\n\n\ny\"\"\"\nd = '''\n\n\n\n'''\n";
+    assert_format!(expected, &formatter::format(src, options(0)));
+
+    // The trailing newline option decides the end of the document.
+    let expected =
+        "\n\na = 1\n\n\nb = 2\n\n\n[table]\n\n\nc = \"\"\"\nx\n\n\n\ny\"\"\"\nd = '''\n\n\n\n'''";
+    assert_format!(
+        expected,
+        &formatter::format(
+            src,
+            formatter::Options {
+                trailing_newline: false,
+                ..options(2)
+            }
+        )
+    );
+}
+
+#[test]
+fn allowed_blank_lines_in_arrays() {
+    let src = "a = [\n  1,\n\n\n\n  2, # comment\n\n\n\n  # comment\n  3,\n]\n";
+
+    let expected = "a = [\n  1,\n\n  2, # comment\n\n  # comment\n  3,\n]\n";
+    assert_format!(
+        expected,
+        &formatter::format(
+            src,
+            formatter::Options {
+                allowed_blank_lines: 1,
+                array_auto_collapse: false,
+                ..Default::default()
+            }
+        )
+    );
+}
+
+#[test]
+fn table_spacing() {
+    let src = r#"a = 1
+[t]
+b = 2
+
+
+# comment of u
+# more comments of u
+[u]
+
+[[v]]
+c = 3
+[[v]]
+"#;
+
+    let expected = r#"a = 1
+
+[t]
+b = 2
+
+# comment of u
+# more comments of u
+[u]
+
+[[v]]
+c = 3
+
+[[v]]
+"#;
+
+    let options = |table_spacing| formatter::Options {
+        table_spacing,
+        ..Default::default()
+    };
+
+    assert_format!(expected, &formatter::format(src, options(Some(1))));
+
+    let expected = r#"a = 1
+[t]
+b = 2
+# comment of u
+# more comments of u
+[u]
+[[v]]
+c = 3
+[[v]]
+"#;
+    assert_format!(expected, &formatter::format(src, options(Some(0))));
+
+    // The blank lines are kept by default.
+    assert_format!(src, &formatter::format(src, options(None)));
+
+    // No blank lines are added at the start of the document.
+    let src = "# comment\n[t]\na = 1\n[u]\n";
+    assert_format!(
+        "# comment\n[t]\na = 1\n\n[u]\n",
+        &formatter::format(src, options(Some(1)))
+    );
+}
+
+#[test]
+fn table_spacing_indent_tables() {
+    let src = r#"[a]
+x = 1
+[a.b]
+y = 2
+
+
+[c]
+z = 3
+"#;
+
+    let expected = r#"[a]
+x = 1
+
+  [a.b]
+  y = 2
+
+[c]
+z = 3
+"#;
+
+    assert_format!(
+        expected,
+        &formatter::format(
+            src,
+            formatter::Options {
+                table_spacing: Some(1),
+                indent_tables: true,
+                ..Default::default()
+            }
+        )
+    );
+}
+
+#[test]
+fn table_spacing_from_str() {
+    let mut options = formatter::Options::default();
+
+    options
+        .update_from_str([("table_spacing", "1")].into_iter())
+        .unwrap();
+    assert_eq!(options.table_spacing, Some(1));
+
+    options
+        .update_from_str([("table_spacing", "")].into_iter())
+        .unwrap();
+    assert_eq!(options.table_spacing, None);
+
+    assert!(options
+        .update_from_str([("table_spacing", "one")].into_iter())
+        .is_err());
+}
+
+#[test]
+fn convert_long_inline_tables() {
+    let src = r#"name = "taplo"
+serde = { version = "1.0", features = ["derive"], optional = true } # serialization
+short = { a = 1 }
+
+[dependencies]
+tokio = { version = "1", features = ["full"], default-features = false }
+log = "0.4"
+
+[[bin]]
+name = "taplo"
+meta = { description = "A TOML toolkit", nested = { a = "long value", b = "a longer value" } }
+"#;
+
+    let expected = r#"name = "taplo"
+short = { a = 1 }
+
+[serde] # serialization
+version = "1.0"
+features = ["derive"]
+optional = true
+
+[dependencies]
+log = "0.4"
+
+[dependencies.tokio]
+version = "1"
+features = ["full"]
+default-features = false
+
+[[bin]]
+name = "taplo"
+
+[bin.meta]
+description = "A TOML toolkit"
+
+[bin.meta.nested]
+a = "long value"
+b = "a longer value"
+"#;
+
+    let options = formatter::Options {
+        convert_long_inline_tables: true,
+        column_width: 50,
+        ..Default::default()
+    };
+
+    let formatted = formatter::format(src, options.clone());
+    assert_format!(expected, &formatted);
+
+    let reformatted = formatter::format(&formatted, options);
+    assert_format!(expected, &reformatted);
+}
+
+#[test]
+fn convert_long_inline_tables_in_arrays() {
+    let src = r#"items = [{ name = "first item", description = "the first item" }]
+tables = { list = [{ name = "first item", description = "the first item" }] }
+"#;
+
+    let expected = r#"items = [
+  { name = "first item", description = "the first item" },
+]
+
+[tables]
+list = [
+  { name = "first item", description = "the first item" },
+]
+"#;
+
+    let formatted = formatter::format(
+        src,
+        formatter::Options {
+            convert_long_inline_tables: true,
+            column_width: 50,
+            ..Default::default()
+        },
+    );
+    assert_format!(expected, &formatted);
+
+    let long_lines = crate::util::syntax::unfixable_long_lines(
+        &crate::parser::parse(&formatted).into_syntax(),
+        50,
+    );
+    assert_eq!(long_lines.len(), 2);
+}
+
+#[test]
+fn prefer_dotted_keys_for_single_entries() {
+    let src = r#"[package]
+name = "taplo"
+
+# Metadata for docs.rs
+[package.metadata] # docs
+docs = { all-features = true }
+
+[dependencies.serde]
+version = "1"
+
+[target.'cfg(unix)'.dependencies]
+libc = "0.2"
+nix = "0.26"
+
+[[bin]]
+name = "taplo"
+
+[bin.meta]
+a = 1
+
+[a.b]
+c = 1
+
+[a.b.d]
+e = 1
+"#;
+
+    let expected = r#"dependencies.serde.version = "1"
+[package]
+name = "taplo"
+
+# Metadata for docs.rs
+# docs
+metadata.docs = { all-features = true }
+
+[target.'cfg(unix)'.dependencies]
+libc = "0.2"
+nix = "0.26"
+
+[[bin]]
+name = "taplo"
+
+[bin.meta]
+a = 1
+
+[a.b]
+c = 1
+
+d.e = 1
+"#;
+
+    let options = formatter::Options {
+        prefer_dotted_keys_for_single_entries: true,
+        ..Default::default()
+    };
+
+    let formatted = formatter::format(src, options.clone());
+    assert_format!(expected, &formatted);
+
+    let reformatted = formatter::format(&formatted, options);
+    assert_format!(expected, &reformatted);
+}
+
+#[test]
+fn prefer_dotted_keys_max_depth() {
+    let src = r#"[a.b.c]
+d = 1
+
+[e.f]
+g = 1
+# a comment that would be orphaned
+
+[h]
+i = 1
+"#;
+
+    let expected = r#"h.i = 1
+[a.b.c]
+d = 1
+
+[e.f]
+g = 1
+# a comment that would be orphaned
+"#;
+
+    let formatted = formatter::format(
+        src,
+        formatter::Options {
+            prefer_dotted_keys_for_single_entries: true,
+            ..Default::default()
+        },
+    );
+    assert_format!(expected, &formatted);
+}
+
+#[test]
+fn expand_dotted_keys() {
+    let src = r#"a.b.c.d = 1
+x = 1
+
+[t]
+a.b.c.d = 1 # comment
+y.z = 2
+a.b.c.long-key = 2
+a.b.x.y = 3
+inline = { a.b.c.d = 1 }
+
+[[arr]]
+a.b.c.d = 1
+"#;
+
+    let expected = r#"x = 1
+
+[a.b.c]
+d = 1
+
+[t]
+y.z = 2
+inline = { a.b.c.d = 1 }
+
+[t.a.b.c]
+d = 1        # comment
+long-key = 2
+
+[t.a.b.x]
+y = 3
+
+[[arr]]
+a.b.c.d = 1
+"#;
+
+    let options = formatter::Options {
+        expand_dotted_keys: true,
+        ..Default::default()
+    };
+
+    let formatted = formatter::format(src, options.clone());
+    assert_format!(expected, &formatted);
+
+    let reformatted = formatter::format(&formatted, options);
+    assert_format!(expected, &reformatted);
+}
+
+#[test]
+fn normalize_integers() {
+    let src = r#"a = 0xDEAD_beef
+b = 1000000
+c = -12_34_567
+d = +100
+e = 1_000.000_1
+f = 0o7_777
+g = 0b1111_0000
+h = [0xff, 1234]
+"#;
+
+    let expected = r#"a = 0xDEAD_BEEF
+b = 1_000_000
+c = -1_234_567
+d = +100
+e = 1_000.000_1
+f = 0o7_777
+g = 0b1111_0000
+h = [0xFF, 1_234]
+"#;
+
+    let options = formatter::Options {
+        hex_case: formatter::HexCase::Upper,
+        integer_grouping: formatter::IntegerGrouping::Thousands,
+        ..Default::default()
+    };
+
+    let formatted = formatter::format(src, options.clone());
+    assert_format!(expected, &formatted);
+
+    let lowercase = formatter::format(
+        src,
+        formatter::Options {
+            hex_case: formatter::HexCase::Lower,
+            ..Default::default()
+        },
+    );
+    assert!(lowercase.starts_with("a = 0xdead_beef\nb = 1000000\n"));
+    assert!(lowercase.contains("h = [0xff, 1234]"));
+
+    let original = crate::parser::parse(src).into_dom();
+
+    for formatted in [&formatted, &lowercase] {
+        let dom = crate::parser::parse(formatted).into_dom();
+
+        for key in ["a", "b", "c", "d"] {
+            assert_eq!(
+                dom.get(key).as_integer().unwrap().value(),
+                original.get(key).as_integer().unwrap().value()
+            );
+        }
+
+        for idx in 0..2 {
+            assert_eq!(
+                dom.get("h").get(idx).as_integer().unwrap().value(),
+                original.get("h").get(idx).as_integer().unwrap().value()
+            );
+        }
+    }
+
+    let reformatted = formatter::format(&formatted, options);
+    assert_format!(expected, &reformatted);
+}
+
+#[test]
+fn normalize_integers_from_str() {
+    let mut options = formatter::Options::default();
+
+    options
+        .update_from_str([("hex_case", "lower"), ("integer_grouping", "thousands")].into_iter())
+        .unwrap();
+    assert_eq!(options.hex_case, formatter::HexCase::Lower);
+    assert_eq!(
+        options.integer_grouping,
+        formatter::IntegerGrouping::Thousands
+    );
+
+    assert!(options
+        .update_from_str([("hex_case", "mixed")].into_iter())
+        .is_err());
+}
+
+#[test]
+fn datetime_style() {
+    let src = r#"a = 2021-01-01t10:00:00z
+b = 2021-01-01 10:00:00Z
+c = 2021-01-01T10:00:00.123456+02:00
+d = 2021-01-01 10:00:00,5-05:30
+e = 2021-01-01t10:00:00.5
+f = 2021-01-01
+g = 10:00:00.25
+h = [2021-01-01T10:00:00z, 1979-05-27 07:32:00]
+"#;
+
+    let t_uppercase = r#"a = 2021-01-01T10:00:00Z
+b = 2021-01-01T10:00:00Z
+c = 2021-01-01T10:00:00.123456+02:00
+d = 2021-01-01T10:00:00,5-05:30
+e = 2021-01-01T10:00:00.5
+f = 2021-01-01
+g = 10:00:00.25
+h = [2021-01-01T10:00:00Z, 1979-05-27T07:32:00]
+"#;
+
+    let space = r#"a = 2021-01-01 10:00:00Z
+b = 2021-01-01 10:00:00Z
+c = 2021-01-01 10:00:00.123456+02:00
+d = 2021-01-01 10:00:00,5-05:30
+e = 2021-01-01 10:00:00.5
+f = 2021-01-01
+g = 10:00:00.25
+h = [2021-01-01 10:00:00Z, 1979-05-27 07:32:00]
+"#;
+
+    let original = crate::parser::parse(src).into_dom();
+
+    for (style, expected) in [
+        (formatter::DateTimeStyle::TUppercase, t_uppercase),
+        (formatter::DateTimeStyle::Space, space),
+        (formatter::DateTimeStyle::Keep, src),
+    ] {
+        let options = formatter::Options {
+            datetime_style: style,
+            ..Default::default()
+        };
+
+        let formatted = formatter::format(src, options.clone());
+        assert_format!(expected, &formatted);
+
+        let reformatted = formatter::format(&formatted, options);
+        assert_format!(expected, &reformatted);
+
+        let dom = crate::parser::parse(&formatted).into_dom();
+
+        for key in ["a", "b", "c", "d", "e", "f", "g"] {
+            assert_eq!(
+                dom.get(key).as_date().unwrap().value(),
+                original.get(key).as_date().unwrap().value()
+            );
+        }
+
+        for idx in 0..2 {
+            assert_eq!(
+                dom.get("h").get(idx).as_date().unwrap().value(),
+                original.get("h").get(idx).as_date().unwrap().value()
+            );
+        }
+    }
+}
+
+#[test]
+fn datetime_style_from_str() {
+    let mut options = formatter::Options::default();
+
+    options
+        .update_from_str([("datetime_style", "space")].into_iter())
+        .unwrap();
+    assert_eq!(options.datetime_style, formatter::DateTimeStyle::Space);
+
+    options
+        .update_from_str([("datetime_style", "t_uppercase")].into_iter())
+        .unwrap();
+    assert_eq!(options.datetime_style, formatter::DateTimeStyle::TUppercase);
+
+    assert!(options
+        .update_from_str([("datetime_style", "lowercase")].into_iter())
+        .is_err());
+}
+
+#[test]
+fn indent_tables_multiline_strings() {
+    let src = r#"[a]
+script = '''
+  #!/bin/sh
+	echo "tab"
+    exit 0
+'''
+
+[a.b]
+cert = """
+  -----BEGIN CERTIFICATE-----
+    MIIB
+  -----END CERTIFICATE-----
+"""
+scripts = ['''
+  one
+''', """
+    two"""]
+inline = { s = '''
+   three
+''' }
+"#;
+
+    let expected = r#"[a]
+  script = '''
+  #!/bin/sh
+	echo "tab"
+    exit 0
+'''
+
+  [a.b]
+    cert = """
+  -----BEGIN CERTIFICATE-----
+    MIIB
+  -----END CERTIFICATE-----
+"""
+    scripts = ['''
+  one
+''', """
+    two"""]
+    inline = { s = '''
+   three
+''' }
+"#;
+
+    let options = formatter::Options {
+        indent_tables: true,
+        indent_entries: true,
+        ..Default::default()
+    };
+
+    let formatted = formatter::format(src, options.clone());
+    assert_format!(expected, &formatted);
+
+    let reformatted = formatter::format(&formatted, options);
+    assert_format!(expected, &reformatted);
+
+    let strings = |src: &str| {
+        let dom = crate::parser::parse(src).into_dom();
+        let b = dom.get("a").get("b");
+
+        [
+            dom.get("a").get("script"),
+            b.get("cert"),
+            b.get("scripts").get(0),
+            b.get("scripts").get(1),
+            b.get("inline").get("s"),
+        ]
+        .map(|node| node.as_str().unwrap().value().to_string())
+    };
+
+    assert_eq!(strings(src), strings(&formatted));
+}
+
+#[test]
+fn reorder_keys_with_comments() {
+    let src = r#"# Section comment
+
+# Documents c.
+c = 3
+# Documents b.
+# On two lines.
+b = 2
+a = 1 # about a
+
+[table]
+z = "z"
+# Documents y.
+y = "y" # about y
+x = "x" # last
+
+# Anchored comment
+
+w = "w"
+# Documents v.
+v = "v"
+# Dangling comment
+
+u = "u"
+"#;
+
+    let expected = r#"# Section comment
+
+a = 1 # about a
+# Documents b.
+# On two lines.
+b = 2
+# Documents c.
+c = 3
+
+[table]
+x = "x" # last
+# Documents y.
+y = "y" # about y
+z = "z"
+
+# Anchored comment
+
+# Documents v.
+v = "v"
+w = "w"
+# Dangling comment
+
+u = "u"
+"#;
+
+    let options = formatter::Options {
+        reorder_keys: true,
+        ..Default::default()
+    };
+
+    let formatted = formatter::format(src, options.clone());
+    assert_format!(expected, &formatted);
+
+    let reformatted = formatter::format(&formatted, options);
+    assert_format!(expected, &reformatted);
+}
+
+#[test]
+fn reorder_keys_with_comments_aligned() {
+    let src = r#"[table]
+ccc = 3 # c
+# Documents bb.
+bb = 2 # bb
+a = 1 # a
+"#;
+
+    let expected = r#"[table]
+a = 1 # a
+# Documents bb.
+bb  = 2 # bb
+ccc = 3 # c
+"#;
+
+    let options = formatter::Options {
+        reorder_keys: true,
+        align_entries: true,
+        ..Default::default()
+    };
+
+    let formatted = formatter::format(src, options);
+    assert_format!(expected, &formatted);
+}
+
+#[test]
+fn format_range_reordered_entries_with_comments() {
+    let src = "# c\nc=3\n# b\nb=|2\na=1\n\nz=0\n";
+
+    assert_format!(
+        "a = 1\n# b\nb = 2\n# c\nc = 3\n\nz=0\n",
+        &format_range(
+            src,
+            formatter::Options {
+                reorder_keys: true,
+                ..Default::default()
+            }
+        )
+        .unwrap()
+    );
+}
+
+#[test]
+fn reorder_tables() {
+    let src = r#"# Root comment
+name = "taplo"
+
+# About z.
+[z]
+a = 1
+
+[[bin]]
+name = "first"
+[bin.meta]
+k = 1
+
+[b]
+c = 2 # trailing
+[[bin]]
+name = "second"
+
+# About a.sub.
+[a.sub]
+x = 1
+[a]
+y = 2
+"#;
+
+    let expected = r#"# Root comment
+name = "taplo"
+
+[a]
+y = 2
+
+# About a.sub.
+[a.sub]
+x = 1
+
+[b]
+c = 2 # trailing
+[[bin]]
+name = "first"
+[bin.meta]
+k = 1
+
+[[bin]]
+name = "second"
+# About z.
+[z]
+a = 1
+"#;
+
+    let options = formatter::Options {
+        reorder_tables: true,
+        ..Default::default()
+    };
+
+    let formatted = formatter::format(src, options.clone());
+    assert_format!(expected, &formatted);
+
+    let reformatted = formatter::format(&formatted, options);
+    assert_format!(expected, &reformatted);
+
+    assert_eq!(
+        serde_json::to_value(crate::parser::parse(src).into_dom()).unwrap(),
+        serde_json::to_value(crate::parser::parse(&formatted).into_dom()).unwrap()
+    );
+}
+
+#[test]
+fn reorder_tables_indent_tables() {
+    let src = r#"[b.x]
+c = 1
+
+[a]
+b = 2
+
+[b]
+d = 3
+
+[a.y]
+e = 4
+"#;
+
+    let expected = r#"[a]
+b = 2
+
+  [a.y]
+  e = 4
+
+[b]
+d = 3
+
+  [b.x]
+  c = 1
+"#;
+
+    let options = formatter::Options {
+        reorder_tables: true,
+        indent_tables: true,
+        ..Default::default()
+    };
+
+    let formatted = formatter::format(src, options.clone());
+    assert_format!(expected, &formatted);
+
+    let reformatted = formatter::format(&formatted, options);
+    assert_format!(expected, &reformatted);
+}
+
+#[test]
+fn reorder_tables_separated_array_sub_tables() {
+    // `[a.b]` belongs to the first element of `a`,
+    // it would belong to the second one after sorting.
+    let src = r#"[[a]]
+x = 1
+
+[z]
+y = 2
+
+[a.b]
+c = 3
+
+[[a]]
+x = 2
+"#;
+
+    let formatted = formatter::format(
+        src,
+        formatter::Options {
+            reorder_tables: true,
+            ..Default::default()
+        },
+    );
+    assert_format!(src, &formatted);
+}
+
+#[test]
+fn reorder_keys_does_not_move_headers() {
+    let src = r#"b = 1
+a = 2
+
+[z]
+d = 1
+c = 2
+
+[[y]]
+f = 1
+e = 2
+
+[x]
+h = 1
+"#;
+
+    let expected = r#"a = 2
+b = 1
+
+[z]
+c = 2
+d = 1
+
+[[y]]
+e = 2
+f = 1
+
+[x]
+h = 1
+"#;
+
+    let formatted = formatter::format(
+        src,
+        formatter::Options {
+            reorder_keys: true,
+            ..Default::default()
+        },
+    );
+    assert_format!(expected, &formatted);
+}
+
+#[test]
+fn format_range_reordered_tables() {
+    let src = "[b]\nx=|1\n\n[a]\ny=2\n";
+
+    assert_format!(
+        "[a]\ny = 2\n\n[b]\nx = 1\n",
+        &format_range(
+            src,
+            formatter::Options {
+                reorder_tables: true,
+                ..Default::default()
+            }
+        )
+        .unwrap()
+    );
+}
+
+#[test]
+fn check_formatted() {
+    let options = formatter::Options::default();
+
+    let result = formatter::check("a = 1\nb = [1, 2]\n", options.clone());
+    assert!(result.is_formatted());
+    assert_eq!(result.formatted, "a = 1\nb = [1, 2]\n");
+
+    let src = "a = 1\nb=[1,2]\nc = 3\n";
+    let result = formatter::check(src, options);
+    assert!(!result.is_formatted());
+    assert_eq!(
+        result
+            .edits
+            .iter()
+            .map(|(range, text)| (&src[*range], text.as_str()))
+            .collect::<Vec<_>>(),
+        [("b=[1,2]\n", "b = [1, 2]\n")]
+    );
+    assert_eq!(apply_edits(src, &result.edits), result.formatted);
+}
+
+/// Options that exercise most of the formatter.
+fn idempotency_options() -> Vec<formatter::Options> {
+    let default = formatter::Options::default();
+
+    vec![
+        default.clone(),
+        formatter::Options {
+            indent_tables: true,
+            indent_entries: true,
+            align_entries: true,
+            align_comments: true,
+            ..default.clone()
+        },
+        formatter::Options {
+            reorder_keys: true,
+            reorder_arrays: true,
+            reorder_tables: true,
+            array_layout: formatter::ArrayLayout::Preserve,
+            sort_string_arrays: vec!["*".into(), "*.*".into(), "*.*.*".into()],
+            ..default.clone()
+        },
+        formatter::Options {
+            column_width: 20,
+            array_auto_collapse: false,
+            compact_arrays: false,
+            compact_inline_tables: true,
+            compact_entries: true,
+            allowed_blank_lines: 0,
+            table_spacing: Some(1),
+            ..default.clone()
+        },
+        formatter::Options {
+            column_width: 40,
+            convert_long_inline_tables: true,
+            prefer_dotted_keys_for_single_entries: true,
+            expand_dotted_keys: true,
+            max_dotted_key_depth: 2,
+            ..default.clone()
+        },
+        formatter::Options {
+            indent_tables: true,
+            reflow_multiline_string_indent: true,
+            crlf: true,
+            hex_case: formatter::HexCase::Upper,
+            integer_grouping: formatter::IntegerGrouping::Thousands,
+            datetime_style: formatter::DateTimeStyle::Space,
+            ..default
+        },
+    ]
+}
+
+#[test]
+fn format_is_idempotent() {
+    for (name, src) in &super::corpus() {
+        for options in idempotency_options() {
+            let formatted = formatter::format(src, options.clone());
+            let result = formatter::check(&formatted, options.clone());
+
+            assert!(
+                result.is_formatted(),
+                "{name} is not formatted idempotently with {options:#?}:\n{:#?}",
+                result.edits
+            );
+        }
+    }
+}
+
+#[test]
+fn long_entry_with_trailing_comment() {
+    let src = "key = \"a long value\" # comment\nkey2 = 1_2_3_4_5 # comment\n";
+
+    let formatted = formatter::format(
+        src,
+        formatter::Options {
+            column_width: 20,
+            ..Default::default()
+        },
+    );
+    assert_format!(src, &formatted);
+}
+
+#[test]
+fn entries_before_invalid_syntax() {
+    let src = "a = 1\nb = @@\nc = [1, 2\n[table]\nd = { e = }\nf = \"ok\"\n";
+
+    let formatted = formatter::format(src, formatter::Options::default());
+    assert_format!(src, &formatted);
+
+    let result = formatter::check(&formatted, formatter::Options::default());
+    assert!(result.is_formatted());
+}
+
+#[test]
+fn options_from_str() {
+    let mut options = formatter::Options::default();
+
+    options
+        .update_from_str([
+            ("align_entries", "true"),
+            ("column_width", "100"),
+            ("indent_string", "\t"),
+        ])
+        .unwrap();
+    assert!(options.align_entries);
+    assert_eq!(options.column_width, 100);
+    assert_eq!(options.indent_string, "\t");
+
+    match options.update_from_str([("unknown_option", "true")]) {
+        Err(formatter::OptionParseError::InvalidOption(key)) => {
+            assert_eq!(key, "unknown_option");
+        }
+        result => panic!("expected an invalid option, got {result:?}"),
+    }
+
+    match options.update_from_str([("align_comments", "yes")]) {
+        Err(formatter::OptionParseError::InvalidValue { key, .. }) => {
+            assert_eq!(key, "align_comments");
+        }
+        result => panic!("expected an invalid value, got {result:?}"),
+    }
+
+    for value in ["-1", "1.5", "", "many"] {
+        match options.update_from_str([("column_width", value)]) {
+            Err(formatter::OptionParseError::InvalidValue { key, .. }) => {
+                assert_eq!(key, "column_width");
+            }
+            result => panic!("expected an invalid value for {value:?}, got {result:?}"),
+        }
+    }
+
+    // The invalid options leave the previous values.
+    assert_eq!(
+        options.align_comments,
+        formatter::Options::default().align_comments
+    );
+    assert_eq!(options.column_width, 100);
+}
+
+#[test]
+fn array_layout_preserve() {
+    let src = r#"features = [
+    "std",   "alloc","serde",
+  "derive", "rc",    "unstable",
+    # Platform specific.
+    "unix", "windows" ,  # comment
+    "wasm"
+]
+short = [
+  1, 2,
+  3
+]
+long = ["aaaaaaaaaaaaaaaaaaaa", "bbbbbbbbbbbbbbbbbbbbbbbbb", "cccccccccccccccccccccccccccc", "dddd"]
+"#;
+
+    let expected = r#"features = [
+  "std", "alloc", "serde",
+  "derive", "rc", "unstable",
+  # Platform specific.
+  "unix", "windows", # comment
+  "wasm",
+]
+short = [
+  1, 2,
+  3,
+]
+long = ["aaaaaaaaaaaaaaaaaaaa", "bbbbbbbbbbbbbbbbbbbbbbbbb", "cccccccccccccccccccccccccccc", "dddd"]
+"#;
+
+    let options = formatter::Options {
+        array_layout: formatter::ArrayLayout::Preserve,
+        ..Default::default()
+    };
+
+    let formatted = formatter::format(src, options.clone());
+    assert_format!(expected, &formatted);
+
+    let reformatted = formatter::format(&formatted, options);
+    assert_format!(expected, &reformatted);
+}
+
+#[test]
+fn array_layout_preserve_reorder() {
+    let src = r#"features = [
+  "serde", "alloc", "std",
+  "rc", "derive",
+]
+"#;
+
+    let expected = r#"features = [
+  "alloc", "serde", "std",
+  "derive", "rc",
+]
+"#;
+
+    let formatted = formatter::format(
+        src,
+        formatter::Options {
+            array_layout: formatter::ArrayLayout::Preserve,
+            reorder_arrays: true,
+            ..Default::default()
+        },
+    );
+    assert_format!(expected, &formatted);
+}
+
+#[test]
+fn expand_arrays_paths() {
+    let src = r#"[workspace]
+members = ["crates/a", "crates/b"]
+exclude = ["crates/a", "crates/b"]
+
+[package]
+keywords = ["toml", "formatter"]
+metadata = { keywords = ["toml", "formatter"] }
+"#;
+
+    let expected = r#"[workspace]
+members = [
+  "crates/a",
+  "crates/b",
+]
+exclude = ["crates/a", "crates/b"]
+
+[package]
+keywords = [
+  "toml",
+  "formatter",
+]
+metadata = { keywords = ["toml", "formatter"] }
+"#;
+
+    let options = formatter::Options {
+        expand_arrays_paths: vec!["workspace.members".into(), "*.keywords".into()],
+        ..Default::default()
+    };
+
+    let formatted = formatter::format(src, options.clone());
+    assert_format!(expected, &formatted);
+
+    let reformatted = formatter::format(&formatted, options.clone());
+    assert_format!(expected, &reformatted);
+
+    let formatted = formatter::format(
+        src,
+        formatter::Options {
+            array_trailing_comma: false,
+            ..options
+        },
+    );
+    assert!(formatted.contains("members = [\n  \"crates/a\",\n  \"crates/b\"\n]"));
+}
+
+#[test]
+fn expand_arrays_paths_from_str() {
+    let mut options = formatter::Options::default();
+
+    options
+        .update_from_str([("expand_arrays_paths", "workspace.members, *.keywords")])
+        .unwrap();
+    assert_eq!(
+        options.expand_arrays_paths,
+        ["workspace.members", "*.keywords"]
+    );
+
+    options
+        .update_from_str([("expand_arrays_paths", "")])
+        .unwrap();
+    assert!(options.expand_arrays_paths.is_empty());
+
+    assert!(options
+        .update_from_str([("expand_arrays_paths", "workspace..members")])
+        .is_err());
+}
+
+#[test]
+fn sort_string_arrays() {
+    // The strings are compared bytewise by their unescaped values, so uppercase
+    // letters come before lowercase ones and non-ASCII characters come last.
+    // Equal values keep their order, the escaped "é" stays before the literal one.
+    let src = r#"[package]
+keywords = ["zebra", 'apple', "\u00e9clair", "Zulu", "ap\u0072icot", "éclair"]
+mixed = ["b", 1, "a"]
+authors = ["b", "a"]
+"#;
+
+    let expected = r#"[package]
+keywords = ["Zulu", 'apple', "ap\u0072icot", "zebra", "\u00e9clair", "éclair"]
+mixed = ["b", 1, "a"]
+authors = ["b", "a"]
+"#;
+
+    let options = formatter::Options {
+        sort_string_arrays: vec!["*.keywords".into(), "package.mixed".into()],
+        ..Default::default()
+    };
+
+    let formatted = formatter::format(src, options.clone());
+    assert_format!(expected, &formatted);
+
+    let result = formatter::check(src, options);
+    assert_eq!(result.issues.len(), 1);
+    assert_eq!(&src[result.issues[0].range], r#"["b", 1, "a"]"#,);
+}
+
+#[test]
+fn sort_string_arrays_with_comments() {
+    let src = r#"members = [
+  # The command line interface.
+  "crates/cli", # binary
+  "crates/b",
+
+  "crates/z",
+  # Trailing.
+  "crates/a",
+  # Dangling.
+]
+"#;
+
+    let expected = r#"members = [
+  "crates/b",
+  # The command line interface.
+  "crates/cli", # binary
+
+  # Trailing.
+  "crates/a",
+  "crates/z",
+  # Dangling.
+]
+"#;
+
+    let options = formatter::Options {
+        sort_string_arrays: vec!["members".into()],
+        ..Default::default()
+    };
+
+    let formatted = formatter::format(src, options.clone());
+    assert_format!(expected, &formatted);
+
+    let reformatted = formatter::format(&formatted, options);
+    assert_format!(expected, &reformatted);
+}
+
+#[test]
+fn sort_string_arrays_from_str() {
+    let mut options = formatter::Options::default();
+
+    options
+        .update_from_str([("sort_string_arrays", "workspace.members, *.keywords")])
+        .unwrap();
+    assert_eq!(
+        options.sort_string_arrays,
+        ["workspace.members", "*.keywords"]
+    );
+
+    assert!(options
+        .update_from_str([("sort_string_arrays", "workspace..members")])
+        .is_err());
+}
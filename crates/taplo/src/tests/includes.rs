@@ -0,0 +1,211 @@
+use crate::{
+    analyze::{resolve_includes, IncludeError, IncludeOptions, MergedDom},
+    dom::{Keys, Node},
+    parser::parse,
+};
+use serde_json::json;
+
+fn loader<'f>(files: &'f [(&str, &str)]) -> impl Fn(&str) -> Result<String, String> + 'f {
+    move |path| {
+        files
+            .iter()
+            .find(|(p, _)| *p == path)
+            .map(|(_, src)| src.to_string())
+            .ok_or_else(|| String::from("file not found"))
+    }
+}
+
+fn resolve(root_path: &str, files: &[(&str, &str)]) -> Result<MergedDom, IncludeError<String>> {
+    let src = files.iter().find(|(p, _)| *p == root_path).unwrap().1;
+    let options = IncludeOptions {
+        root_path: root_path.into(),
+        ..Default::default()
+    };
+
+    resolve_includes(parse(src).into_dom(), &loader(files), &options)
+}
+
+fn source_of<'m>(merged: &'m MergedDom, keys: &str) -> &'m str {
+    let keys: Keys = keys.parse().unwrap();
+    &merged.source_of(&keys).unwrap().path
+}
+
+fn to_json(node: &Node) -> serde_json::Value {
+    serde_json::to_value(node).unwrap()
+}
+
+#[test]
+fn merge_with_provenance() {
+    let merged = resolve(
+        "config/main.toml",
+        &[
+            (
+                "config/main.toml",
+                r#"
+include = ["base.toml", "../shared/extra.toml"]
+name = "main"
+
+[server]
+port = 8080
+"#,
+            ),
+            (
+                "config/base.toml",
+                r#"
+name = "base"
+tags = ["a", "b"]
+
+[server]
+host = "localhost"
+port = 80
+"#,
+            ),
+            (
+                "shared/extra.toml",
+                r#"
+tags = ["c"]
+
+[[plugins]]
+name = "extra"
+"#,
+            ),
+        ],
+    )
+    .unwrap();
+
+    assert_eq!(
+        to_json(merged.root()),
+        json!({
+            "name": "main",
+            "tags": ["c"],
+            "server": { "host": "localhost", "port": 8080 },
+            "plugins": [{ "name": "extra" }],
+        })
+    );
+
+    let paths: Vec<_> = merged.files().iter().map(|f| f.path.as_str()).collect();
+    assert_eq!(
+        paths,
+        ["config/main.toml", "config/base.toml", "shared/extra.toml"]
+    );
+
+    assert_eq!(source_of(&merged, "name"), "config/main.toml");
+    assert_eq!(source_of(&merged, "server"), "config/main.toml");
+    assert_eq!(source_of(&merged, "server.port"), "config/main.toml");
+    assert_eq!(source_of(&merged, "server.host"), "config/base.toml");
+    assert_eq!(source_of(&merged, "tags"), "shared/extra.toml");
+    assert_eq!(source_of(&merged, "plugins.0.name"), "shared/extra.toml");
+}
+
+#[test]
+fn replaced_table_loses_provenance() {
+    let merged = resolve(
+        "main.toml",
+        &[
+            ("main.toml", "include = ['base.toml']\nserver = 'none'"),
+            ("base.toml", "[server]\nhost = 'localhost'"),
+        ],
+    )
+    .unwrap();
+
+    assert_eq!(to_json(merged.root()), json!({ "server": "none" }));
+    assert_eq!(source_of(&merged, "server.host"), "main.toml");
+}
+
+#[test]
+fn nested_and_shared_includes() {
+    let merged = resolve(
+        "main.toml",
+        &[
+            ("main.toml", "include = ['a/a.toml', 'b.toml']"),
+            ("a/a.toml", "include = ['../b.toml']\na = 1"),
+            ("b.toml", "b = 2"),
+        ],
+    )
+    .unwrap();
+
+    assert_eq!(to_json(merged.root()), json!({ "a": 1, "b": 2 }));
+    assert_eq!(merged.files().len(), 3);
+    assert_eq!(source_of(&merged, "b"), "b.toml");
+}
+
+#[test]
+fn include_cycle() {
+    let err = resolve(
+        "main.toml",
+        &[
+            ("main.toml", "include = ['a.toml']"),
+            ("a.toml", "include = ['b.toml']"),
+            ("b.toml", "include = ['./a.toml']"),
+        ],
+    )
+    .unwrap_err();
+
+    match err {
+        IncludeError::Cycle { cycle } => assert_eq!(cycle, ["a.toml", "b.toml", "a.toml"]),
+        err => panic!("unexpected error: {err}"),
+    }
+}
+
+#[test]
+fn self_include() {
+    let err = resolve("main.toml", &[("main.toml", "include = ['main.toml']")]).unwrap_err();
+    assert!(matches!(err, IncludeError::Cycle { cycle } if cycle.len() == 2));
+}
+
+#[test]
+fn missing_file() {
+    let err = resolve("main.toml", &[("main.toml", "include = ['missing.toml']")]).unwrap_err();
+
+    match err {
+        IncludeError::Load { path, error } => {
+            assert_eq!(path, "missing.toml");
+            assert_eq!(error, "file not found");
+        }
+        err => panic!("unexpected error: {err}"),
+    }
+}
+
+#[test]
+fn invalid_include() {
+    let src = "include = ['a.toml', 2]";
+    let err = resolve("main.toml", &[("main.toml", src), ("a.toml", "")]).unwrap_err();
+
+    match err {
+        IncludeError::InvalidInclude { path, range, .. } => {
+            assert_eq!(path, "main.toml");
+            assert_eq!(&src[range.unwrap()], "2");
+        }
+        err => panic!("unexpected error: {err}"),
+    }
+
+    let err = resolve("main.toml", &[("main.toml", "include = 'a.toml'")]).unwrap_err();
+    assert!(matches!(err, IncludeError::InvalidInclude { .. }));
+}
+
+#[test]
+fn syntax_errors_in_included_file() {
+    let err = resolve(
+        "main.toml",
+        &[("main.toml", "include = ['a.toml']"), ("a.toml", "a = ")],
+    )
+    .unwrap_err();
+
+    assert!(
+        matches!(err, IncludeError::Syntax { path, errors } if path == "a.toml" && !errors.is_empty())
+    );
+}
+
+#[test]
+fn nested_too_deep() {
+    let files: Vec<(String, String)> = (0..40)
+        .map(|i| (format!("{i}.toml"), format!("include = ['{}.toml']", i + 1)))
+        .collect();
+    let files: Vec<(&str, &str)> = files
+        .iter()
+        .map(|(p, s)| (p.as_str(), s.as_str()))
+        .collect();
+
+    let err = resolve("0.toml", &files).unwrap_err();
+    assert!(matches!(err, IncludeError::TooDeep { .. }));
+}
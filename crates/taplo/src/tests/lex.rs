@@ -0,0 +1,80 @@
+use super::corpus;
+use crate::{
+    parser::{lex, parse},
+    syntax::SyntaxKind::*,
+};
+use rowan::{NodeOrToken, TextSize};
+use std::collections::BTreeSet;
+
+/// Asserts that the lexer is lossless and that every token boundary
+/// is also a token boundary in the syntax tree.
+fn assert_lex_agrees(name: &str, src: &str) {
+    let tokens: Vec<_> = lex(src).collect();
+
+    let text: String = tokens.iter().map(|(_, range)| &src[*range]).collect();
+    assert_eq!(text, src, "{name}");
+
+    let mut offset = TextSize::from(0);
+    for (kind, range) in &tokens {
+        assert!(kind.is_token(), "{name}: {kind:?}");
+        assert_eq!(range.start(), offset, "{name}");
+        offset = range.end();
+    }
+
+    let mut tree_boundaries = BTreeSet::new();
+    for element in parse(src).into_syntax().descendants_with_tokens() {
+        match element {
+            NodeOrToken::Node(n) => assert!(!n.kind().is_token(), "{name}: {:?}", n.kind()),
+            NodeOrToken::Token(t) => {
+                assert!(t.kind().is_token(), "{name}: {:?}", t.kind());
+                tree_boundaries.insert(t.text_range().start());
+                tree_boundaries.insert(t.text_range().end());
+            }
+        }
+    }
+
+    for (kind, range) in tokens {
+        assert!(
+            tree_boundaries.contains(&range.start()) && tree_boundaries.contains(&range.end()),
+            "{name}: {kind:?} at {range:?} is not a token in the syntax tree"
+        );
+    }
+}
+
+#[test]
+fn lex_agrees_with_parse_on_corpus() {
+    for (name, src) in &corpus() {
+        assert_lex_agrees(name, src);
+    }
+}
+
+#[test]
+fn lex_agrees_with_parse() {
+    for src in [
+        "",
+        "\u{FEFF}a = 1\n",
+        "1.2 = true\n3.14159 = \"pi\"",
+        "a = \"unterminated\nb = 'also\n",
+        "s = \"\"\"\nmulti\n[table]\n",
+        "[a.b] # header\n[[c]]\nd = { e = [1, 2.5, 0x1f] }\n",
+        "a = 1\r\rb = 2 @@ §\n",
+        "x = 1979-05-27T07:32:00Z\ny = 07:32:00\nz = nan\n",
+    ] {
+        assert_lex_agrees(&format!("{src:?}"), src);
+    }
+}
+
+#[test]
+fn lex_bom() {
+    let tokens: Vec<_> = lex("\u{FEFF}a").collect();
+    assert_eq!(
+        tokens,
+        [
+            (BOM, rowan::TextRange::new(0.into(), 3.into())),
+            (IDENT, rowan::TextRange::new(3.into(), 4.into()))
+        ]
+    );
+
+    // Only a leading byte order mark is a `BOM` token.
+    assert!(lex("a\u{FEFF}").all(|(kind, _)| kind != BOM));
+}
@@ -1,10 +1,63 @@
-use crate::parser::parse;
+use crate::parser::{parse, parse_multi};
+use rowan::TextRange;
+use std::path::Path;
 
 mod generated {
     mod invalid;
 }
 
+mod debug;
+mod dom;
+mod empty_keys;
 mod formatter;
+mod includes;
+mod lex;
+mod recovery;
+mod reparse;
+#[cfg(feature = "schema")]
+mod schema_gen;
+mod strings;
+mod syntax_errors;
+
+/// The TOML documents in the test data as `(path, source)`.
+fn corpus() -> Vec<(String, String)> {
+    fn visit(dir: &Path, sources: &mut Vec<(String, String)>) {
+        for entry in std::fs::read_dir(dir).unwrap() {
+            let path = entry.unwrap().path();
+
+            if path.is_dir() {
+                visit(&path, sources);
+            } else if path.extension().is_some_and(|ext| ext == "toml") {
+                // Some of the invalid documents are not valid UTF-8.
+                if let Ok(src) = std::fs::read_to_string(&path) {
+                    sources.push((path.display().to_string(), src));
+                }
+            }
+        }
+    }
+
+    let mut sources = Vec::new();
+    visit(
+        &Path::new(env!("CARGO_MANIFEST_DIR")).join("../../test-data"),
+        &mut sources,
+    );
+    assert!(!sources.is_empty());
+    sources
+}
+
+/// A small deterministic pseudo-random number generator (xorshift),
+/// so that failures of randomized tests are reproducible.
+struct Rng(u64);
+
+impl Rng {
+    /// The next number in `0..max`.
+    fn next(&mut self, max: usize) -> usize {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        (self.0 % max as u64) as usize
+    }
+}
 
 #[test]
 fn time_in_arrays() {
@@ -27,3 +80,280 @@ fn comments_after_tables() {
 
     assert!(errors.is_empty(), "{:#?}", errors);
 }
+
+#[test]
+fn section_ranges_with_comments() {
+    use crate::util::syntax::section_ranges;
+
+    fn sections(src: &str) -> Vec<&str> {
+        section_ranges(&parse(src).into_syntax())
+            .into_iter()
+            .map(|(_, range)| &src[range])
+            .collect()
+    }
+
+    // Comments directly before a header document that header.
+    let src = r#"[a]
+x = 1
+# docs of b
+# more docs of b
+[b]
+y = 2
+"#;
+    assert_eq!(sections(src), ["[a]\nx = 1", "[b]\ny = 2"]);
+
+    // Separated comments stay with the previous section.
+    let src = r#"[a]
+x = 1
+# comment of a
+
+# docs of b
+[b]
+y = 2
+"#;
+    assert_eq!(sections(src), ["[a]\nx = 1\n# comment of a", "[b]\ny = 2"]);
+
+    // Comments at the end of the document belong to the last section.
+    let src = r#"[a]
+x = 1
+# end of a
+"#;
+    assert_eq!(sections(src), ["[a]\nx = 1\n# end of a"]);
+}
+
+#[test]
+fn unfixable_long_lines() {
+    use crate::util::syntax::unfixable_long_lines;
+
+    let url = format!("https://example.com/{}", "a".repeat(100));
+    let key = "k".repeat(100);
+    let items = (0..50)
+        .map(|i| i.to_string())
+        .collect::<Vec<_>>()
+        .join(", ");
+
+    let src = format!(
+        r#"
+url = "{url}"
+{key} = 1
+arr = [{items}]
+short = "value"
+text = """
+{url}
+"""
+"#
+    );
+
+    let root = crate::parser::parse(&src).into_syntax();
+    let lines = unfixable_long_lines(&root, 80)
+        .into_iter()
+        .map(|r| &src[r])
+        .collect::<Vec<_>>();
+
+    assert_eq!(lines, [format!(r#"url = "{url}""#), format!("{key} = 1")]);
+}
+
+#[test]
+fn parse_multiple_documents() {
+    let src = "a = 1\n+++\nb = \"\"\"\n+++\n\"\"\"\n+++  \r\nc = \n";
+
+    let docs = parse_multi(src, "+++");
+    assert_eq!(docs.len(), 3);
+
+    let chunks: Vec<&str> = docs
+        .iter()
+        .map(|(range, _)| &src[std::ops::Range::<usize>::from(*range)])
+        .collect();
+    assert_eq!(chunks, ["a = 1\n", "b = \"\"\"\n+++\n\"\"\"\n", "c = \n"]);
+
+    assert!(docs[0].1.errors.is_empty());
+    assert!(docs[1].1.errors.is_empty());
+
+    let dom = docs[1].1.clone().into_dom();
+    assert_eq!(dom.get("b").as_str().unwrap().value(), "+++\n");
+
+    // Errors point into the original source.
+    let errors = &docs[2].1.errors;
+    assert!(!errors.is_empty());
+    assert!(errors.iter().all(|err| docs[2].0.contains_range(err.range)));
+}
+
+#[test]
+fn parse_multiple_documents_without_separator() {
+    let docs = parse_multi("a = 1\n", "+++");
+    assert_eq!(docs.len(), 1);
+    assert_eq!(docs[0].0.len(), 6.into());
+
+    let docs = parse_multi("+++\na = 1\n+++", "+++");
+    let lens: Vec<u32> = docs.iter().map(|(range, _)| range.len().into()).collect();
+    assert_eq!(lens, [0, 6, 0]);
+}
+
+#[test]
+fn entry_ranges() {
+    use crate::{
+        syntax::SyntaxKind::ENTRY,
+        util::syntax::{entry_is_single_line, entry_key_range, entry_value_range},
+    };
+
+    let src = r#"a = 1 # comment
+"b c" . d = [
+  1,
+  2,
+]
+e = """
+multi"""
+f = """single"""
+g = { h = [1, 2] }
+i = @@ x
+j =
+"#;
+
+    let entries: Vec<_> = parse(src)
+        .into_syntax()
+        .children()
+        .filter(|n| n.kind() == ENTRY)
+        .collect();
+
+    let text = |range: Option<rowan::TextRange>| range.map(|r| &src[r]);
+
+    let keys: Vec<_> = entries.iter().map(|e| text(entry_key_range(e))).collect();
+    assert_eq!(
+        keys,
+        [
+            Some("a"),
+            Some(r#""b c" . d"#),
+            Some("e"),
+            Some("f"),
+            Some("g"),
+            Some("i"),
+            Some("j")
+        ]
+    );
+
+    let values: Vec<_> = entries.iter().map(|e| text(entry_value_range(e))).collect();
+    assert_eq!(
+        values,
+        [
+            Some("1"),
+            Some("[\n  1,\n  2,\n]"),
+            Some("\"\"\"\nmulti\"\"\""),
+            Some(r#""""single""""#),
+            Some("{ h = [1, 2] }"),
+            Some("@@ x"),
+            None
+        ]
+    );
+
+    let single_line: Vec<_> = entries.iter().map(entry_is_single_line).collect();
+    assert_eq!(single_line, [true, false, false, true, true, true, true]);
+}
+
+#[test]
+fn parse_value_fragments() {
+    use crate::parser::parse_value;
+
+    for valid in [
+        "1",
+        " true ",
+        "\"str\" # comment",
+        "[1, [2, 3]]",
+        "{ version = \"1.0\", features = [\"derive\"] }",
+        "1979-05-27T07:32:00Z",
+        "\"\"\"\nmulti\nline\"\"\"",
+    ] {
+        let p = parse_value(valid);
+        assert!(p.errors.is_empty(), "{valid}: {:?}", p.errors);
+        assert_eq!(
+            p.clone().into_syntax().kind(),
+            crate::syntax::SyntaxKind::VALUE
+        );
+        assert_eq!(p.clone().into_syntax().to_string(), valid);
+        assert!(p.into_dom().validate().is_ok(), "{valid}");
+    }
+
+    for invalid in ["", "1 2", "1,", "a = 1", "[1", "{ a = 1 } x", "1\n2"] {
+        let p = parse_value(invalid);
+        assert!(!p.errors.is_empty(), "{invalid}");
+        assert_eq!(p.into_syntax().to_string(), invalid);
+    }
+
+    let p = parse_value("[1] 2 3");
+    assert_eq!(p.errors.len(), 1);
+    assert_eq!(p.errors[0].range, TextRange::new(4.into(), 7.into()));
+}
+
+#[test]
+fn parse_key_fragments() {
+    use crate::{
+        dom::{FromSyntax, Keys},
+        parser::parse_key,
+    };
+
+    let p = parse_key(r#"target."cfg(windows)".dependencies"#);
+    assert!(p.errors.is_empty(), "{:?}", p.errors);
+
+    let keys = Keys::from_syntax(p.into_syntax().into());
+    assert_eq!(
+        keys.iter()
+            .map(|k| k.as_key().unwrap().value())
+            .collect::<Vec<_>>(),
+        ["target", "cfg(windows)", "dependencies"]
+    );
+
+    for invalid in ["", "a.", "a b", "a = 1", "a.*", "[a]"] {
+        let p = parse_key(invalid);
+        assert!(!p.errors.is_empty(), "{invalid}");
+        assert_eq!(p.into_syntax().to_string(), invalid);
+    }
+}
+
+#[test]
+fn byte_order_mark() {
+    use crate::{
+        dom::node::DomNode,
+        parser::{parse_bytes, EncodingError},
+    };
+
+    let src = "\u{FEFF}a = 1\n[b]\nc = 2\n";
+    let p = parse(src);
+    assert!(p.errors.is_empty(), "{:?}", p.errors);
+    assert!(p.has_bom());
+    assert!(!parse("a = 1").has_bom());
+
+    let syntax = p.clone().into_syntax();
+    assert_eq!(syntax.to_string(), src);
+    assert_eq!(
+        syntax.first_token().unwrap().text_range(),
+        TextRange::new(0.into(), 3.into())
+    );
+
+    let dom = p.into_dom();
+    assert!(dom.validate().is_ok());
+    assert_eq!(
+        dom.get("a").syntax().unwrap().text_range(),
+        TextRange::new(7.into(), 8.into())
+    );
+
+    // Only a leading byte order mark is allowed.
+    assert!(!parse("a = 1\n\u{FEFF}b = 2").errors.is_empty());
+
+    assert!(parse_bytes(src.as_bytes()).unwrap().has_bom());
+
+    for (bytes, encoding) in [
+        (&b"\xFF\xFEa\0"[..], "UTF-16 (little-endian)"),
+        (b"\xFE\xFF\0a", "UTF-16 (big-endian)"),
+        (b"\xFF\xFE\0\0a\0\0\0", "UTF-32 (little-endian)"),
+        (b"\0\0\xFE\xFF\0\0\0a", "UTF-32 (big-endian)"),
+    ] {
+        assert_eq!(
+            parse_bytes(bytes).unwrap_err(),
+            EncodingError::UnsupportedEncoding(encoding)
+        );
+    }
+
+    assert_eq!(
+        parse_bytes(b"a = 1\nb = \"\xC3\x28\"").unwrap_err(),
+        EncodingError::InvalidUtf8 { offset: 11 }
+    );
+}
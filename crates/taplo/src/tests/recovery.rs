@@ -0,0 +1,305 @@
+use crate::{
+    dom::{
+        node::{DomNode, Key},
+        FromSyntax, Node,
+    },
+    parser::parse,
+    syntax::{SyntaxKind::*, SyntaxNode},
+};
+use std::{collections::HashMap, ops::Range};
+
+const CORPUS: &str = include_str!("../../../../test-data/recovery.toml");
+
+/// A single-line entry in the corpus.
+struct Entry {
+    lines: Range<usize>,
+    /// The last key of the entry and the text of its value.
+    key_value: (String, String),
+}
+
+fn line_of(src: &str, offset: usize) -> usize {
+    src[..offset].matches('\n').count()
+}
+
+fn is_header_line(line: &str) -> bool {
+    line.starts_with('[')
+}
+
+/// The entries that are in the source with their line ranges,
+/// along with the line ranges of all the top-level nodes.
+fn top_level_items(src: &str, root: &SyntaxNode) -> (Vec<Entry>, Vec<Range<usize>>) {
+    let mut entries = Vec::new();
+    let mut items = Vec::new();
+
+    for node in root.children() {
+        let range = node.text_range();
+        let start = line_of(src, range.start().into());
+        let end = line_of(src, range.end().into()) + 1;
+        items.push(start..end);
+
+        if node.kind() != ENTRY || end - start != 1 {
+            continue;
+        }
+
+        let key = node.first_child().unwrap();
+        let last_key = key
+            .children_with_tokens()
+            .filter(|t| t.kind() == IDENT)
+            .last()
+            .unwrap();
+        let value = key.next_sibling().unwrap();
+
+        let key = Key::from_syntax(last_key);
+        let value = Node::from_syntax(value.into());
+
+        entries.push(Entry {
+            lines: start..end,
+            key_value: (key.value().to_string(), value_text(&value)),
+        });
+    }
+
+    (entries, items)
+}
+
+fn value_text(node: &Node) -> String {
+    node.syntax().map(ToString::to_string).unwrap_or_default()
+}
+
+/// Count all the entries in the DOM by their key and value text,
+/// including conflicting ones.
+fn collect_dom_entries(node: &Node, found: &mut HashMap<(String, String), usize>) {
+    match node {
+        Node::Table(table) => {
+            for (key, value) in table.entries().read().iter() {
+                *found
+                    .entry((key.value().to_string(), value_text(value)))
+                    .or_default() += 1;
+                collect_dom_entries(value, found);
+            }
+        }
+        Node::Array(array) => {
+            for item in array.items().read().iter() {
+                collect_dom_entries(item, found);
+            }
+        }
+        _ => {}
+    }
+}
+
+fn keys(src: &str) -> Vec<String> {
+    parse(src)
+        .into_dom()
+        .flat_iter()
+        .map(|(keys, _)| keys.dotted().to_string())
+        .collect()
+}
+
+#[test]
+fn skip_invalid_entry_line() {
+    let p = parse("a = 1 2 3 = 4\nb = 2\n");
+    assert_eq!(p.errors.len(), 1);
+    assert!(p.errors[0].skipped());
+    assert_eq!(keys("a = 1 2 3 = 4\nb = 2\n"), ["a", "b"]);
+
+    assert_eq!(keys("a = \"abc\nb = 2\n"), ["a", "b"]);
+    assert_eq!(keys("a = 'abc\nb = 2\n"), ["a", "b"]);
+}
+
+#[test]
+fn skip_invalid_header() {
+    let src = "[a b c]\nx = 1\n[[d e]] # comment\ny = 2\n";
+    let p = parse(src);
+    assert_eq!(p.errors.len(), 2);
+    assert_eq!(keys(src), ["a", "a.x", "d", "d.0", "d.0.y"]);
+}
+
+#[test]
+fn entries_after_invalid_table_array_header() {
+    // Whitespace must be skipped again after the header,
+    // or every entry after it would be reported as invalid.
+    for (src, errors) in [
+        (
+            "[[\nb = 1\n",
+            &[(2, "expected identifier"), (3, r#"expected "]]""#)][..],
+        ),
+        ("[[a\nb = 1\n", &[(3, r#"expected "." or "]""#)]),
+        (
+            "[[a.]\nb = 1\n",
+            &[(4, "expected identifier"), (5, r#"expected "]]""#)],
+        ),
+        ("[[a] x\nb = 1\n", &[(4, r#"expected "]]""#)]),
+    ] {
+        let p = parse(src);
+        let actual = p
+            .errors
+            .iter()
+            .map(|e| (usize::from(e.range.start()), e.message.as_str()))
+            .collect::<Vec<_>>();
+        assert_eq!(actual, errors, "{src:?}");
+    }
+}
+
+#[test]
+fn unterminated_array() {
+    assert_eq!(keys("a = [1, 2\nb = 3\n"), ["a", "a.0", "a.1", "b"]);
+    assert_eq!(
+        keys("a = [[1, 2], [3\n\n[t]\nb = 3\n"),
+        ["a", "a.0", "a.0.0", "a.0.1", "a.1", "a.1.0", "t", "t.b"]
+    );
+    assert!(keys("a = { b = [1, 2 }\nc = 3\n").contains(&"c".to_string()));
+
+    // Valid arrays with items that look like keys or headers.
+    for src in ["a = [\n[1]\n]", "a = [\n[\"b\"]\n]", "a = [\n\"b = 1\",\n]"] {
+        assert!(parse(src).errors.is_empty(), "{src}");
+    }
+}
+
+#[test]
+fn unterminated_multi_line_string() {
+    for quotes in [r#"""""#, "'''"] {
+        let src = format!("a = {quotes}abc\nb = 1\n\n[t]\nc = 2\n");
+        assert_eq!(keys(&src), ["a", "t", "t.c"]);
+
+        // The closing delimiter is missing, the next opening one
+        // would close the string otherwise.
+        let src = format!("a = {quotes}abc\n[t]\nb = {quotes}\ndef\n{quotes}\n");
+        assert_eq!(keys(&src), ["a", "t", "t.b"]);
+    }
+
+    // Headers are allowed in terminated strings.
+    let src = "a = \"\"\"\n[t]\nb = 1\n\"\"\"\n";
+    assert!(parse(src).errors.is_empty());
+    assert_eq!(keys(src), ["a"]);
+}
+
+/// Delete every character of a valid document one by one,
+/// the entries on the lines that were not touched must remain in the DOM.
+#[test]
+fn delete_any_character() {
+    let p = parse(CORPUS);
+    assert!(p.errors.is_empty(), "{:?}", p.errors);
+    let root = p.into_syntax();
+    assert!(CORPUS.lines().count() >= 500);
+
+    let (entries, items) = top_level_items(CORPUS, &root);
+    let lines = CORPUS.lines().collect::<Vec<_>>();
+
+    // Deleting a character from the body of a comment or a string
+    // leaves the structure intact, only the delimiters and escapes
+    // are interesting.
+    let mut skipped = vec![false; CORPUS.len()];
+    for token in root
+        .descendants_with_tokens()
+        .filter_map(|t| t.into_token())
+    {
+        let start = usize::from(token.text_range().start());
+        let text = token.text();
+
+        match token.kind() {
+            COMMENT => skipped[start + 1..start + text.len()].fill(true),
+            STRING | MULTI_LINE_STRING | STRING_LITERAL | MULTI_LINE_STRING_LITERAL => {
+                let mut escaped = false;
+                for (i, c) in text.char_indices() {
+                    skipped[start + i] = !matches!(c, '"' | '\'' | '\\') && !escaped;
+                    escaped = c == '\\' && !escaped;
+                }
+            }
+            _ => {}
+        }
+    }
+
+    let mut previous = None;
+    let positions = CORPUS
+        .char_indices()
+        // Deleting any character of a run gives the same document.
+        .filter(|&(offset, c)| previous.replace(c) != Some(c) && !skipped[offset])
+        .collect::<Vec<_>>();
+
+    let threads = std::thread::available_parallelism().map_or(1, usize::from);
+    let mut failures = std::thread::scope(|scope| {
+        positions
+            .chunks(positions.len().div_ceil(threads))
+            .map(|chunk| {
+                scope.spawn(|| {
+                    chunk
+                        .iter()
+                        .flat_map(|&(offset, c)| {
+                            missing_entries(offset, c, &entries, &items, &lines)
+                        })
+                        .collect::<Vec<_>>()
+                })
+            })
+            .collect::<Vec<_>>()
+            .into_iter()
+            .flat_map(|handle| handle.join().unwrap())
+            .collect::<Vec<_>>()
+    });
+
+    let count = failures.len();
+    failures.dedup();
+    assert!(
+        failures.is_empty(),
+        "{count} missing entries:\n{}",
+        failures[..failures.len().min(50)].join("\n")
+    );
+}
+
+/// The entries outside of the affected lines that are
+/// missing from the DOM after deleting the given character.
+fn missing_entries(
+    offset: usize,
+    c: char,
+    entries: &[Entry],
+    items: &[Range<usize>],
+    lines: &[&str],
+) -> Vec<String> {
+    let mut missing = Vec::new();
+
+    let mut src = CORPUS.to_string();
+    src.remove(offset);
+
+    let line = line_of(CORPUS, offset);
+
+    // The lines of the items that contain the deleted character,
+    // if a newline was deleted, the lines before and after are affected.
+    let mut affected = line..(line + 1 + usize::from(c == '\n')).min(lines.len());
+    for item in items {
+        if item.contains(&line) || (c == '\n' && item.contains(&(line + 1))) {
+            affected.start = affected.start.min(item.start);
+            affected.end = affected.end.max(item.end);
+        }
+    }
+
+    // Broken multi-line strings can consume everything until the next table header.
+    let changed_line = src.lines().nth(line).unwrap_or_default();
+    let multi_line_string = (affected.start..affected.end)
+        .any(|l| lines[l].contains("\"\"\"") || lines[l].contains("'''"))
+        || changed_line.contains("\"\"\"")
+        || changed_line.contains("'''");
+    if multi_line_string {
+        while affected.end < lines.len() && !is_header_line(lines[affected.end]) {
+            affected.end += 1;
+        }
+    }
+
+    let dom = parse(&src).into_dom();
+    let mut found = HashMap::new();
+    collect_dom_entries(&dom, &mut found);
+
+    for entry in entries.iter() {
+        if entry.lines.start < affected.end && affected.start < entry.lines.end {
+            continue;
+        }
+
+        if !found.contains_key(&entry.key_value) {
+            missing.push(format!(
+                "deleting {c:?} at line {} removed {:?} at line {}",
+                line + 1,
+                entry.key_value,
+                entry.lines.start + 1
+            ));
+        }
+    }
+
+    missing
+}
@@ -0,0 +1,147 @@
+use super::Rng;
+use crate::parser::{parse, Parse};
+use rowan::{TextRange, TextSize};
+
+const SOURCE: &str = r#"
+# comment
+title = "TOML Example" # trailing comment
+'literal key' = 'value'
+a.b.c = 1_000
+
+[owner]
+name = "Tom Preston-Werner"
+dob = 1979-05-27T07:32:00-08:00
+  indented = [
+    1,
+    2, # two
+  ]
+
+[database]
+enabled = true
+ports = [ 8000, 8001, 8002 ]
+data = [ ["delta", "phi"], [3.14] ]
+temp_targets = { cpu = 79.5, case = 72.0 }
+text = """
+multi
+line"""
+raw = '''raw
+text'''
+
+[[products]]
+name = "Hammer"
+sku = 738594937
+
+[[products]]
+color = "gray"
+"#;
+
+const INSERTIONS: &[&str] = &[
+    "",
+    "1",
+    "a",
+    "x = 2",
+    " ",
+    "\n",
+    "\r\n",
+    "\r",
+    "\"",
+    "'",
+    "\"\"\"",
+    "'''",
+    "[",
+    "]",
+    "[[",
+    "{",
+    "}",
+    "=",
+    ".",
+    ",",
+    "#",
+    "é",
+    "\\",
+    "\t",
+    "1979-05-27",
+];
+
+fn sorted_errors(p: &Parse) -> Vec<(TextRange, String)> {
+    let mut errors: Vec<_> = p
+        .errors
+        .iter()
+        .map(|e| (e.range, e.message.clone()))
+        .collect();
+    errors.sort_by_key(|(range, message)| (range.start(), range.end(), message.clone()));
+    errors
+}
+
+fn char_boundary(source: &str, mut offset: usize) -> usize {
+    while !source.is_char_boundary(offset) {
+        offset -= 1;
+    }
+    offset
+}
+
+#[test]
+fn reparse_random_edits() {
+    for seed in 1..=20u64 {
+        let mut rng = Rng(seed.wrapping_mul(0x9E37_79B9_7F4A_7C15));
+        let mut source = SOURCE.to_string();
+        let mut p = parse(&source);
+
+        for _ in 0..200 {
+            let start = char_boundary(&source, rng.next(source.len() + 1));
+            let end = char_boundary(&source, (start + rng.next(6)).min(source.len()));
+            let insertion = INSERTIONS[rng.next(INSERTIONS.len())];
+
+            let range = TextRange::new(
+                TextSize::try_from(start).unwrap(),
+                TextSize::try_from(end).unwrap(),
+            );
+
+            source.replace_range(start..end, insertion);
+            p = p.update(range, insertion);
+
+            let expected = parse(&source);
+
+            assert_eq!(p.clone().into_syntax().to_string(), source, "seed {seed}");
+            assert_eq!(
+                format!("{:#?}", p.clone().into_syntax()),
+                format!("{:#?}", expected.clone().into_syntax()),
+                "seed {seed}, source:\n{source}"
+            );
+            assert_eq!(
+                sorted_errors(&p),
+                sorted_errors(&expected),
+                "seed {seed}, source:\n{source}"
+            );
+
+            // Keep the document from growing or shrinking too much.
+            if source.len() > SOURCE.len() * 2 || source.len() < SOURCE.len() / 2 {
+                source = SOURCE.to_string();
+                p = parse(&source);
+            }
+        }
+    }
+}
+
+#[test]
+fn reparse_typing_in_entry() {
+    let mut source = String::from("[table]\na = \nb = [\n  2,\n]\n");
+    let mut p = parse(&source);
+
+    for (i, c) in "\"hello \\u00e9 world\" # comment".char_indices() {
+        let offset = TextSize::try_from(12 + i).unwrap();
+        let text = c.to_string();
+
+        source.insert_str(12 + i, &text);
+        p = p.update(TextRange::empty(offset), &text);
+
+        let expected = parse(&source);
+        assert_eq!(
+            format!("{:#?}", p.clone().into_syntax()),
+            format!("{:#?}", expected.clone().into_syntax())
+        );
+        assert_eq!(sorted_errors(&p), sorted_errors(&expected), "{source}");
+    }
+
+    assert!(p.errors.is_empty(), "{:?}", p.errors);
+}
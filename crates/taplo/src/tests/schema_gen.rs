@@ -0,0 +1,65 @@
+use crate::{parser::parse, schema_gen};
+use serde_json::json;
+use std::path::Path;
+
+/// Compares the schemas generated from the documents in `test-data/schema-gen`
+/// with the `.schema.json` files next to them.
+///
+/// Run with `UPDATE_GOLDEN=1` to write the current output instead.
+#[test]
+fn schema_golden_files() {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("../../test-data/schema-gen");
+    let update = std::env::var_os("UPDATE_GOLDEN").is_some();
+
+    let mut fixtures: Vec<_> = std::fs::read_dir(&dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "toml"))
+        .collect();
+    fixtures.sort();
+    assert!(!fixtures.is_empty());
+
+    for fixture in fixtures {
+        let src = std::fs::read_to_string(&fixture).unwrap();
+        let schema = schema_gen::from_dom(
+            &parse(&src).into_dom(),
+            &schema_gen::Options {
+                title: fixture
+                    .file_stem()
+                    .map(|stem| stem.to_string_lossy().into_owned()),
+            },
+        );
+        let schema = serde_json::to_value(&schema).unwrap();
+
+        let golden = fixture.with_extension("schema.json");
+        if update {
+            let mut json = serde_json::to_string_pretty(&schema).unwrap();
+            json.push('\n');
+            std::fs::write(&golden, json).unwrap();
+            continue;
+        }
+
+        let expected: serde_json::Value =
+            serde_json::from_str(&std::fs::read_to_string(&golden).unwrap()).unwrap();
+        assert_eq!(schema, expected, "{}", fixture.display());
+    }
+}
+
+#[test]
+fn empty_and_conflicting_values() {
+    let dom = parse("a = []\nb = [[], [1]]\nc = [{ d = 1 }, { d = 'x', e = true }]").into_dom();
+    let schema = serde_json::to_value(schema_gen::from_dom(&dom, &Default::default())).unwrap();
+
+    assert_eq!(schema["properties"]["a"], json!({ "type": "array" }));
+    assert_eq!(
+        schema["properties"]["b"],
+        json!({ "type": "array", "items": { "type": "array", "items": { "type": "integer" } } })
+    );
+    assert_eq!(
+        schema["properties"]["c"]["items"]["properties"],
+        json!({
+            "d": { "anyOf": [{ "type": "integer" }, { "type": "string" }] },
+            "e": { "type": "boolean" }
+        })
+    );
+}
@@ -0,0 +1,238 @@
+use crate::{
+    parser::parse,
+    util::{
+        check_escape, escape, preferred_string_kind, quote_string, unescape, StringKind,
+        UnescapeError, UnescapeErrorKind,
+    },
+};
+
+const WINDOWS_PATHS: &[&str] = &[
+    r"C:\Users\x",
+    r"C:\",
+    r"\\server\share\dir",
+    r"C:\Program Files (x86)\app\bin",
+    r"D:\a\b\c\d\e\f.txt",
+    r"C:\Users\x\it's",
+    r#"C:\Users\"quoted"\x"#,
+    "C:\\Users\\tab\there",
+    "C:\\Users\\new\nline",
+];
+
+/// Parse a single `value = ...` entry and return the string content.
+fn string_value(toml: &str) -> String {
+    let dom = parse(&format!("value = {toml}")).into_dom();
+    assert!(dom.validate().is_ok(), "invalid string {toml}");
+    dom.get("value").as_str().unwrap().value().to_string()
+}
+
+#[test]
+fn preferred_string_kinds() {
+    assert_eq!(preferred_string_kind(r"C:\Users\x"), StringKind::Literal);
+    assert_eq!(preferred_string_kind("C:\\tab\t"), StringKind::Literal);
+    assert_eq!(preferred_string_kind("no backslash"), StringKind::Basic);
+    assert_eq!(preferred_string_kind(r"C:\Users\x\it's"), StringKind::Basic);
+    assert_eq!(preferred_string_kind("C:\\new\nline"), StringKind::Basic);
+
+    assert_eq!(quote_string(r"C:\Users\x"), r"'C:\Users\x'");
+    assert_eq!(quote_string(r"it's"), r#""it's""#);
+}
+
+#[test]
+fn windows_paths_round_trip() {
+    for path in WINDOWS_PATHS {
+        // Escaped basic strings.
+        assert_eq!(string_value(&format!(r#""{}""#, escape(path))), *path);
+
+        // The preferred quoting.
+        assert_eq!(string_value(&quote_string(path)), *path);
+    }
+
+    // Literal strings from source.
+    assert_eq!(string_value(r"'C:\Users\x'"), r"C:\Users\x");
+    assert_eq!(string_value(r#""C:\\Users\\x""#), r"C:\Users\x");
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn windows_paths_to_toml() {
+    for path in WINDOWS_PATHS {
+        let node: crate::dom::Node =
+            serde_json::from_value(serde_json::json!({ "value": path })).unwrap();
+        let toml = node.to_toml(false, false);
+
+        let expected = quote_string(path);
+        assert_eq!(toml.trim(), format!("value = {expected}"));
+
+        let reparsed = parse(&toml).into_dom();
+        assert_eq!(reparsed.get("value").as_str().unwrap().value(), *path);
+    }
+}
+
+#[test]
+fn unicode_escapes() {
+    assert_eq!(unescape(r"\U0001F600").unwrap(), "\u{1F600}");
+    assert_eq!(unescape(r"\u00E9\U0010FFFF").unwrap(), "\u{E9}\u{10FFFF}");
+    assert_eq!(string_value(r#""\U0001F600""#), "\u{1F600}");
+
+    for (s, offset, len, kind) in [
+        (r"\uD800", 0, 6, UnescapeErrorKind::Surrogate(0xD800)),
+        (r"ab\uDFFF", 2, 6, UnescapeErrorKind::Surrogate(0xDFFF)),
+        (r"\U0000D800", 0, 10, UnescapeErrorKind::Surrogate(0xD800)),
+        (
+            r"\U00110000",
+            0,
+            10,
+            UnescapeErrorKind::OutOfRange(0x110000),
+        ),
+        (
+            r"\UFFFFFFFF",
+            0,
+            10,
+            UnescapeErrorKind::OutOfRange(0xFFFFFFFF),
+        ),
+        (r"\u12G4", 0, 4, UnescapeErrorKind::InvalidHexDigit),
+        (r"\u12", 0, 4, UnescapeErrorKind::InvalidHexDigit),
+        (r"\U1234567", 0, 9, UnescapeErrorKind::InvalidHexDigit),
+        (r"x\q", 1, 2, UnescapeErrorKind::Unknown),
+    ] {
+        let err = UnescapeError { offset, len, kind };
+        assert_eq!(unescape(s), Err(err), "{s}");
+        assert_eq!(check_escape(s), Err(vec![err]), "{s}");
+    }
+
+    // All invalid escapes are reported, valid ones in between are skipped.
+    let errors = check_escape(r"\uD800 \u0041 \U00110000").unwrap_err();
+    assert_eq!(
+        errors
+            .iter()
+            .map(|e| (e.offset, e.kind))
+            .collect::<Vec<_>>(),
+        [
+            (0, UnescapeErrorKind::Surrogate(0xD800)),
+            (14, UnescapeErrorKind::OutOfRange(0x110000))
+        ]
+    );
+}
+
+#[test]
+fn unicode_escape_errors() {
+    let src = "a = \"\\uD800\"\nb = \"\"\"\n\\U00110000\"\"\"\n";
+    let errors = parse(src).errors;
+
+    let errors: Vec<_> = errors
+        .iter()
+        .map(|e| (&src[e.range], e.message.as_str()))
+        .collect();
+    assert_eq!(
+        errors,
+        [
+            (
+                r"\uD800",
+                "unicode escape sequence is a surrogate code point (U+D800)"
+            ),
+            (
+                r"\U00110000",
+                "unicode escape sequence is out of range (U+110000)"
+            ),
+        ]
+    );
+
+    let dom = parse("a = \"\"\"\nok\\U00110000\"\"\"").into_dom();
+    match dom.validate().unwrap_err().collect::<Vec<_>>().as_slice() {
+        [crate::dom::Error::InvalidEscapeSequence { range, error, .. }] => {
+            assert_eq!(*range, rowan::TextRange::new(10.into(), 20.into()));
+            assert_eq!(error.kind, UnescapeErrorKind::OutOfRange(0x110000));
+        }
+        errors => panic!("unexpected errors: {errors:#?}"),
+    }
+}
+
+/// Multi-line strings from the examples of the TOML specification
+/// with their expected values.
+const SPEC_MULTI_LINE_STRINGS: &[(&str, &str)] = &[
+    (
+        "\"\"\"\nRoses are red\nViolets are blue\"\"\"",
+        "Roses are red\nViolets are blue",
+    ),
+    (
+        "\"\"\"\r\nRoses are red\r\nViolets are blue\"\"\"",
+        "Roses are red\r\nViolets are blue",
+    ),
+    (
+        "\"\"\"\nThe quick brown \\\n\n\n  fox jumps over \\\n    the lazy dog.\"\"\"",
+        "The quick brown fox jumps over the lazy dog.",
+    ),
+    (
+        "\"\"\"\\\n       The quick brown \\\n       fox jumps over \\\n       the lazy dog.\\\n       \"\"\"",
+        "The quick brown fox jumps over the lazy dog.",
+    ),
+    (
+        "\"\"\"\\\r\n  The quick brown \\\r\n\r\n  fox jumps over \\ \t\r\n  the lazy dog.\"\"\"",
+        "The quick brown fox jumps over the lazy dog.",
+    ),
+    (
+        r#""""Here are two quotation marks: "". Simple enough.""""#,
+        r#"Here are two quotation marks: "". Simple enough."#,
+    ),
+    (
+        r#""""Here are three quotation marks: ""\".""""#,
+        r#"Here are three quotation marks: """."#,
+    ),
+    (
+        r#""""Here are fifteen quotation marks: ""\"""\"""\"""\"""\".""""#,
+        r#"Here are fifteen quotation marks: """""""""""""""."#,
+    ),
+    (
+        r#"""""This," she said, "is just a pointless statement."""""#,
+        r#""This," she said, "is just a pointless statement.""#,
+    ),
+    (
+        r"'''I [dw]on't need \d{2} apples'''",
+        r"I [dw]on't need \d{2} apples",
+    ),
+    (
+        "'''\nThe first newline is\ntrimmed in raw strings.\n   All other whitespace\n   is preserved.\n'''",
+        "The first newline is\ntrimmed in raw strings.\n   All other whitespace\n   is preserved.\n",
+    ),
+    (
+        "'''\r\nThe first newline is\r\ntrimmed in raw strings.'''",
+        "The first newline is\r\ntrimmed in raw strings.",
+    ),
+    (
+        r#"'''Here are fifteen quotation marks: """""""""""""""'''"#,
+        r#"Here are fifteen quotation marks: """"""""""""""""#,
+    ),
+    (
+        r"''''That,' she said, 'is still pointless.''''",
+        r"'That,' she said, 'is still pointless.'",
+    ),
+    // Line ending backslashes are not special in literal strings.
+    ("'''a \\\n  b'''", "a \\\n  b"),
+];
+
+#[test]
+fn spec_multi_line_strings() {
+    for (toml, expected) in SPEC_MULTI_LINE_STRINGS {
+        assert_eq!(string_value(toml), *expected, "{toml}");
+
+        // The specification allows normalizing line endings, `toml` does that.
+        let value: toml::Value = toml::from_str(&format!("value = {toml}")).unwrap();
+        assert_eq!(
+            value["value"].as_str(),
+            Some(&*expected.replace("\r\n", "\n")),
+            "{toml}"
+        );
+    }
+}
+
+#[test]
+fn line_ending_backslash() {
+    assert_eq!(unescape("a \\\n   b").unwrap(), "a b");
+    assert_eq!(unescape("a \\ \t\r\n \n\r\n\tb").unwrap(), "a b");
+    assert_eq!(unescape("a\\\n").unwrap(), "a");
+
+    // Only whitespace is allowed between the backslash and the line ending.
+    assert!(unescape("a \\ b\n").is_err());
+    assert!(check_escape("a \\ b\n").is_err());
+    assert!(check_escape("a \\  \n  b").is_ok());
+}
@@ -0,0 +1,227 @@
+use crate::{
+    parser::{parse, Error},
+    syntax::SyntaxKind::{self, *},
+};
+use rowan::TextRange;
+
+const VALUE: &[SyntaxKind] = &[
+    STRING,
+    MULTI_LINE_STRING,
+    STRING_LITERAL,
+    MULTI_LINE_STRING_LITERAL,
+    INTEGER,
+    INTEGER_HEX,
+    INTEGER_OCT,
+    INTEGER_BIN,
+    FLOAT,
+    BOOL,
+    DATE_TIME_OFFSET,
+    DATE_TIME_LOCAL,
+    DATE,
+    TIME,
+    BRACKET_START,
+    BRACE_START,
+];
+
+const KEY: &[SyntaxKind] = &[IDENT, STRING, STRING_LITERAL];
+
+/// The first error that contains an expected set.
+fn first_expected(src: &str) -> Error {
+    let errors = parse(src).errors;
+    errors
+        .iter()
+        .find(|e| !e.expected().is_empty())
+        .cloned()
+        .unwrap_or_else(|| panic!("no expected tokens for {src:?}: {errors:?}"))
+}
+
+#[test]
+fn expected_tokens() {
+    let value_or_bracket = [VALUE, &[BRACKET_END]].concat();
+
+    let cases: &[(&str, &[SyntaxKind], &str)] = &[
+        ("[table\n", &[PERIOD, BRACKET_END], r#"expected "." or "]""#),
+        ("[[tables]\n", &[BRACKET_END], r#"expected "]]""#),
+        (
+            "[[tables\n",
+            &[PERIOD, BRACKET_END],
+            r#"expected "." or "]""#,
+        ),
+        ("a 1\n", &[PERIOD, EQ], r#"expected "." or "=""#),
+        ("a =\n", VALUE, "expected value"),
+        ("a = \n", VALUE, "expected value"),
+        ("a = 1979-13-45\n", VALUE, "expected value"),
+        ("a = 2021-01-01T\n", VALUE, "expected value"),
+        ("a = \"abc\n", VALUE, "expected value"),
+        ("a = 1 b = 2\n", &[NEWLINE], "expected new line"),
+        ("[a] b = 2\n", &[NEWLINE], "expected new line"),
+        ("a. = 1\n", KEY, "expected identifier"),
+        ("= 1\n", KEY, "expected identifier"),
+        (
+            "a = [1 2]\n",
+            &[COMMA, BRACKET_END],
+            r#"expected "," or "]""#,
+        ),
+        ("a = [1,", &value_or_bracket, "unexpected EOF"),
+        ("a = [,]\n", &value_or_bracket, r#"unexpected ",""#),
+        (
+            "a = { b = 1 c = 2 }\n",
+            &[COMMA, BRACE_END],
+            r#"expected "," or "}""#,
+        ),
+        ("a = { b = 1", &[COMMA, BRACE_END], r#"expected "," or "}""#),
+        (
+            "a = { b = 1\n}",
+            &[COMMA, BRACE_END],
+            "newline is not allowed in an inline table",
+        ),
+    ];
+
+    for (src, expected, message) in cases {
+        let error = first_expected(src);
+        assert_eq!(error.expected(), *expected, "{src:?}");
+        assert_eq!(error.message, *message, "{src:?}");
+    }
+}
+
+#[test]
+fn expected_message() {
+    let error = first_expected("a = [1 2]");
+    assert_eq!(error.expected_message().unwrap(), r#"expected "," or "]""#);
+
+    let error = first_expected("a = [1,");
+    assert_eq!(
+        error.expected_message().unwrap(),
+        r#"expected value or "]""#
+    );
+
+    let error = first_expected("a = { b = 1\n}");
+    assert_eq!(error.expected_message().unwrap(), r#"expected "," or "}""#);
+
+    let errors = parse("a = 01").errors;
+    assert_eq!(errors.len(), 1);
+    assert_eq!(errors[0].expected_message(), None);
+}
+
+#[test]
+fn skipped_tokens() {
+    // The invalid date is turned into an error token.
+    let errors = parse("a = 1979-13-45").errors;
+    assert_eq!(errors.len(), 1);
+    assert!(errors[0].skipped());
+
+    // The unterminated quote is skipped by the lexer.
+    let errors = parse("a = \"abc").errors;
+    assert_eq!(errors[0].message, "unexpected token");
+    assert!(errors[0].skipped());
+
+    // Nothing is skipped if the value is missing at the end of the line,
+    // the newline is needed to recover.
+    let errors = parse("a =\nb = 1").errors;
+    assert_eq!(errors.len(), 1);
+    assert!(!errors[0].skipped());
+
+    // The parser recovers at the next entry without skipping it.
+    let errors = parse("a = { b = 1 c = 2 }").errors;
+    assert_eq!(errors.len(), 1);
+    assert!(!errors[0].skipped());
+}
+
+#[test]
+fn control_characters() {
+    const FORBIDDEN: &[char] = &[
+        '\u{0}', '\u{1}', '\u{8}', '\u{b}', '\u{c}', '\u{1f}', '\u{7f}',
+    ];
+
+    // Tabs, line breaks in multi-line strings and C1 control characters are allowed.
+    let src = [
+        "# comment \u{1} and \u{7f}\t",
+        "basic = \"a\u{0}b\u{b}c\td\"",
+        "\"key \u{8}\" = 1",
+        "literal = 'é\u{1f}\u{85}'",
+        "multi = \"\"\"a\nb\u{c}\r\nc\"\"\"",
+        "multi_literal = '''a\u{7f}\nb\t'''",
+        "value = 1 # trailing \u{85} \u{0}",
+    ]
+    .join("\n");
+
+    let expected: Vec<_> = src
+        .char_indices()
+        .filter(|(_, c)| FORBIDDEN.contains(c))
+        .map(|(i, _)| TextRange::at((i as u32).into(), 1.into()))
+        .collect();
+    assert_eq!(expected.len(), 9);
+
+    let errors = parse(&src).errors;
+    assert!(errors
+        .iter()
+        .all(|e| e.message.starts_with("invalid control character")));
+    assert_eq!(errors.iter().map(|e| e.range).collect::<Vec<_>>(), expected);
+}
+
+#[test]
+fn bare_carriage_returns() {
+    let src = "a = 1\r\nb = 2\rc = 3\n[t]\r\r\nd = \"\"\"x\r\ny\"\"\" # e\rf = 4";
+
+    let errors = parse(src).errors;
+    assert!(errors
+        .iter()
+        .all(|e| e.message == r"bare carriage return, expected \n or \r\n"));
+
+    let bare: Vec<_> = src
+        .match_indices('\r')
+        .filter(|(i, _)| !src[i + 1..].starts_with('\n'))
+        .map(|(i, _)| TextRange::at((i as u32).into(), 1.into()))
+        .collect();
+    assert_eq!(bare.len(), 3);
+    assert_eq!(errors.iter().map(|e| e.range).collect::<Vec<_>>(), bare);
+
+    // Entries separated by a bare `\r` are still parsed separately.
+    let dom = parse(src).into_dom();
+    for key in ["a", "b", "c", "t.d", "t.f"] {
+        let keys: crate::dom::Keys = key.parse().unwrap();
+        assert_eq!(
+            dom.find_all_matches(keys, false).unwrap().count(),
+            1,
+            "{key}"
+        );
+    }
+}
+
+#[test]
+fn newlines_in_inline_tables() {
+    let src = "t = { a = 1, # one\n  b = [\n    2,\n  ],\n  c = 3 }\nd = 4\n";
+
+    // Newlines in values are allowed, the ones between entries are reported.
+    let errors = parse(src).errors;
+    let newlines: Vec<_> = src
+        .match_indices('\n')
+        .map(|(i, _)| TextRange::at((i as u32).into(), 1.into()))
+        .collect();
+    assert_eq!(
+        errors.iter().map(|e| e.range).collect::<Vec<_>>(),
+        [newlines[0], newlines[3]]
+    );
+    assert!(errors
+        .iter()
+        .all(|e| e.message == "newline is not allowed in an inline table"));
+
+    // The entries on the following lines are still in the table.
+    let dom = parse(src).into_dom();
+    assert_eq!(dom.get("t").as_table().unwrap().entries().read().len(), 3);
+    assert_eq!(
+        dom.get("d").as_integer().unwrap().value().as_positive(),
+        Some(4)
+    );
+
+    // An unclosed table ends at the newline, the following entries are kept.
+    let src = "t = { a = 1,\nb = 2\n[c]\nd = { e = 1 }\n";
+    let errors = parse(src).errors;
+    assert_eq!(errors.len(), 1);
+    assert_eq!(errors[0].range, TextRange::at(12.into(), 1.into()));
+
+    let dom = parse(src).into_dom();
+    assert_eq!(dom.get("t").as_table().unwrap().entries().read().len(), 1);
+    assert!(dom.get("b").is_integer());
+    assert!(dom.get("c").get("d").get("e").is_integer());
+}
@@ -0,0 +1,366 @@
+//! Bounded debug representations of syntax trees and DOM nodes,
+//! and a stable representation of the DOM for snapshot tests.
+//!
+//! Unlike the `{:#?}` output, these stop descending past a given depth
+//! and stop writing after a given length, so they are safe to use
+//! on arbitrarily large documents.
+
+use crate::{
+    dom::{
+        node::{DomNode, TableKind},
+        Node, Segment,
+    },
+    syntax::{SyntaxElement, SyntaxNode},
+};
+use rowan::NodeOrToken;
+use std::fmt::Write;
+
+/// Limits for the debug representations.
+#[derive(Debug, Clone, Copy)]
+pub struct DebugOptions {
+    /// Nodes deeper than this are omitted.
+    pub max_depth: usize,
+
+    /// The maximum length of the output in bytes.
+    pub max_length: usize,
+
+    /// Token texts and values longer than this
+    /// amount of characters are truncated.
+    pub max_text_length: usize,
+}
+
+impl Default for DebugOptions {
+    fn default() -> Self {
+        Self {
+            max_depth: 128,
+            max_length: 10 * 1024 * 1024,
+            max_text_length: 128,
+        }
+    }
+}
+
+/// A debug representation and whether anything was left out of it.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DebugString {
+    pub text: String,
+    pub truncated: bool,
+}
+
+/// The debug representation of a syntax tree,
+/// with one line for every node and token.
+///
+/// ```
+/// use taplo::util::debug::{syntax_debug_string, DebugOptions};
+///
+/// let syntax = taplo::parser::parse("a = 1").into_syntax();
+/// let debug = syntax_debug_string(&syntax, &DebugOptions::default());
+///
+/// assert!(debug.text.starts_with("ROOT@0..5\n  ENTRY@0..5\n"));
+/// assert!(!debug.truncated);
+/// ```
+pub fn syntax_debug_string(node: &SyntaxNode, options: &DebugOptions) -> DebugString {
+    let mut writer = DebugWriter::new(options);
+    write_syntax(&mut writer, &node.clone().into(), 0);
+    writer.finish()
+}
+
+fn write_syntax(writer: &mut DebugWriter, element: &SyntaxElement, depth: usize) -> bool {
+    match element {
+        NodeOrToken::Node(node) => {
+            if !writer.line(
+                depth,
+                format_args!("{:?}@{:?}", node.kind(), node.text_range()),
+            ) {
+                return false;
+            }
+
+            if node.first_child_or_token().is_none() {
+                return true;
+            }
+
+            if !writer.enter(depth + 1) {
+                return true;
+            }
+
+            node.children_with_tokens()
+                .all(|child| write_syntax(writer, &child, depth + 1))
+        }
+        NodeOrToken::Token(token) => {
+            let text = writer.text(token.text());
+            writer.line(
+                depth,
+                format_args!("{:?}@{:?} {text}", token.kind(), token.text_range()),
+            )
+        }
+    }
+}
+
+/// Options for [`Node::tree_string`].
+#[derive(Debug, Clone, Copy)]
+pub struct TreeStringOptions {
+    /// Include the text ranges of the nodes,
+    /// without them the output only changes if the DOM does.
+    pub offsets: bool,
+
+    /// Keys and string values longer than this
+    /// amount of characters are truncated.
+    pub max_text_length: usize,
+}
+
+impl Default for TreeStringOptions {
+    fn default() -> Self {
+        Self {
+            offsets: true,
+            max_text_length: DebugOptions::default().max_text_length,
+        }
+    }
+}
+
+impl Node {
+    /// A stable textual representation of the node and its descendants
+    /// that is suitable for snapshot tests.
+    ///
+    /// Every node is on its own line, indented by two spaces for every level:
+    ///
+    /// ```text
+    /// <path>: <kind>@<start>..<end> <value>
+    /// ```
+    ///
+    /// - `path` is the [`Path`](crate::dom::Path) of the node, it is
+    ///   omitted for the node itself.
+    /// - `kind` is one of `Table`, `InlineTable`, `PseudoTable`, `Array`,
+    ///   `ArrayOfTables`, `Bool`, `String`, `Integer`, `Float`, `Date`
+    ///   and `Invalid`.
+    /// - `@<start>..<end>` is the text range of the node in the source,
+    ///   it is omitted if [`TreeStringOptions::offsets`] is `false`
+    ///   or the node has no syntax.
+    /// - `value` is only written for values that are not tables or arrays,
+    ///   strings are quoted and escaped.
+    ///
+    /// Table entries and array items follow their parent
+    /// in the order they appear in the source. Keys and string
+    /// values are truncated and followed by `...` if they are too long.
+    ///
+    /// ```
+    /// use taplo::util::debug::TreeStringOptions;
+    ///
+    /// let dom = taplo::parser::parse("a = [true, \"x\"]").into_dom();
+    ///
+    /// assert_eq!(
+    ///     dom.tree_string(&TreeStringOptions::default()),
+    ///     "Table@0..15\n  a: Array@4..15\n    a[0]: Bool@5..9 true\n    a[1]: String@11..14 \"x\"\n"
+    /// );
+    ///
+    /// let options = TreeStringOptions {
+    ///     offsets: false,
+    ///     ..Default::default()
+    /// };
+    /// assert_eq!(
+    ///     dom.tree_string(&options),
+    ///     "Table\n  a: Array\n    a[0]: Bool true\n    a[1]: String \"x\"\n"
+    /// );
+    /// ```
+    pub fn tree_string(&self, options: &TreeStringOptions) -> String {
+        let limits = DebugOptions {
+            max_depth: usize::MAX,
+            max_length: usize::MAX,
+            max_text_length: options.max_text_length,
+        };
+
+        let mut writer = DebugWriter::new(&limits);
+        write_dom(&mut writer, options.offsets, None, self, 0);
+        writer.finish().text
+    }
+
+    /// The same representation as [`tree_string`](Node::tree_string)
+    /// with offsets, but limited by the given options.
+    ///
+    /// ```
+    /// use taplo::util::debug::DebugOptions;
+    ///
+    /// let dom = taplo::parser::parse("a = [true]").into_dom();
+    /// let debug = dom.debug_string(&DebugOptions::default());
+    ///
+    /// assert_eq!(
+    ///     debug.text,
+    ///     "Table@0..10\n  a: Array@4..10\n    a[0]: Bool@5..9 true\n"
+    /// );
+    /// ```
+    pub fn debug_string(&self, options: &DebugOptions) -> DebugString {
+        let mut writer = DebugWriter::new(options);
+        write_dom(&mut writer, true, None, self, 0);
+        writer.finish()
+    }
+}
+
+fn write_dom(
+    writer: &mut DebugWriter,
+    offsets: bool,
+    path: Option<&str>,
+    node: &Node,
+    depth: usize,
+) -> bool {
+    let label = path.map(|p| format!("{p}: ")).unwrap_or_default();
+
+    let range = node
+        .syntax()
+        .filter(|_| offsets)
+        .map(|s| format!("@{:?}", s.text_range()))
+        .unwrap_or_default();
+
+    let (kind, value) = match node {
+        Node::Table(t) => (
+            match t.kind() {
+                TableKind::Regular => "Table",
+                TableKind::Inline => "InlineTable",
+                TableKind::Pseudo => "PseudoTable",
+            },
+            String::new(),
+        ),
+        Node::Array(arr) => (
+            if arr.kind().is_tables() {
+                "ArrayOfTables"
+            } else {
+                "Array"
+            },
+            String::new(),
+        ),
+        Node::Bool(v) => ("Bool", format!(" {}", v.value())),
+        Node::Str(v) => ("String", format!(" {}", writer.text(v.value()))),
+        Node::Integer(v) => ("Integer", format!(" {}", v.value())),
+        Node::Float(v) => ("Float", format!(" {}", v.value())),
+        Node::Date(v) => ("Date", format!(" {}", v.value())),
+        Node::Invalid(_) => ("Invalid", String::new()),
+    };
+
+    if !writer.line(depth, format_args!("{label}{kind}{range}{value}")) {
+        return false;
+    }
+
+    let path = path.unwrap_or_default();
+
+    match node {
+        Node::Table(t) => {
+            let entries = t.entries().read();
+            if entries.is_empty() || !writer.enter(depth + 1) {
+                return true;
+            }
+
+            let completed = entries.iter().all(|(key, entry)| {
+                let key = key.value();
+                let truncated = writer.truncate(key);
+
+                let mut segment = Segment::Key(truncated.into()).to_string();
+                if truncated.len() < key.len() {
+                    segment += "...";
+                }
+
+                let child_path = if path.is_empty() {
+                    segment
+                } else {
+                    format!("{path}.{segment}")
+                };
+                write_dom(writer, offsets, Some(&child_path), entry, depth + 1)
+            });
+            completed
+        }
+        Node::Array(arr) => {
+            let items = arr.items().read();
+            if items.is_empty() || !writer.enter(depth + 1) {
+                return true;
+            }
+
+            let completed = items.iter().enumerate().all(|(idx, item)| {
+                let child_path = format!("{path}{}", Segment::Index(idx));
+                write_dom(writer, offsets, Some(&child_path), item, depth + 1)
+            });
+            completed
+        }
+        _ => true,
+    }
+}
+
+struct DebugWriter<'o> {
+    options: &'o DebugOptions,
+    out: String,
+    /// The maximum length was reached.
+    full: bool,
+    /// Something was left out.
+    truncated: bool,
+}
+
+impl<'o> DebugWriter<'o> {
+    fn new(options: &'o DebugOptions) -> Self {
+        Self {
+            options,
+            out: String::new(),
+            full: false,
+            truncated: false,
+        }
+    }
+
+    /// Write a line, returns `false` if the maximum length was reached.
+    fn line(&mut self, depth: usize, content: std::fmt::Arguments) -> bool {
+        if self.full {
+            return false;
+        }
+
+        let start = self.out.len();
+
+        for _ in 0..depth {
+            self.out += "  ";
+        }
+        let _ = self.out.write_fmt(content);
+        self.out += "\n";
+
+        if self.out.len() > self.options.max_length {
+            self.out.truncate(start);
+            self.full = true;
+            self.truncated = true;
+            return false;
+        }
+
+        true
+    }
+
+    /// Whether the children at the given depth should be written,
+    /// a placeholder is written instead if they are too deep.
+    fn enter(&mut self, depth: usize) -> bool {
+        if depth <= self.options.max_depth {
+            return true;
+        }
+
+        self.line(depth, format_args!("..."));
+        self.truncated = true;
+        false
+    }
+
+    /// Escape and truncate the given text.
+    fn text(&mut self, text: &str) -> String {
+        let truncated = self.truncate(text);
+
+        if truncated.len() < text.len() {
+            format!("{truncated:?}...")
+        } else {
+            format!("{text:?}")
+        }
+    }
+
+    /// Truncate the given text to the maximum text length.
+    fn truncate<'t>(&mut self, text: &'t str) -> &'t str {
+        match text.char_indices().nth(self.options.max_text_length) {
+            Some((idx, _)) => {
+                self.truncated = true;
+                &text[..idx]
+            }
+            None => text,
+        }
+    }
+
+    fn finish(self) -> DebugString {
+        DebugString {
+            text: self.out,
+            truncated: self.truncated,
+        }
+    }
+}
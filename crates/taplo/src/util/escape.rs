@@ -1,4 +1,5 @@
 use logos::{Lexer, Logos};
+use thiserror::Error;
 
 /// Escaping based on:
 ///
@@ -19,7 +20,9 @@ pub enum Escape {
     #[token(r#"\t"#)]
     Tab,
 
-    #[regex(r#"(\\\s*\n)|(\\\s*\r\n)"#)]
+    /// A line ending backslash, it is removed along with the line ending
+    /// and all whitespace and line endings up to the next non-whitespace character.
+    #[regex(r#"\\[ \t]*\r?\n([ \t\n]|\r\n)*"#)]
     Newline,
 
     #[token(r#"\n"#)]
@@ -37,12 +40,12 @@ pub enum Escape {
     #[token(r#"\\"#)]
     Backslash,
 
-    // Same thing repeated 4 times, but the {n} repetition syntax is not supported by Logos
-    #[regex(r#"\\u[0-9A-Fa-f_][0-9A-Fa-f_][0-9A-Fa-f_][0-9A-Fa-f_]"#)]
+    /// Followed by 4 hexadecimal digits that are validated separately.
+    #[token(r#"\u"#)]
     Unicode,
 
-    // Same thing repeated 8 times, but the {n} repetition syntax is not supported by Logos
-    #[regex(r#"\\U[0-9A-Fa-f_][0-9A-Fa-f_][0-9A-Fa-f_][0-9A-Fa-f_][0-9A-Fa-f_][0-9A-Fa-f_][0-9A-Fa-f_][0-9A-Fa-f_]"#)]
+    /// Followed by 8 hexadecimal digits that are validated separately.
+    #[token(r#"\U"#)]
     UnicodeLarge,
 
     #[regex(r#"\\."#)]
@@ -75,10 +78,43 @@ pub fn escape(s: &str) -> String {
     escaped
 }
 
+/// An invalid escape sequence in a basic string.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Error)]
+#[error("{kind}")]
+pub struct UnescapeError {
+    /// The byte offset of the escape sequence (at the `\`) in the string.
+    pub offset: usize,
+    /// The length of the escape sequence in bytes.
+    pub len: usize,
+    pub kind: UnescapeErrorKind,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Error)]
+pub enum UnescapeErrorKind {
+    #[error("unknown escape sequence")]
+    Unknown,
+    #[error("expected hexadecimal digits in unicode escape sequence")]
+    InvalidHexDigit,
+    #[error("unicode escape sequence is a surrogate code point (U+{0:04X})")]
+    Surrogate(u32),
+    #[error("unicode escape sequence is out of range (U+{0:X})")]
+    OutOfRange(u32),
+}
+
 /// Unescape all supported sequences found in [Escape](Escape).
 ///
-/// If it fails, the index of failure is returned.
-pub fn unescape(s: &str) -> Result<String, usize> {
+/// If it fails, the first invalid escape sequence is returned.
+///
+/// ```
+/// use taplo::util::{unescape, UnescapeErrorKind};
+///
+/// assert_eq!(unescape(r"\U0001F600 \u00e9").unwrap(), "\u{1F600} \u{e9}");
+///
+/// let err = unescape(r"a\uD800").unwrap_err();
+/// assert_eq!(err.offset, 1);
+/// assert_eq!(err.kind, UnescapeErrorKind::Surrogate(0xD800));
+/// ```
+pub fn unescape(s: &str) -> Result<String, UnescapeError> {
     let mut new_s = String::with_capacity(s.len());
     let mut lexer: Lexer<Escape> = Lexer::new(s);
 
@@ -92,21 +128,15 @@ pub fn unescape(s: &str) -> Result<String, usize> {
             Quote => new_s += "\u{0022}",
             Backslash => new_s += "\u{005C}",
             Newline => {}
-            Unicode => {
-                new_s += &std::char::from_u32(
-                    u32::from_str_radix(&lexer.slice()[2..], 16).map_err(|_| lexer.span().start)?,
-                )
-                .ok_or(lexer.span().start)?
-                .to_string();
+            Unicode => new_s.push(unicode_escape(&mut lexer, 4)?),
+            UnicodeLarge => new_s.push(unicode_escape(&mut lexer, 8)?),
+            Unknown => {
+                return Err(UnescapeError {
+                    offset: lexer.span().start,
+                    len: lexer.slice().len(),
+                    kind: UnescapeErrorKind::Unknown,
+                })
             }
-            UnicodeLarge => {
-                new_s += &std::char::from_u32(
-                    u32::from_str_radix(&lexer.slice()[2..], 16).map_err(|_| lexer.span().start)?,
-                )
-                .ok_or(lexer.span().start)?
-                .to_string();
-            }
-            Unknown => return Err(lexer.span().end),
             UnEscaped => {
                 new_s += lexer.slice();
             }
@@ -117,55 +147,25 @@ pub fn unescape(s: &str) -> Result<String, usize> {
 }
 
 /// Same as unescape, but doesn't create a new
-/// unescaped string, and returns all invalid escape indices.
-pub fn check_escape(s: &str) -> Result<(), Vec<usize>> {
+/// unescaped string, and returns all invalid escape sequences.
+pub fn check_escape(s: &str) -> Result<(), Vec<UnescapeError>> {
     let mut lexer: Lexer<Escape> = Lexer::new(s);
     let mut invalid = Vec::new();
 
     while let Some(t) = lexer.next() {
-        match t {
-            Backspace => {}
-            Tab => {}
-            LineFeed => {}
-            FormFeed => {}
-            CarriageReturn => {}
-            Quote => {}
-            Backslash => {}
-            Newline => {}
-            Unicode => {
-                let char_val = match u32::from_str_radix(&lexer.slice()[2..], 16) {
-                    Ok(v) => v,
-                    Err(_) => {
-                        invalid.push(lexer.span().start);
-                        continue;
-                    }
-                };
-
-                match std::char::from_u32(char_val) {
-                    None => {
-                        invalid.push(lexer.span().start);
-                    }
-                    Some(_) => {}
-                };
-            }
-            UnicodeLarge => {
-                let char_val = match u32::from_str_radix(&lexer.slice()[2..], 16) {
-                    Ok(v) => v,
-                    Err(_) => {
-                        invalid.push(lexer.span().start);
-                        continue;
-                    }
-                };
-
-                match std::char::from_u32(char_val) {
-                    None => {
-                        invalid.push(lexer.span().start);
-                    }
-                    Some(_) => {}
-                };
-            }
-            Unknown => invalid.push(lexer.span().start),
-            UnEscaped => {}
+        let result = match t {
+            Unicode => unicode_escape(&mut lexer, 4).map(drop),
+            UnicodeLarge => unicode_escape(&mut lexer, 8).map(drop),
+            Unknown => Err(UnescapeError {
+                offset: lexer.span().start,
+                len: lexer.slice().len(),
+                kind: UnescapeErrorKind::Unknown,
+            }),
+            _ => Ok(()),
+        };
+
+        if let Err(err) = result {
+            invalid.push(err);
         }
     }
 
@@ -175,3 +175,37 @@ pub fn check_escape(s: &str) -> Result<(), Vec<usize>> {
         Err(invalid)
     }
 }
+
+/// Consume the hexadecimal digits after `\u` or `\U`
+/// and return the escaped character.
+fn unicode_escape(lexer: &mut Lexer<Escape>, digits: usize) -> Result<char, UnescapeError> {
+    let offset = lexer.span().start;
+    let hex_len = lexer
+        .remainder()
+        .bytes()
+        .take(digits)
+        .take_while(u8::is_ascii_hexdigit)
+        .count();
+    lexer.bump(hex_len);
+
+    let error = |kind| UnescapeError {
+        offset,
+        len: lexer.span().end - offset,
+        kind,
+    };
+
+    if hex_len != digits {
+        return Err(error(UnescapeErrorKind::InvalidHexDigit));
+    }
+
+    let value = u32::from_str_radix(&lexer.slice()[2..], 16)
+        .map_err(|_| error(UnescapeErrorKind::InvalidHexDigit))?;
+
+    char::from_u32(value).ok_or_else(|| {
+        if (0xD800..=0xDFFF).contains(&value) {
+            error(UnescapeErrorKind::Surrogate(value))
+        } else {
+            error(UnescapeErrorKind::OutOfRange(value))
+        }
+    })
+}
@@ -36,6 +36,24 @@ where
     }
 }
 
+impl<I> DoubleEndedIterator for ExactIter<I>
+where
+    I: DoubleEndedIterator,
+{
+    fn next_back(&mut self) -> Option<Self::Item> {
+        if self.len != 0 {
+            let v = self
+                .iter
+                .next_back()
+                .expect("exact iterator ended too early");
+            self.len -= 1;
+            Some(v)
+        } else {
+            None
+        }
+    }
+}
+
 impl<I> ExactSizeIterator for ExactIter<I> where I: Iterator {}
 
 pub(crate) trait ExactIterExt: Iterator + Sized {
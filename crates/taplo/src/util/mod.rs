@@ -1,25 +1,35 @@
 use crate::syntax::{SyntaxElement, SyntaxKind, SyntaxNode};
 use rowan::TextRange;
 use rowan::TextSize;
+use std::borrow::Cow;
 
 pub(crate) mod iter;
 pub(crate) mod shared;
 
+pub mod debug;
 mod escape;
 pub mod syntax;
 
-pub use escape::check_escape;
+pub use escape::{check_escape, UnescapeError, UnescapeErrorKind};
 pub use escape::{escape, unescape};
 
 pub(crate) mod allowed_chars {
-    pub(crate) fn comment(s: &str) -> Result<(), Vec<usize>> {
-        let mut err_indices = Vec::new();
+    /// Control characters that are not allowed anywhere,
+    /// tabs and line breaks are handled separately.
+    fn is_forbidden(c: char) -> bool {
+        matches!(c, '\u{0000}'..='\u{0008}' | '\u{000A}'..='\u{001F}' | '\u{007F}')
+    }
 
-        for (i, c) in s.chars().enumerate() {
-            if c != '\t' && c.is_control() {
-                err_indices.push(i);
-            }
-        }
+    /// Byte offsets of the forbidden characters,
+    /// all of them are a single byte long.
+    fn check(s: &str, allow_newlines: bool) -> Result<(), Vec<usize>> {
+        let err_indices: Vec<usize> = s
+            .char_indices()
+            .filter(|&(_, c)| {
+                c != '\t' && !(allow_newlines && (c == '\n' || c == '\r')) && is_forbidden(c)
+            })
+            .map(|(i, _)| i)
+            .collect();
 
         if err_indices.is_empty() {
             Ok(())
@@ -28,56 +38,32 @@ pub(crate) mod allowed_chars {
         }
     }
 
-    pub(crate) fn string(s: &str) -> Result<(), Vec<usize>> {
-        let mut err_indices = Vec::new();
-
-        for (i, c) in s.chars().enumerate() {
-            if c != '\t'
-                && (('\u{0000}'..='\u{0008}').contains(&c)
-                    || ('\u{000A}'..='\u{001F}').contains(&c)
-                    || c == '\u{007F}')
-            {
-                err_indices.push(i);
-            }
-        }
+    pub(crate) fn comment(s: &str) -> Result<(), Vec<usize>> {
+        check(s, false)
+    }
 
-        if err_indices.is_empty() {
-            Ok(())
-        } else {
-            Err(err_indices)
-        }
+    pub(crate) fn string(s: &str) -> Result<(), Vec<usize>> {
+        check(s, false)
     }
 
     pub(crate) fn multi_line_string(s: &str) -> Result<(), Vec<usize>> {
-        let mut err_indices = Vec::new();
-
-        for (i, c) in s.chars().enumerate() {
-            if c != '\t'
-                && c != '\n'
-                && c != '\r'
-                && (('\u{0000}'..='\u{0008}').contains(&c)
-                    || ('\u{000A}'..='\u{001F}').contains(&c)
-                    || c == '\u{007F}')
-            {
-                err_indices.push(i);
-            }
-        }
-
-        if err_indices.is_empty() {
-            Ok(())
-        } else {
-            Err(err_indices)
-        }
+        check(s, true)
     }
 
     pub(crate) fn string_literal(s: &str) -> Result<(), Vec<usize>> {
-        let mut err_indices = Vec::new();
+        check(s, false)
+    }
 
-        for (i, c) in s.chars().enumerate() {
-            if c != '\t' && c.is_control() {
-                err_indices.push(i);
-            }
-        }
+    pub(crate) fn multi_line_string_literal(s: &str) -> Result<(), Vec<usize>> {
+        check(s, true)
+    }
+
+    /// Byte offsets of carriage returns that are not followed by a line feed.
+    pub(crate) fn newline(s: &str) -> Result<(), Vec<usize>> {
+        let bytes = s.as_bytes();
+        let err_indices: Vec<usize> = (0..bytes.len())
+            .filter(|&i| bytes[i] == b'\r' && bytes.get(i + 1) != Some(&b'\n'))
+            .collect();
 
         if err_indices.is_empty() {
             Ok(())
@@ -85,21 +71,62 @@ pub(crate) mod allowed_chars {
             Err(err_indices)
         }
     }
+}
 
-    pub(crate) fn multi_line_string_literal(s: &str) -> Result<(), Vec<usize>> {
-        let mut err_indices = Vec::new();
+/// Quote the key if it is not a valid bare key.
+///
+/// Literal quotes are preferred unless the key contains
+/// characters that are not allowed in literal strings.
+/// Empty and whitespace-only keys are always written
+/// as basic strings (e.g. `""` or `"\t"`), so that they are
+/// easy to tell apart.
+pub fn quote_key(key: &str) -> Cow<'_, str> {
+    if !key.is_empty()
+        && key
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+    {
+        return Cow::Borrowed(key);
+    }
 
-        for (i, c) in s.chars().enumerate() {
-            if c != '\t' && c != '\n' && c != '\r' && c.is_control() {
-                err_indices.push(i);
-            }
-        }
+    if !key.trim().is_empty() && !key.contains('\'') && allowed_chars::string_literal(key).is_ok() {
+        return Cow::Owned(format!("'{key}'"));
+    }
 
-        if err_indices.is_empty() {
-            Ok(())
-        } else {
-            Err(err_indices)
-        }
+    Cow::Owned(format!(r#""{}""#, escape(key)))
+}
+
+/// The quoting of single-line strings.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StringKind {
+    /// A basic string (`"..."`) with escapes.
+    Basic,
+    /// A literal string (`'...'`) without escapes.
+    Literal,
+}
+
+/// The string kind that represents the content most readably.
+///
+/// Literal strings are preferred for content with backslashes
+/// (e.g. Windows paths) so that they don't have to be escaped,
+/// unless the content cannot be represented as a literal string.
+pub fn preferred_string_kind(content: &str) -> StringKind {
+    if content.contains('\\')
+        && !content.contains('\'')
+        && allowed_chars::string_literal(content).is_ok()
+    {
+        StringKind::Literal
+    } else {
+        StringKind::Basic
+    }
+}
+
+/// Quote the content as a TOML string
+/// of the kind returned by [`preferred_string_kind`].
+pub fn quote_string(content: &str) -> String {
+    match preferred_string_kind(content) {
+        StringKind::Basic => format!(r#""{}""#, escape(content)),
+        StringKind::Literal => format!("'{content}'"),
     }
 }
 
@@ -52,8 +52,17 @@ where
         value.into()
     }
 
+    /// Update the value in place.
+    ///
+    /// The value is taken out for the duration of the update,
+    /// so it is only cloned if it is still referenced elsewhere
+    /// (e.g. by [`Shared::get`]). It must not be accessed from
+    /// within `f`.
     pub(crate) fn update(&self, f: impl FnOnce(&mut T)) {
-        let mut inner = self.0.load_full().take().unwrap();
+        let mut inner = self
+            .0
+            .swap(None)
+            .expect("the value was accessed during an update");
         f(Arc::make_mut(&mut inner));
         self.0.store(Some(inner))
     }
@@ -1,6 +1,6 @@
-use rowan::{GreenNodeBuilder, NodeOrToken};
+use rowan::{GreenNodeBuilder, NodeOrToken, TextRange};
 
-use crate::syntax::SyntaxNode;
+use crate::syntax::{SyntaxElement, SyntaxKind::*, SyntaxNode};
 
 pub fn add_all(node: SyntaxNode, builder: &mut GreenNodeBuilder) {
     builder.start_node(node.kind().into());
@@ -14,3 +14,232 @@ pub fn add_all(node: SyntaxNode, builder: &mut GreenNodeBuilder) {
 
     builder.finish_node()
 }
+
+/// The table headers of a root node with the ranges
+/// of their sections.
+///
+/// A section spans from the header to the last entry or comment
+/// that belongs to it. A comment block that is directly followed
+/// by a header (without a blank line between them) documents that header
+/// and is not part of the previous section.
+pub fn section_ranges(root: &SyntaxNode) -> Vec<(SyntaxNode, TextRange)> {
+    let mut sections: Vec<(SyntaxNode, TextRange)> = Vec::new();
+
+    // The comments that might belong to the next header.
+    let mut comments: Option<TextRange> = None;
+
+    for element in root.children_with_tokens() {
+        let range = element.text_range();
+
+        match element.kind() {
+            TABLE_HEADER | TABLE_ARRAY_HEADER => {
+                comments = None;
+                sections.extend(element.into_node().map(|n| (n, range)));
+            }
+            COMMENT => comments = Some(comments.map_or(range, |c| c.cover(range))),
+            NEWLINE => {
+                // The comment block is separated from the next header.
+                if element.to_string().matches('\n').count() > 1 {
+                    if let Some(c) = comments.take() {
+                        extend_last_section(&mut sections, c);
+                    }
+                }
+            }
+            WHITESPACE => {}
+            _ => {
+                if let Some(c) = comments.take() {
+                    extend_last_section(&mut sections, c);
+                }
+                extend_last_section(&mut sections, range);
+            }
+        }
+    }
+
+    // Comments at the end of the document belong to the last section.
+    if let Some(c) = comments {
+        extend_last_section(&mut sections, c);
+    }
+
+    sections
+}
+
+fn extend_last_section(sections: &mut [(SyntaxNode, TextRange)], range: TextRange) {
+    if let Some((_, section)) = sections.last_mut() {
+        *section = section.cover(range);
+    }
+}
+
+/// The ranges of lines longer than `max_width` characters
+/// that the formatter cannot shorten.
+///
+/// Rather than running the formatter, every line is checked against
+/// the constructs that cannot be broken across lines, such as keys,
+/// strings, headers, comments or inline tables together with their keys.
+/// Arrays can be expanded so only their items are considered.
+///
+/// Lines that are part of multi-line strings are ignored.
+pub fn unfixable_long_lines(root: &SyntaxNode, max_width: usize) -> Vec<TextRange> {
+    let text = root.to_string();
+
+    let mut multiline_strings = Vec::new();
+    let mut units: Vec<(TextRange, usize)> = Vec::new();
+
+    for element in root.descendants_with_tokens() {
+        match element.kind() {
+            MULTI_LINE_STRING | MULTI_LINE_STRING_LITERAL => {
+                multiline_strings.push(element.text_range());
+            }
+            COMMENT | TABLE_HEADER | TABLE_ARRAY_HEADER => {
+                let range = element.text_range();
+                units.push((range, text_width(&text, range)));
+            }
+            ENTRY => {
+                let Some(node) = element.as_node() else {
+                    continue;
+                };
+
+                let (Some(key), Some(value)) = (
+                    node.children().find(|n| n.kind() == KEY),
+                    node.children().find(|n| n.kind() == VALUE),
+                ) else {
+                    continue;
+                };
+
+                let key_range = significant_range(&key.into());
+                let key_width = text_width(&text, key_range);
+
+                match value.first_child().filter(|n| n.kind() == ARRAY) {
+                    // Only `key = [` has to stay on the same line.
+                    Some(arr) => units.push((
+                        key_range.cover_offset(arr.text_range().start()),
+                        key_width + " = [".len(),
+                    )),
+                    None => {
+                        let value_range = significant_range(&value.into());
+                        units.push((
+                            key_range.cover(value_range),
+                            key_width + " = ".len() + text_width(&text, value_range),
+                        ));
+                    }
+                }
+            }
+            // Array items, each of them can be on a separate line.
+            VALUE if element.parent().is_some_and(|p| p.kind() == ARRAY) => {
+                let Some(node) = element.as_node() else {
+                    continue;
+                };
+
+                if node.first_child().is_some_and(|n| n.kind() == ARRAY) {
+                    continue;
+                }
+
+                let range = significant_range(&element);
+                units.push((range, text_width(&text, range) + ",".len()));
+            }
+            _ => {}
+        }
+    }
+
+    let mut lines = Vec::new();
+    let mut offset = 0;
+
+    for line in text.split('\n') {
+        let content = line.strip_suffix('\r').unwrap_or(line);
+        let range = TextRange::at(offset.into(), (content.len() as u32).into());
+        offset += line.len() as u32 + 1;
+
+        if content.chars().count() <= max_width
+            || multiline_strings
+                .iter()
+                .any(|r| r.intersect(range).is_some_and(|r| !r.is_empty()))
+        {
+            continue;
+        }
+
+        if units.iter().any(|(r, width)| {
+            *width > max_width && r.intersect(range).is_some_and(|r| !r.is_empty())
+        }) {
+            lines.push(range);
+        }
+    }
+
+    lines
+}
+
+/// The range of the key of an `ENTRY` node without the surrounding whitespace.
+pub fn entry_key_range(entry: &SyntaxNode) -> Option<TextRange> {
+    entry
+        .children()
+        .find(|n| n.kind() == KEY)
+        .map(|key| significant_range(&key.into()))
+}
+
+/// The range of the value of an `ENTRY` node without
+/// the surrounding whitespace and comments.
+///
+/// If the value is missing or invalid, the range covers
+/// whatever syntax there is after the `=`, [`None`] is returned
+/// if there is nothing there.
+pub fn entry_value_range(entry: &SyntaxNode) -> Option<TextRange> {
+    let value = entry.children().find(|n| n.kind() == VALUE);
+
+    if let Some(range) = value.as_ref().and_then(significant_node_range) {
+        return Some(range);
+    }
+
+    entry
+        .children_with_tokens()
+        .skip_while(|c| c.kind() != EQ)
+        .skip(1)
+        .filter_map(|c| match c {
+            NodeOrToken::Node(n) => significant_node_range(&n),
+            NodeOrToken::Token(t) => {
+                (!matches!(t.kind(), WHITESPACE | NEWLINE | COMMENT)).then(|| t.text_range())
+            }
+        })
+        .reduce(TextRange::cover)
+}
+
+/// Whether the key and the value of an `ENTRY` node are on a single line.
+///
+/// This is `false` for multi-line arrays, inline tables and strings.
+pub fn entry_is_single_line(entry: &SyntaxNode) -> bool {
+    let Some(key_range) = entry_key_range(entry) else {
+        return true;
+    };
+
+    let range = entry_value_range(entry).map_or(key_range, |r| key_range.cover(r));
+
+    !entry
+        .descendants_with_tokens()
+        .filter_map(SyntaxElement::into_token)
+        .filter(|t| range.contains_range(t.text_range()))
+        .any(|t| t.text().contains('\n'))
+}
+
+/// The range of the non-trivia tokens in the node,
+/// or [`None`] if there are none.
+fn significant_node_range(node: &SyntaxNode) -> Option<TextRange> {
+    node.descendants_with_tokens()
+        .filter_map(SyntaxElement::into_token)
+        .filter(|t| !matches!(t.kind(), WHITESPACE | NEWLINE | COMMENT))
+        .map(|t| t.text_range())
+        .reduce(TextRange::cover)
+}
+
+/// The range of the element without leading or trailing
+/// whitespace and comments.
+fn significant_range(element: &SyntaxElement) -> TextRange {
+    match element {
+        SyntaxElement::Node(n) => significant_node_range(n).unwrap_or_else(|| n.text_range()),
+        SyntaxElement::Token(t) => t.text_range(),
+    }
+}
+
+/// The width of the first line of the text in the range.
+fn text_width(text: &str, range: TextRange) -> usize {
+    text[range]
+        .lines()
+        .next()
+        .map_or(0, |line| line.chars().count())
+}
@@ -211,6 +211,7 @@ export function register(ctx: vscode.ExtensionContext, c: BaseLanguageClient) {
           "taplo/convertToToml",
           {
             text: input,
+            documentUri: editor.document.uri.toString(),
           }
         );
 
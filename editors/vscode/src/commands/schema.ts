@@ -49,7 +49,10 @@ export function register(ctx: vscode.ExtensionContext, c: BaseLanguageClient) {
           meta: selection.meta,
         });
       }
-    )
+    ),
+    vscode.commands.registerCommand("evenBetterToml.reloadSchemas", () => {
+      c.sendNotification("taplo/reloadSchemas");
+    })
   );
 }
 
@@ -74,6 +74,13 @@ export interface Environment {
    * - `taplo.toml`
    */
   findConfigFile: (from: string) => string | undefined;
+  /**
+   * Download the contents of a remote (`http` or `https`) URL,
+   * the request should be aborted after `timeoutMs` milliseconds.
+   *
+   * Remote schemas and catalogs are not supported if it is not provided.
+   */
+  fetchFile?: (url: string, timeoutMs: number) => Promise<Uint8Array>;
   /**
    * The fetch function if it is not defined on the global Window.
    *
@@ -133,6 +140,7 @@ export function convertEnv(env: Environment): any {
     js_is_absolute: env.isAbsolute,
     js_cwd: env.cwd,
     js_find_config_file: env.findConfigFile,
+    js_fetch_file: env.fetchFile,
   };
 }
 
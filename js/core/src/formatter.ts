@@ -68,4 +68,138 @@ export interface FormatterOptions {
    * Use CRLF for line endings.
    */
   crlf?: boolean;
+  /**
+   * Re-indent multi-line basic strings when the indentation of their entries changes,
+   * adding line ending backslashes so that their values are unchanged.
+   */
+  reflowMultilineStringIndent?: boolean;
+  /**
+   * Keep the UTF-8 byte order mark at the start of the document if there is one.
+   */
+  preserveBom?: boolean;
+  /**
+   * The number of blank lines before table headers, comments directly before a header are kept together with it. The blank lines are kept if not set.
+   */
+  tableSpacing?: number;
+  /**
+   * Convert inline tables that exceed the column width to standard tables, inline tables in arrays are left as-is.
+   */
+  convertLongInlineTables?: boolean;
+  /**
+   * Collapse tables with a single entry into dotted keys under their parent tables, tables with sub-tables or comments in them are left as-is.
+   */
+  preferDottedKeysForSingleEntries?: boolean;
+  /**
+   * Expand the dotted keys of entries that are longer than the maximum dotted key depth into tables.
+   */
+  expandDottedKeys?: boolean;
+  /**
+   * The maximum number of keys in dotted keys that are created or kept by the dotted key options.
+   */
+  maxDottedKeyDepth?: number;
+  /**
+   * The case of the digits of hexadecimal integers. The `0x` prefix is always lowercase.
+   */
+  hexCase?: "keep" | "upper" | "lower";
+  /**
+   * Separate the digits of decimal integers with underscores (e.g. `1_000_000`). Floats and other bases are left as-is.
+   */
+  integerGrouping?: "keep" | "thousands";
+  /**
+   * The separator of date-times and the case of `Z`. Local dates and local times are left as-is.
+   */
+  datetimeStyle?: "keep" | "t_uppercase" | "space";
+  /**
+   * Alphabetically reorder tables by their keys, arrays of tables keep their relative order.
+   */
+  reorderTables?: boolean;
+  /**
+   * Either `auto`, or `preserve` to keep the line breaks between array values, auto collapse and auto expand are not applied then.
+   */
+  arrayLayout?: "auto" | "preserve";
+  /**
+   * Glob patterns of the keys of arrays that are always expanded to one value per line, e.g. `["workspace.members"]`.
+   */
+  expandArraysPaths?: string[];
+  /**
+   * Glob patterns of the keys of arrays that are sorted bytewise by their unescaped values if all of them are strings, comments move with the values.
+   */
+  sortStringArrays?: string[];
+  /**
+   * Expand arrays with more values than this to one value per line.
+   */
+  arrayExpandThreshold?: number;
+  /**
+   * The quotes of keys, `minimal` removes the quotes of keys that can be bare keys.
+   */
+  keyQuotes?: "keep" | "minimal";
+  /**
+   * Keys that are put first in the given order when the keys are reordered, e.g. `["name", "version"]`.
+   */
+  keyOrderRules?: string[];
+  /**
+   * Array options, these take precedence over the flat array options.
+   */
+  array?: ArrayOptions;
+  /**
+   * Inline table options, these take precedence over the flat inline table options.
+   */
+  inlineTable?: InlineTableOptions;
+  /**
+   * Key options, these take precedence over the flat key options.
+   */
+  keys?: KeyOptions;
+}
+
+export interface ArrayOptions {
+  /**
+   * Collapse arrays that don't exceed the maximum column width and don't contain comments.
+   */
+  autoCollapse?: boolean;
+  /**
+   * Expand arrays to multiple lines that exceed the maximum column width.
+   */
+  autoExpand?: boolean;
+  /**
+   * Append trailing commas for multi-line arrays.
+   */
+  trailingComma?: boolean;
+  /**
+   * Add white space padding to single-line arrays.
+   */
+  bracketSpacing?: boolean;
+  /**
+   * Alphabetically reorder array values that are not separated by empty lines.
+   */
+  reorder?: boolean;
+  /**
+   * Expand arrays with more values than this to one value per line.
+   */
+  expandThreshold?: number;
+}
+
+export interface InlineTableOptions {
+  /**
+   * Expand values (e.g. arrays) inside inline tables.
+   */
+  expand?: boolean;
+  /**
+   * Add white space padding to the start and end of inline tables.
+   */
+  braceSpacing?: boolean;
+}
+
+export interface KeyOptions {
+  /**
+   * Alphabetically reorder keys that are not separated by empty lines.
+   */
+  reorder?: boolean;
+  /**
+   * The quotes of keys, `minimal` removes the quotes of keys that can be bare keys.
+   */
+  quotes?: "keep" | "minimal";
+  /**
+   * Keys that are put first in the given order when the keys are reordered.
+   */
+  orderRules?: string[];
 }
@@ -39,7 +39,10 @@ export namespace Client {
   interface ClientRequests {
     "taplo/convertToJson": {
       params: {
-        text: string;
+        text?: string;
+        documentUri?: string;
+        compact?: boolean;
+        tagged?: boolean;
       };
       response: {
         text?: string | null;
@@ -49,6 +52,7 @@ export namespace Client {
     "taplo/convertToToml": {
       params: {
         text: string;
+        documentUri?: string;
       };
       response: {
         text?: string | null;
@@ -71,6 +75,16 @@ export namespace Client {
         schema?: SchemaInfo | null;
       };
     };
+    "taplo/availableKeys": {
+      params: {
+        documentUri: string;
+        position: Position;
+        limit?: number | null;
+      };
+      response: {
+        keys: Array<AvailableKey>;
+      };
+    };
   }
 
   export type NotificationMethod = keyof ClientNotifications;
@@ -111,3 +125,17 @@ export interface SchemaInfo {
   url: string;
   meta: any;
 }
+
+export interface Position {
+  line: number;
+  character: number;
+}
+
+export interface AvailableKey {
+  name: string;
+  typeSummary?: string | null;
+  required: boolean;
+  deprecated: boolean;
+  default?: string | null;
+  docs?: string | null;
+}